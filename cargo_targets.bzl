@@ -33,16 +33,20 @@ _packages = [
     "//crates/h264",
     "//crates/h265",
     "//crates/http",
+    "//crates/media",
     "//crates/metrics",
     "//crates/metrics/derive",
     "//crates/mp4",
+    "//crates/mpegts",
     "//crates/nutype-enum",
     "//crates/openapiv3_1",
     "//crates/postcompile",
     "//crates/pprof",
+    "//crates/proxy-protocol",
     "//crates/rtmp",
     "//crates/settings",
     "//crates/signal",
+    "//crates/srt",
     "//crates/tinc",
     "//crates/tinc/build",
     "//crates/tinc/cel",
@@ -50,6 +54,7 @@ _packages = [
     "//crates/tinc/integration",
     "//crates/tinc/pb-prost",
     "//crates/transmuxer",
+    "//crates/whip",
     "//dev-tools/xtask",
     "//tools/cargo/clippy",
     "//tools/cargo/sync-readme",
@@ -11,6 +11,7 @@ pub(crate) struct Config {
     #[default("[::]:1935".parse().unwrap())]
     pub rtmp_bind: SocketAddr,
     pub rtmps: Option<RtmpsConfig>,
+    pub proxy_protocol: Option<ProxyProtocolConfig>,
     pub telemetry: Option<TelemetryConfig>,
 }
 
@@ -28,6 +29,14 @@ pub(crate) struct RtmpsConfig {
     pub key_path: PathBuf,
 }
 
+#[derive(serde_derive::Deserialize, smart_default::SmartDefault, Debug, Clone)]
+pub(crate) struct ProxyProtocolConfig {
+    /// Networks of the load balancers in front of the RTMP and RTMPS listeners, for example `10.0.0.0/8`.
+    ///
+    /// Connections from these networks must start with a PROXY protocol header.
+    pub trusted_networks: Vec<String>,
+}
+
 #[derive(serde_derive::Deserialize, smart_default::SmartDefault, Debug, Clone)]
 pub(crate) struct TelemetryConfig {
     #[default("[::1]:4317".parse().unwrap())]
@@ -22,6 +22,7 @@ mod config;
 struct Global {
     config: config::Config,
     rtmps: Option<RtmpsGlobal>,
+    proxy_protocol: Option<scuffle_proxy_protocol::ProxyProtocol>,
     open_telemetry: opentelemetry::OpenTelemetry,
 }
 
@@ -44,6 +45,10 @@ impl ingest_traits::ConfigInterface for Global {
     fn rtmp_bind(&self) -> std::net::SocketAddr {
         self.config.rtmp_bind
     }
+
+    fn proxy_protocol(&self) -> Option<&scuffle_proxy_protocol::ProxyProtocol> {
+        self.proxy_protocol.as_ref()
+    }
 }
 
 impl ingest_traits::RtmpsInterface for Global {
@@ -107,6 +112,22 @@ impl scuffle_bootstrap::Global for Global {
             None
         };
 
+        let proxy_protocol = if let Some(proxy_protocol) = config.proxy_protocol.as_ref() {
+            let trusted_networks = proxy_protocol
+                .trusted_networks
+                .iter()
+                .map(|network| network.parse().with_context(|| format!("parse trusted network {network}")))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            Some(
+                scuffle_proxy_protocol::ProxyProtocol::builder()
+                    .trusted_networks(trusted_networks)
+                    .build(),
+            )
+        } else {
+            None
+        };
+
         let tracer = SdkTracerProvider::default();
         opentelemetry::global::set_tracer_provider(tracer.clone());
 
@@ -117,6 +138,7 @@ impl scuffle_bootstrap::Global for Global {
         Ok(Arc::new(Self {
             config,
             rtmps,
+            proxy_protocol,
             open_telemetry,
         }))
     }
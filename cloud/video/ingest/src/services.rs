@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use anyhow::Context;
@@ -29,11 +30,20 @@ impl<G: ingest_traits::Global> scuffle_bootstrap::Service<G> for IngestSvc<G> {
 
             while let Some(connection) = tcp_listener.accept().with_context(&ctx).await {
                 match connection {
-                    Ok((stream, _addr)) => {
-                        let session = scuffle_rtmp::ServerSession::new(stream, rtmp::Handler).with_context(ctx.clone());
+                    Ok((mut stream, addr)) => {
+                        let ctx = ctx.clone();
+                        let global = global.clone();
 
                         // This is bound by the context because we pass it to the session.
                         tokio::spawn(async move {
+                            let Some(Some(handler)) = session_handler(&*global, &mut stream, addr).with_context(&ctx).await
+                            else {
+                                return;
+                            };
+                            tracing::debug!(client_addr = %handler.client_addr, "accepted RTMP connection");
+
+                            let session = scuffle_rtmp::ServerSession::new(stream, handler).with_context(ctx);
+
                             if let Err(err) = session.run().await {
                                 tracing::error!(err = %err, "RTMP session error");
                                 // TODO: what do we do here?
@@ -60,15 +70,24 @@ impl<G: ingest_traits::Global> scuffle_bootstrap::Service<G> for IngestSvc<G> {
 
                 while let Some(connection) = tcp_listener.accept().with_context(&ctx).await {
                     match connection {
-                        Ok((stream, _addr)) => {
+                        Ok((mut stream, addr)) => {
                             let ctx = ctx.clone();
+                            let global = global.clone();
                             let tls_acceptor = tls_acceptor.clone();
 
                             tokio::spawn(async move {
+                                // The PROXY protocol header is sent before the TLS handshake
+                                let Some(Some(handler)) =
+                                    session_handler(&*global, &mut stream, addr).with_context(&ctx).await
+                                else {
+                                    return;
+                                };
+
                                 match tls_acceptor.accept(stream).with_context(&ctx).await {
                                     Some(Ok(stream)) => {
-                                        let session =
-                                            scuffle_rtmp::ServerSession::new(stream, rtmp::Handler).with_context(ctx);
+                                        tracing::debug!(client_addr = %handler.client_addr, "accepted RTMPS connection");
+
+                                        let session = scuffle_rtmp::ServerSession::new(stream, handler).with_context(ctx);
 
                                         // run is bound by the context because we pass it to the session.
                                         if let Err(err) = session.run().await {
@@ -101,3 +120,118 @@ impl<G: ingest_traits::Global> scuffle_bootstrap::Service<G> for IngestSvc<G> {
         Ok(())
     }
 }
+
+/// Creates the session handler for a new connection from `peer_addr`.
+///
+/// The handler gets the address of the client, which is read from the PROXY protocol header if the peer is a trusted
+/// load balancer.
+/// Returns `None` if a trusted peer did not send a valid header, the connection should be closed in that case.
+async fn session_handler<G: ingest_traits::Global, R: tokio::io::AsyncRead + Unpin>(
+    global: &G,
+    stream: &mut R,
+    peer_addr: SocketAddr,
+) -> Option<rtmp::Handler> {
+    let Some(proxy_protocol) = global.proxy_protocol() else {
+        return Some(rtmp::Handler::new(peer_addr));
+    };
+
+    match proxy_protocol.accept(stream, peer_addr).await {
+        Ok((client_addr, _)) => Some(rtmp::Handler::new(client_addr)),
+        Err(err) => {
+            tracing::warn!(peer_addr = %peer_addr, err = %err, "failed to read PROXY protocol header");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use scuffle_proxy_protocol::ProxyProtocol;
+
+    use super::session_handler;
+
+    struct Global {
+        proxy_protocol: Option<ProxyProtocol>,
+    }
+
+    struct RtmpsConfig;
+
+    impl ingest_traits::RtmpConfigInterface for RtmpsConfig {
+        fn rtmps_bind(&self) -> SocketAddr {
+            unreachable!()
+        }
+
+        fn rtmps_rustls_server_config(&self) -> Arc<tokio_rustls::rustls::ServerConfig> {
+            unreachable!()
+        }
+    }
+
+    impl ingest_traits::ConfigInterface for Global {
+        fn rtmp_bind(&self) -> SocketAddr {
+            unreachable!()
+        }
+
+        fn proxy_protocol(&self) -> Option<&ProxyProtocol> {
+            self.proxy_protocol.as_ref()
+        }
+    }
+
+    impl ingest_traits::RtmpsInterface for Global {
+        type RtmpsConfig = RtmpsConfig;
+
+        fn rtmps_config(&self) -> Option<&Self::RtmpsConfig> {
+            None
+        }
+    }
+
+    impl ingest_traits::Global for Global {}
+
+    fn global(proxy_protocol: bool) -> Global {
+        Global {
+            proxy_protocol: proxy_protocol.then(|| {
+                ProxyProtocol::builder()
+                    .trusted_networks(vec!["10.0.0.0/8".parse().unwrap()])
+                    .header_timeout(Duration::from_millis(50))
+                    .build()
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn proxied_client_addr() {
+        let peer: SocketAddr = "10.0.0.5:40000".parse().unwrap();
+        let mut stream = &b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 1935\r\n\x03"[..];
+
+        let handler = session_handler(&global(true), &mut stream, peer).await.unwrap();
+
+        assert_eq!(handler.client_addr, "203.0.113.7:56324".parse().unwrap());
+        // The RTMP handshake is left for the session
+        assert_eq!(stream, b"\x03");
+    }
+
+    #[tokio::test]
+    async fn peer_addr() {
+        let peer: SocketAddr = "198.51.100.1:40000".parse().unwrap();
+        let mut stream = &b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 1935\r\n"[..];
+
+        // Untrusted peers keep their address
+        let handler = session_handler(&global(true), &mut stream, peer).await.unwrap();
+        assert_eq!(handler.client_addr, peer);
+
+        let handler = session_handler(&global(false), &mut stream, peer).await.unwrap();
+        assert_eq!(handler.client_addr, peer);
+    }
+
+    #[tokio::test]
+    async fn missing_header() {
+        let peer: SocketAddr = "10.0.0.5:40000".parse().unwrap();
+        let mut stream = &b"\x03"[..];
+
+        assert!(session_handler(&global(true), &mut stream, peer).await.is_none());
+    }
+}
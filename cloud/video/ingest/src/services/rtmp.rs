@@ -1,6 +1,17 @@
+use std::net::SocketAddr;
+
 use scuffle_rtmp::session::server::{ServerSessionError, SessionData, SessionHandler};
 
-pub(crate) struct Handler;
+pub(crate) struct Handler {
+    /// The address of the client, read from the PROXY protocol header when the peer is a trusted load balancer.
+    pub(crate) client_addr: SocketAddr,
+}
+
+impl Handler {
+    pub(crate) fn new(client_addr: SocketAddr) -> Self {
+        Self { client_addr }
+    }
+}
 
 impl SessionHandler for Handler {
     async fn on_data(&mut self, stream_id: u32, data: SessionData) -> Result<(), ServerSessionError> {
@@ -22,13 +33,13 @@ impl SessionHandler for Handler {
 
     async fn on_publish(&mut self, stream_id: u32, app_name: &str, stream_name: &str) -> Result<(), ServerSessionError> {
         // Handle the publish event
-        tracing::info!(stream_id, app_name, stream_name, "stream published");
+        tracing::info!(stream_id, app_name, stream_name, client_addr = %self.client_addr, "stream published");
         Ok(())
     }
 
     async fn on_unpublish(&mut self, stream_id: u32) -> Result<(), ServerSessionError> {
         // Handle the unpublish event
-        tracing::info!(stream_id, "stream unpublished");
+        tracing::info!(stream_id, client_addr = %self.client_addr, "stream unpublished");
         Ok(())
     }
 }
@@ -1,3 +1,6 @@
 pub trait ConfigInterface: Send + Sync {
     fn rtmp_bind(&self) -> std::net::SocketAddr;
+
+    /// Read PROXY protocol headers from connections of trusted load balancers, applies to RTMP and RTMPS.
+    fn proxy_protocol(&self) -> Option<&scuffle_proxy_protocol::ProxyProtocol>;
 }
@@ -0,0 +1,465 @@
+//! ADTS (Audio Data Transport Stream) headers and frames.
+//!
+//! ADTS is the framing used for AAC in MPEG-TS and raw `.aac` files. Each
+//! frame carries a header describing the stream followed by one or more raw
+//! data blocks. Stripping the header yields the raw AAC access units used by
+//! FLV and MP4, and [`AdtsHeader::audio_specific_config`] yields the matching
+//! [`AudioSpecificConfig`].
+//!
+//! ISO/IEC 14496-3:2019(E) - 1.A.2.2
+
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use bytes::{Buf, Bytes};
+use scuffle_bytes_util::{BitReader, BitWriter, BytesCursorExt};
+
+use crate::{AudioObjectType, AudioSpecificConfig, SampleFrequencyIndex};
+
+/// The ADTS sync word (12 bits).
+const SYNC_WORD: u64 = 0xFFF;
+
+/// The size of the fixed and variable ADTS header, without error check.
+const BASE_HEADER_SIZE: usize = 7;
+
+/// The MPEG version signalled by the ADTS `ID` bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpegVersion {
+    /// MPEG-4 (`ID` = 0)
+    Mpeg4,
+    /// MPEG-2 (`ID` = 1)
+    Mpeg2,
+}
+
+/// The error check of an ADTS header, present if `protection_absent` is 0.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.A.2.2.2 (Table 1.A.8, Table 1.A.9)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdtsErrorCheck {
+    /// The positions of the raw data blocks within the frame, only present if
+    /// the frame contains more than one raw data block.
+    pub raw_data_block_positions: Vec<u16>,
+    /// The CRC of the header.
+    pub crc_check: u16,
+}
+
+/// ADTS Header
+///
+/// ISO/IEC 14496-3:2019(E) - 1.A.2.2.1 (Table 1.A.6, Table 1.A.7)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdtsHeader {
+    /// The MPEG version.
+    pub mpeg_version: MpegVersion,
+    /// The audio object type, ADTS can only signal the first four object types.
+    pub audio_object_type: AudioObjectType,
+    /// The sampling frequency index, ADTS cannot signal the escape value.
+    pub sampling_frequency_index: SampleFrequencyIndex,
+    /// The private bit, not used by MPEG.
+    pub private_bit: bool,
+    /// The channel configuration (3 bits).
+    ///
+    /// If this is 0 the channel layout is described by a program config
+    /// element in the first raw data block.
+    pub channel_configuration: u8,
+    /// If the stream is an original.
+    pub original_copy: bool,
+    /// The home bit.
+    pub home: bool,
+    /// The copyright identification bit.
+    pub copyright_identification_bit: bool,
+    /// The copyright identification start bit.
+    pub copyright_identification_start: bool,
+    /// The length of the frame in bytes, including the header (13 bits).
+    pub frame_length: u16,
+    /// The buffer fullness (11 bits), `0x7FF` signals a variable bitrate stream.
+    pub buffer_fullness: u16,
+    /// The number of raw data blocks in the frame minus one (2 bits).
+    pub number_of_raw_data_blocks_in_frame: u8,
+    /// The error check, present if the header is protected by a CRC.
+    pub error_check: Option<AdtsErrorCheck>,
+}
+
+impl AdtsHeader {
+    /// Creates a new unprotected ADTS header for a single raw data block of
+    /// `payload_size` bytes described by the given [`AudioSpecificConfig`].
+    ///
+    /// Fails if the config cannot be represented by an ADTS header.
+    pub fn from_audio_specific_config(config: &AudioSpecificConfig, payload_size: usize) -> io::Result<Self> {
+        if !matches!(config.audio_object_type.as_u16(), 1..=4) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ADTS only supports audio object types 1 to 4",
+            ));
+        }
+
+        let sampling_frequency_index = SampleFrequencyIndex::from_freq(config.sampling_frequency).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ADTS does not support explicit sampling frequencies",
+            )
+        })?;
+
+        if config.channel_configuration > 7 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ADTS only supports channel configurations 0 to 7",
+            ));
+        }
+
+        let frame_length = payload_size + BASE_HEADER_SIZE;
+        if frame_length > 0x1FFF {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "ADTS frame too large"));
+        }
+
+        Ok(Self {
+            mpeg_version: MpegVersion::Mpeg4,
+            audio_object_type: config.audio_object_type,
+            sampling_frequency_index,
+            private_bit: false,
+            channel_configuration: config.channel_configuration,
+            original_copy: false,
+            home: false,
+            copyright_identification_bit: false,
+            copyright_identification_start: false,
+            frame_length: frame_length as u16,
+            buffer_fullness: 0x7FF,
+            number_of_raw_data_blocks_in_frame: 0,
+            error_check: None,
+        })
+    }
+
+    /// Parses an ADTS header from a reader.
+    pub fn parse<R: io::Read>(reader: R) -> io::Result<Self> {
+        let mut bit_reader = BitReader::new(reader);
+
+        if bit_reader.read_bits(12)? != SYNC_WORD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid ADTS sync word"));
+        }
+
+        let mpeg_version = if bit_reader.read_bit()? {
+            MpegVersion::Mpeg2
+        } else {
+            MpegVersion::Mpeg4
+        };
+
+        if bit_reader.read_bits(2)? != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid ADTS layer"));
+        }
+
+        let protection_absent = bit_reader.read_bit()?;
+        let audio_object_type = AudioObjectType::from_u16(bit_reader.read_bits(2)? as u16 + 1);
+        let sampling_frequency_index = SampleFrequencyIndex::read(&mut bit_reader)?;
+        if sampling_frequency_index.to_freq().is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid sampling frequency index"));
+        }
+
+        let private_bit = bit_reader.read_bit()?;
+        let channel_configuration = bit_reader.read_bits(3)? as u8;
+        let original_copy = bit_reader.read_bit()?;
+        let home = bit_reader.read_bit()?;
+
+        let copyright_identification_bit = bit_reader.read_bit()?;
+        let copyright_identification_start = bit_reader.read_bit()?;
+        let frame_length = bit_reader.read_bits(13)? as u16;
+        let buffer_fullness = bit_reader.read_bits(11)? as u16;
+        let number_of_raw_data_blocks_in_frame = bit_reader.read_bits(2)? as u8;
+
+        let error_check = if protection_absent {
+            None
+        } else {
+            let mut reader = bit_reader.into_inner();
+            let raw_data_block_positions = if number_of_raw_data_blocks_in_frame > 0 {
+                (0..number_of_raw_data_blocks_in_frame)
+                    .map(|_| reader.read_u16::<BigEndian>())
+                    .collect::<io::Result<Vec<_>>>()?
+            } else {
+                Vec::new()
+            };
+
+            Some(AdtsErrorCheck {
+                raw_data_block_positions,
+                crc_check: reader.read_u16::<BigEndian>()?,
+            })
+        };
+
+        let header = Self {
+            mpeg_version,
+            audio_object_type,
+            sampling_frequency_index,
+            private_bit,
+            channel_configuration,
+            original_copy,
+            home,
+            copyright_identification_bit,
+            copyright_identification_start,
+            frame_length,
+            buffer_fullness,
+            number_of_raw_data_blocks_in_frame,
+            error_check,
+        };
+
+        if (header.frame_length as usize) < header.size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ADTS frame length is smaller than the header",
+            ));
+        }
+
+        Ok(header)
+    }
+
+    /// Returns the size of the header in bytes.
+    pub fn size(&self) -> usize {
+        BASE_HEADER_SIZE
+            + match &self.error_check {
+                Some(error_check) => 2 * error_check.raw_data_block_positions.len() + 2,
+                None => 0,
+            }
+    }
+
+    /// Returns the size of the payload following the header in bytes.
+    pub fn payload_size(&self) -> usize {
+        (self.frame_length as usize).saturating_sub(self.size())
+    }
+
+    /// Returns the sampling frequency in Hz.
+    pub fn sampling_frequency(&self) -> Option<u32> {
+        self.sampling_frequency_index.to_freq()
+    }
+
+    /// Returns the number of raw data blocks (access units) in the frame.
+    pub fn raw_data_blocks(&self) -> usize {
+        self.number_of_raw_data_blocks_in_frame as usize + 1
+    }
+
+    /// Returns the [`AudioSpecificConfig`] described by this header.
+    ///
+    /// ADTS cannot explicitly signal SBR or PS, see
+    /// [`AudioSpecificConfig::implicit_sbr_possible`].
+    pub fn audio_specific_config(&self) -> io::Result<AudioSpecificConfig> {
+        let sampling_frequency = self
+            .sampling_frequency()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid sampling frequency index"))?;
+
+        Ok(AudioSpecificConfig::new(
+            self.audio_object_type,
+            sampling_frequency,
+            self.channel_configuration,
+        ))
+    }
+
+    /// Builds the ADTS header into a byte stream.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let profile = self.audio_object_type.as_u16();
+        if !(1..=4).contains(&profile) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ADTS only supports audio object types 1 to 4",
+            ));
+        }
+
+        if let Some(error_check) = &self.error_check
+            && self.number_of_raw_data_blocks_in_frame > 0
+            && error_check.raw_data_block_positions.len() != self.number_of_raw_data_blocks_in_frame as usize
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "raw data block positions do not match the number of raw data blocks",
+            ));
+        }
+
+        let mut bit_writer = BitWriter::new(writer);
+
+        bit_writer.write_bits(SYNC_WORD, 12)?;
+        bit_writer.write_bit(self.mpeg_version == MpegVersion::Mpeg2)?;
+        bit_writer.write_bits(0, 2)?; // layer
+        bit_writer.write_bit(self.error_check.is_none())?;
+        bit_writer.write_bits(profile as u64 - 1, 2)?;
+        bit_writer.write_bits(self.sampling_frequency_index as u64, 4)?;
+        bit_writer.write_bit(self.private_bit)?;
+        bit_writer.write_bits(self.channel_configuration as u64, 3)?;
+        bit_writer.write_bit(self.original_copy)?;
+        bit_writer.write_bit(self.home)?;
+        bit_writer.write_bit(self.copyright_identification_bit)?;
+        bit_writer.write_bit(self.copyright_identification_start)?;
+        bit_writer.write_bits(self.frame_length as u64, 13)?;
+        bit_writer.write_bits(self.buffer_fullness as u64, 11)?;
+        bit_writer.write_bits(self.number_of_raw_data_blocks_in_frame as u64, 2)?;
+
+        if let Some(error_check) = &self.error_check {
+            for position in &error_check.raw_data_block_positions {
+                bit_writer.write_bits(*position as u64, 16)?;
+            }
+            bit_writer.write_bits(error_check.crc_check as u64, 16)?;
+        }
+
+        bit_writer.finish()?;
+
+        Ok(())
+    }
+}
+
+/// An ADTS frame, a header followed by the raw data block(s).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdtsFrame {
+    /// The header of the frame.
+    pub header: AdtsHeader,
+    /// The payload of the frame.
+    ///
+    /// If the frame contains a single raw data block this is a raw AAC access
+    /// unit which can be placed directly in an FLV tag or MP4 sample.
+    pub payload: Bytes,
+}
+
+impl AdtsFrame {
+    /// Creates a new frame with an unprotected header for the given raw data
+    /// block.
+    pub fn new(config: &AudioSpecificConfig, payload: Bytes) -> io::Result<Self> {
+        Ok(Self {
+            header: AdtsHeader::from_audio_specific_config(config, payload.len())?,
+            payload,
+        })
+    }
+
+    /// Parses a single ADTS frame from the reader.
+    pub fn parse(reader: &mut io::Cursor<Bytes>) -> io::Result<Self> {
+        let header = AdtsHeader::parse(&mut *reader)?;
+        let payload = reader.extract_bytes(header.payload_size())?;
+
+        Ok(Self { header, payload })
+    }
+
+    /// Parses all ADTS frames from the given data.
+    ///
+    /// Fails if the data does not end on a frame boundary.
+    pub fn parse_all(data: Bytes) -> io::Result<Vec<Self>> {
+        let mut reader = io::Cursor::new(data);
+        let mut frames = Vec::new();
+
+        while reader.has_remaining() {
+            frames.push(Self::parse(&mut reader)?);
+        }
+
+        Ok(frames)
+    }
+
+    /// Builds the ADTS frame into a byte stream.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.header.payload_size() != self.payload.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ADTS frame length does not match the payload size",
+            ));
+        }
+
+        self.header.build(writer)?;
+        writer.write_all(&self.payload)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adts_header_parse() {
+        // AAC LC, 44100 Hz, stereo, 371 byte frame, VBR
+        let data = [0xff, 0xf1, 0x50, 0x80, 0x2e, 0x7f, 0xfc];
+
+        let header = AdtsHeader::parse(io::Cursor::new(data)).unwrap();
+        assert_eq!(header.mpeg_version, MpegVersion::Mpeg4);
+        assert_eq!(header.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(header.sampling_frequency(), Some(44100));
+        assert_eq!(header.channel_configuration, 2);
+        assert_eq!(header.frame_length, 371);
+        assert_eq!(header.buffer_fullness, 0x7ff);
+        assert_eq!(header.raw_data_blocks(), 1);
+        assert_eq!(header.error_check, None);
+        assert_eq!(header.size(), 7);
+        assert_eq!(header.payload_size(), 364);
+
+        let config = header.audio_specific_config().unwrap();
+        assert_eq!(config, AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 44100, 2));
+
+        let mut built = Vec::new();
+        header.build(&mut built).unwrap();
+        assert_eq!(built, data);
+    }
+
+    #[test]
+    fn test_adts_header_protected_round_trip() {
+        let header = AdtsHeader {
+            mpeg_version: MpegVersion::Mpeg2,
+            audio_object_type: AudioObjectType::AacMain,
+            sampling_frequency_index: SampleFrequencyIndex::Freq48000,
+            private_bit: true,
+            channel_configuration: 6,
+            original_copy: true,
+            home: false,
+            copyright_identification_bit: true,
+            copyright_identification_start: false,
+            frame_length: 100,
+            buffer_fullness: 123,
+            number_of_raw_data_blocks_in_frame: 2,
+            error_check: Some(AdtsErrorCheck {
+                raw_data_block_positions: vec![20, 40],
+                crc_check: 0xBEEF,
+            }),
+        };
+        assert_eq!(header.size(), 13);
+
+        let mut built = Vec::new();
+        header.build(&mut built).unwrap();
+        assert_eq!(built.len(), 13);
+        assert_eq!(AdtsHeader::parse(io::Cursor::new(&built)).unwrap(), header);
+    }
+
+    #[test]
+    fn test_adts_header_invalid() {
+        assert!(AdtsHeader::parse(io::Cursor::new([0x00, 0xf1, 0x50, 0x80, 0x2e, 0x7f, 0xfc])).is_err());
+        // layer != 0
+        assert!(AdtsHeader::parse(io::Cursor::new([0xff, 0xf3, 0x50, 0x80, 0x2e, 0x7f, 0xfc])).is_err());
+        // reserved sampling frequency index
+        assert!(AdtsHeader::parse(io::Cursor::new([0xff, 0xf1, 0x74, 0x80, 0x2e, 0x7f, 0xfc])).is_err());
+        // frame length smaller than header
+        assert!(AdtsHeader::parse(io::Cursor::new([0xff, 0xf1, 0x50, 0x80, 0x00, 0x7f, 0xfc])).is_err());
+    }
+
+    #[test]
+    fn test_adts_frames_round_trip() {
+        let config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 48000, 1);
+
+        let frames = vec![
+            AdtsFrame::new(&config, Bytes::from_static(b"first access unit")).unwrap(),
+            AdtsFrame::new(&config, Bytes::from_static(b"second")).unwrap(),
+        ];
+
+        let mut data = Vec::new();
+        for frame in &frames {
+            frame.build(&mut data).unwrap();
+        }
+
+        let parsed = AdtsFrame::parse_all(Bytes::from(data.clone())).unwrap();
+        assert_eq!(parsed, frames);
+        assert_eq!(parsed[0].header.audio_specific_config().unwrap(), config);
+
+        // truncated frame
+        data.pop();
+        assert!(AdtsFrame::parse_all(Bytes::from(data)).is_err());
+    }
+
+    #[test]
+    fn test_adts_unsupported_config() {
+        let config = AudioSpecificConfig::new(AudioObjectType::ErAacLowDelay, 48000, 2);
+        assert!(AdtsHeader::from_audio_specific_config(&config, 10).is_err());
+
+        let config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 12345, 2);
+        assert!(AdtsHeader::from_audio_specific_config(&config, 10).is_err());
+
+        let config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 48000, 2);
+        assert!(AdtsHeader::from_audio_specific_config(&config, 0x2000).is_err());
+    }
+}
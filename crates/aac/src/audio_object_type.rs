@@ -0,0 +1,173 @@
+use std::io;
+
+use scuffle_bytes_util::{BitReader, BitWriter};
+
+/// Audio Object Type
+/// ISO/IEC 14496-3:2019(E) - 1.5.1.1 (Table 1.17)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub enum AudioObjectType {
+    /// AAC main
+    AacMain,
+    /// AAC LC
+    AacLowComplexity,
+    /// AAC SSR (Scalable Sample Rate)
+    AacScalableSampleRate,
+    /// AAC LTP (Long Term Prediction)
+    AacLongTermPrediction,
+    /// SBR (Spectral Band Replication), used to signal HE-AAC v1
+    SpectralBandReplication,
+    /// AAC Scalable
+    AacScalable,
+    /// ER AAC LC
+    ErAacLowComplexity,
+    /// ER AAC LTP
+    ErAacLongTermPrediction,
+    /// ER AAC scalable
+    ErAacScalable,
+    /// ER BSAC (Bit-Sliced Arithmetic Coding)
+    ErBsac,
+    /// ER AAC LD (Low Delay)
+    ErAacLowDelay,
+    /// PS (Parametric Stereo), used to signal HE-AAC v2
+    ParametricStereo,
+    /// ER AAC ELD (Enhanced Low Delay)
+    ErAacEnhancedLowDelay,
+    /// USAC (Unified Speech and Audio Coding)
+    Usac,
+    /// Any other object type
+    Unknown(u16),
+}
+
+impl AudioObjectType {
+    /// Converts an AudioObjectType to a u16
+    pub const fn as_u16(&self) -> u16 {
+        match self {
+            AudioObjectType::AacMain => 1,
+            AudioObjectType::AacLowComplexity => 2,
+            AudioObjectType::AacScalableSampleRate => 3,
+            AudioObjectType::AacLongTermPrediction => 4,
+            AudioObjectType::SpectralBandReplication => 5,
+            AudioObjectType::AacScalable => 6,
+            AudioObjectType::ErAacLowComplexity => 17,
+            AudioObjectType::ErAacLongTermPrediction => 19,
+            AudioObjectType::ErAacScalable => 20,
+            AudioObjectType::ErBsac => 22,
+            AudioObjectType::ErAacLowDelay => 23,
+            AudioObjectType::ParametricStereo => 29,
+            AudioObjectType::ErAacEnhancedLowDelay => 39,
+            AudioObjectType::Usac => 42,
+            AudioObjectType::Unknown(value) => *value,
+        }
+    }
+
+    /// Converts a u16 to an AudioObjectType
+    pub const fn from_u16(value: u16) -> Self {
+        match value {
+            1 => AudioObjectType::AacMain,
+            2 => AudioObjectType::AacLowComplexity,
+            3 => AudioObjectType::AacScalableSampleRate,
+            4 => AudioObjectType::AacLongTermPrediction,
+            5 => AudioObjectType::SpectralBandReplication,
+            6 => AudioObjectType::AacScalable,
+            17 => AudioObjectType::ErAacLowComplexity,
+            19 => AudioObjectType::ErAacLongTermPrediction,
+            20 => AudioObjectType::ErAacScalable,
+            22 => AudioObjectType::ErBsac,
+            23 => AudioObjectType::ErAacLowDelay,
+            29 => AudioObjectType::ParametricStereo,
+            39 => AudioObjectType::ErAacEnhancedLowDelay,
+            42 => AudioObjectType::Usac,
+            _ => AudioObjectType::Unknown(value),
+        }
+    }
+
+    /// Returns true if the object type is configured with a `GASpecificConfig`.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.19)
+    pub const fn has_ga_specific_config(&self) -> bool {
+        matches!(self.as_u16(), 1 | 2 | 3 | 4 | 6 | 7 | 17 | 19 | 20 | 21 | 22 | 23)
+    }
+
+    /// Returns true if the object type is an error resilient (ER) object type
+    /// which carries an `epConfig` field.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.19)
+    pub const fn is_error_resilient(&self) -> bool {
+        matches!(self.as_u16(), 17 | 19 | 20 | 21 | 22 | 23 | 24 | 25 | 26 | 27 | 39)
+    }
+
+    /// Reads an audio object type using the escape mechanism.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.20)
+    pub(crate) fn read<R: io::Read>(reader: &mut BitReader<R>) -> io::Result<Self> {
+        let mut audio_object_type = reader.read_bits(5)? as u16;
+        if audio_object_type == 31 {
+            audio_object_type = 32 + reader.read_bits(6)? as u16;
+        }
+
+        Ok(audio_object_type.into())
+    }
+
+    /// Writes an audio object type using the escape mechanism.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.20)
+    pub(crate) fn write<W: io::Write>(&self, writer: &mut BitWriter<W>) -> io::Result<()> {
+        match self.as_u16() {
+            value @ 0..=30 => writer.write_bits(value as u64, 5)?,
+            value @ 32..=95 => {
+                writer.write_bits(31, 5)?;
+                writer.write_bits((value - 32) as u64, 6)?;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "audio object type cannot be represented",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<u16> for AudioObjectType {
+    fn from(value: u16) -> Self {
+        Self::from_u16(value)
+    }
+}
+
+impl From<AudioObjectType> for u16 {
+    fn from(value: AudioObjectType) -> Self {
+        value.as_u16()
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_object_type_round_trip() {
+        // 31 is the escape value and cannot be represented
+        for value in (0..=30).chain(32..=95) {
+            let object_type = AudioObjectType::from_u16(value);
+            assert_eq!(object_type.as_u16(), value);
+
+            let mut writer = BitWriter::new(Vec::new());
+            object_type.write(&mut writer).unwrap();
+            let data = writer.finish().unwrap();
+
+            let mut reader = BitReader::new_from_slice(data);
+            assert_eq!(AudioObjectType::read(&mut reader).unwrap(), object_type);
+        }
+    }
+
+    #[test]
+    fn test_audio_object_type_invalid() {
+        let mut writer = BitWriter::new(Vec::new());
+        assert!(AudioObjectType::Unknown(31).write(&mut writer).is_err());
+        assert!(AudioObjectType::Unknown(96).write(&mut writer).is_err());
+    }
+}
@@ -0,0 +1,661 @@
+use std::io;
+
+use scuffle_bytes_util::{BitReader, BitWriter};
+
+use crate::{AudioObjectType, SampleFrequencyIndex};
+
+mod ga_specific_config;
+mod program_config_element;
+
+pub use ga_specific_config::{GaSpecificConfig, GaSpecificConfigExtension};
+pub use program_config_element::{ChannelElement, CouplingChannelElement, MatrixMixdown, ProgramConfigElement};
+
+/// The sync extension type used to signal SBR in a backward compatible way.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.6.6.1
+const SYNC_EXTENSION_TYPE_SBR: u64 = 0x2B7;
+
+/// The sync extension type used to signal PS in a backward compatible way.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.6.6.1
+const SYNC_EXTENSION_TYPE_PS: u64 = 0x548;
+
+/// A Partial Audio Specific Config
+/// ISO/IEC 14496-3:2019(E) - 1.6
+///
+/// This struct does not represent the full AudioSpecificConfig, it only
+/// represents the top few fields.
+/// Use [`AudioSpecificConfig`] to parse the full config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct PartialAudioSpecificConfig {
+    /// Audio Object Type
+    pub audio_object_type: AudioObjectType,
+    /// Sampling Frequency
+    pub sampling_frequency: u32,
+    /// Channel Configuration
+    pub channel_configuration: u8,
+}
+
+impl PartialAudioSpecificConfig {
+    /// Parse the Audio Specific Config from given bytes
+    /// The implementation is based on ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table
+    /// 1.19) This does not parse the entire AAC Data, it only parses the
+    /// top few fields.
+    /// - Audio Object Type
+    /// - Sampling Frequency
+    /// - Channel Configuration
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut bitreader = BitReader::new_from_slice(data);
+
+        // GetAudioObjectType() # ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.20)
+        let audio_object_type = AudioObjectType::read(&mut bitreader)?;
+
+        // The table calls for us to read a 4-bit value. If the value is type FreqEscape
+        // (0xF), we need to read 24 bits to get the sampling frequency.
+        let sampling_frequency = SampleFrequencyIndex::read_freq(&mut bitreader)?;
+
+        // 4 Bits to get the channel configuration
+        let channel_configuration = bitreader.read_bits(4)? as u8;
+
+        Ok(Self {
+            audio_object_type,
+            sampling_frequency,
+            channel_configuration,
+        })
+    }
+}
+
+/// How the SBR / PS extension of an [`AudioSpecificConfig`] is signalled.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.6.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbrSignalling {
+    /// Explicit hierarchical signalling, the audio object type is set to SBR
+    /// (5) or PS (29) and is followed by the object type of the core codec.
+    ///
+    /// This is not backward compatible with decoders which do not support SBR.
+    Hierarchical,
+    /// Explicit backward compatible signalling, the extension is appended after
+    /// the core codec config using a sync extension.
+    BackwardCompatible,
+}
+
+/// The SBR / PS extension of an [`AudioSpecificConfig`], used by HE-AAC v1 and
+/// HE-AAC v2.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.19)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SbrConfig {
+    /// How this extension was signalled.
+    pub signalling: SbrSignalling,
+    /// If SBR is present.
+    ///
+    /// Backward compatible signalling can explicitly signal that SBR is not
+    /// present, which tells the decoder not to look for implicit SBR data.
+    pub sbr_present: bool,
+    /// If PS (Parametric Stereo) is present.
+    pub ps_present: bool,
+    /// The output sampling frequency of the SBR tool, present if SBR is present.
+    pub sampling_frequency: Option<u32>,
+    /// The extension channel configuration, only present when hierarchical
+    /// signalling is used with the ER BSAC object type.
+    pub extension_channel_configuration: Option<u8>,
+}
+
+/// Audio Specific Config
+///
+/// Supports all object types configured with a [`GaSpecificConfig`] (AAC
+/// Main, LC, SSR, LTP, Scalable and their error resilient variants) including
+/// explicitly signalled SBR and PS extensions.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.19)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct AudioSpecificConfig {
+    /// The audio object type of the core codec.
+    ///
+    /// When SBR or PS are signalled hierarchically this is the object type
+    /// which follows the SBR / PS object type.
+    pub audio_object_type: AudioObjectType,
+    /// The sampling frequency of the core codec.
+    pub sampling_frequency: u32,
+    /// The channel configuration (4 bits).
+    ///
+    /// If this is 0 the channel layout is described by the
+    /// [`ProgramConfigElement`] in the [`GaSpecificConfig`].
+    pub channel_configuration: u8,
+    /// The explicitly signalled SBR / PS extension, if any.
+    pub sbr: Option<SbrConfig>,
+    /// The general audio specific config.
+    pub ga_specific_config: GaSpecificConfig,
+    /// The error protection config (2 bits), present for error resilient
+    /// object types.
+    pub ep_config: Option<u8>,
+}
+
+impl AudioSpecificConfig {
+    /// Creates a new AudioSpecificConfig with the default [`GaSpecificConfig`]
+    /// and no extensions.
+    pub fn new(audio_object_type: AudioObjectType, sampling_frequency: u32, channel_configuration: u8) -> Self {
+        Self {
+            audio_object_type,
+            sampling_frequency,
+            channel_configuration,
+            sbr: None,
+            ga_specific_config: GaSpecificConfig::default(),
+            ep_config: audio_object_type.is_error_resilient().then_some(0),
+        }
+    }
+
+    /// Parse the Audio Specific Config from given bytes.
+    ///
+    /// The implementation is based on ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table
+    /// 1.19). Backward compatible SBR / PS signalling is detected if there
+    /// are enough bits left after the core config.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut reader = BitReader::new_from_slice(data);
+        Self::parse_bits(&mut reader, Some(data.len() as u64 * 8))
+    }
+
+    /// Parses an AudioSpecificConfig from a bit reader which is not
+    /// necessarily byte aligned.
+    ///
+    /// `bits_available` is the number of bits the config occupies, if unknown
+    /// backward compatible extensions are not parsed.
+    pub(crate) fn parse_bits<R: io::Read + io::Seek>(
+        reader: &mut BitReader<R>,
+        bits_available: Option<u64>,
+    ) -> io::Result<Self> {
+        let start_bit = reader.bit_stream_position()?;
+
+        let mut audio_object_type = AudioObjectType::read(reader)?;
+        let sampling_frequency = SampleFrequencyIndex::read_freq(reader)?;
+        let channel_configuration = reader.read_bits(4)? as u8;
+
+        let mut sbr = None;
+        if matches!(
+            audio_object_type,
+            AudioObjectType::SpectralBandReplication | AudioObjectType::ParametricStereo
+        ) {
+            let ps_present = audio_object_type == AudioObjectType::ParametricStereo;
+            let extension_sampling_frequency = SampleFrequencyIndex::read_freq(reader)?;
+            audio_object_type = AudioObjectType::read(reader)?;
+            let extension_channel_configuration = if audio_object_type == AudioObjectType::ErBsac {
+                Some(reader.read_bits(4)? as u8)
+            } else {
+                None
+            };
+
+            sbr = Some(SbrConfig {
+                signalling: SbrSignalling::Hierarchical,
+                sbr_present: true,
+                ps_present,
+                sampling_frequency: Some(extension_sampling_frequency),
+                extension_channel_configuration,
+            });
+        }
+
+        if !audio_object_type.has_ga_specific_config() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported audio object type: {}", audio_object_type.as_u16()),
+            ));
+        }
+
+        let ga_specific_config = GaSpecificConfig::parse(reader, start_bit, channel_configuration, audio_object_type)?;
+
+        let ep_config = if audio_object_type.is_error_resilient() {
+            let ep_config = reader.read_bits(2)? as u8;
+            if ep_config >= 2 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "ErrorProtectionSpecificConfig is not supported",
+                ));
+            }
+
+            Some(ep_config)
+        } else {
+            None
+        };
+
+        if sbr.is_none()
+            && let Some(bits_available) = bits_available
+        {
+            let bits_to_decode = |reader: &mut BitReader<R>| -> io::Result<u64> {
+                Ok(bits_available.saturating_sub(reader.bit_stream_position()? - start_bit))
+            };
+
+            if bits_to_decode(reader)? >= 16
+                && reader.read_bits(11)? == SYNC_EXTENSION_TYPE_SBR
+                && AudioObjectType::read(reader)? == AudioObjectType::SpectralBandReplication
+            {
+                let sbr_present = reader.read_bit()?;
+                let mut sampling_frequency = None;
+                let mut ps_present = false;
+
+                if sbr_present {
+                    sampling_frequency = Some(SampleFrequencyIndex::read_freq(reader)?);
+
+                    if bits_to_decode(reader)? >= 12 && reader.read_bits(11)? == SYNC_EXTENSION_TYPE_PS {
+                        ps_present = reader.read_bit()?;
+                    }
+                }
+
+                sbr = Some(SbrConfig {
+                    signalling: SbrSignalling::BackwardCompatible,
+                    sbr_present,
+                    ps_present,
+                    sampling_frequency,
+                    extension_channel_configuration: None,
+                });
+            }
+        }
+
+        Ok(Self {
+            audio_object_type,
+            sampling_frequency,
+            channel_configuration,
+            sbr,
+            ga_specific_config,
+            ep_config,
+        })
+    }
+
+    /// Builds the AudioSpecificConfig into a byte stream.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bit_writer = BitWriter::new(writer);
+
+        let missing_sbr_frequency = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SBR sampling frequency is required when SBR is present",
+            )
+        };
+
+        match &self.sbr {
+            Some(sbr) if sbr.signalling == SbrSignalling::Hierarchical => {
+                if sbr.ps_present {
+                    AudioObjectType::ParametricStereo.write(&mut bit_writer)?;
+                } else {
+                    AudioObjectType::SpectralBandReplication.write(&mut bit_writer)?;
+                }
+                SampleFrequencyIndex::write_freq(self.sampling_frequency, &mut bit_writer)?;
+                bit_writer.write_bits(self.channel_configuration as u64, 4)?;
+                SampleFrequencyIndex::write_freq(
+                    sbr.sampling_frequency.ok_or_else(missing_sbr_frequency)?,
+                    &mut bit_writer,
+                )?;
+                self.audio_object_type.write(&mut bit_writer)?;
+                if self.audio_object_type == AudioObjectType::ErBsac {
+                    bit_writer.write_bits(
+                        sbr.extension_channel_configuration.unwrap_or(self.channel_configuration) as u64,
+                        4,
+                    )?;
+                }
+            }
+            _ => {
+                self.audio_object_type.write(&mut bit_writer)?;
+                SampleFrequencyIndex::write_freq(self.sampling_frequency, &mut bit_writer)?;
+                bit_writer.write_bits(self.channel_configuration as u64, 4)?;
+            }
+        }
+
+        if !self.audio_object_type.has_ga_specific_config() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported audio object type: {}", self.audio_object_type.as_u16()),
+            ));
+        }
+
+        self.ga_specific_config
+            .build(&mut bit_writer, self.channel_configuration, self.audio_object_type)?;
+
+        if self.audio_object_type.is_error_resilient() {
+            bit_writer.write_bits(self.ep_config.unwrap_or_default() as u64, 2)?;
+        }
+
+        if let Some(sbr) = &self.sbr
+            && sbr.signalling == SbrSignalling::BackwardCompatible
+        {
+            bit_writer.write_bits(SYNC_EXTENSION_TYPE_SBR, 11)?;
+            AudioObjectType::SpectralBandReplication.write(&mut bit_writer)?;
+            bit_writer.write_bit(sbr.sbr_present)?;
+            if sbr.sbr_present {
+                SampleFrequencyIndex::write_freq(
+                    sbr.sampling_frequency.ok_or_else(missing_sbr_frequency)?,
+                    &mut bit_writer,
+                )?;
+                if sbr.ps_present {
+                    bit_writer.write_bits(SYNC_EXTENSION_TYPE_PS, 11)?;
+                    bit_writer.write_bit(true)?;
+                }
+            }
+        }
+
+        bit_writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Returns true if SBR is explicitly signalled as present.
+    pub fn sbr_present(&self) -> bool {
+        self.sbr.is_some_and(|sbr| sbr.sbr_present)
+    }
+
+    /// Returns true if PS is explicitly signalled as present.
+    pub fn ps_present(&self) -> bool {
+        self.sbr.is_some_and(|sbr| sbr.sbr_present && sbr.ps_present)
+    }
+
+    /// Returns true if the stream may carry implicitly signalled SBR data.
+    ///
+    /// With implicit signalling the config only describes the AAC LC core at
+    /// half of the output sampling frequency and SBR can only be detected by
+    /// decoding the raw data blocks. In that case the output sampling
+    /// frequency is double the core sampling frequency.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.6.6.2
+    pub fn implicit_sbr_possible(&self) -> bool {
+        self.sbr.is_none() && self.audio_object_type == AudioObjectType::AacLowComplexity && self.sampling_frequency <= 24000
+    }
+
+    /// Returns the sampling frequency of the decoded output.
+    ///
+    /// This is the SBR sampling frequency if SBR is explicitly signalled,
+    /// otherwise the core sampling frequency. See
+    /// [`AudioSpecificConfig::implicit_sbr_possible`] for streams which use
+    /// implicit signalling.
+    pub fn output_sampling_frequency(&self) -> u32 {
+        match &self.sbr {
+            Some(SbrConfig {
+                sbr_present: true,
+                sampling_frequency: Some(sampling_frequency),
+                ..
+            }) => *sampling_frequency,
+            _ => self.sampling_frequency,
+        }
+    }
+
+    /// Returns the number of samples per channel in each access unit of the
+    /// core codec.
+    pub fn frame_length(&self) -> u32 {
+        match (self.audio_object_type, self.ga_specific_config.frame_length_flag) {
+            (AudioObjectType::ErAacLowDelay, false) => 512,
+            (AudioObjectType::ErAacLowDelay, true) => 480,
+            (_, false) => 1024,
+            (_, true) => 960,
+        }
+    }
+
+    /// Returns the number of samples per channel in each access unit of the
+    /// decoded output, this accounts for the upsampling done by SBR.
+    pub fn output_frame_length(&self) -> u32 {
+        let frame_length = self.frame_length();
+        if self.sbr_present() && self.output_sampling_frequency() != self.sampling_frequency {
+            frame_length * 2
+        } else {
+            frame_length
+        }
+    }
+
+    /// Returns the number of output channels.
+    ///
+    /// Returns `None` if the channel configuration is reserved.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.6.3.5 (Table 1.19)
+    pub fn channel_count(&self) -> Option<u8> {
+        let channels = match self.channel_configuration {
+            0 => self.ga_specific_config.program_config_element.as_ref()?.channel_count(),
+            1..=6 => self.channel_configuration,
+            7 => 8,
+            11 => 7,
+            12 | 14 => 8,
+            13 => 24,
+            _ => return None,
+        };
+
+        // Parametric stereo upmixes a mono core to stereo.
+        if channels == 1 && self.ps_present() {
+            Some(2)
+        } else {
+            Some(channels)
+        }
+    }
+
+    /// Returns the [`PartialAudioSpecificConfig`] of this config.
+    pub fn partial(&self) -> PartialAudioSpecificConfig {
+        PartialAudioSpecificConfig {
+            audio_object_type: self.audio_object_type,
+            sampling_frequency: self.sampling_frequency,
+            channel_configuration: self.channel_configuration,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn test_aac_config_parse() {
+        let data = [
+            0x12, 0x10, 0x56, 0xe5, 0x00, 0x2d, 0x96, 0x01, 0x80, 0x80, 0x05, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let config = PartialAudioSpecificConfig::parse(&data).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 44100);
+        assert_eq!(config.channel_configuration, 2);
+    }
+
+    #[test]
+    fn test_full_config_parse_backward_compatible_no_sbr() {
+        // AAC LC, 44100 Hz, stereo followed by a sync extension which explicitly
+        // signals that SBR is not present.
+        let data = [
+            0x12, 0x10, 0x56, 0xe5, 0x00, 0x2d, 0x96, 0x01, 0x80, 0x80, 0x05, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let config = AudioSpecificConfig::parse(&data).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 44100);
+        assert_eq!(config.channel_configuration, 2);
+        assert_eq!(config.ga_specific_config, GaSpecificConfig::default());
+        assert_eq!(
+            config.sbr,
+            Some(SbrConfig {
+                signalling: SbrSignalling::BackwardCompatible,
+                sbr_present: false,
+                ps_present: false,
+                sampling_frequency: None,
+                extension_channel_configuration: None,
+            })
+        );
+        assert!(!config.implicit_sbr_possible());
+        assert_eq!(config.output_sampling_frequency(), 44100);
+        assert_eq!(config.channel_count(), Some(2));
+        assert_eq!(config.partial(), PartialAudioSpecificConfig::parse(&data).unwrap());
+    }
+
+    #[test]
+    fn test_config_parse_lc() {
+        let config = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+        assert_eq!(config, AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 44100, 2));
+        assert_eq!(config.frame_length(), 1024);
+        assert_eq!(config.output_frame_length(), 1024);
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+        assert_eq!(built, [0x12, 0x10]);
+    }
+
+    #[test]
+    fn test_config_parse_he_aac_v1_hierarchical() {
+        // SBR, 22050 Hz core, stereo, 44100 Hz extension, AAC LC
+        let data = [0x2b, 0x92, 0x08, 0x00];
+
+        let config = AudioSpecificConfig::parse(&data).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 22050);
+        assert_eq!(config.channel_configuration, 2);
+        assert_eq!(
+            config.sbr,
+            Some(SbrConfig {
+                signalling: SbrSignalling::Hierarchical,
+                sbr_present: true,
+                ps_present: false,
+                sampling_frequency: Some(44100),
+                extension_channel_configuration: None,
+            })
+        );
+        assert!(config.sbr_present());
+        assert!(!config.ps_present());
+        assert_eq!(config.output_sampling_frequency(), 44100);
+        assert_eq!(config.output_frame_length(), 2048);
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+        assert_eq!(AudioSpecificConfig::parse(&built).unwrap(), config);
+    }
+
+    #[test]
+    fn test_config_he_aac_v2_round_trip() {
+        let mut config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 24000, 1);
+        config.sbr = Some(SbrConfig {
+            signalling: SbrSignalling::Hierarchical,
+            sbr_present: true,
+            ps_present: true,
+            sampling_frequency: Some(48000),
+            extension_channel_configuration: None,
+        });
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+        assert_eq!(
+            PartialAudioSpecificConfig::parse(&built).unwrap().audio_object_type,
+            AudioObjectType::ParametricStereo
+        );
+
+        let parsed = AudioSpecificConfig::parse(&built).unwrap();
+        assert_eq!(parsed, config);
+        assert!(parsed.ps_present());
+        assert_eq!(parsed.channel_count(), Some(2));
+        assert_eq!(parsed.output_sampling_frequency(), 48000);
+    }
+
+    #[test]
+    fn test_config_backward_compatible_round_trip() {
+        let mut config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 22050, 1);
+        config.sbr = Some(SbrConfig {
+            signalling: SbrSignalling::BackwardCompatible,
+            sbr_present: true,
+            ps_present: true,
+            sampling_frequency: Some(44100),
+            extension_channel_configuration: None,
+        });
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+
+        let partial = PartialAudioSpecificConfig::parse(&built).unwrap();
+        assert_eq!(partial.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(partial.sampling_frequency, 22050);
+
+        let parsed = AudioSpecificConfig::parse(&built).unwrap();
+        assert_eq!(parsed, config);
+        assert_eq!(parsed.output_sampling_frequency(), 44100);
+        assert_eq!(parsed.channel_count(), Some(2));
+    }
+
+    #[test]
+    fn test_config_implicit_sbr() {
+        let config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 24000, 2);
+        assert!(config.implicit_sbr_possible());
+        assert_eq!(config.output_sampling_frequency(), 24000);
+
+        let config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 48000, 2);
+        assert!(!config.implicit_sbr_possible());
+    }
+
+    #[test]
+    fn test_config_program_config_element() {
+        let mut config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 48000, 0);
+        config.ga_specific_config.program_config_element = Some(ProgramConfigElement {
+            element_instance_tag: 0,
+            object_type: 1,
+            sampling_frequency_index: SampleFrequencyIndex::Freq48000,
+            front_channel_elements: vec![ChannelElement {
+                is_cpe: true,
+                tag_select: 0,
+            }],
+            side_channel_elements: vec![],
+            back_channel_elements: vec![],
+            lfe_channel_elements: vec![],
+            assoc_data_elements: vec![],
+            cc_elements: vec![],
+            mono_mixdown_element_number: None,
+            stereo_mixdown_element_number: Some(2),
+            matrix_mixdown: None,
+            comment: Bytes::from_static(b"hi"),
+        });
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+
+        let parsed = AudioSpecificConfig::parse(&built).unwrap();
+        assert_eq!(parsed, config);
+        assert_eq!(parsed.channel_count(), Some(2));
+
+        config.ga_specific_config.program_config_element = None;
+        assert!(config.build(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_config_error_resilient() {
+        let mut config = AudioSpecificConfig::new(AudioObjectType::ErAacLowDelay, 48000, 2);
+        config.ga_specific_config.frame_length_flag = true;
+        config.ga_specific_config.extension = Some(GaSpecificConfigExtension {
+            aac_section_data_resilience_flag: true,
+            aac_spectral_data_resilience_flag: true,
+            ..Default::default()
+        });
+        assert_eq!(config.ep_config, Some(0));
+        assert_eq!(config.frame_length(), 480);
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+        assert_eq!(AudioSpecificConfig::parse(&built).unwrap(), config);
+    }
+
+    #[test]
+    fn test_config_escape_frequency_and_core_coder() {
+        let mut config = AudioSpecificConfig::new(AudioObjectType::AacScalable, 12345, 1);
+        config.ga_specific_config.core_coder_delay = Some(100);
+        config.ga_specific_config.layer_nr = Some(3);
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+        assert_eq!(AudioSpecificConfig::parse(&built).unwrap(), config);
+    }
+
+    #[test]
+    fn test_config_unsupported_object_type() {
+        // USAC, 48000 Hz, stereo
+        let mut writer = BitWriter::new(Vec::new());
+        AudioObjectType::Usac.write(&mut writer).unwrap();
+        writer.write_bits(3, 4).unwrap();
+        writer.write_bits(2, 4).unwrap();
+        let data = writer.finish().unwrap();
+
+        let err = AudioSpecificConfig::parse(&data).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(
+            PartialAudioSpecificConfig::parse(&data).unwrap().audio_object_type,
+            AudioObjectType::Usac
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::io;
+
+use scuffle_bytes_util::{BitReader, BitWriter};
+
+use super::ProgramConfigElement;
+use crate::AudioObjectType;
+
+/// General Audio Specific Config
+///
+/// ISO/IEC 14496-3:2019(E) - 4.4.1 (Table 4.1)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GaSpecificConfig {
+    /// If set, the frame length is 960 samples (or 480 for low delay object
+    /// types) instead of 1024 (or 512).
+    pub frame_length_flag: bool,
+    /// The core coder delay in samples (14 bits), present if the stream
+    /// depends on a core coder.
+    pub core_coder_delay: Option<u16>,
+    /// The program config element, present if the `channel_configuration` is 0.
+    pub program_config_element: Option<ProgramConfigElement>,
+    /// The layer number (3 bits), only present for the AAC scalable object
+    /// types.
+    pub layer_nr: Option<u8>,
+    /// The extension fields, present if the `extensionFlag` is set.
+    pub extension: Option<GaSpecificConfigExtension>,
+}
+
+/// The extension fields of a [`GaSpecificConfig`].
+///
+/// ISO/IEC 14496-3:2019(E) - 4.4.1 (Table 4.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GaSpecificConfigExtension {
+    /// The number of sub frames (5 bits), only used by ER BSAC.
+    pub num_of_sub_frame: u8,
+    /// The layer length (11 bits), only used by ER BSAC.
+    pub layer_length: u16,
+    /// The `aacSectionDataResilienceFlag`, only used by ER AAC object types.
+    pub aac_section_data_resilience_flag: bool,
+    /// The `aacScalefactorDataResilienceFlag`, only used by ER AAC object
+    /// types.
+    pub aac_scalefactor_data_resilience_flag: bool,
+    /// The `aacSpectralDataResilienceFlag`, only used by ER AAC object types.
+    pub aac_spectral_data_resilience_flag: bool,
+    /// The `extensionFlag3`, reserved for future use.
+    pub extension_flag3: bool,
+}
+
+const fn has_resilience_flags(audio_object_type: AudioObjectType) -> bool {
+    matches!(audio_object_type.as_u16(), 17 | 19 | 20 | 23)
+}
+
+impl GaSpecificConfig {
+    /// Parses a GASpecificConfig.
+    pub(crate) fn parse<R: io::Read + io::Seek>(
+        reader: &mut BitReader<R>,
+        start_bit: u64,
+        channel_configuration: u8,
+        audio_object_type: AudioObjectType,
+    ) -> io::Result<Self> {
+        let frame_length_flag = reader.read_bit()?;
+        let depends_on_core_coder = reader.read_bit()?;
+        let core_coder_delay = if depends_on_core_coder {
+            Some(reader.read_bits(14)? as u16)
+        } else {
+            None
+        };
+        let extension_flag = reader.read_bit()?;
+
+        let program_config_element = if channel_configuration == 0 {
+            Some(ProgramConfigElement::parse(reader, start_bit)?)
+        } else {
+            None
+        };
+
+        let layer_nr = match audio_object_type {
+            AudioObjectType::AacScalable | AudioObjectType::ErAacScalable => Some(reader.read_bits(3)? as u8),
+            _ => None,
+        };
+
+        let extension = if extension_flag {
+            let mut extension = GaSpecificConfigExtension::default();
+
+            if audio_object_type == AudioObjectType::ErBsac {
+                extension.num_of_sub_frame = reader.read_bits(5)? as u8;
+                extension.layer_length = reader.read_bits(11)? as u16;
+            }
+
+            if has_resilience_flags(audio_object_type) {
+                extension.aac_section_data_resilience_flag = reader.read_bit()?;
+                extension.aac_scalefactor_data_resilience_flag = reader.read_bit()?;
+                extension.aac_spectral_data_resilience_flag = reader.read_bit()?;
+            }
+
+            extension.extension_flag3 = reader.read_bit()?;
+
+            Some(extension)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            frame_length_flag,
+            core_coder_delay,
+            program_config_element,
+            layer_nr,
+            extension,
+        })
+    }
+
+    /// Builds a GASpecificConfig.
+    pub(crate) fn build<W: io::Write>(
+        &self,
+        writer: &mut BitWriter<W>,
+        channel_configuration: u8,
+        audio_object_type: AudioObjectType,
+    ) -> io::Result<()> {
+        writer.write_bit(self.frame_length_flag)?;
+        writer.write_bit(self.core_coder_delay.is_some())?;
+        if let Some(core_coder_delay) = self.core_coder_delay {
+            writer.write_bits(core_coder_delay as u64, 14)?;
+        }
+        writer.write_bit(self.extension.is_some())?;
+
+        if channel_configuration == 0 {
+            self.program_config_element
+                .as_ref()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "program config element is required when channel configuration is 0",
+                    )
+                })?
+                .build(writer)?;
+        }
+
+        if matches!(
+            audio_object_type,
+            AudioObjectType::AacScalable | AudioObjectType::ErAacScalable
+        ) {
+            writer.write_bits(self.layer_nr.unwrap_or_default() as u64, 3)?;
+        }
+
+        if let Some(extension) = &self.extension {
+            if audio_object_type == AudioObjectType::ErBsac {
+                writer.write_bits(extension.num_of_sub_frame as u64, 5)?;
+                writer.write_bits(extension.layer_length as u64, 11)?;
+            }
+
+            if has_resilience_flags(audio_object_type) {
+                writer.write_bit(extension.aac_section_data_resilience_flag)?;
+                writer.write_bit(extension.aac_scalefactor_data_resilience_flag)?;
+                writer.write_bit(extension.aac_spectral_data_resilience_flag)?;
+            }
+
+            writer.write_bit(extension.extension_flag3)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+use std::io;
+
+use bytes::Bytes;
+use scuffle_bytes_util::{BitReader, BitWriter};
+
+use crate::SampleFrequencyIndex;
+
+/// A channel element referenced by a program config element.
+///
+/// ISO/IEC 14496-3:2019(E) - 4.4.1.1 (Table 4.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelElement {
+    /// If the element is a channel pair element (CPE), otherwise it is a single
+    /// channel element (SCE).
+    pub is_cpe: bool,
+    /// The instance tag of the referenced element.
+    pub tag_select: u8,
+}
+
+impl ChannelElement {
+    /// Returns the number of audio channels carried by this element.
+    pub const fn channel_count(&self) -> u8 {
+        if self.is_cpe { 2 } else { 1 }
+    }
+}
+
+/// A coupling channel element referenced by a program config element.
+///
+/// ISO/IEC 14496-3:2019(E) - 4.4.1.1 (Table 4.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CouplingChannelElement {
+    /// If the coupling channel element is independently switched.
+    pub is_ind_sw: bool,
+    /// The instance tag of the referenced element.
+    pub tag_select: u8,
+}
+
+/// Matrix mixdown information of a program config element.
+///
+/// ISO/IEC 14496-3:2019(E) - 4.4.1.1 (Table 4.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatrixMixdown {
+    /// The matrix mixdown coefficient index (2 bits).
+    pub idx: u8,
+    /// If pseudo surround is enabled.
+    pub pseudo_surround_enable: bool,
+}
+
+/// Program Config Element
+///
+/// Describes the channel layout of a stream when the `channel_configuration`
+/// of the [`AudioSpecificConfig`](crate::AudioSpecificConfig) is 0.
+///
+/// ISO/IEC 14496-3:2019(E) - 4.4.1.1 (Table 4.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramConfigElement {
+    /// The instance tag of this element (4 bits).
+    pub element_instance_tag: u8,
+    /// The object type (2 bits), this is the audio object type minus one.
+    pub object_type: u8,
+    /// The sampling frequency index of the program.
+    pub sampling_frequency_index: SampleFrequencyIndex,
+    /// Front channel elements (max 15).
+    pub front_channel_elements: Vec<ChannelElement>,
+    /// Side channel elements (max 15).
+    pub side_channel_elements: Vec<ChannelElement>,
+    /// Back channel elements (max 15).
+    pub back_channel_elements: Vec<ChannelElement>,
+    /// Instance tags of the LFE channel elements (max 3).
+    pub lfe_channel_elements: Vec<u8>,
+    /// Instance tags of the associated data elements (max 7).
+    pub assoc_data_elements: Vec<u8>,
+    /// Coupling channel elements (max 15).
+    pub cc_elements: Vec<CouplingChannelElement>,
+    /// The mono mixdown element number, if present.
+    pub mono_mixdown_element_number: Option<u8>,
+    /// The stereo mixdown element number, if present.
+    pub stereo_mixdown_element_number: Option<u8>,
+    /// The matrix mixdown information, if present.
+    pub matrix_mixdown: Option<MatrixMixdown>,
+    /// The comment field data (max 255 bytes).
+    pub comment: Bytes,
+}
+
+impl ProgramConfigElement {
+    /// Returns the total number of output channels (including LFE channels)
+    /// described by this element.
+    pub fn channel_count(&self) -> u8 {
+        self.front_channel_elements
+            .iter()
+            .chain(self.side_channel_elements.iter())
+            .chain(self.back_channel_elements.iter())
+            .map(ChannelElement::channel_count)
+            .sum::<u8>()
+            + self.lfe_channel_elements.len() as u8
+    }
+
+    /// Parses a program config element.
+    ///
+    /// `start_bit` is the bit position of the start of the enclosing
+    /// `AudioSpecificConfig`, the `byte_alignment()` within the element is
+    /// relative to it.
+    pub(crate) fn parse<R: io::Read + io::Seek>(reader: &mut BitReader<R>, start_bit: u64) -> io::Result<Self> {
+        let element_instance_tag = reader.read_bits(4)? as u8;
+        let object_type = reader.read_bits(2)? as u8;
+        let sampling_frequency_index = SampleFrequencyIndex::read(reader)?;
+        let num_front_channel_elements = reader.read_bits(4)? as usize;
+        let num_side_channel_elements = reader.read_bits(4)? as usize;
+        let num_back_channel_elements = reader.read_bits(4)? as usize;
+        let num_lfe_channel_elements = reader.read_bits(2)? as usize;
+        let num_assoc_data_elements = reader.read_bits(3)? as usize;
+        let num_valid_cc_elements = reader.read_bits(4)? as usize;
+
+        let mono_mixdown_element_number = if reader.read_bit()? {
+            Some(reader.read_bits(4)? as u8)
+        } else {
+            None
+        };
+
+        let stereo_mixdown_element_number = if reader.read_bit()? {
+            Some(reader.read_bits(4)? as u8)
+        } else {
+            None
+        };
+
+        let matrix_mixdown = if reader.read_bit()? {
+            Some(MatrixMixdown {
+                idx: reader.read_bits(2)? as u8,
+                pseudo_surround_enable: reader.read_bit()?,
+            })
+        } else {
+            None
+        };
+
+        let mut read_channel_elements = |count: usize| -> io::Result<Vec<ChannelElement>> {
+            (0..count)
+                .map(|_| {
+                    Ok(ChannelElement {
+                        is_cpe: reader.read_bit()?,
+                        tag_select: reader.read_bits(4)? as u8,
+                    })
+                })
+                .collect()
+        };
+
+        let front_channel_elements = read_channel_elements(num_front_channel_elements)?;
+        let side_channel_elements = read_channel_elements(num_side_channel_elements)?;
+        let back_channel_elements = read_channel_elements(num_back_channel_elements)?;
+
+        let lfe_channel_elements = (0..num_lfe_channel_elements)
+            .map(|_| reader.read_bits(4).map(|v| v as u8))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let assoc_data_elements = (0..num_assoc_data_elements)
+            .map(|_| reader.read_bits(4).map(|v| v as u8))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let cc_elements = (0..num_valid_cc_elements)
+            .map(|_| {
+                Ok(CouplingChannelElement {
+                    is_ind_sw: reader.read_bit()?,
+                    tag_select: reader.read_bits(4)? as u8,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // byte_alignment() relative to the start of the AudioSpecificConfig
+        let offset = (reader.bit_stream_position()? - start_bit) % 8;
+        if offset != 0 {
+            reader.read_bits(8 - offset as u8)?;
+        }
+
+        let comment_field_bytes = reader.read_bits(8)? as usize;
+        let mut comment = vec![0; comment_field_bytes];
+        io::Read::read_exact(reader, &mut comment)?;
+
+        Ok(Self {
+            element_instance_tag,
+            object_type,
+            sampling_frequency_index,
+            front_channel_elements,
+            side_channel_elements,
+            back_channel_elements,
+            lfe_channel_elements,
+            assoc_data_elements,
+            cc_elements,
+            mono_mixdown_element_number,
+            stereo_mixdown_element_number,
+            matrix_mixdown,
+            comment: Bytes::from(comment),
+        })
+    }
+
+    /// Builds the program config element.
+    ///
+    /// The writer is expected to have been aligned at the start of the
+    /// enclosing `AudioSpecificConfig`.
+    pub(crate) fn build<W: io::Write>(&self, writer: &mut BitWriter<W>) -> io::Result<()> {
+        fn check_len<T>(items: &[T], max: usize, name: &str) -> io::Result<u64> {
+            if items.len() > max {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("too many {name}")));
+            }
+
+            Ok(items.len() as u64)
+        }
+
+        writer.write_bits(self.element_instance_tag as u64, 4)?;
+        writer.write_bits(self.object_type as u64, 2)?;
+        writer.write_bits(self.sampling_frequency_index as u64, 4)?;
+        writer.write_bits(check_len(&self.front_channel_elements, 15, "front channel elements")?, 4)?;
+        writer.write_bits(check_len(&self.side_channel_elements, 15, "side channel elements")?, 4)?;
+        writer.write_bits(check_len(&self.back_channel_elements, 15, "back channel elements")?, 4)?;
+        writer.write_bits(check_len(&self.lfe_channel_elements, 3, "lfe channel elements")?, 2)?;
+        writer.write_bits(check_len(&self.assoc_data_elements, 7, "assoc data elements")?, 3)?;
+        writer.write_bits(check_len(&self.cc_elements, 15, "cc elements")?, 4)?;
+
+        writer.write_bit(self.mono_mixdown_element_number.is_some())?;
+        if let Some(number) = self.mono_mixdown_element_number {
+            writer.write_bits(number as u64, 4)?;
+        }
+
+        writer.write_bit(self.stereo_mixdown_element_number.is_some())?;
+        if let Some(number) = self.stereo_mixdown_element_number {
+            writer.write_bits(number as u64, 4)?;
+        }
+
+        writer.write_bit(self.matrix_mixdown.is_some())?;
+        if let Some(matrix_mixdown) = &self.matrix_mixdown {
+            writer.write_bits(matrix_mixdown.idx as u64, 2)?;
+            writer.write_bit(matrix_mixdown.pseudo_surround_enable)?;
+        }
+
+        for element in self
+            .front_channel_elements
+            .iter()
+            .chain(self.side_channel_elements.iter())
+            .chain(self.back_channel_elements.iter())
+        {
+            writer.write_bit(element.is_cpe)?;
+            writer.write_bits(element.tag_select as u64, 4)?;
+        }
+
+        for tag in self.lfe_channel_elements.iter().chain(self.assoc_data_elements.iter()) {
+            writer.write_bits(*tag as u64, 4)?;
+        }
+
+        for element in &self.cc_elements {
+            writer.write_bit(element.is_ind_sw)?;
+            writer.write_bits(element.tag_select as u64, 4)?;
+        }
+
+        writer.align()?;
+
+        writer.write_bits(check_len(&self.comment, 255, "comment bytes")?, 8)?;
+        io::Write::write_all(writer, &self.comment)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    fn sample_pce() -> ProgramConfigElement {
+        // A 5.1 layout: C, L/R front, Ls/Rs back, LFE
+        ProgramConfigElement {
+            element_instance_tag: 0,
+            object_type: 1,
+            sampling_frequency_index: SampleFrequencyIndex::Freq48000,
+            front_channel_elements: vec![
+                ChannelElement {
+                    is_cpe: false,
+                    tag_select: 0,
+                },
+                ChannelElement {
+                    is_cpe: true,
+                    tag_select: 0,
+                },
+            ],
+            side_channel_elements: vec![],
+            back_channel_elements: vec![ChannelElement {
+                is_cpe: true,
+                tag_select: 1,
+            }],
+            lfe_channel_elements: vec![0],
+            assoc_data_elements: vec![],
+            cc_elements: vec![],
+            mono_mixdown_element_number: None,
+            stereo_mixdown_element_number: None,
+            matrix_mixdown: Some(MatrixMixdown {
+                idx: 1,
+                pseudo_surround_enable: true,
+            }),
+            comment: Bytes::from_static(b"scuffle"),
+        }
+    }
+
+    #[test]
+    fn test_pce_round_trip() {
+        let pce = sample_pce();
+        assert_eq!(pce.channel_count(), 6);
+
+        let mut writer = BitWriter::new(Vec::new());
+        pce.build(&mut writer).unwrap();
+        let data = writer.finish().unwrap();
+
+        let mut reader = BitReader::new_from_slice(&data);
+        let parsed = ProgramConfigElement::parse(&mut reader, 0).unwrap();
+        assert_eq!(parsed, pce);
+    }
+
+    #[test]
+    fn test_pce_too_many_elements() {
+        let mut pce = sample_pce();
+        pce.lfe_channel_elements = vec![0, 1, 2, 3];
+
+        let mut writer = BitWriter::new(Vec::new());
+        assert!(pce.build(&mut writer).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+//! LATM (Low-overhead MPEG-4 Audio Transport Multiplex) and LOAS (Low Overhead
+//! Audio Stream) parsing.
+//!
+//! LATM is used for AAC in DVB broadcasts. Unlike ADTS the stream
+//! configuration is carried in band in a [`StreamMuxConfig`] which may be
+//! omitted in subsequent frames, so parsing is done with a stateful
+//! [`LatmParser`].
+//!
+//! ISO/IEC 14496-3:2019(E) - 1.7
+
+use std::io;
+
+use bytes::{Buf, Bytes};
+use scuffle_bytes_util::BitReader;
+
+use crate::{AudioObjectType, AudioSpecificConfig};
+
+/// The LOAS `AudioSyncStream` sync word (11 bits).
+const SYNC_WORD: u64 = 0x2B7;
+
+/// A layer of a program in a [`StreamMuxConfig`].
+///
+/// ISO/IEC 14496-3:2019(E) - 1.7.3.1 (Table 1.42)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatmLayer {
+    /// The config of the layer, if the layer reuses the config of the previous
+    /// layer this is a copy of that config.
+    pub audio_specific_config: AudioSpecificConfig,
+    /// The frame length type (3 bits).
+    pub frame_length_type: u8,
+    /// The buffer fullness, present if the frame length type is 0.
+    pub latm_buffer_fullness: Option<u8>,
+    /// The core frame offset, only present for scalable layers which do not
+    /// share the time framing.
+    pub core_frame_offset: Option<u8>,
+    /// The fixed frame length (9 bits), present if the frame length type is 1.
+    pub frame_length: Option<u16>,
+}
+
+/// A program in a [`StreamMuxConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatmProgram {
+    /// The layers of the program (max 8).
+    pub layers: Vec<LatmLayer>,
+}
+
+/// Stream Mux Config
+///
+/// Only `audioMuxVersionA` 0 is defined by the specification.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.7.3.1 (Table 1.42)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMuxConfig {
+    /// The audio mux version (0 or 1).
+    pub audio_mux_version: u8,
+    /// The tara buffer fullness, present if the audio mux version is 1.
+    pub tara_buffer_fullness: Option<u32>,
+    /// If all streams share the same time framing.
+    pub all_streams_same_time_framing: bool,
+    /// The number of sub frames in each audio mux element minus one (6 bits).
+    pub num_sub_frames: u8,
+    /// The programs of the stream (max 16).
+    pub programs: Vec<LatmProgram>,
+    /// The number of other data bits, present if other data is present.
+    pub other_data_len_bits: Option<u32>,
+    /// The CRC checksum, if present.
+    pub crc_checksum: Option<u8>,
+}
+
+impl StreamMuxConfig {
+    /// Returns an iterator over all layers of all programs, in stream id
+    /// order.
+    pub fn layers(&self) -> impl Iterator<Item = &LatmLayer> {
+        self.programs.iter().flat_map(|program| program.layers.iter())
+    }
+
+    /// Parses a StreamMuxConfig.
+    fn parse<R: io::Read + io::Seek>(reader: &mut BitReader<R>) -> io::Result<Self> {
+        let audio_mux_version = reader.read_bit()? as u8;
+        let audio_mux_version_a = if audio_mux_version == 1 { reader.read_bit()? as u8 } else { 0 };
+
+        if audio_mux_version_a != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "audioMuxVersionA 1 is not supported",
+            ));
+        }
+
+        let tara_buffer_fullness = if audio_mux_version == 1 {
+            Some(latm_get_value(reader)?)
+        } else {
+            None
+        };
+
+        let all_streams_same_time_framing = reader.read_bit()?;
+        let num_sub_frames = reader.read_bits(6)? as u8;
+        let num_program = reader.read_bits(4)? as usize;
+
+        let mut programs = Vec::with_capacity(num_program + 1);
+        let mut previous_config: Option<AudioSpecificConfig> = None;
+        let mut previous_object_type = None;
+
+        for _ in 0..=num_program {
+            let num_layer = reader.read_bits(3)? as usize;
+            let mut layers = Vec::with_capacity(num_layer + 1);
+
+            for _ in 0..=num_layer {
+                let use_same_config = match &previous_config {
+                    None => false,
+                    Some(_) => reader.read_bit()?,
+                };
+
+                let audio_specific_config = match previous_config.take() {
+                    Some(config) if use_same_config => config,
+                    _ if audio_mux_version == 0 => AudioSpecificConfig::parse_bits(reader, None)?,
+                    _ => {
+                        let asc_len = latm_get_value(reader)? as u64;
+                        let start = reader.bit_stream_position()?;
+                        let config = AudioSpecificConfig::parse_bits(reader, Some(asc_len))?;
+                        let used = reader.bit_stream_position()? - start;
+                        let fill_bits = asc_len.checked_sub(used).ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::InvalidData, "AudioSpecificConfig exceeds its signalled length")
+                        })?;
+                        reader.seek_bits(fill_bits as i64)?;
+                        config
+                    }
+                };
+
+                let frame_length_type = reader.read_bits(3)? as u8;
+                let mut latm_buffer_fullness = None;
+                let mut core_frame_offset = None;
+                let mut frame_length = None;
+
+                match frame_length_type {
+                    0 => {
+                        latm_buffer_fullness = Some(reader.read_bits(8)? as u8);
+                        if !all_streams_same_time_framing
+                            && matches!(
+                                audio_specific_config.audio_object_type,
+                                AudioObjectType::AacScalable | AudioObjectType::ErAacScalable
+                            )
+                            && matches!(previous_object_type, Some(8 | 24))
+                        {
+                            core_frame_offset = Some(reader.read_bits(6)? as u8);
+                        }
+                    }
+                    1 => frame_length = Some(reader.read_bits(9)? as u16),
+                    3..=5 => {
+                        // CELPframeLengthTableIndex
+                        reader.read_bits(6)?;
+                    }
+                    6 | 7 => {
+                        // HVXCframeLengthTableIndex
+                        reader.read_bits(1)?;
+                    }
+                    _ => {}
+                }
+
+                previous_object_type = Some(audio_specific_config.audio_object_type.as_u16());
+                previous_config = Some(audio_specific_config.clone());
+
+                layers.push(LatmLayer {
+                    audio_specific_config,
+                    frame_length_type,
+                    latm_buffer_fullness,
+                    core_frame_offset,
+                    frame_length,
+                });
+            }
+
+            programs.push(LatmProgram { layers });
+        }
+
+        let other_data_len_bits = if reader.read_bit()? {
+            if audio_mux_version == 1 {
+                Some(latm_get_value(reader)?)
+            } else {
+                let mut other_data_len_bits = 0u32;
+                loop {
+                    let other_data_len_esc = reader.read_bit()?;
+                    other_data_len_bits = other_data_len_bits
+                        .checked_mul(1 << 8)
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "other data length overflow"))?
+                        + reader.read_bits(8)? as u32;
+                    if !other_data_len_esc {
+                        break;
+                    }
+                }
+                Some(other_data_len_bits)
+            }
+        } else {
+            None
+        };
+
+        let crc_checksum = if reader.read_bit()? {
+            Some(reader.read_bits(8)? as u8)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            audio_mux_version,
+            tara_buffer_fullness,
+            all_streams_same_time_framing,
+            num_sub_frames,
+            programs,
+            other_data_len_bits,
+            crc_checksum,
+        })
+    }
+}
+
+/// ISO/IEC 14496-3:2019(E) - 1.7.3.1 (Table 1.43)
+fn latm_get_value<R: io::Read>(reader: &mut BitReader<R>) -> io::Result<u32> {
+    let bytes_for_value = reader.read_bits(2)?;
+    let mut value = 0;
+    for _ in 0..=bytes_for_value {
+        value = (value << 8) | reader.read_bits(8)? as u32;
+    }
+
+    Ok(value)
+}
+
+/// A parsed Audio Mux Element.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.7.3.1 (Table 1.41)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioMuxElement {
+    /// If this element carried a new [`StreamMuxConfig`] which differs from the
+    /// previous one.
+    pub config_changed: bool,
+    /// The payloads of each sub frame, indexed by stream id (the layers of
+    /// every program in order).
+    ///
+    /// For AAC streams each payload is a raw AAC access unit.
+    pub sub_frames: Vec<Vec<Bytes>>,
+}
+
+/// A stateful parser for LATM / LOAS streams.
+///
+/// The parser keeps the last received [`StreamMuxConfig`] so that elements
+/// which reuse it can be parsed.
+#[derive(Debug, Clone, Default)]
+pub struct LatmParser {
+    stream_mux_config: Option<StreamMuxConfig>,
+}
+
+impl LatmParser {
+    /// Creates a new parser without any config.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current [`StreamMuxConfig`], if one has been received.
+    pub fn stream_mux_config(&self) -> Option<&StreamMuxConfig> {
+        self.stream_mux_config.as_ref()
+    }
+
+    /// Sets the [`StreamMuxConfig`] used for elements which do not carry one,
+    /// for example when it is signalled out of band.
+    pub fn set_stream_mux_config(&mut self, config: StreamMuxConfig) {
+        self.stream_mux_config = Some(config);
+    }
+
+    /// Parses a single LOAS `AudioSyncStream` frame and the audio mux element
+    /// within it.
+    ///
+    /// ISO/IEC 14496-3:2019(E) - 1.7.2 (Table 1.36)
+    pub fn parse_loas_frame(&mut self, reader: &mut io::Cursor<Bytes>) -> io::Result<AudioMuxElement> {
+        if reader.remaining() < 3 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "LOAS frame header truncated"));
+        }
+
+        let header = reader.get_uint(3);
+        if header >> 13 != SYNC_WORD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid LOAS sync word"));
+        }
+
+        let audio_mux_length_bytes = (header & 0x1FFF) as usize;
+        if reader.remaining() < audio_mux_length_bytes {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "LOAS frame truncated"));
+        }
+
+        let element = reader.copy_to_bytes(audio_mux_length_bytes);
+        self.parse_audio_mux_element(&element, true)
+    }
+
+    /// Parses an audio mux element.
+    ///
+    /// `mux_config_present` must be true if the element may carry an in band
+    /// [`StreamMuxConfig`], which is always the case for LOAS.
+    pub fn parse_audio_mux_element(&mut self, data: &[u8], mux_config_present: bool) -> io::Result<AudioMuxElement> {
+        let mut reader = BitReader::new_from_slice(data);
+        let mut config_changed = false;
+
+        if mux_config_present && !reader.read_bit()? {
+            let config = StreamMuxConfig::parse(&mut reader)?;
+            config_changed = self.stream_mux_config.as_ref() != Some(&config);
+            self.stream_mux_config = Some(config);
+        }
+
+        let config = self
+            .stream_mux_config
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing StreamMuxConfig"))?;
+
+        if !config.all_streams_same_time_framing {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "LATM streams without same time framing are not supported",
+            ));
+        }
+
+        let mut sub_frames = Vec::with_capacity(config.num_sub_frames as usize + 1);
+        for _ in 0..=config.num_sub_frames {
+            // PayloadLengthInfo()
+            let lengths = config
+                .layers()
+                .map(|layer| match layer.frame_length_type {
+                    0 => {
+                        let mut mux_slot_length_bytes = 0;
+                        loop {
+                            let tmp = reader.read_bits(8)? as usize;
+                            mux_slot_length_bytes += tmp;
+                            if tmp != 255 {
+                                break;
+                            }
+                        }
+                        Ok(mux_slot_length_bytes)
+                    }
+                    1 => Ok(layer.frame_length.unwrap_or_default() as usize + 20),
+                    ty => Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!("unsupported LATM frame length type: {ty}"),
+                    )),
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+
+            // PayloadMux()
+            let payloads = lengths
+                .into_iter()
+                .map(|length| {
+                    let mut payload = vec![0; length];
+                    io::Read::read_exact(&mut reader, &mut payload)?;
+                    Ok(Bytes::from(payload))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+
+            sub_frames.push(payloads);
+        }
+
+        Ok(AudioMuxElement {
+            config_changed,
+            sub_frames,
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use scuffle_bytes_util::BitWriter;
+
+    use super::*;
+    use crate::{SbrConfig, SbrSignalling};
+
+    fn write_audio_mux_element(config: Option<&AudioSpecificConfig>, audio_mux_version: u8, payloads: &[&[u8]]) -> Vec<u8> {
+        let mut writer = BitWriter::new(Vec::new());
+
+        match config {
+            Some(config) => {
+                writer.write_bit(false).unwrap(); // useSameStreamMux
+
+                // StreamMuxConfig
+                writer.write_bit(audio_mux_version == 1).unwrap();
+                if audio_mux_version == 1 {
+                    writer.write_bit(false).unwrap(); // audioMuxVersionA
+                    // taraBufferFullness = 0xFF
+                    writer.write_bits(0, 2).unwrap();
+                    writer.write_bits(0xFF, 8).unwrap();
+                }
+                writer.write_bit(true).unwrap(); // allStreamsSameTimeFraming
+                writer.write_bits(payloads.len() as u64 - 1, 6).unwrap(); // numSubFrames
+                writer.write_bits(0, 4).unwrap(); // numProgram
+                writer.write_bits(0, 3).unwrap(); // numLayer
+
+                let mut asc = Vec::new();
+                config.build(&mut asc).unwrap();
+                if audio_mux_version == 1 {
+                    // ascLen, including the byte alignment padding as fill bits
+                    writer.write_bits(0, 2).unwrap();
+                    writer.write_bits(asc.len() as u64 * 8, 8).unwrap();
+                }
+                for byte in &asc {
+                    writer.write_bits(*byte as u64, 8).unwrap();
+                }
+
+                writer.write_bits(0, 3).unwrap(); // frameLengthType
+                writer.write_bits(0xFF, 8).unwrap(); // latmBufferFullness
+                writer.write_bit(false).unwrap(); // otherDataPresent
+                writer.write_bit(false).unwrap(); // crcCheckPresent
+            }
+            None => writer.write_bit(true).unwrap(), // useSameStreamMux
+        }
+
+        for payload in payloads {
+            let mut len = payload.len();
+            while len >= 255 {
+                writer.write_bits(255, 8).unwrap();
+                len -= 255;
+            }
+            writer.write_bits(len as u64, 8).unwrap();
+            for byte in *payload {
+                writer.write_bits(*byte as u64, 8).unwrap();
+            }
+        }
+
+        writer.finish().unwrap()
+    }
+
+    fn loas_frame(element: &[u8]) -> Vec<u8> {
+        let header = (SYNC_WORD << 13) | element.len() as u64;
+        let mut frame = header.to_be_bytes()[5..].to_vec();
+        frame.extend_from_slice(element);
+        frame
+    }
+
+    #[test]
+    fn test_latm_parse() {
+        let config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 48000, 2);
+        let long_payload = vec![0xAB; 300];
+
+        let mut parser = LatmParser::new();
+
+        let element = write_audio_mux_element(Some(&config), 0, &[b"first", &long_payload]);
+        let parsed = parser.parse_audio_mux_element(&element, true).unwrap();
+        assert!(parsed.config_changed);
+        assert_eq!(
+            parsed.sub_frames,
+            vec![vec![Bytes::from_static(b"first")], vec![Bytes::from(long_payload.clone())]]
+        );
+
+        let stream_mux_config = parser.stream_mux_config().unwrap();
+        assert_eq!(stream_mux_config.audio_mux_version, 0);
+        assert_eq!(stream_mux_config.num_sub_frames, 1);
+        assert_eq!(stream_mux_config.layers().count(), 1);
+        assert_eq!(stream_mux_config.programs[0].layers[0].audio_specific_config, config);
+        assert_eq!(stream_mux_config.programs[0].layers[0].latm_buffer_fullness, Some(0xFF));
+
+        // Reuse the config
+        let element = write_audio_mux_element(None, 0, &[b"third", b"fourth"]);
+        let parsed = parser.parse_audio_mux_element(&element, true).unwrap();
+        assert!(!parsed.config_changed);
+        assert_eq!(
+            parsed.sub_frames,
+            vec![vec![Bytes::from_static(b"third")], vec![Bytes::from_static(b"fourth")]]
+        );
+    }
+
+    #[test]
+    fn test_latm_parse_version_1_with_sbr() {
+        let mut config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 24000, 2);
+        config.sbr = Some(SbrConfig {
+            signalling: SbrSignalling::BackwardCompatible,
+            sbr_present: true,
+            ps_present: false,
+            sampling_frequency: Some(48000),
+            extension_channel_configuration: None,
+        });
+
+        let element = write_audio_mux_element(Some(&config), 1, &[b"payload"]);
+
+        let mut parser = LatmParser::new();
+        let parsed = parser
+            .parse_loas_frame(&mut io::Cursor::new(Bytes::from(loas_frame(&element))))
+            .unwrap();
+        assert_eq!(parsed.sub_frames, vec![vec![Bytes::from_static(b"payload")]]);
+
+        let stream_mux_config = parser.stream_mux_config().unwrap();
+        assert_eq!(stream_mux_config.audio_mux_version, 1);
+        assert_eq!(stream_mux_config.tara_buffer_fullness, Some(0xFF));
+
+        let parsed_config = &stream_mux_config.programs[0].layers[0].audio_specific_config;
+        assert_eq!(parsed_config, &config);
+        assert_eq!(parsed_config.output_sampling_frequency(), 48000);
+    }
+
+    #[test]
+    fn test_latm_missing_config() {
+        let element = write_audio_mux_element(None, 0, &[b"payload"]);
+        assert!(LatmParser::new().parse_audio_mux_element(&element, true).is_err());
+    }
+
+    #[test]
+    fn test_loas_invalid() {
+        let mut parser = LatmParser::new();
+        assert!(
+            parser
+                .parse_loas_frame(&mut io::Cursor::new(Bytes::from_static(&[0x56])))
+                .is_err()
+        );
+        assert!(
+            parser
+                .parse_loas_frame(&mut io::Cursor::new(Bytes::from_static(&[0x00, 0x00, 0x01, 0x00])))
+                .is_err()
+        );
+        // valid sync word, but length exceeds the data
+        assert!(
+            parser
+                .parse_loas_frame(&mut io::Cursor::new(Bytes::from_static(&[0x56, 0xE0, 0x05, 0x00])))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::io;
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use scuffle_bytes_util::{BitReader, BitWriter};
+
+/// Sampling Frequency Index
+///
+/// The purpose of the FrequencyIndex is to encode commonly used frequencies in
+/// 4 bits to save space. These are the set of commonly used frequencies defined
+/// in the specification.
+///
+/// ISO/IEC 14496-3:2019(E) - 1.6.2.4 (Table 1.22)
+#[derive(FromPrimitive, Debug, Clone, PartialEq, Copy, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+#[must_use]
+pub enum SampleFrequencyIndex {
+    /// 96000 Hz
+    Freq96000 = 0x0,
+    /// 88200 Hz
+    Freq88200 = 0x1,
+    /// 64000 Hz
+    Freq64000 = 0x2,
+    /// 48000 Hz
+    Freq48000 = 0x3,
+    /// 44100 Hz
+    Freq44100 = 0x4,
+    /// 32000 Hz
+    Freq32000 = 0x5,
+    /// 24000 Hz
+    Freq24000 = 0x6,
+    /// 22050 Hz
+    Freq22050 = 0x7,
+    /// 16000 Hz
+    Freq16000 = 0x8,
+    /// 12000 Hz
+    Freq12000 = 0x9,
+    /// 11025 Hz
+    Freq11025 = 0xA,
+    /// 8000 Hz
+    Freq8000 = 0xB,
+    /// 7350 Hz
+    Freq7350 = 0xC,
+    /// Reserved
+    FreqReserved = 0xD,
+    /// Reserved
+    FreqReserved2 = 0xE,
+    /// Escape (Meaning the frequency is not in the table, and we need to read
+    /// an additional 24 bits to get the frequency)
+    FreqEscape = 0xF,
+}
+
+impl SampleFrequencyIndex {
+    /// Convert the SampleFrequencyIndex to the actual frequency in Hz
+    pub const fn to_freq(&self) -> Option<u32> {
+        match self {
+            SampleFrequencyIndex::Freq96000 => Some(96000),
+            SampleFrequencyIndex::Freq88200 => Some(88200),
+            SampleFrequencyIndex::Freq64000 => Some(64000),
+            SampleFrequencyIndex::Freq48000 => Some(48000),
+            SampleFrequencyIndex::Freq44100 => Some(44100),
+            SampleFrequencyIndex::Freq32000 => Some(32000),
+            SampleFrequencyIndex::Freq24000 => Some(24000),
+            SampleFrequencyIndex::Freq22050 => Some(22050),
+            SampleFrequencyIndex::Freq16000 => Some(16000),
+            SampleFrequencyIndex::Freq12000 => Some(12000),
+            SampleFrequencyIndex::Freq11025 => Some(11025),
+            SampleFrequencyIndex::Freq8000 => Some(8000),
+            SampleFrequencyIndex::Freq7350 => Some(7350),
+            SampleFrequencyIndex::FreqReserved => None,
+            SampleFrequencyIndex::FreqReserved2 => None,
+            SampleFrequencyIndex::FreqEscape => None,
+        }
+    }
+
+    /// Convert a frequency in Hz to the matching SampleFrequencyIndex.
+    ///
+    /// Returns `None` if the frequency is not part of the table, in which case
+    /// it must be signalled using [`SampleFrequencyIndex::FreqEscape`].
+    pub const fn from_freq(freq: u32) -> Option<Self> {
+        match freq {
+            96000 => Some(SampleFrequencyIndex::Freq96000),
+            88200 => Some(SampleFrequencyIndex::Freq88200),
+            64000 => Some(SampleFrequencyIndex::Freq64000),
+            48000 => Some(SampleFrequencyIndex::Freq48000),
+            44100 => Some(SampleFrequencyIndex::Freq44100),
+            32000 => Some(SampleFrequencyIndex::Freq32000),
+            24000 => Some(SampleFrequencyIndex::Freq24000),
+            22050 => Some(SampleFrequencyIndex::Freq22050),
+            16000 => Some(SampleFrequencyIndex::Freq16000),
+            12000 => Some(SampleFrequencyIndex::Freq12000),
+            11025 => Some(SampleFrequencyIndex::Freq11025),
+            8000 => Some(SampleFrequencyIndex::Freq8000),
+            7350 => Some(SampleFrequencyIndex::Freq7350),
+            _ => None,
+        }
+    }
+
+    /// Reads a 4 bit sampling frequency index.
+    pub(crate) fn read<R: io::Read>(reader: &mut BitReader<R>) -> io::Result<Self> {
+        SampleFrequencyIndex::from_u8(reader.read_bits(4)? as u8)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid sampling frequency index"))
+    }
+
+    /// Reads a sampling frequency index, followed by the 24 bit explicit
+    /// frequency if the index is [`SampleFrequencyIndex::FreqEscape`].
+    pub(crate) fn read_freq<R: io::Read>(reader: &mut BitReader<R>) -> io::Result<u32> {
+        match Self::read(reader)? {
+            // Uses the extended sampling frequency to represent the freq as a non-common value
+            SampleFrequencyIndex::FreqEscape => Ok(reader.read_bits(24)? as u32),
+            index => index
+                .to_freq()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid sampling frequency index")),
+        }
+    }
+
+    /// Writes a frequency as a sampling frequency index, using the escape
+    /// value if the frequency is not part of the table.
+    pub(crate) fn write_freq<W: io::Write>(freq: u32, writer: &mut BitWriter<W>) -> io::Result<()> {
+        match Self::from_freq(freq) {
+            Some(index) => writer.write_bits(index as u64, 4),
+            None => {
+                writer.write_bits(SampleFrequencyIndex::FreqEscape as u64, 4)?;
+                writer.write_bits(freq as u64, 24)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idx_to_freq() {
+        let cases = [
+            (SampleFrequencyIndex::FreqEscape, None),
+            (SampleFrequencyIndex::FreqReserved2, None),
+            (SampleFrequencyIndex::FreqReserved, None),
+            (SampleFrequencyIndex::Freq7350, Some(7350)),
+            (SampleFrequencyIndex::Freq8000, Some(8000)),
+            (SampleFrequencyIndex::Freq11025, Some(11025)),
+            (SampleFrequencyIndex::Freq12000, Some(12000)),
+            (SampleFrequencyIndex::Freq16000, Some(16000)),
+            (SampleFrequencyIndex::Freq22050, Some(22050)),
+            (SampleFrequencyIndex::Freq24000, Some(24000)),
+            (SampleFrequencyIndex::Freq32000, Some(32000)),
+            (SampleFrequencyIndex::Freq44100, Some(44100)),
+            (SampleFrequencyIndex::Freq48000, Some(48000)),
+            (SampleFrequencyIndex::Freq64000, Some(64000)),
+            (SampleFrequencyIndex::Freq88200, Some(88200)),
+            (SampleFrequencyIndex::Freq96000, Some(96000)),
+        ];
+
+        for (idx, freq) in cases {
+            assert_eq!(freq, idx.to_freq(), "Expected frequency for {idx:?}");
+            if let Some(freq) = freq {
+                assert_eq!(Some(idx), SampleFrequencyIndex::from_freq(freq), "Expected index for {freq}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_freq_escape_round_trip() {
+        for freq in [44100, 48000, 7350, 12345, 0xFFFFFF] {
+            let mut writer = BitWriter::new(Vec::new());
+            SampleFrequencyIndex::write_freq(freq, &mut writer).unwrap();
+            let data = writer.finish().unwrap();
+
+            let mut reader = BitReader::new_from_slice(data);
+            assert_eq!(SampleFrequencyIndex::read_freq(&mut reader).unwrap(), freq);
+        }
+    }
+
+    #[test]
+    fn test_reserved_index() {
+        let mut reader = BitReader::new_from_slice([0xD0]);
+        assert!(SampleFrequencyIndex::read_freq(&mut reader).is_err());
+    }
+}
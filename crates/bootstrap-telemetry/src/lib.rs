@@ -146,6 +146,10 @@ pub mod opentelemetry;
 /// check returns an error, the endpoint returns `500 Internal Server Error`
 /// along with the error message.
 ///
+/// Override [`health_check`](TelemetryConfig::health_check) with
+/// [`ServiceStates::check`](scuffle_bootstrap::service::ServiceStates::check)
+/// to fail it while any service is still starting or shutting down.
+///
 /// ### `/metrics`
 ///
 /// Metrics endpoint which can be used by Prometheus to scrape metrics.
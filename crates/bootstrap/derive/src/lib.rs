@@ -74,89 +74,24 @@ mod tests {
             )?;
             let ctx_handle = ::scuffle_bootstrap::prelude::scuffle_context::Handler::global();
             let mut shared_global = ::core::option::Option::None;
-            let mut services_vec = ::std::vec::Vec::<
-                ::scuffle_bootstrap::service::NamedFuture<
-                    ::scuffle_bootstrap::prelude::tokio::task::JoinHandle<anyhow::Result<()>>,
-                >,
-            >::new();
             let result = runtime
                 .block_on(async {
                     let global = <MyGlobal as ::scuffle_bootstrap::global::Global>::init(config)
                         .await?;
                     shared_global = ::core::option::Option::Some(global.clone());
+                    let mut runner = ::scuffle_bootstrap::service::ServiceRunner::<
+                        MyGlobal,
+                    >::new(
+                        ::core::clone::Clone::clone(
+                            ::scuffle_bootstrap::service::ServiceStates::global(),
+                        ),
+                    );
                     {
-                        #[doc(hidden)]
-                        async fn spawn_service(
-                            svc: impl ::scuffle_bootstrap::service::Service<MyGlobal>,
-                            global: &::std::sync::Arc<MyGlobal>,
-                            ctx_handle: &::scuffle_bootstrap::prelude::scuffle_context::Handler,
-                            name: &'static str,
-                        ) -> anyhow::Result<
-                            Option<
-                                ::scuffle_bootstrap::service::NamedFuture<
-                                    ::scuffle_bootstrap::prelude::tokio::task::JoinHandle<
-                                        anyhow::Result<()>,
-                                    >,
-                                >,
-                            >,
-                        > {
-                            let name = ::scuffle_bootstrap::service::Service::<
-                                MyGlobal,
-                            >::name(&svc)
-                                .unwrap_or_else(|| name);
-                            if ::scuffle_bootstrap::prelude::anyhow::Context::context(
-                                ::scuffle_bootstrap::service::Service::<
-                                    MyGlobal,
-                                >::enabled(&svc, &global)
-                                    .await,
-                                name,
-                            )? {
-                                Ok(
-                                    Some(
-                                        ::scuffle_bootstrap::service::NamedFuture::new(
-                                            name,
-                                            ::scuffle_bootstrap::prelude::tokio::spawn(
-                                                ::scuffle_bootstrap::service::Service::<
-                                                    MyGlobal,
-                                                >::run(svc, global.clone(), ctx_handle.context()),
-                                            ),
-                                        ),
-                                    ),
-                                )
-                            } else {
-                                Ok(None)
-                            }
-                        }
-                        let res = spawn_service(MyService, &global, &ctx_handle, "MyService")
-                            .await;
-                        if let Some(spawned) = res? {
-                            services_vec.push(spawned);
-                        }
+                        runner.add(&global, MyService, "MyService").await?;
                     }
                     <MyGlobal as ::scuffle_bootstrap::global::Global>::on_services_start(&global)
                         .await?;
-                    let mut remaining = services_vec;
-                    while !remaining.is_empty() {
-                        let ((name, result), _, new_remaining) = ::scuffle_bootstrap::prelude::futures::future::select_all(
-                                remaining,
-                            )
-                            .await;
-                        let result = ::scuffle_bootstrap::prelude::anyhow::Context::context(
-                            ::scuffle_bootstrap::prelude::anyhow::Context::context(
-                                result,
-                                name,
-                            )?,
-                            name,
-                        );
-                        <MyGlobal as ::scuffle_bootstrap::global::Global>::on_service_exit(
-                                &global,
-                                name,
-                                result,
-                            )
-                            .await?;
-                        remaining = new_remaining;
-                    }
-                    ::scuffle_bootstrap::prelude::anyhow::Ok(())
+                    runner.run(&global, ctx_handle).await
                 });
             let ::core::option::Option::Some(global) = shared_global else {
                 return result;
@@ -127,55 +127,28 @@ pub(crate) fn impl_main(input: TokenStream) -> Result<TokenStream, syn::Error> {
 
     let crate_path = &options.crate_path;
 
-    let service_type = quote!(#crate_path::service::Service::<#entry>);
-
     let global_ident = Ident::new("global", Span::mixed_site());
     let ctx_handle_ident = Ident::new("ctx_handle", Span::mixed_site());
-    let services_vec_ident = Ident::new("services_vec", Span::mixed_site());
+    let runner_ident = Ident::new("runner", Span::mixed_site());
     let runtime_ident = Ident::new("runtime", Span::mixed_site());
     let config_ident = Ident::new("config", Span::mixed_site());
     let shared_global_ident = Ident::new("shared_global", Span::mixed_site());
 
-    let handle_type =
-        quote!(#crate_path::service::NamedFuture<#crate_path::prelude::tokio::task::JoinHandle<anyhow::Result<()>>>);
-
     let services = items.iter().filter(|item| item.item_kind == ItemKind::Service).map(|item| {
-		let expr = &item.expr;
-		let cfg_attrs = &item.cfg_attrs;
-
-		let expr = quote_spanned!(Span::mixed_site().located_at(expr.span()) => #expr);
-
-		let stringify_expr = quote! { #expr }.to_string();
-
-		quote_spanned! { expr.span() =>
-			#(#cfg_attrs)*
-			{
-				#[doc(hidden)]
-				async fn spawn_service(
-					svc: impl #service_type,
-					global: &::std::sync::Arc<#entry>,
-					ctx_handle: &#crate_path::prelude::scuffle_context::Handler,
-					name: &'static str,
-				) -> anyhow::Result<Option<#crate_path::service::NamedFuture<#crate_path::prelude::tokio::task::JoinHandle<anyhow::Result<()>>>>> {
-					let name = #service_type::name(&svc).unwrap_or_else(|| name);
-					if #crate_path::prelude::anyhow::Context::context(#service_type::enabled(&svc, &global).await, name)? {
-						Ok(Some(#crate_path::service::NamedFuture::new(
-							name,
-							#crate_path::prelude::tokio::spawn(#service_type::run(svc, global.clone(), ctx_handle.context())),
-						)))
-					} else {
-						Ok(None)
-					}
-				}
-
-				let res = spawn_service(#expr, &#global_ident, &#ctx_handle_ident, #stringify_expr).await;
-
-				if let Some(spawned) = res? {
-					#services_vec_ident.push(spawned);
-				}
-			}
-		}
-	});
+        let expr = &item.expr;
+        let cfg_attrs = &item.cfg_attrs;
+
+        let expr = quote_spanned!(Span::mixed_site().located_at(expr.span()) => #expr);
+
+        let stringify_expr = quote! { #expr }.to_string();
+
+        quote_spanned! { expr.span() =>
+            #(#cfg_attrs)*
+            {
+                #runner_ident.add(&#global_ident, #expr, #stringify_expr).await?;
+            }
+        }
+    });
 
     let entry_as_global = quote_spanned! { entry.span() =>
         <#entry as #crate_path::global::Global>
@@ -196,7 +169,6 @@ pub(crate) fn impl_main(input: TokenStream) -> Result<TokenStream, syn::Error> {
         let #ctx_handle_ident = #crate_path::prelude::scuffle_context::Handler::global();
 
         let mut #shared_global_ident = ::core::option::Option::None;
-        let mut #services_vec_ident = ::std::vec::Vec::<#handle_type>::new();
     };
 
     Ok(quote! {
@@ -213,23 +185,15 @@ pub(crate) fn impl_main(input: TokenStream) -> Result<TokenStream, syn::Error> {
 
                 #shared_global_ident = ::core::option::Option::Some(#global_ident.clone());
 
+                let mut #runner_ident = #crate_path::service::ServiceRunner::<#entry>::new(
+                    ::core::clone::Clone::clone(#crate_path::service::ServiceStates::global()),
+                );
+
                 #(#services)*
 
                 #entry_as_global::on_services_start(&#global_ident).await?;
 
-                let mut remaining = #services_vec_ident;
-
-                while !remaining.is_empty() {
-                    let ((name, result), _, new_remaining) = #crate_path::prelude::futures::future::select_all(remaining).await;
-
-                    let result = #crate_path::prelude::anyhow::Context::context(#crate_path::prelude::anyhow::Context::context(result, name)?, name);
-
-                    #entry_as_global::on_service_exit(&#global_ident, name, result).await?;
-
-                    remaining = new_remaining;
-                }
-
-                #crate_path::prelude::anyhow::Ok(())
+                #runner_ident.run(&#global_ident, #ctx_handle_ident).await
             });
 
             let ::core::option::Option::Some(global) = #shared_global_ident else {
@@ -4,7 +4,8 @@
 ///
 /// The avoid having to manually implement this trait, the `bootstrap!` macro in
 /// the [`scuffle-settings`] crate can be used to
-/// generate an implementation.
+/// generate an implementation. Its `LiveSettings` type can be used as the
+/// config to reload it while the application is running.
 ///
 /// # See Also
 ///
@@ -163,9 +163,12 @@ pub trait Global: Send + Sync + 'static {
     /// Called after a service exits.
     ///
     /// `name` is the name of the service that exited and `result` is the result
-    /// the service exited with. Returning an error from this function will
-    /// stop all currently running services and [`on_exit`](Global::on_exit)
-    /// will be called with the result of this function.
+    /// the service exited with. Services that are never started because a
+    /// dependency stopped before it was ready are reported with an error as
+    /// well. Returning an error from this function gracefully shuts down all
+    /// running services in dependency order, and [`on_exit`](Global::on_exit)
+    /// will be called with the result of this function once they exited.
+    /// Returning an error while shutting down aborts the remaining services.
     #[inline(always)]
     fn on_service_exit(
         self: &Arc<Self>,
@@ -252,9 +255,12 @@ pub trait GlobalWithoutConfig: Send + Sync + 'static {
     /// Called after a service exits.
     ///
     /// `name` is the name of the service that exited and `result` is the result
-    /// the service exited with. Returning an error from this function will
-    /// stop all currently running services and [`on_exit`](Global::on_exit)
-    /// will be called with the result of this function.
+    /// the service exited with. Services that are never started because a
+    /// dependency stopped before it was ready are reported with an error as
+    /// well. Returning an error from this function gracefully shuts down all
+    /// running services in dependency order, and [`on_exit`](Global::on_exit)
+    /// will be called with the result of this function once they exited.
+    /// Returning an error while shutting down aborts the remaining services.
     #[inline(always)]
     fn on_service_exit(
         self: &Arc<Self>,
@@ -49,6 +49,72 @@
 //! # }
 //! ```
 //!
+//! ## Dependencies and readiness
+//!
+//! Services can depend on each other by name. A service is only started once
+//! all of its dependencies are ready, and on shutdown it is stopped before any
+//! of them. Services that need time to become ready call
+//! [`service::ready`] from their `run` function.
+//!
+//! ```rust
+//! # use std::sync::Arc;
+//! # struct Global;
+//! struct Database;
+//!
+//! impl scuffle_bootstrap::service::Service<Global> for Database {
+//!     fn name(&self) -> Option<&'static str> {
+//!         Some("database")
+//!     }
+//!
+//!     fn signals_readiness(&self) -> bool {
+//!         true
+//!     }
+//!
+//!     async fn run(self, global: Arc<Global>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+//!         # let _ = global;
+//!         // Connect and run migrations
+//!
+//!         scuffle_bootstrap::service::ready();
+//!         ctx.done().await;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct HttpServer;
+//!
+//! impl scuffle_bootstrap::service::Service<Global> for HttpServer {
+//!     fn dependencies(&self) -> &[&'static str] {
+//!         &["database"]
+//!     }
+//!
+//!     fn shutdown_timeout(&self) -> Option<std::time::Duration> {
+//!         Some(std::time::Duration::from_secs(10))
+//!     }
+//! }
+//! ```
+//!
+//! The state of every service is available through
+//! [`ServiceStates::global`](service::ServiceStates::global), which health
+//! checks can use to report whether the application is ready.
+//!
+//! ## Restarting services
+//!
+//! By default, a service that returns an error shuts down the whole
+//! application. Wrapping it in [`Supervised`](service::Supervised) restarts it
+//! instead, with an exponential backoff, until it fails too often within a
+//! window of time.
+//!
+//! ```rust
+//! # struct Worker;
+//! # impl scuffle_bootstrap::service::Service<()> for Worker {}
+//! use scuffle_bootstrap::service::{Restart, Supervised};
+//!
+//! let worker = Supervised::new(|| Worker)
+//!     .restart(Restart::OnFailure)
+//!     .backoff(std::time::Duration::from_millis(500), std::time::Duration::from_secs(30));
+//! # let _ = worker;
+//! ```
+//!
 //! ## License
 //!
 //! This project is licensed under the MIT or Apache-2.0 license.
@@ -81,6 +147,11 @@ pub mod prelude {
 /// and service types. It will run all the services in parallel and wait for
 /// them to finish before exiting.
 ///
+/// Services are started in the order of their
+/// [dependencies](service::Service::dependencies) and shut down in reverse
+/// order once the global [`Handler`](scuffle_context::Handler::global) is
+/// cancelled, see [`ServiceRunner`](service::ServiceRunner).
+///
 /// # Example
 ///
 /// ```rust
@@ -3,6 +3,15 @@
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+mod readiness;
+mod runner;
+mod supervisor;
+
+pub use readiness::{ReadySignal, ServiceState, ServiceStates, current_service, ready, ready_signal};
+pub use runner::ServiceRunner;
+pub use supervisor::{Restart, Supervised};
 
 /// A service that can be run.
 ///
@@ -27,6 +36,37 @@ pub trait Service<Global>: Send + Sync + 'static + Sized {
         std::future::ready(Ok(true))
     }
 
+    /// Returns the names of the services this service depends on.
+    ///
+    /// The service is only started once all of its dependencies are ready and its
+    /// context is only cancelled once it is the last one standing, so it never
+    /// outlives a dependency during a graceful shutdown. Names are matched against
+    /// [`name`](Service::name), or the expression passed to [`main`](crate::main)
+    /// for services without a name.
+    ///
+    /// Dependencies on services that are not [enabled](Service::enabled) are
+    /// ignored. Unknown names and cycles fail the startup.
+    fn dependencies(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Returns `true` if the service signals its readiness by calling [`ready`]
+    /// from [`run`](Service::run).
+    ///
+    /// Otherwise the service is considered ready as soon as it is started.
+    fn signals_readiness(&self) -> bool {
+        false
+    }
+
+    /// How long the service may take to drain after its context is cancelled.
+    ///
+    /// The service is aborted once the deadline passes, which is reported to
+    /// [`Global::on_service_exit`](crate::Global::on_service_exit) as an error.
+    /// Waits for the service indefinitely if `None`.
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        None
+    }
+
     /// Run the service.
     /// This function should return a future that is pending as long as the
     /// service is running. When the service finishes without any errors,
@@ -0,0 +1,209 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+tokio::task_local! {
+    static CURRENT: Current;
+}
+
+/// The service a task belongs to.
+#[derive(Clone)]
+struct Current {
+    name: &'static str,
+    ready: ReadySignal,
+}
+
+/// Runs `fut` as part of the service called `name`.
+pub(crate) async fn scope<F: std::future::Future>(name: &'static str, ready: ReadySignal, fut: F) -> F::Output {
+    CURRENT.scope(Current { name, ready }, fut).await
+}
+
+/// Returns the name of the service that is currently running.
+///
+/// `None` outside of a service started by [`main`](crate::main).
+pub fn current_service() -> Option<&'static str> {
+    CURRENT.try_with(|current| current.name).ok()
+}
+
+/// Marks the service that is currently running as ready.
+///
+/// Services that return `true` from
+/// [`Service::signals_readiness`](super::Service::signals_readiness) call this from
+/// [`Service::run`](super::Service::run) once they are able to do their work, for example after
+/// binding their listener or finishing database migrations. Services depending on them are only
+/// started afterwards.
+///
+/// Calling this more than once, or outside of a service started by [`main`](crate::main), does nothing.
+/// Use [`ready_signal`] to mark the service as ready from another task.
+pub fn ready() {
+    if let Some(signal) = ready_signal() {
+        signal.ready();
+    }
+}
+
+/// Returns the readiness signal of the service that is currently running.
+///
+/// `None` outside of a service started by [`main`](crate::main).
+/// Unlike [`ready`], the returned signal can be moved to other tasks.
+pub fn ready_signal() -> Option<ReadySignal> {
+    CURRENT.try_with(|current| current.ready.clone()).ok()
+}
+
+/// Marks a service as ready, see [`ready_signal`].
+#[derive(Debug, Clone)]
+pub struct ReadySignal {
+    index: usize,
+    fired: Arc<AtomicBool>,
+    tx: tokio::sync::mpsc::UnboundedSender<usize>,
+}
+
+impl ReadySignal {
+    pub(crate) fn new(index: usize, tx: tokio::sync::mpsc::UnboundedSender<usize>) -> Self {
+        Self {
+            index,
+            fired: Arc::new(AtomicBool::new(false)),
+            tx,
+        }
+    }
+
+    /// Marks the service as ready.
+    ///
+    /// Calling this more than once does nothing.
+    pub fn ready(&self) {
+        if !self.fired.swap(true, Ordering::Relaxed) {
+            // The runner is gone when the process is exiting, nobody is waiting for the service then
+            let _ = self.tx.send(self.index);
+        }
+    }
+}
+
+/// The lifecycle state of a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceState {
+    /// The service is waiting for its dependencies to become ready.
+    Waiting,
+    /// The service is running but has not signalled readiness yet.
+    Starting,
+    /// The service is running and ready.
+    Ready,
+    /// The service was asked to shut down and is draining.
+    Stopping,
+    /// The service is no longer running.
+    Stopped,
+}
+
+impl std::fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Waiting => "waiting",
+            Self::Starting => "starting",
+            Self::Ready => "ready",
+            Self::Stopping => "stopping",
+            Self::Stopped => "stopped",
+        })
+    }
+}
+
+/// The states of all services run by a [`ServiceRunner`](super::ServiceRunner).
+///
+/// The [`main`](crate::main) macro reports to [`ServiceStates::global`], which is what health checks
+/// should look at. Cloning is cheap, all clones share the same states.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceStates {
+    states: Arc<RwLock<Vec<Status>>>,
+}
+
+#[derive(Debug)]
+struct Status {
+    name: &'static str,
+    state: ServiceState,
+    /// Whether the service stopped after finishing successfully.
+    succeeded: bool,
+}
+
+impl ServiceStates {
+    /// Creates an empty set of states.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the states of the services run by [`main`](crate::main).
+    pub fn global() -> &'static Self {
+        static GLOBAL: OnceLock<ServiceStates> = OnceLock::new();
+
+        GLOBAL.get_or_init(ServiceStates::new)
+    }
+
+    /// Returns the state of the service called `name`.
+    pub fn get(&self, name: &str) -> Option<ServiceState> {
+        self.states
+            .read()
+            .expect("lock poisoned")
+            .iter()
+            .find(|status| status.name == name)
+            .map(|status| status.state)
+    }
+
+    /// Returns the names and states of all services, in the order they were added.
+    pub fn services(&self) -> Vec<(&'static str, ServiceState)> {
+        self.states
+            .read()
+            .expect("lock poisoned")
+            .iter()
+            .map(|status| (status.name, status.state))
+            .collect()
+    }
+
+    /// Returns `true` if every service is either ready or has finished successfully.
+    pub fn is_ready(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Returns an error naming every service that is not ready.
+    ///
+    /// Services that finished successfully count as ready, so one-off jobs like migrations don't fail the check.
+    /// Services that are shutting down, failed or were never started do not.
+    pub fn check(&self) -> anyhow::Result<()> {
+        let not_ready = self
+            .states
+            .read()
+            .expect("lock poisoned")
+            .iter()
+            .filter(|status| match status.state {
+                ServiceState::Ready => false,
+                ServiceState::Stopped => !status.succeeded,
+                _ => true,
+            })
+            .map(|status| format!("{} ({})", status.name, status.state))
+            .collect::<Vec<_>>();
+
+        if not_ready.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("services not ready: {}", not_ready.join(", "))
+        }
+    }
+
+    pub(crate) fn reset(&self, names: impl IntoIterator<Item = &'static str>) {
+        *self.states.write().expect("lock poisoned") = names
+            .into_iter()
+            .map(|name| Status {
+                name,
+                state: ServiceState::Waiting,
+                succeeded: false,
+            })
+            .collect();
+    }
+
+    pub(crate) fn set(&self, index: usize, state: ServiceState) {
+        let status = &mut self.states.write().expect("lock poisoned")[index];
+        status.state = state;
+        status.succeeded = false;
+    }
+
+    /// Marks the service as stopped after it finished, `succeeded` tells whether it returned `Ok`.
+    pub(crate) fn set_finished(&self, index: usize, succeeded: bool) {
+        let status = &mut self.states.write().expect("lock poisoned")[index];
+        status.state = ServiceState::Stopped;
+        status.succeeded = succeeded;
+    }
+}
@@ -0,0 +1,1030 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use scuffle_context::{Context, Handler};
+use tokio::task::{AbortHandle, JoinError, JoinSet};
+use tokio::time::Instant;
+
+use super::Service;
+use super::readiness::{ReadySignal, ServiceState, ServiceStates, scope};
+use crate::global::Global;
+
+type BoxFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>;
+type RunFn<G> = Box<dyn FnOnce(Arc<G>, Context) -> BoxFuture + Send>;
+
+/// A service that was added to a [`ServiceRunner`].
+struct Entry<G> {
+    name: &'static str,
+    dependencies: Vec<&'static str>,
+    signals_readiness: bool,
+    shutdown_timeout: Option<Duration>,
+    run: RunFn<G>,
+}
+
+/// Runs services in the order of their dependencies.
+///
+/// Services are started once all of their [dependencies](Service::dependencies) are
+/// [ready](super::ready) and are shut down in reverse order once the handler passed to
+/// [`run`](ServiceRunner::run) is cancelled: a service's context is only cancelled after all services
+/// depending on it have exited.
+///
+/// Every service gets a context of its own. The handler passed to [`run`](ServiceRunner::run) is not
+/// done before every service has exited, so [`Handler::shutdown`] waits for all services to drain.
+///
+/// This is what [`main`](crate::main) uses, you only need it to run services yourself.
+pub struct ServiceRunner<G> {
+    services: Vec<Entry<G>>,
+    disabled: Vec<&'static str>,
+    states: ServiceStates,
+}
+
+impl<G: Global> ServiceRunner<G> {
+    /// Creates a runner that reports the state of its services to `states`.
+    pub fn new(states: ServiceStates) -> Self {
+        Self {
+            services: Vec::new(),
+            disabled: Vec::new(),
+            states,
+        }
+    }
+
+    /// Adds a service if it is [enabled](Service::enabled).
+    ///
+    /// `name` is used if the service does not have a [name](Service::name) of its own.
+    pub async fn add(&mut self, global: &Arc<G>, service: impl Service<G>, name: &'static str) -> anyhow::Result<()> {
+        let name = service.name().unwrap_or(name);
+
+        if !service.enabled(global).await.context(name)? {
+            self.disabled.push(name);
+            return Ok(());
+        }
+
+        self.services.push(Entry {
+            name,
+            dependencies: service.dependencies().to_vec(),
+            signals_readiness: service.signals_readiness(),
+            shutdown_timeout: service.shutdown_timeout(),
+            run: Box::new(move |global, ctx| Box::pin(service.run(global, ctx))),
+        });
+
+        Ok(())
+    }
+
+    /// Runs all services until they exit.
+    ///
+    /// Fails before starting any service if a dependency is unknown or the dependencies form a cycle.
+    /// Every exit is passed to [`Global::on_service_exit`]. If that returns an error, `handler` is
+    /// cancelled to shut down the remaining services and the error is returned once they exited.
+    /// An error returned while shutting down aborts all remaining services right away.
+    pub async fn run(self, global: &Arc<G>, handler: &Handler) -> anyhow::Result<()> {
+        let dependencies = resolve(&self.services, &self.disabled)?;
+        let mut dependents = vec![Vec::new(); dependencies.len()];
+        for (index, dependencies) in dependencies.iter().enumerate() {
+            for &dependency in dependencies {
+                dependents[dependency].push(index);
+            }
+        }
+
+        self.states.reset(self.services.iter().map(|service| service.name));
+
+        let (ready_tx, ready_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut lifecycle = Lifecycle {
+            slots: self.services.into_iter().map(Slot::new).collect(),
+            dependencies,
+            dependents,
+            states: self.states,
+            tasks: JoinSet::new(),
+            ready_tx,
+            ready_rx,
+            shutting_down: false,
+            error: None,
+        };
+
+        lifecycle.run(global, handler).await
+    }
+}
+
+/// Resolves the dependencies of every service to indices, failing on unknown names and cycles.
+fn resolve<G>(services: &[Entry<G>], disabled: &[&'static str]) -> anyhow::Result<Vec<Vec<usize>>> {
+    let mut resolved = Vec::with_capacity(services.len());
+
+    for service in services {
+        let mut indices = Vec::new();
+        for dependency in &service.dependencies {
+            let len = indices.len();
+            indices.extend(
+                services
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| other.name == *dependency)
+                    .map(|(index, _)| index),
+            );
+
+            // Dependencies on disabled services are satisfied, as nothing is going to run them
+            if indices.len() == len && !disabled.contains(dependency) {
+                anyhow::bail!("service {} depends on unknown service {dependency}", service.name);
+            }
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+        resolved.push(indices);
+    }
+
+    // Kahn's algorithm, whatever is left over is part of or depends on a cycle
+    let mut remaining = resolved.iter().map(Vec::len).collect::<Vec<_>>();
+    let mut queue = (0..services.len()).filter(|&index| remaining[index] == 0).collect::<Vec<_>>();
+    while let Some(index) = queue.pop() {
+        for (dependent, dependencies) in resolved.iter().enumerate() {
+            if dependencies.contains(&index) {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    queue.push(dependent);
+                }
+            }
+        }
+    }
+
+    let cycle = services
+        .iter()
+        .zip(&remaining)
+        .filter(|(_, remaining)| **remaining > 0)
+        .map(|(service, _)| service.name)
+        .collect::<Vec<_>>();
+    if !cycle.is_empty() {
+        anyhow::bail!("dependency cycle between services: {}", cycle.join(", "));
+    }
+
+    Ok(resolved)
+}
+
+/// The runtime state of a service.
+struct Slot<G> {
+    name: &'static str,
+    signals_readiness: bool,
+    shutdown_timeout: Option<Duration>,
+    run: Option<RunFn<G>>,
+    state: ServiceState,
+    /// Whether the service signalled readiness or finished successfully, which is what dependents wait for.
+    ready: bool,
+    handler: Option<Handler>,
+    /// A context of the handler passed to [`ServiceRunner::run`], held while the service is running.
+    guard: Option<Context>,
+    task: Option<AbortHandle>,
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl<G> Slot<G> {
+    fn new(entry: Entry<G>) -> Self {
+        Self {
+            name: entry.name,
+            signals_readiness: entry.signals_readiness,
+            shutdown_timeout: entry.shutdown_timeout,
+            run: Some(entry.run),
+            state: ServiceState::Waiting,
+            ready: false,
+            handler: None,
+            guard: None,
+            task: None,
+            deadline: None,
+            timed_out: false,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(
+            self.state,
+            ServiceState::Starting | ServiceState::Ready | ServiceState::Stopping
+        )
+    }
+}
+
+struct Lifecycle<G> {
+    slots: Vec<Slot<G>>,
+    dependencies: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+    states: ServiceStates,
+    tasks: JoinSet<anyhow::Result<()>>,
+    ready_tx: tokio::sync::mpsc::UnboundedSender<usize>,
+    ready_rx: tokio::sync::mpsc::UnboundedReceiver<usize>,
+    shutting_down: bool,
+    /// The error that started the shutdown.
+    error: Option<anyhow::Error>,
+}
+
+impl<G: Global> Lifecycle<G> {
+    async fn run(&mut self, global: &Arc<G>, handler: &Handler) -> anyhow::Result<()> {
+        let result = self.run_inner(global, handler).await;
+
+        if result.is_err() {
+            self.tasks.abort_all();
+            for index in 0..self.slots.len() {
+                self.set_state(index, ServiceState::Stopped);
+            }
+        }
+
+        result
+    }
+
+    async fn run_inner(&mut self, global: &Arc<G>, handler: &Handler) -> anyhow::Result<()> {
+        // Held until the shutdown starts, so we notice the handler being cancelled
+        let mut shutdown = Some(handler.context());
+
+        self.start_ready(global, handler).await?;
+
+        while !self.tasks.is_empty() {
+            let deadline = self.slots.iter().filter_map(|slot| slot.deadline).min();
+
+            tokio::select! {
+                Some(result) = self.tasks.join_next_with_id() => {
+                    let (index, result) = self.finished(result);
+                    self.exited(global, handler, index, result).await?;
+
+                    if self.shutting_down {
+                        self.stop_ready();
+                    } else {
+                        self.start_ready(global, handler).await?;
+                    }
+                }
+                Some(index) = self.ready_rx.recv() => {
+                    if self.slots[index].state == ServiceState::Starting {
+                        self.slots[index].ready = true;
+                        self.set_state(index, ServiceState::Ready);
+                        self.start_ready(global, handler).await?;
+                    }
+                }
+                _ = async { shutdown.as_ref().expect("shutdown context").done().await }, if shutdown.is_some() => {
+                    shutdown = None;
+                    if !self.shutting_down {
+                        self.shutdown();
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    let now = Instant::now();
+                    for slot in &mut self.slots {
+                        if slot.deadline.is_some_and(|deadline| deadline <= now) {
+                            slot.deadline = None;
+                            slot.timed_out = true;
+                            if let Some(task) = &slot.task {
+                                task.abort();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Stops starting services and cancels the ones nothing depends on anymore.
+    fn shutdown(&mut self) {
+        self.shutting_down = true;
+
+        // Services that never started are not going to start anymore
+        for index in 0..self.slots.len() {
+            if self.slots[index].state == ServiceState::Waiting {
+                self.set_state(index, ServiceState::Stopped);
+            }
+        }
+
+        self.stop_ready();
+    }
+
+    /// Passes the exit of a service to [`Global::on_service_exit`], shutting down if that fails.
+    async fn exited(
+        &mut self,
+        global: &Arc<G>,
+        handler: &Handler,
+        index: usize,
+        result: anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let Err(err) = global.on_service_exit(self.slots[index].name, result).await else {
+            return Ok(());
+        };
+
+        if self.shutting_down {
+            return Err(err);
+        }
+
+        self.error = Some(err);
+        handler.cancel();
+        self.shutdown();
+
+        Ok(())
+    }
+
+    fn set_state(&mut self, index: usize, state: ServiceState) {
+        self.slots[index].state = state;
+        self.states.set(index, state);
+    }
+
+    /// Records the exit of a task, returning the index and result of its service.
+    fn finished(&mut self, result: Result<(tokio::task::Id, anyhow::Result<()>), JoinError>) -> (usize, anyhow::Result<()>) {
+        let id = match &result {
+            Ok((id, _)) => *id,
+            Err(err) => err.id(),
+        };
+
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| slot.task.as_ref().is_some_and(|task| task.id() == id))
+            .expect("task of an unknown service");
+
+        let slot = &mut self.slots[index];
+        let result = match result {
+            Ok((_, result)) => result,
+            Err(err) if err.is_cancelled() && slot.timed_out => Err(anyhow::anyhow!(
+                "did not shut down within {:?}",
+                slot.shutdown_timeout.unwrap_or_default()
+            )),
+            Err(err) => Err(err.into()),
+        };
+
+        // A service that finished its work is as good as ready, this is what one-off jobs like migrations do
+        slot.ready |= result.is_ok();
+        slot.task = None;
+        slot.deadline = None;
+        // Contexts the service left behind are cancelled
+        if let Some(handler) = slot.handler.take() {
+            handler.cancel();
+        }
+        slot.guard = None;
+        slot.state = ServiceState::Stopped;
+        self.states.set_finished(index, result.is_ok());
+
+        (index, result.context(self.slots[index].name))
+    }
+
+    /// Starts every waiting service whose dependencies are ready.
+    ///
+    /// Services whose dependencies stopped without becoming ready are never started, their exit is
+    /// reported as an error.
+    async fn start_ready(&mut self, global: &Arc<G>, handler: &Handler) -> anyhow::Result<()> {
+        while !self.shutting_down {
+            let mut changed = false;
+
+            for index in 0..self.slots.len() {
+                if self.shutting_down || self.slots[index].state != ServiceState::Waiting {
+                    continue;
+                }
+
+                let failed = self.dependencies[index]
+                    .iter()
+                    .map(|&dependency| &self.slots[dependency])
+                    .find(|dependency| dependency.state == ServiceState::Stopped && !dependency.ready);
+
+                if let Some(dependency) = failed {
+                    let err = anyhow::anyhow!("dependency {} stopped before it was ready", dependency.name);
+                    self.set_state(index, ServiceState::Stopped);
+                    let result = Err(err).context(self.slots[index].name);
+                    self.exited(global, handler, index, result).await?;
+                    changed = true;
+                } else if self.dependencies[index]
+                    .iter()
+                    .all(|&dependency| self.slots[dependency].ready)
+                {
+                    self.start(index, global, handler);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self, index: usize, global: &Arc<G>, handler: &Handler) {
+        let service_handler = Handler::new();
+        let ctx = service_handler.context();
+
+        let slot = &mut self.slots[index];
+        let run = slot.run.take().expect("service started twice");
+        let signal = ReadySignal::new(index, self.ready_tx.clone());
+        let task = self.tasks.spawn(scope(slot.name, signal, run(global.clone(), ctx)));
+
+        slot.handler = Some(service_handler);
+        // Keeps the handler from being done until the service exited
+        slot.guard = Some(handler.context());
+        slot.task = Some(task);
+        slot.ready = !slot.signals_readiness;
+
+        let state = if slot.ready {
+            ServiceState::Ready
+        } else {
+            ServiceState::Starting
+        };
+        self.set_state(index, state);
+    }
+
+    /// Cancels every running service that no running service depends on anymore.
+    fn stop_ready(&mut self) {
+        for index in 0..self.slots.len() {
+            if !matches!(self.slots[index].state, ServiceState::Starting | ServiceState::Ready) {
+                continue;
+            }
+
+            if self.dependents[index]
+                .iter()
+                .any(|&dependent| self.slots[dependent].is_running())
+            {
+                continue;
+            }
+
+            let slot = &mut self.slots[index];
+            if let Some(handler) = &slot.handler {
+                handler.cancel();
+            }
+            slot.deadline = slot.shutdown_timeout.map(|timeout| Instant::now() + timeout);
+            self.set_state(index, ServiceState::Stopping);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use scuffle_context::Handler;
+    use scuffle_future_ext::FutureExt;
+
+    use super::ServiceRunner;
+    use crate::GlobalWithoutConfig;
+    use crate::service::{Service, ServiceState, ServiceStates};
+
+    #[derive(Default)]
+    struct TestGlobal {
+        events: Mutex<Vec<String>>,
+        propagate_errors: bool,
+    }
+
+    impl TestGlobal {
+        fn event(&self, event: impl Into<String>) {
+            self.events.lock().unwrap().push(event.into());
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    impl GlobalWithoutConfig for TestGlobal {
+        async fn init() -> anyhow::Result<Arc<Self>> {
+            Ok(Arc::new(Self::default()))
+        }
+
+        async fn on_service_exit(self: &Arc<Self>, name: &'static str, result: anyhow::Result<()>) -> anyhow::Result<()> {
+            match result {
+                Ok(()) => self.event(format!("exit {name}")),
+                Err(err) => {
+                    self.event(format!("exit {name}: {}", err.root_cause()));
+                    if self.propagate_errors {
+                        return Err(err);
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct TestSvc {
+        name: &'static str,
+        dependencies: &'static [&'static str],
+        signals_readiness: bool,
+        shutdown_timeout: Option<Duration>,
+        fail: bool,
+        hang: bool,
+    }
+
+    impl Service<TestGlobal> for TestSvc {
+        fn name(&self) -> Option<&'static str> {
+            Some(self.name)
+        }
+
+        fn dependencies(&self) -> &[&'static str] {
+            self.dependencies
+        }
+
+        fn signals_readiness(&self) -> bool {
+            self.signals_readiness
+        }
+
+        fn shutdown_timeout(&self) -> Option<Duration> {
+            self.shutdown_timeout
+        }
+
+        async fn run(self, global: Arc<TestGlobal>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+            global.event(format!("start {}", self.name));
+
+            if self.fail {
+                anyhow::bail!("failed");
+            }
+
+            if self.signals_readiness {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                global.event(format!("ready {}", self.name));
+                crate::service::ready();
+            }
+
+            ctx.done().await;
+            global.event(format!("stop {}", self.name));
+
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+
+            Ok(())
+        }
+    }
+
+    async fn runner(global: &Arc<TestGlobal>, services: Vec<TestSvc>) -> ServiceRunner<TestGlobal> {
+        let mut runner = ServiceRunner::new(ServiceStates::new());
+        for service in services {
+            runner.add(global, service, "unnamed").await.unwrap();
+        }
+        runner
+    }
+
+    #[tokio::test]
+    async fn ordered_startup_and_shutdown() {
+        let global = TestGlobal::init().await.unwrap();
+        let states = ServiceStates::new();
+        let mut runner = ServiceRunner::new(states.clone());
+
+        for service in [
+            TestSvc {
+                name: "http",
+                dependencies: &["db", "cache"],
+                ..Default::default()
+            },
+            TestSvc {
+                name: "cache",
+                dependencies: &["db"],
+                signals_readiness: true,
+                ..Default::default()
+            },
+            TestSvc {
+                name: "db",
+                signals_readiness: true,
+                ..Default::default()
+            },
+        ] {
+            runner.add(&global, service, "unnamed").await.unwrap();
+        }
+
+        let handler = Handler::new();
+        let run = tokio::spawn({
+            let global = global.clone();
+            let handler = handler.clone();
+            async move { runner.run(&global, &handler).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(states.is_ready());
+        assert_eq!(states.get("http"), Some(ServiceState::Ready));
+
+        handler
+            .shutdown()
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .expect("shutdown timed out");
+        run.await.unwrap().unwrap();
+
+        assert_eq!(
+            global.events(),
+            [
+                "start db",
+                "ready db",
+                "start cache",
+                "ready cache",
+                "start http",
+                "stop http",
+                "exit http",
+                "stop cache",
+                "exit cache",
+                "stop db",
+                "exit db",
+            ]
+        );
+        assert_eq!(states.get("db"), Some(ServiceState::Stopped));
+    }
+
+    /// Returns right away, leaving a task behind that holds its context.
+    struct Detached;
+
+    impl Service<TestGlobal> for Detached {
+        fn name(&self) -> Option<&'static str> {
+            Some("detached")
+        }
+
+        async fn run(self, global: Arc<TestGlobal>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+            tokio::spawn(async move {
+                ctx.done().await;
+                global.event("cancel detached");
+            });
+
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn exited_service_contexts() {
+        let global = TestGlobal::init().await.unwrap();
+        let mut runner = runner(
+            &global,
+            vec![TestSvc {
+                name: "http",
+                ..Default::default()
+            }],
+        )
+        .await;
+        runner.add(&global, Detached, "unnamed").await.unwrap();
+
+        let handler = Handler::new();
+        let run = tokio::spawn({
+            let global = global.clone();
+            let handler = handler.clone();
+            async move { runner.run(&global, &handler).await }
+        });
+
+        // The contexts of a service are cancelled once it exits
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(global.events(), ["start http", "exit detached", "cancel detached"]);
+
+        handler
+            .shutdown()
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .expect("shutdown timed out");
+        run.await.unwrap().unwrap();
+    }
+
+    /// Finishes its work without being cancelled.
+    struct Job;
+
+    impl Service<TestGlobal> for Job {
+        fn name(&self) -> Option<&'static str> {
+            Some("job")
+        }
+
+        async fn run(self, _: Arc<TestGlobal>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(ctx);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_after_exit() {
+        let global = TestGlobal::init().await.unwrap();
+        let mut runner = runner(
+            &global,
+            vec![TestSvc {
+                name: "http",
+                ..Default::default()
+            }],
+        )
+        .await;
+        runner.add(&global, Job, "unnamed").await.unwrap();
+
+        let handler = Handler::new();
+        let run = tokio::spawn({
+            let global = global.clone();
+            let handler = handler.clone();
+            async move { runner.run(&global, &handler).await }
+        });
+
+        // The job dropped its context before anything was cancelled
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(global.events(), ["start http", "exit job"]);
+
+        handler
+            .shutdown()
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .expect("shutdown timed out");
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn readiness_state() {
+        let global = TestGlobal::init().await.unwrap();
+        let states = ServiceStates::new();
+        let mut runner = ServiceRunner::new(states.clone());
+
+        runner
+            .add(
+                &global,
+                TestSvc {
+                    name: "slow",
+                    signals_readiness: true,
+                    ..Default::default()
+                },
+                "unnamed",
+            )
+            .await
+            .unwrap();
+        runner
+            .add(
+                &global,
+                TestSvc {
+                    name: "dependent",
+                    dependencies: &["slow"],
+                    ..Default::default()
+                },
+                "unnamed",
+            )
+            .await
+            .unwrap();
+
+        let handler = Handler::new();
+        let run = tokio::spawn({
+            let global = global.clone();
+            let handler = handler.clone();
+            async move { runner.run(&global, &handler).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            states.check().unwrap_err().to_string(),
+            "services not ready: slow (starting), dependent (waiting)"
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        states.check().unwrap();
+
+        handler.cancel();
+        run.await.unwrap().unwrap();
+        states.check().unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_dependencies() {
+        let global = TestGlobal::init().await.unwrap();
+        let handler = Handler::new();
+
+        let unknown = runner(
+            &global,
+            vec![TestSvc {
+                name: "http",
+                dependencies: &["db"],
+                ..Default::default()
+            }],
+        )
+        .await;
+        assert_eq!(
+            unknown.run(&global, &handler).await.unwrap_err().to_string(),
+            "service http depends on unknown service db"
+        );
+
+        let cycle = runner(
+            &global,
+            vec![
+                TestSvc {
+                    name: "a",
+                    dependencies: &["b"],
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "b",
+                    dependencies: &["a"],
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "c",
+                    ..Default::default()
+                },
+            ],
+        )
+        .await;
+        assert_eq!(
+            cycle.run(&global, &handler).await.unwrap_err().to_string(),
+            "dependency cycle between services: a, b"
+        );
+
+        assert!(global.events().is_empty());
+    }
+
+    struct Disabled;
+
+    impl Service<TestGlobal> for Disabled {
+        fn name(&self) -> Option<&'static str> {
+            Some("disabled")
+        }
+
+        async fn enabled(&self, _: &Arc<TestGlobal>) -> anyhow::Result<bool> {
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_and_disabled_dependencies() {
+        let global = TestGlobal::init().await.unwrap();
+        let mut runner = runner(
+            &global,
+            vec![
+                TestSvc {
+                    name: "migrations",
+                    signals_readiness: true,
+                    fail: true,
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "http",
+                    dependencies: &["migrations", "disabled"],
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "metrics",
+                    dependencies: &["disabled"],
+                    ..Default::default()
+                },
+            ],
+        )
+        .await;
+        runner.add(&global, Disabled, "Disabled").await.unwrap();
+
+        let handler = Handler::new();
+        let run = tokio::spawn({
+            let global = global.clone();
+            let handler = handler.clone();
+            async move { runner.run(&global, &handler).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handler.cancel();
+        run.with_timeout(Duration::from_secs(1))
+            .await
+            .expect("run timed out")
+            .unwrap()
+            .unwrap();
+
+        let mut events = global.events();
+        events.sort();
+        assert_eq!(
+            events,
+            [
+                "exit http: dependency migrations stopped before it was ready",
+                "exit metrics",
+                "exit migrations: failed",
+                "start metrics",
+                "start migrations",
+                "stop metrics",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_service_state() {
+        let global = TestGlobal::init().await.unwrap();
+        let states = ServiceStates::new();
+        let mut runner = ServiceRunner::new(states.clone());
+
+        for service in [
+            TestSvc {
+                name: "migrations",
+                signals_readiness: true,
+                fail: true,
+                ..Default::default()
+            },
+            TestSvc {
+                name: "http",
+                dependencies: &["migrations"],
+                ..Default::default()
+            },
+            TestSvc {
+                name: "metrics",
+                ..Default::default()
+            },
+        ] {
+            runner.add(&global, service, "unnamed").await.unwrap();
+        }
+
+        let handler = Handler::new();
+        let run = tokio::spawn({
+            let global = global.clone();
+            let handler = handler.clone();
+            async move { runner.run(&global, &handler).await }
+        });
+
+        // Neither the failed service nor the one that never started count as ready
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(states.get("metrics"), Some(ServiceState::Ready));
+        assert_eq!(
+            states.check().unwrap_err().to_string(),
+            "services not ready: migrations (stopped), http (stopped)"
+        );
+
+        handler.cancel();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_timeout() {
+        let global = TestGlobal::init().await.unwrap();
+        let runner = runner(
+            &global,
+            vec![
+                TestSvc {
+                    name: "db",
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "http",
+                    dependencies: &["db"],
+                    shutdown_timeout: Some(Duration::from_millis(50)),
+                    hang: true,
+                    ..Default::default()
+                },
+            ],
+        )
+        .await;
+
+        let handler = Handler::new();
+        handler.cancel();
+        runner
+            .run(&global, &handler)
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .expect("run timed out")
+            .unwrap();
+
+        assert_eq!(
+            global.events(),
+            [
+                "start db",
+                "start http",
+                "stop http",
+                "exit http: did not shut down within 50ms",
+                "stop db",
+                "exit db",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn exit_error_shuts_down() {
+        let global = Arc::new(TestGlobal {
+            propagate_errors: true,
+            ..Default::default()
+        });
+        let runner = runner(
+            &global,
+            vec![
+                TestSvc {
+                    name: "db",
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "http",
+                    dependencies: &["db"],
+                    ..Default::default()
+                },
+                TestSvc {
+                    name: "worker",
+                    dependencies: &["db"],
+                    fail: true,
+                    ..Default::default()
+                },
+            ],
+        )
+        .await;
+
+        let handler = Handler::new();
+        let err = runner
+            .run(&global, &handler)
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .expect("run timed out")
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "worker");
+        assert_eq!(err.root_cause().to_string(), "failed");
+        assert!(handler.is_done());
+        assert_eq!(
+            global.events(),
+            [
+                "start db",
+                "start http",
+                "start worker",
+                "exit worker: failed",
+                "stop http",
+                "exit http",
+                "stop db",
+                "exit db",
+            ]
+        );
+    }
+}
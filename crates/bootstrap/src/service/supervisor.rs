@@ -0,0 +1,368 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use scuffle_context::ContextFutExt;
+use tokio::time::Instant;
+
+use super::Service;
+
+#[scuffle_metrics::metrics(rename = "bootstrap_service")]
+mod bootstrap_service {
+    use scuffle_metrics::{CounterU64, GaugeU64};
+
+    /// The number of times a supervised service was restarted.
+    #[metrics(unit = "restarts")]
+    pub(super) fn restarts(service: &'static str) -> CounterU64;
+
+    /// The number of times a supervised service failed.
+    #[metrics(unit = "failures")]
+    pub(super) fn failures(service: &'static str) -> CounterU64;
+
+    /// The unix time of the last failure of a supervised service.
+    #[metrics(unit = "seconds")]
+    pub(super) fn last_failure_time(service: &'static str) -> GaugeU64;
+}
+
+/// When a [`Supervised`] service is restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Restart {
+    /// Never restart the service.
+    Never,
+    /// Restart the service when it returns an error.
+    #[default]
+    OnFailure,
+    /// Restart the service whenever it exits.
+    Always,
+}
+
+/// Restarts a service when it exits.
+///
+/// Since [`Service::run`] consumes the service, a new instance is built by calling the factory
+/// for every restart. The first instance is built right away and decides the
+/// [name](Service::name), [dependencies](Service::dependencies) and the other properties of the
+/// supervised service.
+///
+/// Restarts are delayed by an exponential backoff with jitter. A service that needs more than
+/// [`max_restarts`](Supervised::max_restarts) restarts within the window is given up on: `run`
+/// returns the last error, which [`Global::on_service_exit`](crate::Global::on_service_exit) turns
+/// into a shutdown of all services by default. Nothing is restarted once the context is done.
+///
+/// Restarts and failures are counted by the `bootstrap_service_restarts` and `bootstrap_service_failures`
+/// metrics, the time of the last failure is recorded by the `bootstrap_service_last_failure_time` metric.
+/// Failures are logged as warnings.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # struct Global;
+/// use scuffle_bootstrap::service::{Restart, Service, Supervised};
+///
+/// struct EmailQueue;
+///
+/// impl Service<Global> for EmailQueue {
+///     async fn run(self, global: Arc<Global>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+///         # let _ = (global, ctx);
+///         // Consume the queue until the connection drops
+///         anyhow::bail!("connection lost")
+///     }
+/// }
+///
+/// let service = Supervised::new(|| EmailQueue)
+///     .restart(Restart::OnFailure)
+///     .max_restarts(10, std::time::Duration::from_secs(300));
+/// # let _: &dyn std::any::Any = &service;
+/// ```
+pub struct Supervised<F, S> {
+    factory: F,
+    first: Option<S>,
+    restart: Restart,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    max_restarts: usize,
+    window: Duration,
+}
+
+impl<F, S> Supervised<F, S>
+where
+    F: Fn() -> S,
+{
+    /// Supervises the services built by `factory`.
+    ///
+    /// By default, services are restarted [on failure](Restart::OnFailure), backing off from one second
+    /// up to a minute with 20% jitter, and given up on after 5 restarts within a minute.
+    pub fn new(factory: F) -> Self {
+        Self {
+            first: Some(factory()),
+            factory,
+            restart: Restart::OnFailure,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            jitter: 0.2,
+            max_restarts: 5,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets when the service is restarted.
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Sets the delay before the first restart, which doubles with every restart up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Sets how much of the backoff is randomized, between `0.0` and `1.0`.
+    ///
+    /// A jitter of `0.2` shortens every delay by up to 20%, so services that failed together don't
+    /// restart together.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Gives up on the service when it needs more than `max_restarts` restarts within `window`.
+    pub fn max_restarts(mut self, max_restarts: usize, window: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.window = window;
+        self
+    }
+
+    /// The delay before the next restart, after `restarts` recent restarts.
+    fn backoff_delay(&self, restarts: usize) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << restarts.min(31))
+            .min(self.max_backoff);
+
+        backoff.mul_f64(1.0 - self.jitter * rand::random::<f64>())
+    }
+}
+
+impl<G, F, S> Service<G> for Supervised<F, S>
+where
+    G: Send + Sync + 'static,
+    F: Fn() -> S + Send + Sync + 'static,
+    S: Service<G>,
+{
+    fn name(&self) -> Option<&'static str> {
+        self.first.as_ref().and_then(Service::name)
+    }
+
+    async fn enabled(&self, global: &Arc<G>) -> anyhow::Result<bool> {
+        match &self.first {
+            Some(service) => service.enabled(global).await,
+            None => Ok(true),
+        }
+    }
+
+    fn dependencies(&self) -> &[&'static str] {
+        self.first.as_ref().map_or(&[], Service::dependencies)
+    }
+
+    fn signals_readiness(&self) -> bool {
+        self.first.as_ref().is_some_and(Service::signals_readiness)
+    }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        self.first.as_ref().and_then(Service::shutdown_timeout)
+    }
+
+    async fn run(mut self, global: Arc<G>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+        let name = super::current_service()
+            .or_else(|| Service::<G>::name(&self))
+            .unwrap_or("unnamed");
+
+        let mut service = self.first.take().unwrap_or_else(&self.factory);
+        let mut restarts = VecDeque::new();
+
+        loop {
+            let result = service.run(global.clone(), ctx.clone()).await;
+
+            let restart = match self.restart {
+                Restart::Never => false,
+                Restart::OnFailure => result.is_err(),
+                Restart::Always => true,
+            };
+            if !restart || ctx.is_done() {
+                return result;
+            }
+
+            if let Err(err) = &result {
+                tracing::warn!(service = name, err = format!("{err:#}"), "supervised service failed");
+
+                let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+                bootstrap_service::failures(name).incr();
+                bootstrap_service::last_failure_time(name).record(now.as_secs());
+            }
+
+            let now = Instant::now();
+            while restarts
+                .front()
+                .is_some_and(|restart| now.duration_since(*restart) >= self.window)
+            {
+                restarts.pop_front();
+            }
+
+            if restarts.len() >= self.max_restarts {
+                let err = anyhow::anyhow!("restarted {} times within {:?}", restarts.len(), self.window);
+                return Err(match result {
+                    Ok(()) => err,
+                    Err(last) => last.context(err),
+                });
+            }
+
+            let delay = self.backoff_delay(restarts.len());
+            restarts.push_back(now);
+
+            if tokio::time::sleep(delay).with_context(&ctx).await.is_none() {
+                // Shutting down, the service is not running anymore
+                return Ok(());
+            }
+
+            bootstrap_service::restarts(name).incr();
+            service = (self.factory)();
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use scuffle_future_ext::FutureExt;
+
+    use super::{Restart, Supervised};
+    use crate::service::Service;
+
+    /// Fails the first `failures` runs, then waits for the context.
+    struct Flaky {
+        runs: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    impl Service<()> for Flaky {
+        fn name(&self) -> Option<&'static str> {
+            Some("flaky")
+        }
+
+        async fn run(self, _: Arc<()>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+            let run = self.runs.fetch_add(1, Ordering::Relaxed);
+            if run < self.failures {
+                anyhow::bail!("failure {run}");
+            }
+
+            ctx.done().await;
+            Ok(())
+        }
+    }
+
+    fn flaky(runs: &Arc<AtomicUsize>, failures: usize) -> impl Fn() -> Flaky + Send + Sync + 'static {
+        let runs = runs.clone();
+        move || Flaky {
+            runs: runs.clone(),
+            failures,
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_on_failure() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let supervised = Supervised::new(flaky(&runs, 3)).backoff(Duration::from_millis(1), Duration::from_millis(5));
+        assert_eq!(supervised.name(), Some("flaky"));
+
+        let (ctx, handler) = scuffle_context::Context::new();
+        let run = tokio::spawn(supervised.run(Arc::new(()), ctx));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 4);
+
+        handler.cancel();
+        run.with_timeout(Duration::from_secs(1)).await.unwrap().unwrap().unwrap();
+        assert_eq!(runs.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn restart_never() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let supervised = Supervised::new(flaky(&runs, 3)).restart(Restart::Never);
+
+        let (ctx, _handler) = scuffle_context::Context::new();
+        let err = supervised.run(Arc::new(()), ctx).await.unwrap_err();
+        assert_eq!(err.to_string(), "failure 0");
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn restart_always() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let supervised = Supervised::new(flaky(&runs, 0))
+            .restart(Restart::Always)
+            .backoff(Duration::from_millis(1), Duration::from_millis(1));
+
+        // A service that exits on its own is restarted, until the context is done
+        let (ctx, handler) = scuffle_context::Context::new();
+        let (inner_ctx, inner_handler) = ctx.new_child();
+        inner_handler.cancel();
+        drop(ctx);
+        handler.cancel();
+
+        supervised
+            .run(Arc::new(()), inner_ctx)
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let supervised = Supervised::new(flaky(&runs, usize::MAX))
+            .backoff(Duration::from_millis(1), Duration::from_millis(1))
+            .jitter(1.0)
+            .max_restarts(3, Duration::from_secs(60));
+
+        let (ctx, _handler) = scuffle_context::Context::new();
+        let err = supervised
+            .run(Arc::new(()), ctx)
+            .with_timeout(Duration::from_secs(1))
+            .await
+            .unwrap()
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "restarted 3 times within 60s");
+        assert_eq!(err.root_cause().to_string(), "failure 3");
+        assert_eq!(runs.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn backoff() {
+        let supervised = Supervised::new(|| ())
+            .backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .jitter(0.0);
+
+        assert_eq!(supervised.backoff_delay(0), Duration::from_millis(100));
+        assert_eq!(supervised.backoff_delay(2), Duration::from_millis(400));
+        assert_eq!(supervised.backoff_delay(4), Duration::from_secs(1));
+        assert_eq!(supervised.backoff_delay(100), Duration::from_secs(1));
+
+        let supervised = supervised.jitter(0.5);
+        for restarts in 0..10 {
+            let delay = supervised.backoff_delay(restarts);
+            assert!(delay <= Duration::from_secs(1));
+            assert!(delay >= Duration::from_millis(50));
+        }
+    }
+}
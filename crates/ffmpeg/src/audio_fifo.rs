@@ -0,0 +1,478 @@
+use std::num::NonZero;
+
+use crate::AVSampleFormat;
+use crate::error::{FfmpegError, FfmpegErrorCode};
+use crate::ffi::*;
+use crate::frame::{AudioChannelLayout, AudioFrame};
+use crate::rational::Rational;
+use crate::smart_object::SmartPtr;
+use crate::timestamp::Timestamp;
+
+/// A buffer of audio samples. Thin wrapper around [`AVAudioFifo`].
+///
+/// Frames of any size can be written to the fifo and read back in frames of a different size.
+/// The timestamps of the frames read are derived from the first written frame with a timestamp,
+/// so they are continuous even if the written frames are not.
+pub struct AudioFifo {
+    fifo: SmartPtr<AVAudioFifo>,
+    channel_layout: AudioChannelLayout,
+    sample_fmt: AVSampleFormat,
+    sample_rate: i32,
+    time_base: Option<Rational>,
+    start_pts: Option<i64>,
+    samples_read: i64,
+}
+
+/// Safety: `AudioFifo` can be sent between threads.
+unsafe impl Send for AudioFifo {}
+
+impl std::fmt::Debug for AudioFifo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioFifo")
+            .field("channel_count", &self.channel_layout.channel_count())
+            .field("sample_fmt", &self.sample_fmt)
+            .field("sample_rate", &self.sample_rate)
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+impl AudioFifo {
+    /// Creates a new fifo for samples of the given format.
+    pub fn new(
+        channel_layout: AudioChannelLayout,
+        sample_fmt: AVSampleFormat,
+        sample_rate: i32,
+    ) -> Result<Self, FfmpegError> {
+        channel_layout.validate()?;
+        if sample_rate <= 0 {
+            return Err(FfmpegError::Arguments("sample_rate must be positive and not 0"));
+        }
+
+        let destructor = |ptr: &mut *mut AVAudioFifo| {
+            // Safety: The pointer here is valid and was allocated by `av_audio_fifo_alloc`.
+            unsafe { av_audio_fifo_free(*ptr) };
+            *ptr = std::ptr::null_mut();
+        };
+
+        // Safety: `av_audio_fifo_alloc` is safe to call, the fifo grows when samples are written.
+        let fifo = unsafe { av_audio_fifo_alloc(sample_fmt.into(), channel_layout.channel_count(), 1) };
+
+        // Safety: `fifo` is a valid pointer, and `destructor` has been setup to free the fifo.
+        let fifo = unsafe { SmartPtr::wrap_non_null(fifo, destructor) }.ok_or(FfmpegError::Alloc)?;
+
+        Ok(Self {
+            fifo,
+            channel_layout,
+            sample_fmt,
+            sample_rate,
+            time_base: None,
+            start_pts: None,
+            samples_read: 0,
+        })
+    }
+
+    /// Returns the channel layout of the samples in the fifo.
+    pub const fn channel_layout(&self) -> &AudioChannelLayout {
+        &self.channel_layout
+    }
+
+    /// Returns the sample format of the samples in the fifo.
+    pub const fn sample_format(&self) -> AVSampleFormat {
+        self.sample_fmt
+    }
+
+    /// Returns the sample rate of the samples in the fifo.
+    pub const fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    /// Returns the time base of the frames read from the fifo.
+    ///
+    /// This is the time base of the first written frame with a timestamp, or `1 / sample_rate`
+    /// if there was none.
+    pub fn time_base(&self) -> Rational {
+        self.time_base.unwrap_or_else(|| self.sample_time_base())
+    }
+
+    /// Returns the number of samples per channel in the fifo.
+    pub fn size(&self) -> i32 {
+        // Safety: `av_audio_fifo_size` is safe to call, the fifo is valid.
+        unsafe { av_audio_fifo_size(self.fifo.as_ptr().cast_mut()) }
+    }
+
+    /// Returns `true` if the fifo contains no samples.
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Writes the samples of a frame to the fifo.
+    ///
+    /// The frame must have the channel count, sample format and sample rate of the fifo.
+    pub fn write(&mut self, frame: &AudioFrame) -> Result<(), FfmpegError> {
+        if frame.format() != self.sample_fmt
+            || frame.channel_count() != self.channel_layout.channel_count() as usize
+            || frame.sample_rate() != self.sample_rate
+        {
+            return Err(FfmpegError::Arguments("frame does not match the format of the fifo"));
+        }
+
+        if self.start_pts.is_none()
+            && let Some(pts) = frame.pts()
+        {
+            let time_base = frame.time_base();
+            let time_base = if time_base.numerator > 0 {
+                time_base
+            } else {
+                self.sample_time_base()
+            };
+            self.time_base = Some(time_base);
+
+            // The samples that are already buffered or read come right before this frame.
+            let offset = self.samples_read + self.size() as i64;
+            self.start_pts = Some(pts - self.samples_to_pts(offset, time_base));
+        }
+
+        // Safety: `frame` is a valid pointer.
+        let frame = unsafe { frame.as_ptr().as_ref() }.ok_or(FfmpegError::NoFrame)?;
+
+        // Safety: `av_audio_fifo_write` is safe to call, `extended_data` points to a plane for each
+        // channel with at least `nb_samples` samples.
+        let written = FfmpegErrorCode(unsafe {
+            av_audio_fifo_write(
+                self.fifo.as_mut_ptr(),
+                frame.extended_data.cast::<*mut std::ffi::c_void>(),
+                frame.nb_samples,
+            )
+        })
+        .result()?;
+
+        if written < frame.nb_samples {
+            return Err(FfmpegError::Alloc);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a frame of exactly `nb_samples` samples from the fifo.
+    ///
+    /// Returns `None` if the fifo contains fewer samples.
+    pub fn read(&mut self, nb_samples: i32) -> Result<Option<AudioFrame>, FfmpegError> {
+        if nb_samples <= 0 {
+            return Err(FfmpegError::Arguments("nb_samples must be positive and not 0"));
+        }
+
+        if self.size() < nb_samples {
+            return Ok(None);
+        }
+
+        self.read_frame(nb_samples, nb_samples).map(Some)
+    }
+
+    /// Reads a frame of `nb_samples` samples from the fifo, padding it with silence if the fifo
+    /// contains fewer samples.
+    ///
+    /// This is used to read the final frame at the end of a stream. Returns `None` if the fifo is empty.
+    pub fn read_padded(&mut self, nb_samples: i32) -> Result<Option<AudioFrame>, FfmpegError> {
+        if nb_samples <= 0 {
+            return Err(FfmpegError::Arguments("nb_samples must be positive and not 0"));
+        }
+
+        let size = self.size();
+        if size == 0 {
+            return Ok(None);
+        }
+
+        self.read_frame(nb_samples, size.min(nb_samples)).map(Some)
+    }
+
+    /// Removes all samples from the fifo and resets the timestamps, for example after seeking.
+    pub fn reset(&mut self) {
+        // Safety: `av_audio_fifo_reset` is safe to call, the fifo is valid.
+        unsafe { av_audio_fifo_reset(self.fifo.as_mut_ptr()) };
+
+        self.time_base = None;
+        self.start_pts = None;
+        self.samples_read = 0;
+    }
+
+    fn read_frame(&mut self, nb_samples: i32, available: i32) -> Result<AudioFrame, FfmpegError> {
+        let time_base = self.time_base();
+
+        let mut frame = AudioFrame::builder()
+            .channel_layout(self.channel_layout.copy()?)
+            .nb_samples(nb_samples)
+            .sample_fmt(self.sample_fmt)
+            .sample_rate(self.sample_rate)
+            .time_base(time_base)
+            .build()?;
+
+        // Safety: `frame` is a valid pointer.
+        let frame_mut = unsafe { frame.as_mut_ptr().as_mut() }.ok_or(FfmpegError::NoFrame)?;
+
+        if available < nb_samples {
+            // Safety: `av_samples_set_silence` is safe to call, the buffers of the frame were allocated
+            // for `nb_samples` samples of the channel count and format of the fifo.
+            FfmpegErrorCode(unsafe {
+                av_samples_set_silence(
+                    frame_mut.extended_data,
+                    available,
+                    nb_samples - available,
+                    self.channel_layout.channel_count(),
+                    self.sample_fmt.into(),
+                )
+            })
+            .result()?;
+        }
+
+        // Safety: `av_audio_fifo_read` is safe to call, the buffers of the frame can hold `nb_samples` samples.
+        let read = FfmpegErrorCode(unsafe {
+            av_audio_fifo_read(
+                self.fifo.as_mut_ptr(),
+                frame_mut.extended_data.cast::<*mut std::ffi::c_void>(),
+                available,
+            )
+        })
+        .result()?;
+
+        if read < available {
+            return Err(FfmpegError::Code(FfmpegErrorCode::Bug));
+        }
+
+        frame.set_pts(
+            self.start_pts
+                .map(|start_pts| start_pts + self.samples_to_pts(self.samples_read, time_base)),
+        );
+        frame.set_duration(Some(self.samples_to_pts(nb_samples as i64, time_base)));
+        self.samples_read += nb_samples as i64;
+
+        Ok(frame)
+    }
+
+    fn sample_time_base(&self) -> Rational {
+        Rational::new(1, NonZero::new(self.sample_rate).expect("sample_rate is positive"))
+    }
+
+    fn samples_to_pts(&self, samples: i64, time_base: Rational) -> i64 {
+        Timestamp::new(samples, self.sample_time_base()).rescale(time_base).value()
+    }
+}
+
+/// Splits audio frames into frames of a fixed size.
+///
+/// Encoders like AAC and Opus only accept frames of [`Encoder::frame_size`](crate::encoder::Encoder::frame_size)
+/// samples, while decoders and the [`Resampler`](crate::resampler::Resampler) produce frames of any size.
+/// The adapter buffers the samples in an [`AudioFifo`] and returns frames of the right size with continuous timestamps.
+#[derive(Debug)]
+pub struct AudioFrameAdapter {
+    fifo: AudioFifo,
+    frame_size: i32,
+}
+
+impl AudioFrameAdapter {
+    /// Creates a new adapter returning frames of `frame_size` samples of the given format.
+    pub fn new(
+        channel_layout: AudioChannelLayout,
+        sample_fmt: AVSampleFormat,
+        sample_rate: i32,
+        frame_size: i32,
+    ) -> Result<Self, FfmpegError> {
+        if frame_size <= 0 {
+            return Err(FfmpegError::Arguments("frame_size must be positive and not 0"));
+        }
+
+        Ok(Self {
+            fifo: AudioFifo::new(channel_layout, sample_fmt, sample_rate)?,
+            frame_size,
+        })
+    }
+
+    /// Returns the number of samples of the frames returned by the adapter.
+    pub const fn frame_size(&self) -> i32 {
+        self.frame_size
+    }
+
+    /// Returns the fifo buffering the samples.
+    pub const fn fifo(&self) -> &AudioFifo {
+        &self.fifo
+    }
+
+    /// Buffers the samples of a frame.
+    pub fn send_frame(&mut self, frame: &AudioFrame) -> Result<(), FfmpegError> {
+        self.fifo.write(frame)
+    }
+
+    /// Receives a frame of [`frame_size`](Self::frame_size) samples.
+    ///
+    /// Returns `None` if not enough samples are buffered.
+    pub fn receive_frame(&mut self) -> Result<Option<AudioFrame>, FfmpegError> {
+        self.fifo.read(self.frame_size)
+    }
+
+    /// Receives the remaining samples as a frame padded with silence.
+    ///
+    /// This should be called once the last frame has been sent and no more frames can be received.
+    /// Returns `None` if no samples are left.
+    pub fn flush(&mut self) -> Result<Option<AudioFrame>, FfmpegError> {
+        self.fifo.read_padded(self.frame_size)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::AVSampleFormat;
+    use crate::audio_fifo::{AudioFifo, AudioFrameAdapter};
+    use crate::error::FfmpegError;
+    use crate::frame::{AudioChannelLayout, AudioFrame};
+    use crate::rational::Rational;
+
+    const SAMPLE_RATE: i32 = 48000;
+
+    fn frame(nb_samples: i32, first_sample: i16, pts: Option<i64>) -> AudioFrame {
+        let mut frame = AudioFrame::builder()
+            .channel_layout(AudioChannelLayout::new(1).expect("Failed to create channel layout"))
+            .nb_samples(nb_samples)
+            .sample_fmt(AVSampleFormat::S16)
+            .sample_rate(SAMPLE_RATE)
+            .time_base(Rational::static_new::<1, 1000>())
+            .build()
+            .expect("Failed to create frame");
+        frame.set_pts(pts);
+
+        let data = frame.data_mut(0).expect("Missing data");
+        for (index, sample) in data.chunks_exact_mut(2).take(nb_samples as usize).enumerate() {
+            sample.copy_from_slice(&(first_sample + index as i16).to_ne_bytes());
+        }
+
+        frame
+    }
+
+    fn samples(frame: &AudioFrame) -> Vec<i16> {
+        frame
+            .data(0)
+            .expect("Missing data")
+            .chunks_exact(2)
+            .take(frame.nb_samples() as usize)
+            .map(|sample| i16::from_ne_bytes([sample[0], sample[1]]))
+            .collect()
+    }
+
+    fn fifo() -> AudioFifo {
+        AudioFifo::new(
+            AudioChannelLayout::new(1).expect("Failed to create channel layout"),
+            AVSampleFormat::S16,
+            SAMPLE_RATE,
+        )
+        .expect("Failed to create fifo")
+    }
+
+    #[test]
+    fn test_audio_fifo_read_write() {
+        let mut fifo = fifo();
+        assert!(fifo.is_empty());
+        assert_eq!(fifo.time_base(), Rational::new(1, SAMPLE_RATE.try_into().unwrap()));
+
+        fifo.write(&frame(480, 0, Some(1000))).expect("Failed to write frame");
+        assert_eq!(fifo.size(), 480);
+        assert_eq!(fifo.time_base(), Rational::static_new::<1, 1000>());
+        assert!(fifo.read(960).expect("Failed to read").is_none());
+
+        fifo.write(&frame(480, 480, Some(1010))).expect("Failed to write frame");
+
+        let first = fifo.read(600).expect("Failed to read").expect("Expected a frame");
+        assert_eq!(first.nb_samples(), 600);
+        assert_eq!(first.pts(), Some(1000));
+        assert_eq!(first.duration(), Some(13));
+        assert_eq!(samples(&first), (0..600).collect::<Vec<_>>());
+
+        let second = fifo.read(360).expect("Failed to read").expect("Expected a frame");
+        assert_eq!(second.pts(), Some(1013));
+        assert_eq!(samples(&second), (600..960).collect::<Vec<_>>());
+        assert!(fifo.is_empty());
+    }
+
+    #[test]
+    fn test_audio_fifo_read_padded() {
+        let mut fifo = fifo();
+        assert!(fifo.read_padded(1024).expect("Failed to read").is_none());
+
+        fifo.write(&frame(100, 1, None)).expect("Failed to write frame");
+
+        let padded = fifo.read_padded(1024).expect("Failed to read").expect("Expected a frame");
+        assert_eq!(padded.nb_samples(), 1024);
+        assert_eq!(padded.pts(), None);
+
+        let samples = samples(&padded);
+        assert_eq!(samples[..100], (1..101).collect::<Vec<_>>());
+        assert!(samples[100..].iter().all(|&sample| sample == 0), "Expected silence");
+        assert!(fifo.is_empty());
+    }
+
+    #[test]
+    fn test_audio_fifo_format_mismatch() {
+        let mut fifo = AudioFifo::new(
+            AudioChannelLayout::new(2).expect("Failed to create channel layout"),
+            AVSampleFormat::S16,
+            SAMPLE_RATE,
+        )
+        .expect("Failed to create fifo");
+
+        assert_eq!(
+            fifo.write(&frame(100, 0, None)).unwrap_err(),
+            FfmpegError::Arguments("frame does not match the format of the fifo")
+        );
+        assert!(fifo.read(0).is_err());
+    }
+
+    #[test]
+    fn test_audio_fifo_reset() {
+        let mut fifo = fifo();
+        fifo.write(&frame(100, 0, Some(5))).expect("Failed to write frame");
+        fifo.reset();
+
+        assert!(fifo.is_empty());
+        fifo.write(&frame(48, 0, Some(500))).expect("Failed to write frame");
+        let frame = fifo.read(48).expect("Failed to read").expect("Expected a frame");
+        assert_eq!(frame.pts(), Some(500));
+    }
+
+    #[test]
+    fn test_audio_frame_adapter() {
+        let mut adapter = AudioFrameAdapter::new(
+            AudioChannelLayout::new(1).expect("Failed to create channel layout"),
+            AVSampleFormat::S16,
+            SAMPLE_RATE,
+            1024,
+        )
+        .expect("Failed to create adapter");
+
+        // 10ms frames of 480 samples, with a gap in the timestamps that the adapter smooths out.
+        let mut frames = Vec::new();
+        for index in 0..10 {
+            let pts = if index == 5 { 51 } else { index * 10 };
+            adapter.send_frame(&frame(480, 0, Some(pts))).expect("Failed to send frame");
+
+            while let Some(frame) = adapter.receive_frame().expect("Failed to receive frame") {
+                frames.push(frame);
+            }
+        }
+
+        frames.extend(adapter.flush().expect("Failed to flush"));
+        assert!(adapter.flush().expect("Failed to flush").is_none());
+
+        // 4800 samples are 4 full frames and a padded one.
+        assert_eq!(frames.len(), 5);
+        assert!(frames.iter().all(|frame| frame.nb_samples() == 1024));
+
+        let pts: Vec<_> = frames.iter().map(|frame| frame.pts().unwrap()).collect();
+        // 1024 samples at 48kHz are 21.33ms.
+        assert_eq!(pts, [0, 21, 43, 64, 85]);
+        assert!(adapter.fifo().is_empty());
+
+        assert_eq!(
+            AudioFrameAdapter::new(AudioChannelLayout::new(1).unwrap(), AVSampleFormat::S16, SAMPLE_RATE, 0).unwrap_err(),
+            FfmpegError::Arguments("frame_size must be positive and not 0")
+        );
+    }
+}
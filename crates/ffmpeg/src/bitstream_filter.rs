@@ -0,0 +1,275 @@
+use std::ffi::{CStr, CString};
+
+use crate::dict::Dictionary;
+use crate::error::{FfmpegError, FfmpegErrorCode};
+use crate::ffi::*;
+use crate::packet::Packet;
+use crate::rational::Rational;
+use crate::smart_object::SmartPtr;
+use crate::stream::Stream;
+
+/// A bitstream filter. Thin wrapper around [`AVBSFContext`].
+///
+/// Bitstream filters modify encoded packets without decoding them, for example
+/// `h264_mp4toannexb` converts H.264 packets from MP4 to Annex B, and
+/// `aac_adtstoasc` removes the ADTS headers from AAC packets.
+pub struct BitstreamFilter {
+    context: SmartPtr<AVBSFContext>,
+}
+
+/// Safety: `BitstreamFilter` can be sent between threads.
+unsafe impl Send for BitstreamFilter {}
+
+impl std::fmt::Debug for BitstreamFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BitstreamFilter")
+            .field("name", &self.name())
+            .field("time_base_in", &self.time_base_in())
+            .field("time_base_out", &self.time_base_out())
+            .finish()
+    }
+}
+
+impl BitstreamFilter {
+    /// Creates a new bitstream filter with the given name, for the packets of `stream`.
+    pub fn new(name: &str, stream: &Stream) -> Result<Self, FfmpegError> {
+        Self::with_options(name, stream, Dictionary::new())
+    }
+
+    /// Creates a new bitstream filter with the given name and options, for the packets of `stream`.
+    ///
+    /// Returns an error if one of the options is not supported by the filter.
+    pub fn with_options(name: &str, stream: &Stream, options: Dictionary) -> Result<Self, FfmpegError> {
+        let codec_parameters = stream.codec_parameters().ok_or(FfmpegError::NoStream)?;
+
+        // Safety: The codec parameters are valid while the stream is borrowed.
+        unsafe { Self::from_parameters(name, codec_parameters, stream.time_base(), options) }
+    }
+
+    /// Creates a new bitstream filter with the given name and options, for packets with the
+    /// given codec parameters and time base.
+    ///
+    /// # Safety
+    /// `codec_parameters` must be a valid pointer to an [`AVCodecParameters`].
+    pub unsafe fn from_parameters(
+        name: &str,
+        codec_parameters: *const AVCodecParameters,
+        time_base: impl Into<Rational>,
+        mut options: Dictionary,
+    ) -> Result<Self, FfmpegError> {
+        let name = CString::new(name).map_err(|_| FfmpegError::Arguments("name cannot contain a nul byte"))?;
+
+        // Safety: `av_bsf_get_by_name` is safe to call with a valid c-string.
+        let filter = unsafe { av_bsf_get_by_name(name.as_ptr()) };
+        if filter.is_null() {
+            return Err(FfmpegErrorCode::BitstreamFilterNotFound.into());
+        }
+
+        let destructor = |ptr: &mut *mut AVBSFContext| {
+            // Safety: The pointer here is valid and was allocated by `av_bsf_alloc`.
+            unsafe { av_bsf_free(ptr) };
+        };
+
+        let mut context = SmartPtr::null(destructor);
+
+        // Safety: `av_bsf_alloc` is safe to call, `filter` is a valid pointer.
+        FfmpegErrorCode(unsafe { av_bsf_alloc(filter, context.as_mut()) }).result()?;
+
+        let context_mut = context.as_deref_mut().ok_or(FfmpegError::Alloc)?;
+
+        // Safety: `par_in` was allocated by `av_bsf_alloc` and the caller guarantees `codec_parameters` is valid.
+        FfmpegErrorCode(unsafe { avcodec_parameters_copy(context_mut.par_in, codec_parameters) }).result()?;
+        context_mut.time_base_in = time_base.into().into();
+
+        if !options.is_empty() {
+            if context_mut.priv_data.is_null() {
+                return Err(FfmpegErrorCode::OptionNotFound.into());
+            }
+
+            // Safety: `av_opt_set_dict` is safe to call, `priv_data` is an AVClass enabled struct.
+            FfmpegErrorCode(unsafe { av_opt_set_dict(context_mut.priv_data, options.as_mut_ptr_ref()) }).result()?;
+
+            // The options that were not consumed are not supported by the filter.
+            if !options.is_empty() {
+                return Err(FfmpegErrorCode::OptionNotFound.into());
+            }
+        }
+
+        // Safety: `av_bsf_init` is safe to call, the context is valid.
+        FfmpegErrorCode(unsafe { av_bsf_init(context.as_mut_ptr()) }).result()?;
+
+        Ok(Self { context })
+    }
+
+    /// Returns the name of the bitstream filter.
+    pub fn name(&self) -> &str {
+        // Safety: The filter pointer is set by `av_bsf_alloc` and is valid for the lifetime of the context.
+        let filter = unsafe { self.context.as_deref_except().filter.as_ref() };
+
+        filter
+            // Safety: The name of a bitstream filter is a valid c-string.
+            .map(|filter| unsafe { CStr::from_ptr(filter.name) })
+            .and_then(|name| name.to_str().ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns the time base of the packets sent to the filter.
+    pub fn time_base_in(&self) -> Rational {
+        self.context.as_deref_except().time_base_in.into()
+    }
+
+    /// Returns the time base of the packets received from the filter.
+    pub fn time_base_out(&self) -> Rational {
+        self.context.as_deref_except().time_base_out.into()
+    }
+
+    /// Returns the codec parameters of the packets received from the filter.
+    pub const fn output_parameters(&self) -> Option<&AVCodecParameters> {
+        // Safety: `par_out` is owned by the context and valid while the filter is borrowed.
+        unsafe { self.context.as_deref_except().par_out.as_ref() }
+    }
+
+    /// Copies the output codec parameters and time base of the filter to `stream`.
+    ///
+    /// This is used to set up an output stream for the filtered packets.
+    pub fn copy_parameters_to(&self, stream: &mut Stream) -> Result<(), FfmpegError> {
+        // Safety: The stream pointer is valid while the stream is borrowed.
+        let stream_mut = unsafe { stream.as_mut_ptr().as_mut() }.ok_or(FfmpegError::NoStream)?;
+
+        // Safety: `avcodec_parameters_copy` is safe to call, both pointers are valid.
+        FfmpegErrorCode(unsafe { avcodec_parameters_copy(stream_mut.codecpar, self.context.as_deref_except().par_out) })
+            .result()?;
+
+        stream.set_time_base(self.time_base_out());
+
+        Ok(())
+    }
+
+    /// Sends a packet to the filter.
+    pub fn send_packet(&mut self, packet: &Packet) -> Result<(), FfmpegError> {
+        // The filter takes ownership of the packet data, so we send a new reference to it.
+        let mut packet = packet.clone();
+
+        // Safety: `av_bsf_send_packet` is safe to call, both pointers are valid.
+        FfmpegErrorCode(unsafe { av_bsf_send_packet(self.context.as_mut_ptr(), packet.as_mut_ptr()) }).result()?;
+        Ok(())
+    }
+
+    /// Sends an end-of-file to the filter, the remaining packets can then be received.
+    pub fn send_eof(&mut self) -> Result<(), FfmpegError> {
+        // Safety: `av_bsf_send_packet` is safe to call, the context is valid.
+        FfmpegErrorCode(unsafe { av_bsf_send_packet(self.context.as_mut_ptr(), std::ptr::null_mut()) }).result()?;
+        Ok(())
+    }
+
+    /// Receives a filtered packet.
+    ///
+    /// Returns `None` if the filter needs more packets or has been drained.
+    pub fn receive_packet(&mut self) -> Result<Option<Packet>, FfmpegError> {
+        let mut packet = Packet::new()?;
+
+        // Safety: `av_bsf_receive_packet` is safe to call, both pointers are valid.
+        match FfmpegErrorCode(unsafe { av_bsf_receive_packet(self.context.as_mut_ptr(), packet.as_mut_ptr()) }) {
+            FfmpegErrorCode::Eagain | FfmpegErrorCode::Eof => Ok(None),
+            code if code.is_success() => Ok(Some(packet)),
+            code => Err(FfmpegError::Code(code)),
+        }
+    }
+
+    /// Resets the state of the filter, for example after seeking.
+    pub fn flush(&mut self) {
+        // Safety: `av_bsf_flush` is safe to call, the context is valid.
+        unsafe { av_bsf_flush(self.context.as_mut_ptr()) };
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::bitstream_filter::BitstreamFilter;
+    use crate::dict::Dictionary;
+    use crate::error::{FfmpegError, FfmpegErrorCode};
+    use crate::io::Input;
+    use crate::{AVMediaType, file_path};
+
+    fn is_annexb(data: &[u8]) -> bool {
+        data.starts_with(&[0, 0, 0, 1]) || data.starts_with(&[0, 0, 1])
+    }
+
+    #[test]
+    fn test_bitstream_filter_mp4_to_annexb() {
+        let mut input = Input::open(file_path("avc_aac.mp4")).expect("Failed to open valid file");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+        let video_index = stream.index();
+
+        let mut filter = BitstreamFilter::new("h264_mp4toannexb", &stream).expect("Failed to create bitstream filter");
+        assert_eq!(filter.name(), "h264_mp4toannexb");
+        assert_eq!(filter.time_base_out(), stream.time_base());
+        assert!(filter.output_parameters().is_some(), "Expected output parameters");
+
+        let mut filtered = Vec::new();
+        let mut count = 0;
+        while let Some(packet) = input.receive_packet().expect("Failed to receive packet") {
+            if packet.stream_index() != video_index {
+                continue;
+            }
+
+            count += 1;
+            filter.send_packet(&packet).expect("Failed to send packet");
+            while let Some(packet) = filter.receive_packet().expect("Failed to receive packet") {
+                filtered.push(packet);
+            }
+        }
+
+        filter.send_eof().expect("Failed to send eof");
+        while let Some(packet) = filter.receive_packet().expect("Failed to receive packet") {
+            filtered.push(packet);
+        }
+
+        assert_eq!(filtered.len(), count, "Expected one filtered packet per input packet");
+        assert!(
+            filtered.iter().all(|packet| is_annexb(packet.data())),
+            "Expected Annex B packets"
+        );
+
+        // The parameter sets are inserted in front of the first keyframe.
+        let first = &filtered[0];
+        assert!(first.is_key(), "Expected the first packet to be a keyframe");
+        let nal_types: Vec<u8> = first
+            .data()
+            .windows(4)
+            .filter(|window| window[..3] == [0, 0, 1])
+            .map(|window| window[3] & 0x1f)
+            .collect();
+        assert!(nal_types.contains(&7), "Expected a SPS in the first packet");
+        assert!(nal_types.contains(&8), "Expected a PPS in the first packet");
+    }
+
+    #[test]
+    fn test_bitstream_filter_options() {
+        let input = Input::open(file_path("avc_aac.mp4")).expect("Failed to open valid file");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+
+        let options = Dictionary::try_from_iter([("aud", "insert")]).unwrap();
+        let filter = BitstreamFilter::with_options("h264_metadata", &stream, options);
+        assert!(filter.is_ok(), "Expected the filter to accept its options: {filter:?}");
+
+        let options = Dictionary::try_from_iter([("not_an_option", "1")]).unwrap();
+        let filter = BitstreamFilter::with_options("h264_metadata", &stream, options);
+        assert_eq!(filter.unwrap_err(), FfmpegError::Code(FfmpegErrorCode::OptionNotFound));
+    }
+
+    #[test]
+    fn test_bitstream_filter_not_found() {
+        let input = Input::open(file_path("avc_aac.mp4")).expect("Failed to open valid file");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+
+        assert_eq!(
+            BitstreamFilter::new("not_a_filter", &stream).unwrap_err(),
+            FfmpegError::Code(FfmpegErrorCode::BitstreamFilterNotFound)
+        );
+    }
+}
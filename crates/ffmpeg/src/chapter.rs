@@ -0,0 +1,81 @@
+use crate::consts::Const;
+use crate::dict::Dictionary;
+use crate::ffi::*;
+use crate::rational::Rational;
+use crate::timestamp::Timestamp;
+
+/// A chapter is a wrapper around an [`AVChapter`].
+pub struct Chapter<'a>(&'a AVChapter);
+
+impl<'a> Chapter<'a> {
+    /// Creates a new `Chapter` instance.
+    pub(crate) const fn new(chapter: &'a AVChapter) -> Self {
+        Self(chapter)
+    }
+
+    /// Returns a constant pointer to the chapter.
+    pub const fn as_ptr(&self) -> *const AVChapter {
+        self.0
+    }
+
+    /// Returns the ID of the chapter.
+    pub const fn id(&self) -> i64 {
+        self.0.id
+    }
+
+    /// Returns the time base of the chapter.
+    pub fn time_base(&self) -> Rational {
+        self.0.time_base.into()
+    }
+
+    /// Returns the start time of the chapter.
+    pub fn start(&self) -> Timestamp {
+        Timestamp::new(self.0.start, self.time_base())
+    }
+
+    /// Returns the end time of the chapter.
+    pub fn end(&self) -> Timestamp {
+        Timestamp::new(self.0.end, self.time_base())
+    }
+
+    /// Returns the metadata of the chapter, the title of the chapter is stored with the `title` key.
+    pub const fn metadata(&self) -> Const<'_, Dictionary> {
+        // Safety: the pointer metadata pointer does not live longer than this object,
+        // see `Const::new`
+        Const::new(unsafe { Dictionary::from_ptr_ref(self.0.metadata) })
+    }
+}
+
+impl std::fmt::Debug for Chapter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chapter")
+            .field("id", &self.id())
+            .field("start", &self.start())
+            .field("end", &self.end())
+            .field("metadata", &self.metadata())
+            .finish()
+    }
+}
+
+/// Returns the chapters of the format context.
+///
+/// # Safety
+/// The caller must ensure that `context` is a valid pointer and that the chapters
+/// are not mutated for the lifetime `'a`.
+pub(crate) unsafe fn chapters<'a>(context: *const AVFormatContext) -> Vec<Chapter<'a>> {
+    // Safety: The caller guarantees that the pointer is valid.
+    let context = unsafe { &*context };
+    if context.chapters.is_null() {
+        return Vec::new();
+    }
+
+    // Safety: `chapters` is an array of `nb_chapters` pointers.
+    let chapters = unsafe { std::slice::from_raw_parts(context.chapters, context.nb_chapters as usize) };
+
+    chapters
+        .iter()
+        // Safety: The chapter pointers are valid for the lifetime of the format context.
+        .filter_map(|chapter| unsafe { chapter.as_ref() })
+        .map(Chapter::new)
+        .collect()
+}
@@ -1,6 +1,11 @@
 use rusty_ffmpeg::ffi::*;
 
-use crate::AVCodecID;
+use crate::error::FfmpegError;
+use crate::frame::AudioChannelLayout;
+use crate::opt::{OptionInfo, class_options};
+use crate::rational::Rational;
+use crate::utils::c_str;
+use crate::{AVCodecID, AVMediaType, AVPixelFormat, AVSampleFormat};
 
 /// A wrapper around an [`AVCodec`] pointer.
 ///
@@ -67,6 +72,12 @@ impl DecoderCodec {
     pub const unsafe fn from_ptr(ptr: *const AVCodec) -> Self {
         Self(ptr)
     }
+
+    /// Returns the capabilities of the decoder, `None` if the [`DecoderCodec`] is empty.
+    pub fn capabilities(&self) -> Result<Option<CodecCapabilities>, FfmpegError> {
+        // Safety: The pointer is either null or points to a static codec.
+        unsafe { CodecCapabilities::from_ptr(self.0) }
+    }
 }
 
 /// A wrapper around an [`AVCodec`] pointer.
@@ -133,6 +144,12 @@ impl EncoderCodec {
     pub const unsafe fn from_ptr(ptr: *const AVCodec) -> Self {
         Self(ptr)
     }
+
+    /// Returns the capabilities of the encoder, `None` if the [`EncoderCodec`] is empty.
+    pub fn capabilities(&self) -> Result<Option<CodecCapabilities>, FfmpegError> {
+        // Safety: The pointer is either null or points to a static codec.
+        unsafe { CodecCapabilities::from_ptr(self.0) }
+    }
 }
 
 impl From<EncoderCodec> for *const AVCodec {
@@ -147,11 +164,262 @@ impl From<DecoderCodec> for *const AVCodec {
     }
 }
 
+/// Returns an iterator over the decoders of the linked ffmpeg build.
+pub fn decoders() -> impl Iterator<Item = DecoderCodec> {
+    codecs()
+        // Safety: `av_codec_is_decoder` is safe to call with a valid codec.
+        .filter(|&codec| unsafe { av_codec_is_decoder(codec) } != 0)
+        .map(DecoderCodec)
+}
+
+/// Returns an iterator over the encoders of the linked ffmpeg build.
+pub fn encoders() -> impl Iterator<Item = EncoderCodec> {
+    codecs()
+        // Safety: `av_codec_is_encoder` is safe to call with a valid codec.
+        .filter(|&codec| unsafe { av_codec_is_encoder(codec) } != 0)
+        .map(EncoderCodec)
+}
+
+fn codecs() -> impl Iterator<Item = *const AVCodec> {
+    let mut opaque = std::ptr::null_mut();
+
+    std::iter::from_fn(move || {
+        // Safety: `av_codec_iterate` is safe to call, `opaque` is only used by the iteration.
+        let codec = unsafe { av_codec_iterate(&mut opaque) };
+        (!codec.is_null()).then_some(codec)
+    })
+}
+
+/// A profile supported by a codec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecProfile {
+    /// The `AV_PROFILE_*` value of the profile.
+    pub id: i32,
+    /// The name of the profile.
+    pub name: String,
+}
+
+/// The capabilities of a decoder or an encoder.
+///
+/// An empty list of formats, rates or layouts means the codec does not restrict them, use the
+/// `supports_*` methods to check a value against the lists.
+#[derive(Debug)]
+pub struct CodecCapabilities {
+    /// The short name of the codec, like `libx264`.
+    pub name: String,
+    /// The descriptive name of the codec.
+    pub long_name: Option<String>,
+    /// The id of the codec.
+    pub id: AVCodecID,
+    /// The media type of the codec.
+    pub media_type: AVMediaType,
+    /// The supported pixel formats.
+    pub pixel_formats: Vec<AVPixelFormat>,
+    /// The supported frame rates.
+    pub frame_rates: Vec<Rational>,
+    /// The supported sample formats.
+    pub sample_formats: Vec<AVSampleFormat>,
+    /// The supported sample rates.
+    pub sample_rates: Vec<i32>,
+    /// The supported channel layouts.
+    pub channel_layouts: Vec<AudioChannelLayout>,
+    /// The recognized profiles.
+    pub profiles: Vec<CodecProfile>,
+    /// Whether the codec is experimental, which requires `strict` to be set to `experimental` to use it.
+    pub is_experimental: bool,
+    /// Whether the codec is backed by hardware.
+    pub is_hardware: bool,
+    /// The private options of the codec.
+    pub options: Vec<OptionInfo>,
+}
+
+impl CodecCapabilities {
+    /// # Safety
+    /// `codec` must be null or point to a valid [`AVCodec`].
+    unsafe fn from_ptr(codec: *const AVCodec) -> Result<Option<Self>, FfmpegError> {
+        // Safety: The caller guarantees the pointer is null or valid.
+        let Some(codec_ref) = (unsafe { codec.as_ref() }) else {
+            return Ok(None);
+        };
+
+        // Safety: The caller guarantees the pointer is valid.
+        let pixel_formats = unsafe { supported_config::<crate::ffi::AVPixelFormat>(codec, AV_CODEC_CONFIG_PIX_FORMAT) };
+        // Safety: The caller guarantees the pointer is valid.
+        let frame_rates = unsafe { supported_config::<AVRational>(codec, AV_CODEC_CONFIG_FRAME_RATE) };
+        // Safety: The caller guarantees the pointer is valid.
+        let sample_formats = unsafe { supported_config::<crate::ffi::AVSampleFormat>(codec, AV_CODEC_CONFIG_SAMPLE_FORMAT) };
+        // Safety: The caller guarantees the pointer is valid.
+        let sample_rates = unsafe { supported_config::<i32>(codec, AV_CODEC_CONFIG_SAMPLE_RATE) };
+        // Safety: The caller guarantees the pointer is valid.
+        let channel_layouts = unsafe { supported_config::<AVChannelLayout>(codec, AV_CODEC_CONFIG_CHANNEL_LAYOUT) };
+
+        let mut profiles = Vec::new();
+        let mut profile = codec_ref.profiles;
+        // Safety: The profiles are either null or an array terminated by `AV_PROFILE_UNKNOWN`.
+        while let Some(profile_ref) = unsafe { profile.as_ref() } {
+            if profile_ref.profile == AV_PROFILE_UNKNOWN {
+                break;
+            }
+
+            profiles.push(CodecProfile {
+                id: profile_ref.profile,
+                // Safety: The name of a profile is a static c-string.
+                name: unsafe { c_str(profile_ref.name) }.unwrap_or_default().to_owned(),
+            });
+
+            // Safety: The array is terminated, so the next element is valid.
+            profile = unsafe { profile.add(1) };
+        }
+
+        Ok(Some(Self {
+            // Safety: The name of a codec is a static c-string.
+            name: unsafe { c_str(codec_ref.name) }.unwrap_or_default().to_owned(),
+            // Safety: The long name of a codec is either null or a static c-string.
+            long_name: unsafe { c_str(codec_ref.long_name) }.map(ToOwned::to_owned),
+            id: AVCodecID(codec_ref.id as _),
+            media_type: AVMediaType(codec_ref.type_ as _),
+            pixel_formats: pixel_formats.iter().map(|&format| AVPixelFormat(format as _)).collect(),
+            frame_rates: frame_rates.iter().map(|&rate| Rational::from(rate)).collect(),
+            sample_formats: sample_formats.iter().map(|&format| AVSampleFormat(format as _)).collect(),
+            sample_rates: sample_rates.to_vec(),
+            channel_layouts: channel_layouts
+                .iter()
+                .map(AudioChannelLayout::copy_from)
+                .collect::<Result<_, _>>()?,
+            profiles,
+            is_experimental: codec_ref.capabilities & AV_CODEC_CAP_EXPERIMENTAL as i32 != 0,
+            is_hardware: codec_ref.capabilities & AV_CODEC_CAP_HARDWARE as i32 != 0,
+            // Safety: The private class of a codec is either null or a static class.
+            options: unsafe { class_options(codec_ref.priv_class) },
+        }))
+    }
+
+    /// Returns `true` if the codec supports the pixel format.
+    pub fn supports_pixel_format(&self, pixel_format: AVPixelFormat) -> bool {
+        self.pixel_formats.is_empty() || self.pixel_formats.contains(&pixel_format)
+    }
+
+    /// Returns `true` if the codec supports the frame rate.
+    pub fn supports_frame_rate(&self, frame_rate: Rational) -> bool {
+        self.frame_rates.is_empty() || self.frame_rates.contains(&frame_rate)
+    }
+
+    /// Returns `true` if the codec supports the sample format.
+    pub fn supports_sample_format(&self, sample_format: AVSampleFormat) -> bool {
+        self.sample_formats.is_empty() || self.sample_formats.contains(&sample_format)
+    }
+
+    /// Returns `true` if the codec supports the sample rate.
+    pub fn supports_sample_rate(&self, sample_rate: i32) -> bool {
+        self.sample_rates.is_empty() || self.sample_rates.contains(&sample_rate)
+    }
+
+    /// Returns `true` if the codec supports a channel layout with the given number of channels.
+    pub fn supports_channel_count(&self, channels: i32) -> bool {
+        self.channel_layouts.is_empty() || self.channel_layouts.iter().any(|layout| layout.channel_count() == channels)
+    }
+
+    /// Returns the profile with the given name.
+    pub fn profile(&self, name: &str) -> Option<&CodecProfile> {
+        self.profiles.iter().find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the private option with the given name.
+    pub fn option(&self, name: &str) -> Option<&OptionInfo> {
+        self.options.iter().find(|option| option.name == name)
+    }
+}
+
+/// Returns the values of a codec configuration, empty if the codec does not restrict it.
+///
+/// # Safety
+/// `codec` must point to a valid [`AVCodec`] and `T` must be the type of the values of `config`.
+unsafe fn supported_config<T>(codec: *const AVCodec, config: AVCodecConfig) -> &'static [T] {
+    let mut configs: *const std::ffi::c_void = std::ptr::null();
+    let mut count = 0;
+
+    // Safety: `avcodec_get_supported_config` is safe to call without a context and with a valid codec.
+    let ret = unsafe { avcodec_get_supported_config(std::ptr::null(), codec, config, 0, &mut configs, &mut count) };
+    if ret < 0 || configs.is_null() || count <= 0 {
+        return &[];
+    }
+
+    // Safety: The configs are a static array of `count` values of type `T`.
+    unsafe { std::slice::from_raw_parts(configs.cast::<T>(), count as usize) }
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
-    use crate::codec::{AVCodecID, DecoderCodec, EncoderCodec};
+    use crate::codec::{AVCodecID, DecoderCodec, EncoderCodec, decoders, encoders};
     use crate::ffi::{AVCodec, avcodec_find_decoder, avcodec_find_encoder};
+    use crate::{AVMediaType, AVPixelFormat, AVSampleFormat};
+
+    #[test]
+    fn test_codec_iteration() {
+        let mpeg4 = EncoderCodec::new(AVCodecID::Mpeg4).expect("MPEG4 encoder should be available");
+        assert!(encoders().any(|codec| codec == mpeg4), "Expected the MPEG4 encoder");
+
+        let h264 = DecoderCodec::new(AVCodecID::H264).expect("H264 decoder should be available");
+        assert!(decoders().any(|codec| codec == h264), "Expected the H264 decoder");
+        assert!(
+            !encoders().any(|codec| codec.as_ptr() == h264.as_ptr()),
+            "Expected no decoders"
+        );
+    }
+
+    #[test]
+    fn test_encoder_capabilities() {
+        let mpeg4 = EncoderCodec::new(AVCodecID::Mpeg4)
+            .expect("MPEG4 encoder should be available")
+            .capabilities()
+            .expect("Failed to get capabilities")
+            .expect("Expected capabilities");
+
+        assert_eq!(mpeg4.name, "mpeg4");
+        assert_eq!(mpeg4.id, AVCodecID::Mpeg4);
+        assert_eq!(mpeg4.media_type, AVMediaType::Video);
+        assert!(mpeg4.supports_pixel_format(AVPixelFormat::Yuv420p));
+        assert!(!mpeg4.supports_pixel_format(AVPixelFormat::Rgb24));
+        assert!(mpeg4.sample_formats.is_empty());
+        assert!(mpeg4.option("data_partitioning").is_some(), "Expected the private options");
+
+        let aac = EncoderCodec::new(AVCodecID::Aac)
+            .expect("AAC encoder should be available")
+            .capabilities()
+            .expect("Failed to get capabilities")
+            .expect("Expected capabilities");
+
+        assert_eq!(aac.media_type, AVMediaType::Audio);
+        assert_eq!(aac.sample_formats, [AVSampleFormat::Fltp]);
+        assert!(aac.supports_sample_rate(48000));
+        assert!(!aac.supports_sample_rate(1234));
+        assert!(aac.supports_channel_count(2));
+
+        let coder = aac.option("aac_coder").expect("Expected the aac_coder option");
+        assert!(coder.constant("twoloop").is_some(), "Expected the aac_coder constants");
+    }
+
+    #[test]
+    fn test_decoder_capabilities() {
+        let h264 = DecoderCodec::new(AVCodecID::H264)
+            .expect("H264 decoder should be available")
+            .capabilities()
+            .expect("Failed to get capabilities")
+            .expect("Expected capabilities");
+
+        assert_eq!(h264.name, "h264");
+        assert_eq!(h264.media_type, AVMediaType::Video);
+        assert!(h264.profile("high").is_some(), "Expected the High profile");
+        assert!(!h264.is_experimental);
+
+        assert!(
+            DecoderCodec::empty()
+                .capabilities()
+                .expect("Failed to get capabilities")
+                .is_none()
+        );
+    }
 
     #[test]
     fn test_decoder_codec_debug_null() {
@@ -8,9 +8,10 @@ use crate::packet::Packet;
 use crate::rational::Rational;
 use crate::smart_object::SmartPtr;
 use crate::stream::Stream;
+use crate::subtitle::Subtitle;
 use crate::{AVCodecID, AVMediaType, AVPixelFormat, AVSampleFormat};
 
-/// Either a [`VideoDecoder`] or an [`AudioDecoder`].
+/// Either a [`VideoDecoder`], an [`AudioDecoder`] or a [`SubtitleDecoder`].
 ///
 /// This is the most common way to interact with decoders.
 #[derive(Debug)]
@@ -19,6 +20,8 @@ pub enum Decoder {
     Video(VideoDecoder),
     /// An audio decoder.
     Audio(AudioDecoder),
+    /// A subtitle decoder.
+    Subtitle(SubtitleDecoder),
 }
 
 /// A generic decoder that can be used to decode any type of media.
@@ -68,6 +71,21 @@ impl std::fmt::Debug for AudioDecoder {
     }
 }
 
+/// A subtitle decoder.
+///
+/// Subtitles are not decoded into frames, use [`SubtitleDecoder::decode`] instead of
+/// [`GenericDecoder::send_packet`] and [`GenericDecoder::receive_frame`].
+pub struct SubtitleDecoder(GenericDecoder);
+
+impl std::fmt::Debug for SubtitleDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubtitleDecoder")
+            .field("time_base", &self.time_base())
+            .field("codec_id", &self.codec_id())
+            .finish()
+    }
+}
+
 /// Options for creating a [`Decoder`].
 pub struct DecoderOptions {
     /// The codec to use for decoding.
@@ -140,7 +158,10 @@ impl Decoder {
                 unsafe { av_guess_frame_rate(format_context, ist.as_ptr() as *mut AVStream, std::ptr::null_mut()) };
         }
 
-        if matches!(AVMediaType(decoder_mut.codec_type), AVMediaType::Video | AVMediaType::Audio) {
+        if matches!(
+            AVMediaType(decoder_mut.codec_type),
+            AVMediaType::Video | AVMediaType::Audio | AVMediaType::Subtitle
+        ) {
             // Safety: `codec` is a valid pointer, and `decoder` is a valid pointer.
             FfmpegErrorCode(unsafe { avcodec_open2(decoder_mut, codec.as_ptr(), std::ptr::null_mut()) }).result()?;
         }
@@ -148,6 +169,7 @@ impl Decoder {
         Ok(match AVMediaType(decoder_mut.codec_type) {
             AVMediaType::Video => Self::Video(VideoDecoder(GenericDecoder { decoder })),
             AVMediaType::Audio => Self::Audio(AudioDecoder(GenericDecoder { decoder })),
+            AVMediaType::Subtitle => Self::Subtitle(SubtitleDecoder(GenericDecoder { decoder })),
             _ => Err(FfmpegError::NoDecoder)?,
         })
     }
@@ -167,6 +189,14 @@ impl Decoder {
             _ => Err(self),
         }
     }
+
+    /// Returns the subtitle decoder if the decoder is a subtitle decoder.
+    pub fn subtitle(self) -> Result<SubtitleDecoder, Self> {
+        match self {
+            Self::Subtitle(subtitle) => Ok(subtitle),
+            _ => Err(self),
+        }
+    }
 }
 
 impl GenericDecoder {
@@ -298,6 +328,67 @@ impl std::ops::DerefMut for AudioDecoder {
     }
 }
 
+impl SubtitleDecoder {
+    /// Returns the codec id of the decoder.
+    pub const fn codec_id(&self) -> AVCodecID {
+        AVCodecID(self.0.decoder.as_deref_except().codec_id as _)
+    }
+
+    /// Returns the ASS header of the subtitles, used by text subtitle encoders.
+    pub fn subtitle_header(&self) -> Option<&[u8]> {
+        let decoder = self.0.decoder.as_deref_except();
+        if decoder.subtitle_header.is_null() || decoder.subtitle_header_size <= 0 {
+            return None;
+        }
+
+        // Safety: `subtitle_header` points to `subtitle_header_size` bytes owned by the decoder.
+        Some(unsafe { std::slice::from_raw_parts(decoder.subtitle_header, decoder.subtitle_header_size as usize) })
+    }
+
+    /// Decodes a packet into a subtitle.
+    ///
+    /// Returns `None` if the packet did not complete a subtitle. The timestamps of the subtitle
+    /// are in the time base of the stream.
+    pub fn decode(&mut self, packet: &Packet) -> Result<Option<Subtitle>, FfmpegError> {
+        let time_base = self.0.decoder.as_deref_except().pkt_timebase;
+        let mut subtitle = Subtitle::new(time_base);
+        let mut got_subtitle = 0;
+
+        // Safety: `self.decoder`, `subtitle` and `packet` are valid pointers.
+        FfmpegErrorCode(unsafe {
+            avcodec_decode_subtitle2(
+                self.0.decoder.as_mut_ptr(),
+                subtitle.as_mut_ptr(),
+                &mut got_subtitle,
+                packet.as_ptr(),
+            )
+        })
+        .result()?;
+
+        Ok((got_subtitle != 0).then_some(subtitle))
+    }
+
+    /// Drains a subtitle buffered by the decoder, this should be called after the last packet
+    /// until it returns `None`.
+    pub fn decode_eof(&mut self) -> Result<Option<Subtitle>, FfmpegError> {
+        self.decode(&Packet::new()?)
+    }
+}
+
+impl std::ops::Deref for SubtitleDecoder {
+    type Target = GenericDecoder;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for SubtitleDecoder {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
@@ -334,6 +425,7 @@ mod tests {
         let generic_decoder = match decoder {
             Decoder::Video(video_decoder) => video_decoder.0,
             Decoder::Audio(audio_decoder) => audio_decoder.0,
+            Decoder::Subtitle(subtitle_decoder) => subtitle_decoder.0,
         };
 
         insta::assert_debug_snapshot!(generic_decoder, @r"
@@ -9,6 +9,8 @@ use crate::io::Output;
 use crate::packet::Packet;
 use crate::rational::Rational;
 use crate::smart_object::SmartPtr;
+use crate::subtitle::{DEFAULT_ASS_HEADER, Subtitle};
+use crate::timestamp::Timestamp;
 use crate::{AVFormatFlags, AVPixelFormat, AVSampleFormat};
 
 /// Represents an encoder.
@@ -292,6 +294,192 @@ impl Encoder {
     pub const fn outgoing_time_base(&self) -> Rational {
         self.outgoing_time_base
     }
+
+    /// Returns the number of samples per channel every audio frame sent to the encoder must have.
+    ///
+    /// Returns `None` for video encoders and audio encoders accepting frames of any size.
+    /// An [`AudioFrameAdapter`](crate::audio_fifo::AudioFrameAdapter) can be used to split frames to this size.
+    pub const fn frame_size(&self) -> Option<i32> {
+        let frame_size = self.encoder.as_deref_except().frame_size;
+        if frame_size > 0 { Some(frame_size) } else { None }
+    }
+}
+
+/// Represents the settings for a subtitle encoder.
+#[derive(bon::Builder)]
+pub struct SubtitleEncoderSettings {
+    /// The ASS header of the subtitles, [`DEFAULT_ASS_HEADER`] is used if not set.
+    subtitle_header: Option<Vec<u8>>,
+    width: Option<i32>,
+    height: Option<i32>,
+    codec_specific_options: Option<Dictionary>,
+}
+
+impl SubtitleEncoderSettings {
+    fn apply(self, encoder: &mut AVCodecContext) -> Result<(), FfmpegError> {
+        let header = self.subtitle_header.unwrap_or_else(|| DEFAULT_ASS_HEADER.as_bytes().to_vec());
+        let header_size = i32::try_from(header.len()).map_err(|_| FfmpegError::Arguments("subtitle_header is too large"))?;
+
+        // Safety: `av_mallocz` is safe to call, the header is freed by `avcodec_free_context`.
+        let header_ptr = unsafe { av_mallocz(header.len() + 1) } as *mut u8;
+        if header_ptr.is_null() {
+            return Err(FfmpegError::Alloc);
+        }
+
+        // Safety: `header_ptr` was allocated with room for the header and a nul terminator.
+        unsafe { std::ptr::copy_nonoverlapping(header.as_ptr(), header_ptr, header.len()) };
+
+        // Safety: The previous header, if any, was allocated by FFmpeg.
+        unsafe { av_freep(&mut encoder.subtitle_header as *mut *mut u8 as *mut _) };
+        encoder.subtitle_header = header_ptr;
+        encoder.subtitle_header_size = header_size;
+        encoder.width = self.width.unwrap_or(encoder.width);
+        encoder.height = self.height.unwrap_or(encoder.height);
+
+        Ok(())
+    }
+}
+
+/// Represents a subtitle encoder.
+///
+/// Subtitles are not encoded from frames, so this is separate from [`Encoder`].
+pub struct SubtitleEncoder {
+    outgoing_time_base: Rational,
+    encoder: SmartPtr<AVCodecContext>,
+    stream_index: i32,
+}
+
+/// Safety: `SubtitleEncoder` can be sent between threads.
+unsafe impl Send for SubtitleEncoder {}
+
+impl SubtitleEncoder {
+    /// The size of the buffer subtitles are encoded into, the same as the ffmpeg cli uses.
+    const MAX_PACKET_SIZE: i32 = 1024 * 1024;
+
+    /// Creates a new subtitle encoder.
+    pub fn new<T: Send + Sync>(
+        codec: EncoderCodec,
+        output: &mut Output<T>,
+        outgoing_time_base: impl Into<Rational>,
+        mut settings: SubtitleEncoderSettings,
+    ) -> Result<Self, FfmpegError> {
+        if codec.as_ptr().is_null() {
+            return Err(FfmpegError::NoEncoder);
+        }
+
+        let global_header = output
+            .output_flags()
+            .is_some_and(|flags| flags & AVFormatFlags::GlobalHeader != 0);
+
+        let destructor = |ptr: &mut *mut AVCodecContext| {
+            // Safety: `avcodec_free_context` is safe to call when the pointer is valid, and it is because it comes from `avcodec_alloc_context3`.
+            unsafe { avcodec_free_context(ptr) };
+        };
+
+        // Safety: `avcodec_alloc_context3` is safe to call.
+        let encoder = unsafe { avcodec_alloc_context3(codec.as_ptr()) };
+
+        // Safety: The pointer here is valid and the destructor has been setup to handle the cleanup.
+        let mut encoder = unsafe { SmartPtr::wrap_non_null(encoder, destructor) }.ok_or(FfmpegError::Alloc)?;
+
+        let mut ost = output.add_stream(None).ok_or(FfmpegError::NoStream)?;
+
+        let encoder_mut = encoder.as_deref_mut_except();
+
+        // Subtitle timestamps are passed to the encoder in `AV_TIME_BASE`.
+        encoder_mut.time_base = Timestamp::AV_TIME_BASE.into();
+
+        let mut codec_options = settings.codec_specific_options.take();
+
+        let codec_options_ptr = codec_options
+            .as_mut()
+            .map(|options| options.as_mut_ptr_ref() as *mut *mut _)
+            .unwrap_or(std::ptr::null_mut());
+
+        settings.apply(encoder_mut)?;
+
+        if global_header {
+            encoder_mut.flags |= AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+        }
+
+        // Safety: `avcodec_open2` is safe to call, 'encoder' and 'codec' and
+        // 'codec_options_ptr' are a valid pointers.
+        FfmpegErrorCode(unsafe { avcodec_open2(encoder_mut, codec.as_ptr(), codec_options_ptr) }).result()?;
+
+        // Safety: The pointer here is valid.
+        let ost_mut = unsafe { NonNull::new(ost.as_mut_ptr()).ok_or(FfmpegError::NoStream)?.as_mut() };
+
+        // Safety: `avcodec_parameters_from_context` is safe to call, 'ost' and
+        // 'encoder' are valid pointers.
+        FfmpegErrorCode(unsafe { avcodec_parameters_from_context(ost_mut.codecpar, encoder_mut) }).result()?;
+
+        let outgoing_time_base = outgoing_time_base.into();
+        ost.set_time_base(outgoing_time_base);
+
+        Ok(Self {
+            outgoing_time_base,
+            encoder,
+            stream_index: ost.index(),
+        })
+    }
+
+    /// Encodes a subtitle into a packet.
+    ///
+    /// Returns `None` if the encoder produced no data for the subtitle. The subtitle must have a
+    /// start time, see [`Subtitle::set_timing`].
+    pub fn encode(&mut self, subtitle: &Subtitle) -> Result<Option<Packet>, FfmpegError> {
+        let start = subtitle
+            .start()
+            .ok_or(FfmpegError::Arguments("subtitle must have a start time"))?;
+        let end = subtitle.end();
+
+        // Safety: The subtitle pointer is valid, the copy only borrows the rects and is not freed.
+        let mut av_subtitle = unsafe { *subtitle.as_ptr() };
+
+        // Move the display offset into the pts, like the ffmpeg cli does.
+        av_subtitle.pts = start.rescale(Timestamp::AV_TIME_BASE).value();
+        av_subtitle.end_display_time = av_subtitle.end_display_time.saturating_sub(av_subtitle.start_display_time);
+        av_subtitle.start_display_time = 0;
+
+        let mut packet = Packet::new()?;
+
+        // Safety: `av_new_packet` is safe to call, the packet is valid.
+        FfmpegErrorCode(unsafe { av_new_packet(packet.as_mut_ptr(), Self::MAX_PACKET_SIZE) }).result()?;
+
+        // Safety: The packet is valid.
+        let data = unsafe { (*packet.as_mut_ptr()).data };
+
+        // Safety: `self.encoder` and `av_subtitle` are valid, `data` has `MAX_PACKET_SIZE` bytes.
+        let size = FfmpegErrorCode(unsafe {
+            avcodec_encode_subtitle(self.encoder.as_mut_ptr(), data, Self::MAX_PACKET_SIZE, &av_subtitle)
+        })
+        .result()?;
+
+        if size == 0 {
+            return Ok(None);
+        }
+
+        // Safety: `av_shrink_packet` is safe to call, `size` is smaller than the packet size.
+        unsafe { av_shrink_packet(packet.as_mut_ptr(), size) };
+
+        let start = start.rescale(self.outgoing_time_base);
+        packet.set_pts(Some(start.value()));
+        packet.set_dts(Some(start.value()));
+        packet.set_duration(end.map(|end| end.rescale(self.outgoing_time_base).value() - start.value()));
+        packet.set_stream_index(self.stream_index);
+
+        Ok(Some(packet))
+    }
+
+    /// Returns the stream index of the encoder.
+    pub const fn stream_index(&self) -> i32 {
+        self.stream_index
+    }
+
+    /// Returns the outgoing time base of the encoder.
+    pub const fn outgoing_time_base(&self) -> Rational {
+        self.outgoing_time_base
+    }
 }
 
 #[cfg(test)]
@@ -306,11 +494,16 @@ mod tests {
     use crate::codec::EncoderCodec;
     use crate::decoder::Decoder;
     use crate::dict::Dictionary;
-    use crate::encoder::{AudioChannelLayout, AudioEncoderSettings, Encoder, EncoderSettings, VideoEncoderSettings};
+    use crate::encoder::{
+        AudioChannelLayout, AudioEncoderSettings, Encoder, EncoderSettings, SubtitleEncoder, SubtitleEncoderSettings,
+        VideoEncoderSettings,
+    };
     use crate::error::FfmpegError;
     use crate::ffi::AVCodecContext;
     use crate::io::{Input, Output, OutputOptions};
     use crate::rational::Rational;
+    use crate::subtitle::Subtitle;
+    use crate::timestamp::Timestamp;
     use crate::{AVChannelOrder, AVCodecID, AVMediaType, AVPixelFormat, AVSampleFormat, file_path};
 
     #[test]
@@ -601,6 +794,45 @@ mod tests {
         assert_eq!(options.unwrap().get(c"bitrate"), Some(c"128k"));
     }
 
+    #[test]
+    fn test_encoder_frame_size() {
+        let mut output = Output::seekable(
+            std::io::Cursor::new(Vec::new()),
+            OutputOptions::builder().format_name("mp4").unwrap().build(),
+        )
+        .expect("Failed to create Output");
+
+        let time_base = Rational::static_new::<1, 48000>();
+        let aac = Encoder::new(
+            EncoderCodec::new(AVCodecID::Aac).expect("Missing AAC encoder"),
+            &mut output,
+            time_base,
+            time_base,
+            AudioEncoderSettings::builder()
+                .sample_rate(48000)
+                .ch_layout(AudioChannelLayout::new(2).expect("Failed to create channel layout"))
+                .sample_fmt(AVSampleFormat::Fltp)
+                .build(),
+        )
+        .expect("Failed to create AAC encoder");
+        assert_eq!(aac.frame_size(), Some(1024));
+
+        let video = Encoder::new(
+            EncoderCodec::new(AVCodecID::Mpeg4).expect("Missing MPEG-4 encoder"),
+            &mut output,
+            Rational::static_new::<1, 30>(),
+            Rational::static_new::<1, 30>(),
+            VideoEncoderSettings::builder()
+                .width(64)
+                .height(64)
+                .frame_rate(Rational::static_new::<30, 1>())
+                .pixel_format(AVPixelFormat::Yuv420p)
+                .build(),
+        )
+        .expect("Failed to create MPEG-4 encoder");
+        assert_eq!(video.frame_size(), None);
+    }
+
     #[test]
     fn test_from_video_encoder_settings() {
         let sample_aspect_ratio = AVRational { num: 1, den: 1 };
@@ -859,4 +1091,76 @@ mod tests {
         )
         .expect("Failed to create new Encoder");
     }
+
+    #[test]
+    fn test_subtitle_encoder_webvtt_roundtrip() {
+        let mut output = Output::seekable(
+            std::io::Cursor::new(Vec::new()),
+            OutputOptions::builder().format_name("webvtt").unwrap().build(),
+        )
+        .expect("Failed to create Output");
+
+        let time_base = Rational::static_new::<1, 1000>();
+        let mut encoder = SubtitleEncoder::new(
+            EncoderCodec::new(AVCodecID::WebVtt).expect("Missing WebVTT encoder"),
+            &mut output,
+            time_base,
+            SubtitleEncoderSettings::builder().build(),
+        )
+        .expect("Failed to create SubtitleEncoder");
+
+        output.write_header().expect("Failed to write header");
+
+        let cues = [(1000, 2500, "Hello\nworld"), (3000, 4000, "Second cue")];
+        for (start, end, text) in cues {
+            let mut subtitle = Subtitle::new(time_base);
+            subtitle.set_timing(Timestamp::from_millis(start), Some(Timestamp::from_millis(end)));
+            subtitle.add_text(text).expect("Failed to add text");
+
+            let packet = encoder
+                .encode(&subtitle)
+                .expect("Failed to encode subtitle")
+                .expect("Expected a packet");
+            assert_eq!(packet.stream_index(), encoder.stream_index());
+            assert_eq!(packet.pts(), Some(start));
+            assert_eq!(packet.duration(), Some(end - start));
+            output.write_packet(&packet).expect("Failed to write packet");
+        }
+
+        output.write_trailer().expect("Failed to write trailer");
+
+        let data = output.into_inner().into_inner();
+        let document = String::from_utf8(data.clone()).expect("Expected a UTF-8 document");
+        assert!(document.starts_with("WEBVTT"), "Expected a WebVTT document: {document}");
+        assert!(document.contains("Hello\nworld"), "Expected the first cue: {document}");
+        assert!(document.contains("Second cue"), "Expected the second cue: {document}");
+
+        let mut input = Input::seekable(std::io::Cursor::new(data)).expect("Failed to open WebVTT document");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Subtitle).expect("Expected a subtitle stream");
+        let mut decoder = Decoder::new(&stream)
+            .expect("Failed to create decoder")
+            .subtitle()
+            .expect("Expected a subtitle decoder");
+        let stream_time_base = stream.time_base();
+
+        let mut decoded = Vec::new();
+        while let Some(packet) = input.receive_packet().expect("Failed to receive packet") {
+            decoded.extend(decoder.decode(&packet).expect("Failed to decode packet"));
+        }
+        while let Some(subtitle) = decoder.decode_eof().expect("Failed to drain decoder") {
+            decoded.push(subtitle);
+        }
+
+        assert_eq!(decoded.len(), cues.len());
+        for (subtitle, (start, end, text)) in decoded.iter().zip(cues) {
+            assert_eq!(subtitle.time_base(), stream_time_base);
+            assert_eq!(
+                subtitle.start(),
+                Some(Timestamp::from_millis(start).rescale(stream_time_base))
+            );
+            assert_eq!(subtitle.end(), Some(Timestamp::from_millis(end).rescale(stream_time_base)));
+            assert_eq!(subtitle.plain_text(), text);
+        }
+    }
 }
@@ -36,3 +36,18 @@ pub use av_pkt_flags::*;
 
 mod av_discard;
 pub use av_discard::*;
+
+mod av_disposition;
+pub use av_disposition::*;
+
+mod av_packet_side_data_type;
+pub use av_packet_side_data_type::*;
+
+mod av_frame_side_data_type;
+pub use av_frame_side_data_type::*;
+
+mod av_subtitle_type;
+pub use av_subtitle_type::*;
+
+mod av_option_type;
+pub use av_option_type::*;
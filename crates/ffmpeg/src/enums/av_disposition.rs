@@ -0,0 +1,158 @@
+use nutype_enum::{bitwise_enum, nutype_enum};
+
+use crate::ffi::*;
+
+const _: () = {
+    assert!(std::mem::size_of::<AVDisposition>() == std::mem::size_of_val(&AV_DISPOSITION_DEFAULT));
+};
+
+nutype_enum! {
+    /// Stream disposition flags used in FFmpeg's `AVStream`.
+    ///
+    /// These flags describe the role of a stream within its container, a stream can
+    /// have any combination of them.
+    ///
+    /// See the official FFmpeg documentation:
+    /// <https://ffmpeg.org/doxygen/trunk/group__lavf__stream.html>
+    pub enum AVDisposition(i32) {
+        /// No disposition flags are set.
+        /// - **Used for**: Streams without any special role.
+        /// - **Binary representation**: `0b0`
+        None = 0,
+
+        /// The stream should be chosen by default among other streams of the same type.
+        /// - **Used for**: Selecting the default audio, video or subtitle track.
+        /// - **Binary representation**: `0b1`
+        /// - **Equivalent to**: `AV_DISPOSITION_DEFAULT`
+        Default = AV_DISPOSITION_DEFAULT as _,
+
+        /// The stream is not in the original language.
+        /// - **Used for**: Dubbed audio tracks.
+        /// - **Binary representation**: `0b10`
+        /// - **Equivalent to**: `AV_DISPOSITION_DUB`
+        Dub = AV_DISPOSITION_DUB as _,
+
+        /// The stream is in the original language.
+        /// - **Used for**: Original language audio tracks.
+        /// - **Binary representation**: `0b100`
+        /// - **Equivalent to**: `AV_DISPOSITION_ORIGINAL`
+        Original = AV_DISPOSITION_ORIGINAL as _,
+
+        /// The stream is a commentary track.
+        /// - **Used for**: Director or cast commentary.
+        /// - **Binary representation**: `0b1000`
+        /// - **Equivalent to**: `AV_DISPOSITION_COMMENT`
+        Comment = AV_DISPOSITION_COMMENT as _,
+
+        /// The stream contains song lyrics.
+        /// - **Used for**: Lyrics subtitle tracks.
+        /// - **Binary representation**: `0b10000`
+        /// - **Equivalent to**: `AV_DISPOSITION_LYRICS`
+        Lyrics = AV_DISPOSITION_LYRICS as _,
+
+        /// The stream contains karaoke audio.
+        /// - **Used for**: Karaoke tracks.
+        /// - **Binary representation**: `0b100000`
+        /// - **Equivalent to**: `AV_DISPOSITION_KARAOKE`
+        Karaoke = AV_DISPOSITION_KARAOKE as _,
+
+        /// The stream must be shown even if the user did not enable it.
+        /// - **Used for**: Forced subtitles, like translations of on-screen text.
+        /// - **Binary representation**: `0b1000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_FORCED`
+        Forced = AV_DISPOSITION_FORCED as _,
+
+        /// The stream is intended for hearing impaired audiences.
+        /// - **Used for**: SDH subtitles.
+        /// - **Binary representation**: `0b10000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_HEARING_IMPAIRED`
+        HearingImpaired = AV_DISPOSITION_HEARING_IMPAIRED as _,
+
+        /// The stream is intended for visually impaired audiences.
+        /// - **Used for**: Audio description tracks.
+        /// - **Binary representation**: `0b100000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_VISUAL_IMPAIRED`
+        VisualImpaired = AV_DISPOSITION_VISUAL_IMPAIRED as _,
+
+        /// The audio stream contains music and sound effects without voice.
+        /// - **Used for**: Clean audio tracks for dubbing.
+        /// - **Binary representation**: `0b1000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_CLEAN_EFFECTS`
+        CleanEffects = AV_DISPOSITION_CLEAN_EFFECTS as _,
+
+        /// The stream is a single picture attached to the file.
+        /// - **Used for**: Cover art.
+        /// - **Binary representation**: `0b10000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_ATTACHED_PIC`
+        AttachedPic = AV_DISPOSITION_ATTACHED_PIC as _,
+
+        /// The stream is sparse and contains thumbnail images.
+        /// - **Used for**: Timed thumbnail tracks.
+        /// - **Binary representation**: `0b100000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_TIMED_THUMBNAILS`
+        TimedThumbnails = AV_DISPOSITION_TIMED_THUMBNAILS as _,
+
+        /// The stream is not part of the depicted scene.
+        /// - **Used for**: Narration or background music.
+        /// - **Binary representation**: `0b1000000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_NON_DIEGETIC`
+        NonDiegetic = AV_DISPOSITION_NON_DIEGETIC as _,
+
+        /// The subtitle stream contains captions.
+        /// - **Used for**: Transcriptions of speech and sounds.
+        /// - **Binary representation**: `0b10000000000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_CAPTIONS`
+        Captions = AV_DISPOSITION_CAPTIONS as _,
+
+        /// The subtitle stream contains descriptions of the video content.
+        /// - **Used for**: Textual descriptions for visually impaired audiences.
+        /// - **Binary representation**: `0b100000000000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_DESCRIPTIONS`
+        Descriptions = AV_DISPOSITION_DESCRIPTIONS as _,
+
+        /// The subtitle stream contains metadata that is not meant to be shown.
+        /// - **Used for**: Timed metadata tracks.
+        /// - **Binary representation**: `0b1000000000000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_METADATA`
+        Metadata = AV_DISPOSITION_METADATA as _,
+
+        /// The stream is intended to be mixed with another stream before presentation.
+        /// - **Used for**: Additional layers of spatial audio.
+        /// - **Binary representation**: `0b10000000000000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_DEPENDENT`
+        Dependent = AV_DISPOSITION_DEPENDENT as _,
+
+        /// The video stream contains still images.
+        /// - **Used for**: Image sequences that are not meant to be played as video.
+        /// - **Binary representation**: `0b100000000000000000000`
+        /// - **Equivalent to**: `AV_DISPOSITION_STILL_IMAGE`
+        StillImage = AV_DISPOSITION_STILL_IMAGE as _,
+    }
+}
+
+bitwise_enum!(AVDisposition);
+
+impl AVDisposition {
+    /// Returns true if all the flags of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl PartialEq<i32> for AVDisposition {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<u32> for AVDisposition {
+    fn from(value: u32) -> Self {
+        AVDisposition(value as _)
+    }
+}
+
+impl From<AVDisposition> for u32 {
+    fn from(value: AVDisposition) -> Self {
+        value.0 as u32
+    }
+}
@@ -0,0 +1,108 @@
+use nutype_enum::nutype_enum;
+
+use crate::ffi::*;
+
+const _: () = {
+    assert!(std::mem::size_of::<AVFrameSideDataType>() == std::mem::size_of_val(&AV_FRAME_DATA_A53_CC));
+};
+
+nutype_enum! {
+    /// Side data types that can be attached to an `AVFrame`.
+    ///
+    /// See the official FFmpeg documentation:
+    /// <https://ffmpeg.org/doxygen/trunk/group__lavu__frame.html>
+    pub enum AVFrameSideDataType(i32) {
+        /// The pan scan area, `AVPanScan`.
+        /// - **Used for**: MPEG-2 pan and scan.
+        /// - **Equivalent to**: `AV_FRAME_DATA_PANSCAN`
+        PanScan = AV_FRAME_DATA_PANSCAN as _,
+
+        /// ATSC A53 Part 4 closed captions.
+        /// - **Used for**: CEA-608 and CEA-708 captions.
+        /// - **Equivalent to**: `AV_FRAME_DATA_A53_CC`
+        A53Cc = AV_FRAME_DATA_A53_CC as _,
+
+        /// Stereoscopic 3D video information.
+        /// - **Used for**: Side by side or top bottom 3D video.
+        /// - **Equivalent to**: `AV_FRAME_DATA_STEREO3D`
+        Stereo3d = AV_FRAME_DATA_STEREO3D as _,
+
+        /// A 3x3 transformation matrix describing how to display the frame.
+        /// - **Used for**: Rotation and flipping of phone recordings.
+        /// - **Equivalent to**: `AV_FRAME_DATA_DISPLAYMATRIX`
+        DisplayMatrix = AV_FRAME_DATA_DISPLAYMATRIX as _,
+
+        /// The number of samples to skip at the start and end of the frame.
+        /// - **Used for**: Encoder delay and padding.
+        /// - **Equivalent to**: `AV_FRAME_DATA_SKIP_SAMPLES`
+        SkipSamples = AV_FRAME_DATA_SKIP_SAMPLES as _,
+
+        /// The mastering display color volume, `AVMasteringDisplayMetadata`.
+        /// - **Used for**: HDR10 static metadata.
+        /// - **Equivalent to**: `AV_FRAME_DATA_MASTERING_DISPLAY_METADATA`
+        MasteringDisplayMetadata = AV_FRAME_DATA_MASTERING_DISPLAY_METADATA as _,
+
+        /// The spherical video projection, `AVSphericalMapping`.
+        /// - **Used for**: 360 degree video.
+        /// - **Equivalent to**: `AV_FRAME_DATA_SPHERICAL`
+        Spherical = AV_FRAME_DATA_SPHERICAL as _,
+
+        /// The content light level, `AVContentLightMetadata`.
+        /// - **Used for**: HDR10 static metadata.
+        /// - **Equivalent to**: `AV_FRAME_DATA_CONTENT_LIGHT_LEVEL`
+        ContentLightLevel = AV_FRAME_DATA_CONTENT_LIGHT_LEVEL as _,
+
+        /// An ICC profile.
+        /// - **Used for**: Color management of images.
+        /// - **Equivalent to**: `AV_FRAME_DATA_ICC_PROFILE`
+        IccProfile = AV_FRAME_DATA_ICC_PROFILE as _,
+
+        /// SMPTE ST 12-1 timecodes.
+        /// - **Used for**: Broadcast timecodes.
+        /// - **Equivalent to**: `AV_FRAME_DATA_S12M_TIMECODE`
+        S12mTimecode = AV_FRAME_DATA_S12M_TIMECODE as _,
+
+        /// HDR10+ dynamic metadata, `AVDynamicHDRPlus`.
+        /// - **Used for**: HDR10+ content.
+        /// - **Equivalent to**: `AV_FRAME_DATA_DYNAMIC_HDR_PLUS`
+        DynamicHdrPlus = AV_FRAME_DATA_DYNAMIC_HDR_PLUS as _,
+
+        /// User data unregistered SEI messages.
+        /// - **Used for**: Encoder information and custom metadata.
+        /// - **Equivalent to**: `AV_FRAME_DATA_SEI_UNREGISTERED`
+        SeiUnregistered = AV_FRAME_DATA_SEI_UNREGISTERED as _,
+
+        /// Film grain synthesis parameters, `AVFilmGrainParams`.
+        /// - **Used for**: AV1 film grain.
+        /// - **Equivalent to**: `AV_FRAME_DATA_FILM_GRAIN_PARAMS`
+        FilmGrainParams = AV_FRAME_DATA_FILM_GRAIN_PARAMS as _,
+
+        /// The raw Dolby Vision RPU.
+        /// - **Used for**: Dolby Vision streams.
+        /// - **Equivalent to**: `AV_FRAME_DATA_DOVI_RPU_BUFFER`
+        DoviRpuBuffer = AV_FRAME_DATA_DOVI_RPU_BUFFER as _,
+
+        /// The parsed Dolby Vision metadata, `AVDOVIMetadata`.
+        /// - **Used for**: Dolby Vision streams.
+        /// - **Equivalent to**: `AV_FRAME_DATA_DOVI_METADATA`
+        DoviMetadata = AV_FRAME_DATA_DOVI_METADATA as _,
+    }
+}
+
+impl PartialEq<i32> for AVFrameSideDataType {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<u32> for AVFrameSideDataType {
+    fn from(value: u32) -> Self {
+        AVFrameSideDataType(value as _)
+    }
+}
+
+impl From<AVFrameSideDataType> for u32 {
+    fn from(value: AVFrameSideDataType) -> Self {
+        value.0 as u32
+    }
+}
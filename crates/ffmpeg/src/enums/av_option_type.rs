@@ -0,0 +1,113 @@
+use nutype_enum::nutype_enum;
+
+use crate::ffi::*;
+
+const _: () = {
+    assert!(std::mem::size_of::<AVOptionType>() == std::mem::size_of_val(&AV_OPT_TYPE_INT));
+};
+
+nutype_enum! {
+    /// The type of the value of an option.
+    ///
+    /// See FFmpeg's `AVOptionType` in the official documentation:
+    /// <https://ffmpeg.org/doxygen/trunk/group__avoptions.html>
+    pub enum AVOptionType(i32) {
+        /// A set of flags, combined with `+` and `-` when set from a string.
+        /// Corresponds to `AV_OPT_TYPE_FLAGS`.
+        Flags = AV_OPT_TYPE_FLAGS as _,
+
+        /// A signed integer.
+        /// Corresponds to `AV_OPT_TYPE_INT`.
+        Int = AV_OPT_TYPE_INT as _,
+
+        /// A signed 64-bit integer.
+        /// Corresponds to `AV_OPT_TYPE_INT64`.
+        Int64 = AV_OPT_TYPE_INT64 as _,
+
+        /// A double precision floating point number.
+        /// Corresponds to `AV_OPT_TYPE_DOUBLE`.
+        Double = AV_OPT_TYPE_DOUBLE as _,
+
+        /// A single precision floating point number.
+        /// Corresponds to `AV_OPT_TYPE_FLOAT`.
+        Float = AV_OPT_TYPE_FLOAT as _,
+
+        /// A string.
+        /// Corresponds to `AV_OPT_TYPE_STRING`.
+        String = AV_OPT_TYPE_STRING as _,
+
+        /// A rational number.
+        /// Corresponds to `AV_OPT_TYPE_RATIONAL`.
+        Rational = AV_OPT_TYPE_RATIONAL as _,
+
+        /// Binary data, set from a hex string.
+        /// Corresponds to `AV_OPT_TYPE_BINARY`.
+        Binary = AV_OPT_TYPE_BINARY as _,
+
+        /// A dictionary of key value pairs.
+        /// Corresponds to `AV_OPT_TYPE_DICT`.
+        Dict = AV_OPT_TYPE_DICT as _,
+
+        /// An unsigned 64-bit integer.
+        /// Corresponds to `AV_OPT_TYPE_UINT64`.
+        UInt64 = AV_OPT_TYPE_UINT64 as _,
+
+        /// A named constant value of another option, grouped with it by unit.
+        /// Corresponds to `AV_OPT_TYPE_CONST`.
+        Const = AV_OPT_TYPE_CONST as _,
+
+        /// An image size, like `1280x720` or `hd720`.
+        /// Corresponds to `AV_OPT_TYPE_IMAGE_SIZE`.
+        ImageSize = AV_OPT_TYPE_IMAGE_SIZE as _,
+
+        /// A pixel format.
+        /// Corresponds to `AV_OPT_TYPE_PIXEL_FMT`.
+        PixelFormat = AV_OPT_TYPE_PIXEL_FMT as _,
+
+        /// A sample format.
+        /// Corresponds to `AV_OPT_TYPE_SAMPLE_FMT`.
+        SampleFormat = AV_OPT_TYPE_SAMPLE_FMT as _,
+
+        /// A video frame rate, like `30000/1001` or `ntsc`.
+        /// Corresponds to `AV_OPT_TYPE_VIDEO_RATE`.
+        VideoRate = AV_OPT_TYPE_VIDEO_RATE as _,
+
+        /// A duration in microseconds.
+        /// Corresponds to `AV_OPT_TYPE_DURATION`.
+        Duration = AV_OPT_TYPE_DURATION as _,
+
+        /// A color, like `red` or `0xff0000`.
+        /// Corresponds to `AV_OPT_TYPE_COLOR`.
+        Color = AV_OPT_TYPE_COLOR as _,
+
+        /// A boolean.
+        /// Corresponds to `AV_OPT_TYPE_BOOL`.
+        Bool = AV_OPT_TYPE_BOOL as _,
+
+        /// A channel layout.
+        /// Corresponds to `AV_OPT_TYPE_CHLAYOUT`.
+        ChannelLayout = AV_OPT_TYPE_CHLAYOUT as _,
+
+        /// An unsigned integer.
+        /// Corresponds to `AV_OPT_TYPE_UINT`.
+        UInt = AV_OPT_TYPE_UINT as _,
+    }
+}
+
+impl PartialEq<i32> for AVOptionType {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<u32> for AVOptionType {
+    fn from(value: u32) -> Self {
+        AVOptionType(value as i32)
+    }
+}
+
+impl From<AVOptionType> for u32 {
+    fn from(value: AVOptionType) -> Self {
+        value.0 as u32
+    }
+}
@@ -0,0 +1,108 @@
+use nutype_enum::nutype_enum;
+
+use crate::ffi::*;
+
+const _: () = {
+    assert!(std::mem::size_of::<AVPacketSideDataType>() == std::mem::size_of_val(&AV_PKT_DATA_NEW_EXTRADATA));
+};
+
+nutype_enum! {
+    /// Side data types that can be attached to an `AVPacket` or to the codec parameters of a stream.
+    ///
+    /// See the official FFmpeg documentation:
+    /// <https://ffmpeg.org/doxygen/trunk/group__lavc__packet__side__data.html>
+    pub enum AVPacketSideDataType(i32) {
+        /// An `AVPALETTE_SIZE` bytes palette.
+        /// - **Used for**: Paletted video codecs.
+        /// - **Equivalent to**: `AV_PKT_DATA_PALETTE`
+        Palette = AV_PKT_DATA_PALETTE as _,
+
+        /// New codec extradata that replaces the current extradata.
+        /// - **Used for**: In-band parameter set changes.
+        /// - **Equivalent to**: `AV_PKT_DATA_NEW_EXTRADATA`
+        NewExtradata = AV_PKT_DATA_NEW_EXTRADATA as _,
+
+        /// Changes of the sample rate or the dimensions.
+        /// - **Used for**: Signalling mid-stream parameter changes.
+        /// - **Equivalent to**: `AV_PKT_DATA_PARAM_CHANGE`
+        ParamChange = AV_PKT_DATA_PARAM_CHANGE as _,
+
+        /// A 3x3 transformation matrix describing how to display the video.
+        /// - **Used for**: Rotation and flipping of phone recordings.
+        /// - **Equivalent to**: `AV_PKT_DATA_DISPLAYMATRIX`
+        DisplayMatrix = AV_PKT_DATA_DISPLAYMATRIX as _,
+
+        /// Stereoscopic 3D video information.
+        /// - **Used for**: Side by side or top bottom 3D video.
+        /// - **Equivalent to**: `AV_PKT_DATA_STEREO3D`
+        Stereo3d = AV_PKT_DATA_STEREO3D as _,
+
+        /// The number of samples to skip at the start and end of the decoded audio.
+        /// - **Used for**: Encoder delay and padding.
+        /// - **Equivalent to**: `AV_PKT_DATA_SKIP_SAMPLES`
+        SkipSamples = AV_PKT_DATA_SKIP_SAMPLES as _,
+
+        /// A list of zero terminated key and value strings.
+        /// - **Used for**: Metadata updates of a stream.
+        /// - **Equivalent to**: `AV_PKT_DATA_STRINGS_METADATA`
+        StringsMetadata = AV_PKT_DATA_STRINGS_METADATA as _,
+
+        /// The mastering display color volume, `AVMasteringDisplayMetadata`.
+        /// - **Used for**: HDR10 static metadata.
+        /// - **Equivalent to**: `AV_PKT_DATA_MASTERING_DISPLAY_METADATA`
+        MasteringDisplayMetadata = AV_PKT_DATA_MASTERING_DISPLAY_METADATA as _,
+
+        /// The spherical video projection, `AVSphericalMapping`.
+        /// - **Used for**: 360 degree video.
+        /// - **Equivalent to**: `AV_PKT_DATA_SPHERICAL`
+        Spherical = AV_PKT_DATA_SPHERICAL as _,
+
+        /// The content light level, `AVContentLightMetadata`.
+        /// - **Used for**: HDR10 static metadata.
+        /// - **Equivalent to**: `AV_PKT_DATA_CONTENT_LIGHT_LEVEL`
+        ContentLightLevel = AV_PKT_DATA_CONTENT_LIGHT_LEVEL as _,
+
+        /// ATSC A53 Part 4 closed captions.
+        /// - **Used for**: CEA-608 and CEA-708 captions.
+        /// - **Equivalent to**: `AV_PKT_DATA_A53_CC`
+        A53Cc = AV_PKT_DATA_A53_CC as _,
+
+        /// An ICC profile.
+        /// - **Used for**: Color management of images.
+        /// - **Equivalent to**: `AV_PKT_DATA_ICC_PROFILE`
+        IccProfile = AV_PKT_DATA_ICC_PROFILE as _,
+
+        /// The Dolby Vision configuration record, `AVDOVIDecoderConfigurationRecord`.
+        /// - **Used for**: Dolby Vision streams.
+        /// - **Equivalent to**: `AV_PKT_DATA_DOVI_CONF`
+        DoviConf = AV_PKT_DATA_DOVI_CONF as _,
+
+        /// SMPTE ST 12-1 timecodes.
+        /// - **Used for**: Broadcast timecodes.
+        /// - **Equivalent to**: `AV_PKT_DATA_S12M_TIMECODE`
+        S12mTimecode = AV_PKT_DATA_S12M_TIMECODE as _,
+
+        /// HDR10+ dynamic metadata, `AVDynamicHDRPlus`.
+        /// - **Used for**: HDR10+ content.
+        /// - **Equivalent to**: `AV_PKT_DATA_DYNAMIC_HDR10_PLUS`
+        DynamicHdr10Plus = AV_PKT_DATA_DYNAMIC_HDR10_PLUS as _,
+    }
+}
+
+impl PartialEq<i32> for AVPacketSideDataType {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<u32> for AVPacketSideDataType {
+    fn from(value: u32) -> Self {
+        AVPacketSideDataType(value as _)
+    }
+}
+
+impl From<AVPacketSideDataType> for u32 {
+    fn from(value: AVPacketSideDataType) -> Self {
+        value.0 as u32
+    }
+}
@@ -0,0 +1,53 @@
+use nutype_enum::nutype_enum;
+
+use crate::ffi::*;
+
+const _: () = {
+    assert!(std::mem::size_of::<AVSubtitleType>() == std::mem::size_of_val(&SUBTITLE_NONE));
+};
+
+nutype_enum! {
+    /// The type of a subtitle rect, used in FFmpeg's `AVSubtitleRect`.
+    ///
+    /// See the official FFmpeg documentation:
+    /// <https://ffmpeg.org/doxygen/trunk/group__lavc__decoding.html>
+    pub enum AVSubtitleType(i32) {
+        /// **No subtitle type**.
+        /// - **Used for**: Uninitialized rects.
+        /// - **Equivalent to**: `SUBTITLE_NONE`
+        None = SUBTITLE_NONE as _,
+
+        /// **A bitmap subtitle**, the rect contains palette indices and a palette.
+        /// - **Used for**: DVD, DVB and PGS subtitles.
+        /// - **Equivalent to**: `SUBTITLE_BITMAP`
+        Bitmap = SUBTITLE_BITMAP as _,
+
+        /// **A plain text subtitle**, the rect contains a nul-terminated string.
+        /// - **Used for**: Simple text subtitles.
+        /// - **Equivalent to**: `SUBTITLE_TEXT`
+        Text = SUBTITLE_TEXT as _,
+
+        /// **An ASS subtitle**, the rect contains an ASS dialogue event.
+        /// - **Used for**: All text based subtitle codecs (SubRip, WebVTT, MOV text, ASS).
+        /// - **Equivalent to**: `SUBTITLE_ASS`
+        Ass = SUBTITLE_ASS as _,
+    }
+}
+
+impl PartialEq<i32> for AVSubtitleType {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<u32> for AVSubtitleType {
+    fn from(value: u32) -> Self {
+        AVSubtitleType(value as i32)
+    }
+}
+
+impl From<AVSubtitleType> for u32 {
+    fn from(value: AVSubtitleType) -> Self {
+        value.0 as u32
+    }
+}
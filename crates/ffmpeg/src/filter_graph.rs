@@ -1,10 +1,13 @@
 use std::ffi::CString;
 use std::ptr::NonNull;
 
+use crate::AVMediaType;
 use crate::error::{FfmpegError, FfmpegErrorCode};
 use crate::ffi::*;
 use crate::frame::GenericFrame;
+use crate::opt::{OptionInfo, class_options};
 use crate::smart_object::SmartPtr;
+use crate::utils::c_str;
 
 /// A filter graph. Used to chain filters together when transforming media data.
 pub struct FilterGraph(SmartPtr<AVFilterGraph>);
@@ -246,6 +249,94 @@ impl Filter {
     pub const unsafe fn wrap(ptr: *const AVFilter) -> Self {
         Self(ptr)
     }
+
+    const fn filter(&self) -> &'static AVFilter {
+        // Safety: The pointer is valid as guaranteed by the constructors, and filters are static.
+        unsafe { &*self.0 }
+    }
+
+    /// Returns the name of the filter.
+    pub fn name(&self) -> &'static str {
+        // Safety: The name of a filter is a static c-string.
+        unsafe { c_str(self.filter().name) }.unwrap_or_default()
+    }
+
+    /// Returns the description of the filter.
+    pub fn description(&self) -> Option<&'static str> {
+        // Safety: The description of a filter is either null or a static c-string.
+        unsafe { c_str(self.filter().description) }
+    }
+
+    /// Returns the static input pads of the filter.
+    pub fn inputs(&self) -> Vec<FilterPad> {
+        self.pads(false, self.filter().inputs)
+    }
+
+    /// Returns the static output pads of the filter.
+    pub fn outputs(&self) -> Vec<FilterPad> {
+        self.pads(true, self.filter().outputs)
+    }
+
+    /// Returns `true` if the filter creates more inputs depending on its options, like `amix`.
+    pub const fn has_dynamic_inputs(&self) -> bool {
+        self.filter().flags & AVFILTER_FLAG_DYNAMIC_INPUTS as i32 != 0
+    }
+
+    /// Returns `true` if the filter creates more outputs depending on its options, like `split`.
+    pub const fn has_dynamic_outputs(&self) -> bool {
+        self.filter().flags & AVFILTER_FLAG_DYNAMIC_OUTPUTS as i32 != 0
+    }
+
+    /// Returns the private options of the filter.
+    pub fn options(&self) -> Vec<OptionInfo> {
+        // Safety: The private class of a filter is either null or a static class.
+        unsafe { class_options(self.filter().priv_class) }
+    }
+
+    fn pads(&self, is_output: bool, pads: *const AVFilterPad) -> Vec<FilterPad> {
+        // Safety: `avfilter_filter_pad_count` is safe to call with a valid filter.
+        let count = unsafe { avfilter_filter_pad_count(self.0, is_output as i32) };
+
+        (0..count as i32)
+            .map(|index| {
+                // Safety: `index` is less than the number of pads.
+                let name = unsafe { avfilter_pad_get_name(pads, index) };
+
+                FilterPad {
+                    // Safety: The name of a pad is a static c-string.
+                    name: unsafe { c_str(name) }.unwrap_or_default().to_owned(),
+                    // Safety: `index` is less than the number of pads.
+                    media_type: AVMediaType(unsafe { avfilter_pad_get_type(pads, index) } as _),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns an iterator over the filters of the linked ffmpeg build.
+pub fn filters() -> impl Iterator<Item = Filter> {
+    let mut opaque = std::ptr::null_mut();
+
+    std::iter::from_fn(move || {
+        // Safety: `av_filter_iterate` is safe to call, `opaque` is only used by the iteration.
+        let filter = unsafe { av_filter_iterate(&mut opaque) };
+        (!filter.is_null()).then_some(Filter(filter))
+    })
+}
+
+impl std::fmt::Debug for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Filter").field("name", &self.name()).finish()
+    }
+}
+
+/// An input or output pad of a [`Filter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterPad {
+    /// The name of the pad.
+    pub name: String,
+    /// The media type of the frames going through the pad.
+    pub media_type: AVMediaType,
 }
 
 /// Safety: `Filter` is safe to send between threads.
@@ -322,10 +413,39 @@ impl FilterContextSink<'_> {
 mod tests {
     use std::ffi::CString;
 
-    use crate::AVSampleFormat;
     use crate::ffi::avfilter_get_by_name;
-    use crate::filter_graph::{Filter, FilterGraph, FilterGraphParser};
+    use crate::filter_graph::{Filter, FilterGraph, FilterGraphParser, FilterPad, filters};
     use crate::frame::{AudioChannelLayout, AudioFrame, GenericFrame};
+    use crate::{AVMediaType, AVSampleFormat};
+
+    #[test]
+    fn test_filter_capabilities() {
+        let scale = Filter::get("scale").expect("Expected the scale filter");
+        assert_eq!(scale.name(), "scale");
+        assert!(scale.description().is_some());
+        assert_eq!(
+            scale.inputs(),
+            [FilterPad {
+                name: "default".to_owned(),
+                media_type: AVMediaType::Video,
+            }]
+        );
+        assert_eq!(scale.outputs().len(), 1);
+        assert!(!scale.has_dynamic_outputs());
+        assert!(
+            scale.options().iter().any(|option| option.name == "w"),
+            "Expected the w option"
+        );
+
+        let split = Filter::get("asplit").expect("Expected the asplit filter");
+        assert!(split.has_dynamic_outputs());
+        assert_eq!(split.inputs()[0].media_type, AVMediaType::Audio);
+
+        assert!(
+            filters().any(|filter| filter == scale),
+            "Expected the scale filter to be listed"
+        );
+    }
 
     #[test]
     fn test_filter_graph_new() {
@@ -0,0 +1,284 @@
+use std::ffi::CString;
+
+use crate::ffi::*;
+use crate::opt::{OptionInfo, class_options};
+use crate::utils::c_str;
+use crate::{AVCodecID, AVFormatFlags};
+
+/// Splits a comma separated list of a format, like its extensions.
+fn split_list(list: Option<&'static str>) -> Vec<&'static str> {
+    list.map(|list| list.split(',').map(str::trim).filter(|item| !item.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// An output format, also called a muxer. Thin wrapper around [`AVOutputFormat`].
+#[derive(Clone, Copy)]
+pub struct OutputFormat(&'static AVOutputFormat);
+
+impl PartialEq for OutputFormat {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for OutputFormat {}
+
+/// Safety: `OutputFormat` points to a static format, which can be shared between threads.
+unsafe impl Send for OutputFormat {}
+
+/// Safety: `OutputFormat` points to a static format, which can be shared between threads.
+unsafe impl Sync for OutputFormat {}
+
+impl std::fmt::Debug for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputFormat")
+            .field("name", &self.name())
+            .field("long_name", &self.long_name())
+            .finish()
+    }
+}
+
+impl OutputFormat {
+    /// Returns the output format with the given short name, like `mp4`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let name = CString::new(name).ok()?;
+
+        // Safety: `av_guess_format` is safe to call with a valid c-string and null pointers.
+        let format = unsafe { av_guess_format(name.as_ptr(), std::ptr::null(), std::ptr::null()) };
+
+        // Safety: The returned format is either null or static.
+        unsafe { format.as_ref() }.map(Self)
+    }
+
+    /// Returns the output format best matching the extension of `filename`.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let filename = CString::new(filename).ok()?;
+
+        // Safety: `av_guess_format` is safe to call with a valid c-string and null pointers.
+        let format = unsafe { av_guess_format(std::ptr::null(), filename.as_ptr(), std::ptr::null()) };
+
+        // Safety: The returned format is either null or static.
+        unsafe { format.as_ref() }.map(Self)
+    }
+
+    /// Returns the raw pointer to the [`AVOutputFormat`].
+    pub const fn as_ptr(&self) -> *const AVOutputFormat {
+        self.0
+    }
+
+    /// Returns the short name of the format.
+    pub fn name(&self) -> &'static str {
+        // Safety: The name of a format is a static c-string.
+        unsafe { c_str(self.0.name) }.unwrap_or_default()
+    }
+
+    /// Returns the descriptive name of the format.
+    pub fn long_name(&self) -> Option<&'static str> {
+        // Safety: The long name of a format is either null or a static c-string.
+        unsafe { c_str(self.0.long_name) }
+    }
+
+    /// Returns the mime types of the format.
+    pub fn mime_types(&self) -> Vec<&'static str> {
+        // Safety: The mime type of a format is either null or a static c-string.
+        split_list(unsafe { c_str(self.0.mime_type) })
+    }
+
+    /// Returns the file extensions of the format.
+    pub fn extensions(&self) -> Vec<&'static str> {
+        // Safety: The extensions of a format are either null or a static c-string.
+        split_list(unsafe { c_str(self.0.extensions) })
+    }
+
+    /// Returns the default video codec of the format.
+    pub const fn default_video_codec(&self) -> AVCodecID {
+        AVCodecID(self.0.video_codec as _)
+    }
+
+    /// Returns the default audio codec of the format.
+    pub const fn default_audio_codec(&self) -> AVCodecID {
+        AVCodecID(self.0.audio_codec as _)
+    }
+
+    /// Returns the default subtitle codec of the format.
+    pub const fn default_subtitle_codec(&self) -> AVCodecID {
+        AVCodecID(self.0.subtitle_codec as _)
+    }
+
+    /// Returns the flags of the format.
+    pub const fn flags(&self) -> AVFormatFlags {
+        AVFormatFlags(self.0.flags)
+    }
+
+    /// Returns whether the format can store streams of the given codec.
+    ///
+    /// Returns `None` if the format does not know.
+    pub fn supports_codec(&self, codec_id: AVCodecID) -> Option<bool> {
+        // Safety: `avformat_query_codec` is safe to call with a valid format.
+        match unsafe { avformat_query_codec(self.0, codec_id.into(), FF_COMPLIANCE_NORMAL as i32) } {
+            1 => Some(true),
+            0 => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns the private options of the format.
+    pub fn options(&self) -> Vec<OptionInfo> {
+        // Safety: The private class of a format is either null or a static class.
+        unsafe { class_options(self.0.priv_class) }
+    }
+}
+
+/// An input format, also called a demuxer. Thin wrapper around [`AVInputFormat`].
+#[derive(Clone, Copy)]
+pub struct InputFormat(&'static AVInputFormat);
+
+impl PartialEq for InputFormat {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for InputFormat {}
+
+/// Safety: `InputFormat` points to a static format, which can be shared between threads.
+unsafe impl Send for InputFormat {}
+
+/// Safety: `InputFormat` points to a static format, which can be shared between threads.
+unsafe impl Sync for InputFormat {}
+
+impl std::fmt::Debug for InputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InputFormat")
+            .field("name", &self.name())
+            .field("long_name", &self.long_name())
+            .finish()
+    }
+}
+
+impl InputFormat {
+    /// Returns the input format with the given short name, like `mov`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let name = CString::new(name).ok()?;
+
+        // Safety: `av_find_input_format` is safe to call with a valid c-string.
+        let format = unsafe { av_find_input_format(name.as_ptr()) };
+
+        // Safety: The returned format is either null or static.
+        unsafe { format.as_ref() }.map(Self)
+    }
+
+    /// Returns the raw pointer to the [`AVInputFormat`].
+    pub const fn as_ptr(&self) -> *const AVInputFormat {
+        self.0
+    }
+
+    /// Returns the short names of the format.
+    ///
+    /// Some demuxers handle multiple formats, like `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub fn names(&self) -> Vec<&'static str> {
+        // Safety: The name of a format is a static c-string.
+        split_list(unsafe { c_str(self.0.name) })
+    }
+
+    /// Returns the short name of the format, as a comma separated list if it handles multiple formats.
+    pub fn name(&self) -> &'static str {
+        // Safety: The name of a format is a static c-string.
+        unsafe { c_str(self.0.name) }.unwrap_or_default()
+    }
+
+    /// Returns the descriptive name of the format.
+    pub fn long_name(&self) -> Option<&'static str> {
+        // Safety: The long name of a format is either null or a static c-string.
+        unsafe { c_str(self.0.long_name) }
+    }
+
+    /// Returns the mime types of the format.
+    pub fn mime_types(&self) -> Vec<&'static str> {
+        // Safety: The mime type of a format is either null or a static c-string.
+        split_list(unsafe { c_str(self.0.mime_type) })
+    }
+
+    /// Returns the file extensions of the format.
+    pub fn extensions(&self) -> Vec<&'static str> {
+        // Safety: The extensions of a format are either null or a static c-string.
+        split_list(unsafe { c_str(self.0.extensions) })
+    }
+
+    /// Returns the flags of the format.
+    pub const fn flags(&self) -> AVFormatFlags {
+        AVFormatFlags(self.0.flags)
+    }
+
+    /// Returns the private options of the format.
+    pub fn options(&self) -> Vec<OptionInfo> {
+        // Safety: The private class of a format is either null or a static class.
+        unsafe { class_options(self.0.priv_class) }
+    }
+}
+
+/// Returns an iterator over the output formats of the linked ffmpeg build.
+pub fn muxers() -> impl Iterator<Item = OutputFormat> {
+    let mut opaque = std::ptr::null_mut();
+
+    std::iter::from_fn(move || {
+        // Safety: `av_muxer_iterate` is safe to call, `opaque` is only used by the iteration.
+        let format = unsafe { av_muxer_iterate(&mut opaque) };
+
+        // Safety: The returned format is either null or static.
+        unsafe { format.as_ref() }.map(OutputFormat)
+    })
+}
+
+/// Returns an iterator over the input formats of the linked ffmpeg build.
+pub fn demuxers() -> impl Iterator<Item = InputFormat> {
+    let mut opaque = std::ptr::null_mut();
+
+    std::iter::from_fn(move || {
+        // Safety: `av_demuxer_iterate` is safe to call, `opaque` is only used by the iteration.
+        let format = unsafe { av_demuxer_iterate(&mut opaque) };
+
+        // Safety: The returned format is either null or static.
+        unsafe { format.as_ref() }.map(InputFormat)
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::format::{InputFormat, OutputFormat, demuxers, muxers};
+    use crate::{AVCodecID, AVFormatFlags};
+
+    #[test]
+    fn test_output_format() {
+        let mp4 = OutputFormat::by_name("mp4").expect("Expected the mp4 muxer");
+        assert_eq!(mp4.name(), "mp4");
+        assert!(mp4.extensions().contains(&"mp4"));
+        assert_eq!(mp4.mime_types(), ["video/mp4"]);
+        assert_eq!(mp4.supports_codec(AVCodecID::H264), Some(true));
+        assert!(mp4.flags() & AVFormatFlags::GlobalHeader != 0);
+        assert!(
+            mp4.options().iter().any(|option| option.name == "movflags"),
+            "Expected the movflags option"
+        );
+
+        assert_eq!(OutputFormat::from_filename("output.mkv").map(|f| f.name()), Some("matroska"));
+        assert!(OutputFormat::by_name("not_a_format").is_none());
+        assert!(muxers().any(|format| format == mp4), "Expected the mp4 muxer to be listed");
+    }
+
+    #[test]
+    fn test_input_format() {
+        let mov = InputFormat::by_name("mp4").expect("Expected the mov demuxer");
+        assert!(mov.names().contains(&"mov"));
+        assert!(mov.names().contains(&"mp4"));
+        assert!(mov.extensions().contains(&"mp4"));
+
+        assert!(InputFormat::by_name("not_a_format").is_none());
+        assert!(
+            demuxers().any(|format| format == mov),
+            "Expected the mov demuxer to be listed"
+        );
+        assert!(demuxers().all(|format| !format.name().is_empty()));
+    }
+}
@@ -2,12 +2,14 @@ use std::ops::{Index, IndexMut};
 use std::ptr::NonNull;
 
 use crate::consts::{Const, Mut};
+use crate::dict::Dictionary;
 use crate::error::{FfmpegError, FfmpegErrorCode};
 use crate::ffi::*;
 use crate::rational::Rational;
+use crate::side_data::{SideData, side_data_bytes};
 use crate::smart_object::{SmartObject, SmartPtr};
 use crate::utils::{check_i64, or_nopts};
-use crate::{AVPictureType, AVPixelFormat, AVSampleFormat};
+use crate::{AVFrameSideDataType, AVPictureType, AVPixelFormat, AVSampleFormat};
 
 /// Wrapper around the data buffers of AVFrame that handles bottom-to-top line iteration
 #[derive(Debug, PartialEq)]
@@ -293,6 +295,118 @@ impl GenericFrame {
         }
         Some(self.0.as_deref_except().linesize[index])
     }
+
+    /// Returns the side data entries of the frame.
+    fn side_data_entries(&self) -> &[*mut AVFrameSideData] {
+        let frame = self.0.as_deref_except();
+        if frame.side_data.is_null() || frame.nb_side_data <= 0 {
+            return &[];
+        }
+
+        // Safety: `side_data` is an array of `nb_side_data` pointers owned by the frame.
+        unsafe { std::slice::from_raw_parts(frame.side_data, frame.nb_side_data as usize) }
+    }
+
+    /// Returns the side data of the frame that has a typed representation.
+    pub fn side_data(&self) -> Vec<SideData> {
+        self.side_data_entries()
+            .iter()
+            // Safety: The side data pointers are valid while the frame is borrowed.
+            .filter_map(|entry| unsafe { entry.as_ref() })
+            .filter_map(|entry| {
+                // Safety: The side data is valid for `size` bytes while the frame is borrowed.
+                let data = unsafe { side_data_bytes(entry.data, entry.size) };
+                SideData::from_frame_side_data(entry.type_.into(), data)
+            })
+            .collect()
+    }
+
+    /// Returns the raw side data of the given type.
+    pub fn side_data_raw(&self, kind: AVFrameSideDataType) -> Option<&[u8]> {
+        // Safety: av_frame_get_side_data is safe to call, the frame is valid.
+        let entry = unsafe { av_frame_get_side_data(self.as_ptr(), kind.into()) };
+        // Safety: The side data is valid while the frame is borrowed.
+        let entry = unsafe { entry.as_ref() }?;
+
+        // Safety: The side data is valid for `size` bytes while the frame is borrowed.
+        Some(unsafe { side_data_bytes(entry.data, entry.size) })
+    }
+
+    /// Adds side data to the frame, replacing side data of the same type.
+    ///
+    /// Returns an error if the side data cannot be attached to a frame.
+    pub fn add_side_data(&mut self, side_data: &SideData) -> Result<(), FfmpegError> {
+        let kind = side_data
+            .frame_type()
+            .ok_or(FfmpegError::Arguments("side data cannot be attached to a frame"))?;
+
+        self.set_side_data_raw(kind, &side_data.to_bytes())
+    }
+
+    /// Sets the raw side data of the given type, replacing side data of the same type.
+    pub fn set_side_data_raw(&mut self, kind: AVFrameSideDataType, data: &[u8]) -> Result<(), FfmpegError> {
+        self.remove_side_data(kind);
+
+        // Safety: av_frame_new_side_data is safe to call, the frame is valid.
+        let entry = unsafe { av_frame_new_side_data(self.as_mut_ptr(), kind.into(), data.len()) };
+        // Safety: The side data is valid if it is not null.
+        let entry = unsafe { entry.as_mut() }.ok_or(FfmpegError::Alloc)?;
+
+        // Safety: The side data was allocated with `data.len()` bytes.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), entry.data, data.len()) };
+
+        Ok(())
+    }
+
+    /// Removes the side data of the given type from the frame.
+    pub fn remove_side_data(&mut self, kind: AVFrameSideDataType) {
+        // Safety: av_frame_remove_side_data is safe to call, the frame is valid.
+        unsafe { av_frame_remove_side_data(self.as_mut_ptr(), kind.into()) };
+    }
+
+    /// Replaces the side data and the metadata of the frame with the ones of `other`.
+    ///
+    /// This keeps information like the display matrix and HDR metadata when a frame is
+    /// converted into a new frame.
+    pub fn copy_side_data(&mut self, other: &GenericFrame) -> Result<(), FfmpegError> {
+        let kinds: Vec<AVFrameSideDataType> = self
+            .side_data_entries()
+            .iter()
+            // Safety: The side data pointers are valid while the frame is borrowed.
+            .filter_map(|entry| unsafe { entry.as_ref() })
+            .map(|entry| entry.type_.into())
+            .collect();
+
+        for kind in kinds {
+            self.remove_side_data(kind);
+        }
+
+        // Safety: The side data pointers are valid while the other frame is borrowed.
+        for entry in other.side_data_entries().iter().filter_map(|entry| unsafe { entry.as_ref() }) {
+            // Safety: The side data is valid for `size` bytes while the other frame is borrowed.
+            let data = unsafe { side_data_bytes(entry.data, entry.size) };
+            self.set_side_data_raw(entry.type_.into(), data)?;
+        }
+
+        self.set_metadata(other.metadata().clone());
+
+        Ok(())
+    }
+
+    /// Returns the metadata of the frame.
+    pub const fn metadata(&self) -> Const<'_, Dictionary> {
+        // Safety: the pointer metadata pointer does not live longer than this object,
+        // see `Const::new`
+        Const::new(unsafe { Dictionary::from_ptr_ref(self.0.as_deref_except().metadata) })
+    }
+
+    /// Sets the metadata of the frame.
+    pub fn set_metadata(&mut self, metadata: Dictionary) {
+        let frame = self.0.as_deref_mut_except();
+        // Safety: av_dict_free is safe to call, the frame owns its metadata.
+        unsafe { av_dict_free(&mut frame.metadata) };
+        frame.metadata = metadata.leak();
+    }
 }
 
 impl std::fmt::Debug for GenericFrame {
@@ -484,6 +598,12 @@ impl std::ops::DerefMut for VideoFrame {
     }
 }
 
+impl From<VideoFrame> for GenericFrame {
+    fn from(frame: VideoFrame) -> Self {
+        frame.0
+    }
+}
+
 /// A thin wrapper around `AVChannelLayout` to make it easier to use.
 pub struct AudioChannelLayout(SmartObject<AVChannelLayout>);
 
@@ -496,6 +616,15 @@ impl Default for AudioChannelLayout {
     }
 }
 
+impl std::fmt::Debug for AudioChannelLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioChannelLayout")
+            .field("channel_count", &self.channel_count())
+            .field("description", &self.describe())
+            .finish()
+    }
+}
+
 impl AudioChannelLayout {
     #[doc(hidden)]
     fn destructor(ptr: &mut AVChannelLayout) {
@@ -523,6 +652,29 @@ impl AudioChannelLayout {
         Ok(new)
     }
 
+    /// Copies a channel layout owned by ffmpeg.
+    pub(crate) fn copy_from(layout: &AVChannelLayout) -> Result<Self, FfmpegError> {
+        let mut new = Self::default();
+        // Safety: av_channel_layout_copy is safe to call
+        FfmpegErrorCode(unsafe { av_channel_layout_copy(new.0.inner_mut(), layout) }).result()?;
+        Ok(new)
+    }
+
+    /// Returns a description of the layout, like `stereo` or `5.1(side)`.
+    pub fn describe(&self) -> Option<String> {
+        let mut buf = [0u8; 64];
+
+        // Safety: `av_channel_layout_describe` is safe to call, it writes at most `buf.len()` bytes
+        // including the nul terminator.
+        let ret = unsafe { av_channel_layout_describe(self.0.as_ref(), buf.as_mut_ptr().cast(), buf.len()) };
+        if ret < 0 {
+            return None;
+        }
+
+        let description = std::ffi::CStr::from_bytes_until_nul(&buf).ok()?;
+        Some(description.to_str().ok()?.to_owned())
+    }
+
     /// Returns a pointer to the channel layout.
     pub(crate) fn as_ptr(&self) -> *const AVChannelLayout {
         self.0.as_ref()
@@ -710,6 +862,12 @@ impl std::ops::DerefMut for AudioFrame {
     }
 }
 
+impl From<AudioFrame> for GenericFrame {
+    fn from(frame: AudioFrame) -> Self {
+        frame.0
+    }
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
@@ -717,9 +875,12 @@ mod tests {
     use rand::{Rng, rng};
 
     use super::FrameData;
+    use crate::dict::Dictionary;
+    use crate::error::FfmpegError;
     use crate::frame::{AudioChannelLayout, AudioFrame, GenericFrame, VideoFrame};
     use crate::rational::Rational;
-    use crate::{AVChannelOrder, AVPictureType, AVPixelFormat, AVSampleFormat};
+    use crate::side_data::{ContentLightLevel, DisplayLuminance, DisplayMatrix, MasteringDisplayMetadata, SideData};
+    use crate::{AVChannelOrder, AVFrameSideDataType, AVPictureType, AVPixelFormat, AVSampleFormat};
 
     #[test]
     fn test_frame_clone() {
@@ -775,6 +936,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_frame_side_data() {
+        let mut frame = GenericFrame::new().expect("Failed to create frame");
+        assert!(frame.side_data().is_empty(), "A new frame should have no side data.");
+
+        let mastering_display = SideData::MasteringDisplayMetadata(MasteringDisplayMetadata {
+            primaries: None,
+            luminance: Some(DisplayLuminance {
+                min: Rational::static_new::<1, 10000>(),
+                max: Rational::static_new::<1000, 1>(),
+            }),
+        });
+        let content_light_level = SideData::ContentLightLevel(ContentLightLevel {
+            max_content_light_level: 1000,
+            max_frame_average_light_level: 400,
+        });
+
+        frame.add_side_data(&mastering_display).expect("Failed to add side data");
+        frame.add_side_data(&content_light_level).expect("Failed to add side data");
+        assert_eq!(
+            frame.side_data(),
+            vec![mastering_display.clone(), content_light_level.clone()]
+        );
+
+        assert_eq!(
+            frame.add_side_data(&SideData::NewExtradata(vec![1, 2, 3])),
+            Err(FfmpegError::Arguments("side data cannot be attached to a frame")),
+            "Extradata cannot be attached to a frame."
+        );
+
+        frame
+            .set_side_data_raw(AVFrameSideDataType::SeiUnregistered, b"0123456789abcdefpayload")
+            .expect("Failed to add side data");
+        assert_eq!(
+            frame.side_data_raw(AVFrameSideDataType::SeiUnregistered),
+            Some(b"0123456789abcdefpayload".as_slice())
+        );
+
+        frame.remove_side_data(AVFrameSideDataType::MasteringDisplayMetadata);
+        assert_eq!(frame.side_data_raw(AVFrameSideDataType::MasteringDisplayMetadata), None);
+        assert_eq!(frame.side_data(), vec![content_light_level.clone()]);
+
+        let cloned = frame.clone();
+        assert_eq!(cloned.side_data(), vec![content_light_level]);
+    }
+
+    #[test]
+    fn test_frame_metadata_and_copy_side_data() {
+        let mut frame = GenericFrame::new().expect("Failed to create frame");
+        assert!(frame.metadata().is_empty(), "A new frame should have no metadata.");
+
+        frame.set_metadata(Dictionary::try_from_iter([("lavfi.scene_score", "0.5")]).unwrap());
+        frame
+            .add_side_data(&SideData::DisplayMatrix(DisplayMatrix::from_rotation(-90.0)))
+            .expect("Failed to add side data");
+
+        let mut other = GenericFrame::new().expect("Failed to create frame");
+        other
+            .add_side_data(&SideData::A53ClosedCaptions(vec![0xfc, 0x80, 0x80]))
+            .expect("Failed to add side data");
+
+        other.copy_side_data(&frame).expect("Failed to copy side data");
+        assert_eq!(other.side_data(), frame.side_data());
+        assert_eq!(other.side_data_raw(AVFrameSideDataType::A53Cc), None);
+        assert_eq!(other.metadata().get("lavfi.scene_score"), Some(c"0.5"));
+    }
+
     #[test]
     fn test_frame_debug() {
         let mut frame = GenericFrame::new().expect("Failed to create frame");
@@ -2,13 +2,18 @@ use std::ffi::CStr;
 use std::path::Path;
 
 use super::internal::{Inner, InnerOptions, read_packet, seek};
+use crate::AVSeekFlag;
+use crate::chapter::{Chapter, chapters};
 use crate::consts::{Const, DEFAULT_BUFFER_SIZE};
 use crate::dict::Dictionary;
 use crate::error::{FfmpegError, FfmpegErrorCode};
 use crate::ffi::*;
 use crate::packet::{Packet, Packets};
+use crate::program::{Program, programs};
 use crate::smart_object::SmartObject;
 use crate::stream::Streams;
+use crate::timestamp::Timestamp;
+use crate::utils::check_i64;
 
 /// Represents an input stream.
 pub struct Input<T: Send + Sync> {
@@ -128,6 +133,84 @@ impl<T: Send + Sync> Input<T> {
         self.packets().receive()
     }
 
+    /// Returns the start time of the input stream.
+    pub const fn start_time(&self) -> Option<Timestamp> {
+        match check_i64(self.inner.inner_ref().context.as_deref_except().start_time) {
+            Some(start_time) => Some(Timestamp::from_micros(start_time)),
+            None => None,
+        }
+    }
+
+    /// Returns the duration of the input stream.
+    pub const fn duration(&self) -> Option<Timestamp> {
+        match check_i64(self.inner.inner_ref().context.as_deref_except().duration) {
+            Some(duration) => Some(Timestamp::from_micros(duration)),
+            None => None,
+        }
+    }
+
+    /// Returns the total bit rate of the input stream in bits per second.
+    pub const fn bit_rate(&self) -> Option<i64> {
+        match self.inner.inner_ref().context.as_deref_except().bit_rate {
+            0 => None,
+            bit_rate => Some(bit_rate),
+        }
+    }
+
+    /// Returns the metadata of the input stream.
+    pub const fn metadata(&self) -> Const<'_, Dictionary> {
+        // Safety: the pointer metadata pointer does not live longer than this object,
+        // see `Const::new`
+        Const::new(unsafe { Dictionary::from_ptr_ref(self.inner.inner_ref().context.as_deref_except().metadata) })
+    }
+
+    /// Returns the chapters of the input stream.
+    pub fn chapters(&self) -> Vec<Chapter<'_>> {
+        // Safety: The format context is valid and the chapters are not mutated while they are borrowed.
+        unsafe { chapters(self.as_ptr()) }
+    }
+
+    /// Returns the programs of the input stream.
+    pub fn programs(&self) -> Vec<Program<'_>> {
+        // Safety: The format context is valid and the programs are not mutated while they are borrowed.
+        unsafe { programs(self.as_ptr()) }
+    }
+
+    /// Seeks the input stream to the given timestamp.
+    ///
+    /// If `stream` is `Some`, the seek is done on the stream with that index, otherwise the
+    /// demuxer picks a stream. The timestamp is converted to the time base of the stream.
+    ///
+    /// With [`AVSeekFlag::Backward`] the input is positioned on the closest keyframe at or before
+    /// the timestamp, otherwise on the closest keyframe in either direction.
+    /// [`AVSeekFlag::Any`] allows seeking to non-keyframes.
+    ///
+    /// Decoders fed from this input should be flushed after a seek.
+    pub fn seek(&mut self, stream: Option<usize>, timestamp: Timestamp, flags: AVSeekFlag) -> Result<(), FfmpegError> {
+        let (stream_index, timestamp) = match stream {
+            Some(index) => {
+                let streams = self.streams();
+                // Safety: The stream is only used to read the time base.
+                let stream = unsafe { streams.get_unchecked(index) }.ok_or(FfmpegError::NoStream)?;
+                (index as i32, timestamp.rescale(stream.time_base()))
+            }
+            None => (-1, timestamp.rescale(Timestamp::AV_TIME_BASE)),
+        };
+
+        let ts = timestamp.value();
+        let (min_ts, max_ts) = if flags & AVSeekFlag::Backward != 0 {
+            (i64::MIN, ts)
+        } else {
+            (i64::MIN, i64::MAX)
+        };
+
+        // Safety: avformat_seek_file is safe to call, the context is valid and the stream index exists
+        FfmpegErrorCode(unsafe { avformat_seek_file(self.as_mut_ptr(), stream_index, min_ts, ts, max_ts, flags.0) })
+            .result()?;
+
+        Ok(())
+    }
+
     fn create_input(mut inner: Inner<T>, path: Option<&CStr>, dictionary: &mut Dictionary) -> Result<Self, FfmpegError> {
         // Safety: avformat_open_input is safe to call
         FfmpegErrorCode(unsafe {
@@ -184,7 +267,8 @@ mod tests {
     use insta::Settings;
 
     use super::{DEFAULT_BUFFER_SIZE, FfmpegError, Input, InputOptions};
-    use crate::file_path;
+    use crate::timestamp::Timestamp;
+    use crate::{AVMediaType, AVSeekFlag, file_path};
 
     fn configure_insta_filters(settings: &mut Settings) {
         settings.add_filter(r"0x0000000000000000", "[NULL_POINTER]");
@@ -295,7 +379,7 @@ mod tests {
                         nb_frames: Some(
                             64,
                         ),
-                        disposition: 1,
+                        disposition: AVDisposition::Default,
                         discard: AVDiscard::Default,
                         sample_aspect_ratio: Rational {
                             numerator: 1,
@@ -332,7 +416,7 @@ mod tests {
                         nb_frames: Some(
                             48,
                         ),
-                        disposition: 1,
+                        disposition: AVDisposition::Default,
                         discard: AVDiscard::Default,
                         sample_aspect_ratio: Rational {
                             numerator: 0,
@@ -400,4 +484,72 @@ mod tests {
 
         insta::assert_debug_snapshot!(packets);
     }
+
+    #[test]
+    fn test_input_duration_and_metadata() {
+        let input = Input::open(file_path("avc_aac_large.mp4")).expect("Failed to open valid file");
+
+        let duration = input.duration().expect("Expected a duration");
+        assert_eq!(duration.time_base(), Timestamp::AV_TIME_BASE);
+        assert!(
+            (1.0..1.1).contains(&duration.as_seconds_f64()),
+            "Expected a duration of about one second, got {duration:?}"
+        );
+        assert_eq!(input.start_time(), Some(Timestamp::from_micros(0)));
+        assert!(input.bit_rate().is_some(), "Expected a bit rate");
+
+        let metadata = input.metadata();
+        assert!(
+            metadata.get("major_brand").is_some(),
+            "Expected the major brand in the metadata"
+        );
+    }
+
+    #[test]
+    fn test_input_chapters_and_programs() {
+        let input = Input::open(file_path("avc_aac_large.mp4")).expect("Failed to open valid file");
+
+        assert!(input.chapters().is_empty(), "Expected no chapters");
+        assert!(input.programs().is_empty(), "Expected no programs");
+    }
+
+    #[test]
+    fn test_input_seek() {
+        let mut input = Input::open(file_path("avc_aac_large.mp4")).expect("Failed to open valid file");
+        let streams = input.streams();
+        let video_stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+        let video_index = video_stream.index() as usize;
+        let time_base = video_stream.time_base();
+
+        let target = Timestamp::from_millis(500);
+        input
+            .seek(Some(video_index), target, AVSeekFlag::Backward)
+            .expect("Failed to seek");
+
+        let packet = std::iter::from_fn(|| input.receive_packet().expect("Failed to receive packet"))
+            .find(|packet| packet.stream_index() as usize == video_index)
+            .expect("Expected a video packet after seeking");
+
+        assert!(packet.is_key(), "Expected to land on a keyframe");
+        let pts = packet.pts().expect("Expected a pts");
+        assert!(
+            pts <= target.rescale(time_base).value(),
+            "Expected the keyframe to be at or before the target"
+        );
+
+        input
+            .seek(None, Timestamp::from_seconds(0), AVSeekFlag::Backward)
+            .expect("Failed to seek to the start");
+        assert!(input.receive_packet().expect("Failed to receive packet").is_some());
+    }
+
+    #[test]
+    fn test_input_seek_invalid_stream() {
+        let mut input = Input::open(file_path("avc_aac_large.mp4")).expect("Failed to open valid file");
+
+        assert_eq!(
+            input.seek(Some(99), Timestamp::from_seconds(0), AVSeekFlag::Backward),
+            Err(FfmpegError::NoStream)
+        );
+    }
 }
@@ -254,6 +254,12 @@
 #![deny(clippy::undocumented_unsafe_blocks)]
 #![deny(clippy::multiple_unsafe_ops_per_block)]
 
+/// Audio fifo specific functionality.
+pub mod audio_fifo;
+/// Bitstream filter specific functionality.
+pub mod bitstream_filter;
+/// Chapter specific functionality.
+pub mod chapter;
 /// Codec specific functionality.
 pub mod codec;
 /// Constants.
@@ -268,22 +274,39 @@ pub mod encoder;
 pub mod error;
 /// Filter graph specific functionality.
 pub mod filter_graph;
+/// Container format specific functionality.
+pub mod format;
 /// Frame specific functionality.
 pub mod frame;
 /// Input/Output specific functionality.
 pub mod io;
 /// Logging specific functionality.
 pub mod log;
+/// Option specific functionality.
+pub mod opt;
 /// Packet specific functionality.
 pub mod packet;
+/// Parser specific functionality.
+pub mod parser;
+/// Pipeline specific functionality.
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+/// Program specific functionality.
+pub mod program;
 /// Rational number specific functionality.
 pub mod rational;
 /// [`frame::AudioFrame`] resampling and format conversion.
 pub mod resampler;
 /// Scaler specific functionality.
 pub mod scaler;
+/// Side data specific functionality.
+pub mod side_data;
 /// Stream specific functionality.
 pub mod stream;
+/// Subtitle specific functionality.
+pub mod subtitle;
+/// Timestamp specific functionality.
+pub mod timestamp;
 /// Utility functionality.
 pub mod utils;
 
@@ -0,0 +1,239 @@
+use crate::AVOptionType;
+use crate::ffi::*;
+use crate::rational::Rational;
+use crate::utils::c_str;
+
+/// The default value of an option.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    /// An integer value, used by the integer, flags, boolean, format and duration options.
+    Int(i64),
+    /// A floating point value.
+    Float(f64),
+    /// A rational value.
+    Rational(Rational),
+    /// A string value, used by the string, image size, video rate, color and channel layout options.
+    String(String),
+}
+
+/// A named constant which can be used as the value of an option.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionConstant {
+    /// The name of the constant.
+    pub name: String,
+    /// A short description of the constant.
+    pub help: Option<String>,
+    /// The value of the constant.
+    pub value: i64,
+}
+
+/// The description of an option. Owned copy of an [`AVOption`].
+///
+/// These are returned by the capability APIs of codecs, formats and filters, and describe the
+/// private options that can be passed to them with a [`Dictionary`](crate::dict::Dictionary).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionInfo {
+    /// The name of the option.
+    pub name: String,
+    /// A short description of the option.
+    pub help: Option<String>,
+    /// The type of the value of the option.
+    pub kind: AVOptionType,
+    /// Whether the option takes an array of values of [`kind`](Self::kind).
+    pub is_array: bool,
+    /// The default value of the option, if it has one.
+    pub default: Option<OptionValue>,
+    /// The minimum value of the option.
+    pub min: f64,
+    /// The maximum value of the option.
+    pub max: f64,
+    /// The `AV_OPT_FLAG_*` flags of the option.
+    pub flags: i32,
+    /// The named constants that can be used as the value of the option.
+    pub constants: Vec<OptionConstant>,
+}
+
+impl OptionInfo {
+    /// Returns `true` if the option is deprecated.
+    pub const fn is_deprecated(&self) -> bool {
+        self.flags & AV_OPT_FLAG_DEPRECATED as i32 != 0
+    }
+
+    /// Returns `true` if the option can only be read.
+    pub const fn is_readonly(&self) -> bool {
+        self.flags & AV_OPT_FLAG_READONLY as i32 != 0
+    }
+
+    /// Returns `true` if `value` is within the range of the option.
+    pub fn in_range(&self, value: f64) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    /// Returns the value of the constant with the given name.
+    pub fn constant(&self, name: &str) -> Option<i64> {
+        self.constants
+            .iter()
+            .find(|constant| constant.name == name)
+            .map(|constant| constant.value)
+    }
+
+    fn from_ffi(option: &AVOption) -> Self {
+        let is_array = option.type_ as i32 & AV_OPT_TYPE_FLAG_ARRAY as i32 != 0;
+        let kind = AVOptionType(option.type_ as i32 & !(AV_OPT_TYPE_FLAG_ARRAY as i32));
+
+        let default = if is_array {
+            None
+        } else {
+            match kind {
+                AVOptionType::Flags
+                | AVOptionType::Int
+                | AVOptionType::Int64
+                | AVOptionType::UInt
+                | AVOptionType::UInt64
+                | AVOptionType::Bool
+                | AVOptionType::PixelFormat
+                | AVOptionType::SampleFormat
+                | AVOptionType::Duration => {
+                    // Safety: `i64_` is the active field of the union for integer options.
+                    Some(OptionValue::Int(unsafe { option.default_val.i64_ }))
+                }
+                AVOptionType::Double | AVOptionType::Float => {
+                    // Safety: `dbl` is the active field of the union for floating point options.
+                    Some(OptionValue::Float(unsafe { option.default_val.dbl }))
+                }
+                AVOptionType::Rational => {
+                    // Safety: `q` is the active field of the union for rational options.
+                    let q = unsafe { option.default_val.q };
+                    (q.den != 0).then(|| OptionValue::Rational(q.into()))
+                }
+                AVOptionType::String
+                | AVOptionType::ImageSize
+                | AVOptionType::VideoRate
+                | AVOptionType::Color
+                | AVOptionType::ChannelLayout => {
+                    // Safety: `str_` is the active field of the union for string options.
+                    let value = unsafe { option.default_val.str_ };
+                    // Safety: The default value is either null or a static c-string.
+                    unsafe { c_str(value) }.map(|value| OptionValue::String(value.to_owned()))
+                }
+                _ => None,
+            }
+        };
+
+        Self {
+            // Safety: The name of an option is a static c-string.
+            name: unsafe { c_str(option.name) }.unwrap_or_default().to_owned(),
+            // Safety: The help of an option is either null or a static c-string.
+            help: unsafe { c_str(option.help) }.map(ToOwned::to_owned),
+            kind,
+            is_array,
+            default,
+            min: option.min,
+            max: option.max,
+            flags: option.flags,
+            constants: Vec::new(),
+        }
+    }
+}
+
+/// Returns the options of an [`AVClass`], with the constants grouped under the options using them.
+///
+/// # Safety
+/// `class` must be null or point to a valid [`AVClass`].
+pub(crate) unsafe fn class_options(class: *const AVClass) -> Vec<OptionInfo> {
+    if class.is_null() {
+        return Vec::new();
+    }
+
+    // `av_opt_next` takes a pointer to a struct starting with an `AVClass` pointer.
+    let obj = std::ptr::from_ref(&class).cast::<std::ffi::c_void>();
+
+    let mut options = Vec::new();
+    let mut constants = Vec::new();
+    let mut option: *const AVOption = std::ptr::null();
+
+    loop {
+        // Safety: `obj` points to a valid class pointer and `option` is null or was returned by `av_opt_next`.
+        option = unsafe { av_opt_next(obj, option) };
+
+        // Safety: The option is either null or valid for the lifetime of the class.
+        let Some(option_ref) = (unsafe { option.as_ref() }) else {
+            break;
+        };
+
+        // Safety: The unit of an option is either null or a static c-string.
+        let unit = unsafe { c_str(option_ref.unit) };
+
+        if option_ref.type_ as i32 == AVOptionType::Const.0 {
+            constants.push((
+                unit,
+                OptionConstant {
+                    // Safety: The name of an option is a static c-string.
+                    name: unsafe { c_str(option_ref.name) }.unwrap_or_default().to_owned(),
+                    // Safety: The help of an option is either null or a static c-string.
+                    help: unsafe { c_str(option_ref.help) }.map(ToOwned::to_owned),
+                    // Safety: `i64_` is the active field of the union for constants.
+                    value: unsafe { option_ref.default_val.i64_ },
+                },
+            ));
+        } else {
+            options.push((unit, OptionInfo::from_ffi(option_ref)));
+        }
+    }
+
+    options
+        .into_iter()
+        .map(|(unit, mut option)| {
+            if unit.is_some() {
+                option.constants = constants
+                    .iter()
+                    .filter(|(constant_unit, _)| *constant_unit == unit)
+                    .map(|(_, constant)| constant.clone())
+                    .collect();
+            }
+
+            option
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::AVOptionType;
+    use crate::ffi::avcodec_get_class;
+    use crate::opt::{OptionValue, class_options};
+
+    #[test]
+    fn test_class_options() {
+        // Safety: `avcodec_get_class` is safe to call and returns a static class.
+        let options = unsafe { class_options(avcodec_get_class()) };
+        assert!(!options.is_empty(), "Expected the codec context to have options");
+
+        let bitrate = options
+            .iter()
+            .find(|option| option.name == "b")
+            .expect("Expected a bitrate option");
+        assert_eq!(bitrate.kind, AVOptionType::Int64);
+        assert_eq!(bitrate.default, Some(OptionValue::Int(200_000)));
+        assert!(bitrate.in_range(1_000_000.0));
+        assert!(!bitrate.in_range(-1.0));
+
+        let flags = options
+            .iter()
+            .find(|option| option.name == "flags")
+            .expect("Expected a flags option");
+        assert_eq!(flags.kind, AVOptionType::Flags);
+        assert!(flags.constant("global_header").is_some(), "Expected the flags constants");
+        assert!(
+            options.iter().all(|option| option.kind != AVOptionType::Const),
+            "Expected constants to be grouped under their options"
+        );
+    }
+
+    #[test]
+    fn test_class_options_null() {
+        // Safety: A null class is allowed.
+        assert!(unsafe { class_options(std::ptr::null()) }.is_empty());
+    }
+}
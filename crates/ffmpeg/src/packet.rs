@@ -3,9 +3,10 @@ use std::marker::PhantomData;
 use crate::error::{FfmpegError, FfmpegErrorCode};
 use crate::ffi::*;
 use crate::rational::Rational;
+use crate::side_data::{SideData, packet_side_data_entries, parse_packet_side_data, side_data_bytes};
 use crate::smart_object::SmartPtr;
 use crate::utils::{check_i64, or_nopts};
-use crate::{AVPktFlags, AVRounding};
+use crate::{AVPacketSideDataType, AVPktFlags, AVRounding};
 
 /// A collection of packets. [`Packets`] implements [`Iterator`] and will yield packets until the end of the stream is reached.
 /// A wrapper around an [`AVFormatContext`].
@@ -100,6 +101,20 @@ impl Packet {
         unsafe { Self::wrap(packet) }.ok_or(FfmpegError::Alloc)
     }
 
+    /// Creates a new `Packet` with a copy of the given data.
+    pub fn from_data(data: &[u8]) -> Result<Self, FfmpegError> {
+        let size = i32::try_from(data.len()).map_err(|_| FfmpegError::Arguments("packet data is too large"))?;
+        let mut packet = Self::new()?;
+
+        // Safety: `av_new_packet` is safe to call, the packet is valid.
+        FfmpegErrorCode(unsafe { av_new_packet(packet.as_mut_ptr(), size) }).result()?;
+
+        // Safety: The packet data was allocated with `size` bytes.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), packet.0.as_deref_mut_except().data, data.len()) };
+
+        Ok(packet)
+    }
+
     /// Wraps a pointer to a packet.
     /// We take ownership of the pointer and free it when the `Packet` is dropped.
     ///
@@ -238,6 +253,57 @@ impl Packet {
     pub const fn flags(&self) -> AVPktFlags {
         AVPktFlags(self.0.as_deref_except().flags)
     }
+
+    /// Sets the flags of the packet.
+    pub const fn set_flags(&mut self, flags: AVPktFlags) {
+        self.0.as_deref_mut_except().flags = flags.0;
+    }
+
+    /// Returns the side data of the packet that has a typed representation.
+    pub fn side_data(&self) -> Vec<SideData> {
+        let packet = self.0.as_deref_except();
+        // Safety: `side_data` is an array of `side_data_elems` entries owned by the packet.
+        parse_packet_side_data(unsafe { packet_side_data_entries(packet.side_data, packet.side_data_elems) })
+    }
+
+    /// Returns the raw side data of the given type.
+    pub fn side_data_raw(&self, kind: AVPacketSideDataType) -> Option<&[u8]> {
+        let mut size = 0;
+        // Safety: av_packet_get_side_data is safe to call, the packet is valid.
+        let data = unsafe { av_packet_get_side_data(self.as_ptr(), kind.into(), &mut size) };
+        if data.is_null() {
+            return None;
+        }
+
+        // Safety: The side data is valid for `size` bytes while the packet is borrowed.
+        Some(unsafe { side_data_bytes(data, size) })
+    }
+
+    /// Adds side data to the packet, replacing side data of the same type.
+    pub fn add_side_data(&mut self, side_data: &SideData) -> Result<(), FfmpegError> {
+        self.set_side_data_raw(side_data.packet_type(), &side_data.to_bytes())
+    }
+
+    /// Sets the raw side data of the given type, replacing side data of the same type.
+    pub fn set_side_data_raw(&mut self, kind: AVPacketSideDataType, data: &[u8]) -> Result<(), FfmpegError> {
+        // Safety: av_packet_new_side_data is safe to call, the packet is valid.
+        let ptr = unsafe { av_packet_new_side_data(self.as_mut_ptr(), kind.into(), data.len()) };
+        if ptr.is_null() {
+            return Err(FfmpegError::Alloc);
+        }
+
+        // Safety: The side data was allocated with `data.len()` bytes.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len()) };
+
+        Ok(())
+    }
+
+    /// Removes the side data of the given type from the packet.
+    pub fn remove_side_data(&mut self, kind: AVPacketSideDataType) {
+        let packet = self.0.as_deref_mut_except();
+        // Safety: av_packet_side_data_remove is safe to call, the side data array belongs to the packet.
+        unsafe { av_packet_side_data_remove(packet.side_data, &mut packet.side_data_elems, kind.into()) };
+    }
 }
 
 #[cfg(test)]
@@ -247,6 +313,8 @@ mod tests {
 
     use crate::ffi::AVRational;
     use crate::packet::Packet;
+    use crate::side_data::SideData;
+    use crate::{AVPacketSideDataType, AVPktFlags};
 
     #[test]
     fn test_packet_clone_snapshot() {
@@ -380,4 +448,53 @@ mod tests {
             "Expected the data slice to be empty when packet size is zero"
         );
     }
+
+    #[test]
+    fn test_packet_side_data() {
+        let mut packet = Packet::new().expect("Failed to create Packet");
+        assert!(packet.side_data().is_empty(), "Expected a new packet to have no side data");
+
+        let extradata = SideData::NewExtradata(vec![1, 2, 3, 4]);
+        let captions = SideData::A53ClosedCaptions(vec![0xfc, 0x94, 0x20]);
+        packet.add_side_data(&extradata).expect("Failed to add side data");
+        packet.add_side_data(&captions).expect("Failed to add side data");
+        assert_eq!(packet.side_data(), vec![extradata.clone(), captions.clone()]);
+
+        // Adding side data of the same type replaces it.
+        let captions = SideData::A53ClosedCaptions(vec![0xfc, 0x80, 0x80]);
+        packet.add_side_data(&captions).expect("Failed to add side data");
+        assert_eq!(
+            packet.side_data_raw(AVPacketSideDataType::A53Cc),
+            Some([0xfc, 0x80, 0x80].as_slice())
+        );
+
+        // Side data without a typed representation is only available as raw bytes.
+        packet
+            .set_side_data_raw(AVPacketSideDataType::Palette, &[0; 16])
+            .expect("Failed to add side data");
+        assert_eq!(packet.side_data().len(), 2);
+        assert_eq!(packet.side_data_raw(AVPacketSideDataType::Palette), Some([0; 16].as_slice()));
+
+        // Side data survives cloning the packet.
+        let cloned = packet.clone();
+        assert_eq!(cloned.side_data(), vec![extradata, captions.clone()]);
+
+        packet.remove_side_data(AVPacketSideDataType::NewExtradata);
+        assert_eq!(packet.side_data_raw(AVPacketSideDataType::NewExtradata), None);
+        assert_eq!(packet.side_data(), vec![captions]);
+    }
+
+    #[test]
+    fn test_packet_from_data() {
+        let mut packet = Packet::from_data(&[0, 0, 0, 1, 0x65]).expect("Failed to create Packet");
+        assert_eq!(packet.data(), &[0, 0, 0, 1, 0x65]);
+        assert!(!packet.is_key(), "Expected a new packet to not be a keyframe");
+
+        packet.set_flags(AVPktFlags::Key | AVPktFlags::Disposable);
+        assert!(packet.is_key(), "Expected the packet to be a keyframe");
+        assert!(packet.is_disposable(), "Expected the packet to be disposable");
+
+        let empty = Packet::from_data(&[]).expect("Failed to create Packet");
+        assert!(empty.data().is_empty(), "Expected an empty packet");
+    }
 }
@@ -0,0 +1,237 @@
+use crate::error::{FfmpegError, FfmpegErrorCode};
+use crate::ffi::*;
+use crate::packet::Packet;
+use crate::smart_object::SmartPtr;
+use crate::utils::{check_i64, or_nopts};
+use crate::{AVCodecID, AVPictureType, AVPktFlags};
+
+/// A codec parser. Thin wrapper around [`AVCodecParserContext`].
+///
+/// A parser splits a raw elementary stream, like an Annex B H.264 stream or an
+/// ADTS AAC stream, into packets that each contain a single frame.
+pub struct Parser {
+    parser: SmartPtr<AVCodecParserContext>,
+    codec: SmartPtr<AVCodecContext>,
+}
+
+/// Safety: `Parser` can be sent between threads.
+unsafe impl Send for Parser {}
+
+impl std::fmt::Debug for Parser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Parser").field("codec_id", &self.codec_id()).finish()
+    }
+}
+
+impl Parser {
+    /// Creates a new parser for the given codec.
+    ///
+    /// Returns [`FfmpegError::NoDecoder`] if there is no parser for the codec.
+    pub fn new(codec_id: AVCodecID) -> Result<Self, FfmpegError> {
+        let parser_destructor = |ptr: &mut *mut AVCodecParserContext| {
+            // Safety: The pointer here is valid and was allocated by `av_parser_init`.
+            unsafe { av_parser_close(*ptr) };
+            *ptr = std::ptr::null_mut();
+        };
+
+        // Safety: `av_parser_init` is safe to call with any codec id.
+        let parser = unsafe { av_parser_init(codec_id.0 as _) };
+
+        // Safety: `parser` is a valid pointer, and `parser_destructor` has been setup to free the context.
+        let parser = unsafe { SmartPtr::wrap_non_null(parser, parser_destructor) }.ok_or(FfmpegError::NoDecoder)?;
+
+        let codec_destructor = |ptr: &mut *mut AVCodecContext| {
+            // Safety: The pointer here is valid.
+            unsafe { avcodec_free_context(ptr) };
+        };
+
+        // Safety: `avcodec_alloc_context3` is safe to call with a null codec.
+        let codec = unsafe { avcodec_alloc_context3(std::ptr::null()) };
+
+        // Safety: `codec` is a valid pointer, and `codec_destructor` has been setup to free the context.
+        let mut codec = unsafe { SmartPtr::wrap_non_null(codec, codec_destructor) }.ok_or(FfmpegError::Alloc)?;
+        codec.as_deref_mut_except().codec_id = codec_id.into();
+
+        Ok(Self { parser, codec })
+    }
+
+    /// Returns the codec id of the parser.
+    pub const fn codec_id(&self) -> AVCodecID {
+        AVCodecID(self.codec.as_deref_except().codec_id as _)
+    }
+
+    /// Returns the width of the last parsed video frame, if known.
+    pub const fn width(&self) -> Option<i32> {
+        let width = self.parser.as_deref_except().width;
+        if width > 0 { Some(width) } else { None }
+    }
+
+    /// Returns the height of the last parsed video frame, if known.
+    pub const fn height(&self) -> Option<i32> {
+        let height = self.parser.as_deref_except().height;
+        if height > 0 { Some(height) } else { None }
+    }
+
+    /// Returns the picture type of the last parsed video frame.
+    pub const fn picture_type(&self) -> AVPictureType {
+        AVPictureType(self.parser.as_deref_except().pict_type as _)
+    }
+
+    /// Parses a chunk of the elementary stream and returns the complete packets found in it.
+    ///
+    /// The data does not need to be aligned to frame boundaries, incomplete frames are buffered
+    /// until the next call. `pts` and `dts` are the timestamps of the chunk, they are assigned to the
+    /// packet that starts in it.
+    pub fn parse(&mut self, data: &[u8], pts: Option<i64>, dts: Option<i64>) -> Result<Vec<Packet>, FfmpegError> {
+        // The parser may read past the end of the input, so it needs to be padded.
+        let mut buffer = Vec::with_capacity(data.len() + AV_INPUT_BUFFER_PADDING_SIZE as usize);
+        buffer.extend_from_slice(data);
+        buffer.resize(data.len() + AV_INPUT_BUFFER_PADDING_SIZE as usize, 0);
+
+        let mut packets = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let remaining = i32::try_from(data.len() - offset).map_err(|_| FfmpegError::Arguments("data is too large"))?;
+            let (consumed, packet) = self.parse_chunk(buffer[offset..].as_ptr(), remaining, pts, dts)?;
+            packets.extend(packet);
+
+            offset += consumed;
+        }
+
+        Ok(packets)
+    }
+
+    /// Returns the packets that are still buffered in the parser.
+    ///
+    /// This should be called when the end of the elementary stream has been reached.
+    pub fn flush(&mut self) -> Result<Vec<Packet>, FfmpegError> {
+        let mut packets = Vec::new();
+
+        while let (_, Some(packet)) = self.parse_chunk(std::ptr::null(), 0, None, None)? {
+            packets.push(packet);
+        }
+
+        Ok(packets)
+    }
+
+    fn parse_chunk(
+        &mut self,
+        data: *const u8,
+        size: i32,
+        pts: Option<i64>,
+        dts: Option<i64>,
+    ) -> Result<(usize, Option<Packet>), FfmpegError> {
+        let mut out_data = std::ptr::null_mut();
+        let mut out_size = 0;
+
+        // Safety: `av_parser_parse2` is safe to call, the contexts are valid and `data` is either null
+        // or points to `size` bytes followed by `AV_INPUT_BUFFER_PADDING_SIZE` bytes of padding.
+        let consumed = FfmpegErrorCode(unsafe {
+            av_parser_parse2(
+                self.parser.as_mut_ptr(),
+                self.codec.as_mut_ptr(),
+                &mut out_data,
+                &mut out_size,
+                data,
+                size,
+                or_nopts(pts),
+                or_nopts(dts),
+                -1,
+            )
+        })
+        .result()?;
+
+        if out_size <= 0 || out_data.is_null() {
+            return Ok((consumed as usize, None));
+        }
+
+        // Safety: The parser returned a buffer of `out_size` bytes which is valid until the next call.
+        let out = unsafe { std::slice::from_raw_parts(out_data, out_size as usize) };
+
+        let mut packet = Packet::from_data(out)?;
+        let parser = self.parser.as_deref_except();
+        packet.set_pts(check_i64(parser.pts));
+        packet.set_dts(check_i64(parser.dts));
+        packet.set_pos(if parser.pos >= 0 { Some(parser.pos) } else { None });
+        if parser.duration > 0 {
+            packet.set_duration(Some(parser.duration as i64));
+        }
+
+        if parser.key_frame == 1 {
+            packet.set_flags(packet.flags() | AVPktFlags::Key);
+        }
+
+        Ok((consumed as usize, Some(packet)))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::bitstream_filter::BitstreamFilter;
+    use crate::error::FfmpegError;
+    use crate::io::Input;
+    use crate::parser::Parser;
+    use crate::{AVCodecID, AVMediaType, file_path};
+
+    #[test]
+    fn test_parser_annexb_roundtrip() {
+        let mut input = Input::open(file_path("avc_aac.mp4")).expect("Failed to open valid file");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+        let video_index = stream.index();
+        let codec_parameters = stream.codec_parameters().expect("Missing codec parameters");
+        let (width, height) = (codec_parameters.width, codec_parameters.height);
+
+        let mut filter = BitstreamFilter::new("h264_mp4toannexb", &stream).expect("Failed to create bitstream filter");
+
+        let mut elementary_stream = Vec::new();
+        let mut keyframes = 0;
+        let mut count = 0;
+        while let Some(packet) = input.receive_packet().expect("Failed to receive packet") {
+            if packet.stream_index() != video_index {
+                continue;
+            }
+
+            filter.send_packet(&packet).expect("Failed to send packet");
+            while let Some(packet) = filter.receive_packet().expect("Failed to receive packet") {
+                count += 1;
+                keyframes += packet.is_key() as usize;
+                elementary_stream.extend_from_slice(packet.data());
+            }
+        }
+
+        let mut parser = Parser::new(AVCodecID::H264).expect("Failed to create parser");
+        assert_eq!(parser.codec_id(), AVCodecID::H264);
+
+        let mut parsed = Vec::new();
+        for chunk in elementary_stream.chunks(4096) {
+            parsed.extend(parser.parse(chunk, None, None).expect("Failed to parse chunk"));
+        }
+        parsed.extend(parser.flush().expect("Failed to flush parser"));
+
+        assert_eq!(parsed.len(), count, "Expected the same number of packets after parsing");
+        assert_eq!(parsed.iter().filter(|packet| packet.is_key()).count(), keyframes);
+        assert_eq!(
+            parsed.iter().map(|packet| packet.data().len()).sum::<usize>(),
+            elementary_stream.len(),
+            "Expected the parsed packets to cover the whole stream"
+        );
+
+        assert_eq!(parser.width(), Some(width));
+        assert_eq!(parser.height(), Some(height));
+    }
+
+    #[test]
+    fn test_parser_empty_input() {
+        let mut parser = Parser::new(AVCodecID::Aac).expect("Failed to create parser");
+        assert!(parser.parse(&[], None, None).expect("Failed to parse").is_empty());
+        assert!(parser.flush().expect("Failed to flush parser").is_empty());
+    }
+
+    #[test]
+    fn test_parser_not_found() {
+        assert_eq!(Parser::new(AVCodecID::None).unwrap_err(), FfmpegError::NoDecoder);
+    }
+}
@@ -0,0 +1,557 @@
+//! Runs demuxers, decoders, filters, encoders and muxers as a graph of stages.
+//!
+//! The types of this crate are synchronous and most of them are not [`Sync`], so each stage
+//! runs on a dedicated thread. Stages are linked by bounded [`channel`]s, a stage waits when the
+//! next one is not keeping up. The channel halves implement [`futures::Stream`] and
+//! [`futures::Sink`], which allows async code to feed or consume a stage.
+//!
+//! A typical transcode looks like this:
+//!
+//! - [`Pipeline::spawn_demux`] sends the packets of each stream to its decoder.
+//! - [`Pipeline::spawn_stage`] runs each decoder, scaler, resampler, filter graph and encoder.
+//! - The encoders share clones of the same [`Sender`], which feeds [`Pipeline::spawn_mux`].
+//!
+//! Stages stop once their input channel is closed, after flushing the frames or packets they
+//! still buffer. Cancelling the [`Context`] the pipeline was created with stops every stage
+//! without flushing, and so does dropping the [`Pipeline`]. Note that a demuxer blocked on IO only notices the cancellation once the
+//! read returns, use an interrupt callback on the input if the read can block for long.
+
+mod channel;
+mod metrics;
+mod stage;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub use channel::*;
+pub use metrics::*;
+use scuffle_context::{Context, Handler};
+pub use stage::*;
+use tokio::sync::{oneshot, watch};
+
+use crate::error::FfmpegError;
+use crate::io::{Input, Output};
+use crate::packet::Packet;
+
+/// An error that occurs when a pipeline stage fails.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PipelineError {
+    /// An error that occurs when an ffmpeg operation of the stage fails.
+    #[error("ffmpeg error: {0}")]
+    Ffmpeg(#[from] FfmpegError),
+    /// An error that occurs when the pipeline was cancelled before the stage finished.
+    #[error("pipeline cancelled")]
+    Cancelled,
+    /// An error that occurs when the other half of a channel has been dropped.
+    #[error("channel closed")]
+    Closed,
+    /// An error that occurs when the thread of the stage panicked.
+    #[error("stage panicked")]
+    Panicked,
+    /// An error that occurs when the thread of the stage could not be spawned.
+    #[error("failed to spawn stage: {0}")]
+    Spawn(String),
+}
+
+/// A graph of stages, each running on a dedicated thread.
+///
+/// Dropping the pipeline cancels every stage that is still running, call [`Pipeline::wait`]
+/// first to let them finish. See the [module documentation](self) for an overview.
+#[derive(Debug)]
+pub struct Pipeline {
+    ctx: Context,
+    handler: Handler,
+    running: watch::Sender<usize>,
+    stages: Vec<(String, Arc<StageMetrics>)>,
+}
+
+impl Pipeline {
+    /// Creates a new pipeline, which is cancelled when `ctx` is done.
+    ///
+    /// The pipeline and its stage threads hold on to `ctx`, so shutting down its handler
+    /// waits for them.
+    pub fn new(ctx: &Context) -> Self {
+        let (_, handler) = ctx.new_child();
+
+        Self {
+            ctx: ctx.clone(),
+            handler,
+            running: watch::Sender::new(0),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Cancels every stage of the pipeline.
+    pub fn cancel(&self) {
+        self.handler.cancel();
+    }
+
+    /// Cancels every stage of the pipeline and waits for their threads to finish.
+    pub async fn shutdown(&self) {
+        self.cancel();
+        self.wait().await;
+    }
+
+    /// Waits for every stage of the pipeline to finish.
+    pub async fn wait(&self) {
+        // The sender is held by the pipeline, so the receiver never sees it closed.
+        let _ = self.running.subscribe().wait_for(|running| *running == 0).await;
+    }
+
+    /// Returns the name and metrics of every stage, in the order they were spawned.
+    pub fn metrics(&self) -> impl Iterator<Item = (&str, &StageMetrics)> {
+        self.stages.iter().map(|(name, metrics)| (name.as_str(), metrics.as_ref()))
+    }
+
+    /// Spawns a stage reading from `input` and writing to `output`.
+    ///
+    /// The stage is flushed once `input` is closed and all its items have been processed, the
+    /// handle then returns the stage. The stage stops early without an error if `output` is closed.
+    pub fn spawn_stage<S: Stage, I: Into<S::Input> + Send + 'static>(
+        &mut self,
+        name: &str,
+        mut stage: S,
+        mut input: Receiver<I>,
+        mut output: Sender<S::Output>,
+    ) -> Result<StageHandle<S>, PipelineError> {
+        self.spawn(name, move |ctx, metrics| {
+            let mut buffer = Vec::new();
+
+            while let Some(item) = input.blocking_recv(ctx)? {
+                metrics.record_received();
+
+                let start = Instant::now();
+                let result = stage.process(item.into(), &mut buffer);
+                metrics.record_busy(start.elapsed());
+                result.inspect_err(|_| metrics.record_error())?;
+
+                if !send_all(ctx, metrics, &mut output, &mut buffer)? {
+                    return Ok(stage);
+                }
+            }
+
+            let start = Instant::now();
+            let result = stage.flush(&mut buffer);
+            metrics.record_busy(start.elapsed());
+            result.inspect_err(|_| metrics.record_error())?;
+
+            send_all(ctx, metrics, &mut output, &mut buffer)?;
+
+            Ok(stage)
+        })
+    }
+
+    /// Spawns a demuxer, sending the packets of each stream index to the matching sender.
+    ///
+    /// Packets of streams without a sender are dropped. A closed sender is removed, and the
+    /// demuxer stops once every sender has been removed or the end of the input is reached.
+    /// The handle returns the input.
+    pub fn spawn_demux<T: Send + Sync + 'static>(
+        &mut self,
+        name: &str,
+        mut input: Input<T>,
+        outputs: impl IntoIterator<Item = (i32, Sender<Packet>)>,
+    ) -> Result<StageHandle<Input<T>>, PipelineError> {
+        let mut outputs: HashMap<_, _> = outputs.into_iter().collect();
+
+        self.spawn(name, move |ctx, metrics| {
+            while !outputs.is_empty() {
+                if ctx.is_done() {
+                    return Err(PipelineError::Cancelled);
+                }
+
+                let start = Instant::now();
+                let result = input.receive_packet();
+                metrics.record_busy(start.elapsed());
+
+                let Some(packet) = result.inspect_err(|_| metrics.record_error())? else {
+                    break;
+                };
+
+                metrics.record_received();
+
+                let stream_index = packet.stream_index();
+                let Some(output) = outputs.get_mut(&stream_index) else {
+                    continue;
+                };
+
+                match output.blocking_send(ctx, packet) {
+                    Ok(()) => metrics.record_sent(),
+                    Err(PipelineError::Closed) => {
+                        outputs.remove(&stream_index);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+
+            Ok(input)
+        })
+    }
+
+    /// Spawns a muxer, writing the packets received from `input` to `output`.
+    ///
+    /// The header is written before the first packet and the trailer once `input` is closed.
+    /// The packets are written with [`Output::write_interleaved_packet`], so the streams do not
+    /// have to be in sync. The handle returns the output.
+    pub fn spawn_mux<T: Send + Sync + 'static>(
+        &mut self,
+        name: &str,
+        mut output: Output<T>,
+        mut input: Receiver<Packet>,
+    ) -> Result<StageHandle<Output<T>>, PipelineError> {
+        self.spawn(name, move |ctx, metrics| {
+            output.write_header().inspect_err(|_| metrics.record_error())?;
+
+            while let Some(packet) = input.blocking_recv(ctx)? {
+                metrics.record_received();
+
+                let start = Instant::now();
+                let result = output.write_interleaved_packet(packet);
+                metrics.record_busy(start.elapsed());
+                result.inspect_err(|_| metrics.record_error())?;
+
+                metrics.record_sent();
+            }
+
+            output.write_trailer().inspect_err(|_| metrics.record_error())?;
+
+            Ok(output)
+        })
+    }
+
+    fn spawn<R: Send + 'static>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&Context, &StageMetrics) -> Result<R, PipelineError> + Send + 'static,
+    ) -> Result<StageHandle<R>, PipelineError> {
+        let metrics = Arc::new(StageMetrics::default());
+        let (tx, rx) = oneshot::channel();
+
+        let ctx = self.handler.context();
+        let thread_metrics = metrics.clone();
+        let guard = RunningGuard::new(&self.ctx, &self.running);
+
+        std::thread::Builder::new()
+            .name(format!("ffmpeg-{name}"))
+            .spawn(move || {
+                let result = f(&ctx, &thread_metrics);
+                // The stage is counted as running until the thread is done with it, even if it panics.
+                drop(ctx);
+                drop(guard);
+                let _ = tx.send(result);
+            })
+            .map_err(|err| PipelineError::Spawn(err.to_string()))?;
+
+        self.stages.push((name.to_owned(), metrics.clone()));
+
+        Ok(StageHandle { metrics, result: rx })
+    }
+}
+
+/// Counts a stage as running and keeps the context of the pipeline alive until it is dropped.
+struct RunningGuard {
+    _ctx: Context,
+    running: watch::Sender<usize>,
+}
+
+impl RunningGuard {
+    fn new(ctx: &Context, running: &watch::Sender<usize>) -> Self {
+        running.send_modify(|running| *running += 1);
+
+        Self {
+            _ctx: ctx.clone(),
+            running: running.clone(),
+        }
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.running.send_modify(|running| *running -= 1);
+    }
+}
+
+/// Sends the buffered outputs of a stage, returns `false` if the next stage is gone.
+fn send_all<T: Send + 'static>(
+    ctx: &Context,
+    metrics: &StageMetrics,
+    output: &mut Sender<T>,
+    buffer: &mut Vec<T>,
+) -> Result<bool, PipelineError> {
+    for item in buffer.drain(..) {
+        match output.blocking_send(ctx, item) {
+            Ok(()) => metrics.record_sent(),
+            Err(PipelineError::Closed) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(true)
+}
+
+/// A handle to a stage spawned on a [`Pipeline`].
+#[derive(Debug)]
+pub struct StageHandle<R> {
+    metrics: Arc<StageMetrics>,
+    result: oneshot::Receiver<Result<R, PipelineError>>,
+}
+
+impl<R> StageHandle<R> {
+    /// Returns the metrics of the stage.
+    pub fn metrics(&self) -> &StageMetrics {
+        &self.metrics
+    }
+
+    /// Waits for the stage to finish and returns its result.
+    pub async fn join(self) -> Result<R, PipelineError> {
+        self.result.await.unwrap_or(Err(PipelineError::Panicked))
+    }
+
+    /// Blocks the current thread until the stage finishes and returns its result.
+    ///
+    /// This must not be called from an async context, use [`StageHandle::join`] instead.
+    pub fn blocking_join(self) -> Result<R, PipelineError> {
+        futures::executor::block_on(self.join())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use scuffle_context::Context;
+
+    use crate::codec::EncoderCodec;
+    use crate::decoder::Decoder;
+    use crate::encoder::{Encoder, VideoEncoderSettings};
+    use crate::error::FfmpegError;
+    use crate::frame::GenericFrame;
+    use crate::io::{Input, Output, OutputOptions};
+    use crate::packet::Packet;
+    use crate::pipeline::{Pipeline, PipelineError, Stage, channel};
+    use crate::scaler::VideoScaler;
+    use crate::{AVCodecID, AVMediaType, AVPixelFormat, file_path};
+
+    #[test]
+    fn test_pipeline_transcode() {
+        let (ctx, _handler) = Context::new();
+        let mut pipeline = Pipeline::new(&ctx);
+
+        let input = Input::open(file_path("avc_aac.mp4")).expect("Failed to open valid file");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+        let video_index = stream.index();
+        let time_base = stream.time_base();
+
+        let decoder = Decoder::new(&stream)
+            .expect("Failed to create decoder")
+            .video()
+            .expect("Expected a video decoder");
+
+        let (width, height) = (decoder.width() / 2, decoder.height() / 2);
+        let scaler = VideoScaler::new(
+            decoder.width(),
+            decoder.height(),
+            decoder.pixel_format(),
+            width,
+            height,
+            AVPixelFormat::Yuv420p,
+        )
+        .expect("Failed to create scaler");
+
+        let mut output = Output::seekable(
+            std::io::Cursor::new(Vec::new()),
+            OutputOptions::builder().format_name("mp4").unwrap().build(),
+        )
+        .expect("Failed to create Output");
+
+        let encoder = Encoder::new(
+            EncoderCodec::new(AVCodecID::Mpeg4).expect("Missing MPEG-4 encoder"),
+            &mut output,
+            time_base,
+            time_base,
+            VideoEncoderSettings::builder()
+                .width(width)
+                .height(height)
+                .frame_rate(decoder.frame_rate())
+                .pixel_format(AVPixelFormat::Yuv420p)
+                .build(),
+        )
+        .expect("Failed to create encoder");
+
+        let (demux_tx, demux_rx) = channel(8);
+        let (decode_tx, decode_rx) = channel(8);
+        let (scale_tx, scale_rx) = channel(8);
+        let (encode_tx, encode_rx) = channel(8);
+
+        let demux = pipeline
+            .spawn_demux("demux", input, [(video_index, demux_tx)])
+            .expect("Failed to spawn demuxer");
+        let decode = pipeline
+            .spawn_stage("decode", decoder, demux_rx, decode_tx)
+            .expect("Failed to spawn decoder");
+        let scale = pipeline
+            .spawn_stage("scale", scaler, decode_rx, scale_tx)
+            .expect("Failed to spawn scaler");
+        let encode = pipeline
+            .spawn_stage("encode", encoder, scale_rx, encode_tx)
+            .expect("Failed to spawn encoder");
+        let mux = pipeline.spawn_mux("mux", output, encode_rx).expect("Failed to spawn muxer");
+
+        let names: Vec<_> = pipeline.metrics().map(|(name, _)| name).collect();
+        assert_eq!(names, ["demux", "decode", "scale", "encode", "mux"]);
+
+        demux.blocking_join().expect("Demuxer failed");
+        decode.blocking_join().expect("Decoder failed");
+        scale.blocking_join().expect("Scaler failed");
+        encode.blocking_join().expect("Encoder failed");
+        let data = mux.blocking_join().expect("Muxer failed").into_inner().into_inner();
+
+        let metrics: Vec<_> = pipeline.metrics().map(|(_, metrics)| metrics).collect();
+        let [demux_metrics, decode_metrics, scale_metrics, encode_metrics, mux_metrics] = metrics[..] else {
+            panic!("Expected five stages");
+        };
+
+        assert!(demux_metrics.sent() > 0, "Expected the demuxer to send packets");
+        assert_eq!(demux_metrics.sent(), decode_metrics.received());
+        assert_eq!(decode_metrics.sent(), scale_metrics.received());
+        assert_eq!(scale_metrics.sent(), encode_metrics.received());
+        assert_eq!(encode_metrics.sent(), mux_metrics.received());
+        assert_eq!(mux_metrics.received(), mux_metrics.sent());
+        assert!(metrics.iter().all(|metrics| metrics.errors() == 0));
+
+        let mut input = Input::seekable(std::io::Cursor::new(data)).expect("Failed to open output");
+        let streams = input.streams();
+        let stream = streams.best(AVMediaType::Video).expect("Expected a video stream");
+        let codec_parameters = stream.codec_parameters().expect("Missing codec parameters");
+        assert_eq!(AVCodecID(codec_parameters.codec_id as _), AVCodecID::Mpeg4);
+        assert_eq!(codec_parameters.width, width);
+        assert_eq!(codec_parameters.height, height);
+
+        let mut packets = 0u64;
+        while input.receive_packet().expect("Failed to receive packet").is_some() {
+            packets += 1;
+        }
+        assert_eq!(packets, mux_metrics.sent());
+    }
+
+    #[derive(Debug)]
+    struct Passthrough;
+
+    impl Stage for Passthrough {
+        type Input = Packet;
+        type Output = Packet;
+
+        fn process(&mut self, input: Packet, output: &mut Vec<Packet>) -> Result<(), FfmpegError> {
+            output.push(input);
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Failing;
+
+    impl Stage for Failing {
+        type Input = GenericFrame;
+        type Output = GenericFrame;
+
+        fn process(&mut self, _: GenericFrame, _: &mut Vec<GenericFrame>) -> Result<(), FfmpegError> {
+            Err(FfmpegError::NoFrame)
+        }
+    }
+
+    #[test]
+    fn test_pipeline_stream_sink() {
+        let (ctx, _handler) = Context::new();
+        let mut pipeline = Pipeline::new(&ctx);
+
+        let (mut input_tx, input_rx) = channel(2);
+        let (output_tx, output_rx) = channel(2);
+
+        let stage = pipeline
+            .spawn_stage("passthrough", Passthrough, input_rx, output_tx)
+            .expect("Failed to spawn stage");
+
+        let received = futures::executor::block_on(async move {
+            let sending = async move {
+                for _ in 0..16 {
+                    input_tx.send(Packet::new().unwrap()).await.expect("Failed to send packet");
+                }
+
+                input_tx.close().await.expect("Failed to close sender");
+            };
+
+            let (_, received) = futures::join!(sending, output_rx.collect::<Vec<_>>());
+            received
+        });
+
+        assert_eq!(received.len(), 16);
+        assert_eq!(stage.metrics().received(), 16);
+        stage.blocking_join().expect("Stage failed");
+    }
+
+    #[test]
+    fn test_pipeline_wait() {
+        let (ctx, handler) = Context::new();
+        let mut pipeline = Pipeline::new(&ctx);
+        drop(ctx);
+
+        let (input_tx, input_rx) = channel::<Packet>(1);
+        let (output_tx, output_rx) = channel(1);
+
+        let stage = pipeline
+            .spawn_stage("passthrough", Passthrough, input_rx, output_tx)
+            .expect("Failed to spawn stage");
+
+        // Closing the input lets the stage finish without cancelling the pipeline.
+        drop(input_tx);
+        futures::executor::block_on(pipeline.wait());
+        assert!(!handler.is_done());
+        stage.blocking_join().expect("Stage failed");
+        drop(output_rx);
+
+        // The parent handler waits for the pipeline, which is done once it is dropped.
+        drop(pipeline);
+        futures::executor::block_on(handler.shutdown());
+    }
+
+    #[test]
+    fn test_pipeline_cancel() {
+        let (ctx, _handler) = Context::new();
+        let mut pipeline = Pipeline::new(&ctx);
+
+        // The sender is kept alive, so the stage waits for input until it is cancelled.
+        let (_input_tx, input_rx) = channel::<Packet>(1);
+        let (output_tx, _output_rx) = channel(1);
+
+        let stage = pipeline
+            .spawn_stage("passthrough", Passthrough, input_rx, output_tx)
+            .expect("Failed to spawn stage");
+
+        pipeline.cancel();
+        assert_eq!(stage.blocking_join().unwrap_err(), PipelineError::Cancelled);
+        futures::executor::block_on(pipeline.wait());
+    }
+
+    #[test]
+    fn test_pipeline_stage_error() {
+        let (ctx, _handler) = Context::new();
+        let mut pipeline = Pipeline::new(&ctx);
+
+        let (mut input_tx, input_rx) = channel(1);
+        let (output_tx, _output_rx) = channel(1);
+
+        let stage = pipeline
+            .spawn_stage("failing", Failing, input_rx, output_tx)
+            .expect("Failed to spawn stage");
+
+        futures::executor::block_on(input_tx.send(GenericFrame::new().unwrap())).expect("Failed to send frame");
+
+        assert_eq!(
+            stage.blocking_join().unwrap_err(),
+            PipelineError::Ffmpeg(FfmpegError::NoFrame)
+        );
+        assert_eq!(pipeline.metrics().next().unwrap().1.errors(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+
+use futures::future::Either;
+use scuffle_context::Context;
+use tokio::sync::mpsc;
+use tokio_util::sync::PollSender;
+
+use super::PipelineError;
+
+/// Creates a bounded channel used to link pipeline stages.
+///
+/// A stage waits when its output channel is full, which applies backpressure to the stages before it.
+pub fn channel<T: Send + 'static>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::channel(capacity);
+
+    (
+        Sender {
+            inner: PollSender::new(tx),
+        },
+        Receiver { inner: rx },
+    )
+}
+
+/// The sending half of a pipeline [`channel`].
+///
+/// Implements [`futures::Sink`] so it can be fed from async code.
+pub struct Sender<T> {
+    inner: PollSender<T>,
+}
+
+impl<T: Send + 'static> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Send> std::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender").field("closed", &self.inner.is_closed()).finish()
+    }
+}
+
+impl<T: Send + 'static> Sender<T> {
+    /// Sends an item, waiting until there is capacity in the channel.
+    ///
+    /// Returns [`PipelineError::Closed`] if the receiving half has been dropped.
+    pub async fn send(&mut self, item: T) -> Result<(), PipelineError> {
+        std::future::poll_fn(|cx| self.inner.poll_reserve(cx))
+            .await
+            .map_err(|_| PipelineError::Closed)?;
+
+        self.inner.send_item(item).map_err(|_| PipelineError::Closed)
+    }
+
+    /// Returns `true` if the receiving half has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub(super) fn blocking_send(&mut self, ctx: &Context, item: T) -> Result<(), PipelineError> {
+        block_on_cancellable(ctx, self.send(item))?
+    }
+}
+
+impl<T: Send + 'static> futures::Sink<T> for Sender<T> {
+    type Error = PipelineError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().inner.poll_reserve(cx).map_err(|_| PipelineError::Closed)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.get_mut().inner.send_item(item).map_err(|_| PipelineError::Closed)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().inner.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The receiving half of a pipeline [`channel`].
+///
+/// Implements [`futures::Stream`] so it can be consumed from async code.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: mpsc::Receiver<T>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next item.
+    ///
+    /// Returns `None` once all sending halves have been dropped and the channel is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        self.inner.recv().await
+    }
+
+    /// Closes the channel, the stage sending to it stops after its current item.
+    pub fn close(&mut self) {
+        self.inner.close();
+    }
+
+    pub(super) fn blocking_recv(&mut self, ctx: &Context) -> Result<Option<T>, PipelineError> {
+        block_on_cancellable(ctx, self.recv())
+    }
+}
+
+impl<T> futures::Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().inner.poll_recv(cx)
+    }
+}
+
+/// Blocks the current thread on `future`, returns [`PipelineError::Cancelled`] if `ctx` is done first.
+fn block_on_cancellable<F: Future>(ctx: &Context, future: F) -> Result<F::Output, PipelineError> {
+    futures::executor::block_on(async {
+        let future = std::pin::pin!(future);
+        let done = std::pin::pin!(ctx.done());
+
+        match futures::future::select(future, done).await {
+            Either::Left((output, _)) => Ok(output),
+            Either::Right(_) => Err(PipelineError::Cancelled),
+        }
+    })
+}
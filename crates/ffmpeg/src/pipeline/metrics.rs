@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Metrics of a pipeline stage.
+///
+/// The counters are updated by the thread running the stage and can be read at any time.
+#[derive(Debug, Default)]
+pub struct StageMetrics {
+    received: AtomicU64,
+    sent: AtomicU64,
+    errors: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+impl StageMetrics {
+    /// Returns the number of items the stage received.
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of items the stage sent to the next stage.
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of errors the stage encountered.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time the stage spent processing, excluding the time spent waiting on its channels.
+    pub fn busy_time(&self) -> Duration {
+        Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed))
+    }
+
+    pub(super) fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_busy(&self, duration: Duration) {
+        self.busy_nanos
+            .fetch_add(duration.as_nanos().try_into().unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+}
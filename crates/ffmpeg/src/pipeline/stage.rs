@@ -0,0 +1,256 @@
+use crate::audio_fifo::AudioFrameAdapter;
+use crate::bitstream_filter::BitstreamFilter;
+use crate::decoder::{AudioDecoder, SubtitleDecoder, VideoDecoder};
+use crate::encoder::{Encoder, SubtitleEncoder};
+use crate::error::FfmpegError;
+use crate::filter_graph::FilterGraph;
+use crate::frame::{AudioFrame, GenericFrame, VideoFrame};
+use crate::packet::Packet;
+use crate::resampler::Resampler;
+use crate::scaler::VideoScaler;
+use crate::subtitle::Subtitle;
+
+/// A stage of a [`Pipeline`](super::Pipeline), turning inputs into outputs on a dedicated thread.
+///
+/// This is implemented for the decoders, encoders, scaler, resampler, audio frame adapter and bitstream filter of this crate,
+/// and [`FilterGraphStage`] wraps a [`FilterGraph`].
+pub trait Stage: Send + 'static {
+    /// The type of the items the stage receives.
+    type Input: Send + 'static;
+    /// The type of the items the stage produces.
+    type Output: Send + 'static;
+
+    /// Processes an input, pushing the outputs that are ready to `output`.
+    fn process(&mut self, input: Self::Input, output: &mut Vec<Self::Output>) -> Result<(), FfmpegError>;
+
+    /// Called after the last input, pushing the outputs that are still buffered to `output`.
+    fn flush(&mut self, output: &mut Vec<Self::Output>) -> Result<(), FfmpegError> {
+        let _ = output;
+        Ok(())
+    }
+}
+
+impl Stage for VideoDecoder {
+    type Input = Packet;
+    type Output = VideoFrame;
+
+    fn process(&mut self, input: Packet, output: &mut Vec<VideoFrame>) -> Result<(), FfmpegError> {
+        self.send_packet(&input)?;
+        while let Some(frame) = self.receive_frame()? {
+            output.push(frame);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<VideoFrame>) -> Result<(), FfmpegError> {
+        self.send_eof()?;
+        while let Some(frame) = self.receive_frame()? {
+            output.push(frame);
+        }
+
+        Ok(())
+    }
+}
+
+impl Stage for AudioDecoder {
+    type Input = Packet;
+    type Output = AudioFrame;
+
+    fn process(&mut self, input: Packet, output: &mut Vec<AudioFrame>) -> Result<(), FfmpegError> {
+        self.send_packet(&input)?;
+        while let Some(frame) = self.receive_frame()? {
+            output.push(frame);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<AudioFrame>) -> Result<(), FfmpegError> {
+        self.send_eof()?;
+        while let Some(frame) = self.receive_frame()? {
+            output.push(frame);
+        }
+
+        Ok(())
+    }
+}
+
+impl Stage for SubtitleDecoder {
+    type Input = Packet;
+    type Output = Subtitle;
+
+    fn process(&mut self, input: Packet, output: &mut Vec<Subtitle>) -> Result<(), FfmpegError> {
+        output.extend(self.decode(&input)?);
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<Subtitle>) -> Result<(), FfmpegError> {
+        while let Some(subtitle) = self.decode_eof()? {
+            output.push(subtitle);
+        }
+
+        Ok(())
+    }
+}
+
+impl Stage for Encoder {
+    type Input = GenericFrame;
+    type Output = Packet;
+
+    fn process(&mut self, input: GenericFrame, output: &mut Vec<Packet>) -> Result<(), FfmpegError> {
+        self.send_frame(&input)?;
+        while let Some(packet) = self.receive_packet()? {
+            output.push(packet);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<Packet>) -> Result<(), FfmpegError> {
+        self.send_eof()?;
+        while let Some(packet) = self.receive_packet()? {
+            output.push(packet);
+        }
+
+        Ok(())
+    }
+}
+
+impl Stage for SubtitleEncoder {
+    type Input = Subtitle;
+    type Output = Packet;
+
+    fn process(&mut self, input: Subtitle, output: &mut Vec<Packet>) -> Result<(), FfmpegError> {
+        output.extend(self.encode(&input)?);
+        Ok(())
+    }
+}
+
+impl Stage for VideoScaler {
+    type Input = VideoFrame;
+    type Output = VideoFrame;
+
+    fn process(&mut self, input: VideoFrame, output: &mut Vec<VideoFrame>) -> Result<(), FfmpegError> {
+        output.push(VideoScaler::process(self, &input)?.clone());
+        Ok(())
+    }
+}
+
+impl Stage for Resampler {
+    type Input = AudioFrame;
+    type Output = AudioFrame;
+
+    fn process(&mut self, input: AudioFrame, output: &mut Vec<AudioFrame>) -> Result<(), FfmpegError> {
+        output.push(Resampler::process(self, &input)?);
+        Ok(())
+    }
+}
+
+impl Stage for AudioFrameAdapter {
+    type Input = AudioFrame;
+    type Output = AudioFrame;
+
+    fn process(&mut self, input: AudioFrame, output: &mut Vec<AudioFrame>) -> Result<(), FfmpegError> {
+        self.send_frame(&input)?;
+        while let Some(frame) = self.receive_frame()? {
+            output.push(frame);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<AudioFrame>) -> Result<(), FfmpegError> {
+        output.extend(AudioFrameAdapter::flush(self)?);
+        Ok(())
+    }
+}
+
+impl Stage for BitstreamFilter {
+    type Input = Packet;
+    type Output = Packet;
+
+    fn process(&mut self, input: Packet, output: &mut Vec<Packet>) -> Result<(), FfmpegError> {
+        self.send_packet(&input)?;
+        while let Some(packet) = self.receive_packet()? {
+            output.push(packet);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<Packet>) -> Result<(), FfmpegError> {
+        self.send_eof()?;
+        while let Some(packet) = self.receive_packet()? {
+            output.push(packet);
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`FilterGraph`] with a single input and a single output, run as a pipeline [`Stage`].
+pub struct FilterGraphStage {
+    graph: FilterGraph,
+    input: String,
+    output: String,
+}
+
+impl std::fmt::Debug for FilterGraphStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterGraphStage")
+            .field("input", &self.input)
+            .field("output", &self.output)
+            .finish()
+    }
+}
+
+impl FilterGraphStage {
+    /// Creates a new stage from a configured filter graph.
+    ///
+    /// `input` is the name of the buffer source filter and `output` is the name of the buffer sink filter.
+    pub fn new(mut graph: FilterGraph, input: impl Into<String>, output: impl Into<String>) -> Result<Self, FfmpegError> {
+        let input = input.into();
+        let output = output.into();
+
+        if graph.get(&input).is_none() || graph.get(&output).is_none() {
+            return Err(FfmpegError::NoFilter);
+        }
+
+        Ok(Self { graph, input, output })
+    }
+
+    fn drain(&mut self, output: &mut Vec<GenericFrame>) -> Result<(), FfmpegError> {
+        let mut sink = self.graph.get(&self.output).ok_or(FfmpegError::NoFilter)?.sink();
+        while let Some(frame) = sink.receive_frame()? {
+            output.push(frame);
+        }
+
+        Ok(())
+    }
+}
+
+impl Stage for FilterGraphStage {
+    type Input = GenericFrame;
+    type Output = GenericFrame;
+
+    fn process(&mut self, input: GenericFrame, output: &mut Vec<GenericFrame>) -> Result<(), FfmpegError> {
+        self.graph
+            .get(&self.input)
+            .ok_or(FfmpegError::NoFilter)?
+            .source()
+            .send_frame(&input)?;
+
+        self.drain(output)
+    }
+
+    fn flush(&mut self, output: &mut Vec<GenericFrame>) -> Result<(), FfmpegError> {
+        self.graph
+            .get(&self.input)
+            .ok_or(FfmpegError::NoFilter)?
+            .source()
+            .send_eof(None)?;
+
+        self.drain(output)
+    }
+}
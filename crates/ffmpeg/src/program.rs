@@ -0,0 +1,119 @@
+use crate::AVDiscard;
+use crate::consts::Const;
+use crate::dict::Dictionary;
+use crate::ffi::*;
+use crate::timestamp::Timestamp;
+use crate::utils::check_i64;
+
+/// A program is a wrapper around an [`AVProgram`].
+///
+/// Programs group the streams of a container that belong together, like the
+/// services of a MPEG-TS multiplex.
+pub struct Program<'a>(&'a AVProgram);
+
+impl<'a> Program<'a> {
+    /// Creates a new `Program` instance.
+    pub(crate) const fn new(program: &'a AVProgram) -> Self {
+        Self(program)
+    }
+
+    /// Returns a constant pointer to the program.
+    pub const fn as_ptr(&self) -> *const AVProgram {
+        self.0
+    }
+
+    /// Returns the ID of the program.
+    pub const fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    /// Returns the program number, this is the `program_number` of the MPEG-TS program map table.
+    pub const fn program_num(&self) -> i32 {
+        self.0.program_num
+    }
+
+    /// Returns the PID of the program map table.
+    pub const fn pmt_pid(&self) -> i32 {
+        self.0.pmt_pid
+    }
+
+    /// Returns the PID carrying the program clock reference.
+    pub const fn pcr_pid(&self) -> i32 {
+        self.0.pcr_pid
+    }
+
+    /// Returns the discard flag of the program.
+    pub const fn discard(&self) -> AVDiscard {
+        AVDiscard(self.0.discard)
+    }
+
+    /// Returns the indexes of the streams that belong to the program.
+    pub const fn stream_indexes(&self) -> &'a [u32] {
+        if self.0.stream_index.is_null() {
+            return &[];
+        }
+
+        // Safety: `stream_index` is an array of `nb_stream_indexes` stream indexes.
+        unsafe { std::slice::from_raw_parts(self.0.stream_index, self.0.nb_stream_indexes as usize) }
+    }
+
+    /// Returns the start time of the program.
+    pub const fn start_time(&self) -> Option<Timestamp> {
+        match check_i64(self.0.start_time) {
+            Some(start_time) => Some(Timestamp::from_micros(start_time)),
+            None => None,
+        }
+    }
+
+    /// Returns the end time of the program.
+    pub const fn end_time(&self) -> Option<Timestamp> {
+        match check_i64(self.0.end_time) {
+            Some(end_time) => Some(Timestamp::from_micros(end_time)),
+            None => None,
+        }
+    }
+
+    /// Returns the metadata of the program.
+    pub const fn metadata(&self) -> Const<'_, Dictionary> {
+        // Safety: the pointer metadata pointer does not live longer than this object,
+        // see `Const::new`
+        Const::new(unsafe { Dictionary::from_ptr_ref(self.0.metadata) })
+    }
+}
+
+impl std::fmt::Debug for Program<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Program")
+            .field("id", &self.id())
+            .field("program_num", &self.program_num())
+            .field("pmt_pid", &self.pmt_pid())
+            .field("pcr_pid", &self.pcr_pid())
+            .field("discard", &self.discard())
+            .field("stream_indexes", &self.stream_indexes())
+            .field("metadata", &self.metadata())
+            .finish()
+    }
+}
+
+/// Returns the programs of the format context.
+///
+/// # Safety
+/// The caller must ensure that `context` is a valid pointer and that the programs
+/// are not mutated for the lifetime `'a`.
+pub(crate) unsafe fn programs<'a>(context: *const AVFormatContext) -> Vec<Program<'a>> {
+    // Safety: The caller guarantees that the pointer is valid.
+    let context = unsafe { &*context };
+    if context.programs.is_null() {
+        return Vec::new();
+    }
+
+    // Safety: `programs` is an array of `nb_programs` pointers.
+    let programs = unsafe { std::slice::from_raw_parts(context.programs, context.nb_programs as usize) };
+
+    programs
+        .iter()
+        // Safety: The program pointers are valid for the lifetime of the format context.
+        .filter_map(|program| unsafe { program.as_ref() })
+        .map(Program::new)
+        .collect()
+}
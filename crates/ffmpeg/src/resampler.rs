@@ -13,6 +13,9 @@ pub struct Resampler {
     sample_rate: i32,
 }
 
+/// Safety: `Resampler` is safe to send between threads.
+unsafe impl Send for Resampler {}
+
 impl Resampler {
     /// Create a new [`Resampler`] instance
     pub fn new(
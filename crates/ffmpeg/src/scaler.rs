@@ -86,6 +86,11 @@ impl VideoScaler {
 
     /// Processes a frame through the scalar.
     pub fn process<'a>(&'a mut self, frame: &VideoFrame) -> Result<&'a VideoFrame, FfmpegError> {
+        // The output frame may still be referenced by a clone handed out by a previous call,
+        // in which case new buffers are allocated for it instead of overwriting the shared ones.
+        // Safety: `self.frame` is a valid frame.
+        FfmpegErrorCode(unsafe { av_frame_make_writable(self.frame.as_mut_ptr()) }).result()?;
+
         // Safety: `frame` is a valid pointer, and `self.ptr` is a valid pointer.
         let frame_ptr = unsafe { frame.as_ptr().as_ref().unwrap() };
         // Safety: `self.frame` is a valid pointer.
@@ -110,6 +115,7 @@ impl VideoScaler {
         self.frame.set_pts(frame.pts());
         self.frame.set_duration(frame.duration());
         self.frame.set_time_base(frame.time_base());
+        self.frame.copy_side_data(frame)?;
 
         Ok(&self.frame)
     }
@@ -123,6 +129,7 @@ mod tests {
 
     use crate::frame::VideoFrame;
     use crate::scaler::{AVPixelFormat, VideoScaler};
+    use crate::side_data::{DisplayMatrix, SideData};
 
     #[test]
     fn test_scalar_new() {
@@ -199,6 +206,9 @@ mod tests {
             }
         }
 
+        let rotation = SideData::DisplayMatrix(DisplayMatrix::from_rotation(90.0));
+        input_frame.add_side_data(&rotation).expect("Failed to add side data");
+
         let result = scalar.process(&input_frame);
 
         assert!(
@@ -207,6 +217,12 @@ mod tests {
         );
 
         let output_frame = result.unwrap();
+        assert_eq!(
+            output_frame.side_data(),
+            vec![rotation],
+            "Expected the side data to be copied to the output frame"
+        );
+
         assert_debug_snapshot!(output_frame, @r"
         VideoFrame {
             width: 1280,
@@ -0,0 +1,450 @@
+use crate::ffi::*;
+use crate::rational::Rational;
+use crate::{AVFrameSideDataType, AVPacketSideDataType};
+
+/// A 3x3 transformation matrix describing how a video should be displayed.
+///
+/// The first two columns are 16.16 fixed point numbers and the last column is
+/// a 2.30 fixed point number, see
+/// <https://ffmpeg.org/doxygen/trunk/group__lavu__video__display.html>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMatrix(pub [i32; 9]);
+
+impl DisplayMatrix {
+    /// The identity matrix, the video is displayed as is.
+    pub const IDENTITY: Self = Self([1 << 16, 0, 0, 0, 1 << 16, 0, 0, 0, 1 << 30]);
+
+    /// Creates a matrix that rotates the video counterclockwise by `angle` degrees.
+    pub fn from_rotation(angle: f64) -> Self {
+        let mut matrix = [0; 9];
+        // Safety: av_display_rotation_set is safe to call, the matrix has 9 elements.
+        // It takes a clockwise angle, so we negate the angle.
+        unsafe { av_display_rotation_set(matrix.as_mut_ptr(), -angle) };
+        Self(matrix)
+    }
+
+    /// Returns the counterclockwise rotation of the matrix in degrees, in the range `[-180, 180]`.
+    ///
+    /// Returns `None` if the matrix is singular.
+    pub fn rotation(&self) -> Option<f64> {
+        // Safety: av_display_rotation_get is safe to call, the matrix has 9 elements.
+        let angle = unsafe { av_display_rotation_get(self.0.as_ptr()) };
+        (!angle.is_nan()).then_some(angle)
+    }
+
+    /// Flips the matrix horizontally and or vertically.
+    pub fn flip(&mut self, horizontal: bool, vertical: bool) {
+        // Safety: av_display_matrix_flip is safe to call, the matrix has 9 elements.
+        unsafe { av_display_matrix_flip(self.0.as_mut_ptr(), horizontal as i32, vertical as i32) };
+    }
+
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut matrix = [0; 9];
+        for (value, bytes) in matrix.iter_mut().zip(data.get(..36)?.chunks_exact(4)) {
+            *value = i32::from_ne_bytes(bytes.try_into().ok()?);
+        }
+
+        Some(Self(matrix))
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        self.0.iter().flat_map(|value| value.to_ne_bytes()).collect()
+    }
+}
+
+/// The CIE 1931 xy chromaticity coordinates of the primaries and the white point of a display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayPrimaries {
+    /// The red primary.
+    pub red: [Rational; 2],
+    /// The green primary.
+    pub green: [Rational; 2],
+    /// The blue primary.
+    pub blue: [Rational; 2],
+    /// The white point.
+    pub white_point: [Rational; 2],
+}
+
+/// The luminance range of a display in cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayLuminance {
+    /// The minimum luminance.
+    pub min: Rational,
+    /// The maximum luminance.
+    pub max: Rational,
+}
+
+/// The color volume of the display used to master the content, also known as HDR10 static metadata.
+///
+/// Wraps an [`AVMasteringDisplayMetadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplayMetadata {
+    /// The primaries of the display, if known.
+    pub primaries: Option<DisplayPrimaries>,
+    /// The luminance range of the display, if known.
+    pub luminance: Option<DisplayLuminance>,
+}
+
+impl MasteringDisplayMetadata {
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < std::mem::size_of::<AVMasteringDisplayMetadata>() {
+            return None;
+        }
+
+        // Safety: The data is large enough to hold the struct, which only contains integers.
+        let metadata = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const AVMasteringDisplayMetadata) };
+        let point = |point: [AVRational; 2]| point.map(Rational::from);
+
+        Some(Self {
+            primaries: (metadata.has_primaries != 0).then(|| DisplayPrimaries {
+                red: point(metadata.display_primaries[0]),
+                green: point(metadata.display_primaries[1]),
+                blue: point(metadata.display_primaries[2]),
+                white_point: point(metadata.white_point),
+            }),
+            luminance: (metadata.has_luminance != 0).then(|| DisplayLuminance {
+                min: metadata.min_luminance.into(),
+                max: metadata.max_luminance.into(),
+            }),
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let point = |point: [Rational; 2]| point.map(AVRational::from);
+        let primaries = self.primaries.unwrap_or(DisplayPrimaries {
+            red: [Rational::ZERO; 2],
+            green: [Rational::ZERO; 2],
+            blue: [Rational::ZERO; 2],
+            white_point: [Rational::ZERO; 2],
+        });
+        let luminance = self.luminance.unwrap_or(DisplayLuminance {
+            min: Rational::ZERO,
+            max: Rational::ZERO,
+        });
+
+        let metadata = AVMasteringDisplayMetadata {
+            display_primaries: [point(primaries.red), point(primaries.green), point(primaries.blue)],
+            white_point: point(primaries.white_point),
+            min_luminance: luminance.min.into(),
+            max_luminance: luminance.max.into(),
+            has_primaries: self.primaries.is_some() as i32,
+            has_luminance: self.luminance.is_some() as i32,
+        };
+
+        // Safety: The struct only contains integers, so it can be read as bytes.
+        unsafe {
+            std::slice::from_raw_parts(
+                &metadata as *const AVMasteringDisplayMetadata as *const u8,
+                std::mem::size_of::<AVMasteringDisplayMetadata>(),
+            )
+        }
+        .to_vec()
+    }
+}
+
+/// The light level of the content in cd/m², also known as HDR10 static metadata.
+///
+/// Wraps an [`AVContentLightMetadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// The maximum light level of any pixel of the content (MaxCLL).
+    pub max_content_light_level: u32,
+    /// The maximum average light level of any frame of the content (MaxFALL).
+    pub max_frame_average_light_level: u32,
+}
+
+impl ContentLightLevel {
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < std::mem::size_of::<AVContentLightMetadata>() {
+            return None;
+        }
+
+        // Safety: The data is large enough to hold the struct, which only contains integers.
+        let metadata = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const AVContentLightMetadata) };
+
+        Some(Self {
+            max_content_light_level: metadata.MaxCLL,
+            max_frame_average_light_level: metadata.MaxFALL,
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let metadata = AVContentLightMetadata {
+            MaxCLL: self.max_content_light_level,
+            MaxFALL: self.max_frame_average_light_level,
+        };
+
+        // Safety: The struct only contains integers, so it can be read as bytes.
+        unsafe {
+            std::slice::from_raw_parts(
+                &metadata as *const AVContentLightMetadata as *const u8,
+                std::mem::size_of::<AVContentLightMetadata>(),
+            )
+        }
+        .to_vec()
+    }
+}
+
+/// The number of audio samples that should be skipped after decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SkipSamples {
+    /// The number of samples to skip at the start of the frame.
+    pub skip_start: u32,
+    /// The number of samples to skip at the end of the frame.
+    pub skip_end: u32,
+    /// The reason for skipping the samples at the start.
+    pub reason_start: u8,
+    /// The reason for skipping the samples at the end.
+    pub reason_end: u8,
+}
+
+impl SkipSamples {
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        let data: &[u8; 10] = data.get(..10)?.try_into().ok()?;
+
+        Some(Self {
+            skip_start: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            skip_end: u32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+            reason_start: data[8],
+            reason_end: data[9],
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(10);
+        data.extend_from_slice(&self.skip_start.to_le_bytes());
+        data.extend_from_slice(&self.skip_end.to_le_bytes());
+        data.push(self.reason_start);
+        data.push(self.reason_end);
+        data
+    }
+}
+
+/// Side data attached to a packet, a frame or the codec parameters of a stream.
+///
+/// Only the side data types with a typed representation are listed here, other
+/// types can be accessed as raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SideData {
+    /// New codec extradata, only available on packets and streams.
+    NewExtradata(Vec<u8>),
+    /// How the video should be rotated and flipped when displayed.
+    DisplayMatrix(DisplayMatrix),
+    /// The color volume of the mastering display.
+    MasteringDisplayMetadata(MasteringDisplayMetadata),
+    /// The light level of the content.
+    ContentLightLevel(ContentLightLevel),
+    /// ATSC A53 Part 4 closed captions, as `cc_data` triplets.
+    A53ClosedCaptions(Vec<u8>),
+    /// Audio samples to skip after decoding.
+    SkipSamples(SkipSamples),
+}
+
+impl SideData {
+    /// Returns the packet side data type of this side data.
+    pub const fn packet_type(&self) -> AVPacketSideDataType {
+        match self {
+            Self::NewExtradata(_) => AVPacketSideDataType::NewExtradata,
+            Self::DisplayMatrix(_) => AVPacketSideDataType::DisplayMatrix,
+            Self::MasteringDisplayMetadata(_) => AVPacketSideDataType::MasteringDisplayMetadata,
+            Self::ContentLightLevel(_) => AVPacketSideDataType::ContentLightLevel,
+            Self::A53ClosedCaptions(_) => AVPacketSideDataType::A53Cc,
+            Self::SkipSamples(_) => AVPacketSideDataType::SkipSamples,
+        }
+    }
+
+    /// Returns the frame side data type of this side data, or `None` if it
+    /// cannot be attached to a frame.
+    pub const fn frame_type(&self) -> Option<AVFrameSideDataType> {
+        match self {
+            Self::NewExtradata(_) => None,
+            Self::DisplayMatrix(_) => Some(AVFrameSideDataType::DisplayMatrix),
+            Self::MasteringDisplayMetadata(_) => Some(AVFrameSideDataType::MasteringDisplayMetadata),
+            Self::ContentLightLevel(_) => Some(AVFrameSideDataType::ContentLightLevel),
+            Self::A53ClosedCaptions(_) => Some(AVFrameSideDataType::A53Cc),
+            Self::SkipSamples(_) => Some(AVFrameSideDataType::SkipSamples),
+        }
+    }
+
+    /// Parses packet side data of the given type.
+    ///
+    /// Returns `None` if the type has no typed representation or the data is invalid.
+    pub fn from_packet_side_data(kind: AVPacketSideDataType, data: &[u8]) -> Option<Self> {
+        match kind {
+            AVPacketSideDataType::NewExtradata => Some(Self::NewExtradata(data.to_vec())),
+            AVPacketSideDataType::DisplayMatrix => DisplayMatrix::from_bytes(data).map(Self::DisplayMatrix),
+            AVPacketSideDataType::MasteringDisplayMetadata => {
+                MasteringDisplayMetadata::from_bytes(data).map(Self::MasteringDisplayMetadata)
+            }
+            AVPacketSideDataType::ContentLightLevel => ContentLightLevel::from_bytes(data).map(Self::ContentLightLevel),
+            AVPacketSideDataType::A53Cc => Some(Self::A53ClosedCaptions(data.to_vec())),
+            AVPacketSideDataType::SkipSamples => SkipSamples::from_bytes(data).map(Self::SkipSamples),
+            _ => None,
+        }
+    }
+
+    /// Parses frame side data of the given type.
+    ///
+    /// Returns `None` if the type has no typed representation or the data is invalid.
+    pub fn from_frame_side_data(kind: AVFrameSideDataType, data: &[u8]) -> Option<Self> {
+        match kind {
+            AVFrameSideDataType::DisplayMatrix => DisplayMatrix::from_bytes(data).map(Self::DisplayMatrix),
+            AVFrameSideDataType::MasteringDisplayMetadata => {
+                MasteringDisplayMetadata::from_bytes(data).map(Self::MasteringDisplayMetadata)
+            }
+            AVFrameSideDataType::ContentLightLevel => ContentLightLevel::from_bytes(data).map(Self::ContentLightLevel),
+            AVFrameSideDataType::A53Cc => Some(Self::A53ClosedCaptions(data.to_vec())),
+            AVFrameSideDataType::SkipSamples => SkipSamples::from_bytes(data).map(Self::SkipSamples),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw bytes of this side data, as stored by FFmpeg.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::NewExtradata(data) | Self::A53ClosedCaptions(data) => data.clone(),
+            Self::DisplayMatrix(matrix) => matrix.to_bytes(),
+            Self::MasteringDisplayMetadata(metadata) => metadata.to_bytes(),
+            Self::ContentLightLevel(level) => level.to_bytes(),
+            Self::SkipSamples(skip) => skip.to_bytes(),
+        }
+    }
+}
+
+/// Returns the entries of a packet side data array.
+///
+/// # Safety
+/// `ptr` must point to `len` valid entries that outlive `'a`, or be null.
+pub(crate) const unsafe fn packet_side_data_entries<'a>(ptr: *const AVPacketSideData, len: i32) -> &'a [AVPacketSideData] {
+    if ptr.is_null() || len <= 0 {
+        return &[];
+    }
+
+    // Safety: The caller guarantees that the pointer is valid for `len` entries.
+    unsafe { std::slice::from_raw_parts(ptr, len as usize) }
+}
+
+/// Returns the data of a side data entry.
+///
+/// # Safety
+/// `data` must point to `size` valid bytes that outlive `'a`, or be null.
+pub(crate) const unsafe fn side_data_bytes<'a>(data: *const u8, size: usize) -> &'a [u8] {
+    if data.is_null() || size == 0 {
+        return &[];
+    }
+
+    // Safety: The caller guarantees that the pointer is valid for `size` bytes.
+    unsafe { std::slice::from_raw_parts(data, size) }
+}
+
+/// Parses the entries of a packet side data array that have a typed representation.
+pub(crate) fn parse_packet_side_data(entries: &[AVPacketSideData]) -> Vec<SideData> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            // Safety: The entry data is valid for `size` bytes while the entry is borrowed.
+            let data = unsafe { side_data_bytes(entry.data, entry.size) };
+            SideData::from_packet_side_data(entry.type_.into(), data)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::rational::Rational;
+    use crate::side_data::{
+        ContentLightLevel, DisplayLuminance, DisplayMatrix, DisplayPrimaries, MasteringDisplayMetadata, SideData,
+        SkipSamples,
+    };
+    use crate::{AVFrameSideDataType, AVPacketSideDataType};
+
+    #[test]
+    fn test_display_matrix_rotation() {
+        assert_eq!(DisplayMatrix::IDENTITY.rotation(), Some(0.0));
+        assert_eq!(DisplayMatrix([0; 9]).rotation(), None);
+
+        for angle in [90.0, -90.0, 45.0] {
+            let rotation = DisplayMatrix::from_rotation(angle).rotation().expect("Expected a rotation");
+            assert!((rotation - angle).abs() < 0.01, "Expected {angle} but got {rotation}");
+        }
+
+        let mut matrix = DisplayMatrix::IDENTITY;
+        matrix.flip(true, false);
+        assert_eq!(matrix.0[0], -(1 << 16));
+        assert_eq!(matrix.0[4], 1 << 16);
+    }
+
+    #[test]
+    fn test_side_data_roundtrip() {
+        let side_data = [
+            SideData::NewExtradata(vec![1, 2, 3]),
+            SideData::DisplayMatrix(DisplayMatrix::from_rotation(90.0)),
+            SideData::MasteringDisplayMetadata(MasteringDisplayMetadata {
+                primaries: Some(DisplayPrimaries {
+                    red: [Rational::static_new::<34000, 50000>(), Rational::static_new::<16000, 50000>()],
+                    green: [Rational::static_new::<13250, 50000>(), Rational::static_new::<34500, 50000>()],
+                    blue: [Rational::static_new::<7500, 50000>(), Rational::static_new::<3000, 50000>()],
+                    white_point: [Rational::static_new::<15635, 50000>(), Rational::static_new::<16450, 50000>()],
+                }),
+                luminance: Some(DisplayLuminance {
+                    min: Rational::static_new::<50, 10000>(),
+                    max: Rational::static_new::<1000, 1>(),
+                }),
+            }),
+            SideData::MasteringDisplayMetadata(MasteringDisplayMetadata {
+                primaries: None,
+                luminance: None,
+            }),
+            SideData::ContentLightLevel(ContentLightLevel {
+                max_content_light_level: 1000,
+                max_frame_average_light_level: 400,
+            }),
+            SideData::A53ClosedCaptions(vec![0xfc, 0x80, 0x80]),
+            SideData::SkipSamples(SkipSamples {
+                skip_start: 1024,
+                skip_end: 312,
+                reason_start: 0,
+                reason_end: 0,
+            }),
+        ];
+
+        for side_data in side_data {
+            let bytes = side_data.to_bytes();
+            assert_eq!(
+                SideData::from_packet_side_data(side_data.packet_type(), &bytes),
+                Some(side_data.clone())
+            );
+
+            if let Some(frame_type) = side_data.frame_type() {
+                assert_eq!(SideData::from_frame_side_data(frame_type, &bytes), Some(side_data.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_side_data_types() {
+        assert_eq!(SideData::NewExtradata(Vec::new()).frame_type(), None);
+        assert_eq!(
+            SideData::A53ClosedCaptions(Vec::new()).packet_type(),
+            AVPacketSideDataType::A53Cc
+        );
+        assert_eq!(
+            SideData::DisplayMatrix(DisplayMatrix::IDENTITY).frame_type(),
+            Some(AVFrameSideDataType::DisplayMatrix)
+        );
+
+        assert_eq!(
+            SideData::from_packet_side_data(AVPacketSideDataType::Palette, &[0; 1024]),
+            None
+        );
+        assert_eq!(
+            SideData::from_packet_side_data(AVPacketSideDataType::SkipSamples, &[0; 4]),
+            None
+        );
+        assert_eq!(
+            SideData::from_frame_side_data(AVFrameSideDataType::DisplayMatrix, &[0; 8]),
+            None
+        );
+    }
+}
@@ -2,10 +2,12 @@ use std::marker::PhantomData;
 
 use crate::consts::{Const, Mut};
 use crate::dict::Dictionary;
+use crate::error::FfmpegError;
 use crate::ffi::*;
 use crate::rational::Rational;
+use crate::side_data::{SideData, packet_side_data_entries, parse_packet_side_data, side_data_bytes};
 use crate::utils::check_i64;
-use crate::{AVDiscard, AVMediaType};
+use crate::{AVDiscard, AVDisposition, AVMediaType, AVPacketSideDataType};
 
 /// A collection of streams. Streams implements [`IntoIterator`] to iterate over the streams.
 pub struct Streams<'a> {
@@ -244,14 +246,29 @@ impl<'a> Stream<'a> {
         self.0.nb_frames = nb_frames;
     }
 
-    /// Returns the disposition of the stream.
-    pub const fn disposition(&self) -> i32 {
-        self.0.disposition
+    /// Returns the disposition flags of the stream.
+    pub const fn disposition(&self) -> AVDisposition {
+        AVDisposition(self.0.disposition)
     }
 
-    /// Sets the disposition of the stream.
-    pub const fn set_disposition(&mut self, disposition: i32) {
-        self.0.disposition = disposition;
+    /// Sets the disposition flags of the stream.
+    pub fn set_disposition(&mut self, disposition: impl Into<AVDisposition>) {
+        self.0.disposition = disposition.into().into();
+    }
+
+    /// Returns true if the stream should be chosen by default.
+    pub const fn is_default(&self) -> bool {
+        self.disposition().contains(AVDisposition::Default)
+    }
+
+    /// Returns true if the stream must be shown even if the user did not enable it.
+    pub const fn is_forced(&self) -> bool {
+        self.disposition().contains(AVDisposition::Forced)
+    }
+
+    /// Returns true if the stream is a single picture attached to the file, like cover art.
+    pub const fn is_attached_pic(&self) -> bool {
+        self.disposition().contains(AVDisposition::AttachedPic)
     }
 
     /// Returns the discard flag of the stream.
@@ -288,6 +305,73 @@ impl<'a> Stream<'a> {
         Mut::new(unsafe { Dictionary::from_ptr_ref(self.0.metadata) })
     }
 
+    /// Returns the side data of the codec parameters of the stream that has a typed representation.
+    ///
+    /// Containers store stream wide information like the display matrix of a video here.
+    pub fn side_data(&self) -> Vec<SideData> {
+        let Some(codecpar) = self.codec_parameters() else {
+            return Vec::new();
+        };
+
+        // Safety: `coded_side_data` is an array of `nb_coded_side_data` entries owned by the codec parameters.
+        parse_packet_side_data(unsafe { packet_side_data_entries(codecpar.coded_side_data, codecpar.nb_coded_side_data) })
+    }
+
+    /// Returns the raw side data of the given type from the codec parameters of the stream.
+    pub fn side_data_raw(&self, kind: AVPacketSideDataType) -> Option<&[u8]> {
+        let codecpar = self.codec_parameters()?;
+        // Safety: av_packet_side_data_get is safe to call, the side data array belongs to the codec parameters.
+        let entry = unsafe { av_packet_side_data_get(codecpar.coded_side_data, codecpar.nb_coded_side_data, kind.into()) };
+        // Safety: The side data is valid while the stream is borrowed.
+        let entry = unsafe { entry.as_ref() }?;
+
+        // Safety: The side data is valid for `size` bytes while the stream is borrowed.
+        Some(unsafe { side_data_bytes(entry.data, entry.size) })
+    }
+
+    /// Adds side data to the codec parameters of the stream, replacing side data of the same type.
+    ///
+    /// Side data added to an output stream before the header is written is stored by the muxer,
+    /// for example as the display matrix of a MP4 track.
+    pub fn add_side_data(&mut self, side_data: &SideData) -> Result<(), FfmpegError> {
+        self.set_side_data_raw(side_data.packet_type(), &side_data.to_bytes())
+    }
+
+    /// Sets the raw side data of the given type on the codec parameters of the stream,
+    /// replacing side data of the same type.
+    pub fn set_side_data_raw(&mut self, kind: AVPacketSideDataType, data: &[u8]) -> Result<(), FfmpegError> {
+        // Safety: The codec parameters pointer is valid if it is not null.
+        let codecpar =
+            unsafe { self.0.codecpar.as_mut() }.ok_or(FfmpegError::Arguments("stream has no codec parameters"))?;
+
+        // Safety: av_packet_side_data_new is safe to call, the side data array belongs to the codec parameters.
+        let entry = unsafe {
+            av_packet_side_data_new(
+                &mut codecpar.coded_side_data,
+                &mut codecpar.nb_coded_side_data,
+                kind.into(),
+                data.len(),
+                0,
+            )
+        };
+        // Safety: The side data is valid if it is not null.
+        let entry = unsafe { entry.as_mut() }.ok_or(FfmpegError::Alloc)?;
+
+        // Safety: The side data was allocated with `data.len()` bytes.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), entry.data, data.len()) };
+
+        Ok(())
+    }
+
+    /// Removes the side data of the given type from the codec parameters of the stream.
+    pub fn remove_side_data(&mut self, kind: AVPacketSideDataType) {
+        // Safety: The codec parameters pointer is valid if it is not null.
+        if let Some(codecpar) = unsafe { self.0.codecpar.as_mut() } {
+            // Safety: av_packet_side_data_remove is safe to call, the side data array belongs to the codec parameters.
+            unsafe { av_packet_side_data_remove(codecpar.coded_side_data, &mut codecpar.nb_coded_side_data, kind.into()) };
+        }
+    }
+
     /// Returns the average frame rate of the stream.
     pub fn avg_frame_rate(&self) -> Rational {
         self.0.avg_frame_rate.into()
@@ -338,8 +422,9 @@ mod tests {
     use crate::ffi::AVStream;
     use crate::io::Input;
     use crate::rational::Rational;
+    use crate::side_data::{DisplayMatrix, SideData};
     use crate::stream::AVMediaType;
-    use crate::{AVDiscard, file_path};
+    use crate::{AVDiscard, AVDisposition, AVPacketSideDataType, file_path};
 
     #[test]
     fn test_best_stream() {
@@ -485,6 +570,37 @@ mod tests {
             test_disposition,
             "Expected `disposition` to match the set value"
         );
+        assert!(stream.is_default(), "Expected the stream to be a default stream");
+
+        stream.set_disposition(AVDisposition::Forced | AVDisposition::HearingImpaired);
+        assert!(stream.is_forced(), "Expected the stream to be forced");
+        assert!(!stream.is_default(), "Expected the stream to not be a default stream");
+        assert!(
+            stream.disposition().contains(AVDisposition::HearingImpaired),
+            "Expected the stream to be for hearing impaired audiences"
+        );
+        assert!(!stream.is_attached_pic(), "Expected the stream to not be an attached picture");
+    }
+
+    #[test]
+    fn test_stream_side_data() {
+        let mut input = Input::open(file_path("avc_aac_large.mp4")).expect("Failed to open valid file");
+        let mut streams = input.streams_mut();
+        let mut stream = streams.get(0).expect("Expected a valid stream");
+
+        let rotation = SideData::DisplayMatrix(DisplayMatrix::from_rotation(90.0));
+        stream.add_side_data(&rotation).expect("Failed to add side data");
+        assert!(
+            stream.side_data().contains(&rotation),
+            "Expected the display matrix in the side data"
+        );
+        assert_eq!(
+            stream.side_data_raw(AVPacketSideDataType::DisplayMatrix),
+            Some(rotation.to_bytes().as_slice())
+        );
+
+        stream.remove_side_data(AVPacketSideDataType::DisplayMatrix);
+        assert_eq!(stream.side_data_raw(AVPacketSideDataType::DisplayMatrix), None);
     }
 
     #[test]
@@ -619,7 +735,7 @@ mod tests {
                 nb_frames: Some(
                     64,
                 ),
-                disposition: 1,
+                disposition: AVDisposition::Default,
                 discard: AVDiscard::Default,
                 sample_aspect_ratio: Rational {
                     numerator: 1,
@@ -0,0 +1,598 @@
+use std::ffi::{CStr, CString, c_char};
+use std::fmt::Write;
+
+use crate::AVSubtitleType;
+use crate::error::FfmpegError;
+use crate::ffi::*;
+use crate::rational::Rational;
+use crate::smart_object::SmartObject;
+use crate::timestamp::Timestamp;
+use crate::utils::check_i64;
+
+/// The ASS header FFmpeg's text subtitle decoders use when the stream does not provide one.
+///
+/// Text subtitle encoders need an ASS header to parse the events they are given.
+pub const DEFAULT_ASS_HEADER: &str = "[Script Info]\r\n\
+    ; Script generated by FFmpeg/Lavc\r\n\
+    ScriptType: v4.00+\r\n\
+    PlayResX: 384\r\n\
+    PlayResY: 288\r\n\
+    ScaledBorderAndShadow: yes\r\n\
+    YCbCr Matrix: None\r\n\
+    \r\n\
+    [V4+ Styles]\r\n\
+    Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\r\n\
+    Style: Default,Arial,16,&Hffffff,&Hffffff,&H0,&H0,0,0,0,0,100,100,0,0,1,1,0,2,10,10,10,1\r\n\
+    \r\n\
+    [Events]\r\n\
+    Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\r\n";
+
+const MILLISECONDS: Rational = Rational::static_new::<1, 1000>();
+
+/// A subtitle. Thin wrapper around [`AVSubtitle`].
+///
+/// A subtitle contains one or more rects, which are either text, ASS dialogue events or bitmaps.
+/// The display time of the subtitle is expressed in the time base of the stream it belongs to.
+pub struct Subtitle {
+    subtitle: SmartObject<AVSubtitle>,
+    time_base: Rational,
+}
+
+/// Safety: `Subtitle` can be sent between threads.
+unsafe impl Send for Subtitle {}
+
+impl std::fmt::Debug for Subtitle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subtitle")
+            .field("time_base", &self.time_base)
+            .field("start", &self.start())
+            .field("end", &self.end())
+            .field("format", &self.format())
+            .field("rects", &self.rects().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Subtitle {
+    /// Creates a new subtitle without any rects, its timestamps are expressed in `time_base`.
+    pub fn new(time_base: impl Into<Rational>) -> Self {
+        // Safety: `AVSubtitle` is a c-struct and those are safe to zero out.
+        let mut subtitle: AVSubtitle = unsafe { std::mem::zeroed() };
+        subtitle.pts = AV_NOPTS_VALUE;
+        subtitle.format = 1;
+
+        Self {
+            subtitle: SmartObject::new(subtitle, Self::destructor),
+            time_base: time_base.into(),
+        }
+    }
+
+    fn destructor(subtitle: &mut AVSubtitle) {
+        // Safety: The rects were allocated by FFmpeg or with `av_malloc`, so they can be freed by `avsubtitle_free`.
+        unsafe { avsubtitle_free(subtitle) };
+    }
+
+    /// Returns a pointer to the subtitle.
+    pub const fn as_ptr(&self) -> *const AVSubtitle {
+        self.subtitle.inner_ref()
+    }
+
+    /// Returns a mutable pointer to the subtitle.
+    pub const fn as_mut_ptr(&mut self) -> *mut AVSubtitle {
+        self.subtitle.inner_mut()
+    }
+
+    /// Returns the time base of the subtitle timestamps.
+    pub const fn time_base(&self) -> Rational {
+        self.time_base
+    }
+
+    /// Returns the format of the subtitle, `0` for bitmap subtitles and `1` for text subtitles.
+    pub const fn format(&self) -> u16 {
+        self.subtitle.inner_ref().format
+    }
+
+    /// Returns the time the subtitle starts being displayed.
+    pub fn start(&self) -> Option<Timestamp> {
+        let subtitle = self.subtitle.inner_ref();
+        let pts = check_i64(subtitle.pts)?;
+
+        Some(Timestamp::from_micros(pts + subtitle.start_display_time as i64 * 1000).rescale(self.time_base))
+    }
+
+    /// Returns the time the subtitle stops being displayed.
+    ///
+    /// Returns `None` if the subtitle is displayed until the next one.
+    pub fn end(&self) -> Option<Timestamp> {
+        let subtitle = self.subtitle.inner_ref();
+        let pts = check_i64(subtitle.pts)?;
+        if subtitle.end_display_time == u32::MAX || subtitle.end_display_time <= subtitle.start_display_time {
+            return None;
+        }
+
+        Some(Timestamp::from_micros(pts + subtitle.end_display_time as i64 * 1000).rescale(self.time_base))
+    }
+
+    /// Returns how long the subtitle is displayed.
+    pub fn duration(&self) -> Option<Timestamp> {
+        let start = self.start()?;
+        let end = self.end()?;
+
+        Some(Timestamp::new(end.value() - start.value(), self.time_base))
+    }
+
+    /// Sets the display time of the subtitle.
+    ///
+    /// If `end` is `None` the subtitle is displayed until the next one.
+    pub fn set_timing(&mut self, start: Timestamp, end: Option<Timestamp>) {
+        let start = start.rescale(Timestamp::AV_TIME_BASE).value();
+        let end = end.map(|end| end.rescale(Timestamp::AV_TIME_BASE).value());
+
+        let subtitle = self.subtitle.inner_mut();
+        subtitle.pts = start;
+        subtitle.start_display_time = 0;
+        subtitle.end_display_time = end
+            .map(|end| u32::try_from((end - start).max(0) / 1000).unwrap_or(u32::MAX - 1))
+            .unwrap_or(u32::MAX);
+    }
+
+    /// Returns the rects of the subtitle.
+    pub fn rects(&self) -> impl Iterator<Item = SubtitleRect<'_>> {
+        let subtitle = self.subtitle.inner_ref();
+        let rects = if subtitle.rects.is_null() {
+            &[][..]
+        } else {
+            // Safety: `rects` points to `num_rects` rect pointers.
+            unsafe { std::slice::from_raw_parts(subtitle.rects, subtitle.num_rects as usize) }
+        };
+
+        rects
+            .iter()
+            // Safety: The rect pointers are valid for the lifetime of the subtitle.
+            .filter_map(|&rect| unsafe { rect.as_ref() })
+            .map(SubtitleRect)
+    }
+
+    /// Adds a plain text rect to the subtitle.
+    ///
+    /// The text is stored as an ASS dialogue event, since that is what FFmpeg's text subtitle encoders expect.
+    pub fn add_text(&mut self, text: &str) -> Result<(), FfmpegError> {
+        let read_order = self.subtitle.inner_ref().num_rects;
+        self.add_ass(&format!("{read_order},0,Default,,0,0,0,,{}", escape_ass_text(text)))
+    }
+
+    /// Adds an ASS dialogue event to the subtitle.
+    ///
+    /// The event is in the `ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text` format,
+    /// without the timing fields.
+    pub fn add_ass(&mut self, event: &str) -> Result<(), FfmpegError> {
+        let event = CString::new(event).map_err(|_| FfmpegError::Arguments("event cannot contain a nul byte"))?;
+
+        // Safety: `av_strdup` is safe to call with a valid c-string.
+        let ass = unsafe { av_strdup(event.as_ptr()) };
+        if ass.is_null() {
+            return Err(FfmpegError::Alloc);
+        }
+
+        let rect = match self.push_rect() {
+            Ok(rect) => rect,
+            Err(err) => {
+                // Safety: The string was allocated above and is not referenced anywhere.
+                unsafe { av_free(ass as *mut _) };
+                return Err(err);
+            }
+        };
+
+        rect.type_ = AVSubtitleType::Ass.into();
+        rect.ass = ass;
+        self.subtitle.inner_mut().format = 1;
+
+        Ok(())
+    }
+
+    fn push_rect(&mut self) -> Result<&mut AVSubtitleRect, FfmpegError> {
+        // Safety: `av_mallocz` is safe to call, the rect is freed by `avsubtitle_free`.
+        let rect = unsafe { av_mallocz(std::mem::size_of::<AVSubtitleRect>()) } as *mut AVSubtitleRect;
+        if rect.is_null() {
+            return Err(FfmpegError::Alloc);
+        }
+
+        let subtitle = self.subtitle.inner_mut();
+        let num_rects = subtitle.num_rects as usize;
+
+        // Safety: `rects` is either null or was allocated by FFmpeg, so it can be reallocated.
+        let rects = unsafe {
+            av_realloc_array(
+                subtitle.rects as *mut _,
+                num_rects + 1,
+                std::mem::size_of::<*mut AVSubtitleRect>(),
+            )
+        } as *mut *mut AVSubtitleRect;
+        if rects.is_null() {
+            // Safety: The rect was allocated above and is not referenced anywhere.
+            unsafe { av_free(rect as *mut _) };
+            return Err(FfmpegError::Alloc);
+        }
+
+        // Safety: `rects` has room for `num_rects + 1` entries.
+        let slot = unsafe { rects.add(num_rects) };
+        // Safety: `slot` is within the allocation.
+        unsafe { slot.write(rect) };
+
+        subtitle.rects = rects;
+        subtitle.num_rects += 1;
+
+        // Safety: The rect was allocated and zeroed above.
+        Ok(unsafe { &mut *rect })
+    }
+
+    /// Returns the text of the text and ASS rects of the subtitle without any formatting, one line per rect.
+    pub fn plain_text(&self) -> String {
+        self.rects()
+            .filter_map(|rect| rect.plain_text())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Converts the subtitle to a WebVTT cue.
+    ///
+    /// Returns `None` if the subtitle has no text, or if its start or end time is unknown.
+    pub fn to_webvtt_cue(&self) -> Option<String> {
+        webvtt_cue(self.start()?, self.end()?, &self.plain_text())
+    }
+}
+
+/// A rect of a [`Subtitle`]. Thin wrapper around [`AVSubtitleRect`].
+#[derive(Clone, Copy)]
+pub struct SubtitleRect<'a>(&'a AVSubtitleRect);
+
+impl std::fmt::Debug for SubtitleRect<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubtitleRect")
+            .field("kind", &self.kind())
+            .field("text", &self.text())
+            .field("ass", &self.ass())
+            .field("bitmap", &self.bitmap())
+            .finish()
+    }
+}
+
+/// Converts a nullable c-string to a `&str`.
+///
+/// # Safety
+/// `ptr` must be null or a valid nul-terminated string that lives for `'a`.
+unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    // Safety: The caller guarantees the string is valid.
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
+
+impl<'a> SubtitleRect<'a> {
+    /// Returns the type of the rect.
+    pub const fn kind(&self) -> AVSubtitleType {
+        AVSubtitleType(self.0.type_ as _)
+    }
+
+    /// Returns the text of a [`AVSubtitleType::Text`] rect.
+    pub fn text(&self) -> Option<&'a str> {
+        // Safety: `text` is null or a valid c-string owned by the subtitle.
+        unsafe { str_from_ptr(self.0.text) }
+    }
+
+    /// Returns the ASS dialogue event of a [`AVSubtitleType::Ass`] rect.
+    pub fn ass(&self) -> Option<&'a str> {
+        // Safety: `ass` is null or a valid c-string owned by the subtitle.
+        unsafe { str_from_ptr(self.0.ass) }
+    }
+
+    /// Returns the text of the rect without any formatting.
+    ///
+    /// For ASS rects the dialogue fields and override tags are removed.
+    pub fn plain_text(&self) -> Option<String> {
+        match self.kind() {
+            AVSubtitleType::Text => self.text().map(str::to_owned),
+            AVSubtitleType::Ass => self.ass().map(ass_to_plain_text),
+            _ => None,
+        }
+    }
+
+    /// Returns the bitmap of a [`AVSubtitleType::Bitmap`] rect.
+    pub fn bitmap(&self) -> Option<SubtitleBitmap<'a>> {
+        if self.kind() != AVSubtitleType::Bitmap || self.0.data[0].is_null() {
+            return None;
+        }
+
+        Some(SubtitleBitmap(self.0))
+    }
+}
+
+/// The bitmap of a [`SubtitleRect`].
+///
+/// Each pixel is an index into a palette of RGBA colors.
+#[derive(Clone, Copy)]
+pub struct SubtitleBitmap<'a>(&'a AVSubtitleRect);
+
+impl std::fmt::Debug for SubtitleBitmap<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubtitleBitmap")
+            .field("x", &self.x())
+            .field("y", &self.y())
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .field("nb_colors", &self.nb_colors())
+            .finish()
+    }
+}
+
+impl<'a> SubtitleBitmap<'a> {
+    /// Returns the horizontal position of the bitmap in the video frame.
+    pub const fn x(&self) -> i32 {
+        self.0.x
+    }
+
+    /// Returns the vertical position of the bitmap in the video frame.
+    pub const fn y(&self) -> i32 {
+        self.0.y
+    }
+
+    /// Returns the width of the bitmap.
+    pub const fn width(&self) -> i32 {
+        self.0.w
+    }
+
+    /// Returns the height of the bitmap.
+    pub const fn height(&self) -> i32 {
+        self.0.h
+    }
+
+    /// Returns the number of colors in the palette.
+    pub const fn nb_colors(&self) -> i32 {
+        self.0.nb_colors
+    }
+
+    /// Returns the number of bytes per row of the palette indices.
+    pub const fn linesize(&self) -> i32 {
+        self.0.linesize[0]
+    }
+
+    /// Returns the palette indices of the bitmap, [`Self::linesize`] bytes per row.
+    pub fn indices(&self) -> &'a [u8] {
+        let len = self.0.linesize[0].max(0) as usize * self.0.h.max(0) as usize;
+
+        // Safety: The first data plane contains `linesize * h` palette indices.
+        unsafe { std::slice::from_raw_parts(self.0.data[0], len) }
+    }
+
+    /// Returns the palette of the bitmap, as native endian RGBA colors.
+    pub fn palette(&self) -> &'a [u32] {
+        if self.0.data[1].is_null() {
+            return &[];
+        }
+
+        // Safety: The second data plane contains `nb_colors` 32-bit colors.
+        unsafe { std::slice::from_raw_parts(self.0.data[1] as *const u32, self.0.nb_colors.max(0) as usize) }
+    }
+}
+
+/// Escapes text so it can be used as the text of an ASS dialogue event.
+fn escape_ass_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\N"),
+            '\r' => {}
+            '{' | '}' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Removes the dialogue fields and override tags from an ASS dialogue event.
+fn ass_to_plain_text(event: &str) -> String {
+    // ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text
+    let text = event.splitn(9, ',').nth(8).unwrap_or(event);
+
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                // Skip the override block.
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('N' | 'n') => plain.push('\n'),
+                Some('h') => plain.push(' '),
+                Some(c) => plain.push(c),
+                None => plain.push('\\'),
+            },
+            c => plain.push(c),
+        }
+    }
+
+    plain
+}
+
+/// Formats a timestamp as a WebVTT timestamp (`hh:mm:ss.ttt`).
+pub fn webvtt_timestamp(timestamp: Timestamp) -> String {
+    let ms = timestamp.rescale(MILLISECONDS).value().max(0);
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn webvtt_cue(start: Timestamp, end: Timestamp, text: &str) -> Option<String> {
+    let mut cue = format!("{} --> {}\n", webvtt_timestamp(start), webvtt_timestamp(end));
+
+    // Cue text cannot contain blank lines, they end the cue.
+    let mut lines = text.lines().map(str::trim_end).filter(|line| !line.is_empty()).peekable();
+    lines.peek()?;
+
+    for line in lines {
+        for c in line.chars() {
+            match c {
+                '&' => cue.push_str("&amp;"),
+                '<' => cue.push_str("&lt;"),
+                '>' => cue.push_str("&gt;"),
+                c => cue.push(c),
+            }
+        }
+        cue.push('\n');
+    }
+
+    Some(cue)
+}
+
+/// Converts subtitles to a WebVTT document.
+///
+/// Subtitles without an end time are displayed until the next subtitle starts, subtitles
+/// without text or timing are skipped.
+pub fn to_webvtt<'a>(subtitles: impl IntoIterator<Item = &'a Subtitle>) -> String {
+    let mut document = String::from("WEBVTT\n");
+
+    let mut subtitles = subtitles.into_iter().peekable();
+    while let Some(subtitle) = subtitles.next() {
+        let Some(start) = subtitle.start() else {
+            continue;
+        };
+
+        let Some(end) = subtitle
+            .end()
+            .or_else(|| subtitles.peek().and_then(|next| next.start()))
+            .map(|end| end.rescale(start.time_base()))
+        else {
+            continue;
+        };
+
+        if let Some(cue) = webvtt_cue(start, end, &subtitle.plain_text()) {
+            // Writing to a string cannot fail.
+            let _ = write!(document, "\n{cue}");
+        }
+    }
+
+    document
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use crate::AVSubtitleType;
+    use crate::rational::Rational;
+    use crate::subtitle::{Subtitle, ass_to_plain_text, escape_ass_text, to_webvtt, webvtt_timestamp};
+    use crate::timestamp::Timestamp;
+
+    const TIME_BASE: Rational = Rational::static_new::<1, 90_000>();
+
+    #[test]
+    fn test_subtitle_timing() {
+        let mut subtitle = Subtitle::new(TIME_BASE);
+        assert_eq!(subtitle.start(), None);
+        assert_eq!(subtitle.end(), None);
+
+        subtitle.set_timing(Timestamp::from_millis(1500), Some(Timestamp::from_millis(4000)));
+        assert_eq!(subtitle.start(), Some(Timestamp::new(135_000, TIME_BASE)));
+        assert_eq!(subtitle.end(), Some(Timestamp::new(360_000, TIME_BASE)));
+        assert_eq!(subtitle.duration(), Some(Timestamp::new(225_000, TIME_BASE)));
+
+        subtitle.set_timing(Timestamp::from_seconds(2), None);
+        assert_eq!(subtitle.start(), Some(Timestamp::new(180_000, TIME_BASE)));
+        assert_eq!(subtitle.end(), None);
+        assert_eq!(subtitle.duration(), None);
+    }
+
+    #[test]
+    fn test_subtitle_rects() {
+        let mut subtitle = Subtitle::new(TIME_BASE);
+        subtitle.add_text("Hello {world}\nsecond line").expect("Failed to add text");
+        subtitle
+            .add_ass("1,0,Default,,0,0,0,,{\\i1}Italic{\\i0}\\hText")
+            .expect("Failed to add ass");
+
+        let rects: Vec<_> = subtitle.rects().collect();
+        assert_eq!(rects.len(), 2);
+        assert!(rects.iter().all(|rect| rect.kind() == AVSubtitleType::Ass));
+        assert!(rects.iter().all(|rect| rect.bitmap().is_none()));
+        assert_eq!(rects[0].ass(), Some("0,0,Default,,0,0,0,,Hello \\{world\\}\\Nsecond line"));
+        assert_eq!(rects[0].plain_text().as_deref(), Some("Hello {world}\nsecond line"));
+        assert_eq!(rects[1].plain_text().as_deref(), Some("Italic Text"));
+
+        assert_eq!(subtitle.plain_text(), "Hello {world}\nsecond line\nItalic Text");
+        assert!(subtitle.add_text("nul\0byte").is_err());
+    }
+
+    #[test]
+    fn test_ass_text_escaping() {
+        let text = "a\\b {c}\r\nd";
+        assert_eq!(escape_ass_text(text), "a\\\\b \\{c\\}\\Nd");
+        assert_eq!(
+            ass_to_plain_text(&format!("0,0,Default,,0,0,0,,{}", escape_ass_text(text))),
+            "a\\b {c}\nd"
+        );
+        assert_eq!(ass_to_plain_text("Text, with, commas"), "Text, with, commas");
+    }
+
+    #[test]
+    fn test_webvtt_timestamp() {
+        assert_eq!(webvtt_timestamp(Timestamp::from_millis(0)), "00:00:00.000");
+        assert_eq!(webvtt_timestamp(Timestamp::from_millis(3_723_004)), "01:02:03.004");
+        assert_eq!(webvtt_timestamp(Timestamp::new(135_000, TIME_BASE)), "00:00:01.500");
+    }
+
+    #[test]
+    fn test_to_webvtt() {
+        let mut first = Subtitle::new(TIME_BASE);
+        first.set_timing(Timestamp::from_millis(1000), Some(Timestamp::from_millis(2500)));
+        first.add_text("Fish & <chips>").expect("Failed to add text");
+
+        // No end time, displayed until the next subtitle.
+        let mut second = Subtitle::new(TIME_BASE);
+        second.set_timing(Timestamp::from_millis(3000), None);
+        second.add_text("Line one\n\nLine two").expect("Failed to add text");
+
+        let mut third = Subtitle::new(TIME_BASE);
+        third.set_timing(Timestamp::from_millis(5000), Some(Timestamp::from_millis(6000)));
+        third.add_text("Last").expect("Failed to add text");
+
+        // No text, skipped.
+        let mut empty = Subtitle::new(TIME_BASE);
+        empty.set_timing(Timestamp::from_millis(7000), Some(Timestamp::from_millis(8000)));
+
+        assert_eq!(
+            first.to_webvtt_cue().as_deref(),
+            Some("00:00:01.000 --> 00:00:02.500\nFish &amp; &lt;chips&gt;\n")
+        );
+        assert_eq!(second.to_webvtt_cue(), None);
+
+        insta::assert_snapshot!(to_webvtt([&first, &second, &third, &empty]), @r"
+        WEBVTT
+
+        00:00:01.000 --> 00:00:02.500
+        Fish &amp; &lt;chips&gt;
+
+        00:00:03.000 --> 00:00:05.000
+        Line one
+        Line two
+
+        00:00:05.000 --> 00:00:06.000
+        Last
+        ");
+    }
+}
@@ -0,0 +1,123 @@
+use crate::AVRounding;
+use crate::ffi::*;
+use crate::rational::Rational;
+
+/// A timestamp together with the time base it is expressed in.
+///
+/// The timestamp is `value * time_base` seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    value: i64,
+    time_base: Rational,
+}
+
+impl Timestamp {
+    /// The time base of timestamps that do not belong to a stream, like the duration of a container.
+    ///
+    /// Equivalent to `AV_TIME_BASE_Q`.
+    pub const AV_TIME_BASE: Rational = Rational::static_new::<1, 1_000_000>();
+
+    /// Creates a new timestamp of `value` in the given time base.
+    pub fn new(value: i64, time_base: impl Into<Rational>) -> Self {
+        Self {
+            value,
+            time_base: time_base.into(),
+        }
+    }
+
+    /// Creates a new timestamp in microseconds.
+    pub const fn from_micros(value: i64) -> Self {
+        Self {
+            value,
+            time_base: Self::AV_TIME_BASE,
+        }
+    }
+
+    /// Creates a new timestamp in milliseconds.
+    pub const fn from_millis(value: i64) -> Self {
+        Self {
+            value,
+            time_base: Rational::static_new::<1, 1000>(),
+        }
+    }
+
+    /// Creates a new timestamp in seconds.
+    pub const fn from_seconds(value: i64) -> Self {
+        Self {
+            value,
+            time_base: Rational::ONE,
+        }
+    }
+
+    /// Returns the value of the timestamp in its time base.
+    pub const fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// Returns the time base of the timestamp.
+    pub const fn time_base(&self) -> Rational {
+        self.time_base
+    }
+
+    /// Converts the timestamp to the given time base.
+    ///
+    /// The value is rounded to the nearest value in the new time base.
+    pub fn rescale(&self, time_base: impl Into<Rational>) -> Self {
+        let time_base = time_base.into();
+        if time_base == self.time_base {
+            return *self;
+        }
+
+        // Safety: av_rescale_q_rnd is safe to call
+        let value = unsafe {
+            av_rescale_q_rnd(
+                self.value,
+                self.time_base.into(),
+                time_base.into(),
+                AVRounding::NearestAwayFromZero.0 as _,
+            )
+        };
+
+        Self { value, time_base }
+    }
+
+    /// Returns the timestamp in seconds.
+    pub fn as_seconds_f64(&self) -> f64 {
+        self.value as f64 * self.time_base.as_f64()
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::rational::Rational;
+    use crate::timestamp::Timestamp;
+
+    #[test]
+    fn test_timestamp_rescale() {
+        let timestamp = Timestamp::from_millis(1500);
+        assert_eq!(timestamp.value(), 1500);
+        assert_eq!(timestamp.as_seconds_f64(), 1.5);
+
+        let rescaled = timestamp.rescale(Rational::new(1, NonZero::new(90_000).unwrap()));
+        assert_eq!(rescaled.value(), 135_000);
+        assert_eq!(rescaled.time_base(), Rational::new(1, NonZero::new(90_000).unwrap()));
+
+        assert_eq!(
+            Timestamp::from_seconds(2).rescale(Timestamp::AV_TIME_BASE),
+            Timestamp::from_micros(2_000_000)
+        );
+        assert_eq!(
+            Timestamp::new(1001, Rational::new(1, NonZero::new(30_000).unwrap())).rescale(Rational::static_new::<1, 1000>()),
+            Timestamp::from_millis(33)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_rescale_same_time_base() {
+        let timestamp = Timestamp::from_micros(-42);
+        assert_eq!(timestamp.rescale(Timestamp::AV_TIME_BASE), timestamp);
+    }
+}
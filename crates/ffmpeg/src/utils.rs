@@ -9,3 +9,16 @@ pub const fn check_i64(val: i64) -> Option<i64> {
 pub const fn or_nopts(val: Option<i64>) -> i64 {
     if let Some(val) = val { val } else { AV_NOPTS_VALUE }
 }
+
+/// Converts a nul-terminated c-string to a `&str`, returns `None` if it is null or not valid UTF-8.
+///
+/// # Safety
+/// `ptr` must be null or point to a nul-terminated string that is valid for `'a`.
+pub(crate) unsafe fn c_str<'a>(ptr: *const std::ffi::c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    // Safety: The caller guarantees `ptr` is a valid nul-terminated string.
+    unsafe { std::ffi::CStr::from_ptr(ptr) }.to_str().ok()
+}
@@ -0,0 +1,565 @@
+//! Automatic certificates from an ACME certificate authority like [Let's Encrypt](https://letsencrypt.org).
+//!
+//! [`Acme`] implements the client side of [RFC 8555](https://www.rfc-editor.org/rfc/rfc8555).
+//! It obtains a certificate for a list of domains, inserts it into a [`CertStore`] and renews it before it expires.
+//! New TLS handshakes use the renewed certificate right away, the server does not have to be restarted.
+//!
+//! The certificate authority validates the domains with one of two challenges:
+//!
+//! - TLS-ALPN-01 ([RFC 8737](https://www.rfc-editor.org/rfc/rfc8737)), the default, is answered by the TLS acceptor
+//!   of the [`HttpServer`](crate::HttpServer) on port 443. Use [`Acme::server_config`] as the rustls config of the server.
+//! - HTTP-01 is answered by wrapping the service of a plain HTTP server on port 80 with [`Acme::http01_service`].
+//!
+//! The account key and the certificates are persisted with an [`AcmeStorage`], so they survive restarts.
+//! Certificates for wildcard domains require the DNS-01 challenge and are not supported.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use scuffle_http::acme::{Acme, FileStorage, LETS_ENCRYPT_PRODUCTION};
+//!
+//! let acme = Acme::builder()
+//!     .directory_url(LETS_ENCRYPT_PRODUCTION.to_owned())
+//!     .domains(vec!["example.com".to_owned()])
+//!     .contact(vec!["mailto:admin@example.com".to_owned()])
+//!     .storage(FileStorage::new("/var/lib/example/acme"))
+//!     .build()?;
+//!
+//! // Obtains the certificate and renews it until the context is done
+//! tokio::spawn(acme.clone().run(scuffle_context::Context::global()));
+//!
+//! let service = scuffle_http::service::fn_http_service(|_| async move {
+//!     scuffle_http::Response::builder().body("Hello, world!".to_string())
+//! });
+//!
+//! scuffle_http::HttpServer::builder()
+//!     .service_factory(scuffle_http::service::service_clone_factory(service))
+//!     .rustls_config(acme.server_config())
+//!     .bind("[::]:443".parse().unwrap())
+//!     .build()
+//!     .run()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `scuffle-bootstrap` crate, [`Acme`] can be run as a [`Service`](scuffle_bootstrap::service::Service) instead.
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use aws_lc_rs::signature::{ECDSA_P256_SHA256_ASN1_SIGNING, EcdsaKeyPair};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use scuffle_context::ContextFutExt;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::sign::CertifiedKey;
+
+use crate::client::{ClientError, HttpClient, ResponseBodyError};
+use crate::tls::{ACME_TLS_ALPN_NAME, CertError, CertStore};
+
+mod challenge;
+pub(crate) mod der;
+mod jws;
+mod protocol;
+mod storage;
+
+pub use challenge::{AcmeResolver, Http01};
+pub use protocol::Problem;
+pub use storage::{AcmeStorage, FileStorage, MemoryStorage};
+
+use self::challenge::Challenges;
+use self::jws::AccountKey;
+use self::protocol::{Authorization, Order, Session, Status};
+
+/// The directory of the Let's Encrypt production environment.
+pub const LETS_ENCRYPT_PRODUCTION: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// The directory of the Let's Encrypt staging environment, which issues untrusted certificates with higher rate limits.
+pub const LETS_ENCRYPT_STAGING: &str = "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+/// The storage key of the account key.
+const ACCOUNT_KEY: &str = "account.key.pem";
+
+/// An error that can occur when obtaining a certificate.
+#[derive(Debug, thiserror::Error)]
+pub enum AcmeError {
+    /// The request to the ACME server failed.
+    #[error("client error: {0}")]
+    Client(#[from] ClientError),
+    /// The response body could not be read.
+    #[error("response body error: {0}")]
+    ResponseBody(#[from] ResponseBodyError),
+    /// The request could not be built.
+    #[error("http error: {0}")]
+    Http(#[from] http::Error),
+    /// A request or response could not be (de)serialized.
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The ACME server rejected a request.
+    #[error("acme server error ({status}): {problem}")]
+    Problem {
+        /// The status code of the response.
+        status: http::StatusCode,
+        /// The error returned by the server.
+        problem: Problem,
+    },
+    /// The ACME server responded with an unexpected status code.
+    #[error("unexpected status code: {0}")]
+    UnexpectedStatus(http::StatusCode),
+    /// A response of the ACME server is missing a required header.
+    #[error("missing header: {0}")]
+    MissingHeader(http::HeaderName),
+    /// The ACME server could not validate a domain.
+    #[error("authorization of {domain} failed: {problem}")]
+    AuthorizationFailed {
+        /// The domain that could not be validated.
+        domain: String,
+        /// The error of the challenge, if the server returned one.
+        problem: Problem,
+    },
+    /// The order became invalid.
+    #[error("order failed: {0}")]
+    OrderFailed(Problem),
+    /// The ACME server does not offer the configured challenge type for a domain.
+    #[error("no {challenge:?} challenge offered for {domain}")]
+    NoChallenge {
+        /// The domain of the authorization.
+        domain: String,
+        /// The configured challenge type.
+        challenge: ChallengeType,
+    },
+    /// A stored key could not be loaded.
+    #[error("key rejected: {0}")]
+    KeyRejected(#[from] aws_lc_rs::error::KeyRejected),
+    /// Generating a key or a signature failed.
+    #[error("crypto error")]
+    Crypto(#[from] aws_lc_rs::error::Unspecified),
+    /// A stored or issued certificate could not be loaded.
+    #[error("certificate error: {0}")]
+    Cert(#[from] CertError),
+    /// The storage failed to load or store a value.
+    #[error("storage error: {0}")]
+    Storage(#[from] std::io::Error),
+    /// The ACME server did not finish processing an order or authorization in time.
+    #[error("timed out waiting for the acme server")]
+    Timeout,
+    /// A domain is empty or a wildcard, or no domains were given.
+    #[error("invalid domain: {0:?}")]
+    InvalidDomain(String),
+}
+
+impl From<rustls::pki_types::pem::Error> for AcmeError {
+    fn from(err: rustls::pki_types::pem::Error) -> Self {
+        Self::Cert(err.into())
+    }
+}
+
+/// The challenge used to prove control over the domains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChallengeType {
+    /// Answered by the TLS acceptor on port 443, see [`Acme::server_config`].
+    #[default]
+    TlsAlpn01,
+    /// Answered by an HTTP service on port 80, see [`Acme::http01_service`].
+    Http01,
+}
+
+impl ChallengeType {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChallengeType::TlsAlpn01 => "tls-alpn-01",
+            ChallengeType::Http01 => "http-01",
+        }
+    }
+}
+
+/// Obtains and renews a certificate from an ACME certificate authority.
+///
+/// Cloning is cheap, all clones share the same configuration and certificates.
+///
+/// Create a new client by calling [`Acme::builder`]. See the [module documentation](self) for an example.
+pub struct Acme<S> {
+    inner: Arc<AcmeInner<S>>,
+}
+
+impl<S> Clone for Acme<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for Acme<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Acme")
+            .field("directory_url", &self.inner.directory_url)
+            .field("domains", &self.inner.domains)
+            .field("challenge", &self.inner.challenge)
+            .field("storage", &self.inner.storage)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct AcmeInner<S> {
+    directory_url: String,
+    domains: Vec<String>,
+    contact: Vec<String>,
+    challenge: ChallengeType,
+    storage: S,
+    cert_store: CertStore,
+    client: HttpClient,
+    renew_before: Duration,
+    retry_interval: Duration,
+    timeout: Duration,
+    challenges: Arc<Challenges>,
+}
+
+#[bon::bon]
+impl<S: AcmeStorage> Acme<S> {
+    /// Creates a new client.
+    ///
+    /// Fails if `domains` is empty or contains an empty or wildcard domain.
+    #[builder]
+    pub fn new(
+        /// The directory url of the certificate authority, for example [`LETS_ENCRYPT_PRODUCTION`].
+        directory_url: String,
+        /// The domains of the certificate, the first domain is used as the common name.
+        domains: Vec<String>,
+        /// Contact urls of the account, like `mailto:admin@example.com`.
+        #[builder(default)]
+        contact: Vec<String>,
+        /// The challenge used to validate the domains.
+        #[builder(default)]
+        challenge: ChallengeType,
+        /// Persists the account key and the certificates.
+        storage: S,
+        /// The store the certificate is inserted into, once for every domain.
+        #[builder(default)]
+        cert_store: CertStore,
+        /// The client used to talk to the certificate authority.
+        client: Option<HttpClient>,
+        /// Renew the certificate this long before it expires.
+        #[builder(default = Duration::from_secs(30 * 24 * 60 * 60))]
+        renew_before: Duration,
+        /// Wait this long before trying again after obtaining a certificate failed.
+        #[builder(default = Duration::from_secs(10 * 60))]
+        retry_interval: Duration,
+        /// The maximum time to wait for the certificate authority to validate the domains and to issue the certificate.
+        #[builder(default = Duration::from_secs(2 * 60))]
+        timeout: Duration,
+    ) -> Result<Self, AcmeError> {
+        if domains.is_empty() {
+            return Err(AcmeError::InvalidDomain(String::new()));
+        }
+
+        let domains = domains
+            .into_iter()
+            .map(|domain| {
+                if domain.is_empty() || domain.contains('*') {
+                    Err(AcmeError::InvalidDomain(domain))
+                } else {
+                    Ok(domain.to_ascii_lowercase())
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let client = match client {
+            Some(client) => client,
+            None => HttpClient::builder().build()?,
+        };
+
+        Ok(Self {
+            inner: Arc::new(AcmeInner {
+                directory_url,
+                domains,
+                contact,
+                challenge,
+                storage,
+                cert_store,
+                client,
+                renew_before,
+                retry_interval,
+                timeout,
+                challenges: Arc::default(),
+            }),
+        })
+    }
+}
+
+impl<S: AcmeStorage> Acme<S> {
+    /// The store the certificate is inserted into.
+    pub fn cert_store(&self) -> &CertStore {
+        &self.inner.cert_store
+    }
+
+    /// Returns a certificate resolver that answers TLS-ALPN-01 challenges and resolves all other
+    /// handshakes with the [`cert_store`](Self::cert_store).
+    pub fn resolver(&self) -> Arc<AcmeResolver> {
+        Arc::new(AcmeResolver {
+            cert_store: self.inner.cert_store.clone(),
+            challenges: Arc::clone(&self.inner.challenges),
+        })
+    }
+
+    /// Returns a [`rustls::ServerConfig`] that uses the [`resolver`](Self::resolver) to select certificates.
+    ///
+    /// Only `acme-tls/1` is set as ALPN protocol, the [`HttpServer`](crate::HttpServer) adds the protocols
+    /// of the enabled backends.
+    pub fn server_config(&self) -> rustls::ServerConfig {
+        let mut config = rustls::ServerConfig::builder_with_provider(crate::tls::crypto_provider())
+            .with_safe_default_protocol_versions()
+            .expect("the crypto provider should support the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(self.resolver());
+        config.alpn_protocols = vec![ACME_TLS_ALPN_NAME.to_vec()];
+        config
+    }
+
+    /// Wraps a service to answer HTTP-01 challenges, see [`Http01`].
+    pub fn http01_service<T>(&self, inner: T) -> Http01<T> {
+        Http01::new(inner, Arc::clone(&self.inner.challenges))
+    }
+
+    /// The storage key of the certificate or private key, for example `example.com.crt.pem`.
+    fn storage_key(&self, kind: &str) -> String {
+        format!("{}.{kind}.pem", self.inner.domains.join("+"))
+    }
+
+    fn insert(&self, key: CertifiedKey) {
+        for domain in &self.inner.domains {
+            self.inner.cert_store.insert(Some(domain), key.clone());
+        }
+    }
+
+    /// Loads the certificate from the storage into the [`cert_store`](Self::cert_store).
+    ///
+    /// Returns the expiry date of the certificate, `None` if there is no stored certificate.
+    pub async fn load(&self) -> Result<Option<SystemTime>, AcmeError> {
+        let storage = &self.inner.storage;
+        let (Some(cert), Some(key)) = (
+            storage.load(&self.storage_key("crt")).await?,
+            storage.load(&self.storage_key("key")).await?,
+        ) else {
+            return Ok(None);
+        };
+
+        let key = certified_key(&cert, &key)?;
+        let not_after = key.end_entity_cert().ok().and_then(crate::tls::not_after);
+        self.insert(key);
+
+        Ok(not_after)
+    }
+
+    async fn account_key(&self) -> Result<AccountKey, AcmeError> {
+        if let Some(pem) = self.inner.storage.load(ACCOUNT_KEY).await? {
+            let key = PrivatePkcs8KeyDer::from_pem_slice(&pem)?;
+            return AccountKey::from_pkcs8(key.secret_pkcs8_der().to_vec());
+        }
+
+        let key = AccountKey::from_pkcs8(der::generate_key()?)?;
+        self.inner
+            .storage
+            .store(ACCOUNT_KEY, der::pem("PRIVATE KEY", key.pkcs8()).as_bytes())
+            .await?;
+
+        Ok(key)
+    }
+
+    /// Obtains a new certificate, stores it and inserts it into the [`cert_store`](Self::cert_store).
+    ///
+    /// Returns the expiry date of the certificate.
+    pub async fn issue(&self) -> Result<Option<SystemTime>, AcmeError> {
+        let inner = &self.inner;
+
+        let account_key = self.account_key().await?;
+        let session = Session::new(&inner.client, &inner.directory_url, &account_key, &inner.contact).await?;
+
+        let (order_url, order) = session.new_order(&inner.domains).await?;
+        for authorization in &order.authorizations {
+            self.authorize(&session, &account_key, authorization).await?;
+        }
+
+        let order: Order = session
+            .poll(&order_url, inner.timeout, |order: &Order| order.status != Status::Pending)
+            .await?;
+        if order.status != Status::Ready {
+            return Err(AcmeError::OrderFailed(order.error.unwrap_or_default()));
+        }
+
+        let pkcs8 = der::generate_key()?;
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8)?;
+        let csr = der::certificate_signing_request(&key, &inner.domains)?;
+
+        session
+            .post(
+                &order.finalize,
+                Some(serde_json::json!({ "csr": URL_SAFE_NO_PAD.encode(csr) })),
+            )
+            .await?;
+
+        let order: Order = session
+            .poll(&order_url, inner.timeout, |order: &Order| {
+                !matches!(order.status, Status::Ready | Status::Processing)
+            })
+            .await?;
+        let certificate = match (order.status, order.certificate) {
+            (Status::Valid, Some(certificate)) => certificate,
+            _ => return Err(AcmeError::OrderFailed(order.error.unwrap_or_default())),
+        };
+
+        let cert = session.post(&certificate, None).await?.body;
+        let key = der::pem("PRIVATE KEY", &pkcs8);
+
+        let certified_key = certified_key(&cert, key.as_bytes())?;
+        let not_after = certified_key.end_entity_cert().ok().and_then(crate::tls::not_after);
+
+        inner.storage.store(&self.storage_key("key"), key.as_bytes()).await?;
+        inner.storage.store(&self.storage_key("crt"), &cert).await?;
+        self.insert(certified_key);
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(domains = ?inner.domains, not_after = ?not_after, "obtained certificate");
+
+        Ok(not_after)
+    }
+
+    /// Validates a single domain of an order.
+    async fn authorize(&self, session: &Session<'_>, account_key: &AccountKey, url: &str) -> Result<(), AcmeError> {
+        let authorization: Authorization = session.post(url, None).await?.json()?;
+        if authorization.status == Status::Valid {
+            return Ok(());
+        }
+
+        let domain = authorization.identifier.value;
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|challenge| challenge.challenge_type == self.inner.challenge.as_str())
+            .ok_or_else(|| AcmeError::NoChallenge {
+                domain: domain.clone(),
+                challenge: self.inner.challenge,
+            })?;
+
+        let key_authorization = account_key.key_authorization(&challenge.token);
+        let _guard = match self.inner.challenge {
+            ChallengeType::TlsAlpn01 => {
+                let digest = aws_lc_rs::digest::digest(&aws_lc_rs::digest::SHA256, key_authorization.as_bytes());
+                let pkcs8 = der::generate_key()?;
+                let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8)?;
+                let cert = der::tls_alpn_01_certificate(&key, &domain, digest.as_ref())?;
+
+                // Not `CertifiedKey::from_der`, webpki rejects the critical acmeIdentifier extension
+                let signing_key = crate::tls::crypto_provider()
+                    .key_provider
+                    .load_private_key(PrivateKeyDer::Pkcs8(pkcs8.into()))
+                    .map_err(CertError::from)?;
+                let key = CertifiedKey::new(vec![CertificateDer::from(cert)], signing_key);
+
+                self.inner.challenges.add_tls_alpn_01(&domain, key)
+            }
+            ChallengeType::Http01 => self.inner.challenges.add_http_01(&challenge.token, key_authorization),
+        };
+
+        // Tell the server that the challenge can be validated now
+        session.post(&challenge.url, Some(serde_json::json!({}))).await?;
+
+        let authorization: Authorization = session
+            .poll(url, self.inner.timeout, |authorization: &Authorization| {
+                authorization.status != Status::Pending
+            })
+            .await?;
+
+        if authorization.status != Status::Valid {
+            let problem = authorization
+                .challenges
+                .into_iter()
+                .find_map(|challenge| challenge.error)
+                .unwrap_or_default();
+            return Err(AcmeError::AuthorizationFailed { domain, problem });
+        }
+
+        Ok(())
+    }
+
+    /// Loads the stored certificate and renews it before it expires, until the context is done.
+    ///
+    /// A certificate is obtained right away if there is no stored certificate or if it expires within `renew_before`.
+    /// Failures are retried after `retry_interval` and emit a [`CertEvent::ReloadFailed`](crate::tls::CertEvent::ReloadFailed)
+    /// on the [`cert_store`](Self::cert_store).
+    pub async fn run(self, ctx: scuffle_context::Context) {
+        let mut not_after = match self.load().await {
+            Ok(not_after) => not_after,
+            Err(err) => {
+                self.report_error(&err);
+                None
+            }
+        };
+
+        loop {
+            let renew_at = not_after.map_or(SystemTime::UNIX_EPOCH, |not_after| not_after - self.inner.renew_before);
+            let wait = renew_at.duration_since(SystemTime::now()).unwrap_or_default();
+            if tokio::time::sleep(wait).with_context(&ctx).await.is_none() {
+                return;
+            }
+
+            match self.issue().with_context(&ctx).await {
+                Some(Ok(Some(expires))) => not_after = Some(expires),
+                // The expiry date could not be read, try again once the retry interval has passed
+                Some(Ok(None)) => not_after = Some(SystemTime::now() + self.inner.renew_before + self.inner.retry_interval),
+                Some(Err(err)) => {
+                    self.report_error(&err);
+                    if tokio::time::sleep(self.inner.retry_interval)
+                        .with_context(&ctx)
+                        .await
+                        .is_none()
+                    {
+                        return;
+                    }
+                }
+                None => return,
+            }
+        }
+    }
+
+    fn report_error(&self, err: &AcmeError) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(domains = ?self.inner.domains, err = %err, "failed to obtain certificate");
+
+        self.inner.cert_store.report(crate::tls::CertEvent::ReloadFailed {
+            server_name: Some(self.inner.domains[0].clone()),
+            error: err.to_string(),
+        });
+    }
+}
+
+/// Loads a PEM encoded certificate chain and private key.
+fn certified_key(cert: &[u8], key: &[u8]) -> Result<CertifiedKey, AcmeError> {
+    let certs = CertificateDer::pem_slice_iter(cert).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(CertError::NoCertificates.into());
+    }
+
+    let key = PrivateKeyDer::from_pem_slice(key)?;
+
+    Ok(CertifiedKey::from_der(certs, key, &crate::tls::crypto_provider()).map_err(CertError::from)?)
+}
+
+impl<G, S> scuffle_bootstrap::service::Service<G> for Acme<S>
+where
+    G: Send + Sync + 'static,
+    S: AcmeStorage,
+{
+    fn name(&self) -> Option<&'static str> {
+        Some("acme")
+    }
+
+    async fn run(self, _global: Arc<G>, ctx: scuffle_context::Context) -> anyhow::Result<()> {
+        Acme::run(self, ctx).await;
+        Ok(())
+    }
+}
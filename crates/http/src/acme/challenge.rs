@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use http::{Method, StatusCode, header};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+
+use super::ChallengeType;
+use crate::IncomingRequest;
+use crate::middleware::MiddlewareBody;
+use crate::service::HttpService;
+use crate::tls::{ACME_TLS_ALPN_NAME, CertStore};
+
+/// The path prefix of HTTP-01 challenges, RFC 8555 section 8.3.
+const HTTP_01_PATH: &str = "/.well-known/acme-challenge/";
+
+/// The challenges that are currently being validated.
+#[derive(Debug, Default)]
+pub(crate) struct Challenges {
+    /// Challenge certificates by domain.
+    tls_alpn_01: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+    /// Key authorizations by token.
+    http_01: RwLock<HashMap<String, String>>,
+}
+
+impl Challenges {
+    /// Answers TLS-ALPN-01 challenges for `domain` with `key` until the guard is dropped.
+    pub(crate) fn add_tls_alpn_01(&self, domain: &str, key: CertifiedKey) -> ChallengeGuard<'_> {
+        let domain = domain.to_ascii_lowercase();
+        self.tls_alpn_01
+            .write()
+            .expect("lock poisoned")
+            .insert(domain.clone(), Arc::new(key));
+
+        ChallengeGuard {
+            challenges: self,
+            challenge: ChallengeType::TlsAlpn01,
+            key: domain,
+        }
+    }
+
+    /// Answers HTTP-01 challenges for `token` with `key_authorization` until the guard is dropped.
+    pub(crate) fn add_http_01(&self, token: &str, key_authorization: String) -> ChallengeGuard<'_> {
+        self.http_01
+            .write()
+            .expect("lock poisoned")
+            .insert(token.to_owned(), key_authorization);
+
+        ChallengeGuard {
+            challenges: self,
+            challenge: ChallengeType::Http01,
+            key: token.to_owned(),
+        }
+    }
+
+    fn tls_alpn_01(&self, domain: &str) -> Option<Arc<CertifiedKey>> {
+        self.tls_alpn_01
+            .read()
+            .expect("lock poisoned")
+            .get(&domain.to_ascii_lowercase())
+            .cloned()
+    }
+
+    fn http_01(&self, token: &str) -> Option<String> {
+        self.http_01.read().expect("lock poisoned").get(token).cloned()
+    }
+}
+
+/// Stops answering a challenge when dropped.
+pub(crate) struct ChallengeGuard<'a> {
+    challenges: &'a Challenges,
+    challenge: ChallengeType,
+    key: String,
+}
+
+impl Drop for ChallengeGuard<'_> {
+    fn drop(&mut self) {
+        match self.challenge {
+            ChallengeType::TlsAlpn01 => {
+                self.challenges.tls_alpn_01.write().expect("lock poisoned").remove(&self.key);
+            }
+            ChallengeType::Http01 => {
+                self.challenges.http_01.write().expect("lock poisoned").remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Selects the certificate of a TLS handshake, answering TLS-ALPN-01 challenges.
+///
+/// Handshakes that offer the `acme-tls/1` protocol get the challenge certificate of the requested
+/// server name, all other handshakes are resolved by the [`CertStore`].
+///
+/// Get it from [`Acme::resolver`](super::Acme::resolver) to use it in your own [`ServerConfig`](tokio_rustls::rustls::ServerConfig).
+/// The config must list `acme-tls/1` in its ALPN protocols.
+#[derive(Debug, Clone)]
+pub struct AcmeResolver {
+    pub(crate) cert_store: CertStore,
+    pub(crate) challenges: Arc<Challenges>,
+}
+
+impl ResolvesServerCert for AcmeResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let acme_tls = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|protocol| protocol == ACME_TLS_ALPN_NAME));
+
+        if acme_tls {
+            return client_hello
+                .server_name()
+                .and_then(|server_name| self.challenges.tls_alpn_01(server_name));
+        }
+
+        self.cert_store.resolve(client_hello)
+    }
+}
+
+/// Answers HTTP-01 challenges and passes all other requests to the inner service.
+///
+/// Challenges are `GET` requests to `/.well-known/acme-challenge/{token}` and are answered with the key
+/// authorization of the token, or `404 Not Found` for unknown tokens.
+/// HTTP-01 challenges are always sent over plain HTTP to port 80.
+///
+/// Create by calling [`Acme::http01_service`](super::Acme::http01_service).
+#[derive(Debug, Clone)]
+pub struct Http01<S> {
+    inner: S,
+    challenges: Arc<Challenges>,
+}
+
+impl<S> Http01<S> {
+    pub(crate) fn new(inner: S, challenges: Arc<Challenges>) -> Self {
+        Self { inner, challenges }
+    }
+}
+
+impl<S> HttpService for Http01<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = MiddlewareBody<S::ResBody>;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let token = (req.method() == Method::GET)
+            .then(|| req.uri().path().strip_prefix(HTTP_01_PATH))
+            .flatten();
+
+        let Some(token) = token else {
+            return Ok(self.inner.call(req).await?.map(MiddlewareBody::inner));
+        };
+
+        let response = match self.challenges.http_01(token) {
+            Some(key_authorization) => http::Response::builder()
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .body(MiddlewareBody::full(key_authorization)),
+            None => http::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(MiddlewareBody::empty()),
+        };
+
+        Ok(response.expect("valid response"))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::Challenges;
+
+    #[test]
+    fn guard() {
+        let challenges = Challenges::default();
+
+        let guard = challenges.add_http_01("token", "token.thumbprint".to_owned());
+        assert_eq!(challenges.http_01("token").as_deref(), Some("token.thumbprint"));
+        assert_eq!(challenges.http_01("other"), None);
+
+        drop(guard);
+        assert_eq!(challenges.http_01("token"), None);
+    }
+}
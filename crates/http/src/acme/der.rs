@@ -0,0 +1,238 @@
+//! A minimal DER encoder for certificate signing requests and TLS-ALPN-01 challenge certificates.
+use aws_lc_rs::error::Unspecified;
+use aws_lc_rs::rand::SystemRandom;
+use aws_lc_rs::signature::{ECDSA_P256_SHA256_ASN1_SIGNING, EcdsaKeyPair, KeyPair};
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const UTC_TIME: u8 = 0x17;
+const BOOLEAN: u8 = 0x01;
+const DNS_NAME: u8 = 0x82;
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+/// `id-pe-acmeIdentifier`, RFC 8737 section 6.1.
+const OID_ACME_IDENTIFIER: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x1f];
+
+/// Encodes a single DER element.
+pub(crate) fn element(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(contents.len() + 6);
+    out.push(tag);
+
+    if contents.len() < 0x80 {
+        out.push(contents.len() as u8);
+    } else {
+        let len = contents.len().to_be_bytes();
+        let skip = len.iter().take_while(|&&byte| byte == 0).count();
+        out.push(0x80 | (len.len() - skip) as u8);
+        out.extend_from_slice(&len[skip..]);
+    }
+
+    out.extend_from_slice(contents);
+    out
+}
+
+fn sequence(items: &[&[u8]]) -> Vec<u8> {
+    element(SEQUENCE, &items.concat())
+}
+
+fn signature_algorithm() -> Vec<u8> {
+    sequence(&[&element(OID, OID_ECDSA_WITH_SHA256)])
+}
+
+/// A `Name` with a single common name.
+fn name(common_name: &str) -> Vec<u8> {
+    let attribute = sequence(&[&element(OID, OID_COMMON_NAME), &element(UTF8_STRING, common_name.as_bytes())]);
+    sequence(&[&element(SET, &attribute)])
+}
+
+/// The `SubjectPublicKeyInfo` of a P-256 public key in uncompressed form.
+pub(crate) fn subject_public_key_info(public_key: &[u8]) -> Vec<u8> {
+    let algorithm = sequence(&[&element(OID, OID_EC_PUBLIC_KEY), &element(OID, OID_PRIME256V1)]);
+    sequence(&[&algorithm, &element(BIT_STRING, &[&[0], public_key].concat())])
+}
+
+fn extension(oid: &[u8], critical: bool, value: &[u8]) -> Vec<u8> {
+    let critical = if critical { element(BOOLEAN, &[0xff]) } else { Vec::new() };
+    sequence(&[&element(OID, oid), &critical, &element(OCTET_STRING, value)])
+}
+
+fn subject_alt_name(domains: &[String]) -> Vec<u8> {
+    let names = domains
+        .iter()
+        .map(|domain| element(DNS_NAME, domain.as_bytes()))
+        .collect::<Vec<_>>();
+    extension(OID_SUBJECT_ALT_NAME, false, &element(SEQUENCE, &names.concat()))
+}
+
+/// Signs `data` and appends the signature algorithm and signature, completing a signed structure.
+fn signed(key: &EcdsaKeyPair, data: Vec<u8>) -> Result<Vec<u8>, Unspecified> {
+    let signature = key.sign(&SystemRandom::new(), &data)?;
+    Ok(sequence(&[
+        &data,
+        &signature_algorithm(),
+        &element(BIT_STRING, &[&[0], signature.as_ref()].concat()),
+    ]))
+}
+
+/// Generates a new P-256 key, returns it PKCS#8 encoded.
+pub(crate) fn generate_key() -> Result<Vec<u8>, Unspecified> {
+    let key = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())?;
+    Ok(key.as_ref().to_vec())
+}
+
+/// Builds a PKCS#10 certificate signing request for the given domains.
+pub(crate) fn certificate_signing_request(key: &EcdsaKeyPair, domains: &[String]) -> Result<Vec<u8>, Unspecified> {
+    let extensions = sequence(&[&subject_alt_name(domains)]);
+    let extension_request = sequence(&[&element(OID, OID_EXTENSION_REQUEST), &element(SET, &extensions)]);
+
+    let info = sequence(&[
+        &element(INTEGER, &[0]),
+        &name(&domains[0]),
+        &subject_public_key_info(key.public_key().as_ref()),
+        &element(0xa0, &extension_request),
+    ]);
+
+    signed(key, info)
+}
+
+/// Builds a X.509 v3 certificate for the given domains.
+///
+/// The certificate is valid from 2000 until the end of 2049 and signed by `issuer_key`,
+/// which makes it self-signed if the `subject_public_key_info` belongs to the same key.
+pub(crate) fn certificate(
+    issuer_key: &EcdsaKeyPair,
+    issuer: &str,
+    subject_public_key_info: &[u8],
+    domains: &[String],
+    extra_extensions: &[Vec<u8>],
+) -> Result<Vec<u8>, Unspecified> {
+    let mut serial = [0; 16];
+    aws_lc_rs::rand::fill(&mut serial)?;
+    // Positive and without leading zeros
+    serial[0] = (serial[0] & 0x7f) | 0x40;
+
+    let validity = sequence(&[&element(UTC_TIME, b"000101000000Z"), &element(UTC_TIME, b"491231235959Z")]);
+
+    let mut extensions = vec![subject_alt_name(domains)];
+    extensions.extend_from_slice(extra_extensions);
+
+    let tbs_certificate = sequence(&[
+        &element(0xa0, &element(INTEGER, &[2])),
+        &element(INTEGER, &serial),
+        &signature_algorithm(),
+        &name(issuer),
+        &validity,
+        &name(&domains[0]),
+        subject_public_key_info,
+        &element(0xa3, &element(SEQUENCE, &extensions.concat())),
+    ]);
+
+    signed(issuer_key, tbs_certificate)
+}
+
+/// Builds the self-signed certificate that answers a TLS-ALPN-01 challenge, RFC 8737 section 3.
+///
+/// `key_authorization_digest` is the SHA-256 digest of the key authorization of the challenge.
+pub(crate) fn tls_alpn_01_certificate(
+    key: &EcdsaKeyPair,
+    domain: &str,
+    key_authorization_digest: &[u8],
+) -> Result<Vec<u8>, Unspecified> {
+    let acme_identifier = extension(OID_ACME_IDENTIFIER, true, &element(OCTET_STRING, key_authorization_digest));
+
+    certificate(
+        key,
+        domain,
+        &subject_public_key_info(key.public_key().as_ref()),
+        &[domain.to_owned()],
+        &[acme_identifier],
+    )
+}
+
+/// Encodes DER data as PEM.
+pub(crate) fn pem(label: &str, der: &[u8]) -> String {
+    use base64::Engine;
+
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use aws_lc_rs::signature::{ECDSA_P256_SHA256_ASN1_SIGNING, EcdsaKeyPair, KeyPair};
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use tokio_rustls::rustls::sign::CertifiedKey;
+
+    #[test]
+    fn element_length() {
+        assert_eq!(super::element(0x04, &[1, 2]), [0x04, 2, 1, 2]);
+        assert_eq!(super::element(0x04, &[0; 200])[..3], [0x04, 0x81, 200]);
+        assert_eq!(super::element(0x04, &[0; 300])[..4], [0x04, 0x82, 0x01, 0x2c]);
+    }
+
+    #[test]
+    fn certificate() {
+        let pkcs8 = super::generate_key().unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8).unwrap();
+
+        let spki = super::subject_public_key_info(key.public_key().as_ref());
+        let cert = super::certificate(&key, "example.com", &spki, &["example.com".to_owned()], &[]).unwrap();
+
+        // rustls parses the certificate and checks that the key matches
+        CertifiedKey::from_der(
+            vec![CertificateDer::from(cert.clone())],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(pkcs8)),
+            &crate::tls::crypto_provider(),
+        )
+        .expect("invalid certificate");
+
+        assert!(
+            crate::tls::not_after(&CertificateDer::from(cert)).is_some(),
+            "failed to parse certificate"
+        );
+    }
+
+    #[test]
+    fn challenge_certificate() {
+        let pkcs8 = super::generate_key().unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8).unwrap();
+
+        let cert = super::tls_alpn_01_certificate(&key, "example.com", &[7; 32]).unwrap();
+
+        let acme_identifier = super::extension(
+            super::OID_ACME_IDENTIFIER,
+            true,
+            &super::element(super::OCTET_STRING, &[7; 32]),
+        );
+        assert!(cert.windows(acme_identifier.len()).any(|window| window == acme_identifier));
+        assert!(
+            crate::tls::not_after(&CertificateDer::from(cert)).is_some(),
+            "failed to parse certificate"
+        );
+    }
+
+    #[test]
+    fn pem() {
+        let pem = super::pem("TEST", &[0; 60]);
+        assert_eq!(pem.lines().count(), 4);
+        assert!(pem.starts_with("-----BEGIN TEST-----\n"));
+        assert!(pem.ends_with("-----END TEST-----\n"));
+    }
+}
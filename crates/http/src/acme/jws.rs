@@ -0,0 +1,169 @@
+//! The account key and JSON Web Signatures (RFC 7515) of ACME requests.
+use aws_lc_rs::rand::SystemRandom;
+use aws_lc_rs::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde_derive::Serialize;
+
+use super::AcmeError;
+
+/// A P-256 public key as a JSON Web Key (RFC 7517).
+///
+/// The fields are in lexicographic order, as required for the thumbprint (RFC 7638).
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Jwk {
+    crv: &'static str,
+    kty: &'static str,
+    x: String,
+    y: String,
+}
+
+/// The protected header of a request.
+#[derive(Debug, Serialize)]
+struct Protected<'a> {
+    alg: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jwk: Option<&'a Jwk>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<&'a str>,
+    nonce: &'a str,
+    url: &'a str,
+}
+
+/// A flattened JWS, the body of every ACME POST request.
+#[derive(Debug, Serialize)]
+struct Jws {
+    protected: String,
+    payload: String,
+    signature: String,
+}
+
+/// The key that identifies an ACME account.
+pub(crate) struct AccountKey {
+    key: EcdsaKeyPair,
+    pkcs8: Vec<u8>,
+    jwk: Jwk,
+    thumbprint: String,
+}
+
+impl std::fmt::Debug for AccountKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountKey")
+            .field("thumbprint", &self.thumbprint)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AccountKey {
+    /// Loads a PKCS#8 encoded P-256 key.
+    pub(crate) fn from_pkcs8(pkcs8: Vec<u8>) -> Result<Self, AcmeError> {
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8)?;
+
+        // The public key is an uncompressed point: 0x04 || x || y
+        let point = key.public_key().as_ref();
+        let (x, y) = point[1..].split_at(32);
+        let jwk = Jwk {
+            crv: "P-256",
+            kty: "EC",
+            x: URL_SAFE_NO_PAD.encode(x),
+            y: URL_SAFE_NO_PAD.encode(y),
+        };
+
+        let thumbprint = serde_json::to_vec(&jwk)?;
+        let thumbprint = URL_SAFE_NO_PAD.encode(aws_lc_rs::digest::digest(&aws_lc_rs::digest::SHA256, &thumbprint));
+
+        Ok(Self {
+            key,
+            pkcs8,
+            jwk,
+            thumbprint,
+        })
+    }
+
+    /// The PKCS#8 encoding of the key.
+    pub(crate) fn pkcs8(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    /// The key authorization for a challenge token, RFC 8555 section 8.1.
+    pub(crate) fn key_authorization(&self, token: &str) -> String {
+        format!("{token}.{}", self.thumbprint)
+    }
+
+    /// Signs a request to `url`.
+    ///
+    /// Requests are signed with the account url (`kid`) once the account is registered and with the public key before.
+    /// A `payload` of `None` makes a POST-as-GET request.
+    pub(crate) fn sign(
+        &self,
+        url: &str,
+        nonce: &str,
+        kid: Option<&str>,
+        payload: Option<&serde_json::Value>,
+    ) -> Result<Vec<u8>, AcmeError> {
+        let protected = Protected {
+            alg: "ES256",
+            jwk: kid.is_none().then_some(&self.jwk),
+            kid,
+            nonce,
+            url,
+        };
+
+        let protected = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&protected)?);
+        let payload = match payload {
+            Some(payload) => URL_SAFE_NO_PAD.encode(serde_json::to_vec(payload)?),
+            None => String::new(),
+        };
+
+        let signature = self
+            .key
+            .sign(&SystemRandom::new(), format!("{protected}.{payload}").as_bytes())?;
+
+        Ok(serde_json::to_vec(&Jws {
+            protected,
+            payload,
+            signature: URL_SAFE_NO_PAD.encode(signature),
+        })?)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use aws_lc_rs::signature::{ECDSA_P256_SHA256_FIXED, UnparsedPublicKey};
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+    use super::AccountKey;
+
+    #[test]
+    fn sign() {
+        let key = AccountKey::from_pkcs8(crate::acme::der::generate_key().unwrap()).unwrap();
+        assert_eq!(key.key_authorization("token"), format!("token.{}", key.thumbprint));
+
+        let jws = key
+            .sign("https://acme.test/new-account", "nonce", None, Some(&serde_json::json!({})))
+            .unwrap();
+        let jws: serde_json::Value = serde_json::from_slice(&jws).unwrap();
+
+        let protected = URL_SAFE_NO_PAD.decode(jws["protected"].as_str().unwrap()).unwrap();
+        let protected: serde_json::Value = serde_json::from_slice(&protected).unwrap();
+        assert_eq!(protected["alg"], "ES256");
+        assert_eq!(protected["nonce"], "nonce");
+        assert_eq!(protected["url"], "https://acme.test/new-account");
+        assert_eq!(protected["jwk"]["kty"], "EC");
+        assert!(protected.get("kid").is_none());
+
+        let x = URL_SAFE_NO_PAD.decode(protected["jwk"]["x"].as_str().unwrap()).unwrap();
+        let y = URL_SAFE_NO_PAD.decode(protected["jwk"]["y"].as_str().unwrap()).unwrap();
+        let public_key = UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, [&[4], &x[..], &y[..]].concat());
+
+        let message = format!("{}.{}", jws["protected"].as_str().unwrap(), jws["payload"].as_str().unwrap());
+        let signature = URL_SAFE_NO_PAD.decode(jws["signature"].as_str().unwrap()).unwrap();
+        public_key.verify(message.as_bytes(), &signature).expect("invalid signature");
+
+        let jws = key.sign("https://acme.test/order", "nonce", Some("account"), None).unwrap();
+        let jws: serde_json::Value = serde_json::from_slice(&jws).unwrap();
+        assert_eq!(jws["payload"], "");
+    }
+}
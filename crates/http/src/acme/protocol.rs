@@ -0,0 +1,275 @@
+//! The ACME protocol, RFC 8555 section 7.
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use http::{HeaderValue, Method, header};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+
+use super::AcmeError;
+use super::jws::AccountKey;
+use crate::client::HttpClient;
+
+/// The urls of the ACME resources, RFC 8555 section 7.1.1.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+/// The status of an order, authorization or challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Status {
+    Pending,
+    Ready,
+    Processing,
+    Valid,
+    Invalid,
+    Revoked,
+    Deactivated,
+    Expired,
+}
+
+/// An error returned by the ACME server, RFC 7807.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Problem {
+    /// The type of the problem, for example `urn:ietf:params:acme:error:unauthorized`.
+    #[serde(rename = "type", default)]
+    pub problem_type: String,
+    /// A description of the problem.
+    #[serde(default)]
+    pub detail: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.problem_type, self.detail)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Order {
+    pub(crate) status: Status,
+    pub(crate) authorizations: Vec<String>,
+    pub(crate) finalize: String,
+    pub(crate) certificate: Option<String>,
+    pub(crate) error: Option<Problem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Identifier {
+    pub(crate) value: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Authorization {
+    pub(crate) identifier: Identifier,
+    pub(crate) status: Status,
+    pub(crate) challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Challenge {
+    #[serde(rename = "type")]
+    pub(crate) challenge_type: String,
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) token: String,
+    pub(crate) error: Option<Problem>,
+}
+
+/// A response to a signed request.
+pub(crate) struct Response {
+    pub(crate) location: Option<String>,
+    pub(crate) retry_after: Option<Duration>,
+    pub(crate) body: Bytes,
+}
+
+impl Response {
+    pub(crate) fn json<T: DeserializeOwned>(&self) -> Result<T, AcmeError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// A connection to an ACME server with a registered account.
+pub(crate) struct Session<'a> {
+    client: &'a HttpClient,
+    directory: Directory,
+    key: &'a AccountKey,
+    nonce: Mutex<Option<String>>,
+    kid: Option<String>,
+}
+
+const BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+/// Reads the whole body of a response.
+async fn read_body(mut body: crate::client::ResponseBody) -> Result<Bytes, AcmeError> {
+    let mut data = BytesMut::new();
+
+    while let Some(frame) = std::future::poll_fn(|cx| http_body::Body::poll_frame(std::pin::Pin::new(&mut body), cx)).await {
+        if let Ok(chunk) = frame?.into_data() {
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    Ok(data.freeze())
+}
+
+impl<'a> Session<'a> {
+    /// Fetches the directory and registers the account, or looks up the existing account of the key.
+    pub(crate) async fn new(
+        client: &'a HttpClient,
+        directory_url: &str,
+        key: &'a AccountKey,
+        contact: &[String],
+    ) -> Result<Self, AcmeError> {
+        let response = client.get(directory_url).await?;
+        if !response.status().is_success() {
+            return Err(AcmeError::UnexpectedStatus(response.status()));
+        }
+
+        let directory = serde_json::from_slice(&read_body(response.into_body()).await?)?;
+
+        let mut session = Self {
+            client,
+            directory,
+            key,
+            nonce: Mutex::new(None),
+            kid: None,
+        };
+
+        let new_account = session.directory.new_account.clone();
+        let response = session
+            .post(
+                &new_account,
+                Some(serde_json::json!({
+                    "termsOfServiceAgreed": true,
+                    "contact": contact,
+                })),
+            )
+            .await?;
+
+        session.kid = Some(response.location.ok_or(AcmeError::MissingHeader(header::LOCATION))?);
+
+        Ok(session)
+    }
+
+    async fn nonce(&self) -> Result<String, AcmeError> {
+        if let Some(nonce) = self.nonce.lock().expect("poisoned").take() {
+            return Ok(nonce);
+        }
+
+        let request = http::Request::builder()
+            .method(Method::HEAD)
+            .uri(&self.directory.new_nonce)
+            .body(Bytes::new())?;
+        let response = self.client.request(request).await?;
+
+        replay_nonce(response.headers()).ok_or(AcmeError::MissingHeader(REPLAY_NONCE))
+    }
+
+    /// Sends a signed request, a `payload` of `None` makes a POST-as-GET request.
+    ///
+    /// Requests rejected because of a bad nonce are retried with the nonce of the error response.
+    pub(crate) async fn post(&self, url: &str, payload: Option<serde_json::Value>) -> Result<Response, AcmeError> {
+        const MAX_ATTEMPTS: usize = 3;
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+
+            let nonce = self.nonce().await?;
+            let body = self.key.sign(url, &nonce, self.kid.as_deref(), payload.as_ref())?;
+
+            let request = http::Request::builder()
+                .method(Method::POST)
+                .uri(url)
+                .header(header::CONTENT_TYPE, HeaderValue::from_static("application/jose+json"))
+                .body(body)?;
+            let response = self.client.request(request).await?;
+
+            let (parts, body) = response.into_parts();
+            if let Some(nonce) = replay_nonce(&parts.headers) {
+                *self.nonce.lock().expect("poisoned") = Some(nonce);
+            }
+
+            let body = read_body(body).await?;
+
+            if parts.status.is_client_error() || parts.status.is_server_error() {
+                let problem = serde_json::from_slice::<Problem>(&body).unwrap_or_default();
+                if problem.problem_type == BAD_NONCE && attempt < MAX_ATTEMPTS {
+                    continue;
+                }
+
+                return Err(AcmeError::Problem {
+                    status: parts.status,
+                    problem,
+                });
+            }
+
+            let header = |name| parts.headers.get(name).and_then(|value| value.to_str().ok());
+
+            return Ok(Response {
+                location: header(header::LOCATION).map(str::to_owned),
+                retry_after: header(header::RETRY_AFTER)
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs),
+                body,
+            });
+        }
+    }
+
+    /// Creates a new order for the domains, returns its url and the order.
+    pub(crate) async fn new_order(&self, domains: &[String]) -> Result<(String, Order), AcmeError> {
+        let identifiers = domains
+            .iter()
+            .map(|domain| serde_json::json!({ "type": "dns", "value": domain }))
+            .collect::<Vec<_>>();
+
+        let new_order = &self.directory.new_order;
+        let response = self
+            .post(new_order, Some(serde_json::json!({ "identifiers": identifiers })))
+            .await?;
+        let url = response.location.clone().ok_or(AcmeError::MissingHeader(header::LOCATION))?;
+
+        Ok((url, response.json()?))
+    }
+
+    /// Fetches a resource until `done` returns `true`, waiting as long as the server asks for between the attempts.
+    pub(crate) async fn poll<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        timeout: Duration,
+        done: impl Fn(&T) -> bool,
+    ) -> Result<T, AcmeError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let response = self.post(url, None).await?;
+            let resource = response.json()?;
+            if done(&resource) {
+                return Ok(resource);
+            }
+
+            let wait = response.retry_after.unwrap_or(Duration::from_secs(1));
+            if tokio::time::Instant::now() + wait > deadline {
+                return Err(AcmeError::Timeout);
+            }
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+const REPLAY_NONCE: header::HeaderName = header::HeaderName::from_static("replay-nonce");
+
+fn replay_nonce(headers: &http::HeaderMap) -> Option<String> {
+    headers
+        .get(REPLAY_NONCE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
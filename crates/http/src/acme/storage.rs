@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Persists the account key and the certificates of an [`Acme`](super::Acme) client.
+///
+/// Values are PEM encoded and stored under keys like `account.key.pem` or `example.com.crt.pem`,
+/// which are valid file names.
+///
+/// Implement this trait to share certificates between multiple instances, for example through a database.
+pub trait AcmeStorage: Send + Sync + 'static {
+    /// Loads the value stored under `key`, `None` if there is none.
+    fn load(&self, key: &str) -> impl Future<Output = std::io::Result<Option<Vec<u8>>>> + Send;
+
+    /// Stores `value` under `key`, replacing the previous value.
+    fn store(&self, key: &str, value: &[u8]) -> impl Future<Output = std::io::Result<()>> + Send;
+}
+
+/// Stores every value as a file in a directory.
+///
+/// The directory is created when the first value is stored.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Stores values in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl AcmeStorage for FileStorage {
+    async fn load(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.dir.join(key)).await {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn store(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        // Write to a temporary file first, so a crash never leaves a partially written value behind
+        let path = self.dir.join(key);
+        let tmp = self.dir.join(format!(".{key}.tmp"));
+        tokio::fs::write(&tmp, value).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+}
+
+/// Keeps all values in memory.
+///
+/// Cloning the storage is cheap, all clones share the same values.
+/// Nothing survives a restart, so this is mostly useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    values: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AcmeStorage for MemoryStorage {
+    async fn load(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        Ok(self.values.lock().expect("lock poisoned").get(key).cloned())
+    }
+
+    async fn store(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        self.values
+            .lock()
+            .expect("lock poisoned")
+            .insert(key.to_owned(), value.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::{AcmeStorage, FileStorage};
+
+    #[tokio::test]
+    async fn file_storage() {
+        let dir = std::env::temp_dir().join(format!("scuffle-http-acme-{}", std::process::id()));
+        let storage = FileStorage::new(dir.join("nested"));
+
+        assert_eq!(storage.load("account.key.pem").await.unwrap(), None);
+
+        storage.store("account.key.pem", b"first").await.unwrap();
+        storage.store("account.key.pem", b"second").await.unwrap();
+        assert_eq!(
+            storage.load("account.key.pem").await.unwrap().as_deref(),
+            Some(&b"second"[..])
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
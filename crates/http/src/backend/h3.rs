@@ -1,7 +1,6 @@
 //! HTTP3 backend.
 use std::fmt::Debug;
 use std::io;
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 use body::QuicIncomingBody;
@@ -10,11 +9,15 @@ use scuffle_context::ContextFutExt;
 use tracing::Instrument;
 use utils::copy_response_body;
 
+use crate::connection::ConnectionConfig;
 use crate::error::HttpError;
+use crate::listener::ListenerSocket;
 use crate::service::{HttpService, HttpServiceFactory};
 
 pub mod body;
 mod utils;
+#[cfg(feature = "webtransport")]
+pub mod webtransport;
 
 /// A backend that handles incoming HTTP3 connections.
 ///
@@ -31,18 +34,28 @@ pub struct Http3Backend<F> {
     worker_tasks: usize,
     /// The service factory that will be used to create new services.
     service_factory: F,
-    /// The address to bind to.
+    /// The address to bind to or the UDP socket to accept connections on.
     ///
     /// Use `[::]` for a dual-stack listener.
     /// For example, use `[::]:80` to bind to port 80 on both IPv4 and IPv6.
-    bind: SocketAddr,
+    #[builder(into)]
+    bind: ListenerSocket,
+    /// Extensions that are added to every request, in addition to the ones provided by this crate.
+    #[builder(default)]
+    extensions: http::Extensions,
     /// rustls config.
     ///
     /// Use this field to set the server into TLS mode.
     /// It will only accept TLS connections when this is set.
     rustls_config: tokio_rustls::rustls::ServerConfig,
+    /// Connection limits, timeouts and shutdown behavior.
+    #[builder(default)]
+    connection_config: ConnectionConfig,
 }
 
+/// The `H3_NO_ERROR` error code, used to close connections that did not drain in time.
+const H3_NO_ERROR: h3_quinn::quinn::VarInt = h3_quinn::quinn::VarInt::from_u32(0x100);
+
 impl<F> Http3Backend<F>
 where
     F: HttpServiceFactory + Clone + Send + 'static,
@@ -64,10 +77,31 @@ where
         // not quite sure why this is necessary but it is
         self.rustls_config.max_early_data_size = u32::MAX;
         let crypto = h3_quinn::quinn::crypto::rustls::QuicServerConfig::try_from(self.rustls_config)?;
-        let server_config = h3_quinn::quinn::ServerConfig::with_crypto(Arc::new(crypto));
+        let mut server_config = h3_quinn::quinn::ServerConfig::with_crypto(Arc::new(crypto));
+
+        let mut transport_config = h3_quinn::quinn::TransportConfig::default();
+        // Durations that do not fit into a quic idle timeout are treated as no timeout
+        transport_config.max_idle_timeout(self.connection_config.idle_timeout.and_then(|t| t.try_into().ok()));
+        transport_config.keep_alive_interval(self.connection_config.keep_alive_interval);
+        if let Some(max_concurrent_streams) = self.connection_config.max_concurrent_streams {
+            transport_config.max_concurrent_bidi_streams(max_concurrent_streams.into());
+        }
+        server_config.transport_config(Arc::new(transport_config));
+
+        let connection_semaphore = self.connection_config.connection_semaphore();
 
         // Bind the UDP socket
-        let socket = std::net::UdpSocket::bind(self.bind)?;
+        let socket = match &self.bind {
+            ListenerSocket::Bind(addr) => std::net::UdpSocket::bind(addr)?,
+            ListenerSocket::Udp(socket) => socket.try_clone()?,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "http3 requires a udp socket or an address to bind to",
+                )
+                .into());
+            }
+        };
 
         // Runtime for the quinn endpoint
         let runtime = h3_quinn::quinn::default_runtime().ok_or_else(|| io::Error::other("no async runtime found"))?;
@@ -81,6 +115,9 @@ where
             let server_config = server_config.clone();
             let socket = socket.try_clone().expect("failed to clone socket");
             let runtime = Arc::clone(&runtime);
+            let connection_semaphore = Arc::clone(&connection_semaphore);
+            let connection_config = self.connection_config.clone();
+            let extensions = self.extensions.clone();
 
             let worker_fut = async move {
                 let endpoint = h3_quinn::quinn::Endpoint::new(
@@ -93,17 +130,41 @@ where
                 #[cfg(feature = "tracing")]
                 tracing::trace!("waiting for connections");
 
-                while let Some(Some(new_conn)) = endpoint.accept().with_context(&ctx).await {
+                loop {
+                    // Wait for a free connection slot before accepting new connections
+                    let Some(permit) = Arc::clone(&connection_semaphore).acquire_owned().with_context(&ctx).await else {
+                        break;
+                    };
+                    let permit = permit.expect("the connection semaphore is never closed");
+
+                    let Some(Some(new_conn)) = endpoint.accept().with_context(&ctx).await else {
+                        break;
+                    };
+
                     let mut service_factory = service_factory.clone();
                     let ctx = ctx.clone();
+                    let connection_config = connection_config.clone();
+                    let mut extra_extensions = extensions.clone();
 
                     tokio::spawn(async move {
+                        // Hold the connection slot until the connection is closed
+                        let _permit = permit;
+
                         let _res: Result<_, HttpError<F>> = async move {
-                            let Some(conn) = new_conn.with_context(&ctx).await.transpose()? else {
+                            let Some(conn) = tokio::time::timeout(connection_config.tls_handshake_timeout, new_conn)
+                                .with_context(&ctx)
+                                .await
+                            else {
                                 #[cfg(feature = "tracing")]
                                 tracing::trace!("context done while accepting connection");
                                 return Ok(());
                             };
+                            let Ok(conn) = conn else {
+                                #[cfg(feature = "tracing")]
+                                tracing::debug!("quic handshake timed out");
+                                return Ok(());
+                            };
+                            let conn = conn?;
                             let addr = conn.remote_address();
                             let client_certs = conn
                                 .peer_identity()
@@ -113,7 +174,18 @@ where
                             tracing::debug!(addr = %addr, "accepted quic connection");
 
                             let connection_fut = async move {
-                                let Some(mut h3_conn) = h3::server::Connection::new(h3_quinn::Connection::new(conn))
+                                // Keep a handle to close the connection if it does not drain in time
+                                let quic_conn = conn.clone();
+
+                                #[cfg_attr(not(feature = "webtransport"), allow(unused_mut))]
+                                let mut h3_builder = h3::server::builder();
+                                #[cfg(feature = "webtransport")]
+                                if let Some(webtransport) = &connection_config.webtransport {
+                                    webtransport.configure(&mut h3_builder);
+                                }
+
+                                let Some(mut h3_conn) = h3_builder
+                                    .build(h3_quinn::Connection::new(conn))
                                     .with_context(&ctx)
                                     .await
                                     .transpose()?
@@ -123,7 +195,13 @@ where
                                     return Ok(());
                                 };
 
-                                let mut extra_extensions = http::Extensions::new();
+                                #[cfg(feature = "webtransport")]
+                                let webtransport = connection_config.webtransport.clone().map(|config| {
+                                    let sessions = webtransport::Sessions::new(config, quic_conn.clone(), &h3_conn);
+                                    tokio::spawn(sessions.clone().route_datagrams());
+                                    sessions
+                                });
+
                                 extra_extensions.insert(crate::extensions::ClientAddr(addr));
                                 if let Some(certs) = client_certs {
                                     extra_extensions.insert(crate::extensions::ClientIdentity(Arc::new(*certs)));
@@ -135,35 +213,112 @@ where
                                     .await
                                     .map_err(|e| HttpError::ServiceFactoryError(e))?;
 
+                                // The in-flight request tasks, aborted if they do not finish within the drain timeout
+                                let mut requests = tokio::task::JoinSet::new();
+
                                 loop {
-                                    match h3_conn.accept().with_context(&ctx).await {
-                                        Some(Ok(Some(resolver))) => {
-                                            let (req, stream) = match resolver.resolve_request().await {
-                                                Ok(r) => r,
-                                                Err(_err) => {
+                                    // Reap the finished requests
+                                    while requests.try_join_next().is_some() {}
+
+                                    #[cfg(not(feature = "webtransport"))]
+                                    let accept = h3_conn.accept();
+                                    #[cfg(feature = "webtransport")]
+                                    let accept = async {
+                                        match &webtransport {
+                                            Some(sessions) => sessions.accept(&mut h3_conn).await,
+                                            None => h3_conn.accept().await,
+                                        }
+                                    };
+
+                                    let accepted = match accept.with_context(&ctx).await {
+                                        Some(accepted) => accepted,
+                                        // context is done
+                                        None => {
+                                            #[cfg(feature = "tracing")]
+                                            tracing::trace!("context done, shutting down connection gracefully");
+
+                                            // Sends a GOAWAY frame, in-flight requests are allowed to finish
+                                            h3_conn.shutdown(0).await?;
+                                            break;
+                                        }
+                                    };
+
+                                    match accepted {
+                                        Ok(Some(resolver)) => {
+                                            #[cfg(not(feature = "webtransport"))]
+                                            let resolve = resolver.resolve_request();
+                                            #[cfg(feature = "webtransport")]
+                                            let resolve = async {
+                                                match &webtransport {
+                                                    Some(sessions) => sessions.resolve(resolver).await,
+                                                    None => resolver.resolve_request().await.map(Some),
+                                                }
+                                            };
+
+                                            let resolved =
+                                                tokio::time::timeout(connection_config.header_read_timeout, resolve).await;
+                                            let resolved = match resolved {
+                                                Ok(Ok(r)) => r,
+                                                Ok(Err(_err)) => {
                                                     #[cfg(feature = "tracing")]
                                                     tracing::warn!("error on accept: {}", _err);
                                                     continue;
                                                 }
+                                                Err(_) => {
+                                                    #[cfg(feature = "tracing")]
+                                                    tracing::debug!("timed out reading request headers");
+                                                    continue;
+                                                }
                                             };
 
+                                            // Bidirectional WebTransport streams are routed to their session instead
+                                            #[cfg(feature = "webtransport")]
+                                            let Some(resolved) = resolved else {
+                                                continue;
+                                            };
+                                            #[cfg_attr(not(feature = "webtransport"), allow(unused_mut))]
+                                            let (mut req, stream) = resolved;
+
                                             #[cfg(feature = "tracing")]
                                             tracing::debug!(method = %req.method(), uri = %req.uri(), "received request");
 
+                                            #[cfg(feature = "webtransport")]
+                                            let pending_session = webtransport
+                                                .as_ref()
+                                                .and_then(|sessions| sessions.register(&mut req, stream.id()));
+
                                             let (mut send, recv) = stream.split();
 
                                             let size_hint = req
                                                 .headers()
                                                 .get(http::header::CONTENT_LENGTH)
                                                 .and_then(|len| len.to_str().ok().and_then(|x| x.parse().ok()));
-                                            let body = QuicIncomingBody::new(recv, size_hint);
-                                            let mut req = req.map(|_| crate::body::IncomingBody::from(body));
+
+                                            #[cfg(not(feature = "webtransport"))]
+                                            let body =
+                                                crate::body::IncomingBody::from(QuicIncomingBody::new(recv, size_hint));
+                                            // The CONNECT stream of a WebTransport session belongs to the session
+                                            #[cfg(feature = "webtransport")]
+                                            let (body, pending_session) = match pending_session {
+                                                Some(pending_session) => {
+                                                    (crate::body::IncomingBody::Empty, Some((pending_session, recv)))
+                                                }
+                                                None => (
+                                                    crate::body::IncomingBody::from(QuicIncomingBody::new(recv, size_hint)),
+                                                    None,
+                                                ),
+                                            };
+
+                                            let mut req = req.map(|_| body);
 
                                             req.extensions_mut().extend(extra_extensions.clone());
 
                                             let ctx = ctx.clone();
                                             let mut http_service = http_service.clone();
-                                            tokio::spawn(async move {
+                                            requests.spawn(async move {
+                                                #[cfg(feature = "webtransport")]
+                                                let ctx_ref = &ctx;
+
                                                 let _res: Result<_, HttpError<F>> = async move {
                                                     let resp = http_service
                                                         .call(req)
@@ -171,6 +326,20 @@ where
                                                         .map_err(|e| HttpError::ServiceError(e))?;
                                                     let (parts, body) = resp.into_parts();
 
+                                                    #[cfg(feature = "webtransport")]
+                                                    if let Some((pending_session, recv)) = pending_session {
+                                                        let established = parts.status.is_success();
+                                                        send.send_response(http::Response::from_parts(parts, ())).await?;
+
+                                                        if established {
+                                                            pending_session.establish(send, recv, ctx_ref).await;
+                                                        } else {
+                                                            send.finish().await?;
+                                                        }
+
+                                                        return Ok(());
+                                                    }
+
                                                     send.send_response(http::Response::from_parts(parts, ())).await?;
                                                     copy_response_body(send, body).await?;
 
@@ -188,19 +357,25 @@ where
                                             });
                                         }
                                         // indicating no more streams to be received
-                                        Some(Ok(None)) => {
-                                            break;
-                                        }
-                                        Some(Err(err)) => return Err(err.into()),
-                                        // context is done
-                                        None => {
-                                            #[cfg(feature = "tracing")]
-                                            tracing::trace!("context done, stopping connection loop");
+                                        Ok(None) => {
                                             break;
                                         }
+                                        Err(err) => return Err(err.into()),
                                     }
                                 }
 
+                                // Wait for the in-flight requests while the server is running
+                                while let Some(Some(_)) = requests.join_next().with_context(&ctx).await {}
+
+                                // Once it shuts down, the remaining requests are aborted after the drain timeout
+                                let drained = async { while requests.join_next().await.is_some() {} };
+                                if tokio::time::timeout(connection_config.drain_timeout, drained).await.is_err() {
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!("connection did not drain in time, aborting");
+                                    requests.abort_all();
+                                    quic_conn.close(H3_NO_ERROR, b"");
+                                }
+
                                 #[cfg(feature = "tracing")]
                                 tracing::trace!("connection closed");
 
@@ -222,8 +397,16 @@ where
                 }
 
                 // shut down gracefully
-                // wait for connections to be closed before exiting
-                endpoint.wait_idle().await;
+                // wait for connections to be closed before exiting, abort them if they do not drain in time
+                if tokio::time::timeout(connection_config.drain_timeout, endpoint.wait_idle())
+                    .await
+                    .is_err()
+                {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("connections did not drain in time, aborting");
+                    endpoint.close(H3_NO_ERROR, b"");
+                    endpoint.wait_idle().await;
+                }
 
                 Ok::<_, crate::error::HttpError<F>>(())
             };
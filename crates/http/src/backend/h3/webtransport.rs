@@ -0,0 +1,548 @@
+//! WebTransport sessions and HTTP/3 datagrams.
+//!
+//! When [`ConnectionConfig::webtransport`](crate::connection::ConnectionConfigBuilder::webtransport) is set,
+//! the HTTP/3 backend advertises support for WebTransport, extended CONNECT and HTTP/3 datagrams to its clients.
+//! Extended CONNECT requests with the `webtransport` protocol then carry a [`WebTransportUpgrade`] extension.
+//!
+//! A handler takes the upgrade from the request and responds with a `2xx` status to accept the session.
+//! The session becomes available once the response was sent and stays open until it is dropped,
+//! the client closes it or the server shuts down.
+//!
+//! ```rust,no_run
+//! # use scuffle_http::backend::h3::webtransport::WebTransportUpgrade;
+//! # use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//! async fn handle(mut req: scuffle_http::IncomingRequest) -> http::Response<String> {
+//!     let Some(upgrade) = req.extensions_mut().remove::<WebTransportUpgrade>() else {
+//!         return http::Response::builder().status(400).body(String::new()).unwrap();
+//!     };
+//!
+//!     tokio::spawn(async move {
+//!         let Ok(session) = upgrade.session().await else {
+//!             return;
+//!         };
+//!
+//!         // Echo every bidirectional stream
+//!         while let Some((mut send, mut recv)) = session.accept_bi().await {
+//!             let mut buf = Vec::new();
+//!             recv.read_to_end(&mut buf).await.unwrap();
+//!             send.write_all(&buf).await.unwrap();
+//!             send.shutdown().await.unwrap();
+//!         }
+//!     });
+//!
+//!     http::Response::new(String::new())
+//! }
+//! ```
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use h3::error::{ConnectionError, StreamError};
+use h3::ext::Protocol;
+use h3::frame::FrameStream;
+use h3::proto::frame::Frame;
+use h3::proto::varint::VarInt;
+use h3::quic::{BidiStream as _, OpenStreams, RecvStream as _, SendStream as _, StreamId};
+use h3::server::{RequestResolver, RequestStream};
+use h3::stream::{BidiStreamHeader, BufRecvStream, UniStreamHeader, WriteBuf};
+use h3::webtransport::SessionId;
+use tokio::sync::{mpsc, oneshot};
+
+/// The `H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED` error code, used to reject streams that cannot be buffered.
+const H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED: u64 = 0x3994bd84;
+
+type H3Connection = h3::server::Connection<h3_quinn::Connection, Bytes>;
+type H3Resolver = RequestResolver<h3_quinn::Connection, Bytes>;
+type H3RequestStream = RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>;
+
+/// WebTransport settings of HTTP/3 connections.
+///
+/// Use [`WebTransportConfig::builder`] to create a new configuration or [`WebTransportConfig::default`] for the defaults.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct WebTransportConfig {
+    /// The maximum number of concurrent sessions per connection.
+    ///
+    /// It is advertised to the client, extended CONNECT requests beyond it do not carry a [`WebTransportUpgrade`].
+    #[builder(default = 16)]
+    max_sessions: u64,
+    /// The maximum number of incoming streams per session that were not accepted yet.
+    ///
+    /// Streams beyond it, or for sessions that do not exist, are rejected.
+    #[builder(default = 32)]
+    max_pending_streams: usize,
+    /// The maximum number of incoming datagrams per session that were not read yet.
+    ///
+    /// Datagrams beyond it are dropped.
+    #[builder(default = 256)]
+    max_pending_datagrams: usize,
+}
+
+impl Default for WebTransportConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl WebTransportConfig {
+    /// Advertises WebTransport, extended CONNECT and HTTP/3 datagrams in the settings of the connection.
+    pub(crate) fn configure(&self, builder: &mut h3::server::Builder) {
+        builder
+            .enable_webtransport(true)
+            .enable_extended_connect(true)
+            .enable_datagram(true)
+            .max_webtransport_sessions(self.max_sessions);
+    }
+}
+
+/// An error that can occur on a WebTransport session.
+#[derive(Debug, thiserror::Error)]
+pub enum WebTransportError {
+    /// The session was not established.
+    ///
+    /// Either the response did not have a `2xx` status or the session was already taken from a clone of the upgrade.
+    #[error("webtransport session was not established")]
+    NotEstablished,
+    /// An error that occurred on a stream.
+    ///
+    /// Refer to [`h3::quic::StreamErrorIncoming`] for more information.
+    #[error("stream error: {0}")]
+    Stream(#[from] h3::quic::StreamErrorIncoming),
+    /// An error that occurred while sending a datagram.
+    ///
+    /// Refer to [`h3_quinn::quinn::SendDatagramError`] for more information.
+    #[error("send datagram error: {0}")]
+    SendDatagram(#[from] h3_quinn::quinn::SendDatagramError),
+}
+
+/// This extension is present on extended CONNECT requests with the `webtransport` protocol.
+///
+/// Take it from the request and call [`session`](WebTransportUpgrade::session) to wait for the session.
+#[derive(Clone)]
+pub struct WebTransportUpgrade {
+    session: Arc<Mutex<Option<oneshot::Receiver<WebTransportSession>>>>,
+}
+
+impl std::fmt::Debug for WebTransportUpgrade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebTransportUpgrade").finish_non_exhaustive()
+    }
+}
+
+impl WebTransportUpgrade {
+    /// Waits until the response was sent and returns the session.
+    ///
+    /// Returns [`WebTransportError::NotEstablished`] when the response did not have a `2xx` status.
+    pub async fn session(self) -> Result<WebTransportSession, WebTransportError> {
+        let session = self
+            .session
+            .lock()
+            .expect("webtransport upgrade lock poisoned")
+            .take()
+            .ok_or(WebTransportError::NotEstablished)?;
+
+        session.await.map_err(|_| WebTransportError::NotEstablished)
+    }
+}
+
+/// An established WebTransport session.
+///
+/// All methods take `&self`, so the session can be shared between tasks.
+/// Dropping the session closes it.
+pub struct WebTransportSession {
+    session_id: SessionId,
+    stream_id: u64,
+    datagram_prefix: Bytes,
+    connection: h3_quinn::quinn::Connection,
+    opener: h3_quinn::OpenStreams,
+    bi: tokio::sync::Mutex<mpsc::Receiver<(WebTransportSendStream, WebTransportRecvStream)>>,
+    uni: tokio::sync::Mutex<mpsc::Receiver<WebTransportRecvStream>>,
+    datagrams: tokio::sync::Mutex<mpsc::Receiver<Bytes>>,
+    // Dropping the sender tells the backend that the session was closed
+    _closed: oneshot::Sender<()>,
+}
+
+impl std::fmt::Debug for WebTransportSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebTransportSession")
+            .field("stream_id", &self.stream_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebTransportSession {
+    /// The id of the CONNECT stream that established this session.
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
+    /// Accepts the next bidirectional stream opened by the client.
+    ///
+    /// Returns `None` once the session is closed.
+    pub async fn accept_bi(&self) -> Option<(WebTransportSendStream, WebTransportRecvStream)> {
+        self.bi.lock().await.recv().await
+    }
+
+    /// Accepts the next unidirectional stream opened by the client.
+    ///
+    /// Returns `None` once the session is closed.
+    pub async fn accept_uni(&self) -> Option<WebTransportRecvStream> {
+        self.uni.lock().await.recv().await
+    }
+
+    /// Opens a new bidirectional stream.
+    pub async fn open_bi(&self) -> Result<(WebTransportSendStream, WebTransportRecvStream), WebTransportError> {
+        // Every clone opens its own streams, so concurrent calls do not interfere
+        let mut opener = self.opener.clone();
+        let stream = std::future::poll_fn(|cx| OpenStreams::<Bytes>::poll_open_bidi(&mut opener, cx)).await?;
+        let (mut send, recv) = BufRecvStream::new(stream).split();
+
+        send.send_data(WriteBuf::from(BidiStreamHeader::WebTransportBidi(self.session_id)))?;
+        std::future::poll_fn(|cx| send.poll_ready(cx)).await?;
+
+        Ok((WebTransportSendStream(send), WebTransportRecvStream(recv)))
+    }
+
+    /// Opens a new unidirectional stream.
+    pub async fn open_uni(&self) -> Result<WebTransportSendStream, WebTransportError> {
+        let mut opener = self.opener.clone();
+        let stream = std::future::poll_fn(|cx| OpenStreams::<Bytes>::poll_open_send(&mut opener, cx)).await?;
+        let mut send = BufRecvStream::new(stream);
+
+        send.send_data(WriteBuf::from(UniStreamHeader::WebTransportUni(self.session_id)))?;
+        std::future::poll_fn(|cx| send.poll_ready(cx)).await?;
+
+        Ok(WebTransportSendStream(send))
+    }
+
+    /// Sends a datagram to the client.
+    ///
+    /// Datagrams are unreliable, they can be lost or arrive out of order.
+    /// The payload must not exceed [`max_datagram_size`](WebTransportSession::max_datagram_size).
+    pub fn send_datagram(&self, payload: Bytes) -> Result<(), WebTransportError> {
+        let mut datagram = BytesMut::with_capacity(self.datagram_prefix.len() + payload.len());
+        datagram.put_slice(&self.datagram_prefix);
+        datagram.put(payload);
+
+        self.connection.send_datagram(datagram.freeze())?;
+
+        Ok(())
+    }
+
+    /// Reads the next datagram sent by the client.
+    ///
+    /// Returns `None` once the session is closed.
+    pub async fn read_datagram(&self) -> Option<Bytes> {
+        self.datagrams.lock().await.recv().await
+    }
+
+    /// The maximum payload size of a datagram, or `None` if the client does not accept datagrams.
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection
+            .max_datagram_size()
+            .map(|size| size.saturating_sub(self.datagram_prefix.len()))
+    }
+}
+
+/// The sending side of a WebTransport stream.
+///
+/// Implements [`tokio::io::AsyncWrite`], shutting it down finishes the stream.
+pub struct WebTransportSendStream(BufRecvStream<h3_quinn::SendStream<Bytes>, Bytes>);
+
+impl std::fmt::Debug for WebTransportSendStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("WebTransportSendStream").field(&self.0).finish()
+    }
+}
+
+impl WebTransportSendStream {
+    /// Abruptly closes the stream with the given error code.
+    pub fn reset(&mut self, error_code: u64) {
+        self.0.reset(error_code);
+    }
+}
+
+impl tokio::io::AsyncWrite for WebTransportSendStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+    }
+}
+
+/// The receiving side of a WebTransport stream.
+///
+/// Implements [`tokio::io::AsyncRead`].
+pub struct WebTransportRecvStream(BufRecvStream<h3_quinn::RecvStream, Bytes>);
+
+impl std::fmt::Debug for WebTransportRecvStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("WebTransportRecvStream").field(&self.0).finish()
+    }
+}
+
+impl WebTransportRecvStream {
+    /// Asks the client to stop sending on this stream with the given error code.
+    pub fn stop_sending(&mut self, error_code: u64) {
+        self.0.stop_sending(error_code);
+    }
+}
+
+impl tokio::io::AsyncRead for WebTransportRecvStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        tokio::io::AsyncRead::poll_read(Pin::new(&mut self.0), cx, buf)
+    }
+}
+
+/// The channels incoming streams and datagrams are routed to.
+struct SessionChannels {
+    bi: mpsc::Sender<(WebTransportSendStream, WebTransportRecvStream)>,
+    uni: mpsc::Sender<WebTransportRecvStream>,
+    datagrams: mpsc::Sender<Bytes>,
+}
+
+/// The WebTransport sessions of a single connection.
+#[derive(Clone)]
+pub(crate) struct Sessions {
+    config: WebTransportConfig,
+    connection: h3_quinn::quinn::Connection,
+    opener: h3_quinn::OpenStreams,
+    sessions: Arc<Mutex<HashMap<SessionId, SessionChannels>>>,
+}
+
+impl Sessions {
+    pub(crate) fn new(config: WebTransportConfig, connection: h3_quinn::quinn::Connection, conn: &H3Connection) -> Self {
+        Self {
+            config,
+            connection,
+            opener: h3::quic::Connection::<Bytes>::opener(&conn.inner.conn),
+            sessions: Arc::default(),
+        }
+    }
+
+    /// Accepts the next request stream, like [`h3::server::Connection::accept`].
+    ///
+    /// Unidirectional WebTransport streams that were received in the meantime are routed to their session.
+    pub(crate) async fn accept(&self, conn: &mut H3Connection) -> Result<Option<H3Resolver>, ConnectionError> {
+        let stream = std::future::poll_fn(|cx| {
+            let res = conn.poll_accept_request_stream(cx);
+
+            for (session_id, stream) in conn.inner.accepted_streams_mut().wt_uni_streams.drain(..) {
+                self.route_uni(session_id, WebTransportRecvStream(stream));
+            }
+
+            res
+        })
+        .await?;
+
+        let Some(stream) = stream else {
+            conn.shutdown(0).await?;
+            return Ok(None);
+        };
+
+        let resolver = conn.create_resolver(FrameStream::new(BufRecvStream::new(stream)));
+
+        // send the grease frame only once
+        conn.inner.send_grease_frame = false;
+
+        Ok(Some(resolver))
+    }
+
+    /// Reads the request headers, like [`RequestResolver::resolve_request`].
+    ///
+    /// Returns `None` when the stream turns out to be a bidirectional WebTransport stream, it is routed to its session.
+    pub(crate) async fn resolve(
+        &self,
+        mut resolver: H3Resolver,
+    ) -> Result<Option<(http::Request<()>, H3RequestStream)>, StreamError> {
+        let frame = std::future::poll_fn(|cx| resolver.frame_stream.poll_next(cx)).await;
+
+        if let Ok(Some(Frame::WebTransportStream(session_id))) = frame {
+            let (send, recv) = resolver.frame_stream.into_inner().split();
+            self.route_bi(session_id, WebTransportSendStream(send), WebTransportRecvStream(recv));
+            return Ok(None);
+        }
+
+        resolver.accept_with_frame(frame)?.resolve().await.map(Some)
+    }
+
+    fn route_bi(&self, session_id: SessionId, send: WebTransportSendStream, recv: WebTransportRecvStream) {
+        let rejected = match self.lock().get(&session_id) {
+            Some(channels) => channels.bi.try_send((send, recv)).err().map(|err| err.into_inner()),
+            None => Some((send, recv)),
+        };
+
+        if let Some((mut send, mut recv)) = rejected {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?session_id, "rejecting bidirectional webtransport stream");
+            send.reset(H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED);
+            recv.stop_sending(H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED);
+        }
+    }
+
+    fn route_uni(&self, session_id: SessionId, recv: WebTransportRecvStream) {
+        let rejected = match self.lock().get(&session_id) {
+            Some(channels) => channels.uni.try_send(recv).err().map(|err| err.into_inner()),
+            None => Some(recv),
+        };
+
+        if let Some(mut recv) = rejected {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?session_id, "rejecting unidirectional webtransport stream");
+            recv.stop_sending(H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED);
+        }
+    }
+
+    /// Routes incoming datagrams to their session until the connection is closed.
+    pub(crate) async fn route_datagrams(self) {
+        while let Ok(mut datagram) = self.connection.read_datagram().await {
+            // Datagrams start with the quarter stream id of the CONNECT stream
+            let Ok(quarter_stream_id) = VarInt::decode(&mut datagram) else {
+                continue;
+            };
+            let Ok(session_id) = SessionId::try_from(quarter_stream_id.into_inner() << 2) else {
+                continue;
+            };
+
+            if let Some(channels) = self.lock().get(&session_id) {
+                // Datagrams are unreliable, drop them when the session does not keep up
+                let _ = channels.datagrams.try_send(datagram);
+            }
+        }
+    }
+
+    /// Registers a session for an extended CONNECT request with the `webtransport` protocol
+    /// and adds the [`WebTransportUpgrade`] extension to the request.
+    ///
+    /// Returns `None` for any other request or when the connection already has the maximum number of sessions.
+    pub(crate) fn register(&self, req: &mut http::Request<()>, stream_id: StreamId) -> Option<PendingSession> {
+        if req.method() != http::Method::CONNECT || req.extensions().get::<Protocol>() != Some(&Protocol::WEB_TRANSPORT) {
+            return None;
+        }
+
+        let stream_id = stream_id.into_inner();
+        let session_id = SessionId::try_from(stream_id).ok()?;
+
+        let mut sessions = self.lock();
+        if sessions.len() as u64 >= self.config.max_sessions {
+            return None;
+        }
+
+        let (bi_tx, bi_rx) = mpsc::channel(self.config.max_pending_streams.max(1));
+        let (uni_tx, uni_rx) = mpsc::channel(self.config.max_pending_streams.max(1));
+        let (datagrams_tx, datagrams_rx) = mpsc::channel(self.config.max_pending_datagrams.max(1));
+
+        sessions.insert(
+            session_id,
+            SessionChannels {
+                bi: bi_tx,
+                uni: uni_tx,
+                datagrams: datagrams_tx,
+            },
+        );
+        drop(sessions);
+
+        let mut datagram_prefix = BytesMut::new();
+        VarInt::from_u64(stream_id >> 2)
+            .expect("stream ids are valid varints")
+            .encode(&mut datagram_prefix);
+
+        let (closed_tx, closed_rx) = oneshot::channel();
+        let (upgrade_tx, upgrade_rx) = oneshot::channel();
+
+        req.extensions_mut().insert(WebTransportUpgrade {
+            session: Arc::new(Mutex::new(Some(upgrade_rx))),
+        });
+
+        Some(PendingSession {
+            guard: SessionGuard {
+                sessions: self.clone(),
+                session_id,
+            },
+            session: WebTransportSession {
+                session_id,
+                stream_id,
+                datagram_prefix: datagram_prefix.freeze(),
+                connection: self.connection.clone(),
+                opener: self.opener.clone(),
+                bi: tokio::sync::Mutex::new(bi_rx),
+                uni: tokio::sync::Mutex::new(uni_rx),
+                datagrams: tokio::sync::Mutex::new(datagrams_rx),
+                _closed: closed_tx,
+            },
+            upgrade: upgrade_tx,
+            closed: closed_rx,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<SessionId, SessionChannels>> {
+        self.sessions.lock().expect("webtransport sessions lock poisoned")
+    }
+}
+
+/// Removes a session from its connection when dropped, which closes its channels.
+struct SessionGuard {
+    sessions: Sessions,
+    session_id: SessionId,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.lock().remove(&self.session_id);
+    }
+}
+
+/// A session of a WebTransport request that is still waiting for the response.
+///
+/// The session is removed again when this is dropped before it was established.
+pub(crate) struct PendingSession {
+    guard: SessionGuard,
+    session: WebTransportSession,
+    upgrade: oneshot::Sender<WebTransportSession>,
+    closed: oneshot::Receiver<()>,
+}
+
+impl PendingSession {
+    /// Hands the session to the [`WebTransportUpgrade`] after a `2xx` response was sent
+    /// and keeps the CONNECT stream open until the session is closed.
+    pub(crate) async fn establish(
+        self,
+        mut send: RequestStream<h3_quinn::SendStream<Bytes>, Bytes>,
+        mut recv: RequestStream<h3_quinn::RecvStream, Bytes>,
+        ctx: &scuffle_context::Context,
+    ) {
+        let PendingSession {
+            guard,
+            session,
+            upgrade,
+            closed,
+        } = self;
+
+        // Fails when the handler dropped the upgrade, the session is closed right away
+        if upgrade.send(session).is_ok() {
+            // The client closes the session by closing the CONNECT stream, data on it is not used
+            let client_closed = std::pin::pin!(async {
+                while let Ok(Some(mut data)) = recv.recv_data().await {
+                    data.advance(data.remaining());
+                }
+            });
+            let shutdown = std::pin::pin!(ctx.done());
+
+            futures::future::select(closed, futures::future::select(client_closed, shutdown)).await;
+        }
+
+        drop(guard);
+        let _ = send.finish().await;
+    }
+}
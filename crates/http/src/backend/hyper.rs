@@ -1,16 +1,19 @@
 //! Hyper backend.
 use std::fmt::Debug;
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 use scuffle_context::ContextFutExt;
 #[cfg(feature = "tracing")]
 use tracing::Instrument;
 
+use crate::connection::ConnectionConfig;
 use crate::error::HttpError;
+use crate::listener::ListenerSocket;
 use crate::service::{HttpService, HttpServiceFactory};
 
+mod acceptor;
 mod handler;
+mod idle;
 mod stream;
 mod utils;
 
@@ -29,11 +32,15 @@ pub struct HyperBackend<F> {
     worker_tasks: usize,
     /// The service factory that will be used to create new services.
     service_factory: F,
-    /// The address to bind to.
+    /// The address to bind to or the socket to accept connections on.
     ///
     /// Use `[::]` for a dual-stack listener.
     /// For example, use `[::]:80` to bind to port 80 on both IPv4 and IPv6.
-    bind: SocketAddr,
+    #[builder(into)]
+    bind: ListenerSocket,
+    /// Extensions that are added to every request, in addition to the ones provided by this crate.
+    #[builder(default)]
+    extensions: http::Extensions,
     /// rustls config.
     ///
     /// Use this field to set the server into TLS mode.
@@ -48,6 +55,9 @@ pub struct HyperBackend<F> {
     #[cfg(feature = "http2")]
     #[builder(default = true)]
     http2_enabled: bool,
+    /// Connection limits, timeouts and shutdown behavior.
+    #[builder(default)]
+    connection_config: ConnectionConfig,
 }
 
 impl<F> HyperBackend<F>
@@ -76,14 +86,24 @@ where
             rustls_config.max_early_data_size = 0;
         }
 
-        // We have to create an std listener first because the tokio listener isn't clonable
-        let listener = tokio::net::TcpListener::bind(self.bind).await?.into_std()?;
+        #[cfg(feature = "tls-rustls")]
+        if self.rustls_config.is_some() && self.bind.is_unix() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "tls is not supported on unix domain sockets",
+            )
+            .into());
+        }
+
+        let listener = acceptor::StdAcceptor::bind(&self.bind).await?;
 
         #[cfg(feature = "tls-rustls")]
         let tls_acceptor = self
             .rustls_config
             .map(|c| tokio_rustls::TlsAcceptor::from(std::sync::Arc::new(c)));
 
+        let connection_semaphore = self.connection_config.connection_semaphore();
+
         // Create a child context for the workers so we can shut them down if one of them fails without shutting down the main context
         let (worker_ctx, worker_handler) = self.ctx.new_child();
 
@@ -91,18 +111,28 @@ where
             .map(|_n| {
                 let service_factory = self.service_factory.clone();
                 let ctx = worker_ctx.clone();
-                let std_listener = listener.try_clone()?;
-                let listener = tokio::net::TcpListener::from_std(std_listener)?;
+                let listener = listener.try_clone()?;
                 #[cfg(feature = "tls-rustls")]
                 let tls_acceptor = tls_acceptor.clone();
+                let connection_semaphore = Arc::clone(&connection_semaphore);
+                let connection_config = self.connection_config.clone();
+                let extensions = self.extensions.clone();
 
                 let worker_fut = async move {
                     loop {
+                        // Wait for a free connection slot before accepting, this leaves pending connections in the backlog
+                        let Some(permit) = Arc::clone(&connection_semaphore).acquire_owned().with_context(&ctx).await else {
+                            #[cfg(feature = "tracing")]
+                            tracing::trace!("context done, stopping listener");
+                            break;
+                        };
+                        let permit = permit.expect("the connection semaphore is never closed");
+
                         #[cfg(feature = "tracing")]
                         tracing::trace!("waiting for connections");
 
                         let (mut stream, addr) = match listener.accept().with_context(ctx.clone()).await {
-                            Some(Ok((tcp_stream, addr))) => (stream::Stream::Tcp(tcp_stream), addr),
+                            Some(Ok(accepted)) => accepted,
                             Some(Err(e)) if utils::is_fatal_tcp_error(&e) => {
                                 #[cfg(feature = "tracing")]
                                 tracing::error!(err = %e, "failed to accept tcp connection");
@@ -117,27 +147,67 @@ where
                         };
 
                         #[cfg(feature = "tracing")]
-                        tracing::trace!(addr = %addr, "accepted tcp connection");
+                        tracing::trace!(addr = %addr, "accepted connection");
 
                         let ctx = ctx.clone();
                         #[cfg(feature = "tls-rustls")]
                         let tls_acceptor = tls_acceptor.clone();
                         let mut service_factory = service_factory.clone();
+                        let connection_config = connection_config.clone();
+                        let mut extra_extensions = extensions.clone();
 
                         let connection_fut = async move {
+                            // Hold the connection slot until the connection is closed
+                            let _permit = permit;
+
+                            // The PROXY protocol header is sent by the load balancer before the TLS handshake
+                            #[cfg(feature = "proxy-protocol")]
+                            let (addr, proxy_header) = match &connection_config.proxy_protocol {
+                                Some(proxy_protocol) => {
+                                    match proxy_protocol.accept(&mut stream, addr).with_context(&ctx).await {
+                                        Some(Ok(res)) => res,
+                                        Some(Err(_err)) => {
+                                            #[cfg(feature = "tracing")]
+                                            tracing::warn!(err = %_err, "failed to read proxy protocol header");
+                                            return;
+                                        }
+                                        None => {
+                                            #[cfg(feature = "tracing")]
+                                            tracing::trace!("context done, stopping proxy protocol acceptor");
+                                            return;
+                                        }
+                                    }
+                                }
+                                None => (addr, None),
+                            };
+
+                            #[cfg(all(feature = "proxy-protocol", feature = "tracing"))]
+                            if proxy_header.is_some() {
+                                tracing::trace!(client_addr = %addr, "read proxy protocol header");
+                            }
+
                             // Perform the TLS handshake if the acceptor is set
                             #[cfg(feature = "tls-rustls")]
                             if let Some(tls_acceptor) = tls_acceptor {
                                 #[cfg(feature = "tracing")]
                                 tracing::trace!("accepting tls connection");
 
-                                stream = match stream.try_accept_tls(&tls_acceptor).with_context(&ctx).await {
-                                    Some(Ok(stream)) => stream,
-                                    Some(Err(_err)) => {
+                                let handshake = stream.try_accept_tls(&tls_acceptor);
+                                stream = match tokio::time::timeout(connection_config.tls_handshake_timeout, handshake)
+                                    .with_context(&ctx)
+                                    .await
+                                {
+                                    Some(Ok(Ok(stream))) => stream,
+                                    Some(Ok(Err(_err))) => {
                                         #[cfg(feature = "tracing")]
                                         tracing::warn!(err = %_err, "failed to accept tls connection");
                                         return;
                                     }
+                                    Some(Err(_)) => {
+                                        #[cfg(feature = "tracing")]
+                                        tracing::debug!("tls handshake timed out");
+                                        return;
+                                    }
                                     None => {
                                         #[cfg(feature = "tracing")]
                                         tracing::trace!("context done, stopping tls acceptor");
@@ -147,9 +217,17 @@ where
 
                                 #[cfg(feature = "tracing")]
                                 tracing::trace!("accepted tls connection");
+
+                                // TLS-ALPN-01 challenges (RFC 8737) are answered by the certificate of the handshake
+                                if stream.alpn_protocol() == Some(crate::tls::ACME_TLS_ALPN_NAME) {
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!("answered acme tls-alpn-01 challenge");
+
+                                    let _ = tokio::io::AsyncWriteExt::shutdown(&mut stream).await;
+                                    return;
+                                }
                             }
 
-                            let mut extra_extensions = http::Extensions::new();
                             extra_extensions.insert(crate::extensions::ClientAddr(addr));
 
                             #[cfg(feature = "tls-rustls")]
@@ -157,6 +235,11 @@ where
                                 extra_extensions.insert(crate::extensions::ClientIdentity(Arc::new(certs.to_vec())));
                             }
 
+                            #[cfg(feature = "proxy-protocol")]
+                            if let Some(proxy_header) = proxy_header {
+                                extra_extensions.insert(crate::extensions::ProxyHeader(Arc::new(proxy_header)));
+                            }
+
                             // make a new service
                             let http_service = match service_factory.new_service(addr).await {
                                 Ok(service) => service,
@@ -187,6 +270,7 @@ where
                                 stream,
                                 http1,
                                 http2,
+                                &connection_config,
                             )
                             .await;
 
@@ -218,14 +302,12 @@ where
             })
             .collect::<std::io::Result<Vec<_>>>()?;
 
+        let mut result = Ok(());
+
         match futures::future::try_join_all(workers).await {
             Ok(res) => {
-                for r in res {
-                    if let Err(e) = r {
-                        drop(worker_ctx);
-                        worker_handler.shutdown().await;
-                        return Err(e);
-                    }
+                if let Some(Err(e)) = res.into_iter().find(Result::is_err) {
+                    result = Err(e);
                 }
             }
             Err(_e) => {
@@ -237,9 +319,15 @@ where
         drop(worker_ctx);
         worker_handler.shutdown().await;
 
+        // Remove the socket file we created, a new listener cannot bind to the path otherwise
+        #[cfg(unix)]
+        if let ListenerSocket::BindUnix(path) = &self.bind {
+            let _ = std::fs::remove_file(path);
+        }
+
         #[cfg(feature = "tracing")]
         tracing::debug!("all workers finished");
 
-        Ok(())
+        result
     }
 }
@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+
+use super::stream::Stream;
+use crate::listener::ListenerSocket;
+
+/// The address reported for clients connected over a Unix domain socket, they do not have an ip address.
+#[cfg(unix)]
+pub(crate) const UNIX_CLIENT_ADDR: SocketAddr =
+    SocketAddr::V4(std::net::SocketAddrV4::new(std::net::Ipv4Addr::UNSPECIFIED, 0));
+
+/// A bound listener that can be cloned for every worker.
+pub(crate) enum StdAcceptor {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl StdAcceptor {
+    /// Binds or clones the socket of a listener.
+    pub(crate) async fn bind(socket: &ListenerSocket) -> std::io::Result<Self> {
+        match socket {
+            // We have to create an std listener first because the tokio listener isn't clonable
+            ListenerSocket::Bind(addr) => Ok(Self::Tcp(tokio::net::TcpListener::bind(addr).await?.into_std()?)),
+            #[cfg(unix)]
+            ListenerSocket::BindUnix(path) => Ok(Self::Unix(std::os::unix::net::UnixListener::bind(path)?)),
+            ListenerSocket::Tcp(listener) => Ok(Self::Tcp(listener.try_clone()?)),
+            #[cfg(unix)]
+            ListenerSocket::Unix(listener) => Ok(Self::Unix(listener.try_clone()?)),
+            ListenerSocket::Udp(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "udp sockets can only be used for http3",
+            )),
+        }
+    }
+
+    /// Creates a new tokio listener for the same socket.
+    pub(crate) fn try_clone(&self) -> std::io::Result<Acceptor> {
+        match self {
+            Self::Tcp(listener) => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                Ok(Acceptor::Tcp(tokio::net::TcpListener::from_std(listener)?))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                Ok(Acceptor::Unix(tokio::net::UnixListener::from_std(listener)?))
+            }
+        }
+    }
+}
+
+/// A listener of a worker.
+pub(crate) enum Acceptor {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Acceptor {
+    /// Accepts a new connection.
+    ///
+    /// Connections over Unix domain sockets are reported with the [`UNIX_CLIENT_ADDR`].
+    pub(crate) async fn accept(&self) -> std::io::Result<(Stream, SocketAddr)> {
+        match self {
+            Self::Tcp(listener) => listener.accept().await.map(|(stream, addr)| (Stream::Tcp(stream), addr)),
+            #[cfg(unix)]
+            Self::Unix(listener) => listener
+                .accept()
+                .await
+                .map(|(stream, _)| (Stream::Unix(stream), UNIX_CLIENT_ADDR)),
+        }
+    }
+}
@@ -1,12 +1,16 @@
 use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
 use hyper_util::server::conn::auto;
-use scuffle_context::ContextFutExt;
 use tokio::io::{AsyncRead, AsyncWrite};
 
+use super::idle::{IdleIo, IdleTracker};
+use crate::connection::ConnectionConfig;
 use crate::error::HttpError;
 use crate::service::{HttpService, HttpServiceFactory};
 
 /// Helper function used by hyper server to handle incoming connections.
+///
+/// When the context is done, the connection is shut down gracefully and given
+/// [`drain_timeout`](ConnectionConfig::drain_timeout) to finish in-flight requests before it is aborted.
 pub(crate) async fn handle_connection<F, S, I>(
     ctx: scuffle_context::Context,
     service: S,
@@ -14,6 +18,7 @@ pub(crate) async fn handle_connection<F, S, I>(
     io: I,
     http1: bool,
     http2: bool,
+    config: &ConnectionConfig,
 ) -> Result<(), HttpError<F>>
 where
     F: HttpServiceFactory<Service = S>,
@@ -25,7 +30,8 @@ where
     <S::ResBody as http_body::Body>::Error: std::error::Error + Send + Sync,
     I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
 {
-    let io = TokioIo::new(io);
+    let idle_tracker = IdleTracker::new();
+    let io = TokioIo::new(IdleIo::new(io, idle_tracker.clone()));
 
     let hyper_proxy_service = hyper::service::service_fn(move |req: http::Request<hyper::body::Incoming>| {
         let mut service = service.clone();
@@ -43,47 +49,67 @@ where
 
     let mut builder = auto::Builder::new(TokioExecutor::new());
 
-    if http1 && http2 {
-        #[cfg(feature = "http1")]
-        builder.http1().timer(TokioTimer::new());
+    #[cfg(feature = "http1")]
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(config.header_read_timeout);
 
-        #[cfg(feature = "http2")]
-        builder.http2().timer(TokioTimer::new());
-
-        builder
-            .serve_connection_with_upgrades(io, hyper_proxy_service)
-            .with_context(ctx)
-            .await
-            .transpose()
-            .map_err(HttpError::HyperConnection)?;
-    } else if http1 {
-        #[cfg(not(feature = "http1"))]
-        unreachable!("http1 enabled but http1 feature disabled");
+    #[cfg(feature = "http2")]
+    {
+        let mut http2 = builder.http2();
+        http2
+            .timer(TokioTimer::new())
+            .keep_alive_interval(config.keep_alive_interval)
+            .keep_alive_timeout(config.keep_alive_timeout);
 
-        #[cfg(feature = "http1")]
-        builder
-            .http1_only()
-            .serve_connection_with_upgrades(io, hyper_proxy_service)
-            .with_context(ctx)
-            .await
-            .transpose()
-            .map_err(HttpError::HyperConnection)?;
-    } else if http2 {
-        #[cfg(not(feature = "http2"))]
-        unreachable!("http2 enabled but http2 feature disabled");
+        if let Some(max_concurrent_streams) = config.max_concurrent_streams {
+            http2.max_concurrent_streams(max_concurrent_streams);
+        }
+    }
 
+    let builder = match (http1, http2) {
+        (true, true) => builder,
+        #[cfg(feature = "http1")]
+        (true, false) => builder.http1_only(),
         #[cfg(feature = "http2")]
-        builder
-            .http2_only()
-            .serve_connection_with_upgrades(io, hyper_proxy_service)
-            .with_context(ctx)
-            .await
-            .transpose()
-            .map_err(HttpError::HyperConnection)?;
-    } else {
-        #[cfg(feature = "tracing")]
-        tracing::warn!("both http1 and http2 are disabled, closing connection");
+        (false, true) => builder.http2_only(),
+        _ => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("both http1 and http2 are disabled, closing connection");
+            return Ok(());
+        }
+    };
+
+    let mut conn = std::pin::pin!(builder.serve_connection_with_upgrades(io, hyper_proxy_service));
+
+    let shutdown = std::pin::pin!(async {
+        match config.idle_timeout {
+            Some(idle_timeout) => {
+                let idle = std::pin::pin!(idle_tracker.idle(idle_timeout));
+                let done = std::pin::pin!(ctx.done());
+                futures::future::select(idle, done).await;
+            }
+            None => ctx.done().await,
+        }
+    });
+
+    if let futures::future::Either::Left((res, _)) = futures::future::select(conn.as_mut(), shutdown).await {
+        return res.map_err(HttpError::HyperConnection);
     }
 
-    Ok(())
+    #[cfg(feature = "tracing")]
+    tracing::trace!("shutting down connection gracefully");
+
+    // Sends a GOAWAY frame for http2 and disables keep-alive for http1
+    conn.as_mut().graceful_shutdown();
+
+    match tokio::time::timeout(config.drain_timeout, conn).await {
+        Ok(res) => res.map_err(HttpError::HyperConnection),
+        Err(_) => {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("connection did not drain in time, aborting");
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+/// Keeps track of the last time data was read from or written to a connection.
+#[derive(Debug)]
+pub(crate) struct IdleTracker {
+    start: Instant,
+    /// Milliseconds since `start`.
+    last_activity: AtomicU64,
+}
+
+impl IdleTracker {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            start: Instant::now(),
+            last_activity: AtomicU64::new(0),
+        })
+    }
+
+    fn touch(&self) {
+        self.last_activity
+            .store(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn last_activity(&self) -> Instant {
+        self.start + Duration::from_millis(self.last_activity.load(Ordering::Relaxed))
+    }
+
+    /// Resolves once no data was read or written for `timeout`.
+    pub(crate) async fn idle(&self, timeout: Duration) {
+        loop {
+            let deadline = self.last_activity() + timeout;
+            if deadline <= Instant::now() {
+                return;
+            }
+
+            tokio::time::sleep_until(deadline).await;
+        }
+    }
+}
+
+/// Wraps an io and records every successful read or write in an [`IdleTracker`].
+pub(crate) struct IdleIo<I> {
+    inner: I,
+    tracker: Arc<IdleTracker>,
+}
+
+impl<I> IdleIo<I> {
+    pub(crate) fn new(inner: I, tracker: Arc<IdleTracker>) -> Self {
+        Self { inner, tracker }
+    }
+
+    fn track<T, E>(&self, poll: std::task::Poll<Result<T, E>>) -> std::task::Poll<Result<T, E>> {
+        if let std::task::Poll::Ready(Ok(_)) = &poll {
+            self.tracker.touch();
+        }
+
+        poll
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for IdleIo<I> {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let poll = std::pin::Pin::new(&mut this.inner).poll_read(cx, buf);
+        this.track(poll)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for IdleIo<I> {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        let poll = std::pin::Pin::new(&mut this.inner).poll_write(cx, buf);
+        this.track(poll)
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        let poll = std::pin::Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        this.track(poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
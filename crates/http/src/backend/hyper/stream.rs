@@ -1,10 +1,12 @@
 use tokio::io::{AsyncRead, AsyncWrite};
 
-/// A stream that can be either a TCP stream or a TLS stream.
+/// A stream that can be either a TCP stream, a Unix domain socket stream or a TLS stream.
 ///
 /// Implements [`AsyncRead`] and [`AsyncWrite`] by delegating to the inner stream.
 pub(crate) enum Stream {
     Tcp(tokio::net::TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
     #[cfg(feature = "tls-rustls")]
     Tls(Box<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>),
 }
@@ -20,10 +22,26 @@ impl Stream {
                 let stream = tls_acceptor.accept(stream).await?;
                 Ok(Self::Tls(Box::new(stream)))
             }
+            #[cfg(unix)]
+            Stream::Unix(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "tls is not supported on unix domain sockets",
+            )),
             Stream::Tls(_) => Ok(self),
         }
     }
 
+    /// Get the ALPN protocol negotiated during the TLS handshake.
+    #[cfg(feature = "tls-rustls")]
+    pub(crate) fn alpn_protocol(&self) -> Option<&[u8]> {
+        match self {
+            Stream::Tcp(_) => None,
+            #[cfg(unix)]
+            Stream::Unix(_) => None,
+            Stream::Tls(stream) => stream.get_ref().1.alpn_protocol(),
+        }
+    }
+
     /// Get the client certificates from the TLS stream.
     ///
     /// Returns `None` if the stream is not a TLS stream or if no client certificates are present.
@@ -31,6 +49,8 @@ impl Stream {
     pub(crate) fn get_client_certs(&self) -> Option<&[tokio_rustls::rustls::pki_types::CertificateDer<'static>]> {
         match self {
             Stream::Tcp(_) => None,
+            #[cfg(unix)]
+            Stream::Unix(_) => None,
             Stream::Tls(stream) => stream.get_ref().1.peer_certificates(),
         }
     }
@@ -44,6 +64,8 @@ impl AsyncRead for Stream {
     ) -> std::task::Poll<std::io::Result<()>> {
         match self.get_mut() {
             Stream::Tcp(stream) => std::pin::Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => std::pin::Pin::new(stream).poll_read(cx, buf),
             #[cfg(feature = "tls-rustls")]
             Stream::Tls(stream) => std::pin::Pin::new(stream).poll_read(cx, buf),
         }
@@ -58,6 +80,8 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<Result<usize, std::io::Error>> {
         match self.get_mut() {
             Stream::Tcp(stream) => std::pin::Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => std::pin::Pin::new(stream).poll_write(cx, buf),
             #[cfg(feature = "tls-rustls")]
             Stream::Tls(stream) => std::pin::Pin::new(stream).poll_write(cx, buf),
         }
@@ -69,6 +93,8 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<Result<(), std::io::Error>> {
         match self.get_mut() {
             Stream::Tcp(stream) => std::pin::Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => std::pin::Pin::new(stream).poll_flush(cx),
             #[cfg(feature = "tls-rustls")]
             Stream::Tls(stream) => std::pin::Pin::new(stream).poll_flush(cx),
         }
@@ -80,6 +106,8 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<Result<(), std::io::Error>> {
         match self.get_mut() {
             Stream::Tcp(stream) => std::pin::Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => std::pin::Pin::new(stream).poll_shutdown(cx),
             #[cfg(feature = "tls-rustls")]
             Stream::Tls(stream) => std::pin::Pin::new(stream).poll_shutdown(cx),
         }
@@ -92,6 +120,8 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<Result<usize, std::io::Error>> {
         match self.get_mut() {
             Stream::Tcp(stream) => std::pin::Pin::new(stream).poll_write_vectored(cx, bufs),
+            #[cfg(unix)]
+            Stream::Unix(stream) => std::pin::Pin::new(stream).poll_write_vectored(cx, bufs),
             #[cfg(feature = "tls-rustls")]
             Stream::Tls(stream) => std::pin::Pin::new(stream).poll_write_vectored(cx, bufs),
         }
@@ -100,6 +130,8 @@ impl AsyncWrite for Stream {
     fn is_write_vectored(&self) -> bool {
         match self {
             Stream::Tcp(stream) => stream.is_write_vectored(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.is_write_vectored(),
             #[cfg(feature = "tls-rustls")]
             Stream::Tls(stream) => stream.is_write_vectored(),
         }
@@ -18,6 +18,10 @@ pub enum IncomingBodyError {
     #[error("h3 body error: {0}")]
     #[cfg(feature = "http3")]
     H3(#[from] crate::backend::h3::body::H3BodyError),
+    /// The body is larger than the limit set by the [`BodyLimit`](crate::middleware::BodyLimit) middleware.
+    #[error("body exceeded the limit of {0} bytes")]
+    #[cfg(feature = "middleware")]
+    LengthLimitExceeded(u64),
 }
 
 /// The body of an incoming request.
@@ -31,6 +35,12 @@ pub enum IncomingBody {
     /// The body of an incoming h3 request.
     #[cfg(feature = "http3")]
     Quic(crate::backend::h3::body::QuicIncomingBody<h3_quinn::RecvStream>),
+    /// An empty body, used for WebTransport requests because their stream belongs to the session.
+    #[cfg(feature = "webtransport")]
+    Empty,
+    /// A body with a size limit, set by the [`BodyLimit`](crate::middleware::BodyLimit) middleware.
+    #[cfg(feature = "middleware")]
+    Limited(Box<LimitedBody>),
 }
 
 #[cfg(any(feature = "http1", feature = "http2"))]
@@ -57,7 +67,11 @@ impl http_body::Body for IncomingBody {
             IncomingBody::Hyper(body) => body.is_end_stream(),
             #[cfg(feature = "http3")]
             IncomingBody::Quic(body) => body.is_end_stream(),
-            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3")))]
+            #[cfg(feature = "webtransport")]
+            IncomingBody::Empty => true,
+            #[cfg(feature = "middleware")]
+            IncomingBody::Limited(body) => body.is_end_stream(),
+            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3", feature = "middleware")))]
             _ => false,
         }
     }
@@ -71,7 +85,11 @@ impl http_body::Body for IncomingBody {
             IncomingBody::Hyper(body) => std::pin::Pin::new(body).poll_frame(_cx).map_err(Into::into),
             #[cfg(feature = "http3")]
             IncomingBody::Quic(body) => std::pin::Pin::new(body).poll_frame(_cx).map_err(Into::into),
-            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3")))]
+            #[cfg(feature = "webtransport")]
+            IncomingBody::Empty => std::task::Poll::Ready(None),
+            #[cfg(feature = "middleware")]
+            IncomingBody::Limited(body) => std::pin::Pin::new(body.as_mut()).poll_frame(_cx),
+            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3", feature = "middleware")))]
             _ => std::task::Poll::Ready(None),
         }
     }
@@ -82,12 +100,79 @@ impl http_body::Body for IncomingBody {
             IncomingBody::Hyper(body) => body.size_hint(),
             #[cfg(feature = "http3")]
             IncomingBody::Quic(body) => body.size_hint(),
-            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3")))]
+            #[cfg(feature = "webtransport")]
+            IncomingBody::Empty => http_body::SizeHint::with_exact(0),
+            #[cfg(feature = "middleware")]
+            IncomingBody::Limited(body) => body.size_hint(),
+            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3", feature = "middleware")))]
             _ => http_body::SizeHint::default(),
         }
     }
 }
 
+/// An incoming body that fails once more than a given number of bytes has been read from it.
+///
+/// Created by the [`BodyLimit`](crate::middleware::BodyLimit) middleware.
+#[cfg(feature = "middleware")]
+pub struct LimitedBody {
+    body: IncomingBody,
+    limit: u64,
+    remaining: u64,
+}
+
+#[cfg(feature = "middleware")]
+impl LimitedBody {
+    /// Create a new [`LimitedBody`] that allows at most `limit` bytes to be read from `body`.
+    pub fn new(body: IncomingBody, limit: u64) -> Self {
+        Self {
+            body,
+            limit,
+            remaining: limit,
+        }
+    }
+}
+
+#[cfg(feature = "middleware")]
+impl http_body::Body for LimitedBody {
+    type Data = Bytes;
+    type Error = IncomingBodyError;
+
+    fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        match std::task::ready!(Pin::new(&mut this.body).poll_frame(cx)) {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    let len = data.len() as u64;
+                    if len > this.remaining {
+                        this.remaining = 0;
+                        return Poll::Ready(Some(Err(IncomingBodyError::LengthLimitExceeded(this.limit))));
+                    }
+                    this.remaining -= len;
+                }
+
+                Poll::Ready(Some(Ok(frame)))
+            }
+            frame => Poll::Ready(frame),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        let mut hint = self.body.size_hint();
+        if hint.upper().is_none_or(|upper| upper > self.remaining) {
+            hint.set_upper(self.remaining);
+        }
+        if hint.lower() > self.remaining {
+            hint.set_lower(self.remaining);
+        }
+        hint
+    }
+}
+
 pin_project_lite::pin_project! {
     /// A wrapper around an HTTP body that tracks the size of the data that is read from it.
     pub struct TrackedBody<B, T> {
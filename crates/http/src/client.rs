@@ -0,0 +1,335 @@
+//! An HTTP client with support for HTTP/1.1, HTTP/2 and HTTP/3.
+//!
+//! The client keeps a pool of connections per host and picks the protocol by the
+//! [version](http::Request::version) of every request:
+//!
+//! - HTTP/1.1 (the default) switches to HTTP/2 when the server offers it during the TLS handshake.
+//! - HTTP/2 is used with prior knowledge, also for plaintext connections.
+//! - HTTP/3 connects over QUIC and requires TLS.
+//!
+//! Requests with an [idempotent](http::Method::is_idempotent) method are retried when they fail before a
+//! response was received or when the server responds with `502`, `503` or `504`, see [`RetryPolicy`].
+//! Every attempt has a [`timeout`](HttpClientBuilder::timeout), which can be changed for a single request by
+//! inserting a [`RequestTimeout`] or a [`RetryPolicy`] into the extensions of the request.
+//!
+//! All requests are recorded with [`scuffle_metrics`] as `http_client_requests`, `http_client_duration` and
+//! `http_client_retries`.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), scuffle_http::client::ClientError> {
+//! let client = scuffle_http::client::HttpClient::builder().build()?;
+//!
+//! let response = client.get("https://example.com/").await?;
+//! println!("status: {}", response.status());
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::header::USER_AGENT;
+use http::{HeaderValue, Request, Response, StatusCode, Uri, Version};
+use scuffle_future_ext::FutureExt;
+
+mod body;
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod connector;
+#[cfg(feature = "http3")]
+mod h3;
+mod metrics;
+
+pub use body::{ResponseBody, ResponseBodyError};
+
+/// An error that can occur when building a client or sending a request.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// The request could not be built.
+    #[error("http error: {0}")]
+    Http(#[from] http::Error),
+    /// The uri of the request has no scheme or host.
+    #[error("invalid request uri: {0}")]
+    InvalidUri(Uri),
+    /// The HTTP version of the request is not supported or not enabled.
+    #[error("unsupported http version: {0:?}")]
+    UnsupportedVersion(Version),
+    /// No response was received within the timeout.
+    #[error("request timed out")]
+    Timeout,
+    /// An I/O error.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The rustls config could not be built.
+    #[error("rustls error: {0}")]
+    #[cfg(feature = "tls-rustls")]
+    Rustls(#[from] tokio_rustls::rustls::Error),
+    /// An error that occurred while sending a HTTP/1.1 or HTTP/2 request.
+    #[error("hyper error: {0}")]
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    Hyper(#[from] hyper_util::client::legacy::Error),
+    /// No initial cipher suite.
+    ///
+    /// Refer to [`h3_quinn::quinn::crypto::rustls::NoInitialCipherSuite`] for more information.
+    #[error("{0}")]
+    #[cfg(feature = "http3")]
+    NoInitialCipherSuite(#[from] h3_quinn::quinn::crypto::rustls::NoInitialCipherSuite),
+    /// The QUIC connection could not be started.
+    #[error("quinn connect error: {0}")]
+    #[cfg(feature = "http3")]
+    QuinnConnect(#[from] h3_quinn::quinn::ConnectError),
+    /// An error that occurred while establishing a QUIC connection.
+    #[error("quinn connection error: {0}")]
+    #[cfg(feature = "http3")]
+    QuinnConnection(#[from] h3_quinn::quinn::ConnectionError),
+    /// h3 connection error.
+    ///
+    /// Refer to [`h3::error::ConnectionError`] for more information.
+    #[error("h3 connection error: {0}")]
+    #[cfg(feature = "http3")]
+    H3Connection(#[from] ::h3::error::ConnectionError),
+    /// h3 stream error.
+    ///
+    /// Refer to [`h3::error::StreamError`] for more information.
+    #[error("h3 stream error: {0}")]
+    #[cfg(feature = "http3")]
+    H3Stream(#[from] ::h3::error::StreamError),
+}
+
+impl ClientError {
+    /// Returns `true` if sending the request again might succeed.
+    ///
+    /// Errors of the request itself and of the client configuration are not retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http(_) | ClientError::InvalidUri(_) | ClientError::UnsupportedVersion(_) => false,
+            #[cfg(feature = "tls-rustls")]
+            ClientError::Rustls(_) => false,
+            #[cfg(feature = "http3")]
+            ClientError::NoInitialCipherSuite(_) | ClientError::QuinnConnect(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Retries of requests with an [idempotent](http::Method::is_idempotent) method.
+///
+/// Insert it into the extensions of a request to override the policy of the client for that request.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt.
+    #[builder(default = 2)]
+    max_retries: u32,
+    /// The time to wait before the first retry, doubled for every following retry.
+    #[builder(default = Duration::from_millis(100))]
+    initial_backoff: Duration,
+    /// The maximum time to wait between two attempts.
+    #[builder(default = Duration::from_secs(5))]
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn disabled() -> Self {
+        Self::builder().max_retries(0).build()
+    }
+
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+/// Overrides the [`timeout`](HttpClientBuilder::timeout) of the client for a single request.
+///
+/// Insert it into the extensions of the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTimeout(pub Duration);
+
+/// An HTTP client.
+///
+/// Cloning the client is cheap, all clones share the same connection pool.
+///
+/// Create a new client by calling [`HttpClient::builder`].
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    inner: Arc<HttpClientInner>,
+}
+
+#[derive(Debug)]
+struct HttpClientInner {
+    timeout: Duration,
+    retry: RetryPolicy,
+    user_agent: Option<HeaderValue>,
+    #[cfg(feature = "http1")]
+    http1: connector::HyperClient,
+    #[cfg(feature = "http2")]
+    http2: connector::HyperClient,
+    #[cfg(feature = "http3")]
+    http3: h3::H3Pool,
+}
+
+#[bon::bon]
+impl HttpClient {
+    /// Creates a new client.
+    #[builder]
+    pub fn new(
+        /// The maximum time to wait for the response headers of every attempt.
+        #[builder(default = Duration::from_secs(30))]
+        timeout: Duration,
+        /// The maximum time it can take to establish a connection, including the TLS handshake.
+        #[builder(default = Duration::from_secs(10))]
+        connect_timeout: Duration,
+        /// Retries of requests with an idempotent method.
+        #[builder(default)]
+        retry: RetryPolicy,
+        /// Close pooled connections that were not used for this long.
+        #[builder(default = Duration::from_secs(90))]
+        pool_idle_timeout: Duration,
+        /// The maximum number of idle HTTP/1.1 connections kept per host.
+        #[builder(default = 32)]
+        pool_max_idle_per_host: usize,
+        /// The `User-Agent` header of requests that do not set one.
+        user_agent: Option<HeaderValue>,
+        /// rustls config used for `https` requests.
+        ///
+        /// Trusts the root certificates of the platform by default.
+        /// Use [`mtls_client_config`](crate::tls::mtls_client_config) to authenticate with a client certificate.
+        #[cfg(feature = "tls-rustls")]
+        rustls_config: Option<tokio_rustls::rustls::ClientConfig>,
+    ) -> Result<Self, ClientError> {
+        #[cfg(feature = "tls-rustls")]
+        let rustls_config = match rustls_config {
+            Some(rustls_config) => rustls_config,
+            None => {
+                use rustls_platform_verifier::BuilderVerifierExt;
+
+                tokio_rustls::rustls::ClientConfig::builder_with_provider(crate::tls::crypto_provider())
+                    .with_safe_default_protocol_versions()?
+                    .with_platform_verifier()?
+                    .with_no_client_auth()
+            }
+        };
+
+        #[cfg(any(feature = "http1", feature = "http2"))]
+        let hyper_config = connector::HyperConfig {
+            connect_timeout,
+            pool_idle_timeout,
+            pool_max_idle_per_host,
+            #[cfg(feature = "tls-rustls")]
+            rustls_config: rustls_config.clone(),
+        };
+
+        #[cfg(not(feature = "http3"))]
+        let _ = (connect_timeout, pool_idle_timeout);
+        #[cfg(not(any(feature = "http1", feature = "http2")))]
+        let _ = pool_max_idle_per_host;
+
+        Ok(Self {
+            inner: Arc::new(HttpClientInner {
+                timeout,
+                retry,
+                user_agent,
+                #[cfg(feature = "http1")]
+                http1: hyper_config.build(false),
+                #[cfg(feature = "http2")]
+                http2: hyper_config.build(true),
+                #[cfg(feature = "http3")]
+                http3: h3::H3Pool::new(rustls_config, connect_timeout, pool_idle_timeout)?,
+            }),
+        })
+    }
+}
+
+impl HttpClient {
+    /// Sends a `GET` request to `uri`.
+    pub async fn get<U>(&self, uri: U) -> Result<Response<ResponseBody>, ClientError>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        self.request(Request::get(uri).body(Bytes::new())?).await
+    }
+
+    /// Sends a request and waits for the response headers.
+    ///
+    /// The uri of the request must be absolute. The body is buffered so the request can be retried.
+    pub async fn request<B: Into<Bytes>>(&self, req: Request<B>) -> Result<Response<ResponseBody>, ClientError> {
+        let (mut parts, body) = req.into_parts();
+        let body = body.into();
+
+        if parts.uri.scheme().is_none() || parts.uri.host().is_none() {
+            return Err(ClientError::InvalidUri(parts.uri));
+        }
+
+        if let Some(user_agent) = &self.inner.user_agent {
+            parts.headers.entry(USER_AGENT).or_insert_with(|| user_agent.clone());
+        }
+
+        let timeout = parts
+            .extensions
+            .get::<RequestTimeout>()
+            .map_or(self.inner.timeout, |timeout| timeout.0);
+        let retry = parts.extensions.get::<RetryPolicy>().unwrap_or(&self.inner.retry).clone();
+        let max_retries = if parts.method.is_idempotent() { retry.max_retries } else { 0 };
+
+        let labels = metrics::Labels::new(&parts);
+        let mut retries = 0;
+
+        loop {
+            let start = std::time::Instant::now();
+            let result = self
+                .send(parts.clone(), body.clone())
+                .with_timeout(timeout)
+                .await
+                .unwrap_or(Err(ClientError::Timeout));
+
+            labels.record(&result, start.elapsed());
+
+            let retryable = match &result {
+                Ok(response) => matches!(
+                    response.status(),
+                    StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(err) => err.is_retryable(),
+            };
+
+            if !retryable || retries >= max_retries {
+                return result;
+            }
+
+            #[cfg(feature = "tracing")]
+            match &result {
+                Ok(response) => tracing::debug!(uri = %parts.uri, status = %response.status(), retries, "retrying request"),
+                Err(err) => tracing::debug!(uri = %parts.uri, err = %err, retries, "retrying request"),
+            }
+
+            labels.record_retry();
+            tokio::time::sleep(retry.backoff(retries)).await;
+            retries += 1;
+        }
+    }
+
+    async fn send(&self, parts: http::request::Parts, body: Bytes) -> Result<Response<ResponseBody>, ClientError> {
+        match parts.version {
+            #[cfg(feature = "http1")]
+            Version::HTTP_10 | Version::HTTP_11 => self.inner.http1.send(parts, body).await,
+            #[cfg(feature = "http2")]
+            Version::HTTP_2 => self.inner.http2.send(parts, body).await,
+            #[cfg(feature = "http3")]
+            Version::HTTP_3 => self.inner.http3.send(parts, body).await,
+            version => {
+                let _ = body;
+                Err(ClientError::UnsupportedVersion(version))
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! The body of a response received by the client.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "http3")]
+use bytes::Buf;
+use bytes::Bytes;
+
+/// An error that can occur when reading the body of a response.
+#[derive(thiserror::Error, Debug)]
+pub enum ResponseBodyError {
+    /// An error that occurred while reading a hyper body.
+    #[error("hyper error: {0}")]
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    Hyper(#[from] hyper::Error),
+    /// h3 stream error.
+    ///
+    /// Refer to [`h3::error::StreamError`] for more information.
+    #[error("h3 error: {0}")]
+    #[cfg(feature = "http3")]
+    H3(#[from] h3::error::StreamError),
+}
+
+/// The body of a response.
+///
+/// This enum is used to abstract away the differences between the body types of HTTP/1, HTTP/2 and HTTP/3.
+/// It implements the [`http_body::Body`] trait.
+#[derive(Debug)]
+pub enum ResponseBody {
+    /// The body of a HTTP/1.1 or HTTP/2 response.
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    Hyper(hyper::body::Incoming),
+    /// The body of a HTTP/3 response.
+    #[cfg(feature = "http3")]
+    Quic(Box<QuicResponseBody>),
+}
+
+impl http_body::Body for ResponseBody {
+    type Data = Bytes;
+    type Error = ResponseBodyError;
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            ResponseBody::Hyper(body) => body.is_end_stream(),
+            #[cfg(feature = "http3")]
+            ResponseBody::Quic(body) => body.is_end_stream(),
+            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3")))]
+            _ => true,
+        }
+    }
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        match self.get_mut() {
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            ResponseBody::Hyper(body) => Pin::new(body).poll_frame(_cx).map_err(Into::into),
+            #[cfg(feature = "http3")]
+            ResponseBody::Quic(body) => Pin::new(body).poll_frame(_cx),
+            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3")))]
+            _ => Poll::Ready(None),
+        }
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        match self {
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            ResponseBody::Hyper(body) => body.size_hint(),
+            #[cfg(feature = "http3")]
+            ResponseBody::Quic(body) => body.size_hint(),
+            #[cfg(not(any(feature = "http1", feature = "http2", feature = "http3")))]
+            _ => http_body::SizeHint::with_exact(0),
+        }
+    }
+}
+
+#[cfg(feature = "http3")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Data,
+    Trailers,
+    Done,
+}
+
+/// The body of a HTTP/3 response.
+///
+/// Holds on to the connection it was received on, so the connection stays open until the body is dropped.
+#[cfg(feature = "http3")]
+pub struct QuicResponseBody {
+    stream: h3::client::RequestStream<h3_quinn::RecvStream, Bytes>,
+    state: State,
+    _send_request: h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+}
+
+#[cfg(feature = "http3")]
+impl std::fmt::Debug for QuicResponseBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuicResponseBody")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "http3")]
+impl QuicResponseBody {
+    pub(crate) fn new(
+        stream: h3::client::RequestStream<h3_quinn::RecvStream, Bytes>,
+        send_request: h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+    ) -> Self {
+        Self {
+            stream,
+            state: State::Data,
+            _send_request: send_request,
+        }
+    }
+}
+
+#[cfg(feature = "http3")]
+impl http_body::Body for QuicResponseBody {
+    type Data = Bytes;
+    type Error = ResponseBodyError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        if this.state == State::Data {
+            match std::task::ready!(this.stream.poll_recv_data(cx)) {
+                Ok(Some(mut buf)) => {
+                    return Poll::Ready(Some(Ok(http_body::Frame::data(buf.copy_to_bytes(buf.remaining())))));
+                }
+                Ok(None) => this.state = State::Trailers,
+                Err(err) => {
+                    this.state = State::Done;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+            }
+        }
+
+        if this.state == State::Trailers {
+            let trailers = std::task::ready!(this.stream.poll_recv_trailers(cx));
+            this.state = State::Done;
+
+            return Poll::Ready(
+                trailers
+                    .map_err(Into::into)
+                    .transpose()
+                    .map(|trailers| trailers.map(http_body::Frame::trailers)),
+            );
+        }
+
+        Poll::Ready(None)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.state == State::Done
+    }
+}
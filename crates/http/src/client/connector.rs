@@ -0,0 +1,244 @@
+//! HTTP/1.1 and HTTP/2 connections, pooled by the hyper-util client.
+use std::convert::Infallible;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::{Request, Response, Uri};
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpConnector};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use scuffle_future_ext::FutureExt;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tower_service::Service;
+
+use super::{ClientError, ResponseBody};
+
+/// The settings shared by the HTTP/1.1 and HTTP/2 clients.
+pub(crate) struct HyperConfig {
+    pub(crate) connect_timeout: Duration,
+    pub(crate) pool_idle_timeout: Duration,
+    pub(crate) pool_max_idle_per_host: usize,
+    #[cfg(feature = "tls-rustls")]
+    pub(crate) rustls_config: tokio_rustls::rustls::ClientConfig,
+}
+
+impl HyperConfig {
+    /// Builds a client that speaks HTTP/2 only, or HTTP/1.1 with HTTP/2 negotiated by ALPN.
+    pub(crate) fn build(&self, http2_only: bool) -> HyperClient {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_nodelay(true);
+
+        #[cfg(feature = "tls-rustls")]
+        let tls = {
+            let mut rustls_config = self.rustls_config.clone();
+            rustls_config.alpn_protocols = if http2_only {
+                vec![b"h2".to_vec()]
+            } else if cfg!(feature = "http2") {
+                vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+            } else {
+                vec![b"http/1.1".to_vec()]
+            };
+            tokio_rustls::TlsConnector::from(std::sync::Arc::new(rustls_config))
+        };
+
+        let mut builder = hyper_util::client::legacy::Client::builder(TokioExecutor::new());
+        builder
+            .pool_timer(TokioTimer::new())
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host);
+
+        #[cfg(feature = "http2")]
+        builder.timer(TokioTimer::new()).http2_only(http2_only);
+        #[cfg(not(feature = "http2"))]
+        let _ = http2_only;
+
+        HyperClient(builder.build(Connector {
+            http,
+            #[cfg(feature = "tls-rustls")]
+            tls,
+            connect_timeout: self.connect_timeout,
+        }))
+    }
+}
+
+/// A pooled HTTP/1.1 or HTTP/2 client.
+#[derive(Debug)]
+pub(crate) struct HyperClient(hyper_util::client::legacy::Client<Connector, RequestBody>);
+
+impl HyperClient {
+    pub(crate) async fn send(
+        &self,
+        parts: http::request::Parts,
+        body: Bytes,
+    ) -> Result<Response<ResponseBody>, ClientError> {
+        let response = self.0.request(Request::from_parts(parts, RequestBody::new(body))).await?;
+        Ok(response.map(ResponseBody::Hyper))
+    }
+}
+
+/// Connects to `http` and `https` uris.
+#[derive(Clone)]
+pub(crate) struct Connector {
+    http: HttpConnector,
+    #[cfg(feature = "tls-rustls")]
+    tls: tokio_rustls::TlsConnector,
+    connect_timeout: Duration,
+}
+
+impl Service<Uri> for Connector {
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = TokioIo<MaybeTlsStream>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(io::Error::other)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let https = uri.scheme() == Some(&http::uri::Scheme::HTTPS);
+        #[cfg(feature = "tls-rustls")]
+        let server_name = uri.host().unwrap_or_default().trim_matches(['[', ']']).to_owned();
+        #[cfg(feature = "tls-rustls")]
+        let tls = self.tls.clone();
+        let connecting = self.http.call(uri);
+
+        let connect = async move {
+            let tcp = connecting.await.map_err(io::Error::other)?.into_inner();
+            if !https {
+                return Ok(MaybeTlsStream::Tcp(tcp));
+            }
+
+            #[cfg(feature = "tls-rustls")]
+            {
+                let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from(server_name)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+                Ok(MaybeTlsStream::Tls(Box::new(tls.connect(server_name, tcp).await?)))
+            }
+
+            #[cfg(not(feature = "tls-rustls"))]
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "https requires the tls-rustls feature",
+            ))
+        };
+
+        let connect_timeout = self.connect_timeout;
+        Box::pin(async move {
+            connect
+                .with_timeout(connect_timeout)
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))?
+                .map(TokioIo::new)
+        })
+    }
+}
+
+/// A TCP stream, with or without TLS.
+pub(crate) enum MaybeTlsStream {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls-rustls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+impl Connection for MaybeTlsStream {
+    fn connected(&self) -> Connected {
+        match self {
+            MaybeTlsStream::Tcp(stream) => stream.connected(),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => {
+                let (tcp, session) = stream.get_ref();
+                if session.alpn_protocol() == Some(b"h2") {
+                    tcp.connected().negotiated_h2()
+                } else {
+                    tcp.connected()
+                }
+            }
+        }
+    }
+}
+
+impl AsyncRead for MaybeTlsStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for MaybeTlsStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTlsStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTlsStream::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            MaybeTlsStream::Tcp(stream) => stream.is_write_vectored(),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// A buffered request body, sent as a single frame.
+pub(crate) struct RequestBody(Option<Bytes>);
+
+impl RequestBody {
+    fn new(body: Bytes) -> Self {
+        Self((!body.is_empty()).then_some(body))
+    }
+}
+
+impl http_body::Body for RequestBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        Poll::Ready(self.get_mut().0.take().map(|data| Ok(http_body::Frame::data(data))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.is_none()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        http_body::SizeHint::with_exact(self.0.as_ref().map_or(0, |data| data.len() as u64))
+    }
+}
@@ -0,0 +1,145 @@
+//! HTTP/3 connections, one per host.
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use h3_quinn::quinn;
+use http::{Request, Response};
+use scuffle_future_ext::FutureExt;
+
+use super::body::QuicResponseBody;
+use super::{ClientError, ResponseBody};
+
+type SendRequest = h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>;
+
+struct PooledConnection {
+    connection: quinn::Connection,
+    send_request: SendRequest,
+}
+
+/// A pool of HTTP/3 connections.
+///
+/// All requests to the same host share a single connection, as recommended by RFC 9114.
+pub(crate) struct H3Pool {
+    client_config: quinn::ClientConfig,
+    connect_timeout: Duration,
+    endpoint: Mutex<Option<quinn::Endpoint>>,
+    connections: Mutex<HashMap<(String, u16), PooledConnection>>,
+}
+
+impl std::fmt::Debug for H3Pool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("H3Pool")
+            .field("connect_timeout", &self.connect_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl H3Pool {
+    pub(crate) fn new(
+        mut rustls_config: tokio_rustls::rustls::ClientConfig,
+        connect_timeout: Duration,
+        idle_timeout: Duration,
+    ) -> Result<Self, ClientError> {
+        rustls_config.alpn_protocols = vec![b"h3".to_vec()];
+
+        let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(rustls_config)?;
+        let mut client_config = quinn::ClientConfig::new(Arc::new(crypto));
+
+        let mut transport_config = quinn::TransportConfig::default();
+        transport_config.max_idle_timeout(idle_timeout.try_into().ok());
+        client_config.transport_config(Arc::new(transport_config));
+
+        Ok(Self {
+            client_config,
+            connect_timeout,
+            endpoint: Mutex::new(None),
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) async fn send(
+        &self,
+        parts: http::request::Parts,
+        body: Bytes,
+    ) -> Result<Response<ResponseBody>, ClientError> {
+        if parts.uri.scheme() != Some(&http::uri::Scheme::HTTPS) {
+            return Err(ClientError::InvalidUri(parts.uri));
+        }
+
+        let host = parts.uri.host().unwrap_or_default().trim_matches(['[', ']']).to_owned();
+        let port = parts.uri.port_u16().unwrap_or(443);
+        let mut send_request = self.connection(host, port).await?;
+
+        let mut stream = send_request.send_request(Request::from_parts(parts, ())).await?;
+        if !body.is_empty() {
+            stream.send_data(body).await?;
+        }
+        stream.finish().await?;
+
+        let response = stream.recv_response().await?;
+        let (_, recv) = stream.split();
+
+        Ok(response.map(|()| ResponseBody::Quic(Box::new(QuicResponseBody::new(recv, send_request)))))
+    }
+
+    /// Returns the open connection to the host or establishes a new one.
+    async fn connection(&self, host: String, port: u16) -> Result<SendRequest, ClientError> {
+        let key = (host, port);
+
+        if let Some(pooled) = self.connections.lock().expect("poisoned").get(&key)
+            && pooled.connection.close_reason().is_none()
+        {
+            return Ok(pooled.send_request.clone());
+        }
+
+        let pooled = self
+            .connect(&key.0, key.1)
+            .with_timeout(self.connect_timeout)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))??;
+
+        let send_request = pooled.send_request.clone();
+        self.connections.lock().expect("poisoned").insert(key, pooled);
+
+        Ok(send_request)
+    }
+
+    async fn connect(&self, host: &str, port: u16) -> Result<PooledConnection, ClientError> {
+        let addr = tokio::net::lookup_host((host, port))
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host has no addresses"))?;
+
+        let connection = self.endpoint()?.connect_with(self.client_config.clone(), addr, host)?.await?;
+
+        let (mut driver, send_request) = h3::client::new(h3_quinn::Connection::new(connection.clone())).await?;
+
+        tokio::spawn(async move {
+            let _err = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(err = %_err, "h3 connection closed");
+        });
+
+        Ok(PooledConnection {
+            connection,
+            send_request,
+        })
+    }
+
+    /// The endpoint is created on first use because it needs a running tokio runtime.
+    fn endpoint(&self) -> io::Result<quinn::Endpoint> {
+        let mut endpoint = self.endpoint.lock().expect("poisoned");
+        if let Some(endpoint) = endpoint.as_ref() {
+            return Ok(endpoint.clone());
+        }
+
+        let new_endpoint = quinn::Endpoint::client(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)))?;
+        *endpoint = Some(new_endpoint.clone());
+
+        Ok(new_endpoint)
+    }
+}
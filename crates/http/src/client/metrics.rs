@@ -0,0 +1,70 @@
+//! Metrics recorded for every request sent by the client.
+use std::time::Duration;
+
+use http::{Method, Response, Version};
+
+use super::{ClientError, ResponseBody};
+
+#[scuffle_metrics::metrics(rename = "http_client")]
+mod http_client {
+    use scuffle_metrics::{CounterU64, HistogramF64};
+
+    /// The number of attempts to send a request, by response status or error.
+    #[metrics(unit = "requests")]
+    pub(super) fn requests(
+        method: super::Method,
+        host: String,
+        version: &'static str,
+        status: Option<u16>,
+        error: Option<&'static str>,
+    ) -> CounterU64;
+
+    /// The time it took to receive the response headers.
+    #[metrics(unit = "seconds")]
+    pub(super) fn duration(method: super::Method, host: String, version: &'static str) -> HistogramF64;
+
+    /// The number of retries after a failed attempt.
+    #[metrics(unit = "requests")]
+    pub(super) fn retries(method: super::Method, host: String, version: &'static str) -> CounterU64;
+}
+
+/// The labels of a request.
+pub(crate) struct Labels {
+    method: Method,
+    host: String,
+    version: &'static str,
+}
+
+impl Labels {
+    pub(crate) fn new(parts: &http::request::Parts) -> Self {
+        let version = match parts.version {
+            Version::HTTP_09 => "0.9",
+            Version::HTTP_10 => "1.0",
+            Version::HTTP_11 => "1.1",
+            Version::HTTP_2 => "2",
+            Version::HTTP_3 => "3",
+            _ => "unknown",
+        };
+
+        Self {
+            method: parts.method.clone(),
+            host: parts.uri.host().unwrap_or_default().to_owned(),
+            version,
+        }
+    }
+
+    pub(crate) fn record(&self, result: &Result<Response<ResponseBody>, ClientError>, elapsed: Duration) {
+        let (status, error) = match result {
+            Ok(response) => (Some(response.status().as_u16()), None),
+            Err(ClientError::Timeout) => (None, Some("timeout")),
+            Err(_) => (None, Some("error")),
+        };
+
+        http_client::requests(self.method.clone(), self.host.clone(), self.version, status, error).incr();
+        http_client::duration(self.method.clone(), self.host.clone(), self.version).observe(elapsed.as_secs_f64());
+    }
+
+    pub(crate) fn record_retry(&self) {
+        http_client::retries(self.method.clone(), self.host.clone(), self.version).incr();
+    }
+}
@@ -0,0 +1,95 @@
+//! Connection limits, timeouts and shutdown behavior.
+#[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+use std::time::Duration;
+
+/// Limits and timeouts that apply to every connection accepted by a server.
+///
+/// The same configuration is used by all backends, options that only apply to some protocols say so.
+///
+/// Use [`ConnectionConfig::builder`] to create a new configuration or [`ConnectionConfig::default`] for the defaults.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct ConnectionConfig {
+    /// The maximum number of concurrent connections per backend.
+    ///
+    /// Once reached, the server stops accepting new connections until an existing one is closed.
+    /// Pending connections are left in the listen backlog of the operating system.
+    ///
+    /// Unlimited by default.
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+    pub(crate) max_connections: Option<usize>,
+    /// The maximum time a TLS (or QUIC) handshake can take before the connection is closed.
+    #[cfg(any(feature = "http3", all(feature = "tls-rustls", any(feature = "http1", feature = "http2"))))]
+    #[builder(default = Duration::from_secs(10))]
+    pub(crate) tls_handshake_timeout: Duration,
+    /// The maximum time it can take to receive the headers of a request.
+    ///
+    /// For HTTP/1.1 this also bounds the time a kept-alive connection waits for the next request.
+    #[cfg(any(feature = "http1", feature = "http3"))]
+    #[builder(default = Duration::from_secs(30))]
+    pub(crate) header_read_timeout: Duration,
+    /// Close connections that did not read or write any data for this long.
+    ///
+    /// Disabled by default.
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+    pub(crate) idle_timeout: Option<Duration>,
+    /// The interval at which keep-alive pings are sent to the client.
+    ///
+    /// Only applies to HTTP/2 and HTTP/3. Disabled by default.
+    #[cfg(any(feature = "http2", feature = "http3"))]
+    pub(crate) keep_alive_interval: Option<Duration>,
+    /// The time to wait for a keep-alive ping to be acknowledged before the connection is closed.
+    ///
+    /// Only applies to HTTP/2, HTTP/3 connections are closed by the [`idle_timeout`](Self::idle_timeout) instead.
+    #[cfg(feature = "http2")]
+    #[builder(default = Duration::from_secs(20))]
+    pub(crate) keep_alive_timeout: Duration,
+    /// The maximum number of concurrent streams (requests) per connection.
+    ///
+    /// Only applies to HTTP/2 and HTTP/3. Uses the default of the protocol implementation when not set.
+    #[cfg(any(feature = "http2", feature = "http3"))]
+    pub(crate) max_concurrent_streams: Option<u32>,
+    /// The maximum time to wait for in-flight requests when the server shuts down.
+    ///
+    /// When the context is cancelled the server stops accepting new connections and asks the open ones to close,
+    /// by sending a `GOAWAY` frame for HTTP/2 and HTTP/3 or `Connection: close` for HTTP/1.1.
+    /// Connections that are still open after this time are aborted.
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+    #[builder(default = Duration::from_secs(30))]
+    pub(crate) drain_timeout: Duration,
+    /// Read a PROXY protocol header from connections of trusted load balancers.
+    ///
+    /// The client address from the header replaces the peer address in [`ClientAddr`](crate::extensions::ClientAddr)
+    /// and the address passed to the service factory. The header itself is added to every request as the
+    /// [`ProxyHeader`](crate::extensions::ProxyHeader) extension.
+    ///
+    /// Only applies to HTTP/1.1 and HTTP/2, the header is read before the TLS handshake.
+    /// QUIC connections cannot carry a PROXY protocol header. Disabled by default.
+    #[cfg(feature = "proxy-protocol")]
+    pub(crate) proxy_protocol: Option<scuffle_proxy_protocol::ProxyProtocol>,
+    /// Accept WebTransport sessions and HTTP/3 datagrams.
+    ///
+    /// Extended CONNECT requests with the `webtransport` protocol carry a
+    /// [`WebTransportUpgrade`](crate::backend::h3::webtransport::WebTransportUpgrade) extension,
+    /// see the [`webtransport`](crate::backend::h3::webtransport) module.
+    ///
+    /// Only applies to HTTP/3. Disabled by default.
+    #[cfg(feature = "webtransport")]
+    pub(crate) webtransport: Option<crate::backend::h3::webtransport::WebTransportConfig>,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl ConnectionConfig {
+    /// Returns a semaphore with one permit for each connection that can be accepted.
+    #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
+    pub(crate) fn connection_semaphore(&self) -> std::sync::Arc<tokio::sync::Semaphore> {
+        let permits = self.max_connections.map_or(tokio::sync::Semaphore::MAX_PERMITS, |max| {
+            max.min(tokio::sync::Semaphore::MAX_PERMITS)
+        });
+        std::sync::Arc::new(tokio::sync::Semaphore::new(permits))
+    }
+}
@@ -14,6 +14,18 @@ impl Deref for ClientAddr {
     }
 }
 
+/// This extension is present on the request when it was accepted by a [`Listener`](crate::listener::Listener) with a name.
+#[derive(Clone, Debug)]
+pub struct ListenerName(pub std::sync::Arc<str>);
+
+impl Deref for ListenerName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 /// This extension is present on the request when the client has provided one or multiple TLS client
 /// certificates.
 #[derive(Clone, Debug)]
@@ -28,3 +40,37 @@ impl Deref for ClientIdentity {
         &self.0
     }
 }
+
+/// This extension is present on the request when the connection was accepted from a trusted proxy
+/// and contains the PROXY protocol header it sent.
+///
+/// See [`ConnectionConfig::proxy_protocol`](crate::connection::ConnectionConfigBuilder::proxy_protocol).
+#[derive(Clone, Debug)]
+#[cfg(feature = "proxy-protocol")]
+pub struct ProxyHeader(pub std::sync::Arc<scuffle_proxy_protocol::ProxyHeader>);
+
+#[cfg(feature = "proxy-protocol")]
+impl Deref for ProxyHeader {
+    type Target = scuffle_proxy_protocol::ProxyHeader;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// This extension is present on the request when it passed through the
+/// [`SetRequestId`](crate::middleware::SetRequestId) middleware and contains the id of the request.
+///
+/// The same id is sent back to the client in the response headers.
+#[derive(Clone, Debug)]
+#[cfg(feature = "middleware")]
+pub struct RequestId(pub http::HeaderValue);
+
+#[cfg(feature = "middleware")]
+impl Deref for RequestId {
+    type Target = http::HeaderValue;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
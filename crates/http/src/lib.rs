@@ -2,6 +2,13 @@
 //!
 //! It abstracts away [`hyper`](https://crates.io/crates/hyper) and [`h3`](https://crates.io/crates/h3) to provide a rather simple interface for creating and running a server that can handle all three protocols.
 //!
+//! The `client` feature adds an [HTTP client](crate::client) with the same protocol support, built on the same crates.
+//!
+//! The `middleware` and `compression` features add [middleware](crate::middleware) for services, like CORS,
+//! request ids, rate limits and response compression.
+//!
+//! The `acme` feature obtains and renews [certificates](crate::acme) from ACME certificate authorities like Let's Encrypt.
+//!
 //! See the [examples](./examples) directory for usage examples.
 #![cfg_attr(feature = "docs", doc = "\n\nSee the [changelog][changelog] for a full release history.")]
 #![cfg_attr(feature = "docs", doc = "## Feature flags")]
@@ -62,17 +69,29 @@
 #[cfg(all(feature = "http3", not(feature = "tls-rustls")))]
 compile_error!("feature \"tls-rustls\" must be enabled when \"http3\" is enabled.");
 
+#[cfg(feature = "acme")]
+pub mod acme;
 #[cfg(any(feature = "http1", feature = "http2", feature = "http3"))]
 pub mod backend;
 pub mod body;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod connection;
 pub mod error;
 pub mod extensions;
+pub mod listener;
+#[cfg(feature = "middleware")]
+pub mod middleware;
 mod server;
 pub mod service;
 mod tests;
+#[cfg(feature = "tls-rustls")]
+pub mod tls;
 
 pub use http;
 pub use http::Response;
+#[cfg(feature = "proxy-protocol")]
+pub use scuffle_proxy_protocol as proxy_protocol;
 pub use server::{HttpServer, HttpServerBuilder};
 
 /// An incoming request.
@@ -0,0 +1,311 @@
+//! Listeners the server accepts connections on.
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The socket a [`Listener`] accepts connections on.
+///
+/// Stream sockets (TCP and Unix domain sockets) serve HTTP/1.1 and HTTP/2, UDP sockets serve HTTP/3.
+#[derive(Debug, Clone)]
+pub enum ListenerSocket {
+    /// Bind a TCP socket to the address, and a UDP socket when HTTP/3 is enabled.
+    ///
+    /// Use `[::]` for a dual-stack listener.
+    /// For example, use `[::]:80` to bind to port 80 on both IPv4 and IPv6.
+    Bind(SocketAddr),
+    /// Bind a Unix domain socket to the path.
+    ///
+    /// The path must not exist yet, the socket file is removed again when the server stops.
+    #[cfg(unix)]
+    BindUnix(PathBuf),
+    /// A bound TCP listener, for example passed by systemd socket activation.
+    Tcp(Arc<std::net::TcpListener>),
+    /// A bound Unix domain socket listener, for example passed by systemd socket activation.
+    #[cfg(unix)]
+    Unix(Arc<std::os::unix::net::UnixListener>),
+    /// A bound UDP socket for HTTP/3, for example passed by systemd socket activation.
+    Udp(Arc<std::net::UdpSocket>),
+}
+
+impl ListenerSocket {
+    /// Returns whether connections are accepted over UDP (QUIC) on this socket.
+    pub(crate) fn is_udp(&self) -> bool {
+        matches!(self, Self::Udp(_))
+    }
+
+    /// Returns whether connections are accepted over a Unix domain socket.
+    #[cfg(all(feature = "tls-rustls", any(feature = "http1", feature = "http2")))]
+    pub(crate) fn is_unix(&self) -> bool {
+        #[cfg(unix)]
+        if matches!(self, Self::BindUnix(_) | Self::Unix(_)) {
+            return true;
+        }
+
+        false
+    }
+}
+
+impl From<SocketAddr> for ListenerSocket {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Bind(addr)
+    }
+}
+
+#[cfg(unix)]
+impl From<PathBuf> for ListenerSocket {
+    fn from(path: PathBuf) -> Self {
+        Self::BindUnix(path)
+    }
+}
+
+impl From<std::net::TcpListener> for ListenerSocket {
+    fn from(listener: std::net::TcpListener) -> Self {
+        Self::Tcp(Arc::new(listener))
+    }
+}
+
+#[cfg(unix)]
+impl From<std::os::unix::net::UnixListener> for ListenerSocket {
+    fn from(listener: std::os::unix::net::UnixListener) -> Self {
+        Self::Unix(Arc::new(listener))
+    }
+}
+
+impl From<std::net::UdpSocket> for ListenerSocket {
+    fn from(socket: std::net::UdpSocket) -> Self {
+        Self::Udp(Arc::new(socket))
+    }
+}
+
+impl std::fmt::Display for ListenerSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bind(addr) => write!(f, "{addr}"),
+            #[cfg(unix)]
+            Self::BindUnix(path) => write!(f, "unix:{}", path.display()),
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{addr}"),
+                Err(_) => f.write_str("tcp"),
+            },
+            #[cfg(unix)]
+            Self::Unix(listener) => match listener
+                .local_addr()
+                .ok()
+                .and_then(|addr| addr.as_pathname().map(PathBuf::from))
+            {
+                Some(path) => write!(f, "unix:{}", path.display()),
+                None => f.write_str("unix"),
+            },
+            Self::Udp(socket) => match socket.local_addr() {
+                Ok(addr) => write!(f, "udp:{addr}"),
+                Err(_) => f.write_str("udp"),
+            },
+        }
+    }
+}
+
+/// An additional listener of an [`HttpServer`](crate::HttpServer).
+///
+/// Every listener has its own socket, TLS and protocol settings but shares the service factory,
+/// context, worker tasks and [`ConnectionConfig`](crate::connection::ConnectionConfig) of the server.
+///
+/// Use [`Listener::builder`] to create a new listener.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct Listener {
+    /// The socket to accept connections on.
+    #[builder(start_fn, into)]
+    pub(crate) socket: ListenerSocket,
+    /// The name of the listener.
+    ///
+    /// When set, it is added to every request accepted by this listener as the
+    /// [`ListenerName`](crate::extensions::ListenerName) extension, for example to tell internal and public traffic apart.
+    #[builder(into)]
+    pub(crate) name: Option<Arc<str>>,
+    /// Enable HTTP/1.1.
+    #[builder(default = true)]
+    #[cfg(feature = "http1")]
+    pub(crate) enable_http1: bool,
+    /// Enable HTTP/2.
+    #[builder(default = true)]
+    #[cfg(feature = "http2")]
+    pub(crate) enable_http2: bool,
+    /// Enable HTTP/3 on a [`ListenerSocket::Bind`] socket.
+    ///
+    /// Requires a [`rustls_config`](ListenerBuilder::rustls_config). [`ListenerSocket::Udp`] sockets always serve HTTP/3.
+    #[builder(default = false)]
+    #[cfg(feature = "http3")]
+    pub(crate) enable_http3: bool,
+    /// rustls config.
+    ///
+    /// Use this field to set the listener into TLS mode.
+    /// It will only accept TLS connections when this is set. TLS is not supported on Unix domain sockets.
+    #[cfg(feature = "tls-rustls")]
+    pub(crate) rustls_config: Option<tokio_rustls::rustls::ServerConfig>,
+}
+
+/// A socket passed to the process by systemd socket activation.
+#[cfg(all(unix, any(feature = "http1", feature = "http2", feature = "http3")))]
+#[derive(Debug)]
+pub struct ListenFd {
+    /// The name of the socket, set with `FileDescriptorName=` in the socket unit.
+    pub name: Option<String>,
+    /// The socket.
+    pub socket: ListenerSocket,
+}
+
+/// The first file descriptor passed by systemd.
+#[cfg(all(unix, any(feature = "http1", feature = "http2", feature = "http3")))]
+const SD_LISTEN_FDS_START: std::os::fd::RawFd = 3;
+
+/// Takes the sockets passed to the process by systemd socket activation (`LISTEN_FDS`).
+///
+/// Returns an empty list when the process was not socket activated. The sockets can only be taken once,
+/// subsequent calls return an empty list as well.
+///
+/// Pass the sockets to [`Listener::builder`] to serve on them:
+///
+/// ```rust,no_run
+/// # fn main() -> std::io::Result<()> {
+/// let listeners = scuffle_http::listener::listen_fds()?
+///     .into_iter()
+///     .map(|fd| scuffle_http::listener::Listener::builder(fd.socket).maybe_name(fd.name).build())
+///     .collect::<Vec<_>>();
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(all(unix, any(feature = "http1", feature = "http2", feature = "http3")))]
+#[allow(unsafe_code)]
+pub fn listen_fds() -> std::io::Result<Vec<ListenFd>> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    static TAKEN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    let Some(pid) = std::env::var_os("LISTEN_PID") else {
+        return Ok(Vec::new());
+    };
+    let pid: u32 = pid
+        .to_str()
+        .and_then(|pid| pid.parse().ok())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid LISTEN_PID"))?;
+
+    // The sockets are meant for another process, for example our parent
+    if pid != std::process::id() {
+        return Ok(Vec::new());
+    }
+
+    let count: std::os::fd::RawFd = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid LISTEN_FDS"))?;
+    let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+    let mut names = names.split(':');
+
+    if TAKEN.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return Ok(Vec::new());
+    }
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START.saturating_add(count))
+        .map(|fd| {
+            // SAFETY: systemd passes ownership of the file descriptors from 3 to 3 + LISTEN_FDS to this process.
+            // `TAKEN` makes sure that they are only taken once.
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            let name = names.next().filter(|name| !name.is_empty()).map(str::to_owned);
+
+            Ok(ListenFd {
+                name,
+                socket: socket_from_fd(fd)?,
+            })
+        })
+        .collect()
+}
+
+/// Detects the type of a socket file descriptor.
+#[cfg(all(unix, any(feature = "http1", feature = "http2", feature = "http3")))]
+#[allow(unsafe_code)]
+fn socket_from_fd(fd: std::os::fd::OwnedFd) -> std::io::Result<ListenerSocket> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: `fd` is a valid file descriptor.
+    // Do not leak the socket to child processes, like sd_listen_fds does.
+    if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut socket_type: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `socket_type` and `len` are valid for writes and `len` is the size of `socket_type`.
+    let res = unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            (&mut socket_type as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    match socket_type {
+        libc::SOCK_STREAM => {
+            let listener = std::net::TcpListener::from(fd);
+            // The local address of a Unix domain socket cannot be represented as a `SocketAddr`
+            if listener.local_addr().is_ok() {
+                Ok(listener.into())
+            } else {
+                Ok(std::os::unix::net::UnixListener::from(std::os::fd::OwnedFd::from(listener)).into())
+            }
+        }
+        libc::SOCK_DGRAM => Ok(std::net::UdpSocket::from(fd).into()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "unsupported socket type",
+        )),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    #[test]
+    #[cfg(all(unix, any(feature = "http1", feature = "http2", feature = "http3")))]
+    fn socket_from_fd() {
+        use std::os::fd::OwnedFd;
+
+        use super::{ListenerSocket, socket_from_fd};
+
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let socket = socket_from_fd(OwnedFd::from(tcp)).unwrap();
+        assert!(matches!(&socket, ListenerSocket::Tcp(listener) if listener.local_addr().unwrap() == addr));
+        assert_eq!(socket.to_string(), addr.to_string());
+
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = socket_from_fd(OwnedFd::from(udp)).unwrap();
+        assert!(matches!(socket, ListenerSocket::Udp(_)));
+
+        let path = std::env::temp_dir().join(format!("scuffle-http-listen-fd-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let socket = socket_from_fd(OwnedFd::from(unix)).unwrap();
+        assert!(matches!(socket, ListenerSocket::Unix(_)));
+        assert_eq!(socket.to_string(), format!("unix:{}", path.display()));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn display() {
+        use super::ListenerSocket;
+
+        let socket = ListenerSocket::from("[::]:443".parse::<std::net::SocketAddr>().unwrap());
+        assert_eq!(socket.to_string(), "[::]:443");
+
+        #[cfg(unix)]
+        {
+            let socket = ListenerSocket::from(std::path::PathBuf::from("/run/app.sock"));
+            assert_eq!(socket.to_string(), "unix:/run/app.sock");
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Middleware for [`HttpService`]s.
+//!
+//! Every middleware wraps an inner service and is an [`HttpService`] itself, so they can be stacked
+//! without tower or axum. The [`HttpServiceExt`] trait adds a method for each of them to every service.
+//!
+//! Middleware that keeps state (the limits) shares it between all clones, so a stack wrapped by
+//! [`service_clone_factory`](crate::service::service_clone_factory) shares its limits across all connections.
+//!
+//! The last middleware added is the first one to see a request. [`with_request_id`](HttpServiceExt::with_request_id)
+//! should be added after [`with_trace`](HttpServiceExt::with_trace), so the request id is part of the span.
+//!
+//! ```rust
+//! use scuffle_http::middleware::{CorsConfig, HttpServiceExt, RateLimitConfig};
+//!
+//! let service = scuffle_http::service::fn_http_service(|_| async move {
+//!     scuffle_http::Response::builder()
+//!         .header(scuffle_http::http::header::CONTENT_TYPE, "text/plain")
+//!         .body("Hello, world!".to_string())
+//! })
+//! .with_body_limit(1024 * 1024)
+//! .with_cors(CorsConfig::default())
+//! .with_concurrency_limit(16)
+//! .with_rate_limit(RateLimitConfig::builder().requests(100).build())
+//! .with_trace()
+//! .with_request_id(Default::default());
+//!
+//! let service_factory = scuffle_http::service::service_clone_factory(service);
+//! ```
+use crate::service::HttpService;
+
+mod body;
+#[cfg(feature = "compression")]
+mod compression;
+mod cors;
+mod limit;
+mod request_id;
+mod trace;
+
+pub use body::*;
+#[cfg(feature = "compression")]
+pub use compression::*;
+pub use cors::*;
+pub use limit::*;
+pub use request_id::*;
+pub use trace::*;
+
+/// Adds the middleware of this module to every [`HttpService`].
+pub trait HttpServiceExt: HttpService + Sized {
+    /// Compress responses, see [`Compression`].
+    #[cfg(feature = "compression")]
+    fn with_compression(self, config: CompressionConfig) -> Compression<Self> {
+        Compression::new(self, config)
+    }
+
+    /// Handle cross-origin requests, see [`Cors`].
+    fn with_cors(self, config: CorsConfig) -> Cors<Self> {
+        Cors::new(self, config)
+    }
+
+    /// Assign an id to every request, see [`SetRequestId`].
+    fn with_request_id(self, config: RequestIdConfig) -> SetRequestId<Self> {
+        SetRequestId::new(self, config)
+    }
+
+    /// Limit the size of request bodies to `limit` bytes, see [`BodyLimit`].
+    fn with_body_limit(self, limit: u64) -> BodyLimit<Self> {
+        BodyLimit::new(self, limit)
+    }
+
+    /// Limit the number of concurrent requests per client, see [`ConcurrencyLimit`].
+    fn with_concurrency_limit(self, max_per_client: usize) -> ConcurrencyLimit<Self> {
+        ConcurrencyLimit::new(self, max_per_client)
+    }
+
+    /// Limit the rate of requests per client, see [`RateLimit`].
+    fn with_rate_limit(self, config: RateLimitConfig) -> RateLimit<Self> {
+        RateLimit::new(self, config)
+    }
+
+    /// Record metrics and tracing spans for every request, see [`Trace`].
+    fn with_trace(self) -> Trace<Self> {
+        Trace::new(self)
+    }
+}
+
+impl<S: HttpService> HttpServiceExt for S {}
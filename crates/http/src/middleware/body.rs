@@ -0,0 +1,93 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes};
+use http_body::Frame;
+
+pin_project_lite::pin_project! {
+    /// The response body of a middleware that can answer requests without calling the inner service.
+    ///
+    /// Either the body of the inner service or a fixed body, like the body of a `429 Too Many Requests` response.
+    pub struct MiddlewareBody<B> {
+        #[pin]
+        inner: Option<B>,
+        data: Option<Bytes>,
+    }
+}
+
+impl<B> MiddlewareBody<B> {
+    /// Create a new [`MiddlewareBody`] from the body of the inner service.
+    pub fn inner(body: B) -> Self {
+        Self {
+            inner: Some(body),
+            data: None,
+        }
+    }
+
+    /// Create a new [`MiddlewareBody`] with the given data.
+    pub fn full(data: impl Into<Bytes>) -> Self {
+        let data = data.into();
+        Self {
+            inner: None,
+            data: (!data.is_empty()).then_some(data),
+        }
+    }
+
+    /// Create a new empty [`MiddlewareBody`].
+    pub fn empty() -> Self {
+        Self { inner: None, data: None }
+    }
+}
+
+impl<B> std::fmt::Debug for MiddlewareBody<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MiddlewareBody")
+            .field("inner", &self.inner.is_some())
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl<B> http_body::Body for MiddlewareBody<B>
+where
+    B: http_body::Body,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+
+        match this.inner.as_pin_mut() {
+            Some(body) => body
+                .poll_frame(cx)
+                .map_ok(|frame| frame.map_data(|mut data| data.copy_to_bytes(data.remaining()))),
+            None => Poll::Ready(this.data.take().map(|data| Ok(Frame::data(data)))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match &self.inner {
+            Some(body) => body.is_end_stream(),
+            None => self.data.is_none(),
+        }
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        match &self.inner {
+            Some(body) => body.size_hint(),
+            None => http_body::SizeHint::with_exact(self.data.as_ref().map_or(0, |data| data.len() as u64)),
+        }
+    }
+}
+
+/// Builds a plain text response that is sent by a middleware instead of calling the inner service.
+pub(super) fn local_response<B>(status: http::StatusCode) -> http::Response<MiddlewareBody<B>> {
+    let mut response = http::Response::new(MiddlewareBody::full(status.canonical_reason().unwrap_or_default()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
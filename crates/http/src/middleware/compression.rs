@@ -0,0 +1,464 @@
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use http_body::Frame;
+
+use crate::IncomingRequest;
+use crate::service::HttpService;
+
+/// A content coding that can be used by the [`Compression`] middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// `gzip`, supported by every client.
+    Gzip,
+    /// `zstd`, compresses faster and better than gzip at the same level.
+    Zstd,
+    /// `br`, compresses better than gzip, browsers only accept it over HTTPS.
+    Brotli,
+}
+
+impl Encoding {
+    /// The name of the encoding as used in the `Accept-Encoding` and `Content-Encoding` headers.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Zstd => "zstd",
+            Encoding::Brotli => "br",
+        }
+    }
+}
+
+/// How much effort the encoder spends on compressing a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionLevel {
+    /// The fastest level of the encoder.
+    Fastest,
+    /// The default level of the encoder.
+    #[default]
+    Default,
+    /// The level with the best compression, this is very slow and only useful for static content.
+    Best,
+}
+
+/// Settings of the [`Compression`] middleware.
+///
+/// Use [`CompressionConfig::builder`] to create a new configuration or [`CompressionConfig::default`] for the defaults.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct CompressionConfig {
+    /// The encodings the server may use, in order of preference.
+    ///
+    /// When the client accepts multiple of them with the same quality, the first one is used.
+    /// Defaults to zstd, then brotli, then gzip.
+    #[builder(default = vec![Encoding::Zstd, Encoding::Brotli, Encoding::Gzip])]
+    encodings: Vec<Encoding>,
+    /// The compression level.
+    #[builder(default)]
+    level: CompressionLevel,
+    /// Responses with a known size smaller than this are sent uncompressed.
+    ///
+    /// Defaults to 256 bytes.
+    #[builder(default = 256)]
+    min_size: u64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl CompressionConfig {
+    /// Picks the encoding with the highest quality in the `Accept-Encoding` header of the request.
+    fn negotiate(&self, headers: &HeaderMap) -> Option<Encoding> {
+        let mut best: Option<(Encoding, f32)> = None;
+
+        for &encoding in &self.encodings {
+            let mut quality = None;
+            let mut wildcard = None;
+
+            for value in headers.get_all(header::ACCEPT_ENCODING) {
+                let Ok(value) = value.to_str() else {
+                    continue;
+                };
+
+                for item in value.split(',') {
+                    let mut params = item.split(';');
+                    let name = params.next().unwrap_or_default().trim();
+                    let q = params
+                        .filter_map(|param| param.trim().strip_prefix("q="))
+                        .find_map(|q| q.trim().parse::<f32>().ok())
+                        .unwrap_or(1.0);
+
+                    if name.eq_ignore_ascii_case(encoding.as_str()) {
+                        quality = Some(q);
+                    } else if name == "*" {
+                        wildcard = Some(q);
+                    }
+                }
+            }
+
+            let quality = quality.or(wildcard).unwrap_or(0.0);
+            if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+                best = Some((encoding, quality));
+            }
+        }
+
+        best.map(|(encoding, _)| encoding)
+    }
+}
+
+/// Returns `true` if compressing a body of this type is worth it.
+///
+/// Images, video, audio and archives are already compressed and event streams must not be buffered by the encoder.
+fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+
+    match essence.split_once('/') {
+        Some(("text", "event-stream")) => false,
+        Some(("text", _)) => true,
+        Some(("application", subtype)) => {
+            matches!(
+                subtype,
+                "json" | "javascript" | "ecmascript" | "xml" | "wasm" | "x-ndjson" | "graphql-response+json"
+            ) || subtype.ends_with("+json")
+                || subtype.ends_with("+xml")
+        }
+        Some(("image", "svg+xml")) => true,
+        _ => false,
+    }
+}
+
+/// The encoder of a single response body.
+enum Encoder {
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding, level: CompressionLevel) -> io::Result<Self> {
+        match encoding {
+            Encoding::Gzip => {
+                let level = match level {
+                    CompressionLevel::Fastest => flate2::Compression::fast(),
+                    CompressionLevel::Default => flate2::Compression::default(),
+                    CompressionLevel::Best => flate2::Compression::best(),
+                };
+                Ok(Encoder::Gzip(flate2::write::GzEncoder::new(Vec::new(), level)))
+            }
+            Encoding::Zstd => {
+                let level = match level {
+                    CompressionLevel::Fastest => 1,
+                    CompressionLevel::Default => zstd::DEFAULT_COMPRESSION_LEVEL,
+                    CompressionLevel::Best => 19,
+                };
+                Ok(Encoder::Zstd(zstd::stream::write::Encoder::new(Vec::new(), level)?))
+            }
+            Encoding::Brotli => {
+                // The highest levels are too slow to compress responses on the fly
+                let quality = match level {
+                    CompressionLevel::Fastest => 0,
+                    CompressionLevel::Default => 4,
+                    CompressionLevel::Best => 11,
+                };
+                Ok(Encoder::Brotli(Box::new(brotli::CompressorWriter::new(
+                    Vec::new(),
+                    4096,
+                    quality,
+                    22,
+                ))))
+            }
+        }
+    }
+
+    /// Compresses the data and flushes the encoder, so streamed responses are not held back.
+    fn encode(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let output = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Brotli(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(output).into())
+    }
+
+    fn finish(self) -> io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish().map(Into::into),
+            Encoder::Zstd(encoder) => encoder.finish().map(Into::into),
+            Encoder::Brotli(encoder) => Ok(encoder.into_inner().into()),
+        }
+    }
+}
+
+/// An error that can occur when reading a [`CompressionBody`].
+#[derive(thiserror::Error, Debug)]
+pub enum CompressionError<E> {
+    /// An error that occurred while reading the body of the inner service.
+    #[error("body error: {0}")]
+    Body(E),
+    /// An error that occurred while compressing the body.
+    #[error("compression error: {0}")]
+    Io(#[from] io::Error),
+}
+
+pin_project_lite::pin_project! {
+    /// The response body of the [`Compression`] middleware.
+    ///
+    /// Compresses the body of the inner service, or passes it through unchanged when the response is not compressed.
+    pub struct CompressionBody<B> {
+        #[pin]
+        body: B,
+        encoder: Option<Encoder>,
+        trailers: Option<HeaderMap>,
+        done: bool,
+    }
+}
+
+impl<B> std::fmt::Debug for CompressionBody<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressionBody")
+            .field("compressed", &self.encoder.is_some())
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B> http_body::Body for CompressionBody<B>
+where
+    B: http_body::Body,
+{
+    type Data = Bytes;
+    type Error = CompressionError<B::Error>;
+
+    fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+
+        if *this.done {
+            return Poll::Ready(this.trailers.take().map(|trailers| Ok(Frame::trailers(trailers))));
+        }
+
+        loop {
+            let frame = std::task::ready!(this.body.as_mut().poll_frame(cx))
+                .transpose()
+                .map_err(CompressionError::Body)?;
+
+            let Some(encoder) = this.encoder.as_mut() else {
+                if frame.is_none() {
+                    *this.done = true;
+                }
+                return Poll::Ready(frame.map(|frame| Ok(frame.map_data(|mut data| data.copy_to_bytes(data.remaining())))));
+            };
+
+            match frame.map(Frame::into_data) {
+                Some(Ok(mut data)) => {
+                    let compressed = encoder.encode(&data.copy_to_bytes(data.remaining()))?;
+                    if !compressed.is_empty() {
+                        return Poll::Ready(Some(Ok(Frame::data(compressed))));
+                    }
+                }
+                frame => {
+                    *this.done = true;
+                    *this.trailers = frame
+                        .and_then(|frame| frame.err())
+                        .and_then(|frame| frame.into_trailers().ok());
+
+                    let compressed = this.encoder.take().expect("encoder is set").finish()?;
+                    if !compressed.is_empty() {
+                        return Poll::Ready(Some(Ok(Frame::data(compressed))));
+                    }
+
+                    return Poll::Ready(this.trailers.take().map(|trailers| Ok(Frame::trailers(trailers))));
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        if self.done {
+            self.trailers.is_none()
+        } else {
+            self.encoder.is_none() && self.body.is_end_stream()
+        }
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        if self.encoder.is_none() && !self.done {
+            self.body.size_hint()
+        } else {
+            http_body::SizeHint::default()
+        }
+    }
+}
+
+/// Compresses responses with an encoding accepted by the client.
+///
+/// Only responses with a compressible `Content-Type` (text, JSON, JavaScript, XML, WebAssembly and SVG) are compressed.
+/// Responses that already have a `Content-Encoding`, ask for `Cache-Control: no-transform` or are smaller
+/// than [`min_size`](CompressionConfigBuilder::min_size) are sent unchanged.
+///
+/// Create by calling [`Compression::new`] or [`HttpServiceExt::with_compression`](super::HttpServiceExt::with_compression).
+#[derive(Debug, Clone)]
+pub struct Compression<S> {
+    inner: S,
+    config: Arc<CompressionConfig>,
+}
+
+impl<S> Compression<S> {
+    /// Wrap the given service.
+    pub fn new(inner: S, config: CompressionConfig) -> Self {
+        Self {
+            inner,
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl<S> HttpService for Compression<S>
+where
+    S: HttpService + Send,
+    S::ResBody: http_body::Body,
+{
+    type Error = S::Error;
+    type ResBody = CompressionBody<S::ResBody>;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let encoding = if req.method() == Method::HEAD {
+            None
+        } else {
+            self.config.negotiate(req.headers())
+        };
+
+        let (mut parts, body) = self.inner.call(req).await?.into_parts();
+
+        let compressible = !matches!(parts.status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED)
+            && !parts.status.is_informational()
+            && !parts.headers.contains_key(header::CONTENT_ENCODING)
+            && !parts.headers.contains_key(header::CONTENT_RANGE)
+            && !parts
+                .headers
+                .get_all(header::CACHE_CONTROL)
+                .iter()
+                .any(|value| value.to_str().is_ok_and(|value| value.contains("no-transform")))
+            && parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(is_compressible)
+            && http_body::Body::size_hint(&body)
+                .exact()
+                .is_none_or(|size| size >= self.config.min_size);
+
+        if compressible {
+            let varies = parts.headers.get_all(header::VARY).iter().any(|value| {
+                value.to_str().is_ok_and(|value| {
+                    value
+                        .split(',')
+                        .any(|name| name.trim() == "*" || name.trim().eq_ignore_ascii_case("accept-encoding"))
+                })
+            });
+            if !varies {
+                parts
+                    .headers
+                    .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            }
+        }
+
+        let encoder = encoding
+            .filter(|_| compressible)
+            .and_then(|encoding| Some((encoding, Encoder::new(encoding, self.config.level).ok()?)));
+
+        let encoder = encoder.map(|(encoding, encoder)| {
+            parts
+                .headers
+                .insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts.headers.remove(header::ACCEPT_RANGES);
+            encoder
+        });
+
+        Ok(http::Response::from_parts(
+            parts,
+            CompressionBody {
+                body,
+                encoder,
+                trailers: None,
+                done: false,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use http::{HeaderMap, HeaderValue, header};
+
+    use super::{CompressionConfig, Encoding};
+
+    fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_str(accept_encoding).unwrap());
+        CompressionConfig::default().negotiate(&headers)
+    }
+
+    #[test]
+    fn negotiate_encoding() {
+        assert_eq!(negotiate("gzip, deflate, br, zstd"), Some(Encoding::Zstd));
+        assert_eq!(negotiate("gzip, deflate, br"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("gzip, deflate"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("gzip, br;q=0.5"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("gzip;q=1.0, zstd;q=0.5"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("zstd;q=0, *"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("zstd;q=0, br;q=0, *"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("identity"), None);
+        assert_eq!(negotiate("*;q=0"), None);
+        assert_eq!(CompressionConfig::default().negotiate(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn brotli_round_trip() {
+        let mut encoder = super::Encoder::new(Encoding::Brotli, super::CompressionLevel::Default).unwrap();
+        let mut compressed = Vec::new();
+
+        // Every chunk is flushed, so it can be decoded before the response ends
+        compressed.extend_from_slice(&encoder.encode(b"hello ").unwrap());
+        let mut decoded = [0; 6];
+        std::io::Read::read_exact(&mut brotli::Decompressor::new(&compressed[..], 4096), &mut decoded).unwrap();
+        assert_eq!(&decoded, b"hello ");
+
+        compressed.extend_from_slice(&encoder.encode(b"world").unwrap());
+        compressed.extend_from_slice(&encoder.finish().unwrap());
+
+        let mut decoded = String::new();
+        std::io::Read::read_to_string(&mut brotli::Decompressor::new(&compressed[..], 4096), &mut decoded).unwrap();
+        assert_eq!(decoded, "hello world");
+    }
+
+    #[test]
+    fn compressible_content_types() {
+        assert!(super::is_compressible("text/html; charset=utf-8"));
+        assert!(super::is_compressible("application/json"));
+        assert!(super::is_compressible("application/problem+json"));
+        assert!(super::is_compressible("image/svg+xml"));
+        assert!(!super::is_compressible("text/event-stream"));
+        assert!(!super::is_compressible("image/png"));
+        assert!(!super::is_compressible("application/octet-stream"));
+    }
+}
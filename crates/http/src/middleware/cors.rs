@@ -0,0 +1,233 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
+
+use super::body::MiddlewareBody;
+use crate::IncomingRequest;
+use crate::service::HttpService;
+
+/// The origins that are allowed to make cross-origin requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllowOrigin {
+    /// Any origin.
+    ///
+    /// Responds with `Access-Control-Allow-Origin: *`, or with the origin of the request when credentials are allowed.
+    Any,
+    /// Only the listed origins, for example `https://scuffle.cloud`.
+    List(Vec<HeaderValue>),
+}
+
+/// Settings of the [`Cors`] middleware.
+///
+/// Use [`CorsConfig::builder`] to create a new configuration or [`CorsConfig::default`] for the defaults,
+/// which allow `GET`, `HEAD` and `POST` requests from any origin.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct CorsConfig {
+    /// The origins that are allowed to make cross-origin requests.
+    #[builder(default = AllowOrigin::Any)]
+    allow_origin: AllowOrigin,
+    /// The methods that are allowed in cross-origin requests.
+    #[builder(default = vec![Method::GET, Method::HEAD, Method::POST])]
+    allow_methods: Vec<Method>,
+    /// The request headers that are allowed in cross-origin requests, in addition to the CORS-safelisted headers.
+    #[builder(default)]
+    allow_headers: Vec<HeaderName>,
+    /// The response headers that scripts are allowed to read, in addition to the CORS-safelisted headers.
+    #[builder(default)]
+    expose_headers: Vec<HeaderName>,
+    /// Allow requests with credentials (cookies, TLS client certificates and `Authorization` headers).
+    #[builder(default)]
+    allow_credentials: bool,
+    /// How long the result of a preflight request can be cached by the browser.
+    max_age: Option<Duration>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// The header values derived from a [`CorsConfig`], computed once when the middleware is created.
+#[derive(Debug)]
+struct CorsHeaders {
+    allow_origin: AllowOrigin,
+    allow_methods: HeaderValue,
+    allow_headers: Option<HeaderValue>,
+    expose_headers: Option<HeaderValue>,
+    allow_credentials: bool,
+    max_age: Option<HeaderValue>,
+}
+
+fn join<T: AsRef<str>>(items: &[T]) -> Option<HeaderValue> {
+    if items.is_empty() {
+        return None;
+    }
+
+    let joined = items.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(", ");
+    Some(HeaderValue::from_str(&joined).expect("methods and header names are valid header values"))
+}
+
+impl From<CorsConfig> for CorsHeaders {
+    fn from(config: CorsConfig) -> Self {
+        Self {
+            allow_origin: config.allow_origin,
+            allow_methods: join(&config.allow_methods).unwrap_or(HeaderValue::from_static("")),
+            allow_headers: join(&config.allow_headers),
+            expose_headers: join(&config.expose_headers),
+            allow_credentials: config.allow_credentials,
+            max_age: config.max_age.map(|max_age| HeaderValue::from(max_age.as_secs())),
+        }
+    }
+}
+
+impl CorsHeaders {
+    /// Returns the value of `Access-Control-Allow-Origin` for the origin of a request, if it is allowed.
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.allow_origin {
+            AllowOrigin::Any if self.allow_credentials => Some(origin.clone()),
+            AllowOrigin::Any => Some(HeaderValue::from_static("*")),
+            AllowOrigin::List(origins) => origins.contains(origin).then(|| origin.clone()),
+        }
+    }
+
+    /// The headers sent in response to every request from an allowed origin.
+    fn apply(&self, headers: &mut HeaderMap, allow_origin: HeaderValue) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.allow_credentials {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+
+    /// The response to a preflight request.
+    fn preflight<B>(&self, origin: Option<HeaderValue>) -> http::Response<MiddlewareBody<B>> {
+        let mut response = http::Response::new(MiddlewareBody::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+
+        let headers = response.headers_mut();
+        add_vary(headers);
+
+        if let Some(allow_origin) = origin.as_ref().and_then(|origin| self.allow_origin(origin)) {
+            self.apply(headers, allow_origin);
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, self.allow_methods.clone());
+            if let Some(allow_headers) = &self.allow_headers {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allow_headers.clone());
+            }
+            if let Some(max_age) = &self.max_age {
+                headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.clone());
+            }
+        }
+
+        response
+    }
+}
+
+/// Responses depend on the origin of the request, even when it is not allowed.
+fn add_vary(headers: &mut HeaderMap) {
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+}
+
+/// Handles [Cross-Origin Resource Sharing](https://developer.mozilla.org/en-US/docs/Web/HTTP/Guides/CORS).
+///
+/// Preflight requests (`OPTIONS` requests with an `Access-Control-Request-Method` header) are answered
+/// by the middleware and never reach the inner service.
+/// Responses to all other requests from an allowed origin get the `Access-Control-Allow-Origin` header.
+///
+/// Create by calling [`Cors::new`] or [`HttpServiceExt::with_cors`](super::HttpServiceExt::with_cors).
+#[derive(Debug, Clone)]
+pub struct Cors<S> {
+    inner: S,
+    headers: Arc<CorsHeaders>,
+}
+
+impl<S> Cors<S> {
+    /// Wrap the given service.
+    pub fn new(inner: S, config: CorsConfig) -> Self {
+        Self {
+            inner,
+            headers: Arc::new(config.into()),
+        }
+    }
+}
+
+impl<S> HttpService for Cors<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = MiddlewareBody<S::ResBody>;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let origin = req.headers().get(header::ORIGIN).cloned();
+
+        if req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD) {
+            return Ok(self.headers.preflight(origin));
+        }
+
+        let mut response = self.inner.call(req).await?.map(MiddlewareBody::inner);
+
+        let headers = response.headers_mut();
+        add_vary(headers);
+
+        if let Some(allow_origin) = origin.as_ref().and_then(|origin| self.headers.allow_origin(origin)) {
+            self.headers.apply(headers, allow_origin);
+            if let Some(expose_headers) = &self.headers.expose_headers {
+                headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, expose_headers.clone());
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use http::{HeaderValue, Method, StatusCode, header};
+
+    use super::{AllowOrigin, CorsConfig, CorsHeaders};
+
+    #[test]
+    fn preflight() {
+        let headers = CorsHeaders::from(
+            CorsConfig::builder()
+                .allow_origin(AllowOrigin::List(vec![HeaderValue::from_static("https://scuffle.cloud")]))
+                .allow_methods(vec![Method::GET, Method::PUT])
+                .allow_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+                .allow_credentials(true)
+                .max_age(std::time::Duration::from_secs(600))
+                .build(),
+        );
+
+        let response = headers.preflight::<String>(Some(HeaderValue::from_static("https://scuffle.cloud")));
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://scuffle.cloud"
+        );
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT");
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "authorization, content-type"
+        );
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_MAX_AGE], "600");
+
+        let response = headers.preflight::<String>(Some(HeaderValue::from_static("https://example.com")));
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert_eq!(response.headers()[header::VARY], "origin");
+    }
+
+    #[test]
+    fn allow_any_origin() {
+        let origin = HeaderValue::from_static("https://example.com");
+
+        let headers = CorsHeaders::from(CorsConfig::default());
+        assert_eq!(headers.allow_origin(&origin), Some(HeaderValue::from_static("*")));
+
+        let headers = CorsHeaders::from(CorsConfig::builder().allow_credentials(true).build());
+        assert_eq!(headers.allow_origin(&origin), Some(origin));
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::{HeaderValue, StatusCode, header};
+
+use super::body::{MiddlewareBody, local_response};
+use crate::IncomingRequest;
+use crate::body::{IncomingBody, LimitedBody};
+use crate::extensions::ClientAddr;
+use crate::service::HttpService;
+
+/// Returns the ip address of the client that sent the request.
+fn client_ip(req: &IncomingRequest) -> Option<IpAddr> {
+    req.extensions().get::<ClientAddr>().map(|addr| addr.ip().to_canonical())
+}
+
+/// Limits the size of request bodies.
+///
+/// Requests with a `Content-Length` larger than the limit are rejected with `413 Payload Too Large`
+/// without calling the inner service. For all other requests reading the body fails with
+/// [`IncomingBodyError::LengthLimitExceeded`](crate::body::IncomingBodyError::LengthLimitExceeded)
+/// once more than the limit has been read.
+///
+/// Create by calling [`BodyLimit::new`] or [`HttpServiceExt::with_body_limit`](super::HttpServiceExt::with_body_limit).
+#[derive(Debug, Clone)]
+pub struct BodyLimit<S> {
+    inner: S,
+    limit: u64,
+}
+
+impl<S> BodyLimit<S> {
+    /// Wrap the given service, allowing request bodies of at most `limit` bytes.
+    pub fn new(inner: S, limit: u64) -> Self {
+        Self { inner, limit }
+    }
+}
+
+impl<S> HttpService for BodyLimit<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = MiddlewareBody<S::ResBody>;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let content_length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+
+        if content_length.is_some_and(|len| len > self.limit) {
+            return Ok(local_response(StatusCode::PAYLOAD_TOO_LARGE));
+        }
+
+        let limit = self.limit;
+        let req = req.map(|body| IncomingBody::Limited(Box::new(LimitedBody::new(body, limit))));
+
+        Ok(self.inner.call(req).await?.map(MiddlewareBody::inner))
+    }
+}
+
+/// The number of in-flight requests of every client.
+#[derive(Debug, Default)]
+struct InFlight(Mutex<HashMap<IpAddr, usize>>);
+
+/// Releases the slot of a request when it is dropped.
+struct InFlightGuard<'a> {
+    in_flight: &'a InFlight,
+    ip: IpAddr,
+}
+
+impl InFlight {
+    fn acquire(&self, ip: IpAddr, max: usize) -> Option<InFlightGuard<'_>> {
+        let mut in_flight = self.0.lock().expect("poisoned");
+        let count = in_flight.entry(ip).or_default();
+        if *count >= max {
+            return None;
+        }
+
+        *count += 1;
+        Some(InFlightGuard { in_flight: self, ip })
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.0.lock().expect("poisoned");
+        if let Some(count) = in_flight.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Limits the number of concurrent requests of every client, keyed by the ip address in [`ClientAddr`].
+///
+/// A request counts until the inner service has returned the response headers, the response body is not tracked.
+/// Requests above the limit are rejected with `429 Too Many Requests`.
+///
+/// All clones of the middleware share the same limit, so the limit applies across all connections of a client
+/// when the service is cloned for every connection.
+///
+/// Create by calling [`ConcurrencyLimit::new`] or
+/// [`HttpServiceExt::with_concurrency_limit`](super::HttpServiceExt::with_concurrency_limit).
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit<S> {
+    inner: S,
+    max_per_client: usize,
+    in_flight: Arc<InFlight>,
+}
+
+impl<S> ConcurrencyLimit<S> {
+    /// Wrap the given service, allowing at most `max_per_client` concurrent requests per client.
+    pub fn new(inner: S, max_per_client: usize) -> Self {
+        Self {
+            inner,
+            max_per_client,
+            in_flight: Arc::default(),
+        }
+    }
+}
+
+impl<S> HttpService for ConcurrencyLimit<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = MiddlewareBody<S::ResBody>;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let in_flight = self.in_flight.clone();
+
+        let _guard = match client_ip(&req) {
+            Some(ip) => match in_flight.acquire(ip, self.max_per_client) {
+                Some(guard) => Some(guard),
+                None => return Ok(local_response(StatusCode::TOO_MANY_REQUESTS)),
+            },
+            None => None,
+        };
+
+        Ok(self.inner.call(req).await?.map(MiddlewareBody::inner))
+    }
+}
+
+/// Settings of the [`RateLimit`] middleware.
+///
+/// Every client has a bucket of [`burst`](RateLimitConfigBuilder::burst) tokens that is refilled at a rate of
+/// [`requests`](RateLimitConfigBuilder::requests) tokens [`per`](RateLimitConfigBuilder::per) period.
+/// Every request takes one token.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct RateLimitConfig {
+    /// The number of requests a client can make per period.
+    requests: u32,
+    /// The period.
+    ///
+    /// Defaults to one second.
+    #[builder(default = Duration::from_secs(1))]
+    per: Duration,
+    /// The number of requests a client can make at once after being idle.
+    ///
+    /// Defaults to [`requests`](RateLimitConfigBuilder::requests).
+    burst: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The token buckets of all clients.
+#[derive(Debug)]
+struct Buckets {
+    /// Tokens added per second.
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketsState>,
+}
+
+#[derive(Debug)]
+struct BucketsState {
+    buckets: HashMap<IpAddr, Bucket>,
+    last_prune: Instant,
+}
+
+impl Buckets {
+    fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            rate: f64::from(config.requests.max(1)) / config.per.as_secs_f64(),
+            capacity: f64::from(config.burst.unwrap_or(config.requests).max(1)),
+            state: Mutex::new(BucketsState {
+                buckets: HashMap::new(),
+                last_prune: Instant::now(),
+            }),
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity);
+        bucket.updated = now;
+    }
+
+    /// Takes a token from the bucket of the client, or returns the time until the next token is available.
+    fn take(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("poisoned");
+
+        // Forget clients with a full bucket, they would start with a full bucket anyway.
+        let prune_interval = Duration::from_secs_f64(self.capacity / self.rate).max(Duration::from_secs(1));
+        if now.saturating_duration_since(state.last_prune) >= prune_interval {
+            state.last_prune = now;
+            state.buckets.retain(|_, bucket| {
+                self.refill(bucket, now);
+                bucket.tokens < self.capacity
+            });
+        }
+
+        let bucket = state.buckets.entry(ip).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        self.refill(bucket, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+/// Limits the rate of requests of every client, keyed by the ip address in [`ClientAddr`].
+///
+/// Requests above the limit are rejected with `429 Too Many Requests` and a `Retry-After` header.
+///
+/// All clones of the middleware share the same limit, so the limit applies across all connections of a client
+/// when the service is cloned for every connection.
+///
+/// Create by calling [`RateLimit::new`] or [`HttpServiceExt::with_rate_limit`](super::HttpServiceExt::with_rate_limit).
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    buckets: Arc<Buckets>,
+}
+
+impl<S> RateLimit<S> {
+    /// Wrap the given service.
+    pub fn new(inner: S, config: RateLimitConfig) -> Self {
+        Self {
+            inner,
+            buckets: Arc::new(Buckets::new(&config)),
+        }
+    }
+}
+
+impl<S> HttpService for RateLimit<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = MiddlewareBody<S::ResBody>;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        if let Some(ip) = client_ip(&req)
+            && let Err(retry_after) = self.buckets.take(ip)
+        {
+            let mut response = local_response(StatusCode::TOO_MANY_REQUESTS);
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+            );
+            return Ok(response);
+        }
+
+        Ok(self.inner.call(req).await?.map(MiddlewareBody::inner))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use super::{Buckets, InFlight, RateLimitConfig};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER_CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    #[test]
+    fn in_flight() {
+        let in_flight = InFlight::default();
+
+        let first = in_flight.acquire(CLIENT, 2).expect("first request is allowed");
+        let second = in_flight.acquire(CLIENT, 2).expect("second request is allowed");
+        assert!(in_flight.acquire(CLIENT, 2).is_none());
+        assert!(in_flight.acquire(OTHER_CLIENT, 2).is_some());
+
+        drop(first);
+        assert!(in_flight.acquire(CLIENT, 2).is_some());
+
+        drop(second);
+        assert!(in_flight.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn token_bucket() {
+        let buckets = Buckets::new(
+            &RateLimitConfig::builder()
+                .requests(1)
+                .per(Duration::from_secs(10))
+                .burst(2)
+                .build(),
+        );
+
+        assert!(buckets.take(CLIENT).is_ok());
+        assert!(buckets.take(CLIENT).is_ok());
+        let retry_after = buckets.take(CLIENT).expect_err("bucket is empty");
+        assert!(retry_after > Duration::from_secs(9) && retry_after <= Duration::from_secs(10));
+
+        assert!(buckets.take(OTHER_CLIENT).is_ok());
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use http::{HeaderName, HeaderValue};
+
+use crate::IncomingRequest;
+use crate::extensions::RequestId;
+use crate::service::HttpService;
+
+/// The longest request id that is accepted from a client.
+const MAX_INCOMING_LEN: usize = 128;
+
+/// Settings of the [`SetRequestId`] middleware.
+///
+/// Use [`RequestIdConfig::builder`] to create a new configuration or [`RequestIdConfig::default`] for the defaults.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct RequestIdConfig {
+    /// The request and response header that carries the id.
+    ///
+    /// Defaults to `x-request-id`.
+    #[builder(default = HeaderName::from_static("x-request-id"))]
+    header: HeaderName,
+    /// Keep the id sent by the client (or a proxy in front of the server) instead of generating a new one.
+    ///
+    /// Ids longer than 128 bytes or with characters other than visible ASCII are always replaced.
+    /// Enabled by default.
+    #[builder(default = true)]
+    trust_incoming: bool,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Generates a new random request id, formatted as 32 hex characters.
+fn generate() -> HeaderValue {
+    HeaderValue::from_str(&format!("{:032x}", rand::random::<u128>())).expect("hex is a valid header value")
+}
+
+/// Assigns an id to every request and propagates it to the response.
+///
+/// The id is added to the request as the [`RequestId`] extension and to the request and response headers.
+///
+/// Create by calling [`SetRequestId::new`] or [`HttpServiceExt::with_request_id`](super::HttpServiceExt::with_request_id).
+#[derive(Debug, Clone)]
+pub struct SetRequestId<S> {
+    inner: S,
+    config: Arc<RequestIdConfig>,
+}
+
+impl<S> SetRequestId<S> {
+    /// Wrap the given service.
+    pub fn new(inner: S, config: RequestIdConfig) -> Self {
+        Self {
+            inner,
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl<S> HttpService for SetRequestId<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = S::ResBody;
+
+    async fn call(&mut self, mut req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let incoming = req.headers().get(&self.config.header).filter(|id| {
+            self.config.trust_incoming
+                && !id.is_empty()
+                && id.len() <= MAX_INCOMING_LEN
+                && id.as_bytes().iter().all(|b| b.is_ascii_graphic())
+        });
+
+        let id = match incoming {
+            Some(id) => id.clone(),
+            None => {
+                let id = generate();
+                req.headers_mut().insert(self.config.header.clone(), id.clone());
+                id
+            }
+        };
+
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        let mut response = self.inner.call(req).await?;
+        response.headers_mut().insert(self.config.header.clone(), id);
+
+        Ok(response)
+    }
+}
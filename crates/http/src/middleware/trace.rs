@@ -0,0 +1,101 @@
+use std::time::Instant;
+
+use http::{Method, Version};
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
+
+use crate::IncomingRequest;
+use crate::service::HttpService;
+
+#[scuffle_metrics::metrics(rename = "http_server")]
+mod http_server {
+    use scuffle_metrics::{CounterU64, HistogramF64};
+
+    /// The number of handled requests, by response status.
+    ///
+    /// Requests that failed with an error of the service have no status.
+    #[metrics(unit = "requests")]
+    pub(super) fn requests(method: super::Method, version: &'static str, status: Option<u16>) -> CounterU64;
+
+    /// The time it took the service to return the response headers.
+    #[metrics(unit = "seconds")]
+    pub(super) fn duration(method: super::Method, version: &'static str, status: Option<u16>) -> HistogramF64;
+}
+
+fn version_label(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "0.9",
+        Version::HTTP_10 => "1.0",
+        Version::HTTP_11 => "1.1",
+        Version::HTTP_2 => "2",
+        Version::HTTP_3 => "3",
+        _ => "unknown",
+    }
+}
+
+/// Records metrics for every request and, with the `tracing` feature, runs the inner service in a request span.
+///
+/// The `http_server_requests` counter and the `http_server_duration` histogram are labeled with
+/// the method, the HTTP version and the response status.
+///
+/// The span is named `request` and records the method, path, version, client address and,
+/// when [`SetRequestId`](super::SetRequestId) runs before this middleware, the request id.
+///
+/// Create by calling [`Trace::new`] or [`HttpServiceExt::with_trace`](super::HttpServiceExt::with_trace).
+#[derive(Debug, Clone)]
+pub struct Trace<S> {
+    inner: S,
+}
+
+impl<S> Trace<S> {
+    /// Wrap the given service.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> HttpService for Trace<S>
+where
+    S: HttpService + Send,
+{
+    type Error = S::Error;
+    type ResBody = S::ResBody;
+
+    async fn call(&mut self, req: IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        let method = req.method().clone();
+        let version = version_label(req.version());
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "request",
+            method = %method,
+            path = req.uri().path(),
+            version,
+            client_addr = req.extensions().get::<crate::extensions::ClientAddr>().map(|addr| tracing::field::display(**addr)),
+            request_id = req.extensions().get::<crate::extensions::RequestId>().and_then(|id| id.to_str().ok()),
+            status = tracing::field::Empty,
+        );
+
+        let start = Instant::now();
+        let fut = self.inner.call(req);
+        #[cfg(feature = "tracing")]
+        let fut = fut.instrument(span.clone());
+        let result = fut.await;
+        let elapsed = start.elapsed();
+
+        let status = result.as_ref().ok().map(|response| response.status().as_u16());
+
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(status) = status {
+                span.record("status", status);
+            }
+            span.in_scope(|| tracing::debug!(status, elapsed = ?elapsed, "request handled"));
+        }
+
+        http_server::requests(method.clone(), version, status).incr();
+        http_server::duration(method, version, status).observe(elapsed.as_secs_f64());
+
+        result
+    }
+}
@@ -1,7 +1,9 @@
 use std::fmt::Debug;
 use std::net::SocketAddr;
 
+use crate::connection::ConnectionConfig;
 use crate::error::HttpError;
+use crate::listener::{Listener, ListenerSocket};
 use crate::service::{HttpService, HttpServiceFactory};
 
 /// The HTTP server.
@@ -25,7 +27,9 @@ pub struct HttpServer<F> {
     ///
     /// Use `[::]` for a dual-stack listener.
     /// For example, use `[::]:80` to bind to port 80 on both IPv4 and IPv6.
-    bind: SocketAddr,
+    ///
+    /// Can be omitted when the server only serves on additional [`listeners`](HttpServerBuilder::listeners).
+    bind: Option<SocketAddr>,
     /// Enable HTTP/1.1.
     #[builder(default = true)]
     #[cfg(feature = "http1")]
@@ -41,8 +45,22 @@ pub struct HttpServer<F> {
     ///
     /// Use this field to set the server into TLS mode.
     /// It will only accept TLS connections when this is set.
+    ///
+    /// Use [`CertStore::server_config`](crate::tls::CertStore::server_config) to select certificates by SNI
+    /// and to replace them without restarting the server.
     #[cfg(feature = "tls-rustls")]
     rustls_config: Option<tokio_rustls::rustls::ServerConfig>,
+    /// Additional listeners, for example an internal plaintext listener or a Unix domain socket next to the public one.
+    ///
+    /// The TLS and protocol settings above only apply to the [`bind`](HttpServerBuilder::bind) address,
+    /// every listener has its own.
+    #[builder(default)]
+    listeners: Vec<Listener>,
+    /// Connection limits, timeouts and shutdown behavior.
+    ///
+    /// See [`ConnectionConfig`] for the defaults.
+    #[builder(default)]
+    connection_config: ConnectionConfig,
 }
 
 #[cfg(feature = "http3")]
@@ -142,33 +160,6 @@ where
     <<F::Service as HttpService>::ResBody as http_body::Body>::Data: Send,
     <<F::Service as HttpService>::ResBody as http_body::Body>::Error: std::error::Error + Send + Sync,
 {
-    #[cfg(feature = "tls-rustls")]
-    fn set_alpn_protocols(&mut self) {
-        let Some(rustls_config) = &mut self.rustls_config else {
-            return;
-        };
-
-        // https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml#alpn-protocol-ids
-        if rustls_config.alpn_protocols.is_empty() {
-            #[cfg(feature = "http1")]
-            if self.enable_http1 {
-                rustls_config.alpn_protocols.push(b"http/1.0".to_vec());
-                rustls_config.alpn_protocols.push(b"http/1.1".to_vec());
-            }
-
-            #[cfg(feature = "http2")]
-            if self.enable_http2 {
-                rustls_config.alpn_protocols.push(b"h2".to_vec());
-                rustls_config.alpn_protocols.push(b"h2c".to_vec());
-            }
-
-            #[cfg(feature = "http3")]
-            if self.enable_http3 {
-                rustls_config.alpn_protocols.push(b"h3".to_vec());
-            }
-        }
-    }
-
     /// Run the server.
     ///
     /// This will:
@@ -176,116 +167,225 @@ where
     /// - Start listening on all configured interfaces for incoming connections.
     /// - Accept all incoming connections.
     /// - Handle incoming requests by passing them to the configured service factory.
-    pub async fn run(#[allow(unused_mut)] mut self) -> Result<(), HttpError<F>> {
-        #[cfg(feature = "tls-rustls")]
-        self.set_alpn_protocols();
-
-        #[cfg(all(not(any(feature = "http1", feature = "http2")), feature = "tls-rustls"))]
-        let start_tcp_backend = false;
-        #[cfg(all(feature = "http1", not(feature = "http2")))]
-        let start_tcp_backend = self.enable_http1;
-        #[cfg(all(not(feature = "http1"), feature = "http2"))]
-        let start_tcp_backend = self.enable_http2;
-        #[cfg(all(feature = "http1", feature = "http2"))]
-        let start_tcp_backend = self.enable_http1 || self.enable_http2;
-
-        #[cfg(feature = "tls-rustls")]
-        if let Some(_rustls_config) = self.rustls_config {
-            #[cfg(not(feature = "http3"))]
-            let enable_http3 = false;
-            #[cfg(feature = "http3")]
-            let enable_http3 = self.enable_http3;
+    pub async fn run(self) -> Result<(), HttpError<F>> {
+        let mut listeners = Vec::with_capacity(self.listeners.len() + 1);
 
-            match (start_tcp_backend, enable_http3) {
+        if let Some(bind) = self.bind {
+            listeners.push(Listener {
+                socket: ListenerSocket::Bind(bind),
+                name: None,
+                #[cfg(feature = "http1")]
+                enable_http1: self.enable_http1,
+                #[cfg(feature = "http2")]
+                enable_http2: self.enable_http2,
                 #[cfg(feature = "http3")]
-                (false, true) => {
-                    let backend = crate::backend::h3::Http3Backend::builder()
-                        .ctx(self.ctx)
-                        .worker_tasks(self.worker_tasks)
-                        .service_factory(self.service_factory)
-                        .bind(self.bind)
-                        .rustls_config(_rustls_config)
-                        .build();
-
-                    return backend.run().await;
-                }
-                #[cfg(any(feature = "http1", feature = "http2"))]
-                (true, false) => {
-                    let builder = crate::backend::hyper::HyperBackend::builder()
-                        .ctx(self.ctx)
-                        .worker_tasks(self.worker_tasks)
-                        .service_factory(self.service_factory)
-                        .bind(self.bind)
-                        .rustls_config(_rustls_config);
-
-                    #[cfg(feature = "http1")]
-                    let builder = builder.http1_enabled(self.enable_http1);
-
-                    #[cfg(feature = "http2")]
-                    let builder = builder.http2_enabled(self.enable_http2);
-
-                    return builder.build().run().await;
-                }
-                #[cfg(all(any(feature = "http1", feature = "http2"), feature = "http3"))]
-                (true, true) => {
-                    let builder = crate::backend::hyper::HyperBackend::builder()
-                        .ctx(self.ctx.clone())
-                        .worker_tasks(self.worker_tasks)
-                        .service_factory(self.service_factory.clone())
-                        .bind(self.bind)
-                        .rustls_config(_rustls_config.clone());
-
-                    #[cfg(feature = "http1")]
-                    let builder = builder.http1_enabled(self.enable_http1);
-
-                    #[cfg(feature = "http2")]
-                    let builder = builder.http2_enabled(self.enable_http2);
-
-                    let hyper = std::pin::pin!(builder.build().run());
-
-                    let http3 = crate::backend::h3::Http3Backend::builder()
-                        .ctx(self.ctx)
-                        .worker_tasks(self.worker_tasks)
-                        .service_factory(self.service_factory)
-                        .bind(self.bind)
-                        .rustls_config(_rustls_config)
-                        .build()
-                        .run();
-                    let http3 = std::pin::pin!(http3);
-
-                    let res = futures::future::select(hyper, http3).await;
-                    match res {
-                        futures::future::Either::Left((res, _)) => return res,
-                        futures::future::Either::Right((res, _)) => return res,
-                    }
-                }
-                _ => return Ok(()),
-            }
+                enable_http3: self.enable_http3,
+                #[cfg(feature = "tls-rustls")]
+                rustls_config: self.rustls_config,
+            });
+        }
+
+        listeners.extend(self.listeners);
+
+        let listeners = listeners.into_iter().map(|listener| {
+            run_listener(
+                listener,
+                self.ctx.clone(),
+                self.worker_tasks,
+                self.service_factory.clone(),
+                self.connection_config.clone(),
+            )
+        });
+
+        futures::future::try_join_all(listeners).await?;
+
+        Ok(())
+    }
+}
 
-            // This line must be unreachable
+#[cfg(feature = "tls-rustls")]
+fn set_alpn_protocols(listener: &mut Listener) {
+    let Some(rustls_config) = &mut listener.rustls_config else {
+        return;
+    };
+
+    // https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml#alpn-protocol-ids
+    // acme-tls/1 is only used to answer TLS-ALPN-01 challenges, the defaults are still needed
+    if rustls_config
+        .alpn_protocols
+        .iter()
+        .all(|protocol| protocol == crate::tls::ACME_TLS_ALPN_NAME)
+    {
+        #[cfg(feature = "http1")]
+        if listener.enable_http1 {
+            rustls_config.alpn_protocols.push(b"http/1.0".to_vec());
+            rustls_config.alpn_protocols.push(b"http/1.1".to_vec());
         }
 
-        // At this point we know that we are not using TLS either
-        // - because the feature is disabled
-        // - or because it's enabled but the config is None.
+        #[cfg(feature = "http2")]
+        if listener.enable_http2 {
+            rustls_config.alpn_protocols.push(b"h2".to_vec());
+            rustls_config.alpn_protocols.push(b"h2c".to_vec());
+        }
 
-        #[cfg(any(feature = "http1", feature = "http2"))]
-        if start_tcp_backend {
-            let builder = crate::backend::hyper::HyperBackend::builder()
-                .ctx(self.ctx)
-                .worker_tasks(self.worker_tasks)
-                .service_factory(self.service_factory)
-                .bind(self.bind);
+        #[cfg(feature = "http3")]
+        if listener.enable_http3 || listener.socket.is_udp() {
+            rustls_config.alpn_protocols.push(b"h3".to_vec());
+        }
+    }
+}
 
-            #[cfg(feature = "http1")]
-            let builder = builder.http1_enabled(self.enable_http1);
+/// Runs the backends of a single listener.
+#[allow(unused_mut, unused_variables)]
+async fn run_listener<F>(
+    mut listener: Listener,
+    ctx: scuffle_context::Context,
+    worker_tasks: usize,
+    service_factory: F,
+    connection_config: ConnectionConfig,
+) -> Result<(), HttpError<F>>
+where
+    F: HttpServiceFactory + Clone + Send + 'static,
+    F::Error: std::error::Error + Send,
+    F::Service: Clone + Send + 'static,
+    <F::Service as HttpService>::Error: std::error::Error + Send + Sync,
+    <F::Service as HttpService>::ResBody: Send,
+    <<F::Service as HttpService>::ResBody as http_body::Body>::Data: Send,
+    <<F::Service as HttpService>::ResBody as http_body::Body>::Error: std::error::Error + Send + Sync,
+{
+    #[cfg(feature = "tls-rustls")]
+    set_alpn_protocols(&mut listener);
+
+    let mut extensions = http::Extensions::new();
+    if let Some(name) = listener.name {
+        extensions.insert(crate::extensions::ListenerName(name));
+    }
+
+    // UDP sockets can only serve HTTP/3
+    let is_udp = listener.socket.is_udp();
+
+    #[cfg(all(not(any(feature = "http1", feature = "http2")), feature = "tls-rustls"))]
+    let start_tcp_backend = false;
+    #[cfg(all(feature = "http1", not(feature = "http2")))]
+    let start_tcp_backend = listener.enable_http1 && !is_udp;
+    #[cfg(all(not(feature = "http1"), feature = "http2"))]
+    let start_tcp_backend = listener.enable_http2 && !is_udp;
+    #[cfg(all(feature = "http1", feature = "http2"))]
+    let start_tcp_backend = (listener.enable_http1 || listener.enable_http2) && !is_udp;
+
+    #[cfg(feature = "tls-rustls")]
+    if let Some(_rustls_config) = listener.rustls_config {
+        #[cfg(not(feature = "http3"))]
+        let enable_http3 = false;
+        #[cfg(feature = "http3")]
+        let enable_http3 = listener.enable_http3 || is_udp;
+
+        match (start_tcp_backend, enable_http3) {
+            #[cfg(feature = "http3")]
+            (false, true) => {
+                let backend = crate::backend::h3::Http3Backend::builder()
+                    .ctx(ctx)
+                    .worker_tasks(worker_tasks)
+                    .service_factory(service_factory)
+                    .bind(listener.socket)
+                    .extensions(extensions)
+                    .rustls_config(_rustls_config)
+                    .connection_config(connection_config)
+                    .build();
+
+                return backend.run().await;
+            }
+            #[cfg(any(feature = "http1", feature = "http2"))]
+            (true, false) => {
+                let builder = crate::backend::hyper::HyperBackend::builder()
+                    .ctx(ctx)
+                    .worker_tasks(worker_tasks)
+                    .service_factory(service_factory)
+                    .bind(listener.socket)
+                    .extensions(extensions)
+                    .rustls_config(_rustls_config)
+                    .connection_config(connection_config);
+
+                #[cfg(feature = "http1")]
+                let builder = builder.http1_enabled(listener.enable_http1);
 
-            #[cfg(feature = "http2")]
-            let builder = builder.http2_enabled(self.enable_http2);
+                #[cfg(feature = "http2")]
+                let builder = builder.http2_enabled(listener.enable_http2);
 
-            return builder.build().run().await;
+                return builder.build().run().await;
+            }
+            #[cfg(all(any(feature = "http1", feature = "http2"), feature = "http3"))]
+            (true, true) => {
+                let builder = crate::backend::hyper::HyperBackend::builder()
+                    .ctx(ctx.clone())
+                    .worker_tasks(worker_tasks)
+                    .service_factory(service_factory.clone())
+                    .bind(listener.socket.clone())
+                    .extensions(extensions.clone())
+                    .rustls_config(_rustls_config.clone())
+                    .connection_config(connection_config.clone());
+
+                #[cfg(feature = "http1")]
+                let builder = builder.http1_enabled(listener.enable_http1);
+
+                #[cfg(feature = "http2")]
+                let builder = builder.http2_enabled(listener.enable_http2);
+
+                let hyper = std::pin::pin!(builder.build().run());
+
+                let http3 = crate::backend::h3::Http3Backend::builder()
+                    .ctx(ctx)
+                    .worker_tasks(worker_tasks)
+                    .service_factory(service_factory)
+                    .bind(listener.socket)
+                    .extensions(extensions)
+                    .rustls_config(_rustls_config)
+                    .connection_config(connection_config)
+                    .build()
+                    .run();
+                let http3 = std::pin::pin!(http3);
+
+                let res = futures::future::select(hyper, http3).await;
+                match res {
+                    // Let the other backend finish draining its connections
+                    futures::future::Either::Left((Ok(()), http3)) => return http3.await,
+                    futures::future::Either::Right((Ok(()), hyper)) => return hyper.await,
+                    futures::future::Either::Left((res, _)) => return res,
+                    futures::future::Either::Right((res, _)) => return res,
+                }
+            }
+            _ => return Ok(()),
         }
 
-        Ok(())
+        // This line must be unreachable
     }
+
+    // At this point we know that we are not using TLS either
+    // - because the feature is disabled
+    // - or because it's enabled but the config is None.
+
+    if is_udp {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "http3 requires a rustls config").into());
+    }
+
+    #[cfg(any(feature = "http1", feature = "http2"))]
+    if start_tcp_backend {
+        let builder = crate::backend::hyper::HyperBackend::builder()
+            .ctx(ctx)
+            .worker_tasks(worker_tasks)
+            .service_factory(service_factory)
+            .bind(listener.socket)
+            .extensions(extensions)
+            .connection_config(connection_config);
+
+        #[cfg(feature = "http1")]
+        let builder = builder.http1_enabled(listener.enable_http1);
+
+        #[cfg(feature = "http2")]
+        let builder = builder.http2_enabled(listener.enable_http2);
+
+        return builder.build().run().await;
+    }
+
+    Ok(())
 }
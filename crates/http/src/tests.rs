@@ -12,7 +12,7 @@ use tokio_rustls::rustls::pki_types::pem::PemObject;
 use crate::HttpServer;
 use crate::service::{fn_http_service, service_clone_factory};
 
-fn install_provider() {
+pub(crate) fn install_provider() {
     #[cfg(feature = "tls-rustls")]
     {
         static ONCE: std::sync::Once = std::sync::Once::new();
@@ -33,7 +33,7 @@ fn get_available_addr() -> std::io::Result<std::net::SocketAddr> {
 const RESPONSE_TEXT: &str = "Hello, world!";
 
 #[allow(unused)]
-fn file_path(item: &str) -> PathBuf {
+pub(crate) fn file_path(item: &str) -> PathBuf {
     if let Some(env) = std::env::var_os("ASSETS_DIR") {
         PathBuf::from(env).join(item)
     } else {
@@ -685,3 +685,1581 @@ async fn test_client_certs_http2() {
 async fn test_client_certs_http3() {
     test_client_certs(reqwest::Version::HTTP_3).await;
 }
+
+/// Starts a server that responds after `delay` and returns its address.
+#[cfg(feature = "http1")]
+async fn connection_config_server(
+    config: crate::connection::ConnectionConfig,
+    delay: Duration,
+) -> (std::net::SocketAddr, scuffle_context::Handler, tokio::task::JoinHandle<()>) {
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(move |_| async move {
+            tokio::time::sleep(delay).await;
+            Ok::<_, Infallible>(http::Response::new(RESPONSE_TEXT.to_string()))
+        })))
+        .connection_config(config)
+        .bind(addr)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    (addr, handler, handle)
+}
+
+#[tokio::test]
+#[cfg(feature = "http1")]
+async fn max_connections() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let config = crate::connection::ConnectionConfig::builder().max_connections(1).build();
+    let (addr, handler, handle) = connection_config_server(config, Duration::ZERO).await;
+
+    // Occupy the only connection slot
+    let first = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+
+    let mut second = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    second
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await
+        .expect("failed to write request");
+
+    let mut response = Vec::new();
+    second
+        .read_to_end(&mut response)
+        .with_timeout(Duration::from_millis(200))
+        .await
+        .expect_err("second connection should not be accepted while the first one is open");
+
+    drop(first);
+
+    second
+        .read_to_end(&mut response)
+        .with_timeout(Duration::from_secs(1))
+        .await
+        .expect("second connection should be accepted after the first one is closed")
+        .expect("failed to read response");
+    assert!(String::from_utf8_lossy(&response).ends_with(RESPONSE_TEXT));
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(feature = "http1")]
+async fn header_read_timeout() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let config = crate::connection::ConnectionConfig::builder()
+        .header_read_timeout(Duration::from_millis(100))
+        .build();
+    let (addr, handler, handle) = connection_config_server(config, Duration::ZERO).await;
+
+    // Never finish sending the headers
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n")
+        .await
+        .expect("failed to write request");
+
+    let mut response = Vec::new();
+    let _ = stream
+        .read_to_end(&mut response)
+        .with_timeout(Duration::from_secs(1))
+        .await
+        .expect("connection should be closed after the header read timeout");
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(feature = "http1")]
+async fn graceful_shutdown() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (addr, handler, handle) =
+        connection_config_server(crate::connection::ConnectionConfig::default(), Duration::from_millis(200)).await;
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await
+        .expect("failed to write request");
+
+    // Shut down while the request is in-flight
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    handler.cancel();
+
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .with_timeout(Duration::from_secs(1))
+        .await
+        .expect("connection should be closed after the in-flight request")
+        .expect("failed to read response");
+
+    let response = String::from_utf8_lossy(&response).to_lowercase();
+    assert!(response.contains("connection: close"), "{response}");
+    assert!(response.ends_with(&RESPONSE_TEXT.to_lowercase()), "{response}");
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(feature = "http1")]
+async fn drain_timeout() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let config = crate::connection::ConnectionConfig::builder()
+        .drain_timeout(Duration::from_millis(100))
+        .build();
+    let (addr, handler, handle) = connection_config_server(config, Duration::from_secs(10)).await;
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await
+        .expect("failed to write request");
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    handler
+        .shutdown()
+        .with_timeout(Duration::from_secs(1))
+        .await
+        .expect("connections should be aborted after the drain timeout");
+    handle.await.expect("task failed");
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    assert!(response.is_empty(), "the aborted request should not get a response");
+}
+
+#[tokio::test]
+#[cfg(all(feature = "tls-rustls", feature = "http3"))]
+async fn http3_drain_timeout() {
+    install_provider();
+
+    let root_cert = tokio_rustls::rustls::pki_types::CertificateDer::from_pem_file(file_path("root_cert.pem"))
+        .expect("failed to parse mTLS root cert");
+    let reqwest_root_cert = reqwest::Certificate::from_der(&root_cert).expect("failed to convert to reqwest cert");
+
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    // The handler never completes
+    let started = std::sync::Arc::new(tokio::sync::Notify::new());
+    let builder = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service({
+            let started = started.clone();
+            move |_| {
+                let started = started.clone();
+                async move {
+                    started.notify_one();
+                    std::future::pending::<()>().await;
+                    Ok::<_, Infallible>(http::Response::new(RESPONSE_TEXT.to_string()))
+                }
+            }
+        })))
+        .rustls_config(rustls_config())
+        .enable_http3(true)
+        .connection_config(
+            crate::connection::ConnectionConfig::builder()
+                .drain_timeout(Duration::from_millis(100))
+                .build(),
+        );
+
+    #[cfg(feature = "http2")]
+    let builder = builder.enable_http2(false);
+
+    #[cfg(feature = "http1")]
+    let builder = builder.enable_http1(false);
+
+    let server = builder.bind(addr).ctx(ctx).build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest_root_cert)
+        .https_only(true)
+        .http3_prior_knowledge()
+        .build()
+        .expect("failed to build client");
+    let url = format!("https://localhost:{}/", addr.port());
+    let request = tokio::spawn(async move { client.get(&url).version(reqwest::Version::HTTP_3).send().await });
+
+    started
+        .notified()
+        .with_timeout(Duration::from_secs(5))
+        .await
+        .expect("the request should reach the handler");
+
+    handler
+        .shutdown()
+        .with_timeout(Duration::from_secs(2))
+        .await
+        .expect("connections should be aborted after the drain timeout");
+    handle.await.expect("task failed");
+
+    let response = request
+        .with_timeout(Duration::from_secs(2))
+        .await
+        .expect("the request should be aborted")
+        .expect("task failed");
+    assert!(response.is_err(), "the aborted request should not get a response");
+}
+
+#[tokio::test]
+#[cfg(all(feature = "http1", feature = "proxy-protocol"))]
+async fn proxy_protocol() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let proxy_protocol = crate::proxy_protocol::ProxyProtocol::builder()
+        .trusted_networks(vec!["127.0.0.1".parse().unwrap()])
+        .build();
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(|req| async move {
+            let client_addr = req.extensions().get::<crate::extensions::ClientAddr>().unwrap();
+            let authority = req
+                .extensions()
+                .get::<crate::extensions::ProxyHeader>()
+                .and_then(|header| header.authority().map(str::to_owned));
+            Ok::<_, Infallible>(http::Response::new(format!("{} {authority:?}", **client_addr)))
+        })))
+        .connection_config(
+            crate::connection::ConnectionConfig::builder()
+                .proxy_protocol(proxy_protocol)
+                .build(),
+        )
+        .bind(addr)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let request = b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n";
+
+    // v1 header
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream
+        .write_all(format!("PROXY TCP4 203.0.113.7 127.0.0.1 56324 {}\r\n", addr.port()).as_bytes())
+        .await
+        .expect("failed to write header");
+    stream.write_all(request).await.expect("failed to write request");
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.expect("failed to read response");
+    assert!(String::from_utf8_lossy(&response).ends_with("203.0.113.7:56324 None"));
+
+    // v2 header with an authority TLV
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x1b".to_vec();
+    header.extend_from_slice(&[198, 51, 100, 1, 127, 0, 0, 1]);
+    header.extend_from_slice(&443u16.to_be_bytes());
+    header.extend_from_slice(&addr.port().to_be_bytes());
+    header.extend_from_slice(b"\x02\x00\x0cexample.com.");
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream.write_all(&header).await.expect("failed to write header");
+    stream.write_all(request).await.expect("failed to write request");
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.expect("failed to read response");
+    assert!(String::from_utf8_lossy(&response).ends_with("198.51.100.1:443 Some(\"example.com.\")"));
+
+    // A trusted peer has to send a header
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream.write_all(request).await.expect("failed to write request");
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    assert!(response.is_empty());
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(feature = "http1")]
+async fn listeners() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let public_addr = get_available_addr().expect("failed to get available address");
+    let internal_addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    #[cfg(unix)]
+    let unix_path = std::env::temp_dir().join(format!("scuffle-http-listeners-{}.sock", std::process::id()));
+    #[cfg(unix)]
+    let _ = std::fs::remove_file(&unix_path);
+
+    let listeners = vec![
+        crate::listener::Listener::builder(internal_addr).name("internal").build(),
+        #[cfg(unix)]
+        crate::listener::Listener::builder(unix_path.clone()).name("sidecar").build(),
+    ];
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(|req| async move {
+            let name = req
+                .extensions()
+                .get::<crate::extensions::ListenerName>()
+                .map_or("public", |name| &**name)
+                .to_owned();
+            Ok::<_, Infallible>(http::Response::new(name))
+        })))
+        .bind(public_addr)
+        .listeners(listeners)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    async fn request(mut stream: impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin) -> String {
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .expect("failed to write request");
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.expect("failed to read response");
+        String::from_utf8(response).expect("response is not utf8")
+    }
+
+    let public = tokio::net::TcpStream::connect(public_addr).await.expect("failed to connect");
+    assert!(request(public).await.ends_with("public"));
+
+    let internal = tokio::net::TcpStream::connect(internal_addr)
+        .await
+        .expect("failed to connect");
+    assert!(request(internal).await.ends_with("internal"));
+
+    #[cfg(unix)]
+    {
+        let sidecar = tokio::net::UnixStream::connect(&unix_path).await.expect("failed to connect");
+        assert!(request(sidecar).await.ends_with("sidecar"));
+    }
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+
+    #[cfg(unix)]
+    assert!(!unix_path.exists(), "the socket file should be removed");
+}
+
+#[tokio::test]
+#[cfg(all(unix, feature = "http1", feature = "tls-rustls"))]
+async fn listener_unix_tls() {
+    let path = std::env::temp_dir().join(format!("scuffle-http-unix-tls-{}.sock", std::process::id()));
+
+    let err = crate::backend::hyper::HyperBackend::builder()
+        .service_factory(service_clone_factory(fn_http_service(|_| async {
+            Ok::<_, Infallible>(http::Response::new(RESPONSE_TEXT.to_string()))
+        })))
+        .bind(path.clone())
+        .rustls_config(rustls_config())
+        .build()
+        .run()
+        .await
+        .expect_err("tls on unix sockets should be rejected");
+
+    assert!(matches!(err, crate::error::HttpError::Io(e) if e.kind() == std::io::ErrorKind::InvalidInput));
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+#[cfg(feature = "webtransport")]
+async fn webtransport() {
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+    use h3::proto::varint::VarInt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::backend::h3::webtransport::{WebTransportConfig, WebTransportUpgrade};
+
+    fn stream_header(stream_type: u64, session_id: u64, payload: &[u8]) -> Bytes {
+        let mut buf = BytesMut::new();
+        VarInt::from_u64(stream_type).unwrap().encode(&mut buf);
+        VarInt::from_u64(session_id).unwrap().encode(&mut buf);
+        buf.put_slice(payload);
+        buf.freeze()
+    }
+
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+    let (closed_tx, mut closed_rx) = tokio::sync::mpsc::channel::<()>(1);
+
+    let builder = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(move |mut req| {
+            let closed_tx = closed_tx.clone();
+
+            async move {
+                let Some(upgrade) = req.extensions_mut().remove::<WebTransportUpgrade>() else {
+                    return Ok::<_, Infallible>(http::Response::builder().status(400).body(String::new()).unwrap());
+                };
+
+                tokio::spawn(async move {
+                    let session = upgrade.session().await.expect("session was not established");
+
+                    // Echo a datagram
+                    let datagram = session.read_datagram().await.expect("no datagram");
+                    session.send_datagram(datagram).expect("failed to send datagram");
+
+                    // Echo a bidirectional stream
+                    let (mut send, mut recv) = session.accept_bi().await.expect("no bidirectional stream");
+                    let mut buf = Vec::new();
+                    recv.read_to_end(&mut buf).await.expect("failed to read");
+                    send.write_all(&buf).await.expect("failed to write");
+                    send.shutdown().await.expect("failed to finish");
+
+                    // Answer a unidirectional stream on a new bidirectional stream
+                    let mut recv = session.accept_uni().await.expect("no unidirectional stream");
+                    let mut buf = Vec::new();
+                    recv.read_to_end(&mut buf).await.expect("failed to read");
+                    let (mut send, _recv) = session.open_bi().await.expect("failed to open stream");
+                    send.write_all(&buf).await.expect("failed to write");
+                    send.shutdown().await.expect("failed to finish");
+
+                    // The client closes the session
+                    assert!(session.accept_bi().await.is_none());
+                    closed_tx.send(()).await.unwrap();
+                });
+
+                Ok(http::Response::new(String::new()))
+            }
+        })))
+        .rustls_config(rustls_config())
+        .enable_http3(true)
+        .connection_config(
+            crate::connection::ConnectionConfig::builder()
+                .webtransport(WebTransportConfig::default())
+                .build(),
+        );
+
+    #[cfg(feature = "http1")]
+    let builder = builder.enable_http1(false);
+
+    #[cfg(feature = "http2")]
+    let builder = builder.enable_http2(false);
+
+    let server = builder.bind(addr).ctx(ctx).build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let root_cert = tokio_rustls::rustls::pki_types::CertificateDer::from_pem_file(file_path("root_cert.pem"))
+        .expect("failed to parse root cert");
+    let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+    roots.add(root_cert).expect("failed to add root cert");
+    let mut tls_config = tokio_rustls::rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+
+    let mut endpoint = h3_quinn::quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).expect("failed to create endpoint");
+    endpoint.set_default_client_config(h3_quinn::quinn::ClientConfig::new(std::sync::Arc::new(
+        h3_quinn::quinn::crypto::rustls::QuicClientConfig::try_from(tls_config).expect("failed to create quic config"),
+    )));
+    let conn = endpoint
+        .connect(addr, "localhost")
+        .expect("failed to connect")
+        .await
+        .expect("failed to connect");
+
+    // The connection driver is never polled, it would reject the streams opened by the server
+    let (_driver, mut send_request) = h3::client::builder()
+        .enable_extended_connect(true)
+        .enable_datagram(true)
+        .build::<_, _, Bytes>(h3_quinn::Connection::new(conn.clone()))
+        .await
+        .expect("failed to create h3 connection");
+
+    // Requests without the webtransport protocol do not get an upgrade
+    let mut stream = send_request
+        .send_request(
+            http::Request::get(format!("https://localhost:{}/", addr.port()))
+                .body(())
+                .unwrap(),
+        )
+        .await
+        .expect("failed to send request");
+    stream.finish().await.expect("failed to finish request");
+    assert_eq!(stream.recv_response().await.expect("no response").status(), 400);
+
+    let req = http::Request::builder()
+        .method(http::Method::CONNECT)
+        .uri(format!("https://localhost:{}/session", addr.port()))
+        .extension(h3::ext::Protocol::WEB_TRANSPORT)
+        .body(())
+        .unwrap();
+    let mut connect = send_request.send_request(req).await.expect("failed to send request");
+    assert_eq!(connect.recv_response().await.expect("no response").status(), 200);
+    let session_id = connect.id().into_inner();
+
+    // Datagrams start with the quarter stream id of the session
+    let mut datagram = BytesMut::new();
+    VarInt::from_u64(session_id >> 2).unwrap().encode(&mut datagram);
+    datagram.put_slice(b"datagram");
+    let datagram = datagram.freeze();
+    conn.send_datagram(datagram.clone()).expect("failed to send datagram");
+    let echo = conn
+        .read_datagram()
+        .with_timeout(Duration::from_secs(5))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(echo, datagram);
+
+    // Bidirectional streams start with the WEBTRANSPORT_STREAM signal
+    let (mut send, mut recv) = conn.open_bi().await.expect("failed to open stream");
+    send.write_all(&stream_header(0x41, session_id, b"bidi"))
+        .await
+        .expect("failed to write");
+    send.finish().expect("failed to finish");
+    let echo = recv
+        .read_to_end(1024)
+        .with_timeout(Duration::from_secs(5))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(echo, b"bidi");
+
+    // Unidirectional streams start with the WEBTRANSPORT_UNI stream type
+    let mut send = conn.open_uni().await.expect("failed to open stream");
+    send.write_all(&stream_header(0x54, session_id, b"uni"))
+        .await
+        .expect("failed to write");
+    send.finish().expect("failed to finish");
+    let (_send, mut recv) = conn.accept_bi().with_timeout(Duration::from_secs(5)).await.unwrap().unwrap();
+    let mut echo = Bytes::from(recv.read_to_end(1024).await.expect("failed to read"));
+    assert_eq!(VarInt::decode(&mut echo).unwrap().into_inner(), 0x41);
+    assert_eq!(VarInt::decode(&mut echo).unwrap().into_inner(), session_id);
+    assert_eq!(echo.chunk(), b"uni");
+
+    // Closing the CONNECT stream closes the session
+    connect.finish().await.expect("failed to close session");
+    closed_rx
+        .recv()
+        .with_timeout(Duration::from_secs(5))
+        .await
+        .expect("session was not closed");
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[cfg(feature = "client")]
+#[allow(dead_code)]
+async fn read_body(body: crate::client::ResponseBody) -> bytes::Bytes {
+    use bytes::BufMut;
+
+    let mut body = std::pin::pin!(body);
+    let mut data = bytes::BytesMut::new();
+
+    while let Some(frame) = std::future::poll_fn(|cx| http_body::Body::poll_frame(body.as_mut(), cx)).await {
+        if let Ok(chunk) = frame.expect("failed to read body").into_data() {
+            data.put(chunk);
+        }
+    }
+
+    data.freeze()
+}
+
+#[tokio::test]
+#[cfg(all(feature = "client", feature = "http1", feature = "http2"))]
+async fn client_http12() {
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(|req| async move {
+            Ok::<_, Infallible>(http::Response::new(format!("{:?}", req.version())))
+        })))
+        .bind(addr)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = crate::client::HttpClient::builder().build().expect("failed to build client");
+
+    for version in [http::Version::HTTP_11, http::Version::HTTP_2] {
+        let request = http::Request::get(format!("http://localhost:{}/", addr.port()))
+            .version(version)
+            .body(bytes::Bytes::new())
+            .expect("failed to build request");
+
+        let response = client.request(request).await.expect("failed to send request");
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.version(), version);
+        assert_eq!(read_body(response.into_body()).await, format!("{version:?}"));
+    }
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(all(
+    feature = "client",
+    feature = "http1",
+    feature = "http2",
+    feature = "http3",
+    feature = "tls-rustls"
+))]
+async fn client_mtls() {
+    use std::sync::Arc;
+
+    install_provider();
+
+    let root_cert_pem = std::fs::read(file_path("root_cert.pem")).expect("failed to read root cert");
+    let client_cert_pem = std::fs::read(file_path("client_cert.pem")).expect("failed to read client cert");
+    let client_key_pem = std::fs::read(file_path("client_key.pem")).expect("failed to read client key");
+
+    let mut root_cert_store = tokio_rustls::rustls::RootCertStore::empty();
+    root_cert_store
+        .add(
+            tokio_rustls::rustls::pki_types::CertificateDer::from_pem_slice(&root_cert_pem)
+                .expect("failed to parse root cert"),
+        )
+        .expect("failed to add root cert");
+    let client_cert_verifier = tokio_rustls::rustls::server::WebPkiClientVerifier::builder(Arc::new(root_cert_store))
+        .build()
+        .expect("failed to create client cert verifier");
+
+    let certs = tokio_rustls::rustls::pki_types::CertificateDer::pem_file_iter(file_path("server_cert.pem"))
+        .expect("failed to parse certfile")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("failed to parse cert");
+    let key = tokio_rustls::rustls::pki_types::PrivateKeyDer::from_pem_file(file_path("server_key.pem"))
+        .expect("failed to parse key");
+    let mut rustls_config = tokio_rustls::rustls::ServerConfig::builder()
+        .with_client_cert_verifier(client_cert_verifier)
+        .with_single_cert(certs, key)
+        .expect("failed to build config");
+    // Prefer HTTP/2 over HTTP/1.1
+    rustls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec(), b"h3".to_vec()];
+
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(|req| async move {
+            assert!(
+                req.extensions().get::<crate::extensions::ClientIdentity>().is_some(),
+                "client must have presented a certificate"
+            );
+            Ok::<_, Infallible>(http::Response::new(format!("{:?}", req.version())))
+        })))
+        .rustls_config(rustls_config)
+        .enable_http3(true)
+        .bind(addr)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = crate::client::HttpClient::builder()
+        .rustls_config(
+            crate::tls::mtls_client_config(&root_cert_pem, &client_cert_pem, &client_key_pem)
+                .expect("failed to build client config"),
+        )
+        .build()
+        .expect("failed to build client");
+
+    // HTTP/1.1 requests use HTTP/2 when the server prefers it
+    for (version, expected) in [
+        (http::Version::HTTP_11, http::Version::HTTP_2),
+        (http::Version::HTTP_2, http::Version::HTTP_2),
+        (http::Version::HTTP_3, http::Version::HTTP_3),
+    ] {
+        let request = http::Request::get(format!("https://localhost:{}/", addr.port()))
+            .version(version)
+            .body(bytes::Bytes::new())
+            .expect("failed to build request");
+
+        let response = client.request(request).await.expect("failed to send request");
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(read_body(response.into_body()).await, format!("{expected:?}"));
+    }
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(all(feature = "client", feature = "http1"))]
+async fn client_retry() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service({
+            let attempts = attempts.clone();
+            move |_| {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let status = if attempt % 3 == 2 {
+                        http::StatusCode::OK
+                    } else {
+                        http::StatusCode::SERVICE_UNAVAILABLE
+                    };
+                    http::Response::builder().status(status).body(String::new())
+                }
+            }
+        })))
+        .bind(addr)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = crate::client::HttpClient::builder()
+        .retry(
+            crate::client::RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(10))
+                .build(),
+        )
+        .build()
+        .expect("failed to build client");
+    let url = format!("http://localhost:{}/", addr.port());
+
+    let response = client.get(&url).await.expect("failed to send request");
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(attempts.swap(0, Ordering::SeqCst), 3);
+
+    // Requests with a method that is not idempotent are never retried
+    let request = http::Request::post(&url).body("body").expect("failed to build request");
+    let response = client.request(request).await.expect("failed to send request");
+    assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(attempts.swap(0, Ordering::SeqCst), 1);
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(all(feature = "client", feature = "http1"))]
+async fn client_timeout() {
+    let (addr, handler, handle) =
+        connection_config_server(crate::connection::ConnectionConfig::default(), Duration::from_secs(1)).await;
+
+    let client = crate::client::HttpClient::builder()
+        .retry(crate::client::RetryPolicy::disabled())
+        .build()
+        .expect("failed to build client");
+
+    let mut request = http::Request::get(format!("http://localhost:{}/", addr.port()))
+        .body(bytes::Bytes::new())
+        .expect("failed to build request");
+    request
+        .extensions_mut()
+        .insert(crate::client::RequestTimeout(Duration::from_millis(100)));
+
+    let err = client.request(request).await.expect_err("request should time out");
+    assert!(matches!(err, crate::client::ClientError::Timeout), "unexpected error: {err}");
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[cfg(all(feature = "middleware", feature = "http1"))]
+async fn middleware_server<S>(service: S) -> (std::net::SocketAddr, scuffle_context::Handler, tokio::task::JoinHandle<()>)
+where
+    S: crate::service::HttpService + Clone + std::fmt::Debug + Send + 'static,
+    S::Error: std::error::Error + Send + Sync,
+    S::ResBody: Send,
+    <S::ResBody as http_body::Body>::Data: Send,
+    <S::ResBody as http_body::Body>::Error: std::error::Error + Send + Sync,
+{
+    let addr = get_available_addr().expect("failed to get available address");
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let server = HttpServer::builder()
+        .service_factory(service_clone_factory(service))
+        .bind(addr)
+        .ctx(ctx)
+        .build();
+
+    let handle = tokio::spawn(async move {
+        server.run().await.expect("server run failed");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    (addr, handler, handle)
+}
+
+#[tokio::test]
+#[cfg(all(feature = "middleware", feature = "http1"))]
+async fn middleware_stack() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::middleware::{AllowOrigin, CorsConfig, HttpServiceExt, RateLimitConfig};
+
+    let service = fn_http_service(|req| async move {
+        let request_id = req
+            .extensions()
+            .get::<crate::extensions::RequestId>()
+            .expect("request id extension is set")
+            .clone();
+        assert_eq!(req.headers().get("x-request-id"), Some(&*request_id));
+
+        match axum::body::to_bytes(axum::body::Body::new(req.into_body()), usize::MAX).await {
+            Ok(body) => http::Response::builder().body(String::from_utf8_lossy(&body).into_owned()),
+            Err(err) => {
+                assert_eq!(err.to_string(), "body exceeded the limit of 16 bytes");
+                http::Response::builder()
+                    .status(http::StatusCode::PAYLOAD_TOO_LARGE)
+                    .body(String::new())
+            }
+        }
+    })
+    .with_body_limit(16)
+    .with_cors(
+        CorsConfig::builder()
+            .allow_origin(AllowOrigin::List(vec![http::HeaderValue::from_static(
+                "https://scuffle.cloud",
+            )]))
+            .build(),
+    )
+    .with_rate_limit(
+        RateLimitConfig::builder()
+            .requests(1)
+            .per(Duration::from_secs(60))
+            .burst(6)
+            .build(),
+    )
+    .with_trace()
+    .with_request_id(Default::default());
+
+    let (addr, handler, handle) = middleware_server(service).await;
+    let url = format!("http://localhost:{}/", addr.port());
+    let client = reqwest::Client::builder()
+        .http1_only()
+        .build()
+        .expect("failed to build client");
+
+    // Request ids are generated or taken from the request
+    let response = client
+        .post(&url)
+        .body(RESPONSE_TEXT)
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["x-request-id"].len(), 32);
+    assert_eq!(response.text().await.expect("failed to get text"), RESPONSE_TEXT);
+
+    let response = client
+        .get(&url)
+        .header("x-request-id", "my-request")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.headers()["x-request-id"], "my-request");
+
+    // Bodies above the limit are rejected up front or while reading
+    let response = client
+        .post(&url)
+        .body("a".repeat(17))
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+    stream
+        .write_all(b"POST / HTTP/1.1\r\nhost: localhost\r\ntransfer-encoding: chunked\r\nconnection: close\r\n\r\n")
+        .await
+        .expect("failed to write request");
+    stream
+        .write_all(b"a\r\naaaaaaaaaa\r\na\r\naaaaaaaaaa\r\n0\r\n\r\n")
+        .await
+        .expect("failed to write body");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.expect("failed to read response");
+    assert!(response.starts_with("HTTP/1.1 413"), "unexpected response: {response}");
+
+    // CORS headers are only sent to allowed origins
+    let response = client
+        .request(reqwest::Method::OPTIONS, &url)
+        .header("origin", "https://scuffle.cloud")
+        .header("access-control-request-method", "POST")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()["access-control-allow-origin"], "https://scuffle.cloud");
+    assert_eq!(response.headers()["access-control-allow-methods"], "GET, HEAD, POST");
+
+    let response = client
+        .get(&url)
+        .header("origin", "https://example.com")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(!response.headers().contains_key("access-control-allow-origin"));
+
+    // The burst of 6 requests is used up
+    let response = client.get(&url).send().await.expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "60");
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+#[tokio::test]
+#[cfg(all(feature = "compression", feature = "http1"))]
+async fn middleware_compression() {
+    use std::io::Read;
+
+    use crate::middleware::{CompressionConfig, HttpServiceExt};
+
+    let text = RESPONSE_TEXT.repeat(100);
+    let service = fn_http_service({
+        let text = text.clone();
+        move |req| {
+            let content_type = if req.uri().path() == "/image" {
+                "image/png"
+            } else {
+                "text/plain"
+            };
+            let text = text.clone();
+            async move {
+                http::Response::builder()
+                    .header(http::header::CONTENT_TYPE, content_type)
+                    .body(text)
+            }
+        }
+    })
+    .with_compression(CompressionConfig::default());
+
+    let (addr, handler, handle) = middleware_server(service).await;
+    let url = format!("http://localhost:{}/", addr.port());
+    let client = reqwest::Client::builder()
+        .http1_only()
+        .build()
+        .expect("failed to build client");
+
+    let response = client
+        .get(&url)
+        .header("accept-encoding", "gzip")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    assert_eq!(response.headers()["vary"], "accept-encoding");
+    let body = response.bytes().await.expect("failed to get body");
+    assert!(body.len() < text.len());
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .expect("failed to decode gzip");
+    assert_eq!(decoded, text);
+
+    let response = client
+        .get(&url)
+        .header("accept-encoding", "gzip, zstd")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.headers()["content-encoding"], "zstd");
+    let body = response.bytes().await.expect("failed to get body");
+    let decoded = zstd::decode_all(&body[..]).expect("failed to decode zstd");
+    assert_eq!(decoded, text.as_bytes());
+
+    let response = client
+        .get(&url)
+        .header("accept-encoding", "gzip, br")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.headers()["content-encoding"], "br");
+    let body = response.bytes().await.expect("failed to get body");
+    assert!(body.len() < text.len());
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&body[..], 4096)
+        .read_to_string(&mut decoded)
+        .expect("failed to decode brotli");
+    assert_eq!(decoded, text);
+
+    // Images are already compressed
+    let response = client
+        .get(format!("{url}image"))
+        .header("accept-encoding", "gzip")
+        .send()
+        .await
+        .expect("failed to send request");
+    assert!(!response.headers().contains_key("content-encoding"));
+    assert_eq!(response.text().await.expect("failed to get text"), text);
+
+    handler.shutdown().await;
+    handle.await.expect("task failed");
+}
+
+/// A minimal ACME server in the style of [Pebble](https://github.com/letsencrypt/pebble).
+///
+/// It validates challenges synchronously when they are triggered and issues certificates signed by its own CA.
+#[cfg(feature = "acme")]
+mod acme_stand_in {
+    use std::collections::HashSet;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use aws_lc_rs::signature::{
+        ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED, EcdsaKeyPair, KeyPair, UnparsedPublicKey,
+    };
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use tokio::io::AsyncReadExt;
+    use tokio_rustls::rustls;
+
+    use crate::IncomingRequest;
+    use crate::acme::der;
+
+    const CA_NAME: &str = "Stand-in CA";
+
+    struct Order {
+        domains: Vec<String>,
+        tokens: Vec<String>,
+        valid: Vec<Option<bool>>,
+        certificate: Option<String>,
+    }
+
+    #[derive(Default)]
+    pub(super) struct State {
+        next_nonce: u64,
+        nonces: HashSet<String>,
+        rejected_nonce: bool,
+        pub(super) accounts: Vec<serde_json::Value>,
+        orders: Vec<Order>,
+    }
+
+    pub(super) struct AcmeStandIn {
+        base: String,
+        ca_key: EcdsaKeyPair,
+        pub(super) ca_cert: Vec<u8>,
+        tls_addr: SocketAddr,
+        http_addr: SocketAddr,
+        pub(super) state: tokio::sync::Mutex<State>,
+    }
+
+    /// Accepts any server certificate and handshake signature.
+    ///
+    /// webpki rejects the critical acmeIdentifier extension of challenge certificates, even to verify signatures.
+    #[derive(Debug)]
+    struct AnyCert(Arc<rustls::crypto::CryptoProvider>);
+
+    impl rustls::client::danger::ServerCertVerifier for AnyCert {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &rustls::pki_types::CertificateDer<'_>,
+            _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+            _server_name: &rustls::pki_types::ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: rustls::pki_types::UnixTime,
+        ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+            Ok(rustls::client::danger::ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            _message: &[u8],
+            _cert: &rustls::pki_types::CertificateDer<'_>,
+            _dss: &rustls::DigitallySignedStruct,
+        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+            Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            _message: &[u8],
+            _cert: &rustls::pki_types::CertificateDer<'_>,
+            _dss: &rustls::DigitallySignedStruct,
+        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+            Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    fn thumbprint(jwk: &serde_json::Value) -> String {
+        // serde_json sorts the keys, as required by RFC 7638
+        let jwk = serde_json::json!({ "crv": jwk["crv"], "kty": jwk["kty"], "x": jwk["x"], "y": jwk["y"] });
+        let digest = aws_lc_rs::digest::digest(&aws_lc_rs::digest::SHA256, &serde_json::to_vec(&jwk).unwrap());
+        URL_SAFE_NO_PAD.encode(digest)
+    }
+
+    fn decode(value: &serde_json::Value) -> Vec<u8> {
+        URL_SAFE_NO_PAD
+            .decode(value.as_str().expect("expected a string"))
+            .expect("invalid base64")
+    }
+
+    /// Connects like a TLS-ALPN-01 validator and checks the challenge certificate.
+    async fn validate_tls_alpn_01(addr: SocketAddr, domain: &str, key_authorization: &str) -> bool {
+        let provider = crate::tls::crypto_provider();
+        let mut config = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyCert(provider)))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![crate::tls::ACME_TLS_ALPN_NAME.to_vec()];
+
+        let stream = tokio::net::TcpStream::connect(addr).await.expect("failed to connect");
+        let server_name = rustls::pki_types::ServerName::try_from(domain.to_owned()).expect("invalid domain");
+        let Ok(mut stream) = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+        else {
+            return false;
+        };
+
+        let (_, conn) = stream.get_ref();
+        assert_eq!(conn.alpn_protocol(), Some(crate::tls::ACME_TLS_ALPN_NAME));
+        let cert = conn.peer_certificates().expect("no certificate")[0].to_vec();
+
+        // The server closes the connection after the handshake
+        assert_eq!(stream.read(&mut [0; 16]).await.expect("failed to read"), 0);
+
+        // The acmeIdentifier extension contains the digest as an OCTET STRING
+        let digest = aws_lc_rs::digest::digest(&aws_lc_rs::digest::SHA256, key_authorization.as_bytes());
+        let expected = [&[0x04, 0x20], digest.as_ref()].concat();
+        cert.windows(expected.len()).any(|window| window == expected)
+    }
+
+    /// Fetches the key authorization like an HTTP-01 validator.
+    async fn validate_http_01(addr: SocketAddr, token: &str, key_authorization: &str) -> bool {
+        let response = reqwest::get(format!("http://{addr}/.well-known/acme-challenge/{token}"))
+            .await
+            .expect("failed to send request");
+        response.status() == reqwest::StatusCode::OK && response.text().await.unwrap() == key_authorization
+    }
+
+    /// Returns the `SubjectPublicKeyInfo` of a certificate signing request.
+    fn csr_public_key(csr: &[u8]) -> Vec<u8> {
+        let (_, csr, _) = crate::tls::der_element(csr).unwrap();
+        let (_, info, _) = crate::tls::der_element(csr).unwrap();
+        let (_, _, rest) = crate::tls::der_element(info).unwrap(); // version
+        let (_, _, rest) = crate::tls::der_element(rest).unwrap(); // subject
+        let (_, _, after) = crate::tls::der_element(rest).unwrap();
+        rest[..rest.len() - after.len()].to_vec()
+    }
+
+    impl AcmeStandIn {
+        pub(super) fn new(addr: SocketAddr, tls_addr: SocketAddr, http_addr: SocketAddr) -> Self {
+            let pkcs8 = der::generate_key().unwrap();
+            let ca_key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8).unwrap();
+            let ca_cert = der::certificate(
+                &ca_key,
+                CA_NAME,
+                &der::subject_public_key_info(ca_key.public_key().as_ref()),
+                &[CA_NAME.to_owned()],
+                &[],
+            )
+            .unwrap();
+
+            Self {
+                base: format!("http://{addr}"),
+                ca_key,
+                ca_cert,
+                tls_addr,
+                http_addr,
+                state: Default::default(),
+            }
+        }
+
+        fn response(
+            state: &mut State,
+            status: http::StatusCode,
+            location: Option<String>,
+            body: String,
+        ) -> http::Response<String> {
+            state.next_nonce += 1;
+            let nonce = format!("nonce-{}", state.next_nonce);
+            state.nonces.insert(nonce.clone());
+
+            let mut response = http::Response::builder().status(status).header("replay-nonce", nonce);
+            if let Some(location) = location {
+                response = response.header(http::header::LOCATION, location);
+            }
+            response.body(body).unwrap()
+        }
+
+        fn problem(state: &mut State, status: http::StatusCode, kind: &str) -> http::Response<String> {
+            let body = serde_json::json!({ "type": format!("urn:ietf:params:acme:error:{kind}"), "detail": kind });
+            Self::response(state, status, None, body.to_string())
+        }
+
+        fn order_json(&self, id: usize, order: &Order) -> serde_json::Value {
+            let status = match (&order.certificate, order.valid.iter().all(|valid| *valid == Some(true))) {
+                (Some(_), _) => "valid",
+                (None, true) => "ready",
+                (None, false) if order.valid.contains(&Some(false)) => "invalid",
+                (None, false) => "pending",
+            };
+
+            serde_json::json!({
+                "status": status,
+                "authorizations": (0..order.domains.len()).map(|i| format!("{}/authz/{id}/{i}", self.base)).collect::<Vec<_>>(),
+                "finalize": format!("{}/finalize/{id}", self.base),
+                "certificate": order.certificate.as_ref().map(|_| format!("{}/cert/{id}", self.base)),
+            })
+        }
+
+        pub(super) async fn handle(&self, req: IncomingRequest) -> http::Response<String> {
+            let path = req.uri().path().to_owned();
+            let mut state = self.state.lock().await;
+
+            match (req.method().clone(), path.as_str()) {
+                (http::Method::GET, "/directory") => {
+                    let directory = serde_json::json!({
+                        "newNonce": format!("{}/nonce", self.base),
+                        "newAccount": format!("{}/account", self.base),
+                        "newOrder": format!("{}/order", self.base),
+                    });
+                    return Self::response(&mut state, http::StatusCode::OK, None, directory.to_string());
+                }
+                (http::Method::HEAD, "/nonce") => {
+                    return Self::response(&mut state, http::StatusCode::OK, None, String::new());
+                }
+                (http::Method::POST, _) => {}
+                _ => return Self::response(&mut state, http::StatusCode::NOT_FOUND, None, String::new()),
+            }
+
+            assert_eq!(req.headers()[http::header::CONTENT_TYPE], "application/jose+json");
+            let body = axum::body::to_bytes(axum::body::Body::new(req.into_body()), usize::MAX)
+                .await
+                .expect("failed to read body");
+            let jws: serde_json::Value = serde_json::from_slice(&body).expect("invalid jws");
+            let protected: serde_json::Value = serde_json::from_slice(&decode(&jws["protected"])).expect("invalid header");
+
+            assert_eq!(protected["alg"], "ES256");
+            assert_eq!(protected["url"], format!("{}{path}", self.base));
+
+            // Reject the first request to test that the client retries with a new nonce
+            let nonce = protected["nonce"].as_str().expect("missing nonce");
+            if !state.nonces.remove(nonce) || !state.rejected_nonce {
+                state.rejected_nonce = true;
+                return Self::problem(&mut state, http::StatusCode::BAD_REQUEST, "badNonce");
+            }
+
+            let account = if path == "/account" {
+                let jwk = protected["jwk"].clone();
+                match state.accounts.iter().position(|account| *account == jwk) {
+                    Some(account) => account,
+                    None => {
+                        state.accounts.push(jwk);
+                        state.accounts.len() - 1
+                    }
+                }
+            } else {
+                let kid = protected["kid"].as_str().expect("missing kid");
+                let account = kid.strip_prefix(&format!("{}/account/", self.base)).expect("invalid kid");
+                account.parse().expect("invalid kid")
+            };
+
+            let jwk = &state.accounts[account];
+            let public_key = [&[4], &decode(&jwk["x"])[..], &decode(&jwk["y"])[..]].concat();
+            let message = format!("{}.{}", jws["protected"].as_str().unwrap(), jws["payload"].as_str().unwrap());
+            UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+                .verify(message.as_bytes(), &decode(&jws["signature"]))
+                .expect("invalid signature");
+
+            let thumbprint = thumbprint(jwk);
+            let payload: Option<serde_json::Value> = match jws["payload"].as_str() {
+                Some("") => None,
+                _ => Some(serde_json::from_slice(&decode(&jws["payload"])).expect("invalid payload")),
+            };
+
+            let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+            let id = |index: usize| segments[index].parse::<usize>().expect("invalid id");
+
+            match segments[0] {
+                "account" => {
+                    let location = format!("{}/account/{account}", self.base);
+                    let body = serde_json::json!({ "status": "valid" }).to_string();
+                    Self::response(&mut state, http::StatusCode::CREATED, Some(location), body)
+                }
+                "order" if segments.len() == 1 => {
+                    let domains = payload.expect("missing payload")["identifiers"]
+                        .as_array()
+                        .expect("missing identifiers")
+                        .iter()
+                        .map(|identifier| identifier["value"].as_str().unwrap().to_owned())
+                        .collect::<Vec<_>>();
+
+                    let order = Order {
+                        tokens: (0..domains.len())
+                            .map(|_| URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>()))
+                            .collect(),
+                        valid: vec![None; domains.len()],
+                        domains,
+                        certificate: None,
+                    };
+
+                    let id = state.orders.len();
+                    let body = self.order_json(id, &order).to_string();
+                    state.orders.push(order);
+
+                    let location = format!("{}/order/{id}", self.base);
+                    Self::response(&mut state, http::StatusCode::CREATED, Some(location), body)
+                }
+                "order" => {
+                    let body = self.order_json(id(1), &state.orders[id(1)]).to_string();
+                    Self::response(&mut state, http::StatusCode::OK, None, body)
+                }
+                "authz" => {
+                    let (order, index) = (&state.orders[id(1)], id(2));
+                    let status = match order.valid[index] {
+                        Some(true) => "valid",
+                        Some(false) => "invalid",
+                        None => "pending",
+                    };
+
+                    let challenges = ["tls-alpn-01", "http-01"].map(|challenge| {
+                        let error = (status == "invalid").then(|| {
+                            serde_json::json!({ "type": "urn:ietf:params:acme:error:unauthorized", "detail": "validation failed" })
+                        });
+                        serde_json::json!({
+                            "type": challenge,
+                            "url": format!("{}/chall/{}/{index}/{challenge}", self.base, id(1)),
+                            "token": order.tokens[index],
+                            "status": status,
+                            "error": error,
+                        })
+                    });
+
+                    let body = serde_json::json!({
+                        "identifier": { "type": "dns", "value": order.domains[index] },
+                        "status": status,
+                        "challenges": challenges,
+                    });
+                    Self::response(&mut state, http::StatusCode::OK, None, body.to_string())
+                }
+                "chall" => {
+                    let (order, index) = (id(1), id(2));
+                    let domain = state.orders[order].domains[index].clone();
+                    let token = state.orders[order].tokens[index].clone();
+                    let key_authorization = format!("{token}.{thumbprint}");
+
+                    let valid = match segments[3] {
+                        "tls-alpn-01" => validate_tls_alpn_01(self.tls_addr, &domain, &key_authorization).await,
+                        _ => validate_http_01(self.http_addr, &token, &key_authorization).await,
+                    };
+                    state.orders[order].valid[index] = Some(valid);
+
+                    let body = serde_json::json!({ "type": segments[3], "status": "processing" });
+                    Self::response(&mut state, http::StatusCode::OK, None, body.to_string())
+                }
+                "finalize" => {
+                    let csr = decode(&payload.expect("missing payload")["csr"]);
+                    let order = &mut state.orders[id(1)];
+                    let cert = der::certificate(&self.ca_key, CA_NAME, &csr_public_key(&csr), &order.domains, &[])
+                        .expect("failed to issue certificate");
+                    order.certificate = Some(der::pem("CERTIFICATE", &cert) + &der::pem("CERTIFICATE", &self.ca_cert));
+
+                    let body = self.order_json(id(1), &state.orders[id(1)]).to_string();
+                    Self::response(&mut state, http::StatusCode::OK, None, body)
+                }
+                "cert" => {
+                    let chain = state.orders[id(1)].certificate.clone().expect("order is not valid");
+                    Self::response(&mut state, http::StatusCode::OK, None, chain)
+                }
+                _ => Self::problem(&mut state, http::StatusCode::NOT_FOUND, "malformed"),
+            }
+        }
+    }
+}
+
+#[tokio::test]
+#[cfg(feature = "acme")]
+async fn acme() {
+    use std::sync::Arc;
+
+    use crate::acme::{Acme, ChallengeType, MemoryStorage};
+    use crate::tls::CertEvent;
+
+    install_provider();
+
+    let acme_addr = get_available_addr().expect("failed to get available address");
+    let tls_addr = get_available_addr().expect("failed to get available address");
+    let http_addr = get_available_addr().expect("failed to get available address");
+    let stand_in = Arc::new(acme_stand_in::AcmeStandIn::new(acme_addr, tls_addr, http_addr));
+    let (ctx, handler) = scuffle_context::Context::new();
+
+    let service = {
+        let stand_in = stand_in.clone();
+        fn_http_service(move |req| {
+            let stand_in = stand_in.clone();
+            async move { Ok::<_, Infallible>(stand_in.handle(req).await) }
+        })
+    };
+    let acme_server = HttpServer::builder()
+        .service_factory(service_clone_factory(service))
+        .bind(acme_addr)
+        .ctx(ctx.clone())
+        .build();
+
+    let storage = MemoryStorage::new();
+    let build = |challenge| {
+        Acme::builder()
+            .directory_url(format!("http://{acme_addr}/directory"))
+            .domains(vec!["localhost".to_owned()])
+            .contact(vec!["mailto:admin@localhost".to_owned()])
+            .challenge(challenge)
+            .storage(storage.clone())
+            .build()
+            .expect("failed to build acme client")
+    };
+
+    // TLS-ALPN-01 is answered by the server that uses the certificate
+    let acme = build(ChallengeType::TlsAlpn01);
+    let tls_server = HttpServer::builder()
+        .service_factory(service_clone_factory(fn_http_service(|_| async {
+            Ok::<_, Infallible>(http::Response::new(RESPONSE_TEXT.to_string()))
+        })))
+        .rustls_config(acme.server_config())
+        .bind(tls_addr)
+        .ctx(ctx.clone())
+        .build();
+
+    let handles = [
+        tokio::spawn(async move { acme_server.run().await.expect("server run failed") }),
+        tokio::spawn(async move { tls_server.run().await.expect("server run failed") }),
+    ];
+
+    // Wait for the servers to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut events = acme.cert_store().subscribe();
+    let runner = tokio::spawn(acme.clone().run(ctx.clone()));
+
+    let event = events
+        .recv()
+        .with_timeout(Duration::from_secs(10))
+        .await
+        .expect("timed out waiting for the certificate")
+        .expect("failed to receive event");
+    assert!(
+        matches!(&event, CertEvent::Loaded { server_name: Some(name), not_after: Some(_) } if name == "localhost"),
+        "{event:?}"
+    );
+
+    // The issued certificate is trusted by clients that trust the CA
+    let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+    roots.add(stand_in.ca_cert.clone().into()).expect("failed to add root cert");
+    let client = crate::client::HttpClient::builder()
+        .rustls_config(
+            tokio_rustls::rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+        .build()
+        .expect("failed to build client");
+
+    let response = client
+        .get(format!("https://localhost:{}/", tls_addr.port()))
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(read_body(response.into_body()).await, RESPONSE_TEXT);
+
+    // The stored certificate is loaded after a restart
+    let restarted = build(ChallengeType::TlsAlpn01);
+    assert!(restarted.load().await.expect("failed to load").is_some());
+    assert!(restarted.cert_store().resolve_server_name(Some("localhost")).is_some());
+
+    // HTTP-01 is answered by a plain HTTP server, the stored account is reused
+    let acme = build(ChallengeType::Http01);
+    let http_server = HttpServer::builder()
+        .service_factory(service_clone_factory(acme.http01_service(fn_http_service(|_| async {
+            Ok::<_, Infallible>(http::Response::new(RESPONSE_TEXT.to_string()))
+        }))))
+        .bind(http_addr)
+        .ctx(ctx.clone())
+        .build();
+    let http_handle = tokio::spawn(async move { http_server.run().await.expect("server run failed") });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let not_after = acme.issue().await.expect("failed to issue certificate");
+    assert!(not_after.is_some());
+    assert_eq!(stand_in.state.lock().await.accounts.len(), 1);
+
+    let response = reqwest::get(format!("http://{http_addr}/.well-known/acme-challenge/unknown"))
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let response = reqwest::get(format!("http://{http_addr}/"))
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.text().await.expect("failed to get text"), RESPONSE_TEXT);
+
+    drop(ctx);
+    handler.shutdown().await;
+    runner.await.expect("task failed");
+    http_handle.await.expect("task failed");
+    for handle in handles {
+        handle.await.expect("task failed");
+    }
+}
@@ -0,0 +1,578 @@
+//! TLS certificate management.
+//!
+//! A [`CertStore`] holds the certificates of a server and picks one for every TLS handshake based on the
+//! server name (SNI) sent by the client.
+//! Certificates can be replaced at any time, by pushing new ones or by watching PEM files with
+//! [`CertStore::watch_pem_files`].
+//! New handshakes use the new certificate, existing connections are not affected.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use scuffle_context::ContextFutExt;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+
+/// The ALPN protocol of TLS-ALPN-01 challenges, RFC 8737 section 6.2.
+///
+/// Connections that negotiate this protocol are closed after the handshake.
+pub const ACME_TLS_ALPN_NAME: &[u8] = b"acme-tls/1";
+
+/// An error that can occur when loading a certificate.
+#[derive(Debug, thiserror::Error)]
+pub enum CertError {
+    /// The PEM file could not be read or parsed.
+    #[error("pem error: {0}")]
+    Pem(#[from] rustls::pki_types::pem::Error),
+    /// The private key is not supported or does not match the certificate.
+    #[error("rustls error: {0}")]
+    Rustls(#[from] rustls::Error),
+    /// The PEM file does not contain any certificates.
+    #[error("no certificates found")]
+    NoCertificates,
+}
+
+/// An event emitted by a [`CertStore`] when its certificates change.
+///
+/// Subscribe to events with [`CertStore::subscribe`], for example to export metrics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertEvent {
+    /// A certificate was added or replaced.
+    Loaded {
+        /// The server name of the certificate, `None` for the default certificate.
+        server_name: Option<String>,
+        /// The expiry date of the certificate, if it could be read.
+        not_after: Option<SystemTime>,
+    },
+    /// A certificate was removed.
+    Removed {
+        /// The server name of the certificate, `None` for the default certificate.
+        server_name: Option<String>,
+    },
+    /// Reloading a certificate from its files failed, the previous certificate is still in use.
+    ReloadFailed {
+        /// The server name of the certificate, `None` for the default certificate.
+        server_name: Option<String>,
+        /// The error that occurred.
+        error: String,
+    },
+}
+
+/// Information about a certificate in a [`CertStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertInfo {
+    /// The server name of the certificate, `None` for the default certificate.
+    pub server_name: Option<String>,
+    /// The expiry date of the certificate, if it could be read.
+    pub not_after: Option<SystemTime>,
+}
+
+#[derive(Debug, Default)]
+struct Certs {
+    default: Option<Arc<CertifiedKey>>,
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+}
+
+#[derive(Debug)]
+struct CertStoreInner {
+    certs: RwLock<Certs>,
+    events: tokio::sync::broadcast::Sender<CertEvent>,
+}
+
+/// A set of certificates that are selected by the server name (SNI) of the client.
+///
+/// Certificates are looked up by their exact server name first, then by a wildcard name like `*.example.com`
+/// and finally the default certificate is used.
+///
+/// Cloning a store is cheap, all clones share the same certificates.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # async fn example() -> Result<(), scuffle_http::tls::CertError> {
+/// let store = scuffle_http::tls::CertStore::new();
+/// store.load_pem_files(None, "cert.pem", "key.pem")?;
+///
+/// // Reload the certificate when the files change
+/// tokio::spawn(store.clone().watch_pem_files(
+///     scuffle_context::Context::global(),
+///     None,
+///     "cert.pem".into(),
+///     "key.pem".into(),
+///     std::time::Duration::from_secs(10),
+/// ));
+///
+/// let rustls_config = store.server_config();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CertStore {
+    inner: Arc<CertStoreInner>,
+}
+
+impl Default for CertStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CertStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        let (events, _) = tokio::sync::broadcast::channel(16);
+
+        Self {
+            inner: Arc::new(CertStoreInner {
+                certs: RwLock::default(),
+                events,
+            }),
+        }
+    }
+
+    /// Returns a [`rustls::ServerConfig`] that uses this store to select certificates.
+    ///
+    /// The config does not request client certificates. To use client certificates,
+    /// set the store as the [`cert_resolver`](rustls::ServerConfig::cert_resolver) of your own config instead.
+    pub fn server_config(&self) -> rustls::ServerConfig {
+        rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .expect("the crypto provider should support the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self.clone()))
+    }
+
+    /// Subscribes to changes of the certificates in this store.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<CertEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Adds or replaces the certificate for `server_name`, or the default certificate if `server_name` is `None`.
+    ///
+    /// The server name may be a wildcard like `*.example.com`, which matches exactly one label.
+    pub fn insert(&self, server_name: Option<&str>, key: CertifiedKey) {
+        let server_name = server_name.map(str::to_ascii_lowercase);
+        let not_after = key.end_entity_cert().ok().and_then(not_after);
+
+        {
+            let mut certs = self.inner.certs.write().expect("lock poisoned");
+            match &server_name {
+                Some(server_name) => {
+                    certs.by_name.insert(server_name.clone(), Arc::new(key));
+                }
+                None => certs.default = Some(Arc::new(key)),
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(server_name = ?server_name, not_after = ?not_after, "loaded certificate");
+
+        let _ = self.inner.events.send(CertEvent::Loaded { server_name, not_after });
+    }
+
+    /// Removes the certificate for `server_name`, or the default certificate if `server_name` is `None`.
+    ///
+    /// Returns `true` if there was a certificate.
+    pub fn remove(&self, server_name: Option<&str>) -> bool {
+        let server_name = server_name.map(str::to_ascii_lowercase);
+
+        let removed = {
+            let mut certs = self.inner.certs.write().expect("lock poisoned");
+            match &server_name {
+                Some(server_name) => certs.by_name.remove(server_name).is_some(),
+                None => certs.default.take().is_some(),
+            }
+        };
+
+        if removed {
+            let _ = self.inner.events.send(CertEvent::Removed { server_name });
+        }
+
+        removed
+    }
+
+    /// Loads a certificate chain and private key from PEM files and inserts them with [`insert`](Self::insert).
+    pub fn load_pem_files(
+        &self,
+        server_name: Option<&str>,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<(), CertError> {
+        let key = load_pem_files(cert_path.as_ref(), key_path.as_ref())?;
+        self.insert(server_name, key);
+        Ok(())
+    }
+
+    /// Reloads a certificate every time its PEM files change, until the context is done.
+    ///
+    /// The files are checked every `interval`. Load the certificate with [`load_pem_files`](Self::load_pem_files)
+    /// before calling this, it is only loaded again once the files change.
+    ///
+    /// When reloading fails, the previous certificate stays in use, a [`CertEvent::ReloadFailed`] is emitted and
+    /// the reload is retried on the next check.
+    pub async fn watch_pem_files(
+        self,
+        ctx: scuffle_context::Context,
+        server_name: Option<String>,
+        cert_path: PathBuf,
+        key_path: PathBuf,
+        interval: Duration,
+    ) {
+        let modified = || (modified(&cert_path), modified(&key_path));
+        let mut last_modified = modified();
+
+        while tokio::time::sleep(interval).with_context(&ctx).await.is_some() {
+            let current = modified();
+            if current == last_modified {
+                continue;
+            }
+
+            match self.load_pem_files(server_name.as_deref(), &cert_path, &key_path) {
+                Ok(()) => last_modified = current,
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(server_name = ?server_name, err = %err, "failed to reload certificate");
+
+                    let _ = self.inner.events.send(CertEvent::ReloadFailed {
+                        server_name: server_name.clone(),
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Emits an event to all subscribers.
+    #[cfg(feature = "acme")]
+    pub(crate) fn report(&self, event: CertEvent) {
+        let _ = self.inner.events.send(event);
+    }
+
+    /// Returns the server names and expiry dates of all certificates in this store.
+    pub fn certificates(&self) -> Vec<CertInfo> {
+        let certs = self.inner.certs.read().expect("lock poisoned");
+        let info = |server_name: Option<&String>, key: &CertifiedKey| CertInfo {
+            server_name: server_name.cloned(),
+            not_after: key.end_entity_cert().ok().and_then(not_after),
+        };
+
+        certs
+            .default
+            .iter()
+            .map(|key| info(None, key))
+            .chain(certs.by_name.iter().map(|(name, key)| info(Some(name), key)))
+            .collect()
+    }
+
+    /// Returns the certificate that would be used for a client sending `server_name`.
+    pub fn resolve_server_name(&self, server_name: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        let certs = self.inner.certs.read().expect("lock poisoned");
+
+        let Some(server_name) = server_name.map(str::to_ascii_lowercase) else {
+            return certs.default.clone();
+        };
+
+        certs
+            .by_name
+            .get(&server_name)
+            .or_else(|| {
+                let (_, parent) = server_name.split_once('.')?;
+                certs.by_name.get(&format!("*.{parent}"))
+            })
+            .or(certs.default.as_ref())
+            .cloned()
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.resolve_server_name(client_hello.server_name())
+    }
+}
+
+/// Loads a certificate chain and private key from PEM files.
+///
+/// The key is loaded with the default [`CryptoProvider`](rustls::crypto::CryptoProvider),
+/// or aws-lc-rs if none is installed, and must match the first certificate of the chain.
+pub fn load_pem_files(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, CertError> {
+    let certs = CertificateDer::pem_file_iter(cert_path)?.collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(CertError::NoCertificates);
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)?;
+
+    Ok(CertifiedKey::from_der(certs, key, &crypto_provider())?)
+}
+
+/// Builds a [`rustls::ClientConfig`] for mutual TLS from PEM encoded certificates.
+///
+/// The config only trusts servers with a certificate issued by `root_cert_pem` and authenticates
+/// the client with the certificate chain in `cert_pem` and the key in `private_key_pem`.
+/// Pass it to the `rustls_config` of the HTTP client to reuse the certificates of the server side.
+pub fn mtls_client_config(
+    root_cert_pem: &[u8],
+    cert_pem: &[u8],
+    private_key_pem: &[u8],
+) -> Result<rustls::ClientConfig, CertError> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_slice_iter(root_cert_pem) {
+        roots.add(cert?)?;
+    }
+
+    let certs = CertificateDer::pem_slice_iter(cert_pem).collect::<Result<Vec<_>, _>>()?;
+    if roots.is_empty() || certs.is_empty() {
+        return Err(CertError::NoCertificates);
+    }
+
+    let key = PrivateKeyDer::from_pem_slice(private_key_pem)?;
+
+    Ok(rustls::ClientConfig::builder_with_provider(crypto_provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_client_auth_cert(certs, key)?)
+}
+
+/// Returns the default [`CryptoProvider`](rustls::crypto::CryptoProvider), or aws-lc-rs if none is installed.
+pub(crate) fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> {
+    rustls::crypto::CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Reads a DER element, returns its tag, contents and the remaining input.
+pub(crate) fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&len, mut rest) = rest.split_first()?;
+
+    let len = if len & 0x80 == 0 {
+        len as usize
+    } else {
+        let len_bytes = (len & 0x7f) as usize;
+        if len_bytes == 0 || len_bytes > size_of::<usize>() {
+            return None;
+        }
+
+        let (len, remaining) = rest.split_at_checked(len_bytes)?;
+        rest = remaining;
+        len.iter().fold(0, |len, &byte| (len << 8) | byte as usize)
+    };
+
+    let (contents, rest) = rest.split_at_checked(len)?;
+    Some((tag, contents, rest))
+}
+
+/// Returns the expiry date (`notAfter`) of a X.509 certificate.
+pub fn not_after(cert: &CertificateDer<'_>) -> Option<SystemTime> {
+    const VERSION_TAG: u8 = 0xa0;
+
+    let (_, cert, _) = der_element(cert)?;
+    let (_, tbs_certificate, _) = der_element(cert)?;
+
+    // The version is optional, the serial number is not
+    let (tag, _, mut rest) = der_element(tbs_certificate)?;
+    if tag == VERSION_TAG {
+        (_, _, rest) = der_element(rest)?;
+    }
+
+    // signature algorithm and issuer
+    let (_, _, rest) = der_element(rest)?;
+    let (_, _, rest) = der_element(rest)?;
+
+    let (_, validity, _) = der_element(rest)?;
+    let (_, _, validity) = der_element(validity)?;
+    let (tag, time, _) = der_element(validity)?;
+
+    parse_der_time(tag, time)
+}
+
+/// Parses a DER `UTCTime` (`YYMMDDHHMMSSZ`) or `GeneralizedTime` (`YYYYMMDDHHMMSSZ`).
+fn parse_der_time(tag: u8, time: &[u8]) -> Option<SystemTime> {
+    const UTC_TIME_TAG: u8 = 0x17;
+    const GENERALIZED_TIME_TAG: u8 = 0x18;
+
+    let time = std::str::from_utf8(time).ok()?.strip_suffix('Z')?;
+    let number = |range: std::ops::Range<usize>| time.get(range)?.parse::<i64>().ok();
+
+    let (year, rest) = match tag {
+        UTC_TIME_TAG if time.len() == 12 => {
+            // RFC 5280: years 50 to 99 are 19xx, 00 to 49 are 20xx
+            let year = number(0..2)?;
+            (if year < 50 { 2000 + year } else { 1900 + year }, 2)
+        }
+        GENERALIZED_TIME_TAG if time.len() == 14 => (number(0..4)?, 4),
+        _ => return None,
+    };
+
+    let month = number(rest..rest + 2)?;
+    let day = number(rest + 2..rest + 4)?;
+    let hour = number(rest + 4..rest + 6)?;
+    let minute = number(rest + 6..rest + 8)?;
+    let second = number(rest + 8..rest + 10)?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+/// Returns the number of days since the unix epoch for a date in the proleptic gregorian calendar.
+///
+/// <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use tokio_rustls::rustls::pki_types::CertificateDer;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+
+    use super::{CertEvent, CertStore, days_from_civil, load_pem_files, not_after, parse_der_time};
+
+    fn asset(item: &str) -> std::path::PathBuf {
+        crate::tests::file_path(item)
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+
+    #[test]
+    fn der_time() {
+        let time = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+        assert_eq!(parse_der_time(0x17, b"700101000000Z"), time(0));
+        assert_eq!(parse_der_time(0x17, b"491231235959Z"), time(2524607999));
+        assert_eq!(parse_der_time(0x18, b"21250920171856Z"), time(4914062336));
+        assert_eq!(parse_der_time(0x18, b"21251320171856Z"), None);
+        assert_eq!(parse_der_time(0x17, b"21250920171856Z"), None);
+    }
+
+    #[test]
+    fn cert_not_after() {
+        let cert = CertificateDer::from_pem_file(asset("server_cert.pem")).expect("failed to read cert");
+        assert_eq!(
+            not_after(&cert),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(4914062336))
+        );
+        assert_eq!(not_after(&CertificateDer::from(vec![0x30, 0x80])), None);
+    }
+
+    #[test]
+    fn resolve() {
+        crate::tests::install_provider();
+
+        let server =
+            load_pem_files(&asset("server_cert.pem"), &asset("server_key.pem")).expect("failed to load server cert");
+        let root = load_pem_files(&asset("root_cert.pem"), &asset("root_key.pem")).expect("failed to load root cert");
+        let server_cert = server.cert.clone();
+        let root_cert = root.cert.clone();
+
+        let store = CertStore::new();
+        assert!(store.resolve_server_name(Some("localhost")).is_none());
+
+        store.insert(Some("*.example.com"), root);
+        store.insert(None, server);
+
+        let resolve = |name| store.resolve_server_name(name).map(|key| key.cert.clone());
+        assert_eq!(resolve(Some("a.example.com")), Some(root_cert.clone()));
+        assert_eq!(resolve(Some("A.Example.com")), Some(root_cert));
+        assert_eq!(resolve(Some("a.b.example.com")), Some(server_cert.clone()));
+        assert_eq!(resolve(Some("example.com")), Some(server_cert.clone()));
+        assert_eq!(resolve(None), Some(server_cert));
+
+        assert_eq!(store.certificates().len(), 2);
+        assert!(store.remove(Some("*.example.com")));
+        assert!(!store.remove(Some("*.example.com")));
+        assert_eq!(store.certificates().len(), 1);
+    }
+
+    #[test]
+    fn mismatched_key() {
+        crate::tests::install_provider();
+
+        let err = load_pem_files(&asset("server_cert.pem"), &asset("root_key.pem")).expect_err("keys should not match");
+        assert!(matches!(err, super::CertError::Rustls(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn watch_pem_files() {
+        crate::tests::install_provider();
+
+        let dir = std::env::temp_dir().join(format!("scuffle-http-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("failed to create temp dir");
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+
+        std::fs::copy(asset("server_cert.pem"), &cert_path).expect("failed to copy cert");
+        std::fs::copy(asset("server_key.pem"), &key_path).expect("failed to copy key");
+
+        let store = CertStore::new();
+        store
+            .load_pem_files(Some("localhost"), &cert_path, &key_path)
+            .expect("failed to load cert");
+
+        let mut events = store.subscribe();
+        let (ctx, handler) = scuffle_context::Context::new();
+        let watcher = tokio::spawn(store.clone().watch_pem_files(
+            ctx,
+            Some("localhost".to_owned()),
+            cert_path.clone(),
+            key_path.clone(),
+            Duration::from_millis(10),
+        ));
+
+        // Make sure the modification time changes
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // A cert without a matching key fails to reload
+        std::fs::copy(asset("root_cert.pem"), &cert_path).expect("failed to copy cert");
+        let event = events.recv().await.expect("failed to receive event");
+        assert!(matches!(event, CertEvent::ReloadFailed { .. }), "{event:?}");
+
+        std::fs::copy(asset("root_key.pem"), &key_path).expect("failed to copy key");
+        loop {
+            match events.recv().await.expect("failed to receive event") {
+                CertEvent::ReloadFailed { .. } => continue,
+                event => {
+                    assert!(
+                        matches!(&event, CertEvent::Loaded { server_name: Some(name), not_after: Some(_) } if name == "localhost"),
+                        "{event:?}"
+                    );
+                    break;
+                }
+            }
+        }
+
+        let root = CertificateDer::from_pem_file(asset("root_cert.pem")).expect("failed to read cert");
+        let resolved = store.resolve_server_name(Some("localhost")).expect("expected a certificate");
+        assert_eq!(resolved.cert[0], root);
+
+        handler.shutdown().await;
+        watcher.await.expect("watcher panicked");
+        std::fs::remove_dir_all(dir).expect("failed to remove temp dir");
+    }
+}
@@ -0,0 +1,105 @@
+//! Codecs and their configurations.
+
+use bytes::Bytes;
+use scuffle_av1::AV1CodecConfigurationRecord;
+use scuffle_h264::AVCDecoderConfigurationRecord;
+use scuffle_h265::HEVCDecoderConfigurationRecord;
+
+/// The codec of a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// H.264/AVC video.
+    Avc,
+    /// H.265/HEVC video.
+    Hevc,
+    /// AV1 video.
+    Av1,
+    /// VP8 video.
+    Vp8,
+    /// VP9 video.
+    Vp9,
+    /// AAC audio.
+    Aac,
+    /// Opus audio.
+    Opus,
+}
+
+impl Codec {
+    /// Returns true if this is a video codec.
+    pub const fn is_video(&self) -> bool {
+        matches!(self, Self::Avc | Self::Hevc | Self::Av1 | Self::Vp8 | Self::Vp9)
+    }
+
+    /// Returns true if this is an audio codec.
+    pub const fn is_audio(&self) -> bool {
+        !self.is_video()
+    }
+}
+
+/// The decoder configuration of a track.
+///
+/// This is what FLV calls a sequence header and MP4 a sample entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodecConfig {
+    /// H.264/AVC decoder configuration record.
+    Avc(AVCDecoderConfigurationRecord),
+    /// H.265/HEVC decoder configuration record.
+    Hevc(HEVCDecoderConfigurationRecord),
+    /// AV1 codec configuration record.
+    Av1(AV1CodecConfigurationRecord),
+    /// VP8, with the VP codec configuration record if it is known.
+    Vp8(Option<Bytes>),
+    /// VP9, with the VP codec configuration record if it is known.
+    Vp9(Option<Bytes>),
+    /// AAC.
+    Aac {
+        /// The `AudioSpecificConfig`.
+        audio_specific_config: Bytes,
+        /// The number of channels.
+        channel_count: u8,
+        /// The size of a decoded sample in bits.
+        sample_size: u8,
+    },
+    /// Opus.
+    Opus {
+        /// The number of channels.
+        channel_count: u8,
+    },
+}
+
+impl CodecConfig {
+    /// The codec of this configuration.
+    pub const fn codec(&self) -> Codec {
+        match self {
+            Self::Avc(_) => Codec::Avc,
+            Self::Hevc(_) => Codec::Hevc,
+            Self::Av1(_) => Codec::Av1,
+            Self::Vp8(_) => Codec::Vp8,
+            Self::Vp9(_) => Codec::Vp9,
+            Self::Aac { .. } => Codec::Aac,
+            Self::Opus { .. } => Codec::Opus,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{Codec, CodecConfig};
+
+    #[test]
+    fn codec() {
+        let config = CodecConfig::Aac {
+            audio_specific_config: Bytes::from_static(&[0x12, 0x10]),
+            channel_count: 2,
+            sample_size: 16,
+        };
+        assert_eq!(config.codec(), Codec::Aac);
+        assert!(config.codec().is_audio());
+        assert_eq!(CodecConfig::Vp9(None).codec(), Codec::Vp9);
+        assert!(Codec::Vp9.is_video());
+        assert!(!Codec::Opus.is_video());
+    }
+}
@@ -0,0 +1,14 @@
+//! Error types.
+
+use std::io;
+
+/// An error that occurred while converting media.
+#[derive(Debug, thiserror::Error)]
+pub enum MediaError {
+    /// The AAC `AudioSpecificConfig` could not be parsed.
+    #[error("invalid audio specific config: {0}")]
+    InvalidAudioSpecificConfig(io::Error),
+    /// The Opus identification header is invalid.
+    #[error("invalid opus identification header")]
+    InvalidOpusHead,
+}
@@ -0,0 +1,767 @@
+//! Conversion of [`scuffle_flv`] tags to media events.
+
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use scuffle_flv::audio::AudioData;
+use scuffle_flv::audio::body::AudioTagBody;
+use scuffle_flv::audio::body::enhanced::{AudioPacket, ExAudioTagBody};
+use scuffle_flv::audio::body::legacy::LegacyAudioTagBody;
+use scuffle_flv::audio::body::legacy::aac::AacAudioData;
+use scuffle_flv::audio::header::AudioTagHeader;
+use scuffle_flv::audio::header::enhanced::AudioFourCc;
+use scuffle_flv::audio::header::legacy::{SoundSize, SoundType};
+use scuffle_flv::script::{OnMetaData, ScriptData};
+use scuffle_flv::tag::{FlvTag, FlvTagData};
+use scuffle_flv::video::VideoData;
+use scuffle_flv::video::body::VideoTagBody;
+use scuffle_flv::video::body::enhanced::metadata::{MetadataColorInfo, VideoPacketMetadataEntry};
+use scuffle_flv::video::body::enhanced::{ExVideoTagBody, VideoPacket, VideoPacketCodedFrames, VideoPacketSequenceStart};
+use scuffle_flv::video::body::legacy::LegacyVideoTagBody;
+use scuffle_flv::video::header::enhanced::VideoFourCc;
+use scuffle_flv::video::header::legacy::{LegacyVideoTagHeader, LegacyVideoTagHeaderAvcPacket};
+use scuffle_flv::video::header::{VideoFrameType, VideoTagHeaderData};
+
+use crate::codec::{Codec, CodecConfig};
+use crate::error::MediaError;
+use crate::frame::{MediaEvent, MediaFrame, TrackConfig};
+use crate::side_data::{ContentLightLevel, MasteringDisplayColourVolume, SideData};
+use crate::timebase::Timebase;
+
+/// The track id of the video of a FLV stream without multiple tracks.
+pub const VIDEO_TRACK_ID: u32 = 1;
+/// The track id of the audio of a FLV stream without multiple tracks.
+pub const AUDIO_TRACK_ID: u32 = 2;
+
+/// The track id of the FLV video track `flv_track_id` of a multitrack stream.
+///
+/// The video track 0 is [`VIDEO_TRACK_ID`].
+pub const fn video_track_id(flv_track_id: u8) -> u32 {
+    VIDEO_TRACK_ID + 2 * flv_track_id as u32
+}
+
+/// The track id of the FLV audio track `flv_track_id` of a multitrack stream.
+///
+/// The audio track 0 is [`AUDIO_TRACK_ID`].
+pub const fn audio_track_id(flv_track_id: u8) -> u32 {
+    AUDIO_TRACK_ID + 2 * flv_track_id as u32
+}
+
+/// The stream information of the last `onMetaData` script tag.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct StreamInfo {
+    frame_rate: Option<f64>,
+    video_bitrate: Option<u32>,
+    audio_bitrate: Option<u32>,
+}
+
+impl StreamInfo {
+    fn new(metadata: &OnMetaData<'_>) -> Self {
+        // The data rates are in kilobits per second.
+        Self {
+            frame_rate: metadata.framerate.filter(|rate| *rate > 0.0),
+            video_bitrate: metadata.videodatarate.map(|rate| (rate * 1024.0) as u32),
+            audio_bitrate: metadata.audiodatarate.map(|rate| (rate * 1024.0) as u32),
+        }
+    }
+
+    /// Applies the information to a track, it only describes the first
+    /// video and audio track.
+    fn apply(&self, config: &mut TrackConfig) {
+        match config.track_id {
+            VIDEO_TRACK_ID => {
+                config.frame_rate = self.frame_rate;
+                config.bitrate = self.video_bitrate;
+            }
+            AUDIO_TRACK_ID => config.bitrate = self.audio_bitrate,
+            _ => {}
+        }
+    }
+}
+
+/// Converts FLV tags to [`MediaEvent`]s.
+///
+/// Sequence headers become [`MediaEvent::Config`] and coded frames
+/// [`MediaEvent::Frame`] with millisecond timestamps. The frame rate and
+/// bitrates of `onMetaData` are added to the track configurations, a
+/// configuration is sent again if they change. HDR metadata of enhanced
+/// video tags is attached to the next frame of the track.
+///
+/// Supported are H.264, H.265, AV1, VP8 and VP9 video and AAC and Opus
+/// audio, in legacy, enhanced and multitrack tags. Tags of other codecs are
+/// ignored.
+#[derive(Debug, Clone, Default)]
+pub struct FlvConverter {
+    info: StreamInfo,
+    /// The last configuration of each track.
+    configs: BTreeMap<u32, TrackConfig>,
+    /// Side data for the next frame of each track.
+    side_data: BTreeMap<u32, Vec<SideData>>,
+}
+
+impl FlvConverter {
+    /// Creates a new converter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last configuration of a track.
+    pub fn config(&self, track_id: u32) -> Option<&TrackConfig> {
+        self.configs.get(&track_id)
+    }
+
+    /// Converts a tag to the media events it contains.
+    pub fn convert(&mut self, tag: FlvTag<'_>) -> Result<Vec<MediaEvent>, MediaError> {
+        let mut events = Vec::new();
+        let timestamp = tag.timestamp_ms as i64;
+
+        match tag.data {
+            FlvTagData::Video(video) => self.video(timestamp, video, &mut events),
+            FlvTagData::Audio(audio) => self.audio(timestamp, audio, &mut events)?,
+            FlvTagData::ScriptData(ScriptData::OnMetaData(metadata)) => self.metadata(&metadata, &mut events),
+            _ => {}
+        }
+
+        Ok(events)
+    }
+
+    fn metadata(&mut self, metadata: &OnMetaData<'_>, events: &mut Vec<MediaEvent>) {
+        let info = StreamInfo::new(metadata);
+        if info == self.info {
+            return;
+        }
+
+        self.info = info;
+        for config in self.configs.values_mut() {
+            let previous = config.clone();
+            self.info.apply(config);
+            if *config != previous {
+                events.push(MediaEvent::Config(config.clone()));
+            }
+        }
+    }
+
+    fn configure(&mut self, track_id: u32, config: CodecConfig, events: &mut Vec<MediaEvent>) {
+        let mut config = TrackConfig::new(track_id, config);
+        self.info.apply(&mut config);
+        self.configs.insert(track_id, config.clone());
+        events.push(MediaEvent::Config(config));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn frame(
+        &mut self,
+        track_id: u32,
+        codec: Codec,
+        timestamp: i64,
+        composition_time_offset: i64,
+        keyframe: bool,
+        data: Bytes,
+        events: &mut Vec<MediaEvent>,
+    ) {
+        events.push(MediaEvent::Frame(MediaFrame {
+            track_id,
+            codec,
+            timebase: Timebase::MILLISECONDS,
+            dts: timestamp,
+            pts: timestamp + composition_time_offset,
+            keyframe,
+            data,
+            side_data: self.side_data.remove(&track_id).unwrap_or_default(),
+        }));
+    }
+
+    fn video(&mut self, timestamp: i64, video: VideoData<'_>, events: &mut Vec<MediaEvent>) {
+        let keyframe = video.header.frame_type == VideoFrameType::KeyFrame;
+
+        match video.body {
+            VideoTagBody::Legacy(LegacyVideoTagBody::AvcVideoPacketSeqHdr(config)) => {
+                self.configure(VIDEO_TRACK_ID, CodecConfig::Avc(config), events);
+            }
+            VideoTagBody::Legacy(LegacyVideoTagBody::Other { data }) => {
+                if let VideoTagHeaderData::Legacy(LegacyVideoTagHeader::AvcPacket(LegacyVideoTagHeaderAvcPacket::Nalu {
+                    composition_time_offset,
+                })) = video.header.data
+                {
+                    // The composition time offset is a signed 24 bit integer.
+                    let composition_time_offset = ((composition_time_offset << 8) as i32 >> 8) as i64;
+                    self.frame(
+                        VIDEO_TRACK_ID,
+                        Codec::Avc,
+                        timestamp,
+                        composition_time_offset,
+                        keyframe,
+                        data,
+                        events,
+                    );
+                }
+            }
+            VideoTagBody::Enhanced(ExVideoTagBody::NoMultitrack { video_four_cc, packet }) => {
+                self.video_packet(VIDEO_TRACK_ID, video_four_cc, timestamp, keyframe, packet, events);
+            }
+            VideoTagBody::Enhanced(ExVideoTagBody::ManyTracks(tracks)) => {
+                for track in tracks {
+                    let track_id = video_track_id(track.video_track_id);
+                    self.video_packet(track_id, track.video_four_cc, timestamp, keyframe, track.packet, events);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn video_packet(
+        &mut self,
+        track_id: u32,
+        four_cc: VideoFourCc,
+        timestamp: i64,
+        keyframe: bool,
+        packet: VideoPacket<'_>,
+        events: &mut Vec<MediaEvent>,
+    ) {
+        let codec = match four_cc {
+            VideoFourCc::Avc => Codec::Avc,
+            VideoFourCc::Hevc => Codec::Hevc,
+            VideoFourCc::Av1 => Codec::Av1,
+            VideoFourCc::Vp8 => Codec::Vp8,
+            VideoFourCc::Vp9 => Codec::Vp9,
+            _ => return,
+        };
+
+        match packet {
+            VideoPacket::SequenceStart(start) => {
+                let config = match (codec, start) {
+                    (Codec::Avc, VideoPacketSequenceStart::Avc(config)) => CodecConfig::Avc(config),
+                    (Codec::Hevc, VideoPacketSequenceStart::Hevc(config)) => CodecConfig::Hevc(config),
+                    (Codec::Av1, VideoPacketSequenceStart::Av1(config)) => CodecConfig::Av1(config),
+                    (Codec::Vp8, VideoPacketSequenceStart::Other(config)) => CodecConfig::Vp8(Some(config)),
+                    (Codec::Vp9, VideoPacketSequenceStart::Other(config)) => CodecConfig::Vp9(Some(config)),
+                    _ => return,
+                };
+
+                self.configure(track_id, config, events);
+            }
+            VideoPacket::CodedFrames(
+                VideoPacketCodedFrames::Avc {
+                    composition_time_offset,
+                    data,
+                }
+                | VideoPacketCodedFrames::Hevc {
+                    composition_time_offset,
+                    data,
+                },
+            ) => {
+                self.frame(
+                    track_id,
+                    codec,
+                    timestamp,
+                    composition_time_offset as i64,
+                    keyframe,
+                    data,
+                    events,
+                );
+            }
+            VideoPacket::CodedFrames(VideoPacketCodedFrames::Other(data)) | VideoPacket::CodedFramesX { data } => {
+                self.frame(track_id, codec, timestamp, 0, keyframe, data, events);
+            }
+            VideoPacket::Metadata(entries) => {
+                for entry in entries {
+                    if let VideoPacketMetadataEntry::ColorInfo(info) = entry {
+                        self.side_data
+                            .entry(track_id)
+                            .or_default()
+                            .extend(color_info_side_data(&info));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn audio(&mut self, timestamp: i64, audio: AudioData, events: &mut Vec<MediaEvent>) -> Result<(), MediaError> {
+        match audio.body {
+            AudioTagBody::Legacy(LegacyAudioTagBody::Aac(AacAudioData::SequenceHeader(data))) => {
+                let (channel_count, sample_size) = match audio.header {
+                    AudioTagHeader::Legacy(header) => (
+                        if header.sound_type == SoundType::Mono { 1 } else { 2 },
+                        if header.sound_size == SoundSize::Bit8 { 8 } else { 16 },
+                    ),
+                    AudioTagHeader::Enhanced(_) => (aac_channel_count(&data)?, 16),
+                };
+
+                let config = CodecConfig::Aac {
+                    audio_specific_config: data,
+                    channel_count,
+                    sample_size,
+                };
+                self.configure(AUDIO_TRACK_ID, config, events);
+            }
+            AudioTagBody::Legacy(LegacyAudioTagBody::Aac(AacAudioData::Raw(data))) => {
+                self.frame(AUDIO_TRACK_ID, Codec::Aac, timestamp, 0, true, data, events);
+            }
+            AudioTagBody::Enhanced(ExAudioTagBody::NoMultitrack { audio_four_cc, packet }) => {
+                self.audio_packet(AUDIO_TRACK_ID, audio_four_cc, timestamp, packet, events)?;
+            }
+            AudioTagBody::Enhanced(ExAudioTagBody::ManyTracks(tracks)) => {
+                for track in tracks {
+                    let track_id = audio_track_id(track.audio_track_id);
+                    self.audio_packet(track_id, track.audio_four_cc, timestamp, track.packet, events)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn audio_packet(
+        &mut self,
+        track_id: u32,
+        four_cc: AudioFourCc,
+        timestamp: i64,
+        packet: AudioPacket,
+        events: &mut Vec<MediaEvent>,
+    ) -> Result<(), MediaError> {
+        let codec = match four_cc {
+            AudioFourCc::Aac => Codec::Aac,
+            AudioFourCc::Opus => Codec::Opus,
+            _ => return Ok(()),
+        };
+
+        match packet {
+            AudioPacket::SequenceStart { header_data } => {
+                let config = match codec {
+                    Codec::Aac => CodecConfig::Aac {
+                        channel_count: aac_channel_count(&header_data)?,
+                        audio_specific_config: header_data,
+                        sample_size: 16,
+                    },
+                    _ => CodecConfig::Opus {
+                        channel_count: opus_channel_count(&header_data)?,
+                    },
+                };
+
+                self.configure(track_id, config, events);
+            }
+            AudioPacket::CodedFrames { data } => {
+                self.frame(track_id, codec, timestamp, 0, true, data, events);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn aac_channel_count(audio_specific_config: &[u8]) -> Result<u8, MediaError> {
+    let config = scuffle_aac::PartialAudioSpecificConfig::parse(audio_specific_config)
+        .map_err(MediaError::InvalidAudioSpecificConfig)?;
+
+    // Channel configuration 7 is 7.1 and 0 is defined in the bitstream,
+    // which we treat as stereo.
+    Ok(match config.channel_configuration {
+        0 => 2,
+        7 => 8,
+        channels => channels,
+    })
+}
+
+/// Reads the channel count of an Opus identification header (`OpusHead`).
+fn opus_channel_count(head: &[u8]) -> Result<u8, MediaError> {
+    match head {
+        [b'O', b'p', b'u', b's', b'H', b'e', b'a', b'd', _version, channel_count, ..] if *channel_count > 0 => {
+            Ok(*channel_count)
+        }
+        _ => Err(MediaError::InvalidOpusHead),
+    }
+}
+
+fn color_info_side_data(info: &MetadataColorInfo) -> Vec<SideData> {
+    let mut side_data = Vec::new();
+
+    if let Some(mdcv) = &info.hdr_mdcv {
+        // Chromaticity coordinates are in units of 0.00002 and luminance in
+        // units of 0.0001 cd/m².
+        let chromaticity = |x: Option<f64>, y: Option<f64>| {
+            let scale = |v: Option<f64>| (v.unwrap_or_default() * 50_000.0).round() as u16;
+            (scale(x), scale(y))
+        };
+        let luminance = |v: Option<f64>| (v.unwrap_or_default() * 10_000.0).round() as u32;
+
+        side_data.push(SideData::MasteringDisplayColourVolume(MasteringDisplayColourVolume {
+            red: chromaticity(mdcv.red_x, mdcv.red_y),
+            green: chromaticity(mdcv.green_x, mdcv.green_y),
+            blue: chromaticity(mdcv.blue_x, mdcv.blue_y),
+            white_point: chromaticity(mdcv.white_point_x, mdcv.white_point_y),
+            max_luminance: luminance(mdcv.max_luminance),
+            min_luminance: luminance(mdcv.min_luminance),
+        }));
+    }
+
+    if let Some(cll) = &info.hdr_cll {
+        side_data.push(SideData::ContentLightLevel(ContentLightLevel {
+            max_content_light_level: cll.max_cll.unwrap_or_default().round() as u16,
+            max_frame_average_light_level: cll.max_fall.unwrap_or_default().round() as u16,
+        }));
+    }
+
+    side_data
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes::Bytes;
+    use scuffle_flv::audio::AudioData;
+    use scuffle_flv::audio::body::AudioTagBody;
+    use scuffle_flv::audio::body::enhanced::{AudioPacket, AudioTrack, ExAudioTagBody};
+    use scuffle_flv::audio::body::legacy::LegacyAudioTagBody;
+    use scuffle_flv::audio::body::legacy::aac::AacAudioData;
+    use scuffle_flv::audio::header::AudioTagHeader;
+    use scuffle_flv::audio::header::enhanced::{AudioFourCc, AudioPacketType, ExAudioTagHeader, ExAudioTagHeaderContent};
+    use scuffle_flv::audio::header::legacy::{LegacyAudioTagHeader, SoundFormat, SoundRate, SoundSize, SoundType};
+    use scuffle_flv::script::{OnMetaData, ScriptData};
+    use scuffle_flv::tag::{FlvTag, FlvTagData};
+    use scuffle_flv::video::VideoData;
+    use scuffle_flv::video::body::VideoTagBody;
+    use scuffle_flv::video::body::enhanced::metadata::{
+        MetadataColorInfo, MetadataColorInfoHdrCll, MetadataColorInfoHdrMdcv, VideoPacketMetadataEntry,
+    };
+    use scuffle_flv::video::body::enhanced::{
+        ExVideoTagBody, VideoPacket, VideoPacketCodedFrames, VideoPacketSequenceStart,
+    };
+    use scuffle_flv::video::body::legacy::LegacyVideoTagBody;
+    use scuffle_flv::video::header::enhanced::{ExVideoTagHeader, ExVideoTagHeaderContent, VideoFourCc, VideoPacketType};
+    use scuffle_flv::video::header::legacy::{LegacyVideoTagHeader, LegacyVideoTagHeaderAvcPacket};
+    use scuffle_flv::video::header::{VideoFrameType, VideoTagHeader, VideoTagHeaderData};
+
+    use super::{AUDIO_TRACK_ID, FlvConverter, VIDEO_TRACK_ID, audio_track_id, video_track_id};
+    use crate::codec::{Codec, CodecConfig};
+    use crate::error::MediaError;
+    use crate::frame::{MediaEvent, MediaFrame};
+    use crate::side_data::{ContentLightLevel, MasteringDisplayColourVolume, SideData};
+    use crate::timebase::Timebase;
+
+    fn tag(timestamp_ms: u32, data: FlvTagData<'static>) -> FlvTag<'static> {
+        FlvTag {
+            timestamp_ms,
+            stream_id: 0,
+            data,
+        }
+    }
+
+    fn metadata(framerate: f64, videodatarate: f64, audiodatarate: f64) -> FlvTag<'static> {
+        tag(
+            0,
+            FlvTagData::ScriptData(ScriptData::OnMetaData(Box::new(OnMetaData {
+                audiocodecid: None,
+                audiodatarate: Some(audiodatarate),
+                audiodelay: None,
+                audiosamplerate: None,
+                audiosamplesize: None,
+                can_seek_to_end: None,
+                creationdate: None,
+                duration: None,
+                filesize: None,
+                framerate: Some(framerate),
+                height: None,
+                stereo: None,
+                videocodecid: None,
+                videodatarate: Some(videodatarate),
+                width: None,
+                audio_track_id_info_map: None,
+                video_track_id_info_map: None,
+                other: Default::default(),
+            }))),
+        )
+    }
+
+    fn legacy_aac(timestamp_ms: u32, data: AacAudioData) -> FlvTag<'static> {
+        tag(
+            timestamp_ms,
+            FlvTagData::Audio(AudioData {
+                header: AudioTagHeader::Legacy(LegacyAudioTagHeader {
+                    sound_format: SoundFormat::Aac,
+                    sound_rate: SoundRate::Hz44000,
+                    sound_size: SoundSize::Bit16,
+                    sound_type: SoundType::Stereo,
+                }),
+                body: AudioTagBody::Legacy(LegacyAudioTagBody::Aac(data)),
+            }),
+        )
+    }
+
+    fn enhanced_video(
+        timestamp_ms: u32,
+        frame_type: VideoFrameType,
+        video_packet_type: VideoPacketType,
+        packet: VideoPacket<'static>,
+    ) -> FlvTag<'static> {
+        tag(
+            timestamp_ms,
+            FlvTagData::Video(VideoData {
+                header: VideoTagHeader {
+                    frame_type,
+                    data: VideoTagHeaderData::Enhanced(ExVideoTagHeader {
+                        video_packet_mod_exs: Vec::new(),
+                        video_packet_type,
+                        content: ExVideoTagHeaderContent::NoMultiTrack(VideoFourCc::Vp9),
+                    }),
+                },
+                body: VideoTagBody::Enhanced(ExVideoTagBody::NoMultitrack {
+                    video_four_cc: VideoFourCc::Vp9,
+                    packet,
+                }),
+            }),
+        )
+    }
+
+    #[test]
+    fn legacy_aac_with_metadata() {
+        let mut converter = FlvConverter::new();
+
+        assert!(converter.convert(metadata(30.0, 2500.0, 128.0)).unwrap().is_empty());
+
+        let events = converter
+            .convert(legacy_aac(0, AacAudioData::SequenceHeader(Bytes::from_static(&[0x12, 0x10]))))
+            .unwrap();
+        let [MediaEvent::Config(config)] = events.as_slice() else {
+            panic!("expected a config: {events:?}");
+        };
+        assert_eq!(config.track_id, AUDIO_TRACK_ID);
+        assert_eq!(config.bitrate, Some(128 * 1024));
+        assert_eq!(config.frame_rate, None);
+        assert_eq!(
+            config.config,
+            CodecConfig::Aac {
+                audio_specific_config: Bytes::from_static(&[0x12, 0x10]),
+                channel_count: 2,
+                sample_size: 16,
+            }
+        );
+        assert_eq!(converter.config(AUDIO_TRACK_ID), Some(config));
+
+        let events = converter
+            .convert(legacy_aac(21, AacAudioData::Raw(Bytes::from_static(b"aac"))))
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![MediaEvent::Frame(MediaFrame {
+                track_id: AUDIO_TRACK_ID,
+                codec: Codec::Aac,
+                timebase: Timebase::MILLISECONDS,
+                dts: 21,
+                pts: 21,
+                keyframe: true,
+                data: Bytes::from_static(b"aac"),
+                side_data: Vec::new(),
+            })]
+        );
+
+        // The same metadata doesn't change anything.
+        assert!(converter.convert(metadata(30.0, 2500.0, 128.0)).unwrap().is_empty());
+
+        // A changed bitrate configures the track again.
+        let events = converter.convert(metadata(30.0, 2500.0, 160.0)).unwrap();
+        let [MediaEvent::Config(config)] = events.as_slice() else {
+            panic!("expected a config: {events:?}");
+        };
+        assert_eq!(config.bitrate, Some(160 * 1024));
+    }
+
+    #[test]
+    fn legacy_avc_composition_time_offset() {
+        let mut converter = FlvConverter::new();
+
+        let events = converter
+            .convert(tag(
+                100,
+                FlvTagData::Video(VideoData {
+                    header: VideoTagHeader {
+                        frame_type: VideoFrameType::InterFrame,
+                        data: VideoTagHeaderData::Legacy(LegacyVideoTagHeader::AvcPacket(
+                            LegacyVideoTagHeaderAvcPacket::Nalu {
+                                // -10 as a 24 bit integer
+                                composition_time_offset: 0xFF_FFF6,
+                            },
+                        )),
+                    },
+                    body: VideoTagBody::Legacy(LegacyVideoTagBody::Other {
+                        data: Bytes::from_static(b"nalu"),
+                    }),
+                }),
+            ))
+            .unwrap();
+
+        let [MediaEvent::Frame(frame)] = events.as_slice() else {
+            panic!("expected a frame: {events:?}");
+        };
+        assert_eq!(frame.track_id, VIDEO_TRACK_ID);
+        assert_eq!(frame.codec, Codec::Avc);
+        assert_eq!((frame.dts, frame.pts), (100, 90));
+        assert_eq!(frame.composition_offset(), -10);
+        assert!(!frame.keyframe);
+    }
+
+    #[test]
+    fn enhanced_vp9_with_hdr_metadata() {
+        let mut converter = FlvConverter::new();
+
+        converter.convert(metadata(60.0, 6000.0, 128.0)).unwrap();
+
+        let events = converter
+            .convert(enhanced_video(
+                0,
+                VideoFrameType::KeyFrame,
+                VideoPacketType::SequenceStart,
+                VideoPacket::SequenceStart(VideoPacketSequenceStart::Other(Bytes::from_static(b"vpcc"))),
+            ))
+            .unwrap();
+        let [MediaEvent::Config(config)] = events.as_slice() else {
+            panic!("expected a config: {events:?}");
+        };
+        assert_eq!(config.config, CodecConfig::Vp9(Some(Bytes::from_static(b"vpcc"))));
+        assert_eq!(config.frame_rate, Some(60.0));
+        assert_eq!(config.bitrate, Some(6000 * 1024));
+
+        let color_info = MetadataColorInfo {
+            color_config: None,
+            hdr_cll: Some(MetadataColorInfoHdrCll {
+                max_fall: Some(400.0),
+                max_cll: Some(1000.0),
+            }),
+            hdr_mdcv: Some(MetadataColorInfoHdrMdcv {
+                red_x: Some(0.708),
+                red_y: Some(0.292),
+                green_x: Some(0.17),
+                green_y: Some(0.797),
+                blue_x: Some(0.131),
+                blue_y: Some(0.046),
+                white_point_x: Some(0.3127),
+                white_point_y: Some(0.329),
+                max_luminance: Some(1000.0),
+                min_luminance: Some(0.0001),
+            }),
+        };
+        let events = converter
+            .convert(enhanced_video(
+                0,
+                VideoFrameType::KeyFrame,
+                VideoPacketType::Metadata,
+                VideoPacket::Metadata(vec![VideoPacketMetadataEntry::ColorInfo(color_info)]),
+            ))
+            .unwrap();
+        assert!(events.is_empty());
+
+        let events = converter
+            .convert(enhanced_video(
+                0,
+                VideoFrameType::KeyFrame,
+                VideoPacketType::CodedFrames,
+                VideoPacket::CodedFrames(VideoPacketCodedFrames::Other(Bytes::from_static(b"frame"))),
+            ))
+            .unwrap();
+        let [MediaEvent::Frame(frame)] = events.as_slice() else {
+            panic!("expected a frame: {events:?}");
+        };
+        assert_eq!(frame.codec, Codec::Vp9);
+        assert!(frame.keyframe);
+        assert_eq!(
+            frame.side_data,
+            vec![
+                SideData::MasteringDisplayColourVolume(MasteringDisplayColourVolume {
+                    red: (35400, 14600),
+                    green: (8500, 39850),
+                    blue: (6550, 2300),
+                    white_point: (15635, 16450),
+                    max_luminance: 10_000_000,
+                    min_luminance: 1,
+                }),
+                SideData::ContentLightLevel(ContentLightLevel {
+                    max_content_light_level: 1000,
+                    max_frame_average_light_level: 400,
+                }),
+            ]
+        );
+
+        // The side data is only attached once.
+        let events = converter
+            .convert(enhanced_video(
+                33,
+                VideoFrameType::InterFrame,
+                VideoPacketType::CodedFramesX,
+                VideoPacket::CodedFramesX {
+                    data: Bytes::from_static(b"frame"),
+                },
+            ))
+            .unwrap();
+        let [MediaEvent::Frame(frame)] = events.as_slice() else {
+            panic!("expected a frame: {events:?}");
+        };
+        assert!(frame.side_data.is_empty());
+        assert!(!frame.keyframe);
+    }
+
+    #[test]
+    fn multitrack_opus() {
+        let mut converter = FlvConverter::new();
+
+        let head = Bytes::from_static(b"OpusHead\x01\x06\x38\x01\x80\xbb\x00\x00\x00\x00");
+        let audio = |packet: AudioPacket| {
+            tag(
+                40,
+                FlvTagData::Audio(AudioData {
+                    header: AudioTagHeader::Enhanced(ExAudioTagHeader {
+                        audio_packet_mod_exs: Vec::new(),
+                        audio_packet_type: AudioPacketType::Multitrack,
+                        content: ExAudioTagHeaderContent::ManyTracks(AudioFourCc::Opus),
+                    }),
+                    body: AudioTagBody::Enhanced(ExAudioTagBody::ManyTracks(vec![
+                        AudioTrack {
+                            audio_four_cc: AudioFourCc::Opus,
+                            audio_track_id: 0,
+                            packet: packet.clone(),
+                        },
+                        AudioTrack {
+                            audio_four_cc: AudioFourCc::Opus,
+                            audio_track_id: 1,
+                            packet,
+                        },
+                    ])),
+                }),
+            )
+        };
+
+        let events = converter
+            .convert(audio(AudioPacket::SequenceStart { header_data: head }))
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].track_id(), AUDIO_TRACK_ID);
+        assert_eq!(events[1].track_id(), audio_track_id(1));
+        let MediaEvent::Config(config) = &events[1] else {
+            panic!("expected a config: {events:?}");
+        };
+        assert_eq!(config.config, CodecConfig::Opus { channel_count: 6 });
+
+        let events = converter
+            .convert(audio(AudioPacket::CodedFrames {
+                data: Bytes::from_static(b"opus"),
+            }))
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(
+            events
+                .iter()
+                .all(|event| matches!(event, MediaEvent::Frame(frame) if frame.codec == Codec::Opus))
+        );
+
+        let err = converter
+            .convert(audio(AudioPacket::SequenceStart {
+                header_data: Bytes::from_static(b"OpusTags"),
+            }))
+            .unwrap_err();
+        assert!(matches!(err, MediaError::InvalidOpusHead));
+
+        assert_eq!(video_track_id(0), VIDEO_TRACK_ID);
+        assert_eq!(video_track_id(2), 5);
+    }
+}
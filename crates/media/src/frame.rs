@@ -0,0 +1,119 @@
+//! Frames and track events.
+
+use bytes::Bytes;
+
+use crate::codec::{Codec, CodecConfig};
+use crate::side_data::SideData;
+use crate::timebase::Timebase;
+
+/// The configuration of a track.
+///
+/// A track is configured before its first frame. A new configuration of the
+/// same track signals a codec configuration change, every following frame
+/// uses the new configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackConfig {
+    /// The id of the track.
+    pub track_id: u32,
+    /// The decoder configuration.
+    pub config: CodecConfig,
+    /// The frame rate of a video track, if it is known.
+    pub frame_rate: Option<f64>,
+    /// The estimated bitrate in bits per second, if it is known.
+    pub bitrate: Option<u32>,
+}
+
+impl TrackConfig {
+    /// Creates a new track configuration.
+    pub fn new(track_id: u32, config: CodecConfig) -> Self {
+        Self {
+            track_id,
+            config,
+            frame_rate: None,
+            bitrate: None,
+        }
+    }
+
+    /// The codec of the track.
+    pub const fn codec(&self) -> Codec {
+        self.config.codec()
+    }
+}
+
+/// A single access unit of a track.
+///
+/// The data is in the sample format of ISO BMFF:
+///
+/// - length prefixed NAL units for H.264 and H.265, with the length size of
+///   the decoder configuration record,
+/// - OBUs in the low overhead bitstream format for AV1,
+/// - a single frame for VP8 and VP9,
+/// - a raw AAC frame without ADTS or LATM headers,
+/// - a single Opus packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFrame {
+    /// The id of the track.
+    pub track_id: u32,
+    /// The codec of the frame.
+    pub codec: Codec,
+    /// The timebase of [`dts`](Self::dts) and [`pts`](Self::pts).
+    pub timebase: Timebase,
+    /// The decoding timestamp.
+    pub dts: i64,
+    /// The presentation timestamp.
+    pub pts: i64,
+    /// True if decoding can start at this frame.
+    pub keyframe: bool,
+    /// The coded data.
+    pub data: Bytes,
+    /// Side data of this frame.
+    pub side_data: Vec<SideData>,
+}
+
+impl MediaFrame {
+    /// The difference between the presentation and the decoding timestamp.
+    pub const fn composition_offset(&self) -> i64 {
+        self.pts - self.dts
+    }
+
+    /// The decoding timestamp in the timebase `to`.
+    pub fn dts_in(&self, to: Timebase) -> i64 {
+        self.timebase.rescale(self.dts, to)
+    }
+
+    /// The presentation timestamp in the timebase `to`.
+    pub fn pts_in(&self, to: Timebase) -> i64 {
+        self.timebase.rescale(self.pts, to)
+    }
+}
+
+/// An event of a media stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaEvent {
+    /// A track was configured or its configuration changed.
+    Config(TrackConfig),
+    /// A frame of a track.
+    Frame(MediaFrame),
+}
+
+impl MediaEvent {
+    /// The id of the track of this event.
+    pub const fn track_id(&self) -> u32 {
+        match self {
+            Self::Config(config) => config.track_id,
+            Self::Frame(frame) => frame.track_id,
+        }
+    }
+}
+
+impl From<TrackConfig> for MediaEvent {
+    fn from(config: TrackConfig) -> Self {
+        Self::Config(config)
+    }
+}
+
+impl From<MediaFrame> for MediaEvent {
+    fn from(frame: MediaFrame) -> Self {
+        Self::Frame(frame)
+    }
+}
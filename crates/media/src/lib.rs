@@ -0,0 +1,77 @@
+//! A codec-agnostic access unit model, bridging ingest protocols and output
+//! formats.
+//!
+//! A media stream is a sequence of [`MediaEvent`]s:
+//!
+//! - [`TrackConfig`] configures a track with its codec and decoder
+//!   configuration, a new configuration of the same track signals a codec
+//!   configuration change,
+//! - [`MediaFrame`] is a single access unit of a track, with decoding and
+//!   presentation timestamps in a rational [`Timebase`], a keyframe flag and
+//!   [`SideData`] like HDR metadata and captions.
+//!
+//! Ingest protocols convert what they receive to these events and packagers
+//! consume them, so neither has to know about the other. The `flv` feature
+//! enables the conversion of [`scuffle-flv`][scuffle_flv] tags and the `mp4`
+//! feature the conversion of frames to [`scuffle-mp4`][scuffle_mp4] samples.
+#![cfg_attr(feature = "docs", doc = "\n\nSee the [changelog][changelog] for a full release history.")]
+#![cfg_attr(feature = "docs", doc = "## Feature flags")]
+#![cfg_attr(feature = "docs", doc = document_features::document_features!())]
+//! ## Example
+//!
+//! ```rust
+//! # use bytes::Bytes;
+//! # use scuffle_media::{Codec, MediaEvent, MediaFrame, Timebase};
+//! #
+//! // An Opus packet received over RTP with a 48 kHz clock.
+//! let frame = MediaFrame {
+//!     track_id: 2,
+//!     codec: Codec::Opus,
+//!     timebase: Timebase::from_rate(48_000),
+//!     dts: 96_000,
+//!     pts: 96_000,
+//!     keyframe: true,
+//!     data: Bytes::from_static(b"opus"),
+//!     side_data: Vec::new(),
+//! };
+//!
+//! assert_eq!(frame.dts_in(Timebase::MILLISECONDS), 2000);
+//!
+//! let event = MediaEvent::from(frame);
+//! assert_eq!(event.track_id(), 2);
+//! ```
+//!
+//! ## License
+//!
+//! This project is licensed under the MIT or Apache-2.0 license.
+//! You can choose between one of them if you use this work.
+//!
+//! `SPDX-License-Identifier: MIT OR Apache-2.0`
+#![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
+#![deny(unreachable_pub)]
+#![deny(clippy::mod_module_files)]
+
+mod codec;
+mod frame;
+mod side_data;
+mod timebase;
+
+pub mod error;
+#[cfg(feature = "flv")]
+pub mod flv;
+#[cfg(feature = "mp4")]
+pub mod mp4;
+
+pub use codec::{Codec, CodecConfig};
+pub use error::MediaError;
+pub use frame::{MediaEvent, MediaFrame, TrackConfig};
+pub use side_data::{ContentLightLevel, MasteringDisplayColourVolume, SideData};
+pub use timebase::Timebase;
+
+/// Changelogs generated by [scuffle_changelog]
+#[cfg(feature = "docs")]
+#[scuffle_changelog::changelog]
+pub mod changelog {}
@@ -0,0 +1,95 @@
+//! Conversion of media frames to [`scuffle_mp4`] samples.
+
+use scuffle_mp4::types::trun::{TrunSample, TrunSampleFlag};
+
+use crate::codec::Codec;
+use crate::frame::MediaFrame;
+use crate::timebase::Timebase;
+
+/// Creates the `trun` sample of a frame.
+///
+/// The `duration` is in the `timescale` of the track, the composition offset
+/// of the frame is converted to it. Only H.264 and H.265 samples have a
+/// composition offset, the other codecs have no reordering.
+///
+/// The data of the frame is the data of the sample in the `mdat` box.
+pub fn trun_sample(frame: &MediaFrame, timescale: u32, duration: u32) -> TrunSample {
+    let composition_time_offset = match frame.codec {
+        Codec::Avc | Codec::Hevc => Some(
+            frame
+                .timebase
+                .rescale(frame.composition_offset(), Timebase::from_rate(timescale)),
+        ),
+        _ => None,
+    };
+
+    // Every audio frame can be decoded on its own.
+    let sync = frame.keyframe || frame.codec.is_audio();
+
+    TrunSample {
+        duration: Some(duration),
+        composition_time_offset,
+        flags: Some(TrunSampleFlag {
+            reserved: 0,
+            is_leading: 0,
+            sample_degradation_priority: 0,
+            sample_depends_on: if sync { 2 } else { 1 },
+            sample_has_redundancy: 0,
+            sample_is_depended_on: 0,
+            sample_is_non_sync_sample: !sync,
+            sample_padding_value: 0,
+        }),
+        size: Some(frame.data.len() as u32),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes::Bytes;
+
+    use super::trun_sample;
+    use crate::codec::Codec;
+    use crate::frame::MediaFrame;
+    use crate::timebase::Timebase;
+
+    fn frame(codec: Codec, keyframe: bool) -> MediaFrame {
+        MediaFrame {
+            track_id: 1,
+            codec,
+            timebase: Timebase::MILLISECONDS,
+            dts: 1000,
+            pts: 1066,
+            keyframe,
+            data: Bytes::from_static(b"data"),
+            side_data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn video_sample() {
+        let sample = trun_sample(&frame(Codec::Avc, false), 90_000, 3000);
+        assert_eq!(sample.duration, Some(3000));
+        assert_eq!(sample.composition_time_offset, Some(5940));
+        assert_eq!(sample.size, Some(4));
+
+        let flags = sample.flags.unwrap();
+        assert_eq!(flags.sample_depends_on, 1);
+        assert!(flags.sample_is_non_sync_sample);
+
+        let sample = trun_sample(&frame(Codec::Av1, true), 90_000, 3000);
+        assert_eq!(sample.composition_time_offset, None);
+
+        let flags = sample.flags.unwrap();
+        assert_eq!(flags.sample_depends_on, 2);
+        assert!(!flags.sample_is_non_sync_sample);
+    }
+
+    #[test]
+    fn audio_sample() {
+        let sample = trun_sample(&frame(Codec::Aac, false), 48_000, 1024);
+        assert_eq!(sample.duration, Some(1024));
+        assert_eq!(sample.composition_time_offset, None);
+        assert!(!sample.flags.unwrap().sample_is_non_sync_sample);
+    }
+}
@@ -0,0 +1,50 @@
+//! Side data attached to frames.
+
+use bytes::Bytes;
+
+/// Data that is carried next to the coded data of a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SideData {
+    /// HDR mastering display colour volume, applies from this frame on.
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    /// HDR content light level, applies from this frame on.
+    ContentLightLevel(ContentLightLevel),
+    /// Closed captions of this frame.
+    ///
+    /// The data is a sequence of CEA-708 `cc_data` triplets (`cc_valid`,
+    /// `cc_type` and the two bytes of caption data), as carried by ATSC A/53
+    /// user data.
+    Captions(Bytes),
+}
+
+/// The colour volume of the display the content was mastered on.
+///
+/// The units are the ones of SMPTE ST 2086 as used by the mastering display
+/// colour volume SEI message and the `mdcv` box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MasteringDisplayColourVolume {
+    /// The `(x, y)` chromaticity of the red primary in units of 0.00002.
+    pub red: (u16, u16),
+    /// The `(x, y)` chromaticity of the green primary in units of 0.00002.
+    pub green: (u16, u16),
+    /// The `(x, y)` chromaticity of the blue primary in units of 0.00002.
+    pub blue: (u16, u16),
+    /// The `(x, y)` chromaticity of the white point in units of 0.00002.
+    pub white_point: (u16, u16),
+    /// The maximum luminance in units of 0.0001 cd/m².
+    pub max_luminance: u32,
+    /// The minimum luminance in units of 0.0001 cd/m².
+    pub min_luminance: u32,
+}
+
+/// The light level of the content.
+///
+/// The units are the ones of CTA-861.3 as used by the content light level SEI
+/// message and the `clli` box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ContentLightLevel {
+    /// The maximum light level of any pixel in cd/m².
+    pub max_content_light_level: u16,
+    /// The maximum average light level of any frame in cd/m².
+    pub max_frame_average_light_level: u16,
+}
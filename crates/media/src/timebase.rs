@@ -0,0 +1,94 @@
+//! Rational timebases.
+
+use std::fmt;
+
+/// A rational timebase, the duration of one tick in seconds.
+///
+/// A timestamp `ts` in the timebase `num / den` is `ts * num / den` seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timebase {
+    num: u32,
+    den: u32,
+}
+
+impl Timebase {
+    /// One tick per microsecond.
+    pub const MICROSECONDS: Self = Self::new(1, 1_000_000);
+    /// One tick per millisecond, the timebase of FLV and RTMP.
+    pub const MILLISECONDS: Self = Self::new(1, 1000);
+    /// The 90 kHz clock of MPEG-TS and of RTP video.
+    pub const MPEG: Self = Self::new(1, 90_000);
+
+    /// Creates a new timebase of `num / den` seconds per tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num` or `den` is zero.
+    pub const fn new(num: u32, den: u32) -> Self {
+        assert!(num != 0 && den != 0, "timebase must not be zero");
+        Self { num, den }
+    }
+
+    /// Creates a timebase with `rate` ticks per second, like the clock rate
+    /// of an RTP stream or the timescale of a MP4 track.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    pub const fn from_rate(rate: u32) -> Self {
+        Self::new(1, rate)
+    }
+
+    /// The numerator.
+    pub const fn num(&self) -> u32 {
+        self.num
+    }
+
+    /// The denominator.
+    pub const fn den(&self) -> u32 {
+        self.den
+    }
+
+    /// Converts a timestamp in this timebase to the timebase `to`.
+    ///
+    /// The result is rounded down and saturates at the limits of `i64`.
+    pub fn rescale(&self, value: i64, to: Timebase) -> i64 {
+        if *self == to {
+            return value;
+        }
+
+        let num = value as i128 * self.num as i128 * to.den as i128;
+        let den = self.den as i128 * to.num as i128;
+
+        num.div_euclid(den).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+}
+
+impl fmt::Display for Timebase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::Timebase;
+
+    #[test]
+    fn rescale() {
+        assert_eq!(Timebase::MILLISECONDS.rescale(1000, Timebase::MPEG), 90_000);
+        assert_eq!(Timebase::MPEG.rescale(3003, Timebase::MILLISECONDS), 33);
+        assert_eq!(Timebase::MPEG.rescale(-3003, Timebase::MILLISECONDS), -34);
+        assert_eq!(Timebase::new(1001, 30_000).rescale(30, Timebase::MILLISECONDS), 1001);
+        assert_eq!(Timebase::MILLISECONDS.rescale(42, Timebase::MILLISECONDS), 42);
+        assert_eq!(Timebase::MICROSECONDS.rescale(i64::MAX, Timebase::new(1, u32::MAX)), i64::MAX);
+        assert_eq!(Timebase::from_rate(48_000).to_string(), "1/48000");
+    }
+
+    #[test]
+    #[should_panic = "timebase must not be zero"]
+    fn zero() {
+        Timebase::new(1, 0);
+    }
+}
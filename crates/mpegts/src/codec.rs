@@ -0,0 +1,355 @@
+//! Codecs that can be carried in a transport stream.
+
+use std::io::{self, Read};
+
+use bytes::Bytes;
+use scuffle_aac::AudioSpecificConfig;
+use scuffle_bytes_util::EmulationPreventionIo;
+use scuffle_h264::{AVCDecoderConfigurationRecord, AvccExtendedConfig, Sps};
+use scuffle_h265::{
+    ConstantFrameRate, HEVCDecoderConfigurationRecord, NALUnitType, NaluArray, NumTemporalLayers, ParallelismType,
+    ProfileCompatibilityFlags, SpsNALUnit,
+};
+
+use crate::pes::{STREAM_ID_AUDIO, STREAM_ID_PRIVATE_STREAM_1, STREAM_ID_VIDEO};
+use crate::psi::{DESCRIPTOR_TAG_DVB_EXTENSION, DESCRIPTOR_TAG_REGISTRATION, Descriptor, PmtStream};
+use crate::stream_type::StreamType;
+
+/// The format identifier of the Opus registration descriptor.
+const OPUS_FORMAT_IDENTIFIER: &[u8; 4] = b"Opus";
+
+/// The extension descriptor tag of the Opus audio descriptor.
+const OPUS_DESCRIPTOR_TAG_EXTENSION: u8 = 0x80;
+
+/// A codec of an elementary stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// H.264 video in Annex B format.
+    H264,
+    /// H.265 video in Annex B format.
+    H265,
+    /// AAC audio in ADTS frames.
+    Aac,
+    /// Opus audio.
+    Opus {
+        /// The number of channels.
+        channel_count: u8,
+    },
+}
+
+impl Codec {
+    /// Returns the codec of an elementary stream in the PMT, or `None` if
+    /// the codec is not supported.
+    pub fn from_pmt_stream(stream: &PmtStream) -> Option<Self> {
+        match stream.stream_type {
+            StreamType::H264 => Some(Self::H264),
+            StreamType::H265 => Some(Self::H265),
+            StreamType::AdtsAac => Some(Self::Aac),
+            StreamType::PrivateData
+                if stream
+                    .descriptor(DESCRIPTOR_TAG_REGISTRATION)
+                    .is_some_and(|descriptor| descriptor.data.starts_with(OPUS_FORMAT_IDENTIFIER)) =>
+            {
+                let channel_count = stream
+                    .descriptors
+                    .iter()
+                    .filter(|descriptor| descriptor.tag == DESCRIPTOR_TAG_DVB_EXTENSION)
+                    .find_map(|descriptor| match descriptor.data.as_ref() {
+                        [OPUS_DESCRIPTOR_TAG_EXTENSION, channel_config_code, ..] => Some(*channel_config_code),
+                        _ => None,
+                    })
+                    .unwrap_or(2);
+
+                Some(Self::Opus { channel_count })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the stream type used to signal the codec in the PMT.
+    pub const fn stream_type(&self) -> StreamType {
+        match self {
+            Self::H264 => StreamType::H264,
+            Self::H265 => StreamType::H265,
+            Self::Aac => StreamType::AdtsAac,
+            Self::Opus { .. } => StreamType::PrivateData,
+        }
+    }
+
+    /// Returns the descriptors used to signal the codec in the PMT.
+    pub fn descriptors(&self) -> Vec<Descriptor> {
+        match self {
+            Self::Opus { channel_count } => vec![
+                Descriptor {
+                    tag: DESCRIPTOR_TAG_REGISTRATION,
+                    data: Bytes::from_static(OPUS_FORMAT_IDENTIFIER),
+                },
+                Descriptor {
+                    tag: DESCRIPTOR_TAG_DVB_EXTENSION,
+                    data: Bytes::from(vec![OPUS_DESCRIPTOR_TAG_EXTENSION, *channel_count]),
+                },
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the PES stream id used for the codec.
+    pub const fn stream_id(&self) -> u8 {
+        match self {
+            Self::H264 | Self::H265 => STREAM_ID_VIDEO,
+            Self::Aac => STREAM_ID_AUDIO,
+            Self::Opus { .. } => STREAM_ID_PRIVATE_STREAM_1,
+        }
+    }
+
+    /// Returns true if the codec is a video codec.
+    pub const fn is_video(&self) -> bool {
+        matches!(self, Self::H264 | Self::H265)
+    }
+}
+
+/// The decoder configuration of an elementary stream, extracted from the
+/// stream itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodecConfig {
+    /// H.264 decoder configuration, built from the SPS and PPS.
+    Avc(AVCDecoderConfigurationRecord),
+    /// H.265 decoder configuration, built from the VPS, SPS and PPS.
+    Hevc(HEVCDecoderConfigurationRecord),
+    /// AAC decoder configuration, built from the ADTS header.
+    Aac(AudioSpecificConfig),
+}
+
+/// Splits Annex B formatted data into NAL units, without start codes.
+pub(crate) fn split_annexb(data: &Bytes) -> Vec<Bytes> {
+    let mut nal_units = Vec::new();
+    let mut start = None;
+    let mut zeros = 0;
+
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == 0x01 && zeros >= 2 {
+            if let Some(start) = start {
+                let end = i - zeros;
+                if end > start {
+                    nal_units.push(data.slice(start..end));
+                }
+            }
+
+            start = Some(i + 1);
+        }
+
+        zeros = if byte == 0x00 { zeros + 1 } else { 0 };
+    }
+
+    if let Some(start) = start {
+        // Trailing zero bytes belong to the next (missing) start code
+        let end = data.len() - zeros;
+        if end > start {
+            nal_units.push(data.slice(start..end));
+        }
+    }
+
+    nal_units
+}
+
+/// Returns true if the access unit starts a key frame.
+pub(crate) fn is_keyframe(codec: Codec, nal_units: &[Bytes]) -> bool {
+    match codec {
+        // IDR picture
+        Codec::H264 => nal_units.iter().any(|nal_unit| nal_unit[0] & 0x1F == 5),
+        // IRAP picture
+        Codec::H265 => nal_units
+            .iter()
+            .any(|nal_unit| (16..=23).contains(&((nal_unit[0] >> 1) & 0x3F))),
+        Codec::Aac | Codec::Opus { .. } => true,
+    }
+}
+
+/// Builds the H.264 decoder configuration from the parameter sets in an
+/// access unit.
+///
+/// Returns `None` if the access unit does not contain both an SPS and a PPS.
+pub(crate) fn avc_config(nal_units: &[Bytes]) -> io::Result<Option<AVCDecoderConfigurationRecord>> {
+    let sps: Vec<_> = nal_units.iter().filter(|nal_unit| nal_unit[0] & 0x1F == 7).cloned().collect();
+    let pps: Vec<_> = nal_units.iter().filter(|nal_unit| nal_unit[0] & 0x1F == 8).cloned().collect();
+
+    let Some(first_sps) = sps.first() else {
+        return Ok(None);
+    };
+
+    if pps.is_empty() {
+        return Ok(None);
+    }
+
+    let parsed = Sps::parse_with_emulation_prevention(io::Cursor::new(first_sps))?;
+
+    // ISO/IEC 14496-15:2022(E) - 5.3.2.1.2
+    let extended_config = matches!(parsed.profile_idc, 100 | 110 | 122 | 144).then(|| {
+        let ext = parsed.ext.as_ref();
+        AvccExtendedConfig {
+            chroma_format_idc: ext.map_or(1, |ext| ext.chroma_format_idc),
+            bit_depth_luma_minus8: ext.map_or(0, |ext| ext.bit_depth_luma_minus8),
+            bit_depth_chroma_minus8: ext.map_or(0, |ext| ext.bit_depth_chroma_minus8),
+            sequence_parameter_set_ext: Vec::new(),
+        }
+    });
+
+    Ok(Some(AVCDecoderConfigurationRecord {
+        configuration_version: 1,
+        profile_indication: first_sps[1],
+        profile_compatibility: first_sps[2],
+        level_indication: first_sps[3],
+        length_size_minus_one: 3,
+        sps,
+        pps,
+        extended_config,
+    }))
+}
+
+/// Builds the H.265 decoder configuration from the parameter sets in an
+/// access unit.
+///
+/// Returns `None` if the access unit does not contain a VPS, SPS and PPS.
+pub(crate) fn hevc_config(nal_units: &[Bytes]) -> io::Result<Option<HEVCDecoderConfigurationRecord>> {
+    let nal_unit_type = |nal_unit: &Bytes| (nal_unit[0] >> 1) & 0x3F;
+
+    let mut arrays = Vec::new();
+    for nal_type in [NALUnitType::VpsNut, NALUnitType::SpsNut, NALUnitType::PpsNut] {
+        let nalus: Vec<_> = nal_units
+            .iter()
+            .filter(|nal_unit| nal_unit.len() > 2 && nal_unit_type(nal_unit) == nal_type.0)
+            .cloned()
+            .collect();
+
+        if nalus.is_empty() {
+            return Ok(None);
+        }
+
+        arrays.push(NaluArray {
+            array_completeness: true,
+            nal_unit_type: nal_type,
+            nalus,
+        });
+    }
+
+    let sps = &arrays[1].nalus[0];
+    let parsed = SpsNALUnit::parse(io::Cursor::new(sps))?.rbsp;
+
+    // The general profile_tier_level() directly follows the NAL unit header
+    // and the first byte of the SPS, read it raw to get the constraint flags.
+    // ISO/IEC 23008-2 - 7.3.2.2.1
+    let mut ptl = [0; 12];
+    let mut reader = EmulationPreventionIo::new(io::Cursor::new(sps));
+    reader.read_exact(&mut [0; 3])?;
+    reader.read_exact(&mut ptl)?;
+
+    let min_spatial_segmentation_idc = parsed
+        .vui_parameters
+        .as_ref()
+        .map_or(0, |vui| vui.bitstream_restriction.min_spatial_segmentation_idc);
+
+    Ok(Some(HEVCDecoderConfigurationRecord {
+        general_profile_space: ptl[0] >> 6,
+        general_tier_flag: ptl[0] & 0x20 != 0,
+        general_profile_idc: ptl[0] & 0x1F,
+        general_profile_compatibility_flags: ProfileCompatibilityFlags::from_bits_retain(u32::from_be_bytes(
+            ptl[1..5].try_into().unwrap(),
+        )),
+        general_constraint_indicator_flags: ptl[5..11].iter().fold(0, |flags, &byte| flags << 8 | byte as u64),
+        general_level_idc: ptl[11],
+        min_spatial_segmentation_idc,
+        parallelism_type: ParallelismType::MixedOrUnknown,
+        chroma_format_idc: parsed.chroma_format_idc,
+        bit_depth_luma_minus8: parsed.bit_depth_luma_minus8,
+        bit_depth_chroma_minus8: parsed.bit_depth_chroma_minus8,
+        avg_frame_rate: 0,
+        constant_frame_rate: ConstantFrameRate::Unknown,
+        num_temporal_layers: NumTemporalLayers::from(parsed.sps_max_sub_layers_minus1 + 1),
+        temporal_id_nested: parsed.sps_temporal_id_nesting_flag,
+        length_size_minus_one: 3,
+        arrays,
+    }))
+}
+
+/// Returns the duration of an Opus packet in units of 90 kHz, or `None` if
+/// the packet is malformed.
+///
+/// RFC 6716 - 3.1
+pub(crate) fn opus_packet_duration(packet: &[u8]) -> Option<u64> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+
+    // Frame size in samples at 48 kHz
+    let frame_size: u64 = match config {
+        0..=11 => [480, 960, 1920, 2880][config as usize % 4],
+        12..=15 => [480, 960][config as usize % 2],
+        _ => [120, 240, 480, 960][config as usize % 4],
+    };
+
+    let frame_count: u64 = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1)? & 0x3F) as u64,
+    };
+
+    Some(frame_size * frame_count * 90_000 / 48_000)
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_annexb() {
+        let data = Bytes::from_static(&[
+            0x00, 0x00, 0x00, 0x01, 0x09, 0xF0, // AUD with a 4 byte start code
+            0x00, 0x00, 0x01, 0x67, 0x42, 0x1F, // SPS with a 3 byte start code
+            0x00, 0x00, 0x01, 0x65, 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, // IDR slice with trailing zeros
+        ]);
+
+        let nal_units = split_annexb(&data);
+        assert_eq!(
+            nal_units,
+            vec![
+                Bytes::from_static(&[0x09, 0xF0]),
+                Bytes::from_static(&[0x67, 0x42, 0x1F]),
+                Bytes::from_static(&[0x65, 0x00, 0x00, 0x03, 0x01]),
+            ]
+        );
+
+        assert!(is_keyframe(Codec::H264, &nal_units));
+        assert!(!is_keyframe(Codec::H264, &nal_units[..2]));
+        assert!(split_annexb(&Bytes::from_static(&[0xAB, 0xCD])).is_empty());
+    }
+
+    #[test]
+    fn test_opus_packet_duration() {
+        // CELT 20ms, one frame
+        assert_eq!(opus_packet_duration(&[0xFC]), Some(1800));
+        // SILK 60ms, two frames
+        assert_eq!(opus_packet_duration(&[0x19]), Some(10800));
+        // Hybrid 10ms, code 3 with 3 frames
+        assert_eq!(opus_packet_duration(&[0x63, 0x03]), Some(2700));
+        assert_eq!(opus_packet_duration(&[0x63]), None);
+        assert_eq!(opus_packet_duration(&[]), None);
+    }
+
+    #[test]
+    fn test_opus_descriptors() {
+        let codec = Codec::Opus { channel_count: 6 };
+        let stream = PmtStream {
+            stream_type: codec.stream_type(),
+            elementary_pid: 0x101,
+            descriptors: codec.descriptors(),
+        };
+
+        assert_eq!(Codec::from_pmt_stream(&stream), Some(codec));
+
+        let stream = PmtStream {
+            descriptors: Vec::new(),
+            ..stream
+        };
+        assert_eq!(Codec::from_pmt_stream(&stream), None);
+    }
+}
@@ -0,0 +1,43 @@
+/// CRC32 lookup table for the polynomial `0x04C11DB7`.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Calculates the CRC32 used by PSI sections.
+///
+/// ISO/IEC 13818-1:2022(E) - Annex A
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0xFFFF_FFFF, |crc, &byte| {
+        (crc << 8) ^ CRC32_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        // PAT generated by ffmpeg
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        assert_eq!(crc32(&pat), 0x2AB1_04B2);
+        assert_eq!(crc32(&[pat.as_slice(), &[0x2A, 0xB1, 0x04, 0xB2]].concat()), 0);
+    }
+}
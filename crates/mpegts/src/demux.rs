@@ -0,0 +1,519 @@
+//! Transport stream demuxing.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use bytes::{Buf, Bytes, BytesMut};
+use scuffle_aac::adts::AdtsFrame;
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::codec::{Codec, CodecConfig, avc_config, hevc_config, is_keyframe, opus_packet_duration, split_annexb};
+use crate::error::MpegTsError;
+use crate::packet::{PACKET_SIZE, PAT_PID, Pcr, SYNC_BYTE, TsPacket};
+use crate::pes::PesPacket;
+use crate::psi::{Pat, Pmt, section_size};
+use crate::stream_type::StreamType;
+
+/// An elementary stream announced in a PMT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementaryStream {
+    /// The PID of the stream.
+    pub pid: u16,
+    /// The program number of the program the stream belongs to.
+    pub program_number: u16,
+    /// The stream type signalled in the PMT.
+    pub stream_type: StreamType,
+    /// The codec of the stream, `None` if the codec is not supported.
+    ///
+    /// Frames are only emitted for streams with a supported codec.
+    pub codec: Option<Codec>,
+    /// The most recent decoder configuration found in the stream.
+    pub config: Option<CodecConfig>,
+}
+
+/// A demuxed frame (access unit).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The PID of the stream the frame belongs to.
+    pub pid: u16,
+    /// The codec of the frame.
+    pub codec: Codec,
+    /// The presentation timestamp in units of 90 kHz.
+    pub pts: u64,
+    /// The decoding timestamp in units of 90 kHz.
+    pub dts: u64,
+    /// If the frame can be decoded without any previous frames.
+    pub keyframe: bool,
+    /// If data was lost or the stream signalled a discontinuity before this
+    /// frame.
+    pub discontinuity: bool,
+    /// The frame data.
+    ///
+    /// - H.264 / H.265: the access unit in Annex B format.
+    /// - AAC: the raw data block(s) without the ADTS header.
+    /// - Opus: a single Opus packet without the control header.
+    pub data: Bytes,
+}
+
+/// The demuxing state of a single elementary stream.
+#[derive(Debug)]
+struct StreamState {
+    stream: ElementaryStream,
+    pes: Option<BytesMut>,
+    continuity_counter: Option<u8>,
+    discontinuity: bool,
+    next_timestamp: Option<u64>,
+}
+
+impl StreamState {
+    fn new(stream: ElementaryStream) -> Self {
+        Self {
+            stream,
+            pes: None,
+            continuity_counter: None,
+            discontinuity: false,
+            next_timestamp: None,
+        }
+    }
+
+    /// Returns the PES packet if all of its data has been received.
+    fn take_complete_pes(&mut self) -> Option<BytesMut> {
+        let pes = self.pes.as_ref()?;
+        let length = u16::from_be_bytes([*pes.get(4)?, *pes.get(5)?]) as usize;
+        if length != 0 && pes.len() >= 6 + length {
+            self.pes.take()
+        } else {
+            None
+        }
+    }
+}
+
+/// A streaming MPEG-TS demuxer.
+///
+/// Data is pushed into the demuxer in arbitrarily sized chunks and demuxed
+/// frames are taken out with [`TsDemuxer::pop_frame`].
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # fn test(chunks: Vec<Vec<u8>>) -> Result<(), scuffle_mpegts::error::MpegTsError> {
+/// use scuffle_mpegts::TsDemuxer;
+///
+/// let mut demuxer = TsDemuxer::new();
+///
+/// for chunk in chunks {
+///     demuxer.push(&chunk)?;
+///
+///     while let Some(frame) = demuxer.pop_frame() {
+///         println!("pid: {}, pts: {}, size: {}", frame.pid, frame.pts, frame.data.len());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct TsDemuxer {
+    buffer: BytesMut,
+    pat: Option<Pat>,
+    /// The PMT of every program in the PAT, keyed by the PMT PID.
+    pmts: HashMap<u16, Option<Pmt>>,
+    sections: HashMap<u16, BytesMut>,
+    streams: BTreeMap<u16, StreamState>,
+    pcr: Option<Pcr>,
+    frames: VecDeque<Frame>,
+}
+
+impl TsDemuxer {
+    /// Creates a new demuxer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the most recent program association table.
+    pub fn pat(&self) -> Option<&Pat> {
+        self.pat.as_ref()
+    }
+
+    /// Returns the most recent program map table of the given program.
+    pub fn pmt(&self, program_number: u16) -> Option<&Pmt> {
+        self.pmts.values().flatten().find(|pmt| pmt.program_number == program_number)
+    }
+
+    /// Returns the elementary streams, ordered by PID.
+    pub fn streams(&self) -> impl Iterator<Item = &ElementaryStream> {
+        self.streams.values().map(|state| &state.stream)
+    }
+
+    /// Returns the elementary stream with the given PID.
+    pub fn stream(&self, pid: u16) -> Option<&ElementaryStream> {
+        self.streams.get(&pid).map(|state| &state.stream)
+    }
+
+    /// Returns the most recent program clock reference.
+    pub fn pcr(&self) -> Option<Pcr> {
+        self.pcr
+    }
+
+    /// Takes the next demuxed frame out of the demuxer.
+    pub fn pop_frame(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    /// Pushes data into the demuxer.
+    ///
+    /// The data does not need to be aligned to packet boundaries, incomplete
+    /// packets are buffered until the rest of the packet is pushed. Data
+    /// before the next sync byte is skipped.
+    ///
+    /// If an error occurs, the offending packet is dropped and the remaining
+    /// data stays buffered, pushing an empty slice continues demuxing.
+    pub fn push(&mut self, data: &[u8]) -> Result<(), MpegTsError> {
+        self.buffer.extend_from_slice(data);
+
+        loop {
+            // Resynchronize on the sync byte
+            match self.buffer.iter().position(|&byte| byte == SYNC_BYTE) {
+                Some(0) => {}
+                Some(position) => {
+                    self.buffer.advance(position);
+                }
+                None => {
+                    self.buffer.clear();
+                    return Ok(());
+                }
+            }
+
+            if self.buffer.len() < PACKET_SIZE {
+                return Ok(());
+            }
+
+            let packet = self.buffer.split_to(PACKET_SIZE).freeze();
+            self.push_packet(TsPacket::parse(packet)?)?;
+        }
+    }
+
+    /// Pushes a single parsed packet into the demuxer.
+    pub fn push_packet(&mut self, packet: TsPacket) -> Result<(), MpegTsError> {
+        if packet.transport_error_indicator {
+            if let Some(state) = self.streams.get_mut(&packet.pid) {
+                state.pes = None;
+                state.discontinuity = true;
+            }
+
+            return Ok(());
+        }
+
+        if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|field| field.pcr)
+            && self.pmts.values().flatten().any(|pmt| pmt.pcr_pid == packet.pid)
+        {
+            self.pcr = Some(pcr);
+        }
+
+        if packet.pid == PAT_PID || self.pmts.contains_key(&packet.pid) {
+            if let Some(payload) = packet.payload {
+                self.push_psi(packet.pid, packet.payload_unit_start_indicator, payload)?;
+            }
+
+            return Ok(());
+        }
+
+        let Some(state) = self.streams.get_mut(&packet.pid) else {
+            return Ok(());
+        };
+
+        if packet
+            .adaptation_field
+            .as_ref()
+            .is_some_and(|field| field.discontinuity_indicator)
+        {
+            state.discontinuity = true;
+            state.continuity_counter = None;
+        }
+
+        let Some(payload) = packet.payload else {
+            return Ok(());
+        };
+
+        // ISO/IEC 13818-1:2022(E) - 2.4.3.3
+        if let Some(continuity_counter) = state.continuity_counter {
+            if continuity_counter == packet.continuity_counter {
+                // Duplicate packet
+                return Ok(());
+            }
+
+            if (continuity_counter + 1) & 0x0F != packet.continuity_counter {
+                state.pes = None;
+                state.discontinuity = true;
+            }
+        }
+        state.continuity_counter = Some(packet.continuity_counter);
+
+        if packet.payload_unit_start_indicator {
+            if let Some(pes) = state.pes.take() {
+                Self::finish_pes(state, pes.freeze(), &mut self.frames)?;
+            }
+
+            state.pes = Some(BytesMut::from(payload));
+        } else if let Some(pes) = &mut state.pes {
+            pes.extend_from_slice(&payload);
+        }
+
+        if let Some(pes) = state.take_complete_pes() {
+            Self::finish_pes(state, pes.freeze(), &mut self.frames)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes all buffered PES packets.
+    ///
+    /// Should be called at the end of the stream since PES packets with an
+    /// unbounded length are only complete once the next one starts.
+    pub fn flush(&mut self) -> Result<(), MpegTsError> {
+        for state in self.streams.values_mut() {
+            if let Some(pes) = state.pes.take() {
+                Self::finish_pes(state, pes.freeze(), &mut self.frames)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_psi(&mut self, pid: u16, payload_unit_start_indicator: bool, mut payload: Bytes) -> Result<(), MpegTsError> {
+        let buffer = self.sections.entry(pid).or_default();
+
+        if payload_unit_start_indicator {
+            let pointer_field = payload.try_get_u8().map_err(io::Error::from)? as usize;
+            if pointer_field > payload.len() {
+                buffer.clear();
+                return Err(MpegTsError::InvalidSectionLength(pointer_field));
+            }
+
+            // The bytes before the pointer complete the previous section
+            if !buffer.is_empty() {
+                buffer.extend_from_slice(&payload[..pointer_field]);
+            }
+
+            let mut sections = take_sections(buffer);
+            buffer.clear();
+            buffer.extend_from_slice(&payload[pointer_field..]);
+            sections.extend(take_sections(buffer));
+
+            for section in sections {
+                self.handle_section(pid, section)?;
+            }
+        } else if !buffer.is_empty() {
+            buffer.extend_from_slice(&payload);
+
+            for section in take_sections(buffer) {
+                self.handle_section(pid, section)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_section(&mut self, pid: u16, section: Bytes) -> Result<(), MpegTsError> {
+        if pid == PAT_PID {
+            let pat = Pat::parse(section)?;
+            if !pat.current_next_indicator || self.pat.as_ref() == Some(&pat) {
+                return Ok(());
+            }
+
+            // Forget programs which are no longer announced
+            self.pmts.retain(|pid, _| {
+                pat.programs
+                    .iter()
+                    .any(|program| program.program_number != 0 && program.pid == *pid)
+            });
+            for program in pat.programs.iter().filter(|program| program.program_number != 0) {
+                self.pmts.entry(program.pid).or_default();
+            }
+
+            self.streams.retain(|_, state| {
+                pat.programs
+                    .iter()
+                    .any(|program| program.program_number == state.stream.program_number)
+            });
+            self.sections.retain(|pid, _| *pid == PAT_PID || self.pmts.contains_key(pid));
+            self.pat = Some(pat);
+
+            return Ok(());
+        }
+
+        let pmt = Pmt::parse(section)?;
+        if !pmt.current_next_indicator || self.pmts.get(&pid).is_some_and(|current| current.as_ref() == Some(&pmt)) {
+            return Ok(());
+        }
+
+        self.streams.retain(|pid, state| {
+            state.stream.program_number != pmt.program_number
+                || pmt
+                    .stream(*pid)
+                    .is_some_and(|stream| stream.stream_type == state.stream.stream_type)
+        });
+
+        for stream in &pmt.streams {
+            self.streams.entry(stream.elementary_pid).or_insert_with(|| {
+                StreamState::new(ElementaryStream {
+                    pid: stream.elementary_pid,
+                    program_number: pmt.program_number,
+                    stream_type: stream.stream_type,
+                    codec: Codec::from_pmt_stream(stream),
+                    config: None,
+                })
+            });
+        }
+
+        self.pmts.insert(pid, Some(pmt));
+
+        Ok(())
+    }
+
+    fn finish_pes(state: &mut StreamState, data: Bytes, frames: &mut VecDeque<Frame>) -> Result<(), MpegTsError> {
+        let Some(codec) = state.stream.codec else {
+            return Ok(());
+        };
+
+        let pes = PesPacket::parse(data)?;
+
+        let Some(pts) = pes.header.pts.or(state.next_timestamp) else {
+            // Without any timestamp the frame cannot be placed on the timeline
+            return Ok(());
+        };
+        let dts = pes.header.dts.unwrap_or(pts);
+
+        let mut frame = Frame {
+            pid: state.stream.pid,
+            codec,
+            pts,
+            dts,
+            keyframe: true,
+            discontinuity: std::mem::take(&mut state.discontinuity),
+            data: Bytes::new(),
+        };
+
+        match codec {
+            Codec::H264 | Codec::H265 => {
+                let nal_units = split_annexb(&pes.payload);
+
+                let config = match codec {
+                    Codec::H264 => avc_config(&nal_units)?.map(CodecConfig::Avc),
+                    _ => hevc_config(&nal_units)?.map(CodecConfig::Hevc),
+                };
+                if config.is_some() {
+                    state.stream.config = config;
+                }
+
+                frame.keyframe = is_keyframe(codec, &nal_units);
+                frame.data = pes.payload;
+                state.next_timestamp = Some(dts);
+                frames.push_back(frame);
+            }
+            Codec::Aac => {
+                let mut reader = io::Cursor::new(pes.payload);
+                let mut timestamp = pts;
+
+                while reader.has_remaining() {
+                    let adts = AdtsFrame::parse(&mut reader)?;
+                    let config = adts.header.audio_specific_config()?;
+                    let duration = adts.header.sampling_frequency().map_or(0, |sampling_frequency| {
+                        adts.header.raw_data_blocks() as u64 * config.frame_length() as u64 * 90_000
+                            / sampling_frequency as u64
+                    });
+
+                    state.stream.config = Some(CodecConfig::Aac(config));
+                    frames.push_back(Frame {
+                        pts: timestamp,
+                        dts: timestamp,
+                        data: adts.payload,
+                        ..frame.clone()
+                    });
+
+                    frame.discontinuity = false;
+                    timestamp += duration;
+                }
+
+                state.next_timestamp = Some(timestamp);
+            }
+            Codec::Opus { .. } => {
+                let mut reader = io::Cursor::new(pes.payload);
+                let mut timestamp = pts;
+
+                while reader.has_remaining() {
+                    let packet = read_opus_access_unit(&mut reader)?;
+                    let duration = opus_packet_duration(&packet).unwrap_or(0);
+
+                    frames.push_back(Frame {
+                        pts: timestamp,
+                        dts: timestamp,
+                        data: packet,
+                        ..frame.clone()
+                    });
+
+                    frame.discontinuity = false;
+                    timestamp += duration;
+                }
+
+                state.next_timestamp = Some(timestamp);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Takes all complete sections out of the buffer.
+fn take_sections(buffer: &mut BytesMut) -> Vec<Bytes> {
+    let mut sections = Vec::new();
+
+    loop {
+        // Stuffing bytes after the last section
+        if buffer.first() == Some(&0xFF) {
+            buffer.clear();
+        }
+
+        match section_size(buffer) {
+            Some(size) if size <= buffer.len() => sections.push(buffer.split_to(size).freeze()),
+            _ => return sections,
+        }
+    }
+}
+
+/// Reads an Opus access unit with its control header.
+///
+/// Transport of Opus in MPEG-2 Transport Stream - `opus_access_unit()`
+fn read_opus_access_unit(reader: &mut io::Cursor<Bytes>) -> io::Result<Bytes> {
+    let header = reader.read_u16::<BigEndian>()?;
+    if header >> 5 != 0x3FF {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid opus control header prefix",
+        ));
+    }
+
+    let mut size = 0;
+    loop {
+        let byte = reader.read_u8()?;
+        size += byte as usize;
+        if byte != 0xFF {
+            break;
+        }
+    }
+
+    // start_trim and end_trim
+    for flag in [0x10, 0x08] {
+        if header & flag != 0 {
+            reader.read_u16::<BigEndian>()?;
+        }
+    }
+
+    // control_extension
+    if header & 0x04 != 0 {
+        let length = reader.read_u8()? as usize;
+        reader.extract_bytes(length)?;
+    }
+
+    reader.extract_bytes(size)
+}
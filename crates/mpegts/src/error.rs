@@ -0,0 +1,49 @@
+//! Error types.
+
+/// Error type for MPEG-TS processing.
+#[derive(Debug, thiserror::Error)]
+pub enum MpegTsError {
+    /// IO error.
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    /// The packet does not start with the sync byte.
+    #[error("invalid sync byte: 0x{0:x}")]
+    InvalidSyncByte(u8),
+    /// The packet is not exactly 188 bytes long.
+    #[error("invalid packet size: {0}")]
+    InvalidPacketSize(usize),
+    /// The adaptation field length exceeds the packet.
+    #[error("invalid adaptation field length: {0}")]
+    InvalidAdaptationFieldLength(u8),
+    /// The payload does not fit into a single packet.
+    #[error("payload too large: {0} bytes")]
+    PayloadTooLarge(usize),
+    /// The PES packet does not start with the start code prefix.
+    #[error("invalid PES start code prefix: 0x{0:x}")]
+    InvalidPesStartCode(u32),
+    /// The section has an unexpected table id.
+    #[error("unexpected table id: expected 0x{expected:x}, got 0x{actual:x}")]
+    UnexpectedTableId {
+        /// The expected table id.
+        expected: u8,
+        /// The actual table id.
+        actual: u8,
+    },
+    /// The section length is invalid.
+    #[error("invalid section length: {0}")]
+    InvalidSectionLength(usize),
+    /// The CRC32 of the section does not match.
+    #[error("crc mismatch: expected 0x{expected:08x}, got 0x{actual:08x}")]
+    CrcMismatch {
+        /// The CRC32 stored in the section.
+        expected: u32,
+        /// The CRC32 calculated over the section.
+        actual: u32,
+    },
+    /// The stream requires a decoder configuration.
+    #[error("missing codec config")]
+    MissingCodecConfig,
+    /// The PID is not part of the stream.
+    #[error("unknown pid: 0x{0:x}")]
+    UnknownPid(u16),
+}
@@ -0,0 +1,316 @@
+//! A pure Rust implementation of the MPEG transport stream format, allowing
+//! for demuxing and muxing of transport streams as carried by SRT, UDP or HLS.
+//!
+//! The demuxer reassembles H.264, H.265, AAC (ADTS) and Opus elementary
+//! streams and extracts their decoder configurations using
+//! [`scuffle-h264`][scuffle_h264], [`scuffle-h265`][scuffle_h265] and
+//! [`scuffle-aac`][scuffle_aac]. The muxer writes
+//! the same elementary streams with continuity counters, periodic PAT/PMT
+//! and program clock references.
+#![cfg_attr(feature = "docs", doc = "\n\nSee the [changelog][changelog] for a full release history.")]
+#![cfg_attr(feature = "docs", doc = "## Feature flags")]
+#![cfg_attr(feature = "docs", doc = document_features::document_features!())]
+//! ## Example
+//!
+//! ```rust
+//! # fn test() -> Result<(), scuffle_mpegts::error::MpegTsError> {
+//! use bytes::Bytes;
+//! use scuffle_mpegts::{Codec, Frame, TsDemuxer, TsMuxer};
+//!
+//! let mut muxer = TsMuxer::new();
+//! let pid = muxer.add_stream(Codec::Opus { channel_count: 2 }, None)?;
+//!
+//! let frame = Frame {
+//!     pid,
+//!     codec: Codec::Opus { channel_count: 2 },
+//!     pts: 90_000,
+//!     dts: 90_000,
+//!     keyframe: true,
+//!     discontinuity: false,
+//!     data: Bytes::from_static(&[0xFC, 0xFF, 0xFE]),
+//! };
+//!
+//! let mut ts = Vec::new();
+//! muxer.write_frame(&mut ts, &frame)?;
+//!
+//! let mut demuxer = TsDemuxer::new();
+//! demuxer.push(&ts)?;
+//!
+//! assert_eq!(demuxer.pop_frame(), Some(frame));
+//! # Ok(())
+//! # }
+//! # test().unwrap();
+//! ```
+//!
+//! ## Specifications
+//!
+//! | Name | Version | Link | Comments |
+//! | --- | --- | --- | --- |
+//! | ISO/IEC 13818-1 | `2022` | <https://www.iso.org/standard/83239.html> | Transport stream, PES and PSI |
+//! | ISO/IEC 14496-3 | `2019` | <https://www.iso.org/standard/76383.html> | ADTS |
+//! | ETSI EN 300 468 | `1.17.1` | <https://www.etsi.org/deliver/etsi_en/300400_300499/300468/01.17.01_60/en_300468v011701p.pdf> | Extension descriptor |
+//! | Transport of Opus in MPEG-2 Transport Stream | `0.1.3` | <https://opus-codec.org/docs/ETSI_TS_opus-v0.1.3-draft.pdf> | |
+//!
+//! ## License
+//!
+//! This project is licensed under the MIT or Apache-2.0 license.
+//! You can choose between one of them if you use this work.
+//!
+//! `SPDX-License-Identifier: MIT OR Apache-2.0`
+#![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
+#![deny(unreachable_pub)]
+#![deny(clippy::mod_module_files)]
+
+mod codec;
+mod crc;
+mod demux;
+mod mux;
+mod stream_type;
+
+pub mod error;
+pub mod packet;
+pub mod pes;
+pub mod psi;
+
+pub use codec::{Codec, CodecConfig};
+pub use demux::{ElementaryStream, Frame, TsDemuxer};
+pub use mux::{DEFAULT_FIRST_STREAM_PID, DEFAULT_PMT_PID, DEFAULT_PSI_INTERVAL, TsMuxer};
+pub use stream_type::StreamType;
+
+/// Changelogs generated by [scuffle_changelog]
+#[cfg(feature = "docs")]
+#[scuffle_changelog::changelog]
+pub mod changelog {}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::io;
+
+    use bytes::Bytes;
+    use scuffle_aac::{AudioObjectType, AudioSpecificConfig};
+    use scuffle_h264::AVCDecoderConfigurationRecord;
+    use scuffle_h265::HEVCDecoderConfigurationRecord;
+
+    use crate::packet::{PACKET_SIZE, PAT_PID, TsPacket};
+    use crate::{Codec, CodecConfig, DEFAULT_PMT_PID, Frame, StreamType, TsDemuxer, TsMuxer};
+
+    fn avc_config() -> AVCDecoderConfigurationRecord {
+        let data = Bytes::from(b"\x01d\0\x1f\xff\xe1\0\x19\x67\x64\x00\x1F\xAC\xD9\x41\xE0\x6D\xF9\xE6\xA0\x20\x20\x28\x00\x00\x03\x00\x08\x00\x00\x03\x01\xE0\x01\0\x06h\xeb\xe3\xcb\"\xc0\xfd\xf8\xf8\0".to_vec());
+        AVCDecoderConfigurationRecord::parse(&mut io::Cursor::new(data)).unwrap()
+    }
+
+    fn hevc_config() -> HEVCDecoderConfigurationRecord {
+        let data = Bytes::from(b"\x01\x01@\0\0\0\x90\0\0\0\0\0\x99\xf0\0\xfc\xfd\xf8\xf8\0\0\x0f\x03 \0\x01\0\x18@\x01\x0c\x01\xff\xff\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\x95@\x90!\0\x01\0=B\x01\x01\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\xa0\x01@ \x05\xa1e\x95R\x90\x84d_\xf8\xc0Z\x80\x80\x80\x82\0\0\x03\0\x02\0\0\x03\x01 \xc0\x0b\xbc\xa2\0\x02bX\0\x011-\x08\"\0\x01\0\x07D\x01\xc0\x93|\x0c\xc9".to_vec());
+        HEVCDecoderConfigurationRecord::demux(&mut io::Cursor::new(data)).unwrap()
+    }
+
+    fn frame(pid: u16, codec: Codec, pts: u64, dts: u64, keyframe: bool, data: Vec<u8>) -> Frame {
+        Frame {
+            pid,
+            codec,
+            pts,
+            dts,
+            keyframe,
+            discontinuity: false,
+            data: Bytes::from(data),
+        }
+    }
+
+    fn demux(data: &[u8], chunk_size: usize) -> (TsDemuxer, Vec<Frame>) {
+        let mut demuxer = TsDemuxer::new();
+        let mut frames = Vec::new();
+
+        for chunk in data.chunks(chunk_size) {
+            demuxer.push(chunk).unwrap();
+            frames.extend(std::iter::from_fn(|| demuxer.pop_frame()));
+        }
+
+        demuxer.flush().unwrap();
+        frames.extend(std::iter::from_fn(|| demuxer.pop_frame()));
+
+        (demuxer, frames)
+    }
+
+    #[test]
+    fn test_mux_demux_avc_aac() {
+        let avc_config = avc_config();
+        let aac_config = AudioSpecificConfig::new(AudioObjectType::AacLowComplexity, 48000, 2);
+
+        let mut muxer = TsMuxer::new();
+        let video = muxer
+            .add_stream(Codec::H264, Some(CodecConfig::Avc(avc_config.clone())))
+            .unwrap();
+        let audio = muxer
+            .add_stream(Codec::Aac, Some(CodecConfig::Aac(aac_config.clone())))
+            .unwrap();
+        assert_eq!(muxer.pcr_pid(), Some(video));
+
+        let mut frames = Vec::new();
+        for i in 0..10u64 {
+            // IDR slice for the first frame, non-IDR slices afterwards, large enough to span packets
+            let nal_header = if i == 0 { 0x65 } else { 0x41 };
+            let mut data = vec![0, 0, 0, 1, 0x09, 0xF0, 0, 0, 0, 1, nal_header];
+            data.extend(std::iter::repeat_n(i as u8 + 1, 500 * (i as usize + 1)));
+
+            frames.push(frame(video, Codec::H264, 3003 * i + 6006, 3003 * i + 3003, i == 0, data));
+            frames.push(frame(audio, Codec::Aac, 1920 * i, 1920 * i, true, vec![0x21, i as u8, 0x80]));
+        }
+
+        let mut ts = Vec::new();
+        for frame in &frames {
+            muxer.write_frame(&mut ts, frame).unwrap();
+        }
+        assert_eq!(ts.len() % PACKET_SIZE, 0);
+
+        let (demuxer, demuxed) = demux(&ts, 1000);
+
+        let pat = demuxer.pat().unwrap();
+        assert_eq!(pat.programs.len(), 1);
+        assert_eq!(pat.programs[0].pid, DEFAULT_PMT_PID);
+
+        let pmt = demuxer.pmt(1).unwrap();
+        assert_eq!(pmt.pcr_pid, video);
+        assert_eq!(pmt.streams.len(), 2);
+        assert_eq!(pmt.stream(audio).unwrap().stream_type, StreamType::AdtsAac);
+        assert_eq!(demuxer.pcr().unwrap().base, 3003 * 9 + 3003);
+
+        // The parameter sets are inserted in front of the first key frame
+        let mut expected = frames.clone();
+        let mut first = vec![0, 0, 0, 1];
+        first.extend_from_slice(&avc_config.sps[0]);
+        first.extend_from_slice(&[0, 0, 0, 1]);
+        first.extend_from_slice(&avc_config.pps[0]);
+        first.extend_from_slice(&frames[0].data);
+        expected[0].data = Bytes::from(first);
+
+        let sort = |frames: &mut Vec<Frame>| frames.sort_by_key(|frame| (frame.pid, frame.dts));
+        let mut demuxed = demuxed;
+        sort(&mut demuxed);
+        sort(&mut expected);
+        assert_eq!(demuxed, expected);
+
+        assert_eq!(demuxer.stream(video).unwrap().config, Some(CodecConfig::Avc(avc_config)));
+        assert_eq!(demuxer.stream(audio).unwrap().config, Some(CodecConfig::Aac(aac_config)));
+    }
+
+    #[test]
+    fn test_mux_demux_hevc_opus() {
+        let hevc_config = hevc_config();
+
+        let mut muxer = TsMuxer::new();
+        let audio = muxer.add_stream(Codec::Opus { channel_count: 2 }, None).unwrap();
+        let video = muxer.add_stream(Codec::H265, None).unwrap();
+        assert_eq!(muxer.pcr_pid(), Some(video));
+
+        // Parameter sets followed by an IDR_W_RADL slice
+        let mut keyframe = Vec::new();
+        for nal_unit in hevc_config.arrays.iter().flat_map(|array| &array.nalus) {
+            keyframe.extend_from_slice(&[0, 0, 0, 1]);
+            keyframe.extend_from_slice(nal_unit);
+        }
+        keyframe.extend_from_slice(&[0, 0, 1, 0x26, 0x01, 0xAF, 0x00, 0x11]);
+
+        let frames = vec![
+            frame(video, Codec::H265, 0, 0, true, keyframe),
+            // 20 ms CELT packets, the second one larger than 255 bytes
+            frame(audio, Codec::Opus { channel_count: 2 }, 0, 0, true, vec![0xFC; 10]),
+            frame(audio, Codec::Opus { channel_count: 2 }, 1800, 1800, true, vec![0xFC; 300]),
+            // TRAIL_R slice
+            frame(video, Codec::H265, 3000, 3000, false, vec![0, 0, 1, 0x02, 0x01, 0xD0, 0x00]),
+        ];
+
+        let mut ts = Vec::new();
+        for frame in &frames {
+            muxer.write_frame(&mut ts, frame).unwrap();
+        }
+
+        let (demuxer, demuxed) = demux(&ts, PACKET_SIZE * 3 + 7);
+
+        assert_eq!(demuxed.len(), frames.len());
+        for frame in &frames {
+            assert!(demuxed.contains(frame), "missing frame: {frame:?}");
+        }
+
+        // The demuxer always marks the arrays as complete
+        let mut expected = hevc_config;
+        expected.arrays.iter_mut().for_each(|array| array.array_completeness = true);
+        assert_eq!(demuxer.stream(video).unwrap().config, Some(CodecConfig::Hevc(expected)));
+        assert_eq!(demuxer.stream(audio).unwrap().codec, Some(Codec::Opus { channel_count: 2 }));
+    }
+
+    #[test]
+    fn test_demux_discontinuity() {
+        let mut muxer = TsMuxer::new();
+        let audio = muxer.add_stream(Codec::Opus { channel_count: 1 }, None).unwrap();
+
+        let mut ts = Vec::new();
+        for i in 0..3 {
+            let frame = frame(
+                audio,
+                Codec::Opus { channel_count: 1 },
+                i * 1800,
+                i * 1800,
+                true,
+                vec![0xFC; 400],
+            );
+            muxer.write_frame(&mut ts, &frame).unwrap();
+        }
+
+        // Drop the second packet of the first frame, after the PAT and PMT
+        let mut packets: Vec<_> = ts.chunks(PACKET_SIZE).map(<[u8]>::to_vec).collect();
+        packets.remove(3);
+
+        // Garbage between packets is skipped
+        let mut data = vec![0x00, 0x12];
+        for packet in packets {
+            data.extend(packet);
+            data.push(0xAB);
+        }
+
+        let (_, demuxed) = demux(&data, 100);
+        assert_eq!(demuxed.len(), 2);
+        assert_eq!(demuxed[0].pts, 1800);
+        assert!(demuxed[0].discontinuity);
+        assert_eq!(demuxed[1].pts, 3600);
+        assert!(!demuxed[1].discontinuity);
+    }
+
+    #[test]
+    fn test_mux_errors() {
+        let mut muxer = TsMuxer::new();
+        assert!(matches!(
+            muxer.add_stream(Codec::Aac, None),
+            Err(crate::error::MpegTsError::MissingCodecConfig)
+        ));
+
+        let frame = frame(0x200, Codec::H264, 0, 0, true, Vec::new());
+        assert!(matches!(
+            muxer.write_frame(&mut Vec::new(), &frame),
+            Err(crate::error::MpegTsError::UnknownPid(0x200))
+        ));
+    }
+
+    #[test]
+    fn test_mux_psi_repetition() {
+        let mut muxer = TsMuxer::new().with_psi_interval(90_000);
+        let audio = muxer.add_stream(Codec::Opus { channel_count: 2 }, None).unwrap();
+
+        let mut ts = Vec::new();
+        for i in 0..100 {
+            let frame = frame(audio, Codec::Opus { channel_count: 2 }, i * 1800, i * 1800, true, vec![0xFC]);
+            muxer.write_frame(&mut ts, &frame).unwrap();
+        }
+
+        // 100 frames of 20 ms span 2 seconds, so the PAT is written 2 times
+        let pat_count = ts
+            .chunks(PACKET_SIZE)
+            .map(|packet| TsPacket::parse(Bytes::copy_from_slice(packet)).unwrap())
+            .filter(|packet| packet.pid == PAT_PID)
+            .count();
+        assert_eq!(pat_count, 2);
+    }
+}
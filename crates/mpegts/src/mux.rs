@@ -0,0 +1,357 @@
+//! Transport stream muxing.
+
+use std::io;
+
+use bytes::Bytes;
+use scuffle_aac::adts::AdtsFrame;
+
+use crate::codec::{Codec, CodecConfig, split_annexb};
+use crate::demux::Frame;
+use crate::error::MpegTsError;
+use crate::packet::{AdaptationField, MAX_PAYLOAD_SIZE, PAT_PID, Pcr, TsPacket};
+use crate::pes::{PesHeader, PesPacket};
+use crate::psi::{Pat, PatProgram, Pmt, PmtStream};
+
+/// The PID of the program map table written by the muxer.
+pub const DEFAULT_PMT_PID: u16 = 0x1000;
+
+/// The PID of the first elementary stream written by the muxer.
+pub const DEFAULT_FIRST_STREAM_PID: u16 = 0x100;
+
+/// The default interval between PAT/PMT repetitions in units of 90 kHz.
+pub const DEFAULT_PSI_INTERVAL: u64 = 9_000;
+
+/// An elementary stream added to the muxer.
+#[derive(Debug)]
+struct MuxStream {
+    pid: u16,
+    codec: Codec,
+    config: Option<CodecConfig>,
+    continuity_counter: u8,
+}
+
+/// An MPEG-TS muxer for a single program.
+///
+/// The muxer writes the PAT and PMT before the first frame, before every
+/// key frame and at least every [`psi_interval`](TsMuxer::with_psi_interval).
+/// The program clock reference is carried on the first video stream, or the
+/// first stream if there is no video stream.
+///
+/// ## Example
+///
+/// ```rust
+/// # fn test() -> Result<(), scuffle_mpegts::error::MpegTsError> {
+/// use bytes::Bytes;
+/// use scuffle_mpegts::{Codec, Frame, TsMuxer};
+///
+/// let mut muxer = TsMuxer::new();
+/// let pid = muxer.add_stream(Codec::Opus { channel_count: 2 }, None)?;
+///
+/// let mut output = Vec::new();
+/// muxer.write_frame(
+///     &mut output,
+///     &Frame {
+///         pid,
+///         codec: Codec::Opus { channel_count: 2 },
+///         pts: 0,
+///         dts: 0,
+///         keyframe: true,
+///         discontinuity: false,
+///         data: Bytes::from_static(&[0xFC, 0xFF, 0xFE]),
+///     },
+/// )?;
+///
+/// assert_eq!(output.len() % 188, 0);
+/// # Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TsMuxer {
+    transport_stream_id: u16,
+    program_number: u16,
+    pmt_pid: u16,
+    psi_interval: u64,
+    psi_version: u8,
+    pat_continuity_counter: u8,
+    pmt_continuity_counter: u8,
+    last_psi: Option<u64>,
+    streams: Vec<MuxStream>,
+}
+
+impl Default for TsMuxer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TsMuxer {
+    /// Creates a new muxer.
+    pub fn new() -> Self {
+        Self {
+            transport_stream_id: 1,
+            program_number: 1,
+            pmt_pid: DEFAULT_PMT_PID,
+            psi_interval: DEFAULT_PSI_INTERVAL,
+            psi_version: 0,
+            pat_continuity_counter: 0,
+            pmt_continuity_counter: 0,
+            last_psi: None,
+            streams: Vec::new(),
+        }
+    }
+
+    /// Sets the interval between PAT/PMT repetitions in units of 90 kHz.
+    pub fn with_psi_interval(mut self, psi_interval: u64) -> Self {
+        self.psi_interval = psi_interval;
+        self
+    }
+
+    /// Adds an elementary stream and returns its PID.
+    ///
+    /// The decoder configuration is required for AAC since frames are
+    /// written as raw data blocks and need an ADTS header. For H.264 and
+    /// H.265 it is optional, if present the parameter sets are inserted in
+    /// front of key frames which do not carry them.
+    pub fn add_stream(&mut self, codec: Codec, config: Option<CodecConfig>) -> Result<u16, MpegTsError> {
+        if codec == Codec::Aac && !matches!(config, Some(CodecConfig::Aac(_))) {
+            return Err(MpegTsError::MissingCodecConfig);
+        }
+
+        let pid = DEFAULT_FIRST_STREAM_PID + self.streams.len() as u16;
+        self.streams.push(MuxStream {
+            pid,
+            codec,
+            config,
+            continuity_counter: 0,
+        });
+
+        // Announce the new stream with the next frame
+        if self.last_psi.is_some() {
+            self.psi_version = (self.psi_version + 1) & 0x1F;
+        }
+        self.last_psi = None;
+
+        Ok(pid)
+    }
+
+    /// Updates the decoder configuration of a stream.
+    pub fn set_config(&mut self, pid: u16, config: CodecConfig) -> Result<(), MpegTsError> {
+        let stream = self.stream_mut(pid)?;
+        stream.config = Some(config);
+        Ok(())
+    }
+
+    /// Returns the PID which carries the program clock reference.
+    pub fn pcr_pid(&self) -> Option<u16> {
+        self.streams
+            .iter()
+            .find(|stream| stream.codec.is_video())
+            .or(self.streams.first())
+            .map(|stream| stream.pid)
+    }
+
+    /// Returns the program association table written by the muxer.
+    pub fn pat(&self) -> Pat {
+        Pat {
+            transport_stream_id: self.transport_stream_id,
+            version_number: self.psi_version,
+            current_next_indicator: true,
+            programs: vec![PatProgram {
+                program_number: self.program_number,
+                pid: self.pmt_pid,
+            }],
+        }
+    }
+
+    /// Returns the program map table written by the muxer.
+    pub fn pmt(&self) -> Pmt {
+        Pmt {
+            program_number: self.program_number,
+            version_number: self.psi_version,
+            current_next_indicator: true,
+            pcr_pid: self.pcr_pid().unwrap_or(crate::packet::NULL_PID),
+            descriptors: Vec::new(),
+            streams: self
+                .streams
+                .iter()
+                .map(|stream| PmtStream {
+                    stream_type: stream.codec.stream_type(),
+                    elementary_pid: stream.pid,
+                    descriptors: stream.codec.descriptors(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the PAT and PMT.
+    pub fn write_psi<W: io::Write>(&mut self, writer: &mut W) -> Result<(), MpegTsError> {
+        let mut pat = Vec::new();
+        self.pat().build(&mut pat)?;
+        write_section(writer, PAT_PID, &mut self.pat_continuity_counter, &pat)?;
+
+        let mut pmt = Vec::new();
+        self.pmt().build(&mut pmt)?;
+        write_section(writer, self.pmt_pid, &mut self.pmt_continuity_counter, &pmt)?;
+
+        Ok(())
+    }
+
+    /// Writes a frame.
+    ///
+    /// The frame data must be in the same format as the frames emitted by
+    /// the [`TsDemuxer`](crate::TsDemuxer), see [`Frame::data`].
+    pub fn write_frame<W: io::Write>(&mut self, writer: &mut W, frame: &Frame) -> Result<(), MpegTsError> {
+        let pcr_pid = self.pcr_pid();
+        let stream = self.stream_mut(frame.pid)?;
+        let codec = stream.codec;
+
+        let payload = match (&codec, &stream.config) {
+            (Codec::H264 | Codec::H265, config) => annexb_payload(codec, config.as_ref(), frame),
+            (Codec::Aac, Some(CodecConfig::Aac(config))) => {
+                let mut payload = Vec::with_capacity(frame.data.len() + 9);
+                AdtsFrame::new(config, frame.data.clone())?.build(&mut payload)?;
+                Bytes::from(payload)
+            }
+            (Codec::Aac, _) => return Err(MpegTsError::MissingCodecConfig),
+            (Codec::Opus { .. }, _) => opus_payload(&frame.data),
+        };
+
+        let pes = PesPacket {
+            header: PesHeader {
+                stream_id: codec.stream_id(),
+                data_alignment_indicator: true,
+                pts: Some(frame.pts & 0x1_FFFF_FFFF),
+                dts: (frame.dts != frame.pts).then_some(frame.dts & 0x1_FFFF_FFFF),
+            },
+            payload,
+        };
+
+        let mut data = Vec::with_capacity(pes.header_size() + pes.payload.len());
+        pes.build(&mut data)?;
+        let data = Bytes::from(data);
+
+        let write_psi = self.last_psi.is_none_or(|last_psi| {
+            (frame.keyframe && codec.is_video()) || frame.dts.saturating_sub(last_psi) >= self.psi_interval
+        });
+        if write_psi {
+            self.write_psi(writer)?;
+            self.last_psi = Some(frame.dts);
+        }
+
+        let stream = self.stream_mut(frame.pid)?;
+
+        let mut adaptation_field = AdaptationField {
+            discontinuity_indicator: frame.discontinuity,
+            random_access_indicator: frame.keyframe,
+            pcr: (pcr_pid == Some(frame.pid)).then(|| Pcr::from_90khz(frame.dts)),
+            ..Default::default()
+        };
+
+        let mut offset = 0;
+        while offset < data.len() {
+            let field = (!adaptation_field.is_empty()).then_some(&adaptation_field);
+            let size = TsPacket::max_payload_size(field).min(data.len() - offset);
+
+            TsPacket {
+                payload_unit_start_indicator: offset == 0,
+                pid: stream.pid,
+                continuity_counter: stream.continuity_counter,
+                adaptation_field: field.cloned(),
+                payload: Some(data.slice(offset..offset + size)),
+                ..Default::default()
+            }
+            .build(writer)?;
+
+            stream.continuity_counter = (stream.continuity_counter + 1) & 0x0F;
+            adaptation_field = AdaptationField::default();
+            offset += size;
+        }
+
+        Ok(())
+    }
+
+    fn stream_mut(&mut self, pid: u16) -> Result<&mut MuxStream, MpegTsError> {
+        self.streams
+            .iter_mut()
+            .find(|stream| stream.pid == pid)
+            .ok_or(MpegTsError::UnknownPid(pid))
+    }
+}
+
+/// Writes a PSI section, splitting it over as many packets as needed.
+fn write_section<W: io::Write>(
+    writer: &mut W,
+    pid: u16,
+    continuity_counter: &mut u8,
+    section: &[u8],
+) -> Result<(), MpegTsError> {
+    // pointer_field followed by the section
+    let mut data = Vec::with_capacity(1 + section.len());
+    data.push(0);
+    data.extend_from_slice(section);
+
+    for (i, chunk) in data.chunks(MAX_PAYLOAD_SIZE).enumerate() {
+        // PSI packets are padded with stuffing bytes in the payload
+        let mut payload = chunk.to_vec();
+        payload.resize(MAX_PAYLOAD_SIZE, 0xFF);
+
+        TsPacket {
+            payload_unit_start_indicator: i == 0,
+            pid,
+            continuity_counter: *continuity_counter,
+            payload: Some(Bytes::from(payload)),
+            ..Default::default()
+        }
+        .build(writer)?;
+
+        *continuity_counter = (*continuity_counter + 1) & 0x0F;
+    }
+
+    Ok(())
+}
+
+/// Returns the PES payload of a video frame, inserting the parameter sets
+/// in front of key frames which do not carry them.
+fn annexb_payload(codec: Codec, config: Option<&CodecConfig>, frame: &Frame) -> Bytes {
+    if !frame.keyframe {
+        return frame.data.clone();
+    }
+
+    let parameter_sets: Vec<&Bytes> = match config {
+        Some(CodecConfig::Avc(config)) => config.sps.iter().chain(&config.pps).collect(),
+        Some(CodecConfig::Hevc(config)) => config.arrays.iter().flat_map(|array| &array.nalus).collect(),
+        _ => return frame.data.clone(),
+    };
+
+    let has_parameter_sets = split_annexb(&frame.data).iter().any(|nal_unit| match codec {
+        Codec::H264 => nal_unit[0] & 0x1F == 7,
+        _ => (nal_unit[0] >> 1) & 0x3F == 33,
+    });
+    if has_parameter_sets {
+        return frame.data.clone();
+    }
+
+    let mut payload = Vec::with_capacity(frame.data.len() + parameter_sets.iter().map(|nalu| nalu.len() + 4).sum::<usize>());
+    for nal_unit in parameter_sets {
+        payload.extend_from_slice(&[0, 0, 0, 1]);
+        payload.extend_from_slice(nal_unit);
+    }
+    payload.extend_from_slice(&frame.data);
+
+    Bytes::from(payload)
+}
+
+/// Wraps an Opus packet into an access unit with a control header.
+///
+/// Transport of Opus in MPEG-2 Transport Stream - `opus_access_unit()`
+fn opus_payload(packet: &[u8]) -> Bytes {
+    let mut payload = Vec::with_capacity(packet.len() + 3 + packet.len() / 255);
+    // opus_control_header_prefix without trim or extension flags
+    payload.extend_from_slice(&[0x7F, 0xE0]);
+    payload.extend(std::iter::repeat_n(0xFF, packet.len() / 255));
+    payload.push((packet.len() % 255) as u8);
+    payload.extend_from_slice(packet);
+
+    Bytes::from(payload)
+}
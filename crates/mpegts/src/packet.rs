@@ -0,0 +1,438 @@
+//! Transport stream packets.
+
+use std::io;
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, Bytes};
+use scuffle_bytes_util::{BitReader, BitWriter, BytesCursorExt};
+
+use crate::error::MpegTsError;
+
+/// The size of a transport stream packet in bytes.
+pub const PACKET_SIZE: usize = 188;
+
+/// The sync byte every transport stream packet starts with.
+pub const SYNC_BYTE: u8 = 0x47;
+
+/// The PID of the program association table.
+pub const PAT_PID: u16 = 0x0000;
+
+/// The PID of null packets.
+pub const NULL_PID: u16 = 0x1FFF;
+
+/// The size of the packet header in bytes.
+const HEADER_SIZE: usize = 4;
+
+/// The maximum payload size of a packet without an adaptation field.
+pub const MAX_PAYLOAD_SIZE: usize = PACKET_SIZE - HEADER_SIZE;
+
+/// A program clock reference.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pcr {
+    /// The base of the clock reference in units of 90 kHz (33 bits).
+    pub base: u64,
+    /// The extension of the clock reference in units of 27 MHz (9 bits, in range \[0, 299\]).
+    pub extension: u16,
+}
+
+impl Pcr {
+    /// Creates a new clock reference from a timestamp in units of 90 kHz.
+    pub const fn from_90khz(timestamp: u64) -> Self {
+        Self {
+            base: timestamp & 0x1_FFFF_FFFF,
+            extension: 0,
+        }
+    }
+
+    /// Creates a new clock reference from a timestamp in units of 27 MHz.
+    pub const fn from_27mhz(timestamp: u64) -> Self {
+        Self {
+            base: (timestamp / 300) & 0x1_FFFF_FFFF,
+            extension: (timestamp % 300) as u16,
+        }
+    }
+
+    /// Returns the clock reference in units of 27 MHz.
+    pub const fn as_27mhz(&self) -> u64 {
+        self.base * 300 + self.extension as u64
+    }
+
+    fn parse<R: io::Read>(reader: &mut BitReader<R>) -> io::Result<Self> {
+        let base = reader.read_bits(33)?;
+        reader.read_bits(6)?; // reserved
+        let extension = reader.read_bits(9)? as u16;
+
+        Ok(Self { base, extension })
+    }
+
+    fn build<W: io::Write>(&self, writer: &mut BitWriter<W>) -> io::Result<()> {
+        writer.write_bits(self.base, 33)?;
+        writer.write_bits(0b111111, 6)?; // reserved
+        writer.write_bits(self.extension as u64, 9)?;
+
+        Ok(())
+    }
+}
+
+/// The adaptation field of a transport stream packet.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.4 (Table 2-6)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AdaptationField {
+    /// Indicates that there is a discontinuity in the continuity counter or
+    /// the system time base.
+    pub discontinuity_indicator: bool,
+    /// Indicates that the packet contains information to aid random access,
+    /// for example the start of a key frame.
+    pub random_access_indicator: bool,
+    /// Indicates that the payload has a higher priority than other packets
+    /// of the same PID.
+    pub elementary_stream_priority_indicator: bool,
+    /// The program clock reference.
+    pub pcr: Option<Pcr>,
+    /// The original program clock reference.
+    pub opcr: Option<Pcr>,
+    /// The number of packets remaining until a splicing point.
+    pub splice_countdown: Option<i8>,
+    /// Private data.
+    pub transport_private_data: Option<Bytes>,
+    /// The raw adaptation field extension, excluding its length byte.
+    pub extension: Option<Bytes>,
+}
+
+impl AdaptationField {
+    /// Returns true if none of the flags or optional fields are set.
+    ///
+    /// An empty adaptation field can be encoded with a length of zero.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Returns the minimum encoded size of the adaptation field, excluding
+    /// the length byte and any stuffing.
+    pub fn size(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+
+        1 + self.pcr.map_or(0, |_| 6)
+            + self.opcr.map_or(0, |_| 6)
+            + self.splice_countdown.map_or(0, |_| 1)
+            + self.transport_private_data.as_ref().map_or(0, |data| 1 + data.len())
+            + self.extension.as_ref().map_or(0, |data| 1 + data.len())
+    }
+
+    /// Parses the adaptation field from its data, excluding the length byte.
+    fn parse(data: Bytes) -> Result<Self, MpegTsError> {
+        if data.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let mut reader = io::Cursor::new(data);
+        let flags = reader.read_u8()?;
+
+        let mut field = Self {
+            discontinuity_indicator: flags & 0x80 != 0,
+            random_access_indicator: flags & 0x40 != 0,
+            elementary_stream_priority_indicator: flags & 0x20 != 0,
+            ..Default::default()
+        };
+
+        if flags & 0x10 != 0 {
+            field.pcr = Some(Pcr::parse(&mut BitReader::new(reader.extract_bytes(6)?.reader()))?);
+        }
+
+        if flags & 0x08 != 0 {
+            field.opcr = Some(Pcr::parse(&mut BitReader::new(reader.extract_bytes(6)?.reader()))?);
+        }
+
+        if flags & 0x04 != 0 {
+            field.splice_countdown = Some(reader.read_i8()?);
+        }
+
+        if flags & 0x02 != 0 {
+            let length = reader.read_u8()? as usize;
+            field.transport_private_data = Some(reader.extract_bytes(length)?);
+        }
+
+        if flags & 0x01 != 0 {
+            let length = reader.read_u8()? as usize;
+            field.extension = Some(reader.extract_bytes(length)?);
+        }
+
+        // The rest is stuffing
+        Ok(field)
+    }
+
+    /// Builds the adaptation field including the length byte, padding it with
+    /// stuffing bytes to the given length.
+    fn build<W: io::Write>(&self, writer: &mut W, length: u8) -> Result<(), MpegTsError> {
+        let size = self.size();
+        if size > length as usize {
+            return Err(MpegTsError::InvalidAdaptationFieldLength(length));
+        }
+
+        writer.write_u8(length)?;
+        if length == 0 {
+            return Ok(());
+        }
+
+        let flags = (self.discontinuity_indicator as u8) << 7
+            | (self.random_access_indicator as u8) << 6
+            | (self.elementary_stream_priority_indicator as u8) << 5
+            | (self.pcr.is_some() as u8) << 4
+            | (self.opcr.is_some() as u8) << 3
+            | (self.splice_countdown.is_some() as u8) << 2
+            | (self.transport_private_data.is_some() as u8) << 1
+            | self.extension.is_some() as u8;
+        writer.write_u8(flags)?;
+
+        let mut bit_writer = BitWriter::new(&mut *writer);
+        if let Some(pcr) = &self.pcr {
+            pcr.build(&mut bit_writer)?;
+        }
+        if let Some(opcr) = &self.opcr {
+            opcr.build(&mut bit_writer)?;
+        }
+        bit_writer.finish()?;
+
+        if let Some(splice_countdown) = self.splice_countdown {
+            writer.write_i8(splice_countdown)?;
+        }
+
+        for data in [&self.transport_private_data, &self.extension].into_iter().flatten() {
+            writer.write_u8(data.len() as u8)?;
+            writer.write_all(data)?;
+        }
+
+        let stuffing = length as usize - size.max(1);
+        writer.write_all(&[0xFF; PACKET_SIZE][..stuffing])?;
+
+        Ok(())
+    }
+}
+
+/// A transport stream packet.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.2 (Table 2-2)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TsPacket {
+    /// Indicates that at least one uncorrectable bit error exists in the packet.
+    pub transport_error_indicator: bool,
+    /// Indicates that the payload starts a PES packet or contains the start
+    /// of a PSI section (preceded by a pointer field).
+    pub payload_unit_start_indicator: bool,
+    /// Indicates that the packet has a higher priority than other packets
+    /// of the same PID.
+    pub transport_priority: bool,
+    /// The packet identifier (13 bits).
+    pub pid: u16,
+    /// The scrambling mode of the payload (2 bits).
+    pub transport_scrambling_control: u8,
+    /// The continuity counter (4 bits), incremented for every packet with a
+    /// payload on the same PID.
+    pub continuity_counter: u8,
+    /// The adaptation field.
+    pub adaptation_field: Option<AdaptationField>,
+    /// The payload.
+    pub payload: Option<Bytes>,
+}
+
+impl TsPacket {
+    /// Parses a transport stream packet.
+    ///
+    /// The data must be exactly [`PACKET_SIZE`] bytes long.
+    pub fn parse(data: Bytes) -> Result<Self, MpegTsError> {
+        if data.len() != PACKET_SIZE {
+            return Err(MpegTsError::InvalidPacketSize(data.len()));
+        }
+
+        let mut reader = io::Cursor::new(data);
+
+        let sync_byte = reader.read_u8()?;
+        if sync_byte != SYNC_BYTE {
+            return Err(MpegTsError::InvalidSyncByte(sync_byte));
+        }
+
+        let mut bit_reader = BitReader::new(&mut reader);
+        let transport_error_indicator = bit_reader.read_bit()?;
+        let payload_unit_start_indicator = bit_reader.read_bit()?;
+        let transport_priority = bit_reader.read_bit()?;
+        let pid = bit_reader.read_bits(13)? as u16;
+        let transport_scrambling_control = bit_reader.read_bits(2)? as u8;
+        let adaptation_field_control = bit_reader.read_bits(2)? as u8;
+        let continuity_counter = bit_reader.read_bits(4)? as u8;
+
+        let adaptation_field = if adaptation_field_control & 0b10 != 0 {
+            let length = reader.read_u8()?;
+            // An adaptation field followed by a payload must leave room for at
+            // least one byte of payload.
+            let max_length = if adaptation_field_control & 0b01 != 0 { 182 } else { 183 };
+            if length > max_length {
+                return Err(MpegTsError::InvalidAdaptationFieldLength(length));
+            }
+
+            Some(AdaptationField::parse(reader.extract_bytes(length as usize)?)?)
+        } else {
+            None
+        };
+
+        let payload = if adaptation_field_control & 0b01 != 0 {
+            Some(reader.extract_remaining())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            transport_error_indicator,
+            payload_unit_start_indicator,
+            transport_priority,
+            pid,
+            transport_scrambling_control,
+            continuity_counter,
+            adaptation_field,
+            payload,
+        })
+    }
+
+    /// Returns the maximum payload size that fits into a packet with the given
+    /// adaptation field.
+    pub fn max_payload_size(adaptation_field: Option<&AdaptationField>) -> usize {
+        match adaptation_field {
+            Some(field) => MAX_PAYLOAD_SIZE - 1 - field.size(),
+            None => MAX_PAYLOAD_SIZE,
+        }
+    }
+
+    /// Builds the packet.
+    ///
+    /// If the payload does not fill the packet, the remaining space is filled
+    /// with stuffing bytes in the adaptation field.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> Result<(), MpegTsError> {
+        let payload_size = self.payload.as_ref().map_or(0, |payload| payload.len());
+        if payload_size > Self::max_payload_size(self.adaptation_field.as_ref()) {
+            return Err(MpegTsError::PayloadTooLarge(payload_size));
+        }
+
+        let has_adaptation_field = self.adaptation_field.is_some() || payload_size < MAX_PAYLOAD_SIZE;
+        let adaptation_field_control = (has_adaptation_field as u8) << 1 | self.payload.is_some() as u8;
+
+        writer.write_u8(SYNC_BYTE)?;
+
+        let mut bit_writer = BitWriter::new(&mut *writer);
+        bit_writer.write_bit(self.transport_error_indicator)?;
+        bit_writer.write_bit(self.payload_unit_start_indicator)?;
+        bit_writer.write_bit(self.transport_priority)?;
+        bit_writer.write_bits(self.pid as u64, 13)?;
+        bit_writer.write_bits(self.transport_scrambling_control as u64, 2)?;
+        bit_writer.write_bits(adaptation_field_control as u64, 2)?;
+        bit_writer.write_bits(self.continuity_counter as u64, 4)?;
+        bit_writer.finish()?;
+
+        if has_adaptation_field {
+            let length = (MAX_PAYLOAD_SIZE - 1 - payload_size) as u8;
+            self.adaptation_field
+                .as_ref()
+                .unwrap_or(&AdaptationField::default())
+                .build(writer, length)?;
+        }
+
+        if let Some(payload) = &self.payload {
+            writer.write_all(payload)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_round_trip() {
+        let packet = TsPacket {
+            payload_unit_start_indicator: true,
+            pid: 0x100,
+            continuity_counter: 7,
+            adaptation_field: Some(AdaptationField {
+                random_access_indicator: true,
+                pcr: Some(Pcr::from_27mhz(123_456_789)),
+                ..Default::default()
+            }),
+            payload: Some(Bytes::from_static(&[1, 2, 3, 4, 5])),
+            ..Default::default()
+        };
+
+        let mut data = Vec::new();
+        packet.build(&mut data).unwrap();
+        assert_eq!(data.len(), PACKET_SIZE);
+
+        let parsed = TsPacket::parse(Bytes::from(data)).unwrap();
+        assert_eq!(parsed, packet);
+        assert_eq!(parsed.adaptation_field.unwrap().pcr.unwrap().as_27mhz(), 123_456_789);
+    }
+
+    #[test]
+    fn test_packet_stuffing() {
+        // 183 bytes of payload require an adaptation field with a length of zero
+        let packet = TsPacket {
+            pid: 0x101,
+            payload: Some(Bytes::from(vec![0xAB; 183])),
+            ..Default::default()
+        };
+
+        let mut data = Vec::new();
+        packet.build(&mut data).unwrap();
+        assert_eq!(data.len(), PACKET_SIZE);
+        assert_eq!(data[3] & 0x30, 0x30);
+        assert_eq!(data[4], 0);
+
+        let parsed = TsPacket::parse(Bytes::from(data)).unwrap();
+        assert_eq!(parsed.adaptation_field, Some(AdaptationField::default()));
+        assert_eq!(parsed.payload, packet.payload);
+
+        // Full packets have no adaptation field
+        let packet = TsPacket {
+            pid: 0x101,
+            payload: Some(Bytes::from(vec![0xAB; 184])),
+            ..Default::default()
+        };
+
+        let mut data = Vec::new();
+        packet.build(&mut data).unwrap();
+        assert_eq!(TsPacket::parse(Bytes::from(data)).unwrap(), packet);
+
+        let packet = TsPacket {
+            pid: 0x101,
+            payload: Some(Bytes::from(vec![0xAB; 185])),
+            ..Default::default()
+        };
+        assert!(matches!(
+            packet.build(&mut Vec::new()),
+            Err(MpegTsError::PayloadTooLarge(185))
+        ));
+    }
+
+    #[test]
+    fn test_packet_invalid() {
+        assert!(matches!(
+            TsPacket::parse(Bytes::from_static(&[0x47; 10])),
+            Err(MpegTsError::InvalidPacketSize(10))
+        ));
+        assert!(matches!(
+            TsPacket::parse(Bytes::from(vec![0u8; PACKET_SIZE])),
+            Err(MpegTsError::InvalidSyncByte(0))
+        ));
+
+        let mut data = vec![0xFF; PACKET_SIZE];
+        data[..5].copy_from_slice(&[SYNC_BYTE, 0x01, 0x00, 0x30, 183]);
+        assert!(matches!(
+            TsPacket::parse(Bytes::from(data)),
+            Err(MpegTsError::InvalidAdaptationFieldLength(183))
+        ));
+    }
+}
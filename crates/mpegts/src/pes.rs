@@ -0,0 +1,255 @@
+//! Packetized elementary stream (PES) packets.
+
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::error::MpegTsError;
+
+/// The start code prefix every PES packet starts with.
+const START_CODE_PREFIX: u32 = 0x000001;
+
+/// The stream id of private stream 1, used for Opus.
+pub const STREAM_ID_PRIVATE_STREAM_1: u8 = 0xBD;
+
+/// The first stream id for audio streams.
+pub const STREAM_ID_AUDIO: u8 = 0xC0;
+
+/// The first stream id for video streams.
+pub const STREAM_ID_VIDEO: u8 = 0xE0;
+
+/// Returns true if PES packets with this stream id carry the optional PES
+/// header.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.7 (Table 2-21)
+const fn has_optional_header(stream_id: u8) -> bool {
+    !matches!(
+        stream_id,
+        // program_stream_map, padding_stream, private_stream_2, ECM, EMM,
+        // program_stream_directory, DSMCC_stream, H.222.1 type E
+        0xBC | 0xBE | 0xBF | 0xF0 | 0xF1 | 0xFF | 0xF2 | 0xF8
+    )
+}
+
+/// The header of a PES packet.
+///
+/// Only the fields relevant for muxing and demuxing are exposed, other
+/// optional fields are skipped when parsing.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.6 (Table 2-21)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PesHeader {
+    /// The stream id.
+    pub stream_id: u8,
+    /// Indicates that the payload starts with a video start code or an audio
+    /// sync word.
+    pub data_alignment_indicator: bool,
+    /// The presentation timestamp in units of 90 kHz (33 bits).
+    pub pts: Option<u64>,
+    /// The decoding timestamp in units of 90 kHz (33 bits).
+    ///
+    /// Only present if it differs from the presentation timestamp.
+    pub dts: Option<u64>,
+}
+
+/// A PES packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PesPacket {
+    /// The header of the packet.
+    pub header: PesHeader,
+    /// The payload of the packet.
+    pub payload: Bytes,
+}
+
+impl PesPacket {
+    /// Parses a PES packet.
+    ///
+    /// The data must contain the whole packet, if the `PES_packet_length` is
+    /// zero the payload extends until the end of the data.
+    pub fn parse(data: Bytes) -> Result<Self, MpegTsError> {
+        let mut reader = io::Cursor::new(data);
+
+        let start_code_prefix = reader.read_u24::<BigEndian>()?;
+        if start_code_prefix != START_CODE_PREFIX {
+            return Err(MpegTsError::InvalidPesStartCode(start_code_prefix));
+        }
+
+        let stream_id = reader.read_u8()?;
+        let packet_length = reader.read_u16::<BigEndian>()? as usize;
+
+        let mut reader = if packet_length == 0 {
+            reader
+        } else {
+            io::Cursor::new(reader.extract_bytes(packet_length)?)
+        };
+
+        let mut header = PesHeader {
+            stream_id,
+            ..Default::default()
+        };
+
+        if has_optional_header(stream_id) {
+            let flags = reader.read_u16::<BigEndian>()?;
+            header.data_alignment_indicator = flags & 0x0400 != 0;
+
+            let header_data = reader.read_u8()? as usize;
+            let mut header_reader = io::Cursor::new(reader.extract_bytes(header_data)?);
+
+            let pts_dts_flags = (flags >> 6) & 0b11;
+            if pts_dts_flags & 0b10 != 0 {
+                header.pts = Some(read_timestamp(&mut header_reader)?);
+            }
+
+            if pts_dts_flags == 0b11 {
+                header.dts = Some(read_timestamp(&mut header_reader)?);
+            }
+        }
+
+        Ok(Self {
+            header,
+            payload: reader.extract_remaining(),
+        })
+    }
+
+    /// Returns the size of the PES header in bytes.
+    pub fn header_size(&self) -> usize {
+        if !has_optional_header(self.header.stream_id) {
+            return 6;
+        }
+
+        // The DTS is only written if the PTS is present
+        9 + self.header.pts.map_or(0, |_| 5 + self.header.dts.map_or(0, |_| 5))
+    }
+
+    /// Builds the PES packet.
+    ///
+    /// If the packet is too large for the 16 bit `PES_packet_length`, the
+    /// length is set to zero which is only allowed for video streams.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> Result<(), MpegTsError> {
+        let packet_length = self.header_size() - 6 + self.payload.len();
+
+        writer.write_u24::<BigEndian>(START_CODE_PREFIX)?;
+        writer.write_u8(self.header.stream_id)?;
+        writer.write_u16::<BigEndian>(u16::try_from(packet_length).unwrap_or(0))?;
+
+        if has_optional_header(self.header.stream_id) {
+            let pts_dts_flags = match (self.header.pts, self.header.dts) {
+                (Some(_), Some(_)) => 0b11,
+                (Some(_), None) => 0b10,
+                (None, _) => 0b00,
+            };
+
+            // '10' marker bits followed by the flags
+            writer.write_u8(0x80 | (self.header.data_alignment_indicator as u8) << 2)?;
+            writer.write_u8(pts_dts_flags << 6)?;
+            writer.write_u8((self.header_size() - 9) as u8)?;
+
+            if let Some(pts) = self.header.pts {
+                write_timestamp(writer, pts_dts_flags, pts)?;
+            }
+
+            if let Some(dts) = self.header.dts.filter(|_| self.header.pts.is_some()) {
+                write_timestamp(writer, 0b0001, dts)?;
+            }
+        }
+
+        writer.write_all(&self.payload)?;
+
+        Ok(())
+    }
+}
+
+/// Reads a 33-bit timestamp.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.7
+fn read_timestamp<R: io::Read>(reader: &mut R) -> io::Result<u64> {
+    let high = reader.read_u8()? as u64;
+    let middle = reader.read_u16::<BigEndian>()? as u64;
+    let low = reader.read_u16::<BigEndian>()? as u64;
+
+    Ok(((high >> 1) & 0x07) << 30 | (middle >> 1) << 15 | low >> 1)
+}
+
+/// Writes a 33-bit timestamp with the given 4 bit prefix.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.3.7
+fn write_timestamp<W: io::Write>(writer: &mut W, prefix: u8, timestamp: u64) -> io::Result<()> {
+    writer.write_u8(prefix << 4 | (((timestamp >> 30) & 0x07) as u8) << 1 | 1)?;
+    writer.write_u16::<BigEndian>((((timestamp >> 15) & 0x7FFF) as u16) << 1 | 1)?;
+    writer.write_u16::<BigEndian>(((timestamp & 0x7FFF) as u16) << 1 | 1)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        for timestamp in [0, 1, 90_000, 0x1_FFFF_FFFF] {
+            let mut data = Vec::new();
+            write_timestamp(&mut data, 0b0010, timestamp).unwrap();
+            assert_eq!(data[0] >> 4, 0b0010);
+            assert_eq!(read_timestamp(&mut io::Cursor::new(data)).unwrap(), timestamp);
+        }
+    }
+
+    #[test]
+    fn test_pes_round_trip() {
+        let packet = PesPacket {
+            header: PesHeader {
+                stream_id: STREAM_ID_VIDEO,
+                data_alignment_indicator: true,
+                pts: Some(183_000),
+                dts: Some(180_000),
+            },
+            payload: Bytes::from_static(&[0, 0, 0, 1, 0x09, 0xF0]),
+        };
+
+        let mut data = Vec::new();
+        packet.build(&mut data).unwrap();
+        assert_eq!(data.len(), packet.header_size() + packet.payload.len());
+        assert_eq!(u16::from_be_bytes([data[4], data[5]]) as usize, data.len() - 6);
+
+        assert_eq!(PesPacket::parse(Bytes::from(data)).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_pes_unbounded() {
+        let packet = PesPacket {
+            header: PesHeader {
+                stream_id: STREAM_ID_VIDEO,
+                pts: Some(90_000),
+                ..Default::default()
+            },
+            payload: Bytes::from(vec![0xAB; 70_000]),
+        };
+
+        let mut data = Vec::new();
+        packet.build(&mut data).unwrap();
+        assert_eq!(&data[4..6], &[0, 0]);
+
+        assert_eq!(PesPacket::parse(Bytes::from(data)).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_pes_without_optional_header() {
+        let data = Bytes::from_static(&[0x00, 0x00, 0x01, 0xBE, 0x00, 0x03, 0xFF, 0xFF, 0xFF]);
+        let packet = PesPacket::parse(data.clone()).unwrap();
+        assert_eq!(packet.header.stream_id, 0xBE);
+        assert_eq!(packet.payload.len(), 3);
+
+        let mut built = Vec::new();
+        packet.build(&mut built).unwrap();
+        assert_eq!(built, data);
+
+        assert!(matches!(
+            PesPacket::parse(Bytes::from_static(&[0x00, 0x00, 0x02, 0xE0, 0x00, 0x00])),
+            Err(MpegTsError::InvalidPesStartCode(2))
+        ));
+    }
+}
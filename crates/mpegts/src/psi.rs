@@ -0,0 +1,438 @@
+//! Program specific information (PSI) tables.
+
+use std::io::{self, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, Bytes};
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::crc::crc32;
+use crate::error::MpegTsError;
+use crate::stream_type::StreamType;
+
+/// The table id of the program association section.
+pub const TABLE_ID_PAT: u8 = 0x00;
+
+/// The table id of the program map section.
+pub const TABLE_ID_PMT: u8 = 0x02;
+
+/// The maximum value of `section_length` for PSI tables.
+const MAX_SECTION_LENGTH: usize = 1021;
+
+/// The tag of the registration descriptor.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.6.8
+pub const DESCRIPTOR_TAG_REGISTRATION: u8 = 0x05;
+
+/// The tag of the DVB extension descriptor.
+///
+/// ETSI EN 300 468 - 6.2.16
+pub const DESCRIPTOR_TAG_DVB_EXTENSION: u8 = 0x7F;
+
+/// A descriptor in a PSI table.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.6.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptor {
+    /// The descriptor tag.
+    pub tag: u8,
+    /// The descriptor data.
+    pub data: Bytes,
+}
+
+impl Descriptor {
+    /// Parses a loop of descriptors.
+    fn parse_all(data: Bytes) -> io::Result<Vec<Self>> {
+        let mut reader = io::Cursor::new(data);
+        let mut descriptors = Vec::new();
+
+        while reader.has_remaining() {
+            let tag = reader.read_u8()?;
+            let length = reader.read_u8()? as usize;
+            descriptors.push(Self {
+                tag,
+                data: reader.extract_bytes(length)?,
+            });
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Builds a loop of descriptors, prefixed with its 12 bit length.
+    fn build_all<W: io::Write>(descriptors: &[Self], writer: &mut W) -> io::Result<()> {
+        let length: usize = descriptors.iter().map(|descriptor| 2 + descriptor.data.len()).sum();
+        writer.write_u16::<BigEndian>(0xF000 | length as u16)?;
+
+        for descriptor in descriptors {
+            writer.write_u8(descriptor.tag)?;
+            writer.write_u8(descriptor.data.len() as u8)?;
+            writer.write_all(&descriptor.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A PSI section using the long section syntax.
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.4
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Section {
+    table_id: u8,
+    table_id_extension: u16,
+    version_number: u8,
+    current_next_indicator: bool,
+    section_number: u8,
+    last_section_number: u8,
+    data: Bytes,
+}
+
+impl Section {
+    /// Parses a section including the CRC32.
+    fn parse(data: Bytes, expected_table_id: u8) -> Result<Self, MpegTsError> {
+        let length = section_size(&data).ok_or(MpegTsError::InvalidSectionLength(data.len()))?;
+        if length > data.len() || length < 12 {
+            return Err(MpegTsError::InvalidSectionLength(length));
+        }
+
+        let data = data.slice(..length);
+        let expected = u32::from_be_bytes(data[length - 4..].try_into().unwrap());
+        let actual = crc32(&data[..length - 4]);
+        if expected != actual {
+            return Err(MpegTsError::CrcMismatch { expected, actual });
+        }
+
+        let mut reader = io::Cursor::new(data.slice(..length - 4));
+        let table_id = reader.read_u8()?;
+        if table_id != expected_table_id {
+            return Err(MpegTsError::UnexpectedTableId {
+                expected: expected_table_id,
+                actual: table_id,
+            });
+        }
+
+        reader.read_u16::<BigEndian>()?; // section_syntax_indicator, reserved and section_length
+        let table_id_extension = reader.read_u16::<BigEndian>()?;
+        let version = reader.read_u8()?;
+        let section_number = reader.read_u8()?;
+        let last_section_number = reader.read_u8()?;
+
+        Ok(Self {
+            table_id,
+            table_id_extension,
+            version_number: (version >> 1) & 0x1F,
+            current_next_indicator: version & 0x01 != 0,
+            section_number,
+            last_section_number,
+            data: reader.extract_remaining(),
+        })
+    }
+
+    /// Builds the section including the CRC32.
+    fn build<W: io::Write>(&self, writer: &mut W) -> Result<(), MpegTsError> {
+        // 5 bytes of header after the section_length and 4 bytes of CRC32
+        let section_length = 5 + self.data.len() + 4;
+        if section_length > MAX_SECTION_LENGTH {
+            return Err(MpegTsError::InvalidSectionLength(section_length));
+        }
+
+        let mut section = Vec::with_capacity(3 + section_length);
+        section.write_u8(self.table_id)?;
+        // section_syntax_indicator = 1, '0', reserved = '11'
+        section.write_u16::<BigEndian>(0xB000 | section_length as u16)?;
+        section.write_u16::<BigEndian>(self.table_id_extension)?;
+        section.write_u8(0xC0 | (self.version_number & 0x1F) << 1 | self.current_next_indicator as u8)?;
+        section.write_u8(self.section_number)?;
+        section.write_u8(self.last_section_number)?;
+        section.write_all(&self.data)?;
+        section.write_u32::<BigEndian>(crc32(&section))?;
+
+        writer.write_all(&section)?;
+
+        Ok(())
+    }
+}
+
+/// Returns the total size of the section starting at the beginning of the
+/// data, or `None` if the header is incomplete.
+pub(crate) fn section_size(data: &[u8]) -> Option<usize> {
+    if data.len() < 3 {
+        return None;
+    }
+
+    Some(3 + (u16::from_be_bytes([data[1], data[2]]) & 0x0FFF) as usize)
+}
+
+/// A program in the program association table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatProgram {
+    /// The program number, 0 refers to the network PID.
+    pub program_number: u16,
+    /// The PID of the program map table (or network information table).
+    pub pid: u16,
+}
+
+/// The program association table (PAT).
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.4.4 (Table 2-30)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pat {
+    /// The transport stream id.
+    pub transport_stream_id: u16,
+    /// The version number (5 bits).
+    pub version_number: u8,
+    /// Indicates that the table is currently applicable.
+    pub current_next_indicator: bool,
+    /// The programs in the transport stream.
+    pub programs: Vec<PatProgram>,
+}
+
+impl Pat {
+    /// Parses a program association section including the CRC32.
+    pub fn parse(data: Bytes) -> Result<Self, MpegTsError> {
+        let section = Section::parse(data, TABLE_ID_PAT)?;
+
+        let mut reader = io::Cursor::new(section.data);
+        let mut programs = Vec::new();
+        while reader.remaining() >= 4 {
+            let program_number = reader.read_u16::<BigEndian>()?;
+            let pid = reader.read_u16::<BigEndian>()? & 0x1FFF;
+            programs.push(PatProgram { program_number, pid });
+        }
+
+        Ok(Self {
+            transport_stream_id: section.table_id_extension,
+            version_number: section.version_number,
+            current_next_indicator: section.current_next_indicator,
+            programs,
+        })
+    }
+
+    /// Builds the program association section including the CRC32.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> Result<(), MpegTsError> {
+        let mut data = Vec::with_capacity(self.programs.len() * 4);
+        for program in &self.programs {
+            data.write_u16::<BigEndian>(program.program_number)?;
+            data.write_u16::<BigEndian>(0xE000 | program.pid)?;
+        }
+
+        Section {
+            table_id: TABLE_ID_PAT,
+            table_id_extension: self.transport_stream_id,
+            version_number: self.version_number,
+            current_next_indicator: self.current_next_indicator,
+            section_number: 0,
+            last_section_number: 0,
+            data: Bytes::from(data),
+        }
+        .build(writer)
+    }
+}
+
+/// An elementary stream in the program map table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PmtStream {
+    /// The stream type.
+    pub stream_type: StreamType,
+    /// The PID of the elementary stream.
+    pub elementary_pid: u16,
+    /// The descriptors of the elementary stream.
+    pub descriptors: Vec<Descriptor>,
+}
+
+impl PmtStream {
+    /// Returns the first descriptor with the given tag.
+    pub fn descriptor(&self, tag: u8) -> Option<&Descriptor> {
+        self.descriptors.iter().find(|descriptor| descriptor.tag == tag)
+    }
+}
+
+/// The program map table (PMT).
+///
+/// ISO/IEC 13818-1:2022(E) - 2.4.4.9 (Table 2-33)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pmt {
+    /// The program number.
+    pub program_number: u16,
+    /// The version number (5 bits).
+    pub version_number: u8,
+    /// Indicates that the table is currently applicable.
+    pub current_next_indicator: bool,
+    /// The PID of the packets carrying the program clock reference.
+    pub pcr_pid: u16,
+    /// The program descriptors.
+    pub descriptors: Vec<Descriptor>,
+    /// The elementary streams of the program.
+    pub streams: Vec<PmtStream>,
+}
+
+impl Pmt {
+    /// Parses a program map section including the CRC32.
+    pub fn parse(data: Bytes) -> Result<Self, MpegTsError> {
+        let section = Section::parse(data, TABLE_ID_PMT)?;
+
+        let mut reader = io::Cursor::new(section.data);
+        let pcr_pid = reader.read_u16::<BigEndian>()? & 0x1FFF;
+        let program_info_length = (reader.read_u16::<BigEndian>()? & 0x0FFF) as usize;
+        let descriptors = Descriptor::parse_all(reader.extract_bytes(program_info_length)?)?;
+
+        let mut streams = Vec::new();
+        while reader.has_remaining() {
+            let stream_type = StreamType::from(reader.read_u8()?);
+            let elementary_pid = reader.read_u16::<BigEndian>()? & 0x1FFF;
+            let es_info_length = (reader.read_u16::<BigEndian>()? & 0x0FFF) as usize;
+            let descriptors = Descriptor::parse_all(reader.extract_bytes(es_info_length)?)?;
+
+            streams.push(PmtStream {
+                stream_type,
+                elementary_pid,
+                descriptors,
+            });
+        }
+
+        Ok(Self {
+            program_number: section.table_id_extension,
+            version_number: section.version_number,
+            current_next_indicator: section.current_next_indicator,
+            pcr_pid,
+            descriptors,
+            streams,
+        })
+    }
+
+    /// Returns the stream with the given PID.
+    pub fn stream(&self, pid: u16) -> Option<&PmtStream> {
+        self.streams.iter().find(|stream| stream.elementary_pid == pid)
+    }
+
+    /// Builds the program map section including the CRC32.
+    pub fn build<W: io::Write>(&self, writer: &mut W) -> Result<(), MpegTsError> {
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(0xE000 | self.pcr_pid)?;
+        Descriptor::build_all(&self.descriptors, &mut data)?;
+
+        for stream in &self.streams {
+            data.write_u8(stream.stream_type.0)?;
+            data.write_u16::<BigEndian>(0xE000 | stream.elementary_pid)?;
+            Descriptor::build_all(&stream.descriptors, &mut data)?;
+        }
+
+        Section {
+            table_id: TABLE_ID_PMT,
+            table_id_extension: self.program_number,
+            version_number: self.version_number,
+            current_next_indicator: self.current_next_indicator,
+            section_number: 0,
+            last_section_number: 0,
+            data: Bytes::from(data),
+        }
+        .build(writer)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pat_parse() {
+        // PAT generated by ffmpeg
+        let data = Bytes::from_static(&[
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1, 0x04, 0xB2,
+        ]);
+
+        let pat = Pat::parse(data.clone()).unwrap();
+        assert_eq!(
+            pat,
+            Pat {
+                transport_stream_id: 1,
+                version_number: 0,
+                current_next_indicator: true,
+                programs: vec![PatProgram {
+                    program_number: 1,
+                    pid: 0x1000,
+                }],
+            }
+        );
+
+        let mut built = Vec::new();
+        pat.build(&mut built).unwrap();
+        assert_eq!(built, data);
+    }
+
+    #[test]
+    fn test_pmt_round_trip() {
+        let pmt = Pmt {
+            program_number: 1,
+            version_number: 3,
+            current_next_indicator: true,
+            pcr_pid: 0x100,
+            descriptors: Vec::new(),
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: 0x100,
+                    descriptors: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::PrivateData,
+                    elementary_pid: 0x101,
+                    descriptors: vec![Descriptor {
+                        tag: DESCRIPTOR_TAG_REGISTRATION,
+                        data: Bytes::from_static(b"Opus"),
+                    }],
+                },
+            ],
+        };
+
+        let mut data = Vec::new();
+        pmt.build(&mut data).unwrap();
+        assert_eq!(section_size(&data), Some(data.len()));
+
+        let parsed = Pmt::parse(Bytes::from(data)).unwrap();
+        assert_eq!(parsed, pmt);
+        assert_eq!(
+            parsed
+                .stream(0x101)
+                .unwrap()
+                .descriptor(DESCRIPTOR_TAG_REGISTRATION)
+                .unwrap()
+                .data,
+            Bytes::from_static(b"Opus")
+        );
+    }
+
+    #[test]
+    fn test_section_errors() {
+        let mut data = Vec::new();
+        Pat {
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            programs: Vec::new(),
+        }
+        .build(&mut data)
+        .unwrap();
+
+        assert!(matches!(
+            Pmt::parse(Bytes::from(data.clone())),
+            Err(MpegTsError::UnexpectedTableId {
+                expected: TABLE_ID_PMT,
+                actual: TABLE_ID_PAT,
+            })
+        ));
+
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        assert!(matches!(
+            Pat::parse(Bytes::from(data.clone())),
+            Err(MpegTsError::CrcMismatch { .. })
+        ));
+
+        assert!(matches!(
+            Pat::parse(Bytes::from(data[..6].to_vec())),
+            Err(MpegTsError::InvalidSectionLength(12))
+        ));
+    }
+}
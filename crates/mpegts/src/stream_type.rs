@@ -0,0 +1,33 @@
+use nutype_enum::nutype_enum;
+
+nutype_enum! {
+    /// The stream type of an elementary stream in the PMT.
+    ///
+    /// ISO/IEC 13818-1:2022(E) - 2.4.4.9 (Table 2-34)
+    pub enum StreamType(u8) {
+        /// ISO/IEC 11172-2 video
+        Mpeg1Video = 0x01,
+        /// ISO/IEC 13818-2 video
+        Mpeg2Video = 0x02,
+        /// ISO/IEC 11172-3 audio
+        Mpeg1Audio = 0x03,
+        /// ISO/IEC 13818-3 audio
+        Mpeg2Audio = 0x04,
+        /// ISO/IEC 13818-1 private sections
+        PrivateSections = 0x05,
+        /// ISO/IEC 13818-1 PES packets containing private data
+        ///
+        /// Used for Opus, which is identified by a registration descriptor.
+        PrivateData = 0x06,
+        /// ISO/IEC 13818-7 audio with ADTS transport syntax
+        AdtsAac = 0x0F,
+        /// ISO/IEC 14496-2 visual
+        Mpeg4Visual = 0x10,
+        /// ISO/IEC 14496-3 audio with the LATM transport syntax
+        LatmAac = 0x11,
+        /// ISO/IEC 14496-10 (H.264) video
+        H264 = 0x1B,
+        /// ISO/IEC 23008-2 (H.265) video
+        H265 = 0x24,
+    }
+}
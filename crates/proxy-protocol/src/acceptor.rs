@@ -0,0 +1,133 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use tokio::io::AsyncRead;
+
+use crate::error::ProxyProtocolError;
+use crate::header::ProxyHeader;
+use crate::network::IpNetwork;
+
+/// Decides which connections start with a PROXY protocol header and reads it.
+///
+/// Only peers in one of the [`trusted_networks`](ProxyProtocolBuilder::trusted_networks) are expected to send a
+/// header, their connections are rejected when they do not. Connections from any other peer are passed through
+/// untouched and keep their peer address, so a client can never spoof its address by sending a header itself.
+///
+/// Use [`ProxyProtocol::builder`] to create a new configuration.
+#[derive(Debug, Clone, bon::Builder)]
+pub struct ProxyProtocol {
+    /// The networks of the proxies or load balancers in front of the listener.
+    ///
+    /// Use `0.0.0.0/0` and `::/0` to require a header from every peer.
+    #[builder(default)]
+    trusted_networks: Vec<IpNetwork>,
+    /// The maximum time a trusted peer can take to send the header.
+    #[builder(default = Duration::from_secs(5))]
+    header_timeout: Duration,
+}
+
+impl ProxyProtocol {
+    /// Returns whether `addr` is part of one of the trusted networks.
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        self.trusted_networks.iter().any(|network| network.contains(addr))
+    }
+
+    /// Reads the header from a newly accepted connection from `peer_addr`.
+    ///
+    /// Returns the address of the client and the header, if the peer is trusted.
+    /// The client address is the peer address for untrusted peers and for headers that do not carry a source address,
+    /// like health checks of the proxy.
+    pub async fn accept<R: AsyncRead + Unpin>(
+        &self,
+        stream: &mut R,
+        peer_addr: SocketAddr,
+    ) -> Result<(SocketAddr, Option<ProxyHeader>), ProxyProtocolError> {
+        if !self.is_trusted(peer_addr.ip()) {
+            return Ok((peer_addr, None));
+        }
+
+        let header = tokio::time::timeout(self.header_timeout, ProxyHeader::read(stream))
+            .await
+            .map_err(|_| ProxyProtocolError::Timeout)??;
+
+        Ok((header.source.unwrap_or(peer_addr), Some(header)))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use super::ProxyProtocol;
+    use crate::error::ProxyProtocolError;
+
+    fn proxy_protocol() -> ProxyProtocol {
+        ProxyProtocol::builder()
+            .trusted_networks(vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()])
+            .header_timeout(Duration::from_millis(50))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn trusted_peer() {
+        let peer: SocketAddr = "10.0.0.5:40000".parse().unwrap();
+        let mut stream = &b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 1935\r\nrest"[..];
+
+        let (client_addr, header) = proxy_protocol().accept(&mut stream, peer).await.unwrap();
+
+        assert_eq!(client_addr, "203.0.113.7:56324".parse().unwrap());
+        assert!(header.is_some());
+        assert_eq!(stream, b"rest");
+    }
+
+    #[tokio::test]
+    async fn trusted_mapped_peer() {
+        let peer: SocketAddr = "[::ffff:10.0.0.5]:40000".parse().unwrap();
+        let mut stream = &b"PROXY UNKNOWN\r\n"[..];
+
+        let (client_addr, header) = proxy_protocol().accept(&mut stream, peer).await.unwrap();
+
+        // Headers without addresses keep the peer address
+        assert_eq!(client_addr, peer);
+        assert!(header.is_some());
+    }
+
+    #[tokio::test]
+    async fn untrusted_peer() {
+        let peer: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let mut stream = &b"PROXY TCP4 1.1.1.1 10.0.0.1 56324 1935\r\n"[..];
+
+        let (client_addr, header) = proxy_protocol().accept(&mut stream, peer).await.unwrap();
+
+        assert_eq!(client_addr, peer);
+        assert!(header.is_none());
+        // Nothing is consumed
+        assert_eq!(stream.len(), 40);
+    }
+
+    #[tokio::test]
+    async fn trusted_peer_without_header() {
+        let peer: SocketAddr = "[fd00::5]:40000".parse().unwrap();
+        let mut stream = &b"GET / HTTP/1.1\r\n"[..];
+
+        let err = proxy_protocol().accept(&mut stream, peer).await.unwrap_err();
+        assert!(matches!(err, ProxyProtocolError::MissingHeader));
+    }
+
+    #[tokio::test]
+    async fn trusted_peer_timeout() {
+        let peer: SocketAddr = "10.0.0.5:40000".parse().unwrap();
+        let (mut stream, _client) = tokio::io::duplex(64);
+
+        let err = proxy_protocol().accept(&mut stream, peer).await.unwrap_err();
+        assert!(matches!(err, ProxyProtocolError::Timeout));
+    }
+
+    #[test]
+    fn no_trusted_networks() {
+        let proxy_protocol = ProxyProtocol::builder().build();
+        assert!(!proxy_protocol.is_trusted("10.0.0.1".parse().unwrap()));
+    }
+}
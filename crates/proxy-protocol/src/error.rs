@@ -0,0 +1,24 @@
+//! Error types.
+
+/// PROXY protocol error.
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyProtocolError {
+    /// IO error.
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    /// A trusted peer did not start the connection with a PROXY protocol header.
+    #[error("missing PROXY protocol header")]
+    MissingHeader,
+    /// A trusted peer did not send the complete header in time.
+    #[error("timed out waiting for the PROXY protocol header")]
+    Timeout,
+    /// The version 1 (text) header is malformed.
+    #[error("invalid PROXY protocol v1 header: {0}")]
+    InvalidV1(&'static str),
+    /// The version 2 (binary) header is malformed or not supported.
+    #[error("invalid PROXY protocol v2 header: {0}")]
+    InvalidV2(&'static str),
+    /// The network is not a valid CIDR notation.
+    #[error("invalid network: {0}")]
+    InvalidNetwork(&'static str),
+}
@@ -0,0 +1,547 @@
+//! The PROXY protocol header.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+use bytes::{Buf, Bytes};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::ProxyProtocolError;
+
+/// The signature every version 2 header starts with.
+pub const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum length of a version 1 header, including the trailing CRLF.
+pub const V1_MAX_LEN: usize = 107;
+
+/// The length of the fixed part of a version 2 header.
+const V2_HEADER_LEN: usize = 16;
+
+/// The version of the PROXY protocol a header was sent with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// The human readable text format.
+    V1,
+    /// The binary format.
+    V2,
+}
+
+/// The command of a PROXY protocol header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// The connection was opened by the proxy itself, for example for a health check.
+    ///
+    /// The addresses of the header must be ignored, the peer address is the address of the client.
+    Local,
+    /// The connection was relayed on behalf of the client described by the header.
+    Proxy,
+}
+
+nutype_enum::nutype_enum! {
+    /// The type of a version 2 TLV (type-length-value) field.
+    pub enum TlvType(u8) {
+        /// The application layer protocol negotiated by the proxy.
+        Alpn = 0x01,
+        /// The host name the client sent, usually taken from the TLS SNI extension.
+        Authority = 0x02,
+        /// A CRC32c checksum of the header.
+        Crc32c = 0x03,
+        /// Padding that must be ignored.
+        Noop = 0x04,
+        /// An opaque identifier of the connection, generated by the proxy.
+        UniqueId = 0x05,
+        /// Information about the TLS connection between the client and the proxy.
+        Ssl = 0x20,
+        /// The network namespace the connection was accepted in.
+        Netns = 0x30,
+        /// AWS specific information, used by AWS PrivateLink (VPC endpoint services).
+        Aws = 0xEA,
+        /// Azure specific information, used by Azure Private Link services.
+        Azure = 0xEE,
+    }
+}
+
+/// The subtype of an [`TlvType::Aws`] field containing the VPC endpoint id.
+const AWS_VPC_ENDPOINT_ID: u8 = 0x01;
+
+/// A version 2 TLV (type-length-value) field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tlv {
+    /// The type of the field.
+    pub kind: TlvType,
+    /// The raw value of the field.
+    pub value: Bytes,
+}
+
+/// A parsed PROXY protocol header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// The version the header was sent with.
+    pub version: Version,
+    /// Whether the connection was relayed or opened by the proxy itself.
+    pub command: Command,
+    /// The address of the client.
+    ///
+    /// `None` for [`Command::Local`], for unknown or unspecified address families and for unix sockets.
+    pub source: Option<SocketAddr>,
+    /// The address the client connected to on the proxy.
+    ///
+    /// `None` in the same cases as [`source`](Self::source).
+    pub destination: Option<SocketAddr>,
+    /// The TLV fields of the header, always empty for version 1.
+    pub tlvs: Vec<Tlv>,
+}
+
+impl ProxyHeader {
+    /// Reads a version 1 or version 2 header from the start of a connection.
+    ///
+    /// Only the bytes of the header are consumed, so the reader can be used for the proxied connection afterwards.
+    /// Returns [`ProxyProtocolError::MissingHeader`] when the connection does not start with a header.
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self, ProxyProtocolError> {
+        // Both "PROXY" and the v2 signature are 5 bytes or longer, so this never reads past a header.
+        let mut prefix = [0; 5];
+        reader.read_exact(&mut prefix).await?;
+
+        if &prefix == b"PROXY" {
+            let mut line = Vec::with_capacity(V1_MAX_LEN);
+            line.extend_from_slice(&prefix);
+
+            // The header has no length field, so read byte by byte to not consume any data after it.
+            while !line.ends_with(b"\r\n") {
+                if line.len() >= V1_MAX_LEN {
+                    return Err(ProxyProtocolError::InvalidV1("header too long"));
+                }
+
+                line.push(reader.read_u8().await?);
+            }
+
+            Self::parse_v1(&line)
+        } else if prefix == V2_SIGNATURE[..5] {
+            let mut header = [0; V2_HEADER_LEN];
+            header[..5].copy_from_slice(&prefix);
+            reader.read_exact(&mut header[5..]).await?;
+
+            let len = u16::from_be_bytes([header[14], header[15]]) as usize;
+            let mut payload = vec![0; len];
+            reader.read_exact(&mut payload).await?;
+
+            Self::parse_v2(&header, Bytes::from(payload))
+        } else {
+            Err(ProxyProtocolError::MissingHeader)
+        }
+    }
+
+    /// Parses a version 1 header line, including the trailing CRLF.
+    pub fn parse_v1(line: &[u8]) -> Result<Self, ProxyProtocolError> {
+        if line.len() > V1_MAX_LEN {
+            return Err(ProxyProtocolError::InvalidV1("header too long"));
+        }
+
+        let line = line
+            .strip_suffix(b"\r\n")
+            .ok_or(ProxyProtocolError::InvalidV1("missing CRLF"))?;
+        let line = std::str::from_utf8(line).map_err(|_| ProxyProtocolError::InvalidV1("not ASCII"))?;
+
+        let mut parts = line.split(' ');
+        if parts.next() != Some("PROXY") {
+            return Err(ProxyProtocolError::InvalidV1("missing PROXY prefix"));
+        }
+
+        let (source, destination) = match parts.next() {
+            // The rest of the line must be ignored for unknown connections.
+            Some("UNKNOWN") => (None, None),
+            Some(family @ ("TCP4" | "TCP6")) => {
+                let mut next = || parts.next().ok_or(ProxyProtocolError::InvalidV1("missing field"));
+
+                let (source_ip, destination_ip) = if family == "TCP4" {
+                    (parse_v1_ip::<Ipv4Addr>(next()?)?, parse_v1_ip::<Ipv4Addr>(next()?)?)
+                } else {
+                    (parse_v1_ip::<Ipv6Addr>(next()?)?, parse_v1_ip::<Ipv6Addr>(next()?)?)
+                };
+                let source_port = parse_v1_port(next()?)?;
+                let destination_port = parse_v1_port(next()?)?;
+
+                if parts.next().is_some() {
+                    return Err(ProxyProtocolError::InvalidV1("trailing fields"));
+                }
+
+                (
+                    Some(SocketAddr::new(source_ip, source_port)),
+                    Some(SocketAddr::new(destination_ip, destination_port)),
+                )
+            }
+            Some(_) => return Err(ProxyProtocolError::InvalidV1("unknown protocol family")),
+            None => return Err(ProxyProtocolError::InvalidV1("missing protocol family")),
+        };
+
+        Ok(Self {
+            version: Version::V1,
+            command: Command::Proxy,
+            source,
+            destination,
+            tlvs: Vec::new(),
+        })
+    }
+
+    /// Parses a version 2 header from its 16 byte fixed part and the variable part that follows it.
+    pub fn parse_v2(header: &[u8; 16], mut payload: Bytes) -> Result<Self, ProxyProtocolError> {
+        if header[..12] != V2_SIGNATURE {
+            return Err(ProxyProtocolError::InvalidV2("invalid signature"));
+        }
+
+        if header[12] >> 4 != 2 {
+            return Err(ProxyProtocolError::InvalidV2("unsupported version"));
+        }
+
+        let command = match header[12] & 0x0F {
+            0x0 => Command::Local,
+            0x1 => Command::Proxy,
+            _ => return Err(ProxyProtocolError::InvalidV2("unknown command")),
+        };
+
+        if u16::from_be_bytes([header[14], header[15]]) as usize != payload.len() {
+            return Err(ProxyProtocolError::InvalidV2("length mismatch"));
+        }
+
+        // The high nibble is the address family, the low nibble the transport protocol which we do not care about.
+        let addresses_len = match header[13] >> 4 {
+            // AF_UNSPEC
+            0x0 => 0,
+            // AF_INET
+            0x1 => 12,
+            // AF_INET6
+            0x2 => 36,
+            // AF_UNIX
+            0x3 => 216,
+            _ => return Err(ProxyProtocolError::InvalidV2("unknown address family")),
+        };
+
+        if payload.len() < addresses_len {
+            return Err(ProxyProtocolError::InvalidV2("address block too short"));
+        }
+
+        let mut addresses = payload.split_to(addresses_len);
+        let (source, destination) = match header[13] >> 4 {
+            0x1 => {
+                let source_ip = Ipv4Addr::from(addresses.get_u32());
+                let destination_ip = Ipv4Addr::from(addresses.get_u32());
+                let source_port = addresses.get_u16();
+                let destination_port = addresses.get_u16();
+
+                (
+                    Some(SocketAddr::V4(SocketAddrV4::new(source_ip, source_port))),
+                    Some(SocketAddr::V4(SocketAddrV4::new(destination_ip, destination_port))),
+                )
+            }
+            0x2 => {
+                let source_ip = Ipv6Addr::from(addresses.get_u128());
+                let destination_ip = Ipv6Addr::from(addresses.get_u128());
+                let source_port = addresses.get_u16();
+                let destination_port = addresses.get_u16();
+
+                (
+                    Some(SocketAddr::V6(SocketAddrV6::new(source_ip, source_port, 0, 0))),
+                    Some(SocketAddr::V6(SocketAddrV6::new(destination_ip, destination_port, 0, 0))),
+                )
+            }
+            _ => (None, None),
+        };
+
+        let mut tlvs = Vec::new();
+        while payload.has_remaining() {
+            if payload.len() < 3 {
+                return Err(ProxyProtocolError::InvalidV2("truncated TLV"));
+            }
+
+            let kind = TlvType::from(payload.get_u8());
+            let len = payload.get_u16() as usize;
+            if payload.len() < len {
+                return Err(ProxyProtocolError::InvalidV2("truncated TLV"));
+            }
+
+            tlvs.push(Tlv {
+                kind,
+                value: payload.split_to(len),
+            });
+        }
+
+        let (source, destination) = match command {
+            // The receiver must ignore the addresses of local connections.
+            Command::Local => (None, None),
+            Command::Proxy => (source, destination),
+        };
+
+        Ok(Self {
+            version: Version::V2,
+            command,
+            source,
+            destination,
+            tlvs,
+        })
+    }
+
+    /// Returns the value of the first TLV field of the given type.
+    pub fn tlv(&self, kind: TlvType) -> Option<&Bytes> {
+        self.tlvs.iter().find(|tlv| tlv.kind == kind).map(|tlv| &tlv.value)
+    }
+
+    /// The host name the client connected to, if the proxy sent it.
+    pub fn authority(&self) -> Option<&str> {
+        self.tlv(TlvType::Authority).and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// The identifier the proxy assigned to the connection, if it sent one.
+    pub fn unique_id(&self) -> Option<&Bytes> {
+        self.tlv(TlvType::UniqueId)
+    }
+
+    /// The id of the AWS VPC endpoint the connection came through, for listeners behind AWS PrivateLink.
+    pub fn aws_vpc_endpoint_id(&self) -> Option<&str> {
+        self.tlvs
+            .iter()
+            .filter(|tlv| tlv.kind == TlvType::Aws)
+            .find_map(|tlv| match tlv.value.split_first() {
+                Some((&AWS_VPC_ENDPOINT_ID, id)) => std::str::from_utf8(id).ok(),
+                _ => None,
+            })
+    }
+}
+
+fn parse_v1_ip<T: std::str::FromStr + Into<IpAddr>>(s: &str) -> Result<IpAddr, ProxyProtocolError> {
+    s.parse::<T>()
+        .map(Into::into)
+        .map_err(|_| ProxyProtocolError::InvalidV1("invalid address"))
+}
+
+fn parse_v1_port(s: &str) -> Result<u16, ProxyProtocolError> {
+    // Ports must be plain decimal numbers without a sign or leading zeros.
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) || (s.len() > 1 && s.starts_with('0')) {
+        return Err(ProxyProtocolError::InvalidV1("invalid port"));
+    }
+
+    s.parse().map_err(|_| ProxyProtocolError::InvalidV1("invalid port"))
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::SocketAddr;
+
+    use bytes::Bytes;
+
+    use super::{Command, ProxyHeader, TlvType, V2_SIGNATURE, Version};
+    use crate::error::ProxyProtocolError;
+
+    fn v2(command: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(0x20 | command);
+        buf.push(family);
+        buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[test]
+    fn parse_v1_tcp4() {
+        let header = ProxyHeader::parse_v1(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 443\r\n").unwrap();
+
+        assert_eq!(header.version, Version::V1);
+        assert_eq!(header.command, Command::Proxy);
+        assert_eq!(header.source, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().unwrap()));
+        assert!(header.tlvs.is_empty());
+    }
+
+    #[test]
+    fn parse_v1_tcp6() {
+        let header = ProxyHeader::parse_v1(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n").unwrap();
+
+        assert_eq!(header.source, Some("[2001:db8::1]:56324".parse().unwrap()));
+        assert_eq!(header.destination, Some("[2001:db8::2]:443".parse().unwrap()));
+    }
+
+    #[test]
+    fn parse_v1_unknown() {
+        let header = ProxyHeader::parse_v1(b"PROXY UNKNOWN ignored stuff\r\n").unwrap();
+        assert_eq!(header.source, None);
+        assert_eq!(header.destination, None);
+
+        let header = ProxyHeader::parse_v1(b"PROXY UNKNOWN\r\n").unwrap();
+        assert_eq!(header.source, None);
+    }
+
+    #[test]
+    fn parse_v1_invalid() {
+        let cases: &[&[u8]] = &[
+            b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 443",
+            b"PROXY TCP4 192.168.0.1 10.0.0.1 56324\r\n",
+            b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 443 1\r\n",
+            b"PROXY TCP4 2001:db8::1 10.0.0.1 56324 443\r\n",
+            b"PROXY TCP6 192.168.0.1 10.0.0.1 56324 443\r\n",
+            b"PROXY TCP4 192.168.0.1 10.0.0.1 +5632 443\r\n",
+            b"PROXY TCP4 192.168.0.1 10.0.0.1 05632 443\r\n",
+            b"PROXY TCP4 192.168.0.1 10.0.0.1 65536 443\r\n",
+            b"PROXY UDP4 192.168.0.1 10.0.0.1 56324 443\r\n",
+            b"PROXY\r\n",
+            b"HELLO TCP4 192.168.0.1 10.0.0.1 56324 443\r\n",
+        ];
+
+        for case in cases {
+            assert!(
+                matches!(ProxyHeader::parse_v1(case), Err(ProxyProtocolError::InvalidV1(_))),
+                "{}",
+                String::from_utf8_lossy(case)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_v2_inet() {
+        let mut payload = vec![192, 168, 0, 1, 10, 0, 0, 1];
+        payload.extend_from_slice(&56324u16.to_be_bytes());
+        payload.extend_from_slice(&443u16.to_be_bytes());
+        let buf = v2(0x1, 0x11, &payload);
+
+        let header = ProxyHeader::parse_v2(buf[..16].try_into().unwrap(), Bytes::copy_from_slice(&buf[16..])).unwrap();
+
+        assert_eq!(header.version, Version::V2);
+        assert_eq!(header.command, Command::Proxy);
+        assert_eq!(header.source, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().unwrap()));
+        assert!(header.tlvs.is_empty());
+    }
+
+    #[test]
+    fn parse_v2_inet6() {
+        let source: SocketAddr = "[2001:db8::1]:56324".parse().unwrap();
+        let destination: SocketAddr = "[2001:db8::2]:443".parse().unwrap();
+
+        let mut payload = Vec::new();
+        let (SocketAddr::V6(src), SocketAddr::V6(dst)) = (source, destination) else {
+            unreachable!()
+        };
+        payload.extend_from_slice(&src.ip().octets());
+        payload.extend_from_slice(&dst.ip().octets());
+        payload.extend_from_slice(&src.port().to_be_bytes());
+        payload.extend_from_slice(&dst.port().to_be_bytes());
+        let buf = v2(0x1, 0x21, &payload);
+
+        let header = ProxyHeader::parse_v2(buf[..16].try_into().unwrap(), Bytes::copy_from_slice(&buf[16..])).unwrap();
+
+        assert_eq!(header.source, Some(source));
+        assert_eq!(header.destination, Some(destination));
+    }
+
+    #[test]
+    fn parse_v2_local() {
+        let mut payload = vec![192, 168, 0, 1, 10, 0, 0, 1];
+        payload.extend_from_slice(&[0; 4]);
+        let buf = v2(0x0, 0x11, &payload);
+
+        let header = ProxyHeader::parse_v2(buf[..16].try_into().unwrap(), Bytes::copy_from_slice(&buf[16..])).unwrap();
+
+        assert_eq!(header.command, Command::Local);
+        assert_eq!(header.source, None);
+        assert_eq!(header.destination, None);
+    }
+
+    #[test]
+    fn parse_v2_unix() {
+        let buf = v2(0x1, 0x31, &[0; 216]);
+
+        let header = ProxyHeader::parse_v2(buf[..16].try_into().unwrap(), Bytes::copy_from_slice(&buf[16..])).unwrap();
+
+        assert_eq!(header.command, Command::Proxy);
+        assert_eq!(header.source, None);
+    }
+
+    #[test]
+    fn parse_v2_tlvs() {
+        let mut payload = vec![192, 168, 0, 1, 10, 0, 0, 1, 0, 80, 0, 80];
+        // authority
+        payload.extend_from_slice(&[0x02, 0x00, 0x0B]);
+        payload.extend_from_slice(b"example.com");
+        // unique id
+        payload.extend_from_slice(&[0x05, 0x00, 0x02, 0xAB, 0xCD]);
+        // aws vpc endpoint id
+        payload.extend_from_slice(&[0xEA, 0x00, 0x17, 0x01]);
+        payload.extend_from_slice(b"vpce-08d2bf15fac5001c9");
+        // noop
+        payload.extend_from_slice(&[0x04, 0x00, 0x00]);
+        let buf = v2(0x1, 0x11, &payload);
+
+        let header = ProxyHeader::parse_v2(buf[..16].try_into().unwrap(), Bytes::copy_from_slice(&buf[16..])).unwrap();
+
+        assert_eq!(header.tlvs.len(), 4);
+        assert_eq!(header.authority(), Some("example.com"));
+        assert_eq!(header.unique_id().map(|id| &id[..]), Some(&[0xAB, 0xCD][..]));
+        assert_eq!(header.aws_vpc_endpoint_id(), Some("vpce-08d2bf15fac5001c9"));
+        assert_eq!(header.tlv(TlvType::Noop).map(|v| v.len()), Some(0));
+        assert_eq!(header.tlv(TlvType::Azure), None);
+    }
+
+    #[test]
+    fn parse_v2_invalid() {
+        let address = [192, 168, 0, 1, 10, 0, 0, 1, 0, 80, 0, 80];
+
+        let mut bad_signature = v2(0x1, 0x11, &address);
+        bad_signature[11] = b'X';
+
+        let mut bad_version = v2(0x1, 0x11, &address);
+        bad_version[12] = 0x11;
+
+        let mut bad_length = v2(0x1, 0x11, &address);
+        bad_length[15] = 13;
+
+        let cases = [
+            bad_signature,
+            bad_version,
+            bad_length,
+            v2(0x2, 0x11, &address),
+            v2(0x1, 0x41, &address),
+            v2(0x1, 0x21, &address),
+            v2(0x1, 0x11, &[&address[..], &[0x02, 0x00]].concat()),
+            v2(0x1, 0x11, &[&address[..], &[0x02, 0x00, 0x05, b'a']].concat()),
+        ];
+
+        for case in cases {
+            assert!(matches!(
+                ProxyHeader::parse_v2(case[..16].try_into().unwrap(), Bytes::copy_from_slice(&case[16..])),
+                Err(ProxyProtocolError::InvalidV2(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn read_leaves_remaining_data() {
+        let mut reader = &b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n"[..];
+        let header = ProxyHeader::read(&mut reader).await.unwrap();
+        assert_eq!(header.source, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(reader, b"GET / HTTP/1.1\r\n");
+
+        let mut buf = v2(0x1, 0x11, &[192, 168, 0, 1, 10, 0, 0, 1, 0, 80, 0, 80]);
+        buf.extend_from_slice(b"\x16\x03\x01");
+        let mut reader = &buf[..];
+        let header = ProxyHeader::read(&mut reader).await.unwrap();
+        assert_eq!(header.source, Some("192.168.0.1:80".parse().unwrap()));
+        assert_eq!(reader, b"\x16\x03\x01");
+    }
+
+    #[tokio::test]
+    async fn read_errors() {
+        let mut reader = &b"GET / HTTP/1.1\r\n"[..];
+        assert!(matches!(
+            ProxyHeader::read(&mut reader).await,
+            Err(ProxyProtocolError::MissingHeader)
+        ));
+
+        let line = [b"PROXY ".as_slice(), &[b'A'; 200]].concat();
+        let mut reader = &line[..];
+        assert!(matches!(
+            ProxyHeader::read(&mut reader).await,
+            Err(ProxyProtocolError::InvalidV1("header too long"))
+        ));
+
+        let mut reader = &b"PROXY TCP4"[..];
+        assert!(matches!(ProxyHeader::read(&mut reader).await, Err(ProxyProtocolError::Io(_))));
+    }
+}
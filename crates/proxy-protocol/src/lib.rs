@@ -0,0 +1,79 @@
+//! A parser for the PROXY protocol, used by L4 load balancers and proxies to pass the address of the client
+//! to the server they forward a TCP connection to.
+//!
+//! Both the human readable version 1 and the binary version 2 of the protocol are supported, including the
+//! TLV (type-length-value) fields of version 2, like the AWS VPC endpoint id or the unique id of the connection.
+//!
+//! [`ProxyProtocol`] only expects a header from trusted peers, so it can be enabled on listeners that also accept
+//! direct connections.
+#![cfg_attr(feature = "docs", doc = "\n\nSee the [changelog][changelog] for a full release history.")]
+#![cfg_attr(feature = "docs", doc = "## Feature flags")]
+#![cfg_attr(feature = "docs", doc = document_features::document_features!())]
+//! ## Example
+//!
+//! ```no_run
+//! # use scuffle_proxy_protocol::ProxyProtocol;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!     let proxy_protocol = ProxyProtocol::builder()
+//!         .trusted_networks(vec!["10.0.0.0/8".parse().unwrap()])
+//!         .build();
+//!
+//!     let listener = tokio::net::TcpListener::bind("[::]:1935").await.unwrap();
+//!
+//!     while let Ok((mut stream, peer_addr)) = listener.accept().await {
+//!         let proxy_protocol = proxy_protocol.clone();
+//!
+//!         tokio::spawn(async move {
+//!             let (client_addr, header) = match proxy_protocol.accept(&mut stream, peer_addr).await {
+//!                 Ok(res) => res,
+//!                 Err(err) => {
+//!                     // The peer is trusted but did not send a valid header
+//!                     return;
+//!                 }
+//!             };
+//!
+//!             let vpc_endpoint_id = header.as_ref().and_then(|h| h.aws_vpc_endpoint_id());
+//!
+//!             // Handle the connection from `client_addr`, the header is no longer part of the stream
+//!         });
+//!     }
+//! }
+//! ```
+//!
+//! ## Specifications
+//!
+//! | Name | Version | Link | Comments |
+//! | --- | --- | --- | --- |
+//! | The PROXY protocol | Versions 1 & 2 | <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt> | |
+//! | Network Load Balancer proxy protocol | | <https://docs.aws.amazon.com/elasticloadbalancing/latest/network/edit-target-group-attributes.html#proxy-protocol> | AWS VPC endpoint id TLV |
+//!
+//! ## License
+//!
+//! This project is licensed under the MIT or Apache-2.0 license.
+//! You can choose between one of them if you use this work.
+//!
+//! `SPDX-License-Identifier: MIT OR Apache-2.0`
+#![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
+#![deny(unreachable_pub)]
+#![deny(clippy::mod_module_files)]
+
+mod acceptor;
+mod network;
+
+pub mod error;
+pub mod header;
+
+pub use acceptor::ProxyProtocol;
+pub use error::ProxyProtocolError;
+pub use header::ProxyHeader;
+pub use network::IpNetwork;
+
+/// Changelogs generated by [scuffle_changelog]
+#[cfg(feature = "docs")]
+#[scuffle_changelog::changelog]
+pub mod changelog {}
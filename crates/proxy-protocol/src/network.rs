@@ -0,0 +1,126 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::error::ProxyProtocolError;
+
+/// An IPv4 or IPv6 network in CIDR notation, for example `10.0.0.0/8` or `fd00::/8`.
+///
+/// A single address without a prefix length is parsed as a network containing only that address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// Creates a new network, the host bits of `addr` are cleared.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, ProxyProtocolError> {
+        let addr = match addr {
+            IpAddr::V4(addr) if prefix_len <= 32 => IpAddr::V4(Ipv4Addr::from(u32::from(addr) & v4_mask(prefix_len))),
+            IpAddr::V6(addr) if prefix_len <= 128 => IpAddr::V6(Ipv6Addr::from(u128::from(addr) & v6_mask(prefix_len))),
+            _ => return Err(ProxyProtocolError::InvalidNetwork("prefix length too long")),
+        };
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// The network address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The number of leading bits of the network address.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns whether `addr` is part of this network.
+    ///
+    /// IPv4-mapped IPv6 addresses, as reported by dual-stack listeners, are treated as IPv4 addresses.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => u32::from(addr) & v4_mask(self.prefix_len) == u32::from(network),
+            (IpAddr::V6(network), IpAddr::V6(addr)) => u128::from(addr) & v6_mask(self.prefix_len) == u128::from(network),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = ProxyProtocolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| ProxyProtocolError::InvalidNetwork("invalid address"))?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .map_err(|_| ProxyProtocolError::InvalidNetwork("invalid prefix length"))?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl std::fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+fn v4_mask(prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+fn v6_mask(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::IpNetwork;
+
+    #[test]
+    fn parse() {
+        let network: IpNetwork = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(network.addr(), "10.0.0.0".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(network.prefix_len(), 8);
+        assert_eq!(network.to_string(), "10.0.0.0/8");
+
+        assert_eq!("fd00::1/8".parse::<IpNetwork>().unwrap().to_string(), "fd00::/8");
+        assert_eq!("192.168.0.1".parse::<IpNetwork>().unwrap().to_string(), "192.168.0.1/32");
+        assert_eq!("::1".parse::<IpNetwork>().unwrap().to_string(), "::1/128");
+        assert_eq!("0.0.0.0/0".parse::<IpNetwork>().unwrap().to_string(), "0.0.0.0/0");
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("::/129".parse::<IpNetwork>().is_err());
+        assert!("10.0.0/8".parse::<IpNetwork>().is_err());
+        assert!("10.0.0.0/abc".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn contains() {
+        let network: IpNetwork = "10.0.0.0/8".parse().unwrap();
+        assert!(network.contains("10.255.0.1".parse().unwrap()));
+        assert!(network.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+        assert!(!network.contains("fd00::1".parse().unwrap()));
+
+        let network: IpNetwork = "fd00::/8".parse().unwrap();
+        assert!(network.contains("fd12::1".parse().unwrap()));
+        assert!(!network.contains("fe80::1".parse().unwrap()));
+        assert!(!network.contains("10.0.0.1".parse().unwrap()));
+
+        assert!("0.0.0.0/0".parse::<IpNetwork>().unwrap().contains("1.2.3.4".parse().unwrap()));
+        assert!("::/0".parse::<IpNetwork>().unwrap().contains("2001:db8::1".parse().unwrap()));
+    }
+}
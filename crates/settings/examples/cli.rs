@@ -12,6 +12,7 @@ fn main() {
         cli: Some(scuffle_settings::cli!()),
         default_config_file: Some("config"),
         env_prefix: Some("APP"),
+        ..Default::default()
     });
 
     println!("{config:#?}");
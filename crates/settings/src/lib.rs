@@ -90,6 +90,89 @@
 //! - `--override` or `-o`
 //!
 //!   Provide an override for a configuration value, in the format `KEY=VALUE`.
+//! - `--print-schema`
+//!
+//!   Print the JSON schema of the configuration and exit.
+//! - `--print-config`
+//!
+//!   Print a default configuration file with every setting and exit.
+//!
+//! ## Validation
+//!
+//! [`parse_settings_with_origins`] returns where every setting comes from, see [`Origin`].
+//! Invalid values are reported as [`SettingsError::Invalid`], which names the key and its origin.
+//! With [`Options::strict`], settings that are not part of the settings type are rejected instead of ignored.
+//!
+//! The schema of the settings type is traced from its [`Deserialize`](serde::Deserialize)
+//! implementation by [`Schema::of`], which also backs the `--print-schema` and `--print-config` flags.
+//!
+//! ## Secrets
+//!
+//! If the `secrets` feature is enabled, string settings can refer to secrets instead of containing them.
+//! Before the settings are deserialized, `file:///run/secrets/db_password` is replaced by the content
+//! of the file and `env:DB_PASSWORD` by the value of the environment variable. Other schemes are
+//! resolved by registering a [`SecretProvider`] in [`Options::secrets`].
+//!
+//! Wrap secrets in [`Secret`], which is redacted in `Debug` output and zeroized when dropped:
+//!
+//! ```rust
+//! #[derive(Debug, serde_derive::Deserialize)]
+//! struct MyConfig {
+//!     // `database_url = "file:///run/secrets/database_url"` in the config file
+//!     database_url: scuffle_settings::Secret<String>,
+//! }
+//! ```
+//!
+//! Secrets are resolved again by every reload, and the files that secrets are read from are watched like config files.
+//!
+//! ## Reloading
+//!
+//! If the `reload` feature is enabled, settings can be reloaded while the application is running.
+//! [`LiveSettings`] parses the settings again whenever a config file changes or the process
+//! receives `SIGHUP`, and publishes them to every clone of the handle. Keys that can't change
+//! without a restart are listed in [`Reload::RESTART_REQUIRED`], reloads changing them are rejected.
+//! The keys changed by every reload are logged.
+//!
+//! With [`scuffle_bootstrap`](scuffle_bootstrap), use [`LiveSettings`] as the config and run the
+//! `ReloadSvc` service:
+//!
+//! ```rust
+//! # use std::sync::Arc;
+//! #[derive(serde_derive::Deserialize)]
+//! struct MyConfig {
+//!     bind: String,
+//!     log_level: String,
+//! }
+//!
+//! scuffle_settings::bootstrap!(MyConfig);
+//!
+//! impl scuffle_settings::Reload for MyConfig {
+//!     const RESTART_REQUIRED: &'static [&'static str] = &["bind"];
+//! }
+//!
+//! struct Global {
+//!     config: scuffle_settings::LiveSettings<MyConfig>,
+//! }
+//!
+//! impl scuffle_bootstrap::global::Global for Global {
+//!     type Config = scuffle_settings::LiveSettings<MyConfig>;
+//!
+//!     async fn init(config: Self::Config) -> anyhow::Result<Arc<Self>> {
+//!         // Read the current settings with `config.get()`
+//!         Ok(Arc::new(Self { config }))
+//!     }
+//! }
+//!
+//! impl scuffle_settings::ReloadConfig for Global {
+//!     type Settings = MyConfig;
+//!
+//!     fn settings(&self) -> &scuffle_settings::LiveSettings<MyConfig> {
+//!         &self.config
+//!     }
+//! }
+//!
+//! // Add `scuffle_settings::ReloadSvc` to the services in `scuffle_bootstrap::main!`
+//! ```
 //!
 //! ## License
 //!
@@ -105,16 +188,33 @@
 #![deny(clippy::mod_module_files)]
 
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use config::FileStoredFormat;
 
 mod options;
+mod origin;
+#[cfg(feature = "reload")]
+mod reload;
+mod schema;
+#[cfg(feature = "secrets")]
+mod secrets;
 
 pub use options::*;
-
-#[derive(Debug, Clone, Copy)]
-struct FormatWrapper;
+pub use origin::*;
+#[cfg(feature = "reload")]
+pub use reload::*;
+pub use schema::*;
+#[cfg(feature = "secrets")]
+pub use secrets::*;
+
+#[derive(Debug, Clone, Default)]
+struct FormatWrapper {
+    /// The files that were rendered as templates, by uri.
+    templates: Arc<Mutex<BTreeSet<String>>>,
+}
 
 #[cfg(not(feature = "templates"))]
 fn template_text<'a>(
@@ -132,6 +232,8 @@ fn template_text<'a>(
     use minijinja::syntax::SyntaxConfig;
 
     let mut env = minijinja::Environment::new();
+    // Files without template syntax render to themselves, so templates can be told apart
+    env.set_keep_trailing_newline(true);
 
     env.add_global("env", std::env::vars().collect::<std::collections::HashMap<_, _>>());
     env.set_syntax(
@@ -174,7 +276,14 @@ impl config::Format for FormatWrapper {
         }
 
         for format in formats {
-            if let Ok(map) = format.parse(uri, template_text(text, &format)?.as_ref()) {
+            let rendered = template_text(text, &format)?;
+            if let Ok(map) = format.parse(uri, rendered.as_ref()) {
+                if let Some(uri) = uri
+                    && rendered != text
+                {
+                    self.templates.lock().expect("lock poisoned").insert(uri.clone());
+                }
+
                 return Ok(map);
             }
         }
@@ -213,23 +322,117 @@ pub enum SettingsError {
     /// An error occurred while parsing the settings.
     #[error(transparent)]
     Config(#[from] config::ConfigError),
+    /// A setting has a value that can't be deserialized.
+    #[error("invalid value for {key} from {origin}")]
+    Invalid {
+        /// The key of the setting.
+        key: String,
+        /// Where the value comes from.
+        origin: Origin,
+        /// The error deserializing the value.
+        #[source]
+        error: Box<config::ConfigError>,
+    },
+    /// Settings that are not part of the settings type were set, in [strict mode](Options::strict).
+    #[error("unknown settings: {}", .0.iter().map(|(key, origin)| format!("{key} from {origin}")).collect::<Vec<_>>().join(", "))]
+    UnknownKeys(Vec<(String, Origin)>),
+    /// A secret reference could not be resolved.
+    #[cfg(feature = "secrets")]
+    #[error("failed to resolve the secret for {key}")]
+    Secret {
+        /// The key of the setting.
+        key: String,
+        /// The error returned by the [`SecretProvider`].
+        #[source]
+        error: SecretError,
+    },
     /// An error occurred while parsing the CLI arguments.
     #[cfg(feature = "cli")]
     #[error(transparent)]
     Clap(#[from] clap::Error),
+    /// The reloaded settings change keys that require a restart.
+    #[cfg(feature = "reload")]
+    #[error("changing {} requires a restart", .0.join(", "))]
+    RestartRequired(Vec<String>),
 }
 
 /// Parse settings using the given options.
 ///
 /// Refer to the [`Options`] struct for more information on how to customize parsing.
 pub fn parse_settings<T: serde::de::DeserializeOwned>(options: Options) -> Result<T, SettingsError> {
-    let mut config = config::Config::builder();
+    parse_settings_with_origins(options).map(|(settings, _)| settings)
+}
+
+/// Parse settings using the given options, returning where every setting comes from.
+///
+/// Refer to the [`Options`] struct for more information on how to customize parsing.
+pub fn parse_settings_with_origins<T: serde::de::DeserializeOwned>(options: Options) -> Result<(T, Origins), SettingsError> {
+    let mut resolved = build_config::<T>(&options)?;
+    let settings = resolved.deserialize(options.strict)?;
+    Ok((settings, resolved.origins))
+}
+
+/// The settings collected from all sources.
+struct Resolved {
+    config: config::Config,
+    /// The config files the settings are read from.
+    #[cfg_attr(not(feature = "reload"), allow(dead_code))]
+    files: Vec<String>,
+    /// The files secrets are read from.
+    #[cfg_attr(not(feature = "reload"), allow(dead_code))]
+    secret_files: Vec<std::path::PathBuf>,
+    origins: Origins,
+}
+
+impl Resolved {
+    /// Deserializes the settings, citing the origin of the offending setting on error.
+    ///
+    /// The settings are moved out of the config, so resolved secrets are not left behind.
+    fn deserialize<T: serde::de::DeserializeOwned>(&mut self, strict: bool) -> Result<T, SettingsError> {
+        if strict {
+            let schema = Schema::of::<T>();
+            let unknown = self
+                .origins
+                .iter()
+                .filter(|(key, _)| !schema.allows(key))
+                .map(|(key, origin)| (key.to_owned(), origin.clone()))
+                .collect::<Vec<_>>();
+
+            if !unknown.is_empty() {
+                return Err(SettingsError::UnknownKeys(unknown));
+            }
+        }
+
+        std::mem::take(&mut self.config).try_deserialize().map_err(|error| {
+            let key = match &error {
+                config::ConfigError::Type { key: Some(key), .. } | config::ConfigError::At { key: Some(key), .. } => key,
+                _ => return error.into(),
+            };
+
+            match self.origins.get(key) {
+                Some(origin) => SettingsError::Invalid {
+                    key: key.clone(),
+                    origin: origin.clone(),
+                    error: Box::new(error),
+                },
+                None => error.into(),
+            }
+        })
+    }
+}
 
+/// Collects the settings from all sources.
+///
+/// `T` is the settings type, for `--print-schema` and `--print-config`.
+#[cfg_attr(not(feature = "cli"), allow(clippy::extra_unused_type_parameters))]
+fn build_config<T: serde::de::DeserializeOwned>(options: &Options) -> Result<Resolved, SettingsError> {
+    let mut config = config::Config::builder();
+    let format = FormatWrapper::default();
     #[allow(unused_mut)]
-    let mut added_files = false;
+    let mut files = Vec::new();
 
     #[cfg(feature = "cli")]
-    if let Some(cli) = options.cli {
+    if let Some(cli) = &options.cli {
         let command = clap::Command::new(cli.name)
             .version(cli.version)
             .about(cli.about)
@@ -250,14 +453,36 @@ pub fn parse_settings<T: serde::de::DeserializeOwned>(options: Options) -> Resul
                     .alias("set")
                     .help("Provide an override for a configuration value, in the format KEY=VALUE")
                     .action(clap::ArgAction::Append),
+            )
+            .arg(
+                clap::Arg::new("print-schema")
+                    .long("print-schema")
+                    .help("Print the JSON schema of the configuration and exit")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("print-config")
+                    .long("print-config")
+                    .help("Print a default configuration file and exit")
+                    .action(clap::ArgAction::SetTrue),
             );
 
-        let matches = command.get_matches_from(cli.argv);
+        let matches = command.get_matches_from(&cli.argv);
+
+        if matches.get_flag("print-schema") {
+            println!("{:#}", Schema::of::<T>().json_schema());
+            std::process::exit(0);
+        }
+
+        if matches.get_flag("print-config") {
+            print!("{}", Schema::of::<T>().default_config());
+            std::process::exit(0);
+        }
 
         if let Some(config_files) = matches.get_many::<String>("config") {
             for path in config_files {
-                config = config.add_source(config::File::new(path, FormatWrapper));
-                added_files = true;
+                config = config.add_source(config::File::new(path, format.clone()));
+                files.push(path.clone());
             }
         }
 
@@ -275,21 +500,49 @@ pub fn parse_settings<T: serde::de::DeserializeOwned>(options: Options) -> Resul
         }
     }
 
-    if !added_files && let Some(default_config_file) = options.default_config_file {
-        config = config.add_source(config::File::new(default_config_file, FormatWrapper).required(false));
+    let default_file = files.is_empty() && options.default_config_file.is_some();
+    if files.is_empty()
+        && let Some(default_config_file) = options.default_config_file
+    {
+        config = config.add_source(config::File::new(default_config_file, format.clone()).required(false));
+        files.push(default_config_file.to_owned());
     }
 
     if let Some(env_prefix) = options.env_prefix {
         config = config.add_source(config::Environment::with_prefix(env_prefix));
     }
 
-    Ok(config.build()?.try_deserialize()?)
+    #[allow(unused_mut)]
+    let mut config = config.build()?;
+    let values = origin::flatten(&config)?;
+    let templates = format.templates.lock().expect("lock poisoned");
+    let origins = Origins::collect(&values, default_file, &templates, options.env_prefix);
+    drop(templates);
+
+    #[cfg(feature = "secrets")]
+    let secret_files = options.secrets.resolve(&mut config)?;
+    #[cfg(not(feature = "secrets"))]
+    let secret_files = Vec::new();
+
+    Ok(Resolved {
+        config,
+        files,
+        secret_files,
+        origins,
+    })
 }
 
 #[doc(hidden)]
 #[cfg(feature = "bootstrap")]
 pub mod macros {
-    pub use {anyhow, scuffle_bootstrap};
+    pub use anyhow;
+    pub use scuffle_bootstrap;
+
+    /// The options settings are parsed with, implemented by [`bootstrap!`](crate::bootstrap).
+    pub trait BootstrapOptions {
+        /// Returns the options to parse the settings with.
+        fn options() -> crate::Options;
+    }
 }
 
 /// This macro can be used to integrate with the [`scuffle_bootstrap`] ecosystem.
@@ -309,13 +562,19 @@ pub mod macros {
 #[macro_export]
 macro_rules! bootstrap {
     ($ty:ty) => {
+        impl $crate::macros::BootstrapOptions for $ty {
+            fn options() -> $crate::Options {
+                $crate::Options {
+                    cli: Some($crate::cli!()),
+                    ..::std::default::Default::default()
+                }
+            }
+        }
+
         impl $crate::macros::scuffle_bootstrap::config::ConfigParser for $ty {
             async fn parse() -> $crate::macros::anyhow::Result<Self> {
                 $crate::macros::anyhow::Context::context(
-                    $crate::parse_settings($crate::Options {
-                        cli: Some($crate::cli!()),
-                        ..::std::default::Default::default()
-                    }),
+                    $crate::parse_settings(<$ty as $crate::macros::BootstrapOptions>::options()),
                     "config",
                 )
             }
@@ -335,8 +594,10 @@ mod tests {
 
     use serde_derive::Deserialize;
 
+    #[cfg(all(feature = "cli", any(feature = "toml", feature = "templates")))]
+    use crate::parse_settings_with_origins;
     #[cfg(feature = "cli")]
-    use crate::Cli;
+    use crate::{Cli, Origin, SettingsError};
     use crate::{Options, parse_settings};
 
     #[derive(Debug, Deserialize)]
@@ -521,8 +782,105 @@ mod tests {
         unsafe {
             std::env::set_var("SETTINGS_TEMPLATES_TEST", "templatevalue");
         }
-        let settings: TestSettings = parse_settings(options).expect("failed to parse settings");
+        let (settings, origins) = parse_settings_with_origins::<TestSettings>(options).expect("failed to parse settings");
 
         assert_eq!(settings.key, "templatevalue");
+        assert!(matches!(origins.get("key"), Some(Origin::Template(uri)) if uri.ends_with("templates.toml")));
+    }
+
+    #[test]
+    #[cfg(all(feature = "cli", feature = "toml"))]
+    fn origins() {
+        let options = Options {
+            cli: Some(Cli {
+                name: "test",
+                version: "0.1.0",
+                about: "test",
+                author: "test",
+                argv: vec![
+                    "test".to_string(),
+                    "-c".to_string(),
+                    file_path("test.toml").display().to_string(),
+                    "-o".to_string(),
+                    "other.nested=value".to_string(),
+                ],
+            }),
+            env_prefix: Some("SETTINGS_ORIGINS_TEST"),
+            ..Default::default()
+        };
+        // Safety: This is a test and we do not have multiple threads.
+        #[allow(unsafe_code)]
+        unsafe {
+            std::env::set_var("SETTINGS_ORIGINS_TEST_ENV", "envvalue");
+        }
+        let (_, origins) = parse_settings_with_origins::<TestSettings>(options).expect("failed to parse settings");
+
+        assert!(matches!(origins.get("key"), Some(Origin::File(uri)) if uri.ends_with("test.toml")));
+        assert_eq!(origins.get("other.nested"), Some(&Origin::Override));
+        assert_eq!(
+            origins.get("env"),
+            Some(&Origin::Env("SETTINGS_ORIGINS_TEST_ENV".to_string()))
+        );
+        assert_eq!(
+            origins.get("env").unwrap().to_string(),
+            "environment variable SETTINGS_ORIGINS_TEST_ENV"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn strict() {
+        let options = |strict| Options {
+            cli: Some(Cli {
+                name: "test",
+                version: "0.1.0",
+                about: "test",
+                author: "test",
+                argv: vec![
+                    "test".to_string(),
+                    "-o".to_string(),
+                    "key=value".to_string(),
+                    "-o".to_string(),
+                    "kye=typo".to_string(),
+                ],
+            }),
+            strict,
+            ..Default::default()
+        };
+
+        parse_settings::<TestSettings>(options(false)).expect("failed to parse settings");
+
+        let err = parse_settings::<TestSettings>(options(true)).expect_err("expected error");
+        assert!(matches!(&err, SettingsError::UnknownKeys(keys) if keys == &[("kye".to_string(), Origin::Override)]));
+        assert_eq!(err.to_string(), "unknown settings: kye from command line override");
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn invalid() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct LimitSettings {
+            limit: u32,
+        }
+
+        let options = Options {
+            cli: Some(Cli {
+                name: "test",
+                version: "0.1.0",
+                about: "test",
+                author: "test",
+                argv: vec!["test".to_string(), "-o".to_string(), "limit=ten".to_string()],
+            }),
+            ..Default::default()
+        };
+        let err = parse_settings::<LimitSettings>(options).expect_err("expected error");
+
+        assert!(matches!(&err, SettingsError::Invalid { key, origin: Origin::Override, .. } if key == "limit"));
+        assert_eq!(err.to_string(), "invalid value for limit from command line override");
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "invalid type: string \"ten\", expected an integer for key `limit`"
+        );
     }
 }
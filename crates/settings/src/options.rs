@@ -10,6 +10,15 @@ pub struct Options {
     ///
     /// A setting called `foo` would be read from the environment as `APP_FOO` where `APP` is the prefix.
     pub env_prefix: Option<&'static str>,
+    /// Reject settings that are not part of the settings type
+    ///
+    /// Misspelled keys are silently ignored otherwise. See [`SettingsError::UnknownKeys`](crate::SettingsError::UnknownKeys).
+    pub strict: bool,
+    /// The providers secret references in settings are resolved with
+    ///
+    /// See [`Secrets`](crate::Secrets) for the secret references that are resolved by default.
+    #[cfg(feature = "secrets")]
+    pub secrets: crate::Secrets,
 }
 
 impl Default for Options {
@@ -19,6 +28,9 @@ impl Default for Options {
             cli: None,
             default_config_file: Some("config"),
             env_prefix: Some("APP"),
+            strict: false,
+            #[cfg(feature = "secrets")]
+            secrets: crate::Secrets::default(),
         }
     }
 }
@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use config::Source;
+
+/// Where the value of a setting comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The [default config file](crate::Options::default_config_file).
+    DefaultFile(String),
+    /// A config file passed with `--config`.
+    File(String),
+    /// A config file that was rendered as a template.
+    Template(String),
+    /// An environment variable, by its name.
+    Env(String),
+    /// A `--override` passed on the command line.
+    Override,
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DefaultFile(path) => write!(f, "default config file {path}"),
+            Self::File(path) => write!(f, "config file {path}"),
+            Self::Template(path) => write!(f, "template {path}"),
+            Self::Env(name) => write!(f, "environment variable {name}"),
+            Self::Override => f.write_str("command line override"),
+        }
+    }
+}
+
+/// The origin of every resolved setting.
+///
+/// Keys are separated by dots, with array indices in brackets, like `servers[0].bind`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origins(BTreeMap<String, Origin>);
+
+impl Origins {
+    /// Returns where the setting at `key` comes from.
+    pub fn get(&self, key: &str) -> Option<&Origin> {
+        self.0.get(key)
+    }
+
+    /// Iterates over all resolved settings and their origins, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Origin)> {
+        self.0.iter().map(|(key, origin)| (key.as_str(), origin))
+    }
+
+    /// Finds the origins of the flattened settings.
+    pub(crate) fn collect(
+        values: &BTreeMap<String, config::Value>,
+        default_file: bool,
+        templates: &BTreeSet<String>,
+        env_prefix: Option<&str>,
+    ) -> Self {
+        Self(
+            values
+                .iter()
+                .map(|(key, value)| {
+                    let origin = match value.origin() {
+                        None => Origin::Override,
+                        // The name config gives all environment variables
+                        Some("the environment") => Origin::Env(match env_prefix {
+                            Some(prefix) => format!("{prefix}_{key}").to_uppercase(),
+                            None => key.to_uppercase(),
+                        }),
+                        Some(uri) if templates.contains(uri) => Origin::Template(uri.to_owned()),
+                        Some(uri) if default_file => Origin::DefaultFile(uri.to_owned()),
+                        Some(uri) => Origin::File(uri.to_owned()),
+                    };
+
+                    (key.clone(), origin)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Flattens the settings into a map from keys to the values that are not tables or arrays.
+pub(crate) fn flatten(config: &config::Config) -> Result<BTreeMap<String, config::Value>, config::ConfigError> {
+    fn insert(values: &mut BTreeMap<String, config::Value>, key: String, value: config::Value) {
+        match value.kind {
+            config::ValueKind::Table(table) => {
+                for (name, value) in table {
+                    let key = if key.is_empty() { name } else { format!("{key}.{name}") };
+                    insert(values, key, value);
+                }
+            }
+            config::ValueKind::Array(array) => {
+                for (index, value) in array.into_iter().enumerate() {
+                    insert(values, format!("{key}[{index}]"), value);
+                }
+            }
+            _ => {
+                values.insert(key, value);
+            }
+        }
+    }
+
+    let mut values = BTreeMap::new();
+    for (key, value) in config.collect()? {
+        insert(&mut values, key, value);
+    }
+
+    Ok(values)
+}
@@ -0,0 +1,452 @@
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use config::FileStoredFormat;
+
+use crate::{FormatWrapper, Options, SettingsError, build_config, origin};
+
+/// Settings that can be reloaded while the application is running.
+///
+/// All keys can be reloaded unless they are listed in
+/// [`RESTART_REQUIRED`](Reload::RESTART_REQUIRED).
+///
+/// ```rust
+/// #[derive(serde_derive::Deserialize)]
+/// struct MySettings {
+///     bind: String,
+///     log_level: String,
+/// }
+///
+/// impl scuffle_settings::Reload for MySettings {
+///     const RESTART_REQUIRED: &'static [&'static str] = &["bind"];
+/// }
+/// ```
+pub trait Reload: serde::de::DeserializeOwned + Send + Sync + 'static {
+    /// Keys that can only be changed by restarting the application.
+    ///
+    /// Nested keys are separated by dots, like `server.bind`, and listing a key
+    /// covers everything nested under it. A reload changing one of these keys is
+    /// rejected with [`SettingsError::RestartRequired`] and the current settings are kept.
+    const RESTART_REQUIRED: &'static [&'static str] = &[];
+}
+
+/// A handle to settings that are reloaded while the application is running.
+///
+/// The settings are parsed once by [`LiveSettings::new`] and then again by every
+/// [`reload`](LiveSettings::reload), which publishes them to all clones of the handle.
+/// [`watch`](LiveSettings::watch) reloads them when a config file changes or the
+/// process receives `SIGHUP`.
+///
+/// Code that only reads settings calls [`get`](LiveSettings::get) whenever it needs
+/// them, code that has to react to changes waits for [`changed`](LiveSettings::changed).
+pub struct LiveSettings<T> {
+    rx: tokio::sync::watch::Receiver<Arc<T>>,
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    options: Options,
+    files: Vec<String>,
+    /// The files the current secrets are read from.
+    secret_files: Mutex<Vec<PathBuf>>,
+    /// Hashes of the flattened values of the current settings, to find the keys a reload changes.
+    ///
+    /// Only hashes are kept, so resolved secrets don't stay around in memory.
+    values: Mutex<BTreeMap<String, u64>>,
+    tx: tokio::sync::watch::Sender<Arc<T>>,
+}
+
+impl<T> Clone for LiveSettings<T> {
+    fn clone(&self) -> Self {
+        Self {
+            rx: self.rx.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for LiveSettings<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiveSettings")
+            .field("options", &self.inner.options)
+            .field("files", &self.inner.files)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Reload> LiveSettings<T> {
+    /// Parses the settings using the given options.
+    ///
+    /// The same options are used for every reload, so command line overrides stay
+    /// in place while files and environment variables are read again.
+    pub fn new(options: Options) -> Result<Self, SettingsError> {
+        let mut resolved = build_config::<T>(&options)?;
+        let values = flatten(&resolved.config)?;
+        let (tx, rx) = tokio::sync::watch::channel(Arc::new(resolved.deserialize(options.strict)?));
+
+        Ok(Self {
+            rx,
+            inner: Arc::new(Inner {
+                options,
+                files: resolved.files,
+                secret_files: Mutex::new(resolved.secret_files),
+                values: Mutex::new(values),
+                tx,
+            }),
+        })
+    }
+
+    /// Returns the current settings.
+    pub fn get(&self) -> Arc<T> {
+        self.rx.borrow().clone()
+    }
+
+    /// Waits for the settings to change and returns the new settings.
+    pub async fn changed(&mut self) -> Arc<T> {
+        // The sender lives as long as the handle, so this can't fail
+        let _ = self.rx.changed().await;
+        self.rx.borrow_and_update().clone()
+    }
+
+    /// Returns a receiver that is notified whenever the settings change.
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<Arc<T>> {
+        self.rx.clone()
+    }
+
+    /// Parses the settings again and publishes them if anything changed.
+    ///
+    /// Secret references are resolved again, so rotated secrets are picked up as well.
+    /// Returns the keys that changed. On error, including when a
+    /// [restart is required](Reload::RESTART_REQUIRED), the current settings are kept.
+    pub fn reload(&self) -> Result<Vec<String>, SettingsError> {
+        let mut resolved = build_config::<T>(&self.inner.options)?;
+        let values = flatten(&resolved.config)?;
+        let settings = resolved.deserialize(self.inner.options.strict)?;
+
+        let mut current = self.inner.values.lock().expect("lock poisoned");
+        let changed = diff(&current, &values);
+
+        let restart_required = changed
+            .iter()
+            .filter(|key| requires_restart(key, T::RESTART_REQUIRED))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !restart_required.is_empty() {
+            return Err(SettingsError::RestartRequired(restart_required));
+        }
+
+        if !changed.is_empty() {
+            tracing::info!(changed = ?changed, "settings reloaded");
+            *current = values;
+            self.inner.tx.send_replace(Arc::new(settings));
+        }
+
+        *self.inner.secret_files.lock().expect("lock poisoned") = resolved.secret_files;
+
+        Ok(changed)
+    }
+
+    /// Reloads the settings whenever a config file changes or the process receives `SIGHUP`.
+    ///
+    /// Config files and the files secrets are read from are checked for changes every `interval`. Failed reloads are logged and
+    /// the current settings are kept. This future never completes, it is meant to be run
+    /// until the application shuts down.
+    pub async fn watch(&self, interval: Duration) {
+        #[cfg(unix)]
+        let mut signals = scuffle_signal::SignalHandler::with_signals([scuffle_signal::UnixSignalKind::hangup()]);
+        #[cfg(not(unix))]
+        let mut signals = scuffle_signal::SignalHandler::new();
+
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        let mut modified = self.modified();
+
+        loop {
+            tokio::select! {
+                _ = signals.recv() => {},
+                _ = interval.tick() => {
+                    let now = self.modified();
+                    if now == modified {
+                        continue;
+                    }
+
+                    modified = now;
+                },
+            }
+
+            if let Err(err) = self.reload() {
+                tracing::warn!(error = %err, "failed to reload settings, keeping the current settings");
+            }
+        }
+    }
+
+    /// The modification times of the config files and secret files.
+    ///
+    /// Config files may be given without an extension, so every extension the file could have is checked as well.
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        let secret_files = self.inner.secret_files.lock().expect("lock poisoned").clone();
+
+        self.inner
+            .files
+            .iter()
+            .flat_map(|file| {
+                std::iter::once(file.clone()).chain(
+                    FormatWrapper::default()
+                        .file_extensions()
+                        .iter()
+                        .map(move |extension| format!("{file}.{extension}")),
+                )
+            })
+            .map(PathBuf::from)
+            .chain(secret_files)
+            .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+}
+
+/// Flattens the settings into a map from dotted keys to hashes of their values.
+fn flatten(config: &config::Config) -> Result<BTreeMap<String, u64>, SettingsError> {
+    Ok(origin::flatten(config)?
+        .into_iter()
+        .map(|(key, value)| {
+            let mut hasher = DefaultHasher::new();
+            value.to_string().hash(&mut hasher);
+            (key, hasher.finish())
+        })
+        .collect())
+}
+
+/// Returns the keys that were added, removed or changed, in order.
+fn diff(old: &BTreeMap<String, u64>, new: &BTreeMap<String, u64>) -> Vec<String> {
+    let mut changed = new
+        .iter()
+        .filter(|(key, value)| old.get(*key) != Some(value))
+        .map(|(key, _)| key.clone())
+        .chain(old.keys().filter(|key| !new.contains_key(*key)).cloned())
+        .collect::<Vec<_>>();
+    changed.sort();
+    changed
+}
+
+/// Returns `true` if `key` is one of `restart_required` or nested under one of them.
+fn requires_restart(key: &str, restart_required: &[&str]) -> bool {
+    restart_required.iter().any(|prefix| {
+        key.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+    })
+}
+
+#[cfg(feature = "bootstrap")]
+mod bootstrap {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use scuffle_bootstrap::global::Global;
+    use scuffle_bootstrap::prelude::scuffle_context::ContextFutExt;
+    use scuffle_bootstrap::service::Service;
+
+    use super::{LiveSettings, Reload};
+    use crate::macros::BootstrapOptions;
+
+    impl<T: Reload + BootstrapOptions> scuffle_bootstrap::config::ConfigParser for LiveSettings<T> {
+        async fn parse() -> anyhow::Result<Self> {
+            anyhow::Context::context(LiveSettings::new(T::options()), "config")
+        }
+    }
+
+    /// A [`Service`] that reloads the settings of the global while the application is running.
+    ///
+    /// See [`LiveSettings::watch`].
+    #[derive(Default, Debug, Clone, Copy)]
+    pub struct ReloadSvc;
+
+    /// Configuration for the reload service.
+    pub trait ReloadConfig: Global {
+        /// The settings that are reloaded.
+        type Settings: Reload;
+
+        /// Returns the settings to reload, usually the config passed to [`Global::init`].
+        fn settings(&self) -> &LiveSettings<Self::Settings>;
+
+        /// How often config files are checked for changes.
+        ///
+        /// By default, every 5 seconds.
+        fn reload_interval(&self) -> Duration {
+            Duration::from_secs(5)
+        }
+    }
+
+    impl<Global: ReloadConfig> Service<Global> for ReloadSvc {
+        async fn run(
+            self,
+            global: Arc<Global>,
+            ctx: scuffle_bootstrap::prelude::scuffle_context::Context,
+        ) -> anyhow::Result<()> {
+            global.settings().watch(global.reload_interval()).with_context(&ctx).await;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "bootstrap")]
+pub use bootstrap::{ReloadConfig, ReloadSvc};
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use serde_derive::Deserialize;
+
+    use super::{LiveSettings, Reload, requires_restart};
+    use crate::{Options, SettingsError};
+
+    #[derive(Debug, Deserialize)]
+    struct TestSettings {
+        bind: String,
+        limit: u32,
+    }
+
+    impl Reload for TestSettings {
+        const RESTART_REQUIRED: &'static [&'static str] = &["bind"];
+    }
+
+    fn set_env(key: &str, value: &str) {
+        // Safety: Every test uses its own variables.
+        #[allow(unsafe_code)]
+        unsafe {
+            std::env::set_var(key, value);
+        }
+    }
+
+    #[tokio::test]
+    async fn reload() {
+        set_env("SETTINGS_RELOAD_TEST_BIND", "[::]:80");
+        set_env("SETTINGS_RELOAD_TEST_LIMIT", "10");
+
+        let settings = LiveSettings::<TestSettings>::new(Options {
+            #[cfg(feature = "cli")]
+            cli: None,
+            default_config_file: None,
+            env_prefix: Some("SETTINGS_RELOAD_TEST"),
+            strict: false,
+            #[cfg(feature = "secrets")]
+            secrets: Default::default(),
+        })
+        .expect("failed to parse settings");
+        let mut changes = settings.clone();
+        assert_eq!(settings.get().limit, 10);
+
+        assert!(settings.reload().unwrap().is_empty());
+
+        set_env("SETTINGS_RELOAD_TEST_LIMIT", "20");
+        assert_eq!(settings.reload().unwrap(), ["limit"]);
+        assert_eq!(changes.changed().await.limit, 20);
+
+        set_env("SETTINGS_RELOAD_TEST_BIND", "[::]:8080");
+        set_env("SETTINGS_RELOAD_TEST_LIMIT", "30");
+        let err = settings.reload().unwrap_err();
+        assert!(matches!(&err, SettingsError::RestartRequired(keys) if keys == &["bind"]));
+        assert_eq!(err.to_string(), "changing bind requires a restart");
+        assert_eq!(settings.get().limit, 20);
+        assert_eq!(settings.get().bind, "[::]:80");
+
+        set_env("SETTINGS_RELOAD_TEST_BIND", "[::]:80");
+        set_env("SETTINGS_RELOAD_TEST_LIMIT", "invalid");
+        let err = settings.reload().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for limit from environment variable SETTINGS_RELOAD_TEST_LIMIT"
+        );
+        assert_eq!(settings.get().limit, 20);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "toml")]
+    async fn watch() {
+        let dir = std::env::temp_dir().join(format!("scuffle-settings-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), "bind = \"[::]:80\"\nlimit = 10\n").unwrap();
+
+        // Without an extension, like the default config file
+        let file = dir.join("config").display().to_string();
+        let settings = LiveSettings::<TestSettings>::new(Options {
+            #[cfg(feature = "cli")]
+            cli: None,
+            default_config_file: Some(file.leak()),
+            env_prefix: None,
+            strict: false,
+            #[cfg(feature = "secrets")]
+            secrets: Default::default(),
+        })
+        .expect("failed to parse settings");
+        let mut changes = settings.clone();
+
+        let watch = tokio::spawn({
+            let settings = settings.clone();
+            async move { settings.watch(std::time::Duration::from_millis(10)).await }
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        std::fs::write(dir.join("config.toml"), "bind = \"[::]:80\"\nlimit = 20\n").unwrap();
+
+        let changed = tokio::time::timeout(std::time::Duration::from_secs(1), changes.changed())
+            .await
+            .expect("settings were not reloaded");
+        assert_eq!(changed.limit, 20);
+
+        watch.abort();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "secrets")]
+    fn secrets() {
+        #[derive(Debug, Deserialize)]
+        struct SecretSettings {
+            password: crate::Secret<String>,
+        }
+
+        impl Reload for SecretSettings {}
+
+        let dir = std::env::temp_dir().join(format!("scuffle-settings-reload-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("password");
+        std::fs::write(&path, "hunter2\n").unwrap();
+        set_env("SETTINGS_RELOAD_SECRETS_TEST_PASSWORD", &format!("file://{}", path.display()));
+
+        let settings = LiveSettings::<SecretSettings>::new(Options {
+            #[cfg(feature = "cli")]
+            cli: None,
+            default_config_file: None,
+            env_prefix: Some("SETTINGS_RELOAD_SECRETS_TEST"),
+            strict: false,
+            secrets: Default::default(),
+        })
+        .expect("failed to parse settings");
+        assert_eq!(settings.get().password.expose(), "hunter2");
+        assert_eq!(*settings.inner.secret_files.lock().unwrap(), std::slice::from_ref(&path));
+
+        assert!(settings.reload().unwrap().is_empty());
+
+        std::fs::write(&path, "hunter3\n").unwrap();
+        assert_eq!(settings.reload().unwrap(), ["password"]);
+        assert_eq!(settings.get().password.expose(), "hunter3");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restart_required() {
+        let restart_required = &["server.bind", "tls"];
+
+        assert!(requires_restart("server.bind", restart_required));
+        assert!(requires_restart("tls.cert", restart_required));
+        assert!(requires_restart("tls[0]", restart_required));
+        assert!(!requires_restart("server.binding", restart_required));
+        assert!(!requires_restart("server", restart_required));
+        assert!(!requires_restart("limits.tls", restart_required));
+    }
+}
@@ -0,0 +1,751 @@
+use std::fmt::Write;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+
+/// The structure of a settings type.
+///
+/// Traced from the [`Deserialize`](serde::Deserialize) implementation of the type by
+/// [`Schema::of`], so no extra derive is needed. Types that deserialize themselves
+/// from any value, like untagged enums, show up as [`Schema::Any`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Any value.
+    Any,
+    /// A boolean.
+    Bool,
+    /// An integer.
+    Integer,
+    /// A floating point number.
+    Float,
+    /// A string.
+    String,
+    /// A value that can be left out.
+    Optional(Box<Schema>),
+    /// A list of values.
+    Array(Box<Schema>),
+    /// A map from arbitrary keys to values.
+    Map(Box<Schema>),
+    /// A struct with named fields.
+    Struct {
+        /// The name of the struct.
+        name: &'static str,
+        /// The fields of the struct.
+        fields: Vec<Field>,
+    },
+    /// An enum, by the names of its variants.
+    Enum {
+        /// The name of the enum.
+        name: &'static str,
+        /// The names of the variants.
+        variants: &'static [&'static str],
+    },
+}
+
+/// A field of a [`Schema::Struct`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// The name of the field, after renaming.
+    pub name: &'static str,
+    /// Whether the field has to be set, because it has no default.
+    pub required: bool,
+    /// The schema of the field.
+    pub schema: Schema,
+}
+
+impl Schema {
+    /// Traces the schema of `T`.
+    ///
+    /// `T` is deserialized from sample values. If it rejects one, like a socket address parsed
+    /// from a string, the fields that come after it are traced as [`Schema::Any`].
+    pub fn of<T: serde::de::DeserializeOwned>() -> Self {
+        let mut schema = Schema::Any;
+        // Deserializing the sample values can fail, everything traced until then is kept
+        let _ = T::deserialize(Tracer::new(&mut schema, None));
+
+        let mut paths = Vec::new();
+        schema.field_paths(&mut Vec::new(), &mut paths);
+
+        // A field is required if leaving it out fails with a missing field error
+        for path in paths {
+            let mut scratch = Schema::Any;
+            let required = matches!(
+                T::deserialize(Tracer::new(&mut scratch, Some(&path))),
+                Err(TraceError::MissingField(field)) if path.last() == Some(&field)
+            );
+
+            if let Some(field) = schema.field_mut(&path) {
+                field.required = required;
+            }
+        }
+
+        schema
+    }
+
+    /// Returns `true` if `key` is a setting of this schema.
+    ///
+    /// Keys are separated by dots, with array indices in brackets, like `servers[0].bind`.
+    pub fn allows(&self, key: &str) -> bool {
+        let segments = key
+            .split('.')
+            .flat_map(|segment| {
+                let name = segment.split('[').next().unwrap_or_default();
+                std::iter::once(name).chain(std::iter::repeat_n("[]", segment.matches('[').count()))
+            })
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+
+        self.allows_segments(&segments)
+    }
+
+    fn allows_segments(&self, segments: &[&str]) -> bool {
+        let Some((first, rest)) = segments.split_first() else {
+            return true;
+        };
+
+        match self {
+            Self::Any | Self::Enum { .. } => true,
+            Self::Optional(schema) => schema.allows_segments(segments),
+            Self::Struct { fields, .. } => fields
+                .iter()
+                .find(|field| field.name == *first)
+                .is_some_and(|field| field.schema.allows_segments(rest)),
+            Self::Map(schema) => schema.allows_segments(rest),
+            Self::Array(schema) => *first == "[]" && schema.allows_segments(rest),
+            Self::Bool | Self::Integer | Self::Float | Self::String => false,
+        }
+    }
+
+    /// Returns the schema as a [JSON Schema](https://json-schema.org).
+    pub fn json_schema(&self) -> serde_json::Value {
+        let mut schema = self.json();
+        if let Some(object) = schema.as_object_mut() {
+            object.insert("$schema".into(), "https://json-schema.org/draft/2020-12/schema".into());
+            if let Self::Struct { name, .. } = self {
+                object.insert("title".into(), (*name).into());
+            }
+        }
+
+        schema
+    }
+
+    fn json(&self) -> serde_json::Value {
+        match self {
+            Self::Any => serde_json::json!({}),
+            Self::Bool => serde_json::json!({ "type": "boolean" }),
+            Self::Integer => serde_json::json!({ "type": "integer" }),
+            Self::Float => serde_json::json!({ "type": "number" }),
+            Self::String => serde_json::json!({ "type": "string" }),
+            Self::Optional(schema) => schema.json(),
+            Self::Array(schema) => serde_json::json!({ "type": "array", "items": schema.json() }),
+            Self::Map(schema) => serde_json::json!({ "type": "object", "additionalProperties": schema.json() }),
+            Self::Struct { fields, .. } => serde_json::json!({
+                "type": "object",
+                "properties": fields
+                    .iter()
+                    .map(|field| (field.name.to_owned(), field.schema.json()))
+                    .collect::<serde_json::Map<_, _>>(),
+                "required": fields.iter().filter(|field| field.required).map(|field| field.name).collect::<Vec<_>>(),
+                "additionalProperties": false,
+            }),
+            Self::Enum { variants, .. } => serde_json::json!({ "enum": variants }),
+        }
+    }
+
+    /// Returns a commented TOML config file with every setting of this schema.
+    ///
+    /// Required settings are set to a placeholder, settings with a default are commented out.
+    pub fn default_config(&self) -> String {
+        let mut config = String::new();
+        if let Self::Struct { fields, .. } = self.required() {
+            write_table(&mut config, &mut Vec::new(), fields, false);
+        }
+
+        config
+    }
+
+    /// The schema without [`Schema::Optional`].
+    fn required(&self) -> &Self {
+        match self {
+            Self::Optional(schema) => schema.required(),
+            schema => schema,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Any => "any value".into(),
+            Self::Bool => "boolean".into(),
+            Self::Integer => "integer".into(),
+            Self::Float => "float".into(),
+            Self::String => "string".into(),
+            Self::Optional(schema) => schema.describe(),
+            Self::Array(schema) => format!("array of {}", schema.describe()),
+            Self::Map(schema) => format!("map of {}", schema.describe()),
+            Self::Struct { .. } => "table".into(),
+            Self::Enum { variants, .. } => format!(
+                "one of {}",
+                variants
+                    .iter()
+                    .map(|variant| format!("{variant:?}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    fn placeholder(&self) -> String {
+        match self {
+            Self::Any | Self::String => "\"\"".into(),
+            Self::Bool => "false".into(),
+            Self::Integer => "0".into(),
+            Self::Float => "0.0".into(),
+            Self::Optional(schema) => schema.placeholder(),
+            Self::Array(_) => "[]".into(),
+            Self::Map(_) | Self::Struct { .. } => "{}".into(),
+            Self::Enum { variants, .. } => variants
+                .first()
+                .map_or_else(|| "\"\"".into(), |variant| format!("{variant:?}")),
+        }
+    }
+
+    /// Collects the paths of all struct fields, in the segments used by [`Tracer`].
+    fn field_paths(&self, path: &mut Vec<&'static str>, paths: &mut Vec<Vec<&'static str>>) {
+        match self {
+            Self::Optional(schema) => schema.field_paths(path, paths),
+            Self::Array(schema) | Self::Map(schema) => {
+                path.push(if matches!(self, Self::Array(_)) { "[]" } else { "*" });
+                schema.field_paths(path, paths);
+                path.pop();
+            }
+            Self::Struct { fields, .. } => {
+                for field in fields {
+                    path.push(field.name);
+                    paths.push(path.clone());
+                    field.schema.field_paths(path, paths);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn field_mut(&mut self, path: &[&'static str]) -> Option<&mut Field> {
+        let (first, rest) = path.split_first()?;
+        match self {
+            Self::Optional(schema) => schema.field_mut(path),
+            Self::Array(schema) | Self::Map(schema) => schema.field_mut(rest),
+            Self::Struct { fields, .. } => {
+                let field = fields.iter_mut().find(|field| field.name == *first)?;
+                if rest.is_empty() {
+                    Some(field)
+                } else {
+                    field.schema.field_mut(rest)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn write_table(config: &mut String, path: &mut Vec<&'static str>, fields: &[Field], commented: bool) {
+    let is_table = |field: &&Field| matches!(field.schema.required(), Schema::Struct { .. });
+
+    for field in fields.iter().filter(|field| !is_table(field)) {
+        let prefix = if commented || !field.required { "# " } else { "" };
+        let requirement = if field.required { "required" } else { "optional" };
+        let _ = writeln!(config, "# {}: {}, {requirement}", field.name, field.schema.describe());
+        let _ = writeln!(config, "{prefix}{} = {}", toml_key(field.name), field.schema.placeholder());
+    }
+
+    for field in fields.iter().filter(is_table) {
+        let Schema::Struct { fields, .. } = field.schema.required() else {
+            continue;
+        };
+
+        let commented = commented || !field.required;
+        path.push(field.name);
+
+        if !config.is_empty() {
+            config.push('\n');
+        }
+        let prefix = if commented { "# " } else { "" };
+        let header = path.iter().map(|name| toml_key(name)).collect::<Vec<_>>().join(".");
+        let _ = writeln!(config, "{prefix}[{header}]");
+
+        write_table(config, path, fields, commented);
+        path.pop();
+    }
+}
+
+/// Quotes keys that are not valid bare TOML keys.
+fn toml_key(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        name.to_owned()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+#[derive(Debug)]
+enum TraceError {
+    MissingField(&'static str),
+    Custom,
+}
+
+impl std::fmt::Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "missing field `{field}`"),
+            Self::Custom => f.write_str("invalid sample value"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: std::fmt::Display>(_: T) -> Self {
+        Self::Custom
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Self::MissingField(field)
+    }
+}
+
+/// A deserializer that records the schema of whatever is deserialized from it, feeding it sample values.
+///
+/// Every field is recorded under its path, struct fields by name, array elements as `[]` and
+/// map values as `*`. The field at `omit` is left out, to find out whether it is required.
+struct Tracer<'s, 'o> {
+    schema: &'s mut Schema,
+    path: Vec<&'static str>,
+    omit: Option<&'o [&'static str]>,
+}
+
+impl<'s, 'o> Tracer<'s, 'o> {
+    fn new(schema: &'s mut Schema, omit: Option<&'o [&'static str]>) -> Self {
+        Self {
+            schema,
+            path: Vec::new(),
+            omit,
+        }
+    }
+
+    fn child<'c>(&self, schema: &'c mut Schema, segment: &'static str) -> Tracer<'c, 'o> {
+        let mut path = self.path.clone();
+        path.push(segment);
+        Tracer {
+            schema,
+            path,
+            omit: self.omit,
+        }
+    }
+}
+
+macro_rules! trace {
+    ($($method:ident => $schema:ident, $visit:ident($($value:expr)?);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                *self.schema = Schema::$schema;
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Tracer<'_, '_> {
+    type Error = TraceError;
+
+    trace! {
+        deserialize_any => Any, visit_unit();
+        deserialize_bool => Bool, visit_bool(false);
+        deserialize_i8 => Integer, visit_i64(1);
+        deserialize_i16 => Integer, visit_i64(1);
+        deserialize_i32 => Integer, visit_i64(1);
+        deserialize_i64 => Integer, visit_i64(1);
+        deserialize_i128 => Integer, visit_i128(1);
+        deserialize_u8 => Integer, visit_u64(1);
+        deserialize_u16 => Integer, visit_u64(1);
+        deserialize_u32 => Integer, visit_u64(1);
+        deserialize_u64 => Integer, visit_u64(1);
+        deserialize_u128 => Integer, visit_u128(1);
+        deserialize_f32 => Float, visit_f64(1.0);
+        deserialize_f64 => Float, visit_f64(1.0);
+        deserialize_char => String, visit_char('a');
+        deserialize_str => String, visit_str("");
+        deserialize_string => String, visit_str("");
+        deserialize_bytes => String, visit_bytes(&[]);
+        deserialize_byte_buf => String, visit_bytes(&[]);
+        deserialize_unit => Any, visit_unit();
+        deserialize_identifier => String, visit_str("");
+        deserialize_ignored_any => Any, visit_unit();
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut inner = Schema::Any;
+        let result = visitor.visit_some(Tracer {
+            schema: &mut inner,
+            path: self.path,
+            omit: self.omit,
+        });
+        *self.schema = Schema::Optional(Box::new(inner));
+        result
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut inner = Schema::Any;
+        let result = visitor.visit_seq(SeqTracer {
+            tracer: self.child(&mut inner, "[]"),
+            remaining: 1,
+        });
+        *self.schema = Schema::Array(Box::new(inner));
+        result
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        // Elements of tuples have different types, only the array itself is recorded
+        let mut scratch = Schema::Any;
+        let result = visitor.visit_seq(SeqTracer {
+            tracer: self.child(&mut scratch, "[]"),
+            remaining: len,
+        });
+        *self.schema = Schema::Array(Box::new(Schema::Any));
+        result
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut inner = Schema::Any;
+        let result = visitor.visit_map(MapTracer {
+            tracer: self.child(&mut inner, "*"),
+            done: false,
+        });
+        *self.schema = Schema::Map(Box::new(inner));
+        result
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.schema = Schema::Struct {
+            name,
+            fields: fields
+                .iter()
+                .map(|name| Field {
+                    name,
+                    required: false,
+                    schema: Schema::Any,
+                })
+                .collect(),
+        };
+        let Schema::Struct { fields: traced, .. } = self.schema else {
+            unreachable!()
+        };
+
+        visitor.visit_map(StructTracer {
+            fields: traced,
+            names: fields,
+            next: 0,
+            path: self.path,
+            omit: self.omit,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.schema = Schema::Enum { name, variants };
+        let variant = variants.first().ok_or(TraceError::Custom)?;
+        visitor.visit_enum(EnumTracer { variant })
+    }
+}
+
+struct SeqTracer<'s, 'o> {
+    tracer: Tracer<'s, 'o>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqTracer<'_, '_> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(Tracer {
+            schema: &mut *self.tracer.schema,
+            path: self.tracer.path.clone(),
+            omit: self.tracer.omit,
+        })
+        .map(Some)
+    }
+}
+
+struct MapTracer<'s, 'o> {
+    tracer: Tracer<'s, 'o>,
+    done: bool,
+}
+
+impl<'de> de::MapAccess<'de> for MapTracer<'_, '_> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        if self.done {
+            return Ok(None);
+        }
+
+        seed.deserialize("key".into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        self.done = true;
+        seed.deserialize(Tracer {
+            schema: &mut *self.tracer.schema,
+            path: self.tracer.path.clone(),
+            omit: self.tracer.omit,
+        })
+    }
+}
+
+struct StructTracer<'s, 'o> {
+    fields: &'s mut Vec<Field>,
+    names: &'static [&'static str],
+    next: usize,
+    path: Vec<&'static str>,
+    omit: Option<&'o [&'static str]>,
+}
+
+impl StructTracer<'_, '_> {
+    fn omitted(&self, name: &str) -> bool {
+        self.omit.is_some_and(|omit| {
+            omit.split_last()
+                .is_some_and(|(last, parent)| *last == name && parent == self.path.as_slice())
+        })
+    }
+}
+
+impl<'de> de::MapAccess<'de> for StructTracer<'_, '_> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        while let Some(name) = self.names.get(self.next) {
+            self.next += 1;
+            if !self.omitted(name) {
+                return seed.deserialize((*name).into_deserializer()).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let index = self.next - 1;
+        let mut path = self.path.clone();
+        path.push(self.names[index]);
+
+        seed.deserialize(Tracer {
+            schema: &mut self.fields[index].schema,
+            path,
+            omit: self.omit,
+        })
+    }
+}
+
+struct EnumTracer {
+    variant: &'static str,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumTracer {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error> {
+        Ok((seed.deserialize(self.variant.into_deserializer())?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumTracer {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Self::Error> {
+        seed.deserialize(Tracer::new(&mut Schema::Any, None))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_tuple(Tracer::new(&mut Schema::Any, None), len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_struct(Tracer::new(&mut Schema::Any, None), self.variant, fields, visitor)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_derive::Deserialize;
+
+    use super::Schema;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Server {
+        bind: String,
+        #[serde(default)]
+        workers: u32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct TestSettings {
+        server: Server,
+        level: Level,
+        #[serde(default)]
+        ratio: f64,
+        tls: Option<Server>,
+        #[serde(rename = "allowed-origins", default)]
+        allowed_origins: Vec<String>,
+        #[serde(default)]
+        limits: HashMap<String, u64>,
+    }
+
+    #[test]
+    fn trace() {
+        let schema = Schema::of::<TestSettings>();
+
+        assert!(schema.allows("server.bind"));
+        assert!(schema.allows("tls.workers"));
+        assert!(schema.allows("allowed-origins[3]"));
+        assert!(schema.allows("limits.anything"));
+        assert!(!schema.allows("server.port"));
+        assert!(!schema.allows("ratio.value"));
+        assert!(!schema.allows("allowed_origins"));
+
+        insta::assert_snapshot!(serde_json::to_string_pretty(&schema.json_schema()).unwrap(), @r#"
+        {
+          "$schema": "https://json-schema.org/draft/2020-12/schema",
+          "additionalProperties": false,
+          "properties": {
+            "allowed-origins": {
+              "items": {
+                "type": "string"
+              },
+              "type": "array"
+            },
+            "level": {
+              "enum": [
+                "Debug",
+                "Info"
+              ]
+            },
+            "limits": {
+              "additionalProperties": {
+                "type": "integer"
+              },
+              "type": "object"
+            },
+            "ratio": {
+              "type": "number"
+            },
+            "server": {
+              "additionalProperties": false,
+              "properties": {
+                "bind": {
+                  "type": "string"
+                },
+                "workers": {
+                  "type": "integer"
+                }
+              },
+              "required": [
+                "bind"
+              ],
+              "type": "object"
+            },
+            "tls": {
+              "additionalProperties": false,
+              "properties": {
+                "bind": {
+                  "type": "string"
+                },
+                "workers": {
+                  "type": "integer"
+                }
+              },
+              "required": [
+                "bind"
+              ],
+              "type": "object"
+            }
+          },
+          "required": [
+            "server",
+            "level"
+          ],
+          "title": "TestSettings",
+          "type": "object"
+        }
+        "#);
+
+        insta::assert_snapshot!(schema.default_config(), @r#"
+        # level: one of "Debug", "Info", required
+        level = "Debug"
+        # ratio: float, optional
+        # ratio = 0.0
+        # allowed-origins: array of string, optional
+        # allowed-origins = []
+        # limits: map of integer, optional
+        # limits = {}
+
+        [server]
+        # bind: string, required
+        bind = ""
+        # workers: integer, optional
+        # workers = 0
+
+        # [tls]
+        # bind: string, required
+        # bind = ""
+        # workers: integer, optional
+        # workers = 0
+        "#);
+    }
+}
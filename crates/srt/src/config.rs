@@ -0,0 +1,77 @@
+//! Socket configuration.
+
+use std::fmt;
+use std::time::Duration;
+
+/// The configuration of a SRT listener or caller.
+#[derive(Clone)]
+pub struct SrtConfig {
+    /// The latency used for timestamp based packet delivery.
+    ///
+    /// Each side proposes a latency in the handshake and the larger of the two
+    /// is used. Packets are delivered to the application exactly this long
+    /// after they were sent, which leaves time for retransmissions.
+    pub latency: Duration,
+    /// The passphrase used to encrypt the payload, between 10 and 79 bytes.
+    ///
+    /// A listener with a passphrase rejects callers without one and vice versa.
+    pub passphrase: Option<String>,
+    /// The length of the generated encryption key in bytes (16, 24 or 32).
+    ///
+    /// Only used by the caller, the listener uses the key length announced by
+    /// the caller.
+    pub key_length: usize,
+    /// The stream id sent by the caller.
+    pub stream_id: Option<String>,
+    /// The maximum transmission unit in bytes, including the IP and UDP
+    /// headers.
+    pub mtu: u32,
+    /// The maximum number of packets in flight.
+    pub flow_window: u32,
+    /// The maximum payload size of a data packet.
+    ///
+    /// Defaults to `1316`, which is seven MPEG-TS packets.
+    pub payload_size: usize,
+    /// Drop packets that are still missing when a later packet is due for
+    /// delivery, instead of stalling the stream.
+    pub too_late_packet_drop: bool,
+    /// The connection is closed if nothing is received from the peer for this
+    /// long.
+    pub peer_idle_timeout: Duration,
+    /// The maximum duration of the handshake.
+    pub connect_timeout: Duration,
+}
+
+impl Default for SrtConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(120),
+            passphrase: None,
+            key_length: 16,
+            stream_id: None,
+            mtu: 1500,
+            flow_window: 8192,
+            payload_size: 1316,
+            too_late_packet_drop: true,
+            peer_idle_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(3),
+        }
+    }
+}
+
+impl fmt::Debug for SrtConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SrtConfig")
+            .field("latency", &self.latency)
+            .field("passphrase", &self.passphrase.as_ref().map(|_| "<redacted>"))
+            .field("key_length", &self.key_length)
+            .field("stream_id", &self.stream_id)
+            .field("mtu", &self.mtu)
+            .field("flow_window", &self.flow_window)
+            .field("payload_size", &self.payload_size)
+            .field("too_late_packet_drop", &self.too_late_packet_drop)
+            .field("peer_idle_timeout", &self.peer_idle_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .finish()
+    }
+}
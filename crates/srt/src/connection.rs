@@ -0,0 +1,878 @@
+//! The connection state machine.
+//!
+//! [`Connection`] implements the data transfer part of SRT (ARQ, timestamp
+//! based packet delivery and encryption) without performing any IO itself.
+//! The caller feeds it received packets and the current time and polls it for
+//! packets to transmit and payloads to deliver.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use crate::crypto::Crypto;
+use crate::packet::{Ack, AckInfo, ControlKind, ControlPacket, DataPacket, KeyIndex, Packet, PacketPosition};
+use crate::seq::SeqNumber;
+use crate::socket::Statistics;
+
+/// The interval of the periodic timer driving acks, loss reports,
+/// keep-alives and the idle timeout.
+const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The interval of keep-alive packets when nothing else is sent.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An unchanged ack is repeated after this long, in case the previous one was
+/// lost.
+const ACK_REPEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The minimum interval of periodic loss reports.
+const MIN_NAK_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The maximum number of loss ranges in a single loss report.
+const MAX_NAK_RANGES: usize = 256;
+
+/// The number of unacknowledged acks that are remembered for RTT measurement.
+const MAX_ACK_HISTORY: usize = 64;
+
+/// The sender keeps unacknowledged packets for this long on top of the
+/// latency before dropping them.
+const SEND_DROP_DELAY: Duration = Duration::from_secs(1);
+
+/// The minimum time without acks after which the last packet is
+/// retransmitted.
+const MIN_TAIL_PROBE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// The initial round trip time estimate.
+const INITIAL_RTT: Duration = Duration::from_millis(100);
+
+const MESSAGE_NUMBER_MASK: u32 = 0x03FF_FFFF;
+
+/// The parameters negotiated in the handshake.
+pub(crate) struct ConnectionParams {
+    pub(crate) local_socket_id: u32,
+    pub(crate) peer_socket_id: u32,
+    pub(crate) initial_seq: SeqNumber,
+    pub(crate) flow_window: u32,
+    pub(crate) payload_size: usize,
+    pub(crate) recv_latency: Duration,
+    pub(crate) send_latency: Duration,
+    pub(crate) too_late_packet_drop: bool,
+    pub(crate) peer_idle_timeout: Duration,
+    pub(crate) crypto: Option<Crypto>,
+    /// The timestamps of outgoing packets are relative to this instant.
+    pub(crate) start: Instant,
+    /// The local instant corresponding to a peer timestamp of `0`.
+    pub(crate) peer_time_base: Instant,
+}
+
+/// Why a connection was closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CloseReason {
+    /// The connection was closed locally.
+    Local,
+    /// The peer sent a shutdown.
+    Peer,
+    /// Nothing was received from the peer for too long.
+    Timeout,
+}
+
+#[derive(Debug)]
+struct SentPacket {
+    seq: SeqNumber,
+    message_number: u32,
+    timestamp: u32,
+    key: KeyIndex,
+    payload: Bytes,
+    scheduled_at: Instant,
+    /// The packet is waiting in the retransmission queue.
+    queued: bool,
+    /// The packet was reported lost at least once.
+    lost: bool,
+}
+
+#[derive(Debug)]
+enum Slot {
+    Empty,
+    Dropped,
+    Packet {
+        timestamp: u64,
+        payload: Bytes,
+    },
+}
+
+/// Extends the 32-bit microsecond timestamps, which wrap around after about
+/// 71 minutes.
+#[derive(Debug, Default)]
+struct TimestampUnwrapper {
+    last: Option<u64>,
+}
+
+impl TimestampUnwrapper {
+    fn unwrap(&mut self, timestamp: u32) -> u64 {
+        const WRAP: u64 = 1 << 32;
+        const HALF: u64 = 1 << 31;
+
+        let Some(last) = self.last else {
+            self.last = Some(u64::from(timestamp));
+            return u64::from(timestamp);
+        };
+
+        let mut extended = (last & !(WRAP - 1)) | u64::from(timestamp);
+        if extended + HALF < last {
+            extended += WRAP;
+        } else if extended > last + HALF && extended >= WRAP {
+            extended -= WRAP;
+        }
+
+        self.last = Some(last.max(extended));
+        extended
+    }
+}
+
+/// The state of an established connection.
+pub(crate) struct Connection {
+    params: ConnectionParams,
+    closed: Option<CloseReason>,
+    stats: Statistics,
+
+    control: VecDeque<ControlKind>,
+    last_sent: Instant,
+    last_received: Instant,
+    next_tick: Instant,
+    rtt: Duration,
+    rtt_variance: Duration,
+    rtt_measured: bool,
+
+    send_next_seq: SeqNumber,
+    send_next_message: u32,
+    send_buffer: VecDeque<SentPacket>,
+    send_new: VecDeque<SeqNumber>,
+    send_retransmit: VecDeque<SeqNumber>,
+    /// The last time the send buffer made progress, either through a new
+    /// packet, an ack or a tail probe.
+    send_progress: Instant,
+
+    recv_base: SeqNumber,
+    recv_slots: VecDeque<Slot>,
+    recv_timestamps: TimestampUnwrapper,
+    recv_any: bool,
+    ack_number: u32,
+    last_ack_seq: Option<SeqNumber>,
+    last_ack_time: Instant,
+    ack_history: VecDeque<(u32, Instant)>,
+    last_nak_time: Instant,
+}
+
+impl Connection {
+    pub(crate) fn new(params: ConnectionParams, now: Instant) -> Self {
+        Self {
+            closed: None,
+            stats: Statistics::default(),
+            control: VecDeque::new(),
+            last_sent: now,
+            last_received: now,
+            next_tick: now + TICK_INTERVAL,
+            rtt: INITIAL_RTT,
+            rtt_variance: INITIAL_RTT / 2,
+            rtt_measured: false,
+            send_next_seq: params.initial_seq,
+            send_next_message: 1,
+            send_buffer: VecDeque::new(),
+            send_new: VecDeque::new(),
+            send_retransmit: VecDeque::new(),
+            send_progress: now,
+            recv_base: params.initial_seq,
+            recv_slots: VecDeque::new(),
+            recv_timestamps: TimestampUnwrapper::default(),
+            recv_any: false,
+            ack_number: 0,
+            last_ack_seq: None,
+            last_ack_time: now,
+            ack_history: VecDeque::new(),
+            last_nak_time: now,
+            params,
+        }
+    }
+
+    pub(crate) fn local_socket_id(&self) -> u32 {
+        self.params.local_socket_id
+    }
+
+    pub(crate) fn closed(&self) -> Option<CloseReason> {
+        self.closed
+    }
+
+    pub(crate) fn stats(&self) -> Statistics {
+        Statistics {
+            rtt: self.rtt,
+            ..self.stats
+        }
+    }
+
+    /// Queues a payload for sending, splitting it into packets of at most the
+    /// configured payload size.
+    pub(crate) fn send(&mut self, now: Instant, data: Bytes) {
+        if self.closed.is_some() {
+            return;
+        }
+
+        let timestamp = self.timestamp(now);
+
+        for offset in (0..data.len()).step_by(self.params.payload_size.max(1)) {
+            let chunk = data.slice(offset..(offset + self.params.payload_size).min(data.len()));
+
+            let seq = self.send_next_seq;
+            self.send_next_seq = seq + 1;
+
+            let message_number = self.send_next_message;
+            self.send_next_message = (message_number + 1) & MESSAGE_NUMBER_MASK;
+            if self.send_next_message == 0 {
+                self.send_next_message = 1;
+            }
+
+            let (key, payload) = match &self.params.crypto {
+                Some(crypto) => crypto.encrypt(seq, &chunk),
+                None => (KeyIndex::Unencrypted, chunk),
+            };
+
+            if self.send_buffer.len() >= self.params.flow_window as usize {
+                self.send_buffer.pop_front();
+                self.stats.packets_dropped += 1;
+            }
+
+            self.send_buffer.push_back(SentPacket {
+                seq,
+                message_number,
+                timestamp,
+                key,
+                payload,
+                scheduled_at: now,
+                queued: false,
+                lost: false,
+            });
+            self.send_new.push_back(seq);
+        }
+    }
+
+    /// Closes the connection and queues a shutdown packet.
+    pub(crate) fn close(&mut self) {
+        if self.closed.is_none() {
+            self.closed = Some(CloseReason::Local);
+            self.control.clear();
+            self.control.push_back(ControlKind::Shutdown);
+        }
+    }
+
+    pub(crate) fn handle_packet(&mut self, now: Instant, packet: Packet) {
+        if self.closed.is_some() {
+            return;
+        }
+
+        self.last_received = now;
+
+        match packet {
+            Packet::Data(packet) => self.handle_data(packet),
+            Packet::Control(packet) => match packet.kind {
+                ControlKind::Ack(ack) => self.handle_ack(now, ack),
+                ControlKind::Nak(ranges) => self.handle_nak(&ranges),
+                ControlKind::AckAck(ack_number) => self.handle_ack_ack(now, ack_number),
+                ControlKind::DropRequest { first, last, .. } => self.handle_drop_request(first, last),
+                ControlKind::Shutdown => self.closed = Some(CloseReason::Peer),
+                // Duplicate handshakes are answered by the listener.
+                ControlKind::Handshake(_) | ControlKind::KeepAlive => {}
+                ControlKind::Unknown { control_type, .. } => {
+                    tracing::trace!(control_type = ?control_type, "ignoring unknown control packet");
+                }
+            },
+        }
+    }
+
+    /// Runs the periodic timer if it is due.
+    pub(crate) fn handle_timeout(&mut self, now: Instant) {
+        if self.closed.is_some() || now < self.next_tick {
+            return;
+        }
+
+        self.next_tick = now + TICK_INTERVAL;
+
+        if now.duration_since(self.last_received) >= self.params.peer_idle_timeout {
+            self.closed = Some(CloseReason::Timeout);
+            return;
+        }
+
+        self.send_ack(now);
+        self.send_nak(now);
+        self.drop_expired(now);
+        self.probe_tail(now);
+
+        if now.duration_since(self.last_sent) >= KEEPALIVE_INTERVAL && self.control.is_empty() {
+            self.control.push_back(ControlKind::KeepAlive);
+        }
+    }
+
+    /// Returns the instant at which [`Connection::handle_timeout`] or
+    /// [`Connection::poll_deliver`] has to be called next.
+    pub(crate) fn next_timeout(&self) -> Instant {
+        match self.next_delivery() {
+            Some(delivery) => delivery.min(self.next_tick),
+            None => self.next_tick,
+        }
+    }
+
+    /// Returns the next packet to transmit.
+    pub(crate) fn poll_transmit(&mut self, now: Instant) -> Option<Packet> {
+        let timestamp = self.timestamp(now);
+
+        // Data queued before a local close is sent ahead of the shutdown.
+        let flushing = self.closed == Some(CloseReason::Local) && !self.send_new.is_empty();
+
+        if !flushing && let Some(kind) = self.control.pop_front() {
+            self.last_sent = now;
+            return Some(Packet::Control(ControlPacket {
+                timestamp,
+                dest_socket_id: self.params.peer_socket_id,
+                kind,
+            }));
+        }
+
+        if self.closed.is_some() && !flushing {
+            return None;
+        }
+
+        while let Some(seq) = self.send_retransmit.pop_front().filter(|_| !flushing) {
+            let Some(packet) = self.sent_packet_mut(seq) else {
+                continue;
+            };
+
+            packet.queued = false;
+            let packet = self.data_packet(seq, true)?;
+            self.stats.packets_retransmitted += 1;
+            self.last_sent = now;
+            return Some(packet);
+        }
+
+        while let Some(seq) = self.send_new.pop_front() {
+            let Some(packet) = self.data_packet(seq, false) else {
+                continue;
+            };
+
+            self.stats.packets_sent += 1;
+            self.send_progress = now;
+            if let Packet::Data(data) = &packet {
+                self.stats.bytes_sent += data.payload.len() as u64;
+            }
+            self.last_sent = now;
+            return Some(packet);
+        }
+
+        None
+    }
+
+    /// Returns the next payload that is due for delivery.
+    pub(crate) fn poll_deliver(&mut self, now: Instant) -> Option<Bytes> {
+        // Everything that arrived before the peer shut down is delivered
+        // immediately.
+        if self.closed == Some(CloseReason::Peer) {
+            while let Some(slot) = self.pop_slot() {
+                if let Slot::Packet { payload, .. } = slot {
+                    return Some(payload);
+                }
+            }
+
+            return None;
+        }
+
+        loop {
+            match self.recv_slots.front()? {
+                Slot::Dropped => {
+                    self.pop_slot();
+                }
+                Slot::Packet { timestamp, .. } => {
+                    if self.delivery_time(*timestamp) > now {
+                        return None;
+                    }
+
+                    match self.pop_slot() {
+                        Some(Slot::Packet { payload, .. }) => return Some(payload),
+                        _ => unreachable!(),
+                    }
+                }
+                Slot::Empty => {
+                    if !self.params.too_late_packet_drop {
+                        return None;
+                    }
+
+                    let (missing, timestamp) = self.recv_slots.iter().enumerate().find_map(|(i, slot)| match slot {
+                        Slot::Packet { timestamp, .. } => Some((i, *timestamp)),
+                        _ => None,
+                    })?;
+
+                    if self.delivery_time(timestamp) > now {
+                        return None;
+                    }
+
+                    for _ in 0..missing {
+                        if let Some(Slot::Empty) = self.pop_slot() {
+                            self.stats.packets_dropped += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn pop_slot(&mut self) -> Option<Slot> {
+        let slot = self.recv_slots.pop_front()?;
+        self.recv_base = self.recv_base + 1;
+        Some(slot)
+    }
+
+    fn next_delivery(&self) -> Option<Instant> {
+        let timestamp = self.recv_slots.iter().find_map(|slot| match slot {
+            Slot::Packet { timestamp, .. } => Some(*timestamp),
+            _ => None,
+        })?;
+
+        if !self.params.too_late_packet_drop && matches!(self.recv_slots.front(), Some(Slot::Empty)) {
+            return None;
+        }
+
+        Some(self.delivery_time(timestamp))
+    }
+
+    fn delivery_time(&self, timestamp: u64) -> Instant {
+        self.params.peer_time_base + Duration::from_micros(timestamp) + self.params.recv_latency
+    }
+
+    fn timestamp(&self, now: Instant) -> u32 {
+        // Truncating is intentional, timestamps wrap around.
+        now.saturating_duration_since(self.params.start).as_micros() as u32
+    }
+
+    fn sent_packet_mut(&mut self, seq: SeqNumber) -> Option<&mut SentPacket> {
+        let front = self.send_buffer.front()?.seq;
+        let index = usize::try_from(seq - front).ok()?;
+        self.send_buffer.get_mut(index)
+    }
+
+    fn data_packet(&mut self, seq: SeqNumber, retransmitted: bool) -> Option<Packet> {
+        let dest_socket_id = self.params.peer_socket_id;
+        let packet = self.sent_packet_mut(seq)?;
+
+        Some(Packet::Data(DataPacket {
+            seq,
+            position: PacketPosition::Solo,
+            in_order: false,
+            key: packet.key,
+            retransmitted,
+            message_number: packet.message_number,
+            timestamp: packet.timestamp,
+            dest_socket_id,
+            payload: packet.payload.clone(),
+        }))
+    }
+
+    fn handle_data(&mut self, packet: DataPacket) {
+        self.stats.packets_received += 1;
+        self.stats.bytes_received += packet.payload.len() as u64;
+        self.recv_any = true;
+
+        let Ok(offset) = usize::try_from(packet.seq - self.recv_base) else {
+            // Already delivered or dropped.
+            return;
+        };
+
+        if offset >= self.params.flow_window as usize {
+            tracing::debug!(seq = %packet.seq, "packet outside of the receive window");
+            return;
+        }
+
+        let payload = match (packet.key, &self.params.crypto) {
+            (KeyIndex::Unencrypted, _) => Some(packet.payload),
+            (key, Some(crypto)) => crypto.decrypt(key, packet.seq, &packet.payload),
+            (_, None) => None,
+        };
+
+        let slot = match payload {
+            Some(payload) => Slot::Packet {
+                timestamp: self.recv_timestamps.unwrap(packet.timestamp),
+                payload,
+            },
+            None => {
+                tracing::debug!(seq = %packet.seq, "dropping packet that cannot be decrypted");
+                self.stats.packets_dropped += 1;
+                Slot::Dropped
+            }
+        };
+
+        if offset >= self.recv_slots.len() {
+            let missing = offset - self.recv_slots.len();
+            if missing > 0 {
+                let first = self.recv_base + self.recv_slots.len() as u32;
+                let last = packet.seq + u32::MAX;
+                self.stats.packets_lost += missing as u64;
+                self.control.push_back(ControlKind::Nak(vec![(first, last)]));
+            }
+
+            self.recv_slots.extend((0..missing).map(|_| Slot::Empty));
+            self.recv_slots.push_back(slot);
+        } else if matches!(self.recv_slots[offset], Slot::Empty) {
+            self.recv_slots[offset] = slot;
+        }
+    }
+
+    fn ack_seq(&self) -> SeqNumber {
+        let contiguous = self
+            .recv_slots
+            .iter()
+            .position(|slot| matches!(slot, Slot::Empty))
+            .unwrap_or(self.recv_slots.len());
+
+        self.recv_base + contiguous as u32
+    }
+
+    fn send_ack(&mut self, now: Instant) {
+        if !self.recv_any {
+            return;
+        }
+
+        let seq = self.ack_seq();
+        if self.last_ack_seq == Some(seq) && now.duration_since(self.last_ack_time) < ACK_REPEAT_INTERVAL {
+            return;
+        }
+
+        self.ack_number = self.ack_number.wrapping_add(1).max(1);
+        self.last_ack_seq = Some(seq);
+        self.last_ack_time = now;
+
+        self.ack_history.push_back((self.ack_number, now));
+        if self.ack_history.len() > MAX_ACK_HISTORY {
+            self.ack_history.pop_front();
+        }
+
+        self.control.push_back(ControlKind::Ack(Ack {
+            ack_number: self.ack_number,
+            last_ack: seq,
+            info: Some(AckInfo {
+                rtt: self.rtt.as_micros() as u32,
+                rtt_variance: self.rtt_variance.as_micros() as u32,
+                available_buffer: (self.params.flow_window as usize).saturating_sub(self.recv_slots.len()) as u32,
+                ..Default::default()
+            }),
+        }));
+    }
+
+    fn send_nak(&mut self, now: Instant) {
+        let interval = ((self.rtt + 4 * self.rtt_variance) / 2).max(MIN_NAK_INTERVAL);
+        if now.duration_since(self.last_nak_time) < interval {
+            return;
+        }
+
+        let mut ranges: Vec<(SeqNumber, SeqNumber)> = Vec::new();
+        for (i, slot) in self.recv_slots.iter().enumerate() {
+            if !matches!(slot, Slot::Empty) {
+                continue;
+            }
+
+            let seq = self.recv_base + i as u32;
+            if let Some((_, last)) = ranges.last_mut().filter(|(_, last)| *last + 1 == seq) {
+                *last = seq;
+            } else if ranges.len() < MAX_NAK_RANGES {
+                ranges.push((seq, seq));
+            } else {
+                break;
+            }
+        }
+
+        if !ranges.is_empty() {
+            self.last_nak_time = now;
+            self.control.push_back(ControlKind::Nak(ranges));
+        }
+    }
+
+    /// Drops unacknowledged packets that are too old to be useful to the
+    /// receiver.
+    fn drop_expired(&mut self, now: Instant) {
+        let threshold = self.params.send_latency + SEND_DROP_DELAY;
+
+        while let Some(packet) = self.send_buffer.front() {
+            if now.duration_since(packet.scheduled_at) < threshold {
+                break;
+            }
+
+            let packet = self.send_buffer.pop_front().expect("front exists");
+            self.stats.packets_dropped += 1;
+
+            if packet.lost {
+                self.control.push_back(ControlKind::DropRequest {
+                    message_number: packet.message_number,
+                    first: packet.seq,
+                    last: packet.seq,
+                });
+            }
+        }
+    }
+
+    fn handle_ack(&mut self, now: Instant, ack: Ack) {
+        if ack.last_ack - self.send_next_seq > 0 {
+            tracing::debug!(seq = %ack.last_ack, "ignoring ack for packets that were never sent");
+            return;
+        }
+
+        while self.send_buffer.front().is_some_and(|p| p.seq - ack.last_ack < 0) {
+            self.send_buffer.pop_front();
+            self.send_progress = now;
+        }
+
+        if ack.ack_number != 0 {
+            self.control.push_back(ControlKind::AckAck(ack.ack_number));
+        }
+
+        // The receiver measures the RTT, a pure sender learns it from the acks.
+        if let Some(info) = ack.info.filter(|info| info.rtt > 0)
+            && !self.rtt_measured
+        {
+            self.rtt = Duration::from_micros(u64::from(info.rtt));
+            self.rtt_variance = Duration::from_micros(u64::from(info.rtt_variance));
+        }
+    }
+
+    /// Retransmits the last unacknowledged packet if no acks arrive.
+    ///
+    /// The receiver can only detect a loss once a later packet arrives, so
+    /// losing the last packets of a burst would otherwise go unnoticed until
+    /// the next packet is sent.
+    fn probe_tail(&mut self, now: Instant) {
+        let timeout = (self.rtt + 4 * self.rtt_variance + TICK_INTERVAL).max(MIN_TAIL_PROBE_TIMEOUT);
+        if !self.send_new.is_empty() || now.duration_since(self.send_progress) < timeout {
+            return;
+        }
+
+        if let Some(packet) = self.send_buffer.back_mut()
+            && !packet.queued
+        {
+            packet.queued = true;
+            self.send_retransmit.push_back(packet.seq);
+            self.send_progress = now;
+        }
+    }
+
+    fn handle_nak(&mut self, ranges: &[(SeqNumber, SeqNumber)]) {
+        for &(first, last) in ranges {
+            let Ok(count) = u32::try_from(last - first) else {
+                continue;
+            };
+
+            for seq in (0..=count.min(self.params.flow_window)).map(|i| first + i) {
+                let Some(packet) = self.sent_packet_mut(seq) else {
+                    continue;
+                };
+
+                packet.lost = true;
+                if !packet.queued {
+                    packet.queued = true;
+                    self.send_retransmit.push_back(seq);
+                }
+            }
+        }
+    }
+
+    fn handle_ack_ack(&mut self, now: Instant, ack_number: u32) {
+        let Some(index) = self.ack_history.iter().position(|(n, _)| *n == ack_number) else {
+            return;
+        };
+
+        let (_, sent_at) = self.ack_history[index];
+        self.ack_history.drain(..=index);
+
+        let sample = now.duration_since(sent_at);
+        if self.rtt_measured {
+            self.rtt_variance = (3 * self.rtt_variance + self.rtt.abs_diff(sample)) / 4;
+            self.rtt = (7 * self.rtt + sample) / 8;
+        } else {
+            self.rtt = sample;
+            self.rtt_variance = sample / 2;
+            self.rtt_measured = true;
+        }
+    }
+
+    fn handle_drop_request(&mut self, first: SeqNumber, last: SeqNumber) {
+        let Ok(count) = u32::try_from(last - first) else {
+            return;
+        };
+
+        for seq in (0..=count).map(|i| first + i) {
+            let Ok(offset) = usize::try_from(seq - self.recv_base) else {
+                continue;
+            };
+
+            if let Some(slot @ Slot::Empty) = self.recv_slots.get_mut(offset) {
+                *slot = Slot::Dropped;
+                self.stats.packets_dropped += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    fn pair(now: Instant, too_late_packet_drop: bool) -> (Connection, Connection) {
+        let params = |local, peer| ConnectionParams {
+            local_socket_id: local,
+            peer_socket_id: peer,
+            initial_seq: SeqNumber::new(SeqNumber::MAX - 5),
+            flow_window: 8192,
+            payload_size: 1316,
+            recv_latency: Duration::from_millis(120),
+            send_latency: Duration::from_millis(120),
+            too_late_packet_drop,
+            peer_idle_timeout: Duration::from_secs(5),
+            crypto: None,
+            start: now,
+            peer_time_base: now,
+        };
+
+        (Connection::new(params(1, 2), now), Connection::new(params(2, 1), now))
+    }
+
+    /// Moves all pending packets from `from` to `to`, dropping the data
+    /// packets for which `lose` returns true.
+    fn transfer(now: Instant, from: &mut Connection, to: &mut Connection, mut lose: impl FnMut(&DataPacket) -> bool) {
+        while let Some(packet) = from.poll_transmit(now) {
+            if let Packet::Data(data) = &packet
+                && lose(data)
+            {
+                continue;
+            }
+
+            to.handle_packet(now, packet);
+        }
+    }
+
+    #[test]
+    fn timestamp_unwrap() {
+        let mut unwrapper = TimestampUnwrapper::default();
+        assert_eq!(unwrapper.unwrap(u32::MAX - 10), u64::from(u32::MAX - 10));
+        assert_eq!(unwrapper.unwrap(5), (1 << 32) + 5);
+        // A late packet from before the wrap.
+        assert_eq!(unwrapper.unwrap(u32::MAX - 20), u64::from(u32::MAX - 20));
+        assert_eq!(unwrapper.unwrap(10), (1 << 32) + 10);
+    }
+
+    #[test]
+    fn retransmission() {
+        let start = Instant::now();
+        let (mut sender, mut receiver) = pair(start, true);
+
+        for i in 0..20u8 {
+            sender.send(start, Bytes::from(vec![i; 100]));
+        }
+
+        // Lose every third packet, across the sequence number wrap.
+        let mut n = 0;
+        transfer(start, &mut sender, &mut receiver, |_| {
+            n += 1;
+            n % 3 == 0
+        });
+
+        assert_eq!(receiver.stats().packets_lost, 6);
+        assert!(receiver.poll_deliver(start).is_none());
+
+        // The immediate loss reports trigger retransmissions.
+        transfer(start, &mut receiver, &mut sender, |_| false);
+        transfer(start, &mut sender, &mut receiver, |_| false);
+        assert_eq!(sender.stats().packets_retransmitted, 6);
+
+        let delivery = start + Duration::from_millis(120);
+        receiver.handle_timeout(delivery);
+        let delivered: Vec<_> = std::iter::from_fn(|| receiver.poll_deliver(delivery)).collect();
+        assert_eq!(delivered.len(), 20);
+        for (i, payload) in delivered.iter().enumerate() {
+            assert_eq!(payload[0], i as u8);
+        }
+
+        // The ack releases the send buffer.
+        transfer(delivery, &mut receiver, &mut sender, |_| false);
+        assert!(sender.send_buffer.is_empty());
+        transfer(delivery, &mut sender, &mut receiver, |_| false);
+        assert!(receiver.ack_history.is_empty());
+    }
+
+    #[test]
+    fn too_late_packet_drop() {
+        let start = Instant::now();
+        let (mut sender, mut receiver) = pair(start, true);
+
+        sender.send(start, Bytes::from_static(b"a"));
+        sender.send(start, Bytes::from_static(b"b"));
+        sender.send(start, Bytes::from_static(b"c"));
+        transfer(start, &mut sender, &mut receiver, |p| &p.payload[..] == b"b");
+
+        assert_eq!(receiver.next_timeout(), start + Duration::from_millis(10));
+        let delivery = start + Duration::from_millis(120);
+        assert_eq!(receiver.poll_deliver(delivery).as_deref(), Some(&b"a"[..]));
+        assert_eq!(receiver.poll_deliver(delivery).as_deref(), Some(&b"c"[..]));
+        assert_eq!(receiver.stats().packets_dropped, 1);
+    }
+
+    #[test]
+    fn no_too_late_packet_drop() {
+        let start = Instant::now();
+        let (mut sender, mut receiver) = pair(start, false);
+
+        sender.send(start, Bytes::from_static(b"a"));
+        sender.send(start, Bytes::from_static(b"b"));
+        transfer(start, &mut sender, &mut receiver, |p| &p.payload[..] == b"a");
+
+        let delivery = start + Duration::from_millis(500);
+        assert_eq!(receiver.poll_deliver(delivery), None);
+
+        // The sender gives up and tells the receiver to skip the packet.
+        transfer(start, &mut receiver, &mut sender, |_| false);
+        sender.handle_timeout(start + Duration::from_secs(2));
+        transfer(delivery, &mut sender, &mut receiver, |p| &p.payload[..] == b"a");
+        assert_eq!(receiver.poll_deliver(delivery).as_deref(), Some(&b"b"[..]));
+    }
+
+    #[test]
+    fn rtt_and_idle_timeout() {
+        let start = Instant::now();
+        let (mut sender, mut receiver) = pair(start, true);
+
+        sender.send(start, Bytes::from_static(b"a"));
+        transfer(start, &mut sender, &mut receiver, |_| false);
+
+        let tick = start + TICK_INTERVAL;
+        receiver.handle_timeout(tick);
+        transfer(tick, &mut receiver, &mut sender, |_| false);
+        let later = tick + Duration::from_millis(30);
+        transfer(later, &mut sender, &mut receiver, |_| false);
+        assert_eq!(receiver.stats().rtt, Duration::from_millis(30));
+
+        receiver.handle_timeout(later + Duration::from_secs(5));
+        assert_eq!(receiver.closed(), Some(CloseReason::Timeout));
+    }
+
+    #[test]
+    fn shutdown() {
+        let start = Instant::now();
+        let (mut a, mut b) = pair(start, true);
+
+        a.send(start, Bytes::from_static(b"a"));
+        a.close();
+        assert_eq!(a.closed(), Some(CloseReason::Local));
+
+        // The pending data is sent before the shutdown.
+        assert!(matches!(a.poll_transmit(start), Some(Packet::Data(_))));
+        b.handle_packet(start, a.poll_transmit(start).unwrap());
+        assert!(a.poll_transmit(start).is_none());
+        assert_eq!(b.closed(), Some(CloseReason::Peer));
+
+        b.close();
+        assert!(b.poll_transmit(start).is_none());
+    }
+}
@@ -0,0 +1,449 @@
+//! Payload encryption.
+//!
+//! The caller generates a random stream encrypting key (SEK) and a salt, wraps
+//! the key with a key encrypting key (KEK) derived from the passphrase and
+//! sends the result as [`KeyMaterial`] in the conclusion handshake. Data
+//! packet payloads are encrypted with AES-CTR, using the salt and the packet
+//! sequence number as the IV.
+
+use std::io;
+
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use rand::Rng;
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::error::SrtError;
+use crate::packet::KeyIndex;
+use crate::seq::SeqNumber;
+
+/// The length of the salt in bytes.
+pub const SALT_LEN: usize = 16;
+
+/// The valid passphrase lengths in bytes.
+pub const PASSPHRASE_LEN: std::ops::RangeInclusive<usize> = 10..=79;
+
+/// The number of PBKDF2 iterations used to derive the key encrypting key.
+const PBKDF2_ITERATIONS: u32 = 2048;
+
+/// The default IV of the AES key wrap algorithm (RFC 3394).
+const KEY_WRAP_IV: u64 = 0xA6A6_A6A6_A6A6_A6A6;
+
+/// The first byte of a key material message: version `1`, packet type `2`.
+const KM_VERSION_TYPE: u8 = 0x12;
+
+/// The signature of a key material message.
+const KM_SIGNATURE: u16 = 0x2029;
+
+/// The AES-CTR cipher.
+const CIPHER_AES_CTR: u8 = 2;
+
+/// The stream encapsulation used by SRT.
+const STREAM_ENCAPSULATION_SRT: u8 = 2;
+
+nutype_enum::nutype_enum! {
+    /// The key material state sent in place of the key material when the
+    /// listener cannot use the caller's keys.
+    pub enum KmState(u32) {
+        /// Encryption is not configured.
+        Unsecured = 0,
+        /// The keys are being exchanged.
+        Securing = 1,
+        /// The keys were exchanged successfully.
+        Secured = 2,
+        /// The peer has no passphrase configured.
+        NoSecret = 3,
+        /// The passphrase does not match.
+        BadSecret = 4,
+    }
+}
+
+/// A key material message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMaterial {
+    /// The keys contained in the message (`1` even, `2` odd, `3` both).
+    pub key_flags: u8,
+    /// The cipher.
+    pub cipher: u8,
+    /// The length of each key in bytes.
+    pub key_len: usize,
+    /// The salt.
+    pub salt: Bytes,
+    /// The wrapped keys.
+    pub wrapped_keys: Bytes,
+}
+
+impl KeyMaterial {
+    /// Parses a key material message.
+    pub fn parse(data: Bytes) -> Result<Self, SrtError> {
+        let mut reader = io::Cursor::new(data);
+
+        if reader.read_u8()? != KM_VERSION_TYPE {
+            return Err(SrtError::InvalidKeyMaterial("unsupported version or packet type"));
+        }
+
+        if reader.read_u16::<BigEndian>()? != KM_SIGNATURE {
+            return Err(SrtError::InvalidKeyMaterial("invalid signature"));
+        }
+
+        let key_flags = reader.read_u8()? & 0b11;
+        let _keki = reader.read_u32::<BigEndian>()?;
+        let cipher = reader.read_u8()?;
+        let _auth = reader.read_u8()?;
+        let _se = reader.read_u8()?;
+        let _resv = reader.read_u8()?;
+        let _resv = reader.read_u16::<BigEndian>()?;
+        let salt_len = usize::from(reader.read_u8()?) * 4;
+        let key_len = usize::from(reader.read_u8()?) * 4;
+
+        if cipher != CIPHER_AES_CTR {
+            return Err(SrtError::InvalidKeyMaterial("unsupported cipher"));
+        }
+
+        if !matches!(key_len, 16 | 24 | 32) {
+            return Err(SrtError::InvalidKeyLength(key_len));
+        }
+
+        let salt = reader.extract_bytes(salt_len)?;
+        let key_count = key_flags.count_ones() as usize;
+        let wrapped_keys = reader.extract_bytes(key_len * key_count + 8)?;
+
+        Ok(Self {
+            key_flags,
+            cipher,
+            key_len,
+            salt,
+            wrapped_keys,
+        })
+    }
+
+    /// Writes the key material message.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_u8(KM_VERSION_TYPE)?;
+        writer.write_u16::<BigEndian>(KM_SIGNATURE)?;
+        writer.write_u8(self.key_flags & 0b11)?;
+        writer.write_u32::<BigEndian>(0)?;
+        writer.write_all(&[self.cipher, 0, STREAM_ENCAPSULATION_SRT, 0])?;
+        writer.write_u16::<BigEndian>(0)?;
+        writer.write_u8((self.salt.len() / 4) as u8)?;
+        writer.write_u8((self.key_len / 4) as u8)?;
+        writer.write_all(&self.salt)?;
+        writer.write_all(&self.wrapped_keys)
+    }
+}
+
+/// The key material response sent by the listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KmResponse {
+    /// The key material was accepted and is echoed back.
+    Material(KeyMaterial),
+    /// The key material was not accepted.
+    State(KmState),
+}
+
+impl KmResponse {
+    /// Parses a key material response.
+    pub fn parse(data: Bytes) -> Result<Self, SrtError> {
+        if data.len() == 4 {
+            let state = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            Ok(Self::State(KmState::from(state)))
+        } else {
+            KeyMaterial::parse(data).map(Self::Material)
+        }
+    }
+
+    /// Writes the key material response.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        match self {
+            Self::Material(km) => km.write(writer),
+            Self::State(state) => writer.write_u32::<BigEndian>(state.0),
+        }
+    }
+}
+
+/// Returns an error if the passphrase has an invalid length.
+pub(crate) fn validate_passphrase(passphrase: &str, key_len: usize) -> Result<(), SrtError> {
+    if !PASSPHRASE_LEN.contains(&passphrase.len()) {
+        return Err(SrtError::InvalidPassphrase);
+    }
+
+    if !matches!(key_len, 16 | 24 | 32) {
+        return Err(SrtError::InvalidKeyLength(key_len));
+    }
+
+    Ok(())
+}
+
+/// The negotiated encryption state of a connection.
+#[derive(Clone)]
+pub(crate) struct Crypto {
+    salt: [u8; SALT_LEN],
+    even: Option<Vec<u8>>,
+    odd: Option<Vec<u8>>,
+}
+
+impl Crypto {
+    /// Generates a new stream encrypting key and the key material announcing
+    /// it.
+    pub(crate) fn generate(passphrase: &str, key_len: usize) -> Result<(Self, KeyMaterial), SrtError> {
+        validate_passphrase(passphrase, key_len)?;
+
+        let mut rng = rand::rng();
+        let mut salt = [0; SALT_LEN];
+        rng.fill(&mut salt);
+        let mut key = vec![0; key_len];
+        rng.fill(key.as_mut_slice());
+
+        let kek = derive_kek(passphrase, &salt, key_len);
+        let wrapped_keys = Aes::new(&kek).wrap(&key);
+
+        let km = KeyMaterial {
+            key_flags: KeyIndex::Even.0,
+            cipher: CIPHER_AES_CTR,
+            key_len,
+            salt: Bytes::copy_from_slice(&salt),
+            wrapped_keys: Bytes::from(wrapped_keys),
+        };
+
+        Ok((
+            Self {
+                salt,
+                even: Some(key),
+                odd: None,
+            },
+            km,
+        ))
+    }
+
+    /// Unwraps the keys announced in the key material.
+    pub(crate) fn from_key_material(km: &KeyMaterial, passphrase: &str) -> Result<Self, SrtError> {
+        let salt: [u8; SALT_LEN] = km
+            .salt
+            .as_ref()
+            .try_into()
+            .map_err(|_| SrtError::InvalidKeyMaterial("invalid salt length"))?;
+
+        let kek = derive_kek(passphrase, &salt, km.key_len);
+        let keys = Aes::new(&kek).unwrap(&km.wrapped_keys).ok_or(SrtError::BadSecret)?;
+
+        let mut keys = keys.chunks_exact(km.key_len).map(<[u8]>::to_vec);
+        let even = (km.key_flags & KeyIndex::Even.0 != 0).then(|| keys.next()).flatten();
+        let odd = (km.key_flags & KeyIndex::Odd.0 != 0).then(|| keys.next()).flatten();
+
+        Ok(Self { salt, even, odd })
+    }
+
+    /// Encrypts the payload of the packet with the given sequence number.
+    pub(crate) fn encrypt(&self, seq: SeqNumber, payload: &[u8]) -> (KeyIndex, Bytes) {
+        let (index, key) = match (&self.even, &self.odd) {
+            (Some(key), _) => (KeyIndex::Even, key),
+            (None, Some(key)) => (KeyIndex::Odd, key),
+            (None, None) => return (KeyIndex::Unencrypted, Bytes::copy_from_slice(payload)),
+        };
+
+        let mut payload = payload.to_vec();
+        self.apply_keystream(key, seq, &mut payload);
+        (index, Bytes::from(payload))
+    }
+
+    /// Decrypts the payload of the packet with the given sequence number.
+    ///
+    /// Returns `None` if the key is not known.
+    pub(crate) fn decrypt(&self, index: KeyIndex, seq: SeqNumber, payload: &[u8]) -> Option<Bytes> {
+        let key = match index {
+            KeyIndex::Even => self.even.as_ref()?,
+            KeyIndex::Odd => self.odd.as_ref()?,
+            _ => return None,
+        };
+
+        let mut payload = payload.to_vec();
+        self.apply_keystream(key, seq, &mut payload);
+        Some(Bytes::from(payload))
+    }
+
+    fn apply_keystream(&self, key: &[u8], seq: SeqNumber, payload: &mut [u8]) {
+        // IV = MSB(112, salt) XOR (0 || seq) || 16-bit block counter
+        let mut iv = [0; 16];
+        iv[..14].copy_from_slice(&self.salt[..14]);
+        for (iv, seq) in iv[10..14].iter_mut().zip(seq.value().to_be_bytes()) {
+            *iv ^= seq;
+        }
+
+        match key.len() {
+            16 => ctr::Ctr128BE::<aes::Aes128>::new_from_slices(key, &iv)
+                .expect("valid key length")
+                .apply_keystream(payload),
+            24 => ctr::Ctr128BE::<aes::Aes192>::new_from_slices(key, &iv)
+                .expect("valid key length")
+                .apply_keystream(payload),
+            _ => ctr::Ctr128BE::<aes::Aes256>::new_from_slices(key, &iv)
+                .expect("valid key length")
+                .apply_keystream(payload),
+        }
+    }
+}
+
+/// Derives the key encrypting key from the passphrase using PBKDF2 with the
+/// least significant 64 bits of the salt.
+fn derive_kek(passphrase: &str, salt: &[u8; SALT_LEN], key_len: usize) -> Vec<u8> {
+    let mut kek = vec![0; key_len];
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(passphrase.as_bytes(), &salt[SALT_LEN - 8..], PBKDF2_ITERATIONS, &mut kek);
+    kek
+}
+
+enum Aes {
+    Aes128(aes::Aes128),
+    Aes192(aes::Aes192),
+    Aes256(aes::Aes256),
+}
+
+impl Aes {
+    fn new(key: &[u8]) -> Self {
+        match key.len() {
+            16 => Self::Aes128(aes::Aes128::new_from_slice(key).expect("valid key length")),
+            24 => Self::Aes192(aes::Aes192::new_from_slice(key).expect("valid key length")),
+            _ => Self::Aes256(aes::Aes256::new_from_slice(key).expect("valid key length")),
+        }
+    }
+
+    fn encrypt(&self, block: &mut [u8; 16]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Self::Aes128(cipher) => cipher.encrypt_block(block),
+            Self::Aes192(cipher) => cipher.encrypt_block(block),
+            Self::Aes256(cipher) => cipher.encrypt_block(block),
+        }
+    }
+
+    fn decrypt(&self, block: &mut [u8; 16]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Self::Aes128(cipher) => cipher.decrypt_block(block),
+            Self::Aes192(cipher) => cipher.decrypt_block(block),
+            Self::Aes256(cipher) => cipher.decrypt_block(block),
+        }
+    }
+
+    /// AES key wrap (RFC 3394).
+    fn wrap(&self, key: &[u8]) -> Vec<u8> {
+        let n = key.len() / 8;
+        let mut a = KEY_WRAP_IV;
+        let mut r: Vec<[u8; 8]> = key.chunks_exact(8).map(|c| c.try_into().unwrap()).collect();
+
+        for j in 0..6 {
+            for (i, r) in r.iter_mut().enumerate() {
+                let mut block = [0; 16];
+                block[..8].copy_from_slice(&a.to_be_bytes());
+                block[8..].copy_from_slice(r);
+                self.encrypt(&mut block);
+
+                let t = (n * j + i + 1) as u64;
+                a = u64::from_be_bytes(block[..8].try_into().unwrap()) ^ t;
+                r.copy_from_slice(&block[8..]);
+            }
+        }
+
+        let mut out = a.to_be_bytes().to_vec();
+        out.extend(r.iter().flatten());
+        out
+    }
+
+    /// AES key unwrap (RFC 3394), returns `None` if the integrity check fails.
+    fn unwrap(&self, wrapped: &[u8]) -> Option<Vec<u8>> {
+        if wrapped.len() < 16 || !wrapped.len().is_multiple_of(8) {
+            return None;
+        }
+
+        let n = wrapped.len() / 8 - 1;
+        let mut a = u64::from_be_bytes(wrapped[..8].try_into().unwrap());
+        let mut r: Vec<[u8; 8]> = wrapped[8..].chunks_exact(8).map(|c| c.try_into().unwrap()).collect();
+
+        for j in (0..6).rev() {
+            for (i, r) in r.iter_mut().enumerate().rev() {
+                let t = (n * j + i + 1) as u64;
+                let mut block = [0; 16];
+                block[..8].copy_from_slice(&(a ^ t).to_be_bytes());
+                block[8..].copy_from_slice(r);
+                self.decrypt(&mut block);
+
+                a = u64::from_be_bytes(block[..8].try_into().unwrap());
+                r.copy_from_slice(&block[8..]);
+            }
+        }
+
+        (a == KEY_WRAP_IV).then(|| r.iter().flatten().copied().collect())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_wrap_rfc3394() {
+        let kek: Vec<u8> = (0..16).collect();
+        let key = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF,
+        ];
+        let wrapped = [
+            0x1F, 0xA6, 0x8B, 0x0A, 0x81, 0x12, 0xB4, 0x47, 0xAE, 0xF3, 0x4B, 0xD8, 0xFB, 0x5A, 0x7B, 0x82, 0x9D, 0x3E,
+            0x86, 0x23, 0x71, 0xD2, 0xCF, 0xE5,
+        ];
+
+        let aes = Aes::new(&kek);
+        assert_eq!(aes.wrap(&key), wrapped);
+        assert_eq!(aes.unwrap(&wrapped).unwrap(), key);
+
+        let mut corrupted = wrapped;
+        corrupted[0] ^= 1;
+        assert_eq!(aes.unwrap(&corrupted), None);
+    }
+
+    #[test]
+    fn encrypt_decrypt() {
+        for key_len in [16, 24, 32] {
+            let (sender, km) = Crypto::generate("correct horse battery", key_len).unwrap();
+
+            let mut buf = Vec::new();
+            km.write(&mut buf).unwrap();
+            let km = KeyMaterial::parse(Bytes::from(buf)).unwrap();
+            assert_eq!(km.key_len, key_len);
+
+            let receiver = Crypto::from_key_material(&km, "correct horse battery").unwrap();
+            let seq = SeqNumber::new(12345);
+            let (index, encrypted) = sender.encrypt(seq, b"hello world");
+            assert_eq!(index, KeyIndex::Even);
+            assert_ne!(&encrypted[..], b"hello world");
+            assert_eq!(&receiver.decrypt(index, seq, &encrypted).unwrap()[..], b"hello world");
+            assert_eq!(receiver.decrypt(KeyIndex::Odd, seq, &encrypted), None);
+
+            assert!(matches!(
+                Crypto::from_key_material(&km, "wrong horse battery"),
+                Err(SrtError::BadSecret)
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid_passphrase() {
+        assert!(matches!(Crypto::generate("short", 16), Err(SrtError::InvalidPassphrase)));
+        assert!(matches!(
+            Crypto::generate("long enough passphrase", 17),
+            Err(SrtError::InvalidKeyLength(17))
+        ));
+    }
+
+    #[test]
+    fn km_response() {
+        let mut buf = Vec::new();
+        KmResponse::State(KmState::BadSecret).write(&mut buf).unwrap();
+        assert_eq!(
+            KmResponse::parse(Bytes::from(buf)).unwrap(),
+            KmResponse::State(KmState::BadSecret)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Error types.
+
+use crate::handshake::RejectReason;
+use crate::session::server::ServerSessionError;
+
+/// SRT error.
+#[derive(Debug, thiserror::Error)]
+pub enum SrtError {
+    /// IO error.
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    /// The datagram is shorter than the packet header.
+    #[error("packet too short: {0} bytes")]
+    PacketTooShort(usize),
+    /// The handshake is malformed or not supported.
+    #[error("invalid handshake: {0}")]
+    InvalidHandshake(&'static str),
+    /// The key material is malformed or not supported.
+    #[error("invalid key material: {0}")]
+    InvalidKeyMaterial(&'static str),
+    /// The passphrase has an invalid length.
+    #[error("passphrase must be between 10 and 79 bytes long")]
+    InvalidPassphrase,
+    /// The key length is not 16, 24 or 32 bytes.
+    #[error("invalid key length: {0}")]
+    InvalidKeyLength(usize),
+    /// The keys could not be unwrapped with the passphrase.
+    #[error("bad secret")]
+    BadSecret,
+    /// The stream id is too long.
+    #[error("stream id too long: {0} bytes")]
+    StreamIdTooLong(usize),
+    /// The peer rejected the connection.
+    #[error("connection rejected: {0:?}")]
+    Rejected(RejectReason),
+    /// The handshake did not complete in time.
+    #[error("connect timeout")]
+    ConnectTimeout,
+    /// Nothing was received from the peer for too long.
+    #[error("peer idle timeout")]
+    PeerIdle,
+    /// The connection or listener is closed.
+    #[error("closed")]
+    Closed,
+    /// Session error.
+    #[error("session error: {0}")]
+    Session(#[from] ServerSessionError),
+}
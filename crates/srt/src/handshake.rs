@@ -0,0 +1,527 @@
+//! Handshake control packets.
+//!
+//! SRT uses the UDT handshake (version 5) in two rounds:
+//!
+//! 1. Induction: the caller sends an induction request and the listener
+//!    answers with a SYN cookie.
+//! 2. Conclusion: the caller repeats the cookie together with the SRT
+//!    extensions (latency, encryption, stream id) and the listener answers with
+//!    its own extensions, or rejects the connection.
+
+use std::io;
+use std::net::IpAddr;
+use std::ops::BitOr;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::crypto::{KeyMaterial, KmResponse};
+use crate::error::SrtError;
+use crate::seq::SeqNumber;
+
+/// The magic value sent by the listener in the extension field of the
+/// induction response.
+pub const HANDSHAKE_MAGIC: u16 = 0x4A17;
+
+/// The SRT version implemented by this crate (`1.5.0`).
+pub const SRT_VERSION: u32 = 0x0001_0500;
+
+/// The maximum length of a stream id in bytes.
+pub const MAX_STREAM_ID_LEN: usize = 512;
+
+nutype_enum::nutype_enum! {
+    /// The type of a handshake packet.
+    ///
+    /// Values of `1000` and above are [`RejectReason`]s.
+    pub enum HandshakeType(u32) {
+        /// Rendezvous final handshake.
+        Done = 0xFFFF_FFFD,
+        /// Rendezvous agreement.
+        Agreement = 0xFFFF_FFFE,
+        /// Conclusion request or response.
+        Conclusion = 0xFFFF_FFFF,
+        /// Rendezvous wave-a-hand.
+        WaveAHand = 0x0000_0000,
+        /// Induction request or response.
+        Induction = 0x0000_0001,
+    }
+}
+
+impl HandshakeType {
+    /// Returns the reject reason if this handshake rejects the connection.
+    pub fn reject_reason(self) -> Option<RejectReason> {
+        (1000..0xFFFF_FFFD).contains(&self.0).then_some(RejectReason(self.0))
+    }
+}
+
+impl From<RejectReason> for HandshakeType {
+    fn from(value: RejectReason) -> Self {
+        Self(value.0)
+    }
+}
+
+nutype_enum::nutype_enum! {
+    /// The reason a connection was rejected.
+    ///
+    /// Values between `1000` and `1999` are defined by SRT, values between
+    /// `1400` and `1599` are the access control codes defined by the SRT access
+    /// control guidelines and values of `2000` and above are application
+    /// defined.
+    pub enum RejectReason(u32) {
+        /// Unknown reason.
+        Unknown = 1000,
+        /// System function error.
+        System = 1001,
+        /// Rejected by the peer.
+        Peer = 1002,
+        /// Resource allocation problem.
+        Resource = 1003,
+        /// Incorrect data in handshake.
+        Rogue = 1004,
+        /// Listener's backlog exceeded.
+        Backlog = 1005,
+        /// Internal program error.
+        InternalError = 1006,
+        /// Socket is closing.
+        Close = 1007,
+        /// Peer is older than the minimum supported version.
+        Version = 1008,
+        /// Rendezvous cookie collision.
+        RendezvousCookie = 1009,
+        /// Wrong passphrase.
+        BadSecret = 1010,
+        /// Password required or unexpected.
+        Unsecure = 1011,
+        /// Stream flag collision.
+        MessageApi = 1012,
+        /// Incompatible congestion controller.
+        Congestion = 1013,
+        /// Incompatible packet filter.
+        Filter = 1014,
+        /// Incompatible group.
+        Group = 1015,
+        /// Connection timeout.
+        Timeout = 1016,
+        /// General syntax error in the stream id.
+        BadRequest = 1400,
+        /// Authentication failed.
+        Unauthorized = 1401,
+        /// The server is too heavily loaded.
+        Overload = 1402,
+        /// Access denied to the resource.
+        Forbidden = 1403,
+        /// The resource was not found.
+        NotFound = 1404,
+        /// The requested mode is not supported for this resource.
+        BadMode = 1405,
+        /// The requested parameters cannot be satisfied.
+        Unacceptable = 1406,
+        /// The resource is already in use.
+        Conflict = 1409,
+    }
+}
+
+/// Flags exchanged in the SRT handshake extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SrtFlags(pub u32);
+
+impl SrtFlags {
+    /// The peer supports encryption.
+    pub const CRYPT: Self = Self(0x04);
+    /// Packet filters are supported.
+    pub const PACKET_FILTER: Self = Self(0x80);
+    /// Loss reports are sent periodically.
+    pub const PERIODIC_NAK: Self = Self(0x10);
+    /// The retransmission flag is present in the data packet header.
+    pub const REXMIT_FLAG: Self = Self(0x20);
+    /// Stream (file) transmission mode.
+    pub const STREAM: Self = Self(0x40);
+    /// Packets that arrive too late are dropped.
+    pub const TOO_LATE_PACKET_DROP: Self = Self(0x08);
+    /// The receiver uses timestamp based packet delivery.
+    pub const TSBPD_RECV: Self = Self(0x02);
+    /// The sender uses timestamp based packet delivery.
+    pub const TSBPD_SEND: Self = Self(0x01);
+
+    /// Returns true if all flags in `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for SrtFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The SRT handshake extension (`HSREQ` / `HSRSP`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrtHandshake {
+    /// The SRT version of the peer.
+    pub version: u32,
+    /// The capabilities of the peer.
+    pub flags: SrtFlags,
+    /// The latency of the peer as a receiver in milliseconds.
+    pub recv_latency: u16,
+    /// The latency of the peer as a sender in milliseconds.
+    pub send_latency: u16,
+}
+
+nutype_enum::nutype_enum! {
+    /// The type of a handshake extension.
+    pub enum ExtensionType(u16) {
+        /// SRT handshake request.
+        HsReq = 1,
+        /// SRT handshake response.
+        HsRsp = 2,
+        /// Key material request.
+        KmReq = 3,
+        /// Key material response.
+        KmRsp = 4,
+        /// Stream id.
+        StreamId = 5,
+        /// Congestion controller.
+        Congestion = 6,
+        /// Packet filter.
+        Filter = 7,
+        /// Group membership.
+        Group = 8,
+    }
+}
+
+/// Flags in the extension field of a conclusion handshake announcing which
+/// extensions are present.
+pub mod extension_flags {
+    /// A `HSREQ` or `HSRSP` extension is present.
+    pub const HSREQ: u16 = 0x1;
+    /// A `KMREQ` or `KMRSP` extension is present.
+    pub const KMREQ: u16 = 0x2;
+    /// A stream id, congestion or filter extension is present.
+    pub const CONFIG: u16 = 0x4;
+}
+
+/// A handshake extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandshakeExtension {
+    /// SRT handshake request, sent by the caller.
+    HsReq(SrtHandshake),
+    /// SRT handshake response, sent by the listener.
+    HsRsp(SrtHandshake),
+    /// Key material request, sent by the caller.
+    KmReq(KeyMaterial),
+    /// Key material response, sent by the listener.
+    KmRsp(KmResponse),
+    /// The stream id requested by the caller.
+    StreamId(String),
+    /// An extension that is not handled by this crate.
+    Unknown {
+        /// The extension type.
+        extension_type: ExtensionType,
+        /// The extension contents.
+        data: Bytes,
+    },
+}
+
+/// A handshake control packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    /// The handshake version, `4` in the induction request and `5` otherwise.
+    pub version: u32,
+    /// The encryption field, the key length in bytes divided by 8 or `0`.
+    pub encryption: u16,
+    /// The extension field, either [`HANDSHAKE_MAGIC`] or
+    /// [`extension_flags`].
+    pub extension: u16,
+    /// The initial sequence number.
+    pub initial_seq: SeqNumber,
+    /// The maximum transmission unit in bytes.
+    pub mtu: u32,
+    /// The maximum flow window size in packets.
+    pub flow_window: u32,
+    /// The handshake type.
+    pub handshake_type: HandshakeType,
+    /// The socket id of the sender.
+    pub socket_id: u32,
+    /// The SYN cookie.
+    pub syn_cookie: u32,
+    /// The address of the receiver.
+    pub peer_ip: IpAddr,
+    /// The handshake extensions.
+    pub extensions: Vec<HandshakeExtension>,
+}
+
+impl Handshake {
+    /// Parses the handshake from the control information field.
+    pub fn parse(reader: &mut io::Cursor<Bytes>) -> Result<Self, SrtError> {
+        let version = reader.read_u32::<BigEndian>()?;
+        let encryption = reader.read_u16::<BigEndian>()?;
+        let extension = reader.read_u16::<BigEndian>()?;
+        let initial_seq = SeqNumber::new(reader.read_u32::<BigEndian>()?);
+        let mtu = reader.read_u32::<BigEndian>()?;
+        let flow_window = reader.read_u32::<BigEndian>()?;
+        let handshake_type = HandshakeType::from(reader.read_u32::<BigEndian>()?);
+        let socket_id = reader.read_u32::<BigEndian>()?;
+        let syn_cookie = reader.read_u32::<BigEndian>()?;
+
+        let mut ip = [0; 16];
+        io::Read::read_exact(reader, &mut ip)?;
+        let peer_ip = if ip[4..].iter().all(|b| *b == 0) {
+            IpAddr::from([ip[3], ip[2], ip[1], ip[0]])
+        } else {
+            IpAddr::from(ip)
+        };
+
+        let mut extensions = Vec::new();
+        if version >= 5 && handshake_type == HandshakeType::Conclusion {
+            while reader.get_ref().len() as u64 - reader.position() >= 4 {
+                let extension_type = ExtensionType::from(reader.read_u16::<BigEndian>()?);
+                let len = usize::from(reader.read_u16::<BigEndian>()?) * 4;
+                let data = reader.extract_bytes(len)?;
+                extensions.push(HandshakeExtension::parse(extension_type, data)?);
+            }
+        }
+
+        Ok(Self {
+            version,
+            encryption,
+            extension,
+            initial_seq,
+            mtu,
+            flow_window,
+            handshake_type,
+            socket_id,
+            syn_cookie,
+            peer_ip,
+            extensions,
+        })
+    }
+
+    /// Writes the handshake control information field.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.version)?;
+        writer.write_u16::<BigEndian>(self.encryption)?;
+        writer.write_u16::<BigEndian>(self.extension)?;
+        writer.write_u32::<BigEndian>(self.initial_seq.value())?;
+        writer.write_u32::<BigEndian>(self.mtu)?;
+        writer.write_u32::<BigEndian>(self.flow_window)?;
+        writer.write_u32::<BigEndian>(self.handshake_type.0)?;
+        writer.write_u32::<BigEndian>(self.socket_id)?;
+        writer.write_u32::<BigEndian>(self.syn_cookie)?;
+
+        // IPv4 addresses are written as a little endian 32-bit word followed
+        // by zeros, like the reference implementation does.
+        match self.peer_ip {
+            IpAddr::V4(ip) => {
+                let [a, b, c, d] = ip.octets();
+                writer.write_all(&[d, c, b, a])?;
+                writer.write_all(&[0; 12])?;
+            }
+            IpAddr::V6(ip) => writer.write_all(&ip.octets())?,
+        }
+
+        for extension in &self.extensions {
+            extension.write(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the SRT handshake request or response extension.
+    pub fn srt_handshake(&self) -> Option<&SrtHandshake> {
+        self.extensions.iter().find_map(|ext| match ext {
+            HandshakeExtension::HsReq(hs) | HandshakeExtension::HsRsp(hs) => Some(hs),
+            _ => None,
+        })
+    }
+
+    /// Returns the stream id extension.
+    pub fn stream_id(&self) -> Option<&str> {
+        self.extensions.iter().find_map(|ext| match ext {
+            HandshakeExtension::StreamId(id) => Some(id.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the key material request extension.
+    pub fn key_material_request(&self) -> Option<&KeyMaterial> {
+        self.extensions.iter().find_map(|ext| match ext {
+            HandshakeExtension::KmReq(km) => Some(km),
+            _ => None,
+        })
+    }
+
+    /// Returns the key material response extension.
+    pub fn key_material_response(&self) -> Option<&KmResponse> {
+        self.extensions.iter().find_map(|ext| match ext {
+            HandshakeExtension::KmRsp(km) => Some(km),
+            _ => None,
+        })
+    }
+}
+
+impl HandshakeExtension {
+    fn parse(extension_type: ExtensionType, data: Bytes) -> Result<Self, SrtError> {
+        Ok(match extension_type {
+            ExtensionType::HsReq | ExtensionType::HsRsp => {
+                let mut reader = io::Cursor::new(data);
+                let hs = SrtHandshake {
+                    version: reader.read_u32::<BigEndian>()?,
+                    flags: SrtFlags(reader.read_u32::<BigEndian>()?),
+                    recv_latency: reader.read_u16::<BigEndian>()?,
+                    send_latency: reader.read_u16::<BigEndian>()?,
+                };
+
+                if extension_type == ExtensionType::HsReq {
+                    Self::HsReq(hs)
+                } else {
+                    Self::HsRsp(hs)
+                }
+            }
+            ExtensionType::KmReq => Self::KmReq(KeyMaterial::parse(data)?),
+            ExtensionType::KmRsp => Self::KmRsp(KmResponse::parse(data)?),
+            ExtensionType::StreamId => Self::StreamId(decode_stream_id(&data)?),
+            _ => Self::Unknown { extension_type, data },
+        })
+    }
+
+    fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        let mut data = Vec::new();
+
+        let extension_type = match self {
+            Self::HsReq(hs) | Self::HsRsp(hs) => {
+                data.write_u32::<BigEndian>(hs.version)?;
+                data.write_u32::<BigEndian>(hs.flags.0)?;
+                data.write_u16::<BigEndian>(hs.recv_latency)?;
+                data.write_u16::<BigEndian>(hs.send_latency)?;
+
+                if matches!(self, Self::HsReq(_)) {
+                    ExtensionType::HsReq
+                } else {
+                    ExtensionType::HsRsp
+                }
+            }
+            Self::KmReq(km) => {
+                km.write(&mut data)?;
+                ExtensionType::KmReq
+            }
+            Self::KmRsp(km) => {
+                km.write(&mut data)?;
+                ExtensionType::KmRsp
+            }
+            Self::StreamId(id) => {
+                data.extend_from_slice(&encode_stream_id(id));
+                ExtensionType::StreamId
+            }
+            Self::Unknown {
+                extension_type,
+                data: raw,
+            } => {
+                data.extend_from_slice(raw);
+                *extension_type
+            }
+        };
+
+        // Extensions are always padded to 32-bit words.
+        data.resize(data.len().next_multiple_of(4), 0);
+
+        writer.write_u16::<BigEndian>(extension_type.0)?;
+        writer.write_u16::<BigEndian>((data.len() / 4) as u16)?;
+        writer.write_all(&data)
+    }
+}
+
+/// The stream id is sent as a sequence of 32-bit words with the bytes of each
+/// word reversed, padded with zeros.
+fn encode_stream_id(id: &str) -> Vec<u8> {
+    let mut data = id.as_bytes().to_vec();
+    data.resize(data.len().next_multiple_of(4), 0);
+
+    for word in data.chunks_exact_mut(4) {
+        word.reverse();
+    }
+
+    data
+}
+
+fn decode_stream_id(data: &[u8]) -> Result<String, SrtError> {
+    if data.len() > MAX_STREAM_ID_LEN {
+        return Err(SrtError::StreamIdTooLong(data.len()));
+    }
+
+    let mut data = data.to_vec();
+    for word in data.chunks_mut(4) {
+        word.reverse();
+    }
+
+    while data.last() == Some(&0) {
+        data.pop();
+    }
+
+    String::from_utf8(data).map_err(|_| SrtError::InvalidHandshake("stream id is not valid utf-8"))
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn conclusion_round_trip() {
+        let handshake = Handshake {
+            version: 5,
+            encryption: 0,
+            extension: extension_flags::HSREQ | extension_flags::CONFIG,
+            initial_seq: SeqNumber::new(1234),
+            mtu: 1500,
+            flow_window: 8192,
+            handshake_type: HandshakeType::Conclusion,
+            socket_id: 0x1122_3344,
+            syn_cookie: 0x5566_7788,
+            peer_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            extensions: vec![
+                HandshakeExtension::HsReq(SrtHandshake {
+                    version: SRT_VERSION,
+                    flags: SrtFlags::TSBPD_SEND | SrtFlags::TSBPD_RECV,
+                    recv_latency: 120,
+                    send_latency: 120,
+                }),
+                HandshakeExtension::StreamId("#!::r=live/test,m=publish".into()),
+            ],
+        };
+
+        let mut buf = Vec::new();
+        handshake.write(&mut buf).unwrap();
+        assert_eq!(&buf[32..36], &[1, 0, 0, 127]);
+
+        let parsed = Handshake::parse(&mut io::Cursor::new(Bytes::from(buf))).unwrap();
+        assert_eq!(parsed, handshake);
+        assert_eq!(parsed.stream_id(), Some("#!::r=live/test,m=publish"));
+        assert_eq!(parsed.srt_handshake().unwrap().recv_latency, 120);
+    }
+
+    #[test]
+    fn stream_id_encoding() {
+        assert_eq!(encode_stream_id("abcde"), b"dcba\0\0\0e");
+        assert_eq!(decode_stream_id(b"dcba\0\0\0e").unwrap(), "abcde");
+        assert!(matches!(decode_stream_id(&[b'a'; 516]), Err(SrtError::StreamIdTooLong(516))));
+    }
+
+    #[test]
+    fn reject_reason() {
+        assert_eq!(HandshakeType::Conclusion.reject_reason(), None);
+        assert_eq!(HandshakeType::Induction.reject_reason(), None);
+        assert_eq!(
+            HandshakeType::from(RejectReason::BadSecret).reject_reason(),
+            Some(RejectReason::BadSecret)
+        );
+        assert_eq!(HandshakeType(2404).reject_reason(), Some(RejectReason(2404)));
+    }
+}
@@ -0,0 +1,384 @@
+//! A pure Rust implementation of SRT (Secure Reliable Transport), for
+//! receiving live MPEG-TS contributions next to RTMP.
+//!
+//! This crate implements the caller and listener roles of SRT in live mode:
+//!
+//! - the version 5 caller/listener handshake with SYN cookies and reject
+//!   reasons,
+//! - ARQ with immediate and periodic loss reports and retransmissions,
+//! - timestamp based packet delivery (TSBPD) with a negotiated latency and
+//!   dropping of packets that arrive too late,
+//! - AES-CTR payload encryption with a passphrase,
+//! - `streamid` parsing and filtering of incoming connections.
+//!
+//! The [`ServerSession`] demuxes the received transport stream with
+//! [`scuffle-mpegts`][scuffle_mpegts] and exposes a
+//! [`SessionHandler`](session::server::SessionHandler) shaped like the one of
+//! the RTMP server session, so SRT and RTMP publishers can be handled the
+//! same way.
+#![cfg_attr(feature = "docs", doc = "\n\nSee the [changelog][changelog] for a full release history.")]
+#![cfg_attr(feature = "docs", doc = "## Feature flags")]
+#![cfg_attr(feature = "docs", doc = document_features::document_features!())]
+//! ## Example
+//!
+//! ```no_run
+//! # use scuffle_srt::{ServerSession, SrtConfig, SrtListener, StreamId};
+//! # use scuffle_srt::handshake::RejectReason;
+//! # use scuffle_srt::session::server::{ServerSessionError, SessionData, SessionHandler};
+//! #
+//! struct Handler;
+//!
+//! impl SessionHandler for Handler {
+//!     async fn on_data(&mut self, stream_id: u32, data: SessionData) -> Result<(), ServerSessionError> {
+//!         // Handle incoming video/audio data
+//!         Ok(())
+//!     }
+//!
+//!     async fn on_publish(&mut self, stream_id: u32, app_name: &str, stream_name: &str) -> Result<(), ServerSessionError> {
+//!         // Handle the publish event
+//!         Ok(())
+//!     }
+//!
+//!     async fn on_unpublish(&mut self, stream_id: u32) -> Result<(), ServerSessionError> {
+//!         // Handle the unpublish event
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let config = SrtConfig::default();
+//!
+//!     // Only accept publishers to the "live" app.
+//!     let filter = |_, stream_id: Option<&StreamId>| match stream_id {
+//!         Some(id) if id.app_and_stream_name().0 == "live" => Ok(()),
+//!         _ => Err(RejectReason::NotFound),
+//!     };
+//!
+//!     let mut listener = SrtListener::bind_with_filter("[::]:9000", config, filter).await.unwrap();
+//!     // listening on [::]:9000
+//!
+//!     while let Ok(socket) = listener.accept().await {
+//!         let session = ServerSession::new(socket, Handler);
+//!
+//!         tokio::spawn(async move {
+//!             if let Err(err) = session.run().await {
+//!                 // Handle the session error
+//!             }
+//!         });
+//!     }
+//! }
+//! ```
+//!
+//! ## Specifications
+//!
+//! | Name | Version | Link | Comments |
+//! | --- | --- | --- | --- |
+//! | The SRT Protocol | `draft-sharabayko-srt-01` | <https://datatracker.ietf.org/doc/html/draft-sharabayko-srt-01> | |
+//! | SRT Access Control Guidelines | | <https://github.com/Haivision/srt/blob/master/docs/features/access-control.md> | Stream id syntax and reject reasons |
+//! | Advanced Encryption Standard (AES) Key Wrap Algorithm | `RFC 3394` | <https://datatracker.ietf.org/doc/html/rfc3394> | |
+//!
+//! ## License
+//!
+//! This project is licensed under the MIT or Apache-2.0 license.
+//! You can choose between one of them if you use this work.
+//!
+//! `SPDX-License-Identifier: MIT OR Apache-2.0`
+#![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
+#![deny(unreachable_pub)]
+#![deny(clippy::mod_module_files)]
+
+mod config;
+mod connection;
+mod listener;
+mod seq;
+mod socket;
+mod stream_id;
+
+pub mod crypto;
+pub mod error;
+pub mod handshake;
+pub mod packet;
+pub mod session;
+
+pub use config::SrtConfig;
+pub use listener::{ConnectionFilter, SrtListener};
+pub use seq::SeqNumber;
+pub use session::server::ServerSession;
+pub use socket::{SrtSocket, Statistics};
+pub use stream_id::{StreamId, StreamMode};
+
+/// Changelogs generated by [scuffle_changelog]
+#[cfg(feature = "docs")]
+#[scuffle_changelog::changelog]
+pub mod changelog {}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use scuffle_mpegts::{Codec, Frame, TsMuxer};
+    use tokio::net::UdpSocket;
+    use tokio::sync::mpsc;
+
+    use crate::error::SrtError;
+    use crate::handshake::RejectReason;
+    use crate::session::server::{ServerSessionError, SessionData, SessionHandler};
+    use crate::{ServerSession, SrtConfig, SrtListener, SrtSocket, StreamId};
+
+    /// Forwards datagrams between a caller and `target`, dropping every
+    /// `loss_interval`-th data packet sent by the caller.
+    async fn lossy_proxy(target: SocketAddr, loss_interval: usize) -> SocketAddr {
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut caller = None;
+            let mut data_packets = 0;
+            let mut buf = vec![0; 65536];
+
+            loop {
+                let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+                if from == target {
+                    if let Some(caller) = caller {
+                        socket.send_to(&buf[..len], caller).await.unwrap();
+                    }
+                    continue;
+                }
+
+                caller = Some(from);
+                if buf[0] & 0x80 == 0 {
+                    data_packets += 1;
+                    if data_packets % loss_interval == 0 {
+                        continue;
+                    }
+                }
+
+                socket.send_to(&buf[..len], target).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn transfer_with_loss_and_encryption() {
+        let config = SrtConfig {
+            passphrase: Some("correct horse battery".into()),
+            ..Default::default()
+        };
+
+        let mut listener = SrtListener::bind("127.0.0.1:0", config.clone()).await.unwrap();
+        let proxy = lossy_proxy(listener.local_addr(), 5).await;
+
+        let caller = SrtSocket::connect(
+            proxy,
+            SrtConfig {
+                stream_id: Some("#!::r=live/test,m=publish".into()),
+                key_length: 32,
+                ..config
+            },
+        )
+        .await
+        .unwrap();
+        let mut accepted = listener.accept().await.unwrap();
+
+        assert!(caller.is_encrypted());
+        assert!(accepted.is_encrypted());
+        assert_eq!(accepted.stream_id().unwrap().resource, "live/test");
+        assert_eq!(accepted.latency(), Duration::from_millis(120));
+
+        for i in 0..100u32 {
+            caller.send(Bytes::from(i.to_be_bytes().repeat(300))).await.unwrap();
+        }
+
+        for i in 0..100u32 {
+            let payload = accepted.recv().await.unwrap().unwrap();
+            assert_eq!(payload, Bytes::from(i.to_be_bytes().repeat(300)));
+        }
+
+        assert!(accepted.stats().packets_lost > 0);
+        assert!(caller.stats().packets_retransmitted >= accepted.stats().packets_lost);
+
+        caller.close().await;
+        assert!(accepted.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reject() {
+        let filter = |_, stream_id: Option<&StreamId>| match stream_id {
+            Some(id) if id.resource == "live/allowed" => Ok(()),
+            _ => Err(RejectReason::Forbidden),
+        };
+
+        let config = SrtConfig {
+            passphrase: Some("correct horse battery".into()),
+            ..Default::default()
+        };
+
+        let listener = SrtListener::bind_with_filter("127.0.0.1:0", config.clone(), filter)
+            .await
+            .unwrap();
+        let addr = listener.local_addr();
+
+        let connect = |stream_id: &str, passphrase: Option<&str>| {
+            SrtSocket::connect(
+                addr,
+                SrtConfig {
+                    stream_id: Some(stream_id.into()),
+                    passphrase: passphrase.map(Into::into),
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert!(matches!(
+            connect("live/denied", Some("correct horse battery")).await,
+            Err(SrtError::Rejected(RejectReason::Forbidden))
+        ));
+        assert!(matches!(
+            connect("live/allowed", Some("wrong horse battery")).await,
+            Err(SrtError::Rejected(RejectReason::BadSecret))
+        ));
+        assert!(matches!(
+            connect("live/allowed", None).await,
+            Err(SrtError::Rejected(RejectReason::Unsecure))
+        ));
+        assert!(connect("live/allowed", Some("correct horse battery")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn connect_timeout() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let result = SrtSocket::connect(
+            socket.local_addr().unwrap(),
+            SrtConfig {
+                connect_timeout: Duration::from_millis(100),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(SrtError::ConnectTimeout)));
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Publish(String, String),
+        Stream(u16),
+        Audio(u32, Bytes),
+        Unpublish,
+    }
+
+    struct Handler(mpsc::UnboundedSender<Event>);
+
+    impl SessionHandler for Handler {
+        async fn on_publish(&mut self, _: u32, app_name: &str, stream_name: &str) -> Result<(), ServerSessionError> {
+            self.0.send(Event::Publish(app_name.into(), stream_name.into())).unwrap();
+            Ok(())
+        }
+
+        async fn on_unpublish(&mut self, _: u32) -> Result<(), ServerSessionError> {
+            self.0.send(Event::Unpublish).unwrap();
+            Ok(())
+        }
+
+        async fn on_data(&mut self, _: u32, data: SessionData) -> Result<(), ServerSessionError> {
+            match data {
+                SessionData::Stream(stream) => self.0.send(Event::Stream(stream.pid)).unwrap(),
+                SessionData::Audio { timestamp, frame } => self.0.send(Event::Audio(timestamp, frame.data)).unwrap(),
+                SessionData::Video { .. } => unreachable!(),
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn server_session() {
+        let mut listener = SrtListener::bind("127.0.0.1:0", SrtConfig::default()).await.unwrap();
+        let proxy = lossy_proxy(listener.local_addr(), 7).await;
+
+        let caller = SrtSocket::connect(
+            proxy,
+            SrtConfig {
+                stream_id: Some("live/key".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let session = ServerSession::new(listener.accept().await.unwrap(), Handler(tx));
+        let session = tokio::spawn(session.run());
+
+        let codec = Codec::Opus { channel_count: 2 };
+        let mut muxer = TsMuxer::new();
+        let pid = muxer.add_stream(codec, None).unwrap();
+
+        let mut ts = Vec::new();
+        for i in 0..50u64 {
+            muxer
+                .write_frame(
+                    &mut ts,
+                    &Frame {
+                        pid,
+                        codec,
+                        pts: i * 1800,
+                        dts: i * 1800,
+                        keyframe: true,
+                        discontinuity: false,
+                        data: Bytes::from(vec![0xFC, i as u8]),
+                    },
+                )
+                .unwrap();
+        }
+
+        for chunk in ts.chunks(1316) {
+            caller.send(Bytes::copy_from_slice(chunk)).await.unwrap();
+        }
+
+        // Give the lost packets time to be retransmitted before shutting down.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        caller.close().await;
+
+        assert!(session.await.unwrap().unwrap());
+
+        assert_eq!(rx.recv().await, Some(Event::Publish("live".into(), "key".into())));
+        assert_eq!(rx.recv().await, Some(Event::Stream(pid)));
+        for i in 0..50u32 {
+            assert_eq!(rx.recv().await, Some(Event::Audio(i * 20, Bytes::from(vec![0xFC, i as u8]))));
+        }
+        assert_eq!(rx.recv().await, Some(Event::Unpublish));
+    }
+
+    #[tokio::test]
+    async fn play_not_supported() {
+        let mut listener = SrtListener::bind("127.0.0.1:0", SrtConfig::default()).await.unwrap();
+
+        let _caller = SrtSocket::connect(
+            listener.local_addr(),
+            SrtConfig {
+                stream_id: Some("#!::r=live/key".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let session = ServerSession::new(listener.accept().await.unwrap(), Handler(tx));
+        assert!(matches!(
+            session.run().await,
+            Err(SrtError::Session(ServerSessionError::PlayNotSupported))
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+//! The SRT listener.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher, RandomState};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use tokio::net::{ToSocketAddrs, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::config::SrtConfig;
+use crate::connection::ConnectionParams;
+use crate::crypto::{Crypto, KmResponse, validate_passphrase};
+use crate::error::SrtError;
+use crate::handshake::{
+    HANDSHAKE_MAGIC, Handshake, HandshakeExtension, HandshakeType, RejectReason, SRT_VERSION, SrtHandshake, extension_flags,
+};
+use crate::packet::{ControlKind, ControlPacket, Packet};
+use crate::socket::{
+    MAX_DATAGRAM_SIZE, PACKET_QUEUE_SIZE, SRT_FLAGS, SocketParts, SrtSocket, negotiate_latency, payload_size,
+    random_socket_id, send_control,
+};
+use crate::stream_id::StreamId;
+
+/// The number of accepted connections waiting for [`SrtListener::accept`].
+const ACCEPT_BACKLOG: usize = 64;
+
+/// SYN cookies are valid for the current and the previous period.
+const COOKIE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Decides whether an incoming connection is accepted.
+///
+/// This is called before the handshake is answered, so a rejected caller
+/// receives the [`RejectReason`].
+pub trait ConnectionFilter: Send + Sync + 'static {
+    /// Returns an error to reject the connection.
+    fn filter(&mut self, peer_addr: SocketAddr, stream_id: Option<&StreamId>) -> Result<(), RejectReason>;
+}
+
+impl<F> ConnectionFilter for F
+where
+    F: FnMut(SocketAddr, Option<&StreamId>) -> Result<(), RejectReason> + Send + Sync + 'static,
+{
+    fn filter(&mut self, peer_addr: SocketAddr, stream_id: Option<&StreamId>) -> Result<(), RejectReason> {
+        self(peer_addr, stream_id)
+    }
+}
+
+/// A SRT listener.
+///
+/// All connections share the listener's UDP socket. The listener keeps
+/// routing packets to accepted connections after it is dropped, until all of
+/// them are closed.
+#[derive(Debug)]
+pub struct SrtListener {
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<SrtSocket>,
+}
+
+impl SrtListener {
+    /// Binds a listener accepting all connections.
+    pub async fn bind(addr: impl ToSocketAddrs, config: SrtConfig) -> Result<Self, SrtError> {
+        Self::bind_with_filter(addr, config, |_, _: Option<&StreamId>| Ok(())).await
+    }
+
+    /// Binds a listener that uses `filter` to decide which connections are
+    /// accepted, for example based on the stream id.
+    pub async fn bind_with_filter(
+        addr: impl ToSocketAddrs,
+        config: SrtConfig,
+        filter: impl ConnectionFilter,
+    ) -> Result<Self, SrtError> {
+        if let Some(passphrase) = &config.passphrase {
+            validate_passphrase(passphrase, config.key_length)?;
+        }
+
+        let udp = Arc::new(UdpSocket::bind(addr).await?);
+        let local_addr = udp.local_addr()?;
+        let (accepted_tx, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+        let (closed_tx, closed) = mpsc::unbounded_channel();
+
+        let driver = ListenerDriver {
+            udp,
+            config,
+            filter,
+            start: Instant::now(),
+            cookie_key: RandomState::new(),
+            connections: HashMap::new(),
+            peers: HashMap::new(),
+            accepted: accepted_tx,
+            closed_tx,
+            closed,
+        };
+
+        tokio::spawn(driver.run());
+
+        Ok(Self { local_addr, accepted })
+    }
+
+    /// Returns the local address of the listener.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Waits for the next connection.
+    pub async fn accept(&mut self) -> Result<SrtSocket, SrtError> {
+        self.accepted.recv().await.ok_or(SrtError::Closed)
+    }
+}
+
+struct Accepted {
+    packets: mpsc::Sender<(Instant, Packet)>,
+    peer: (SocketAddr, u32),
+    response: Handshake,
+}
+
+struct ListenerDriver<F> {
+    udp: Arc<UdpSocket>,
+    config: SrtConfig,
+    filter: F,
+    start: Instant,
+    cookie_key: RandomState,
+    /// The accepted connections by local socket id.
+    connections: HashMap<u32, Accepted>,
+    /// The local socket id by peer address and peer socket id.
+    peers: HashMap<(SocketAddr, u32), u32>,
+    accepted: mpsc::Sender<SrtSocket>,
+    closed_tx: mpsc::UnboundedSender<u32>,
+    closed: mpsc::UnboundedReceiver<u32>,
+}
+
+impl<F: ConnectionFilter> ListenerDriver<F> {
+    async fn run(mut self) {
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+
+        loop {
+            // Keep running after the listener was dropped until all accepted
+            // connections are closed.
+            if self.accepted.is_closed() && self.connections.is_empty() {
+                return;
+            }
+
+            tokio::select! {
+                result = self.udp.recv_from(&mut buf) => match result {
+                    Ok((len, from)) => {
+                        let data = Bytes::copy_from_slice(&buf[..len]);
+                        if let Err(err) = self.handle_datagram(data, from).await {
+                            tracing::debug!(from = %from, err = %err, "failed to handle packet");
+                        }
+                    }
+                    Err(err) => tracing::debug!(err = %err, "failed to receive packet"),
+                },
+                Some(socket_id) = self.closed.recv() => {
+                    if let Some(accepted) = self.connections.remove(&socket_id) {
+                        self.peers.remove(&accepted.peer);
+                    }
+                }
+                _ = self.accepted.closed(), if !self.accepted.is_closed() => {}
+            }
+        }
+    }
+
+    async fn handle_datagram(&mut self, data: Bytes, from: SocketAddr) -> Result<(), SrtError> {
+        let now = Instant::now();
+        let packet = Packet::parse(data)?;
+
+        let dest_socket_id = packet.dest_socket_id();
+        if dest_socket_id != 0 {
+            if let Some(accepted) = self.connections.get(&dest_socket_id)
+                && accepted.packets.try_send((now, packet)).is_err()
+            {
+                tracing::trace!(socket_id = dest_socket_id, "connection queue full, dropping packet");
+            }
+
+            return Ok(());
+        }
+
+        let Packet::Control(ControlPacket {
+            timestamp,
+            kind: ControlKind::Handshake(handshake),
+            ..
+        }) = packet
+        else {
+            return Ok(());
+        };
+
+        match handshake.handshake_type {
+            HandshakeType::Induction => {
+                let response = Handshake {
+                    version: 5,
+                    encryption: 0,
+                    extension: HANDSHAKE_MAGIC,
+                    handshake_type: HandshakeType::Induction,
+                    socket_id: 0,
+                    syn_cookie: self.cookie(from, 0),
+                    peer_ip: from.ip(),
+                    extensions: Vec::new(),
+                    ..handshake
+                };
+
+                self.respond(from, handshake.socket_id, response).await
+            }
+            HandshakeType::Conclusion => self.handle_conclusion(now, timestamp, handshake, from).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn handle_conclusion(
+        &mut self,
+        now: Instant,
+        timestamp: u32,
+        handshake: Handshake,
+        from: SocketAddr,
+    ) -> Result<(), SrtError> {
+        // The caller did not receive our response, repeat it.
+        if let Some(socket_id) = self.peers.get(&(from, handshake.socket_id)) {
+            let response = self.connections[socket_id].response.clone();
+            return self.respond(from, handshake.socket_id, response).await;
+        }
+
+        if handshake.syn_cookie != self.cookie(from, 0) && handshake.syn_cookie != self.cookie(from, 1) {
+            tracing::debug!(from = %from, "ignoring conclusion with invalid cookie");
+            return Ok(());
+        }
+
+        let params = match self.accept(now, timestamp, &handshake, from) {
+            Ok(params) => params,
+            Err(reason) => {
+                tracing::debug!(from = %from, reason = ?reason, "rejecting connection");
+                return self.reject(from, &handshake, reason).await;
+            }
+        };
+
+        let Ok(permit) = self.accepted.try_reserve() else {
+            return self.reject(from, &handshake, RejectReason::Backlog).await;
+        };
+
+        let mut extension = extension_flags::HSREQ;
+        let mut extensions = vec![HandshakeExtension::HsRsp(SrtHandshake {
+            version: SRT_VERSION,
+            flags: SRT_FLAGS,
+            recv_latency: params.recv_latency.as_millis() as u16,
+            send_latency: params.send_latency.as_millis() as u16,
+        })];
+
+        if let Some(km) = handshake.key_material_request() {
+            extension |= extension_flags::KMREQ;
+            extensions.push(HandshakeExtension::KmRsp(KmResponse::Material(km.clone())));
+        }
+
+        let response = Handshake {
+            version: 5,
+            extension,
+            mtu: self.config.mtu.min(handshake.mtu),
+            flow_window: params.flow_window,
+            socket_id: params.local_socket_id,
+            peer_ip: from.ip(),
+            extensions,
+            ..handshake.clone()
+        };
+
+        let socket_id = params.local_socket_id;
+        let (packets_tx, packets) = mpsc::channel(PACKET_QUEUE_SIZE);
+
+        permit.send(SrtSocket::spawn(SocketParts {
+            params,
+            udp: self.udp.clone(),
+            peer_addr: from,
+            stream_id: handshake.stream_id().map(StreamId::parse),
+            packets,
+            on_close: Some(self.closed_tx.clone()),
+        }));
+
+        self.peers.insert((from, handshake.socket_id), socket_id);
+        self.connections.insert(
+            socket_id,
+            Accepted {
+                packets: packets_tx,
+                peer: (from, handshake.socket_id),
+                response: response.clone(),
+            },
+        );
+
+        tracing::debug!(from = %from, socket_id, stream_id = ?handshake.stream_id(), "accepted connection");
+
+        self.respond(from, handshake.socket_id, response).await
+    }
+
+    fn accept(
+        &mut self,
+        now: Instant,
+        timestamp: u32,
+        handshake: &Handshake,
+        from: SocketAddr,
+    ) -> Result<ConnectionParams, RejectReason> {
+        if self.accepted.is_closed() {
+            return Err(RejectReason::Close);
+        }
+
+        let hs = match handshake.srt_handshake() {
+            Some(hs) if handshake.version >= 5 => hs,
+            _ => return Err(RejectReason::Version),
+        };
+
+        let crypto = match (handshake.key_material_request(), &self.config.passphrase) {
+            (Some(km), Some(passphrase)) => match Crypto::from_key_material(km, passphrase) {
+                Ok(crypto) => Some(crypto),
+                Err(SrtError::BadSecret) => return Err(RejectReason::BadSecret),
+                Err(_) => return Err(RejectReason::Rogue),
+            },
+            (None, None) => None,
+            _ => return Err(RejectReason::Unsecure),
+        };
+
+        let stream_id = handshake.stream_id().map(StreamId::parse);
+        self.filter.filter(from, stream_id.as_ref())?;
+
+        let socket_id = loop {
+            let id = random_socket_id();
+            if !self.connections.contains_key(&id) {
+                break id;
+            }
+        };
+
+        Ok(ConnectionParams {
+            local_socket_id: socket_id,
+            peer_socket_id: handshake.socket_id,
+            initial_seq: handshake.initial_seq,
+            flow_window: self.config.flow_window.min(handshake.flow_window),
+            payload_size: payload_size(&self.config, self.config.mtu.min(handshake.mtu)),
+            recv_latency: negotiate_latency(self.config.latency, hs.send_latency),
+            send_latency: negotiate_latency(self.config.latency, hs.recv_latency),
+            too_late_packet_drop: self.config.too_late_packet_drop,
+            peer_idle_timeout: self.config.peer_idle_timeout,
+            crypto,
+            start: now,
+            peer_time_base: now.checked_sub(Duration::from_micros(u64::from(timestamp))).unwrap_or(now),
+        })
+    }
+
+    async fn reject(&self, to: SocketAddr, handshake: &Handshake, reason: RejectReason) -> Result<(), SrtError> {
+        let response = Handshake {
+            version: 5,
+            handshake_type: reason.into(),
+            socket_id: 0,
+            peer_ip: to.ip(),
+            extensions: Vec::new(),
+            ..handshake.clone()
+        };
+
+        self.respond(to, handshake.socket_id, response).await
+    }
+
+    async fn respond(&self, to: SocketAddr, dest_socket_id: u32, handshake: Handshake) -> Result<(), SrtError> {
+        let timestamp = self.start.elapsed().as_micros() as u32;
+        send_control(&self.udp, to, timestamp, dest_socket_id, ControlKind::Handshake(handshake)).await
+    }
+
+    /// Returns the SYN cookie of the peer for the current period minus
+    /// `periods_ago`.
+    fn cookie(&self, peer: SocketAddr, periods_ago: u64) -> u32 {
+        let period = (self.start.elapsed().as_secs() / COOKIE_PERIOD.as_secs()).wrapping_sub(periods_ago);
+
+        let mut hasher = self.cookie_key.build_hasher();
+        peer.hash(&mut hasher);
+        period.hash(&mut hasher);
+        hasher.finish() as u32
+    }
+}
@@ -0,0 +1,489 @@
+//! SRT packet types.
+//!
+//! Every SRT packet starts with a 16 byte header. The most significant bit of
+//! the first word decides if the packet is a data packet or a control packet.
+
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::error::SrtError;
+use crate::handshake::Handshake;
+use crate::seq::SeqNumber;
+
+/// The size of the SRT packet header.
+pub const HEADER_SIZE: usize = 16;
+
+/// The size of the IP and UDP headers subtracted from the MTU to get the
+/// maximum size of a SRT packet.
+pub const UDP_HEADER_SIZE: usize = 28;
+
+nutype_enum::nutype_enum! {
+    /// The position of a data packet within its message.
+    pub enum PacketPosition(u8) {
+        /// The packet is in the middle of a message.
+        Middle = 0b00,
+        /// The packet is the last packet of a message.
+        Last = 0b01,
+        /// The packet is the first packet of a message.
+        First = 0b10,
+        /// The packet contains a complete message.
+        Solo = 0b11,
+    }
+}
+
+nutype_enum::nutype_enum! {
+    /// The key used to encrypt the payload of a data packet.
+    pub enum KeyIndex(u8) {
+        /// The payload is not encrypted.
+        Unencrypted = 0b00,
+        /// The payload is encrypted with the even key.
+        Even = 0b01,
+        /// The payload is encrypted with the odd key.
+        Odd = 0b10,
+    }
+}
+
+nutype_enum::nutype_enum! {
+    /// The type of a control packet.
+    pub enum ControlType(u16) {
+        /// Handshake.
+        Handshake = 0x0000,
+        /// Keep-alive.
+        KeepAlive = 0x0001,
+        /// Acknowledgement.
+        Ack = 0x0002,
+        /// Negative acknowledgement (loss report).
+        Nak = 0x0003,
+        /// Congestion warning.
+        CongestionWarning = 0x0004,
+        /// Shutdown.
+        Shutdown = 0x0005,
+        /// Acknowledgement of an acknowledgement.
+        AckAck = 0x0006,
+        /// Message drop request.
+        DropRequest = 0x0007,
+        /// Peer error.
+        PeerError = 0x0008,
+        /// User defined control packet.
+        UserDefined = 0x7FFF,
+    }
+}
+
+/// A SRT packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    /// A data packet.
+    Data(DataPacket),
+    /// A control packet.
+    Control(ControlPacket),
+}
+
+impl Packet {
+    /// Parses a packet from a single UDP datagram.
+    pub fn parse(data: Bytes) -> Result<Self, SrtError> {
+        if data.len() < HEADER_SIZE {
+            return Err(SrtError::PacketTooShort(data.len()));
+        }
+
+        let mut reader = io::Cursor::new(data);
+        let word0 = reader.read_u32::<BigEndian>()?;
+        let word1 = reader.read_u32::<BigEndian>()?;
+        let timestamp = reader.read_u32::<BigEndian>()?;
+        let dest_socket_id = reader.read_u32::<BigEndian>()?;
+
+        if word0 & 0x8000_0000 == 0 {
+            return Ok(Self::Data(DataPacket {
+                seq: SeqNumber::new(word0),
+                position: PacketPosition::from((word1 >> 30) as u8),
+                in_order: word1 & (1 << 29) != 0,
+                key: KeyIndex::from(((word1 >> 27) & 0b11) as u8),
+                retransmitted: word1 & (1 << 26) != 0,
+                message_number: word1 & MESSAGE_NUMBER_MASK,
+                timestamp,
+                dest_socket_id,
+                payload: reader.extract_remaining(),
+            }));
+        }
+
+        let control_type = ControlType::from(((word0 >> 16) & 0x7FFF) as u16);
+        let subtype = (word0 & 0xFFFF) as u16;
+
+        let kind = match control_type {
+            ControlType::Handshake => ControlKind::Handshake(Handshake::parse(&mut reader)?),
+            ControlType::KeepAlive => ControlKind::KeepAlive,
+            ControlType::Ack => ControlKind::Ack(Ack::parse(word1, &mut reader)?),
+            ControlType::Nak => ControlKind::Nak(parse_loss_list(&mut reader)?),
+            ControlType::Shutdown => ControlKind::Shutdown,
+            ControlType::AckAck => ControlKind::AckAck(word1),
+            ControlType::DropRequest => ControlKind::DropRequest {
+                message_number: word1,
+                first: SeqNumber::new(reader.read_u32::<BigEndian>()?),
+                last: SeqNumber::new(reader.read_u32::<BigEndian>()?),
+            },
+            _ => ControlKind::Unknown {
+                control_type,
+                subtype,
+                type_specific: word1,
+                data: reader.extract_remaining(),
+            },
+        };
+
+        Ok(Self::Control(ControlPacket {
+            timestamp,
+            dest_socket_id,
+            kind,
+        }))
+    }
+
+    /// Writes the packet.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        match self {
+            Self::Data(packet) => packet.write(writer),
+            Self::Control(packet) => packet.write(writer),
+        }
+    }
+
+    /// Returns the destination socket id of the packet.
+    pub fn dest_socket_id(&self) -> u32 {
+        match self {
+            Self::Data(packet) => packet.dest_socket_id,
+            Self::Control(packet) => packet.dest_socket_id,
+        }
+    }
+}
+
+const MESSAGE_NUMBER_MASK: u32 = 0x03FF_FFFF;
+
+/// A data packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataPacket {
+    /// The sequence number of the packet.
+    pub seq: SeqNumber,
+    /// The position of the packet within its message.
+    pub position: PacketPosition,
+    /// If the message must be delivered in order.
+    pub in_order: bool,
+    /// The key used to encrypt the payload.
+    pub key: KeyIndex,
+    /// If the packet is a retransmission.
+    pub retransmitted: bool,
+    /// The 26-bit message number.
+    pub message_number: u32,
+    /// The time the packet was scheduled for sending, in microseconds since the
+    /// sender's connection start.
+    pub timestamp: u32,
+    /// The socket id of the receiver.
+    pub dest_socket_id: u32,
+    /// The payload of the packet.
+    pub payload: Bytes,
+}
+
+impl DataPacket {
+    /// Writes the packet.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.seq.value())?;
+        writer.write_u32::<BigEndian>(
+            (u32::from(self.position.0 & 0b11) << 30)
+                | (u32::from(self.in_order) << 29)
+                | (u32::from(self.key.0 & 0b11) << 27)
+                | (u32::from(self.retransmitted) << 26)
+                | (self.message_number & MESSAGE_NUMBER_MASK),
+        )?;
+        writer.write_u32::<BigEndian>(self.timestamp)?;
+        writer.write_u32::<BigEndian>(self.dest_socket_id)?;
+        writer.write_all(&self.payload)
+    }
+}
+
+/// A control packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlPacket {
+    /// The time the packet was sent, in microseconds since the sender's
+    /// connection start.
+    pub timestamp: u32,
+    /// The socket id of the receiver.
+    pub dest_socket_id: u32,
+    /// The type specific contents of the packet.
+    pub kind: ControlKind,
+}
+
+/// The contents of a control packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlKind {
+    /// A handshake.
+    Handshake(Handshake),
+    /// A keep-alive.
+    KeepAlive,
+    /// An acknowledgement.
+    Ack(Ack),
+    /// A loss report containing inclusive ranges of lost sequence numbers.
+    Nak(Vec<(SeqNumber, SeqNumber)>),
+    /// A shutdown.
+    Shutdown,
+    /// The acknowledgement of the ack with the given ack number.
+    AckAck(u32),
+    /// A request to drop a message that will not be retransmitted.
+    DropRequest {
+        /// The message number of the dropped message.
+        message_number: u32,
+        /// The first sequence number of the message.
+        first: SeqNumber,
+        /// The last sequence number of the message.
+        last: SeqNumber,
+    },
+    /// A control packet that is not handled by this crate.
+    Unknown {
+        /// The control type.
+        control_type: ControlType,
+        /// The control subtype.
+        subtype: u16,
+        /// The type specific information.
+        type_specific: u32,
+        /// The control information field.
+        data: Bytes,
+    },
+}
+
+impl ControlKind {
+    fn header(&self) -> (ControlType, u16, u32) {
+        match self {
+            Self::Handshake(_) => (ControlType::Handshake, 0, 0),
+            Self::KeepAlive => (ControlType::KeepAlive, 0, 0),
+            Self::Ack(ack) => (ControlType::Ack, 0, ack.ack_number),
+            Self::Nak(_) => (ControlType::Nak, 0, 0),
+            Self::Shutdown => (ControlType::Shutdown, 0, 0),
+            Self::AckAck(ack_number) => (ControlType::AckAck, 0, *ack_number),
+            Self::DropRequest { message_number, .. } => (ControlType::DropRequest, 0, *message_number),
+            Self::Unknown {
+                control_type,
+                subtype,
+                type_specific,
+                ..
+            } => (*control_type, *subtype, *type_specific),
+        }
+    }
+}
+
+impl ControlPacket {
+    /// Writes the packet.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        let (control_type, subtype, type_specific) = self.kind.header();
+
+        writer.write_u32::<BigEndian>(0x8000_0000 | (u32::from(control_type.0 & 0x7FFF) << 16) | u32::from(subtype))?;
+        writer.write_u32::<BigEndian>(type_specific)?;
+        writer.write_u32::<BigEndian>(self.timestamp)?;
+        writer.write_u32::<BigEndian>(self.dest_socket_id)?;
+
+        match &self.kind {
+            ControlKind::Handshake(handshake) => handshake.write(writer),
+            ControlKind::Ack(ack) => ack.write(writer),
+            ControlKind::Nak(ranges) => {
+                for (first, last) in ranges {
+                    if first == last {
+                        writer.write_u32::<BigEndian>(first.value())?;
+                    } else {
+                        writer.write_u32::<BigEndian>(0x8000_0000 | first.value())?;
+                        writer.write_u32::<BigEndian>(last.value())?;
+                    }
+                }
+
+                Ok(())
+            }
+            // Keep-alive and shutdown packets carry a single padding word.
+            ControlKind::KeepAlive | ControlKind::Shutdown | ControlKind::AckAck(_) => writer.write_u32::<BigEndian>(0),
+            ControlKind::DropRequest { first, last, .. } => {
+                writer.write_u32::<BigEndian>(first.value())?;
+                writer.write_u32::<BigEndian>(last.value())
+            }
+            ControlKind::Unknown { data, .. } => writer.write_all(data),
+        }
+    }
+}
+
+/// An acknowledgement.
+///
+/// A light ack only carries the acknowledged sequence number, a full ack also
+/// carries [`AckInfo`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ack {
+    /// The ack number, echoed back in the ack of ack.
+    ///
+    /// This is `0` for light acks.
+    pub ack_number: u32,
+    /// The sequence number following the last packet that was received
+    /// without any gaps.
+    pub last_ack: SeqNumber,
+    /// The statistics of the receiver, only sent in full acks.
+    pub info: Option<AckInfo>,
+}
+
+/// The receiver statistics sent in a full ack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AckInfo {
+    /// The round trip time in microseconds.
+    pub rtt: u32,
+    /// The round trip time variance in microseconds.
+    pub rtt_variance: u32,
+    /// The available receive buffer size in packets.
+    pub available_buffer: u32,
+    /// The receive rate in packets per second.
+    pub packet_receive_rate: u32,
+    /// The estimated link capacity in packets per second.
+    pub link_capacity: u32,
+    /// The receive rate in bytes per second.
+    pub receive_rate: u32,
+}
+
+impl Ack {
+    fn parse(ack_number: u32, reader: &mut io::Cursor<Bytes>) -> Result<Self, SrtError> {
+        let last_ack = SeqNumber::new(reader.read_u32::<BigEndian>()?);
+
+        let mut fields = [0; 6];
+        let mut count = 0;
+        while count < fields.len() && reader.get_ref().len() as u64 - reader.position() >= 4 {
+            fields[count] = reader.read_u32::<BigEndian>()?;
+            count += 1;
+        }
+
+        let info = (count > 0).then_some(AckInfo {
+            rtt: fields[0],
+            rtt_variance: fields[1],
+            available_buffer: fields[2],
+            packet_receive_rate: fields[3],
+            link_capacity: fields[4],
+            receive_rate: fields[5],
+        });
+
+        Ok(Self {
+            ack_number,
+            last_ack,
+            info,
+        })
+    }
+
+    fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.last_ack.value())?;
+
+        if let Some(info) = &self.info {
+            for field in [
+                info.rtt,
+                info.rtt_variance,
+                info.available_buffer,
+                info.packet_receive_rate,
+                info.link_capacity,
+                info.receive_rate,
+            ] {
+                writer.write_u32::<BigEndian>(field)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_loss_list(reader: &mut io::Cursor<Bytes>) -> Result<Vec<(SeqNumber, SeqNumber)>, SrtError> {
+    let mut ranges = Vec::new();
+
+    while reader.get_ref().len() as u64 - reader.position() >= 4 {
+        let first = reader.read_u32::<BigEndian>()?;
+        if first & 0x8000_0000 != 0 {
+            let last = reader.read_u32::<BigEndian>()?;
+            ranges.push((SeqNumber::new(first), SeqNumber::new(last)));
+        } else {
+            ranges.push((SeqNumber::new(first), SeqNumber::new(first)));
+        }
+    }
+
+    Ok(ranges)
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    fn round_trip(packet: Packet) {
+        let mut buf = Vec::new();
+        packet.write(&mut buf).unwrap();
+        assert_eq!(Packet::parse(Bytes::from(buf)).unwrap(), packet);
+    }
+
+    #[test]
+    fn data_packet() {
+        let packet = Packet::Data(DataPacket {
+            seq: SeqNumber::new(0x1234_5678),
+            position: PacketPosition::Solo,
+            in_order: false,
+            key: KeyIndex::Even,
+            retransmitted: true,
+            message_number: 0x02AB_CDEF,
+            timestamp: 42,
+            dest_socket_id: 0xDEAD_BEEF,
+            payload: Bytes::from_static(b"hello"),
+        });
+
+        let mut buf = Vec::new();
+        packet.write(&mut buf).unwrap();
+        assert_eq!(&buf[..8], &[0x12, 0x34, 0x56, 0x78, 0xCE, 0xAB, 0xCD, 0xEF]);
+
+        round_trip(packet);
+    }
+
+    #[test]
+    fn control_packets() {
+        for kind in [
+            ControlKind::KeepAlive,
+            ControlKind::Shutdown,
+            ControlKind::AckAck(7),
+            ControlKind::Ack(Ack {
+                ack_number: 0,
+                last_ack: SeqNumber::new(10),
+                info: None,
+            }),
+            ControlKind::Ack(Ack {
+                ack_number: 3,
+                last_ack: SeqNumber::new(10),
+                info: Some(AckInfo {
+                    rtt: 100_000,
+                    rtt_variance: 50_000,
+                    available_buffer: 8192,
+                    ..Default::default()
+                }),
+            }),
+            ControlKind::Nak(vec![
+                (SeqNumber::new(1), SeqNumber::new(1)),
+                (SeqNumber::new(5), SeqNumber::new(9)),
+            ]),
+            ControlKind::DropRequest {
+                message_number: 5,
+                first: SeqNumber::new(3),
+                last: SeqNumber::new(4),
+            },
+            ControlKind::Unknown {
+                control_type: ControlType::UserDefined,
+                subtype: 1,
+                type_specific: 2,
+                data: Bytes::from_static(&[1, 2, 3, 4]),
+            },
+        ] {
+            round_trip(Packet::Control(ControlPacket {
+                timestamp: 1000,
+                dest_socket_id: 1,
+                kind,
+            }));
+        }
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(matches!(
+            Packet::parse(Bytes::from_static(&[0; 15])),
+            Err(SrtError::PacketTooShort(15))
+        ));
+    }
+}
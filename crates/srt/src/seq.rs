@@ -0,0 +1,78 @@
+//! Packet sequence numbers.
+
+use std::fmt;
+use std::ops::{Add, Sub};
+
+/// A 31-bit packet sequence number.
+///
+/// Sequence numbers wrap around after [`SeqNumber::MAX`], so they can only be
+/// compared by their (signed) distance to each other.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SeqNumber(u32);
+
+impl SeqNumber {
+    /// The largest possible sequence number.
+    pub const MAX: u32 = 0x7FFF_FFFF;
+
+    /// Creates a new sequence number, discarding the most significant bit.
+    pub const fn new(value: u32) -> Self {
+        Self(value & Self::MAX)
+    }
+
+    /// Returns the raw value of the sequence number.
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Debug for SeqNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SeqNumber({})", self.0)
+    }
+}
+
+impl fmt::Display for SeqNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Add<u32> for SeqNumber {
+    type Output = Self;
+
+    fn add(self, rhs: u32) -> Self::Output {
+        Self::new(self.0.wrapping_add(rhs))
+    }
+}
+
+impl Sub for SeqNumber {
+    type Output = i32;
+
+    /// Returns the signed distance from `rhs` to `self`.
+    fn sub(self, rhs: Self) -> Self::Output {
+        let diff = self.0.wrapping_sub(rhs.0) & Self::MAX;
+        if diff > Self::MAX / 2 {
+            diff as i32 - (Self::MAX as i32) - 1
+        } else {
+            diff as i32
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::SeqNumber;
+
+    #[test]
+    fn wrapping() {
+        let a = SeqNumber::new(SeqNumber::MAX);
+        let b = a + 1;
+        assert_eq!(b, SeqNumber::new(0));
+        assert_eq!(b - a, 1);
+        assert_eq!(a - b, -1);
+        assert_eq!(SeqNumber::new(10) - SeqNumber::new(5), 5);
+        assert_eq!(SeqNumber::new(5) - SeqNumber::new(10), -5);
+        assert_eq!(SeqNumber::new(u32::MAX).value(), SeqNumber::MAX);
+    }
+}
@@ -0,0 +1,3 @@
+//! SRT sessions.
+
+pub mod server;
@@ -0,0 +1,140 @@
+//! SRT server session.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use scuffle_context::ContextFutExt;
+use scuffle_mpegts::{ElementaryStream, TsDemuxer};
+
+use crate::error::SrtError;
+use crate::socket::SrtSocket;
+use crate::stream_id::StreamMode;
+
+mod error;
+mod handler;
+
+pub use error::ServerSessionError;
+pub use handler::{SessionData, SessionHandler};
+
+/// A SRT server session that receives a published MPEG-TS stream from a
+/// caller.
+///
+/// This provides the same high-level API as the RTMP server session: the
+/// transport stream is demuxed and the frames are passed to a
+/// [`SessionHandler`].
+pub struct ServerSession<H> {
+    /// The context of the session.
+    /// The connection is closed if this context gets cancelled.
+    ctx: Option<scuffle_context::Context>,
+    socket: SrtSocket,
+    handler: H,
+    demuxer: TsDemuxer,
+    /// The streams last announced to the handler.
+    streams: HashMap<u16, ElementaryStream>,
+}
+
+impl<H> ServerSession<H> {
+    /// Create a new session from an accepted socket.
+    pub fn new(socket: SrtSocket, handler: H) -> Self {
+        Self {
+            ctx: None,
+            socket,
+            handler,
+            demuxer: TsDemuxer::new(),
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Set the context of the session.
+    pub fn with_context(mut self, ctx: scuffle_context::Context) -> Self {
+        self.ctx = Some(ctx);
+        self
+    }
+}
+
+impl<H: SessionHandler> ServerSession<H> {
+    /// Run the session to completion.
+    ///
+    /// The result of the return value will be true if the caller closed the
+    /// connection with a shutdown and false if the connection timed out or the
+    /// context was cancelled.
+    pub async fn run(mut self) -> Result<bool, SrtError> {
+        let ctx = self.ctx.clone().unwrap_or_else(scuffle_context::Context::global);
+
+        let stream_id = self.socket.stream_id().cloned().unwrap_or_default();
+        if self.socket.stream_id().is_some() && stream_id.mode == StreamMode::Request {
+            self.socket.close().await;
+            return Err(ServerSessionError::PlayNotSupported.into());
+        }
+
+        let session_id = self.socket.socket_id();
+        let (app_name, stream_name) = stream_id.app_and_stream_name();
+        self.handler.on_publish(session_id, app_name, stream_name).await?;
+
+        let result = loop {
+            match self.socket.recv().with_context(&ctx).await {
+                Some(Ok(Some(data))) => self.handle_data(session_id, data).await?,
+                Some(Ok(None)) => break Ok(true),
+                Some(Err(SrtError::PeerIdle)) => {
+                    tracing::debug!("caller timed out");
+                    break Ok(false);
+                }
+                Some(Err(err)) => break Err(err),
+                None => break Ok(false), // Context was cancelled
+            }
+        };
+
+        if let Err(err) = self.demuxer.flush() {
+            tracing::debug!(err = %err, "failed to flush demuxer");
+        }
+        self.emit(session_id).await?;
+
+        self.handler.on_unpublish(session_id).await?;
+        self.socket.close().await;
+
+        result
+    }
+
+    async fn handle_data(&mut self, session_id: u32, data: Bytes) -> Result<(), SrtError> {
+        self.handler.on_transport_stream(session_id, &data).await?;
+
+        // The demuxer drops the offending packet on error, pushing an empty
+        // slice continues with the remaining data.
+        let mut data = &data[..];
+        while let Err(err) = self.demuxer.push(data) {
+            tracing::debug!(err = %err, "invalid transport stream packet");
+            data = &[];
+        }
+
+        self.emit(session_id).await
+    }
+
+    async fn emit(&mut self, session_id: u32) -> Result<(), SrtError> {
+        let changed: Vec<_> = self
+            .demuxer
+            .streams()
+            .filter(|stream| self.streams.get(&stream.pid) != Some(stream))
+            .cloned()
+            .collect();
+
+        for stream in changed {
+            self.streams.insert(stream.pid, stream.clone());
+            self.handler.on_data(session_id, SessionData::Stream(stream)).await?;
+        }
+
+        while let Some(frame) = self.demuxer.pop_frame() {
+            // The 33-bit 90 kHz timestamp in milliseconds, wrapping like the
+            // RTMP timestamp.
+            let timestamp = (frame.dts / 90) as u32;
+            let data = if frame.codec.is_video() {
+                SessionData::Video { timestamp, frame }
+            } else {
+                SessionData::Audio { timestamp, frame }
+            };
+
+            self.handler.on_data(session_id, data).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+//! Error type for server sessions.
+
+/// Errors that can occur during a server session.
+#[derive(Debug, thiserror::Error)]
+pub enum ServerSessionError {
+    /// The caller requested to play a stream.
+    #[error("play not supported")]
+    PlayNotSupported,
+}
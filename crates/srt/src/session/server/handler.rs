@@ -0,0 +1,66 @@
+//! Defines types for handling session events.
+
+use bytes::Bytes;
+use scuffle_mpegts::{ElementaryStream, Frame};
+
+use super::error::ServerSessionError;
+
+/// Data received from a session.
+#[derive(Debug, Clone)]
+pub enum SessionData {
+    /// An elementary stream was announced, or its decoder configuration
+    /// changed.
+    ///
+    /// This is always sent before the first frame of the stream and again
+    /// whenever the decoder configuration found in the stream changes.
+    Stream(ElementaryStream),
+    /// Video data.
+    Video {
+        /// Decoding timestamp of the data in milliseconds.
+        timestamp: u32,
+        /// Data.
+        frame: Frame,
+    },
+    /// Audio data.
+    Audio {
+        /// Decoding timestamp of the data in milliseconds.
+        timestamp: u32,
+        /// Data.
+        frame: Frame,
+    },
+}
+
+/// Handler for session events.
+pub trait SessionHandler {
+    /// Called when a stream is published.
+    ///
+    /// The app name and stream name are taken from the resource of the stream
+    /// id, see [`StreamId::app_and_stream_name`](crate::StreamId::app_and_stream_name).
+    fn on_publish(
+        &mut self,
+        stream_id: u32,
+        app_name: &str,
+        stream_name: &str,
+    ) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send;
+
+    /// Called when a stream is unpublished.
+    fn on_unpublish(&mut self, stream_id: u32) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send;
+
+    /// Called with the raw transport stream payload of every delivered packet,
+    /// before it is demuxed.
+    fn on_transport_stream(
+        &mut self,
+        stream_id: u32,
+        data: &Bytes,
+    ) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send {
+        let _ = (stream_id, data);
+        async { Ok(()) }
+    }
+
+    /// Called when data is received.
+    fn on_data(
+        &mut self,
+        stream_id: u32,
+        data: SessionData,
+    ) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send;
+}
@@ -0,0 +1,504 @@
+//! Established SRT connections.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::config::SrtConfig;
+use crate::connection::{CloseReason, Connection, ConnectionParams};
+use crate::crypto::{Crypto, KmResponse, KmState};
+use crate::error::SrtError;
+use crate::handshake::{
+    HANDSHAKE_MAGIC, Handshake, HandshakeExtension, HandshakeType, RejectReason, SRT_VERSION, SrtFlags, SrtHandshake,
+    extension_flags,
+};
+use crate::packet::{ControlKind, ControlPacket, HEADER_SIZE, Packet, UDP_HEADER_SIZE};
+use crate::seq::SeqNumber;
+use crate::stream_id::StreamId;
+
+/// The interval at which unanswered handshake packets are resent.
+const HANDSHAKE_RESEND_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The number of received payloads buffered for the application.
+const DELIVERY_QUEUE_SIZE: usize = 1024;
+
+/// The number of packets buffered between the UDP socket and a connection.
+pub(crate) const PACKET_QUEUE_SIZE: usize = 1024;
+
+/// The maximum size of a UDP datagram.
+pub(crate) const MAX_DATAGRAM_SIZE: usize = 65536;
+
+/// The flags sent in the SRT handshake extension.
+pub(crate) const SRT_FLAGS: SrtFlags = SrtFlags(
+    SrtFlags::TSBPD_SEND.0
+        | SrtFlags::TSBPD_RECV.0
+        | SrtFlags::CRYPT.0
+        | SrtFlags::TOO_LATE_PACKET_DROP.0
+        | SrtFlags::PERIODIC_NAK.0
+        | SrtFlags::REXMIT_FLAG.0,
+);
+
+/// Connection statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// The number of data packets sent, excluding retransmissions.
+    pub packets_sent: u64,
+    /// The number of data packets received, including retransmissions.
+    pub packets_received: u64,
+    /// The number of data packets retransmitted.
+    pub packets_retransmitted: u64,
+    /// The number of data packets detected as lost by the receiver.
+    pub packets_lost: u64,
+    /// The number of data packets dropped, because they arrived too late,
+    /// could not be decrypted or were not acknowledged in time.
+    pub packets_dropped: u64,
+    /// The number of payload bytes sent, excluding retransmissions.
+    pub bytes_sent: u64,
+    /// The number of payload bytes received.
+    pub bytes_received: u64,
+    /// The smoothed round trip time.
+    pub rtt: Duration,
+}
+
+enum Command {
+    Send(Bytes),
+    Close,
+}
+
+/// An established SRT connection.
+///
+/// The connection is driven by a background task. Dropping the socket closes
+/// the connection.
+pub struct SrtSocket {
+    socket_id: u32,
+    peer_addr: SocketAddr,
+    stream_id: Option<StreamId>,
+    latency: Duration,
+    encrypted: bool,
+    commands: mpsc::Sender<Command>,
+    incoming: mpsc::Receiver<Result<Bytes, SrtError>>,
+    stats: Arc<Mutex<Statistics>>,
+    task: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for SrtSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SrtSocket")
+            .field("socket_id", &self.socket_id)
+            .field("peer_addr", &self.peer_addr)
+            .field("stream_id", &self.stream_id)
+            .field("latency", &self.latency)
+            .field("encrypted", &self.encrypted)
+            .finish()
+    }
+}
+
+/// Everything needed to drive an accepted or connected socket.
+pub(crate) struct SocketParts {
+    pub(crate) params: ConnectionParams,
+    pub(crate) udp: Arc<UdpSocket>,
+    pub(crate) peer_addr: SocketAddr,
+    pub(crate) stream_id: Option<StreamId>,
+    pub(crate) packets: mpsc::Receiver<(Instant, Packet)>,
+    /// Notified with the local socket id once the connection is closed.
+    pub(crate) on_close: Option<mpsc::UnboundedSender<u32>>,
+}
+
+impl SrtSocket {
+    /// Connects to a SRT listener.
+    ///
+    /// The stream id and passphrase are taken from the config.
+    pub async fn connect(addr: SocketAddr, config: SrtConfig) -> Result<Self, SrtError> {
+        let bind_addr: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let udp = Arc::new(UdpSocket::bind(bind_addr).await?);
+
+        let params = tokio::time::timeout(config.connect_timeout, caller_handshake(&udp, addr, &config))
+            .await
+            .map_err(|_| SrtError::ConnectTimeout)??;
+
+        let (packets_tx, packets) = mpsc::channel(PACKET_QUEUE_SIZE);
+        tokio::spawn(read_packets(udp.clone(), addr, packets_tx));
+
+        Ok(Self::spawn(SocketParts {
+            params,
+            udp,
+            peer_addr: addr,
+            stream_id: config.stream_id.as_deref().map(StreamId::parse),
+            packets,
+            on_close: None,
+        }))
+    }
+
+    pub(crate) fn spawn(parts: SocketParts) -> Self {
+        let (commands_tx, commands) = mpsc::channel(DELIVERY_QUEUE_SIZE);
+        let (incoming_tx, incoming) = mpsc::channel(DELIVERY_QUEUE_SIZE);
+        let stats = Arc::new(Mutex::new(Statistics::default()));
+
+        let socket_id = parts.params.local_socket_id;
+        let latency = parts.params.recv_latency;
+        let encrypted = parts.params.crypto.is_some();
+
+        let driver = Driver {
+            connection: Connection::new(parts.params, Instant::now()),
+            udp: parts.udp,
+            peer_addr: parts.peer_addr,
+            packets: parts.packets,
+            commands,
+            incoming: incoming_tx,
+            stats: stats.clone(),
+            on_close: parts.on_close,
+            app_dropped: 0,
+        };
+
+        Self {
+            socket_id,
+            peer_addr: parts.peer_addr,
+            stream_id: parts.stream_id,
+            latency,
+            encrypted,
+            commands: commands_tx,
+            incoming,
+            stats,
+            task: tokio::spawn(driver.run()),
+        }
+    }
+
+    /// Returns the local socket id.
+    pub fn socket_id(&self) -> u32 {
+        self.socket_id
+    }
+
+    /// Returns the address of the peer.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Returns the stream id requested by the caller.
+    pub fn stream_id(&self) -> Option<&StreamId> {
+        self.stream_id.as_ref()
+    }
+
+    /// Returns the negotiated receive latency.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Returns true if the payload is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Returns a snapshot of the connection statistics.
+    pub fn stats(&self) -> Statistics {
+        *self.stats.lock().expect("stats lock poisoned")
+    }
+
+    /// Receives the next payload, once it is due for delivery.
+    ///
+    /// Returns `None` once the connection was closed.
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, SrtError> {
+        self.incoming.recv().await.transpose()
+    }
+
+    /// Sends a payload.
+    ///
+    /// Payloads larger than the configured payload size are split into
+    /// multiple packets, each of which is delivered separately.
+    pub async fn send(&self, data: Bytes) -> Result<(), SrtError> {
+        self.commands.send(Command::Send(data)).await.map_err(|_| SrtError::Closed)
+    }
+
+    /// Closes the connection, sending a shutdown to the peer.
+    pub async fn close(self) {
+        if self.commands.send(Command::Close).await.is_ok() {
+            let _ = self.task.await;
+        }
+    }
+}
+
+struct Driver {
+    connection: Connection,
+    udp: Arc<UdpSocket>,
+    peer_addr: SocketAddr,
+    packets: mpsc::Receiver<(Instant, Packet)>,
+    commands: mpsc::Receiver<Command>,
+    incoming: mpsc::Sender<Result<Bytes, SrtError>>,
+    stats: Arc<Mutex<Statistics>>,
+    on_close: Option<mpsc::UnboundedSender<u32>>,
+    app_dropped: u64,
+}
+
+impl Driver {
+    async fn run(mut self) {
+        let result = self.drive().await;
+
+        if let Some(on_close) = &self.on_close {
+            let _ = on_close.send(self.connection.local_socket_id());
+        }
+
+        if let Err(err) = result {
+            tracing::debug!(socket_id = self.connection.local_socket_id(), err = %err, "connection failed");
+            let _ = self.incoming.send(Err(err)).await;
+        }
+    }
+
+    async fn drive(&mut self) -> Result<(), SrtError> {
+        let mut buf = Vec::with_capacity(MAX_DATAGRAM_SIZE);
+        let mut commands_open = true;
+
+        loop {
+            let now = Instant::now();
+            self.connection.handle_timeout(now);
+
+            while let Some(payload) = self.connection.poll_deliver(now) {
+                if let Err(mpsc::error::TrySendError::Full(_)) = self.incoming.try_send(Ok(payload)) {
+                    self.app_dropped += 1;
+                }
+            }
+
+            while let Some(packet) = self.connection.poll_transmit(now) {
+                buf.clear();
+                packet.write(&mut buf)?;
+                self.udp.send_to(&buf, self.peer_addr).await?;
+            }
+
+            let mut stats = self.connection.stats();
+            stats.packets_dropped += self.app_dropped;
+            *self.stats.lock().expect("stats lock poisoned") = stats;
+
+            match self.connection.closed() {
+                Some(CloseReason::Timeout) => return Err(SrtError::PeerIdle),
+                Some(CloseReason::Local | CloseReason::Peer) => return Ok(()),
+                None => {}
+            }
+
+            let deadline = self.connection.next_timeout();
+            tokio::select! {
+                packet = self.packets.recv() => match packet {
+                    Some((received_at, packet)) => self.connection.handle_packet(received_at, packet),
+                    None => return Err(SrtError::Closed),
+                },
+                command = self.commands.recv(), if commands_open => match command {
+                    Some(Command::Send(data)) => self.connection.send(Instant::now(), data),
+                    Some(Command::Close) => self.connection.close(),
+                    None => {
+                        commands_open = false;
+                        self.connection.close();
+                    }
+                },
+                _ = tokio::time::sleep_until(deadline.into()) => {}
+            }
+        }
+    }
+}
+
+/// Reads packets from a caller's UDP socket until the connection is closed.
+async fn read_packets(udp: Arc<UdpSocket>, peer_addr: SocketAddr, packets: mpsc::Sender<(Instant, Packet)>) {
+    let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+
+    loop {
+        let (len, from) = tokio::select! {
+            result = udp.recv_from(&mut buf) => match result {
+                Ok(result) => result,
+                Err(err) => {
+                    tracing::debug!(err = %err, "failed to receive packet");
+                    continue;
+                }
+            },
+            _ = packets.closed() => return,
+        };
+
+        if from != peer_addr {
+            continue;
+        }
+
+        match Packet::parse(Bytes::copy_from_slice(&buf[..len])) {
+            Ok(packet) => {
+                if packets.send((Instant::now(), packet)).await.is_err() {
+                    return;
+                }
+            }
+            Err(err) => tracing::trace!(err = %err, "ignoring invalid packet"),
+        }
+    }
+}
+
+/// Returns a random socket id.
+pub(crate) fn random_socket_id() -> u32 {
+    loop {
+        let id = rand::random::<u32>() & 0x3FFF_FFFF;
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Returns the payload size that fits into the MTU.
+pub(crate) fn payload_size(config: &SrtConfig, mtu: u32) -> usize {
+    config
+        .payload_size
+        .min((mtu as usize).saturating_sub(UDP_HEADER_SIZE + HEADER_SIZE))
+        .max(1)
+}
+
+/// Returns the larger of the local and the peer latency.
+pub(crate) fn negotiate_latency(local: Duration, peer_ms: u16) -> Duration {
+    local.max(Duration::from_millis(u64::from(peer_ms)))
+}
+
+pub(crate) async fn send_control(
+    udp: &UdpSocket,
+    addr: SocketAddr,
+    timestamp: u32,
+    dest_socket_id: u32,
+    kind: ControlKind,
+) -> Result<(), SrtError> {
+    let mut buf = Vec::new();
+    ControlPacket {
+        timestamp,
+        dest_socket_id,
+        kind,
+    }
+    .write(&mut buf)?;
+    udp.send_to(&buf, addr).await?;
+    Ok(())
+}
+
+async fn caller_handshake(udp: &UdpSocket, addr: SocketAddr, config: &SrtConfig) -> Result<ConnectionParams, SrtError> {
+    let start = Instant::now();
+    let socket_id = random_socket_id();
+    let initial_seq = SeqNumber::new(rand::random());
+
+    let crypto = match &config.passphrase {
+        Some(passphrase) => Some(Crypto::generate(passphrase, config.key_length)?),
+        None => None,
+    };
+
+    let mut request = Handshake {
+        // The induction request uses version 4 and the UDT socket type (2)
+        // for compatibility with older listeners.
+        version: 4,
+        encryption: 0,
+        extension: 2,
+        initial_seq,
+        mtu: config.mtu,
+        flow_window: config.flow_window,
+        handshake_type: HandshakeType::Induction,
+        socket_id,
+        syn_cookie: 0,
+        peer_ip: addr.ip(),
+        extensions: Vec::new(),
+    };
+
+    let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+    let mut resend = tokio::time::interval(HANDSHAKE_RESEND_INTERVAL);
+
+    loop {
+        let (len, from) = tokio::select! {
+            _ = resend.tick() => {
+                let timestamp = start.elapsed().as_micros() as u32;
+                send_control(udp, addr, timestamp, 0, ControlKind::Handshake(request.clone())).await?;
+                continue;
+            }
+            result = udp.recv_from(&mut buf) => result?,
+        };
+
+        if from != addr {
+            continue;
+        }
+
+        let Ok(Packet::Control(ControlPacket {
+            timestamp,
+            kind: ControlKind::Handshake(response),
+            ..
+        })) = Packet::parse(Bytes::copy_from_slice(&buf[..len]))
+        else {
+            continue;
+        };
+
+        if let Some(reason) = response.handshake_type.reject_reason() {
+            return Err(SrtError::Rejected(reason));
+        }
+
+        match (request.handshake_type, response.handshake_type) {
+            (HandshakeType::Induction, HandshakeType::Induction) => {
+                if response.version < 5 || response.extension != HANDSHAKE_MAGIC {
+                    return Err(SrtError::InvalidHandshake("listener does not support handshake version 5"));
+                }
+
+                let mut extension = extension_flags::HSREQ;
+                let mut extensions = vec![HandshakeExtension::HsReq(SrtHandshake {
+                    version: SRT_VERSION,
+                    flags: SRT_FLAGS,
+                    recv_latency: config.latency.as_millis() as u16,
+                    send_latency: config.latency.as_millis() as u16,
+                })];
+
+                if let Some((_, km)) = &crypto {
+                    extension |= extension_flags::KMREQ;
+                    extensions.push(HandshakeExtension::KmReq(km.clone()));
+                }
+
+                if let Some(stream_id) = &config.stream_id {
+                    extension |= extension_flags::CONFIG;
+                    extensions.push(HandshakeExtension::StreamId(stream_id.clone()));
+                }
+
+                request = Handshake {
+                    version: 5,
+                    encryption: crypto.as_ref().map_or(0, |(_, km)| (km.key_len / 8) as u16),
+                    extension,
+                    handshake_type: HandshakeType::Conclusion,
+                    syn_cookie: response.syn_cookie,
+                    extensions,
+                    ..request
+                };
+
+                resend.reset_immediately();
+            }
+            (HandshakeType::Conclusion, HandshakeType::Conclusion) => {
+                let now = Instant::now();
+                let hs = response
+                    .srt_handshake()
+                    .ok_or(SrtError::InvalidHandshake("missing SRT handshake response"))?;
+
+                let crypto = match (crypto, response.key_material_response()) {
+                    (Some((crypto, _)), Some(KmResponse::Material(_))) => Some(crypto),
+                    (Some(_), Some(KmResponse::State(KmState::BadSecret))) => {
+                        return Err(SrtError::Rejected(RejectReason::BadSecret));
+                    }
+                    (Some(_), _) => return Err(SrtError::Rejected(RejectReason::Unsecure)),
+                    (None, _) => None,
+                };
+
+                let mtu = config.mtu.min(response.mtu);
+
+                return Ok(ConnectionParams {
+                    local_socket_id: socket_id,
+                    peer_socket_id: response.socket_id,
+                    initial_seq,
+                    flow_window: config.flow_window.min(response.flow_window),
+                    payload_size: payload_size(config, mtu),
+                    recv_latency: negotiate_latency(config.latency, hs.send_latency),
+                    send_latency: negotiate_latency(config.latency, hs.recv_latency),
+                    too_late_packet_drop: config.too_late_packet_drop,
+                    peer_idle_timeout: config.peer_idle_timeout,
+                    crypto,
+                    start,
+                    peer_time_base: now.checked_sub(Duration::from_micros(u64::from(timestamp))).unwrap_or(now),
+                });
+            }
+            _ => {}
+        }
+    }
+}
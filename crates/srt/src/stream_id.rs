@@ -0,0 +1,150 @@
+//! Stream id parsing.
+//!
+//! The stream id is a free-form string sent by the caller. Most encoders use
+//! either a plain resource name (`live/stream-key`) or the structured syntax
+//! recommended by the SRT access control guidelines:
+//! `#!::r=live/stream-key,m=publish,u=user`.
+
+use std::fmt;
+
+/// The prefix of the structured stream id syntax.
+const STRUCTURED_PREFIX: &str = "#!::";
+
+/// The mode requested in a stream id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StreamMode {
+    /// The caller wants to receive the stream.
+    #[default]
+    Request,
+    /// The caller wants to send the stream.
+    Publish,
+    /// The caller wants to send and receive.
+    Bidirectional,
+}
+
+/// A parsed stream id.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StreamId {
+    /// The resource name (`r`), or the whole stream id if it does not use the
+    /// structured syntax.
+    pub resource: String,
+    /// The user name (`u`).
+    pub user: Option<String>,
+    /// The host name (`h`).
+    pub host: Option<String>,
+    /// The session id (`s`).
+    pub session: Option<String>,
+    /// The type of the transmission (`t`), usually `stream`.
+    pub kind: Option<String>,
+    /// The requested mode (`m`).
+    ///
+    /// Defaults to [`StreamMode::Request`] for structured stream ids and to
+    /// [`StreamMode::Publish`] for plain ones, since encoders sending a
+    /// plain stream id are almost always publishing.
+    pub mode: StreamMode,
+    /// Any other keys.
+    pub extra: Vec<(String, String)>,
+}
+
+impl StreamId {
+    /// Parses a stream id.
+    ///
+    /// Unknown keys are kept in [`StreamId::extra`], keys without a value are
+    /// ignored.
+    pub fn parse(id: &str) -> Self {
+        let Some(fields) = id.strip_prefix(STRUCTURED_PREFIX) else {
+            return Self {
+                resource: id.to_owned(),
+                mode: StreamMode::Publish,
+                ..Default::default()
+            };
+        };
+
+        let mut stream_id = Self::default();
+
+        for field in fields.split(',') {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+
+            let value = value.to_owned();
+            match key.trim() {
+                "r" => stream_id.resource = value,
+                "u" => stream_id.user = Some(value),
+                "h" => stream_id.host = Some(value),
+                "s" => stream_id.session = Some(value),
+                "t" => stream_id.kind = Some(value),
+                "m" => {
+                    stream_id.mode = match value.as_str() {
+                        "publish" => StreamMode::Publish,
+                        "bidirectional" => StreamMode::Bidirectional,
+                        _ => StreamMode::Request,
+                    }
+                }
+                key => stream_id.extra.push((key.to_owned(), value)),
+            }
+        }
+
+        stream_id
+    }
+
+    /// Splits the resource name into an app name and a stream name at the
+    /// last `/`, like the app name and stream key of a RTMP url.
+    ///
+    /// The app name is empty if the resource does not contain a `/`.
+    pub fn app_and_stream_name(&self) -> (&str, &str) {
+        self.resource.rsplit_once('/').unwrap_or(("", &self.resource))
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{STRUCTURED_PREFIX}r={}", self.resource)?;
+
+        for (key, value) in [("u", &self.user), ("h", &self.host), ("s", &self.session), ("t", &self.kind)] {
+            if let Some(value) = value {
+                write!(f, ",{key}={value}")?;
+            }
+        }
+
+        let mode = match self.mode {
+            StreamMode::Request => "request",
+            StreamMode::Publish => "publish",
+            StreamMode::Bidirectional => "bidirectional",
+        };
+        write!(f, ",m={mode}")?;
+
+        for (key, value) in &self.extra {
+            write!(f, ",{key}={value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn structured() {
+        let id = StreamId::parse("#!::r=live/key,m=publish,u=alice,x=1,broken");
+        assert_eq!(id.resource, "live/key");
+        assert_eq!(id.mode, StreamMode::Publish);
+        assert_eq!(id.user.as_deref(), Some("alice"));
+        assert_eq!(id.extra, vec![("x".to_owned(), "1".to_owned())]);
+        assert_eq!(id.app_and_stream_name(), ("live", "key"));
+        assert_eq!(StreamId::parse(&id.to_string()), id);
+
+        assert_eq!(StreamId::parse("#!::r=abc").mode, StreamMode::Request);
+    }
+
+    #[test]
+    fn plain() {
+        let id = StreamId::parse("app/nested/key");
+        assert_eq!(id.mode, StreamMode::Publish);
+        assert_eq!(id.app_and_stream_name(), ("app/nested", "key"));
+        assert_eq!(StreamId::parse("key").app_and_stream_name(), ("", "key"));
+    }
+}
@@ -1,6 +1,5 @@
 use bytes::Bytes;
 use scuffle_aac::PartialAudioSpecificConfig;
-use scuffle_flv::audio::header::legacy::{SoundSize, SoundType};
 use scuffle_mp4::DynBox;
 use scuffle_mp4::types::esds::Esds;
 use scuffle_mp4::types::esds::descriptor::header::DescriptorHeader;
@@ -10,30 +9,29 @@ use scuffle_mp4::types::esds::descriptor::types::decoder_specific_info::DecoderS
 use scuffle_mp4::types::esds::descriptor::types::es::EsDescriptor;
 use scuffle_mp4::types::mp4a::Mp4a;
 use scuffle_mp4::types::stsd::{AudioSampleEntry, SampleEntry};
-use scuffle_mp4::types::trun::{TrunSample, TrunSampleFlag};
 
 use crate::TransmuxError;
 
 pub(crate) fn stsd_entry(
-    sound_size: SoundSize,
-    sound_type: SoundType,
+    channel_count: u8,
+    sample_size: u8,
     data: Bytes,
 ) -> Result<(DynBox, PartialAudioSpecificConfig), TransmuxError> {
     let aac_config = scuffle_aac::PartialAudioSpecificConfig::parse(&data)?;
 
+    if channel_count == 0 {
+        return Err(TransmuxError::InvalidAudioChannels);
+    }
+
+    if sample_size != 8 && sample_size != 16 {
+        return Err(TransmuxError::InvalidAudioSampleSize);
+    }
+
     Ok((
         Mp4a::new(
             SampleEntry::new(AudioSampleEntry::new(
-                match sound_type {
-                    SoundType::Mono => 1,
-                    SoundType::Stereo => 2,
-                    _ => return Err(TransmuxError::InvalidAudioChannels),
-                },
-                match sound_size {
-                    SoundSize::Bit8 => 8,
-                    SoundSize::Bit16 => 16,
-                    _ => return Err(TransmuxError::InvalidAudioSampleSize),
-                },
+                channel_count as u16,
+                sample_size as u16,
                 aac_config.sampling_frequency,
             )),
             Esds::new(EsDescriptor::new(
@@ -60,24 +58,3 @@ pub(crate) fn stsd_entry(
         aac_config,
     ))
 }
-
-pub(crate) fn trun_sample(data: &Bytes) -> Result<(TrunSample, u32), TransmuxError> {
-    Ok((
-        TrunSample {
-            duration: Some(1024),
-            composition_time_offset: None,
-            flags: Some(TrunSampleFlag {
-                reserved: 0,
-                is_leading: 0,
-                sample_degradation_priority: 0,
-                sample_depends_on: 2,
-                sample_has_redundancy: 0,
-                sample_is_depended_on: 0,
-                sample_is_non_sync_sample: false,
-                sample_padding_value: 0,
-            }),
-            size: Some(data.len() as u32),
-        },
-        1024,
-    ))
-}
@@ -1,12 +1,9 @@
-use bytes::Bytes;
-use scuffle_flv::video::header::VideoFrameType;
 use scuffle_h264::{AVCDecoderConfigurationRecord, Sps};
 use scuffle_mp4::DynBox;
 use scuffle_mp4::types::avc1::Avc1;
 use scuffle_mp4::types::avcc::AvcC;
 use scuffle_mp4::types::colr::{ColorType, Colr};
 use scuffle_mp4::types::stsd::{SampleEntry, VisualSampleEntry};
-use scuffle_mp4::types::trun::{TrunSample, TrunSampleFlag};
 
 use crate::TransmuxError;
 
@@ -31,26 +28,3 @@ pub(crate) fn stsd_entry(config: AVCDecoderConfigurationRecord, sps: &Sps) -> Re
     )
     .into())
 }
-
-pub(crate) fn trun_sample(
-    frame_type: VideoFrameType,
-    composition_time: u32,
-    duration: u32,
-    data: &Bytes,
-) -> Result<TrunSample, TransmuxError> {
-    Ok(TrunSample {
-        composition_time_offset: Some(composition_time as i64),
-        duration: Some(duration),
-        flags: Some(TrunSampleFlag {
-            reserved: 0,
-            is_leading: 0,
-            sample_degradation_priority: 0,
-            sample_depends_on: if frame_type == VideoFrameType::KeyFrame { 2 } else { 1 },
-            sample_has_redundancy: 0,
-            sample_is_depended_on: 0,
-            sample_is_non_sync_sample: frame_type != VideoFrameType::KeyFrame,
-            sample_padding_value: 0,
-        }),
-        size: Some(data.len() as u32),
-    })
-}
@@ -1,14 +1,11 @@
 use std::io;
 
-use bytes::Bytes;
-use scuffle_flv::video::header::VideoFrameType;
 use scuffle_h265::{HEVCDecoderConfigurationRecord, SpsRbsp};
 use scuffle_mp4::DynBox;
 use scuffle_mp4::types::colr::{ColorType, Colr};
 use scuffle_mp4::types::hev1::Hev1;
 use scuffle_mp4::types::hvcc::HvcC;
 use scuffle_mp4::types::stsd::{SampleEntry, VisualSampleEntry};
-use scuffle_mp4::types::trun::{TrunSample, TrunSampleFlag};
 
 use crate::TransmuxError;
 
@@ -47,26 +44,3 @@ pub(crate) fn stsd_entry(config: HEVCDecoderConfigurationRecord) -> Result<(DynB
         sps,
     ))
 }
-
-pub(crate) fn trun_sample(
-    frame_type: VideoFrameType,
-    composition_time: i32,
-    duration: u32,
-    data: &Bytes,
-) -> Result<TrunSample, TransmuxError> {
-    Ok(TrunSample {
-        composition_time_offset: Some(composition_time as i64),
-        duration: Some(duration),
-        flags: Some(TrunSampleFlag {
-            reserved: 0,
-            is_leading: 0,
-            sample_degradation_priority: 0,
-            sample_depends_on: if frame_type == VideoFrameType::KeyFrame { 2 } else { 1 },
-            sample_has_redundancy: 0,
-            sample_is_depended_on: 0,
-            sample_is_non_sync_sample: frame_type != VideoFrameType::KeyFrame,
-            sample_padding_value: 0,
-        }),
-        size: Some(data.len() as u32),
-    })
-}
@@ -1,6 +1,5 @@
 use bytes::Bytes;
 use scuffle_av1::AV1CodecConfigurationRecord;
-use scuffle_flv::audio::header::legacy::{SoundSize, SoundType};
 use scuffle_h264::AVCDecoderConfigurationRecord;
 use scuffle_h265::HEVCDecoderConfigurationRecord;
 use scuffle_mp4::codec::{AudioCodec, VideoCodec};
@@ -12,8 +11,8 @@ pub(crate) enum VideoSequenceHeader {
 }
 
 pub(crate) struct AudioSequenceHeader {
-    pub sound_size: SoundSize,
-    pub sound_type: SoundType,
+    pub channel_count: u8,
+    pub sample_size: u8,
     pub data: AudioSequenceHeaderData,
 }
 
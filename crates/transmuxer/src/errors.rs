@@ -24,4 +24,6 @@ pub enum TransmuxError {
     Io(#[from] io::Error),
     #[error("flv error: {0}")]
     Flv(#[from] scuffle_flv::error::FlvError),
+    #[error("media error: {0}")]
+    Media(#[from] scuffle_media::MediaError),
 }
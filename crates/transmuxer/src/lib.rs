@@ -20,22 +20,10 @@ use std::io;
 
 use byteorder::{BigEndian, ReadBytesExt};
 use bytes::{Buf, Bytes};
-use scuffle_flv::audio::AudioData;
-use scuffle_flv::audio::body::AudioTagBody;
-use scuffle_flv::audio::body::legacy::LegacyAudioTagBody;
-use scuffle_flv::audio::body::legacy::aac::AacAudioData;
-use scuffle_flv::audio::header::AudioTagHeader;
-use scuffle_flv::audio::header::legacy::{LegacyAudioTagHeader, SoundType};
-use scuffle_flv::script::{OnMetaData, ScriptData};
-use scuffle_flv::tag::{FlvTag, FlvTagData};
-use scuffle_flv::video::VideoData;
-use scuffle_flv::video::body::VideoTagBody;
-use scuffle_flv::video::body::enhanced::{ExVideoTagBody, VideoPacket, VideoPacketCodedFrames, VideoPacketSequenceStart};
-use scuffle_flv::video::body::legacy::LegacyVideoTagBody;
-use scuffle_flv::video::header::enhanced::VideoFourCc;
-use scuffle_flv::video::header::legacy::{LegacyVideoTagHeader, LegacyVideoTagHeaderAvcPacket};
-use scuffle_flv::video::header::{VideoFrameType, VideoTagHeader, VideoTagHeaderData};
+use scuffle_flv::tag::FlvTag;
 use scuffle_h264::Sps;
+use scuffle_media::flv::FlvConverter;
+use scuffle_media::{CodecConfig, MediaEvent, Timebase, TrackConfig};
 use scuffle_mp4::BoxType;
 use scuffle_mp4::codec::{AudioCodec, VideoCodec};
 use scuffle_mp4::types::ftyp::{FourCC, Ftyp};
@@ -72,40 +60,52 @@ mod errors;
 pub use define::*;
 pub use errors::TransmuxError;
 
-struct Tags<'a> {
-    video_sequence_header: Option<VideoSequenceHeader>,
-    audio_sequence_header: Option<AudioSequenceHeader>,
-    scriptdata_tag: Option<OnMetaData<'a>>,
+/// The tracks of the init segment.
+struct Tracks<'a> {
+    video: Option<(&'a TrackConfig, VideoSequenceHeader)>,
+    audio: Option<(&'a TrackConfig, AudioSequenceHeader)>,
 }
 
+/// Transmuxes media streams to fragmented MP4.
+///
+/// The transmuxer accepts [`MediaEvent`]s from any source, FLV tags are
+/// converted with a [`FlvConverter`]. The init segment is created from the
+/// first H.264, H.265 or AV1 video track and the first AAC audio track,
+/// frames of other tracks are ignored.
 #[derive(Debug, Clone)]
-pub struct Transmuxer<'a> {
+pub struct Transmuxer {
     // These durations are measured in timescales
     /// sample_freq * 1000
     audio_duration: u64,
     /// fps * 1000
     video_duration: u64,
     sequence_number: u32,
-    last_video_timestamp: u32,
+    /// The timestamp of the last video frame in milliseconds.
+    last_video_timestamp: i64,
     settings: Option<(VideoSettings, AudioSettings)>,
-    tags: VecDeque<FlvTag<'a>>,
+    /// The ids of the video and audio track of the init segment.
+    track_ids: (u32, u32),
+    converter: FlvConverter,
+    events: VecDeque<MediaEvent>,
 }
 
-impl Default for Transmuxer<'_> {
+impl Default for Transmuxer {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'a> Transmuxer<'a> {
+impl Transmuxer {
     pub fn new() -> Self {
         Self {
             sequence_number: 1,
-            tags: VecDeque::new(),
+            events: VecDeque::new(),
             audio_duration: 0,
             video_duration: 0,
             last_video_timestamp: 0,
             settings: None,
+            track_ids: (0, 0),
+            converter: FlvConverter::new(),
         }
     }
 
@@ -119,15 +119,21 @@ impl<'a> Transmuxer<'a> {
             }
 
             let tag = FlvTag::demux(&mut cursor)?;
-            self.tags.push_back(tag);
+            self.add_tag(tag)?;
         }
 
         Ok(())
     }
 
     /// Feed a single FLV tag to the transmuxer.
-    pub fn add_tag(&mut self, tag: FlvTag<'a>) {
-        self.tags.push_back(tag);
+    pub fn add_tag(&mut self, tag: FlvTag<'_>) -> Result<(), TransmuxError> {
+        self.events.extend(self.converter.convert(tag)?);
+        Ok(())
+    }
+
+    /// Feed a track configuration or a frame to the transmuxer.
+    pub fn add_event(&mut self, event: impl Into<MediaEvent>) {
+        self.events.push_back(event.into());
     }
 
     /// Get the next transmuxed packet. This will return `None` if there is not
@@ -135,14 +141,14 @@ impl<'a> Transmuxer<'a> {
     pub fn mux(&mut self) -> Result<Option<TransmuxResult>, TransmuxError> {
         let mut writer = Vec::new();
 
-        let Some((video_settings, _)) = &self.settings else {
+        let Some((video_settings, audio_settings)) = &self.settings else {
             let Some((video_settings, audio_settings)) = self.init_sequence(&mut writer)? else {
-                if self.tags.len() > 30 {
+                if self.events.len() > 30 {
                     // We are clearly not getting any sequence headers, so we should just give up
                     return Err(TransmuxError::NoSequenceHeaders);
                 }
 
-                // We don't have enough tags to create an init segment yet
+                // We don't have enough events to create an init segment yet
                 return Ok(None);
             };
 
@@ -156,125 +162,66 @@ impl<'a> Transmuxer<'a> {
         };
 
         loop {
-            let Some(tag) = self.tags.pop_front() else {
+            let Some(event) = self.events.pop_front() else {
                 return Ok(None);
             };
 
-            let mdat_data;
-            let total_duration;
-            let trun_sample;
-            let mut is_audio = false;
-            let mut is_keyframe = false;
+            // Configuration changes after the init segment are not supported.
+            let MediaEvent::Frame(frame) = event else {
+                continue;
+            };
 
-            let duration =
-                if self.last_video_timestamp == 0 || tag.timestamp_ms == 0 || tag.timestamp_ms < self.last_video_timestamp {
-                    1000 // the first frame is always 1000 ticks where the
-                // timescale is 1000 * fps.
-                } else {
-                    // Since the delta is in milliseconds (ie 1/1000 of a second)
-                    // Rounding errors happen. Our presision is only 1/1000 of a second.
-                    // So if we have a 30fps video the delta should be 33.33ms (1000/30)
-                    // But we can only represent this as 33ms or 34ms. So we will get rounding
-                    // errors. To fix this we just check if the delta is 1 more or 1 less than the
-                    // expected delta. And if it is we just use the expected delta.
-                    // The reason we use a timescale which is 1000 * fps is because then we can
-                    // always represent the delta as an integer. If we use a timescale of 1000, we
-                    // would run into the same rounding errors.
-                    let delta = tag.timestamp_ms as f64 - self.last_video_timestamp as f64;
-                    let expected_delta = 1000.0 / video_settings.framerate;
-                    if (delta - expected_delta).abs() <= 1.0 {
-                        1000
-                    } else {
-                        (delta * video_settings.framerate) as u32
-                    }
-                };
+            let is_audio = match self.track_ids {
+                (video, _) if frame.track_id == video => false,
+                (_, audio) if frame.track_id == audio => true,
+                _ => continue,
+            };
 
-            match tag.data {
-                FlvTagData::Audio(AudioData {
-                    body: AudioTagBody::Legacy(LegacyAudioTagBody::Aac(AacAudioData::Raw(data))),
-                    ..
-                }) => {
-                    let (sample, duration) = codecs::aac::trun_sample(&data)?;
-
-                    trun_sample = sample;
-                    mdat_data = data;
-                    total_duration = duration;
-                    is_audio = true;
-                }
-                FlvTagData::Video(VideoData {
-                    header:
-                        VideoTagHeader {
-                            frame_type,
-                            data:
-                                VideoTagHeaderData::Legacy(LegacyVideoTagHeader::AvcPacket(
-                                    LegacyVideoTagHeaderAvcPacket::Nalu { composition_time_offset },
-                                )),
-                        },
-                    body: VideoTagBody::Legacy(LegacyVideoTagBody::Other { data }),
-                    ..
-                }) => {
-                    let composition_time =
-                        ((composition_time_offset as f64 * video_settings.framerate) / 1000.0).floor() * 1000.0;
+            let timestamp = frame.dts_in(Timebase::MILLISECONDS);
 
-                    let sample = codecs::avc::trun_sample(frame_type, composition_time as u32, duration, &data)?;
+            let duration = if self.last_video_timestamp == 0 || timestamp == 0 || timestamp < self.last_video_timestamp {
+                1000 // the first frame is always 1000 ticks where the
+            // timescale is 1000 * fps.
+            } else {
+                // Since the delta is in milliseconds (ie 1/1000 of a second)
+                // Rounding errors happen. Our presision is only 1/1000 of a second.
+                // So if we have a 30fps video the delta should be 33.33ms (1000/30)
+                // But we can only represent this as 33ms or 34ms. So we will get rounding
+                // errors. To fix this we just check if the delta is 1 more or 1 less than the
+                // expected delta. And if it is we just use the expected delta.
+                // The reason we use a timescale which is 1000 * fps is because then we can
+                // always represent the delta as an integer. If we use a timescale of 1000, we
+                // would run into the same rounding errors.
+                let delta = timestamp as f64 - self.last_video_timestamp as f64;
+                let expected_delta = 1000.0 / video_settings.framerate;
+                if (delta - expected_delta).abs() <= 1.0 {
+                    1000
+                } else {
+                    (delta * video_settings.framerate) as u32
+                }
+            };
 
-                    trun_sample = sample;
-                    total_duration = duration;
-                    mdat_data = data;
+            let (trun_sample, total_duration) = if is_audio {
+                // An AAC frame always has 1024 samples.
+                let duration = 1024;
+                (
+                    scuffle_media::mp4::trun_sample(&frame, audio_settings.timescale, duration),
+                    duration,
+                )
+            } else {
+                let mut sample = scuffle_media::mp4::trun_sample(&frame, video_settings.timescale, duration);
 
-                    is_keyframe = frame_type == VideoFrameType::KeyFrame;
-                }
-                FlvTagData::Video(VideoData {
-                    header: VideoTagHeader { frame_type, .. },
-                    body:
-                        VideoTagBody::Enhanced(ExVideoTagBody::NoMultitrack {
-                            video_four_cc: VideoFourCc::Av1,
-                            packet: VideoPacket::CodedFrames(VideoPacketCodedFrames::Other(data)),
-                        }),
-                    ..
-                }) => {
-                    let sample = codecs::av1::trun_sample(frame_type, duration, &data)?;
-
-                    trun_sample = sample;
-                    total_duration = duration;
-                    mdat_data = data;
-
-                    is_keyframe = frame_type == VideoFrameType::KeyFrame;
-                }
-                FlvTagData::Video(VideoData {
-                    header: VideoTagHeader { frame_type, .. },
-                    body:
-                        VideoTagBody::Enhanced(ExVideoTagBody::NoMultitrack {
-                            video_four_cc: VideoFourCc::Hevc,
-                            packet,
-                        }),
-                    ..
-                }) => {
-                    let (composition_time, data) = match packet {
-                        VideoPacket::CodedFrames(VideoPacketCodedFrames::Hevc {
-                            composition_time_offset,
-                            data,
-                        }) => (Some(composition_time_offset), data),
-                        VideoPacket::CodedFramesX { data } => (None, data),
-                        _ => continue,
-                    };
-
-                    let composition_time =
-                        ((composition_time.unwrap_or_default() as f64 * video_settings.framerate) / 1000.0).floor() * 1000.0;
-
-                    let sample = codecs::hevc::trun_sample(frame_type, composition_time as i32, duration, &data)?;
-
-                    trun_sample = sample;
-                    total_duration = duration;
-                    mdat_data = data;
-
-                    is_keyframe = frame_type == VideoFrameType::KeyFrame;
-                }
-                _ => {
-                    // We don't support anything else
-                    continue;
+                // The composition time is rounded down to whole frames.
+                if let Some(composition_time_offset) = &mut sample.composition_time_offset {
+                    let offset = frame.timebase.rescale(frame.composition_offset(), Timebase::MILLISECONDS);
+                    *composition_time_offset = ((offset as f64 * video_settings.framerate) / 1000.0).floor() as i64 * 1000;
                 }
-            }
+
+                (sample, duration)
+            };
+
+            let is_keyframe = !is_audio && frame.keyframe;
+            let mdat_data = frame.data;
 
             let trafs = {
                 let (main_duration, main_id) = if is_audio {
@@ -330,7 +277,7 @@ impl<'a> Transmuxer<'a> {
                 })));
             } else {
                 self.video_duration += total_duration as u64;
-                self.last_video_timestamp = tag.timestamp_ms;
+                self.last_video_timestamp = timestamp;
                 return Ok(Some(TransmuxResult::MediaSegment(MediaSegment {
                     data: Bytes::from(writer),
                     ty: MediaType::Video,
@@ -341,71 +288,49 @@ impl<'a> Transmuxer<'a> {
         }
     }
 
-    /// Internal function to find the tags we need to create the init segment.
-    fn find_tags(&self) -> Tags<'a> {
-        let tags = self.tags.iter();
-        let mut video_sequence_header = None;
-        let mut audio_sequence_header = None;
-        let mut scriptdata_tag = None;
+    /// Internal function to find the tracks we need to create the init
+    /// segment, with their latest configuration.
+    fn find_tracks(&self) -> Tracks<'_> {
+        let mut video: Option<(&TrackConfig, VideoSequenceHeader)> = None;
+        let mut audio: Option<(&TrackConfig, AudioSequenceHeader)> = None;
 
-        for tag in tags {
-            if video_sequence_header.is_some() && audio_sequence_header.is_some() && scriptdata_tag.is_some() {
-                break;
-            }
+        for event in &self.events {
+            let MediaEvent::Config(config) = event else {
+                continue;
+            };
 
-            match &tag.data {
-                FlvTagData::Video(VideoData {
-                    body: VideoTagBody::Legacy(LegacyVideoTagBody::AvcVideoPacketSeqHdr(data)),
-                    ..
-                }) => {
-                    video_sequence_header = Some(VideoSequenceHeader::Avc(data.clone()));
-                }
-                FlvTagData::Video(VideoData {
-                    body:
-                        VideoTagBody::Enhanced(ExVideoTagBody::NoMultitrack {
-                            video_four_cc: VideoFourCc::Av1,
-                            packet: VideoPacket::SequenceStart(VideoPacketSequenceStart::Av1(config)),
-                        }),
-                    ..
-                }) => {
-                    video_sequence_header = Some(VideoSequenceHeader::Av1(config.clone()));
+            let is_video_track = video.as_ref().is_none_or(|(video, _)| video.track_id == config.track_id);
+            let is_audio_track = audio.as_ref().is_none_or(|(audio, _)| audio.track_id == config.track_id);
+
+            match &config.config {
+                CodecConfig::Avc(record) if is_video_track => {
+                    video = Some((config, VideoSequenceHeader::Avc(record.clone())));
                 }
-                FlvTagData::Video(VideoData {
-                    body:
-                        VideoTagBody::Enhanced(ExVideoTagBody::NoMultitrack {
-                            video_four_cc: VideoFourCc::Hevc,
-                            packet: VideoPacket::SequenceStart(VideoPacketSequenceStart::Hevc(config)),
-                        }),
-                    ..
-                }) => {
-                    video_sequence_header = Some(VideoSequenceHeader::Hevc(config.clone()));
+                CodecConfig::Hevc(record) if is_video_track => {
+                    video = Some((config, VideoSequenceHeader::Hevc(record.clone())));
                 }
-                FlvTagData::Audio(AudioData {
-                    body: AudioTagBody::Legacy(LegacyAudioTagBody::Aac(AacAudioData::SequenceHeader(data))),
-                    header:
-                        AudioTagHeader::Legacy(LegacyAudioTagHeader {
-                            sound_size, sound_type, ..
-                        }),
-                    ..
-                }) => {
-                    audio_sequence_header = Some(AudioSequenceHeader {
-                        data: AudioSequenceHeaderData::Aac(data.clone()),
-                        sound_size: *sound_size,
-                        sound_type: *sound_type,
-                    });
+                CodecConfig::Av1(record) if is_video_track => {
+                    video = Some((config, VideoSequenceHeader::Av1(record.clone())));
                 }
-                FlvTagData::ScriptData(ScriptData::OnMetaData(metadata)) => {
-                    scriptdata_tag = Some(*metadata.clone());
+                CodecConfig::Aac {
+                    audio_specific_config,
+                    channel_count,
+                    sample_size,
+                } if is_audio_track => {
+                    audio = Some((
+                        config,
+                        AudioSequenceHeader {
+                            data: AudioSequenceHeaderData::Aac(audio_specific_config.clone()),
+                            channel_count: *channel_count,
+                            sample_size: *sample_size,
+                        },
+                    ));
                 }
                 _ => {}
             }
         }
 
-        Tags {
-            video_sequence_header,
-            audio_sequence_header,
-            scriptdata_tag,
-        }
+        Tracks { video, audio }
     }
 
     /// Create the init segment.
@@ -413,37 +338,28 @@ impl<'a> Transmuxer<'a> {
         &mut self,
         writer: &mut impl io::Write,
     ) -> Result<Option<(VideoSettings, AudioSettings)>, TransmuxError> {
-        // We need to find the tag that is the video sequence header
-        // and the audio sequence header
-        let Tags {
-            video_sequence_header,
-            audio_sequence_header,
-            scriptdata_tag,
-        } = self.find_tags();
-
-        let Some(video_sequence_header) = video_sequence_header else {
+        // We need to find the configuration of the video and the audio track
+        let Tracks { video, audio } = self.find_tracks();
+
+        let Some((video_config, video_sequence_header)) = video else {
             return Ok(None);
         };
-        let Some(audio_sequence_header) = audio_sequence_header else {
+        let Some((audio_config, audio_sequence_header)) = audio else {
             return Ok(None);
         };
 
+        let track_ids = (video_config.track_id, audio_config.track_id);
+
         let video_codec;
         let audio_codec;
         let video_width;
         let video_height;
         let audio_channels;
         let audio_sample_rate;
-        let mut video_fps = 0.0;
+        let mut video_fps = video_config.frame_rate.unwrap_or(0.0);
 
-        let mut estimated_video_bitrate = 0;
-        let mut estimated_audio_bitrate = 0;
-
-        if let Some(scriptdata_tag) = scriptdata_tag {
-            video_fps = scriptdata_tag.framerate.unwrap_or(0.0);
-            estimated_video_bitrate = scriptdata_tag.videodatarate.map(|v| (v * 1024.0) as u32).unwrap_or(0);
-            estimated_audio_bitrate = scriptdata_tag.audiodatarate.map(|v| (v * 1024.0) as u32).unwrap_or(0);
-        }
+        let estimated_video_bitrate = video_config.bitrate.unwrap_or(0);
+        let estimated_audio_bitrate = audio_config.bitrate.unwrap_or(0);
 
         let mut compatable_brands = vec![FourCC::Iso5, FourCC::Iso6];
 
@@ -520,18 +436,14 @@ impl<'a> Transmuxer<'a> {
             AudioSequenceHeaderData::Aac(data) => {
                 compatable_brands.push(FourCC::Mp41);
                 let (entry, config) =
-                    codecs::aac::stsd_entry(audio_sequence_header.sound_size, audio_sequence_header.sound_type, data)?;
+                    codecs::aac::stsd_entry(audio_sequence_header.channel_count, audio_sequence_header.sample_size, data)?;
 
                 audio_sample_rate = config.sampling_frequency;
 
                 audio_codec = AudioCodec::Aac {
                     object_type: config.audio_object_type,
                 };
-                audio_channels = match audio_sequence_header.sound_type {
-                    SoundType::Mono => 1,
-                    SoundType::Stereo => 2,
-                    _ => return Err(TransmuxError::InvalidAudioChannels),
-                };
+                audio_channels = audio_sequence_header.channel_count;
 
                 entry
             }
@@ -603,6 +515,8 @@ impl<'a> Transmuxer<'a> {
         )
         .mux(writer)?;
 
+        self.track_ids = track_ids;
+
         Ok(Some((
             VideoSettings {
                 width: video_width,
@@ -4,8 +4,13 @@ use std::io::{
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
+use byteorder::{BigEndian, ReadBytesExt};
+use bytes::{Buf, Bytes};
 use scuffle_aac::AudioObjectType;
 use scuffle_flv::header::FlvHeader;
+use scuffle_flv::tag::FlvTag;
+use scuffle_media::flv::FlvConverter;
+use scuffle_media::{Codec, CodecConfig, MediaEvent, MediaFrame, Timebase, TrackConfig};
 use scuffle_mp4::codec::{AudioCodec, VideoCodec};
 
 use crate::define::{AudioSettings, VideoSettings};
@@ -346,3 +351,69 @@ fn test_transmuxer_hevc_aac() {
     assert_eq!(json["streams"][1]["sample_rate"], "48000");
     assert_eq!(json["streams"][1]["channels"], 2);
 }
+
+#[test]
+fn test_transmuxer_media_events() {
+    let data = std::fs::read(file_path("avc_aac.flv")).unwrap();
+
+    let mut cursor = io::Cursor::new(Bytes::from(data));
+    FlvHeader::demux(&mut cursor).unwrap();
+
+    let pos = cursor.position() as usize;
+    let data = cursor.into_inner().slice(pos..);
+
+    let mut expected = Vec::new();
+    let mut transmuxer = Transmuxer::new();
+    transmuxer.demux(data.clone()).unwrap();
+    while let Some(data) = transmuxer.mux().unwrap() {
+        expected.extend_from_slice(&data.into_bytes());
+    }
+
+    // The same stream with other track ids and a 90 kHz timebase, next to a
+    // track the transmuxer doesn't support.
+    let mut transmuxer = Transmuxer::new();
+    transmuxer.add_event(TrackConfig::new(1, CodecConfig::Opus { channel_count: 2 }));
+
+    let mut converter = FlvConverter::new();
+    let mut cursor = io::Cursor::new(data);
+    while cursor.has_remaining() {
+        cursor.read_u32::<BigEndian>().unwrap(); // previous tag size
+        if !cursor.has_remaining() {
+            break;
+        }
+
+        for event in converter.convert(FlvTag::demux(&mut cursor).unwrap()).unwrap() {
+            match event {
+                MediaEvent::Config(mut config) => {
+                    config.track_id += 10;
+                    transmuxer.add_event(config);
+                }
+                MediaEvent::Frame(mut frame) => {
+                    frame.track_id += 10;
+                    frame.dts = frame.dts_in(Timebase::MPEG);
+                    frame.pts = frame.pts_in(Timebase::MPEG);
+                    frame.timebase = Timebase::MPEG;
+                    transmuxer.add_event(frame);
+                }
+            }
+        }
+
+        transmuxer.add_event(MediaFrame {
+            track_id: 1,
+            codec: Codec::Opus,
+            timebase: Timebase::from_rate(48_000),
+            dts: 0,
+            pts: 0,
+            keyframe: true,
+            data: Bytes::from_static(b"opus"),
+            side_data: Vec::new(),
+        });
+    }
+
+    let mut writer = Vec::new();
+    while let Some(data) = transmuxer.mux().unwrap() {
+        writer.extend_from_slice(&data.into_bytes());
+    }
+
+    assert_eq!(writer, expected);
+}
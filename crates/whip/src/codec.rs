@@ -0,0 +1,156 @@
+//! Codecs that can be received over WHIP.
+
+use std::io;
+
+use bytes::Bytes;
+use scuffle_av1::seq::SequenceHeaderObu;
+use scuffle_av1::{AV1CodecConfigurationRecord, ObuHeader, ObuType};
+use scuffle_h264::{AVCDecoderConfigurationRecord, AvccExtendedConfig, Sps};
+
+/// A codec of a received track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// H.264 video in Annex B format.
+    H264,
+    /// VP8 video frames.
+    Vp8,
+    /// VP9 video frames, multiple spatial layers are combined into a
+    /// superframe.
+    Vp9,
+    /// AV1 temporal units in the low overhead bitstream format, without
+    /// temporal delimiters.
+    Av1,
+    /// Opus audio packets.
+    Opus,
+}
+
+impl Codec {
+    /// All supported codecs, video codecs first, in the default order of
+    /// preference.
+    pub const ALL: [Self; 5] = [Self::H264, Self::Av1, Self::Vp9, Self::Vp8, Self::Opus];
+
+    /// Returns the codec of a SDP `rtpmap` encoding name and clock rate, or
+    /// `None` if the codec is not supported.
+    pub fn from_rtpmap(encoding_name: &str, clock_rate: u32) -> Option<Self> {
+        let codec = Self::ALL
+            .into_iter()
+            .find(|codec| codec.encoding_name().eq_ignore_ascii_case(encoding_name))?;
+
+        (codec.clock_rate() == clock_rate).then_some(codec)
+    }
+
+    /// Returns the SDP `rtpmap` encoding name of the codec.
+    pub const fn encoding_name(&self) -> &'static str {
+        match self {
+            Self::H264 => "H264",
+            Self::Vp8 => "VP8",
+            Self::Vp9 => "VP9",
+            Self::Av1 => "AV1",
+            Self::Opus => "opus",
+        }
+    }
+
+    /// Returns the RTP clock rate of the codec.
+    pub const fn clock_rate(&self) -> u32 {
+        match self {
+            Self::Opus => 48000,
+            _ => 90000,
+        }
+    }
+
+    /// Returns true if the codec is a video codec.
+    pub const fn is_video(&self) -> bool {
+        !matches!(self, Self::Opus)
+    }
+}
+
+/// The decoder configuration of a track, extracted from the stream itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodecConfig {
+    /// H.264 decoder configuration, built from the SPS and PPS.
+    Avc(AVCDecoderConfigurationRecord),
+    /// AV1 decoder configuration, built from the sequence header.
+    Av1(AV1CodecConfigurationRecord),
+}
+
+/// Builds the H.264 decoder configuration from the parameter sets in an
+/// access unit.
+///
+/// Returns `None` if the access unit does not contain both an SPS and a PPS.
+pub(crate) fn avc_config(nal_units: &[Bytes]) -> io::Result<Option<AVCDecoderConfigurationRecord>> {
+    let sps: Vec<_> = nal_units.iter().filter(|nal_unit| nal_unit[0] & 0x1F == 7).cloned().collect();
+    let pps: Vec<_> = nal_units.iter().filter(|nal_unit| nal_unit[0] & 0x1F == 8).cloned().collect();
+
+    let Some(first_sps) = sps.first() else {
+        return Ok(None);
+    };
+
+    if pps.is_empty() || first_sps.len() < 4 {
+        return Ok(None);
+    }
+
+    let parsed = Sps::parse_with_emulation_prevention(io::Cursor::new(first_sps))?;
+
+    // ISO/IEC 14496-15:2022(E) - 5.3.2.1.2
+    let extended_config = matches!(parsed.profile_idc, 100 | 110 | 122 | 144).then(|| {
+        let ext = parsed.ext.as_ref();
+        AvccExtendedConfig {
+            chroma_format_idc: ext.map_or(1, |ext| ext.chroma_format_idc),
+            bit_depth_luma_minus8: ext.map_or(0, |ext| ext.bit_depth_luma_minus8),
+            bit_depth_chroma_minus8: ext.map_or(0, |ext| ext.bit_depth_chroma_minus8),
+            sequence_parameter_set_ext: Vec::new(),
+        }
+    });
+
+    Ok(Some(AVCDecoderConfigurationRecord {
+        configuration_version: 1,
+        profile_indication: first_sps[1],
+        profile_compatibility: first_sps[2],
+        level_indication: first_sps[3],
+        length_size_minus_one: 3,
+        sps,
+        pps,
+        extended_config,
+    }))
+}
+
+/// Builds the AV1 decoder configuration from a sequence header OBU with a
+/// size field.
+pub(crate) fn av1_config(obu: &Bytes) -> io::Result<AV1CodecConfigurationRecord> {
+    let mut reader = io::Cursor::new(obu.clone());
+    let header = ObuHeader::parse(&mut reader)?;
+    if header.obu_type != ObuType::SequenceHeader {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a sequence header"));
+    }
+
+    let payload = match header.size {
+        Some(size) => {
+            let start = reader.position() as usize;
+            let end = start
+                .checked_add(size as usize)
+                .filter(|&end| end <= obu.len())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated sequence header"))?;
+            obu.slice(start..end)
+        }
+        None => obu.slice(reader.position() as usize..),
+    };
+
+    let sequence_header = SequenceHeaderObu::parse(header, &mut io::Cursor::new(payload))?;
+    let operating_point = sequence_header.operating_points.first();
+    let color_config = &sequence_header.color_config;
+
+    Ok(AV1CodecConfigurationRecord {
+        seq_profile: sequence_header.seq_profile,
+        seq_level_idx_0: operating_point.map_or(0, |point| point.seq_level_idx),
+        seq_tier_0: operating_point.is_some_and(|point| point.seq_tier),
+        high_bitdepth: color_config.bit_depth > 8,
+        twelve_bit: color_config.bit_depth == 12,
+        monochrome: color_config.mono_chrome,
+        chroma_subsampling_x: color_config.subsampling_x,
+        chroma_subsampling_y: color_config.subsampling_y,
+        chroma_sample_position: color_config.chroma_sample_position,
+        hdr_wcg_idc: 0,
+        initial_presentation_delay_minus_one: None,
+        config_obu: obu.clone(),
+    })
+}
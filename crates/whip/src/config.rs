@@ -0,0 +1,50 @@
+//! Server configuration.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::codec::Codec;
+
+/// The configuration of a WHIP server.
+#[derive(Debug, Clone)]
+pub struct WhipConfig {
+    /// The addresses announced as ICE host candidates, together with the port
+    /// of the UDP socket.
+    ///
+    /// If empty, the address the socket is bound to is used, which must not
+    /// be unspecified.
+    pub candidates: Vec<IpAddr>,
+    /// The accepted codecs in the order of preference.
+    ///
+    /// For every media description of an offer the first codec of this list
+    /// that is offered is chosen.
+    pub codecs: Vec<Codec>,
+    /// The maximum bitrate requested from publishers in bits per second.
+    ///
+    /// This is sent as a receiver estimated maximum bitrate (REMB) once every
+    /// second.
+    pub max_bitrate: u64,
+    /// How long a missing packet is waited for before it is considered lost.
+    pub reorder_delay: Duration,
+    /// The maximum duration of the ICE and DTLS handshake.
+    pub handshake_timeout: Duration,
+    /// The session is closed if nothing is received from the publisher for
+    /// this long.
+    pub peer_idle_timeout: Duration,
+    /// The maximum size of a SDP offer in bytes.
+    pub max_offer_size: usize,
+}
+
+impl Default for WhipConfig {
+    fn default() -> Self {
+        Self {
+            candidates: Vec::new(),
+            codecs: Codec::ALL.to_vec(),
+            max_bitrate: 10_000_000,
+            reorder_delay: Duration::from_millis(50),
+            handshake_timeout: Duration::from_secs(10),
+            peer_idle_timeout: Duration::from_secs(10),
+            max_offer_size: 64 * 1024,
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! RTP depacketizers, turning RTP payloads back into access units.
+//!
+//! Packets must be pushed in sequence number order, the receiver reorders
+//! them before they reach the depacketizer. Gaps are reported with
+//! [`Depacketizer::discard`], which drops the incomplete access unit and for
+//! video waits for the next key frame.
+
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::codec::{Codec, CodecConfig};
+use crate::rtp::RtpPacket;
+
+mod av1;
+mod h264;
+mod vp8;
+mod vp9;
+
+/// An access unit assembled from one or more RTP packets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessUnit {
+    /// The RTP timestamp of the access unit.
+    pub timestamp: u32,
+    /// If the access unit can be decoded without any previous access units.
+    pub keyframe: bool,
+    /// If access units were dropped before this one because of packet loss.
+    pub discontinuity: bool,
+    /// The decoder configuration found in the access unit, if any.
+    pub config: Option<CodecConfig>,
+    /// The access unit data, see [`Codec`] for the format.
+    pub data: Bytes,
+}
+
+/// The codec specific depacketizer state.
+#[derive(Debug)]
+enum State {
+    H264(h264::H264Depacketizer),
+    Vp8(vp8::Vp8Depacketizer),
+    Vp9(vp9::Vp9Depacketizer),
+    Av1(av1::Av1Depacketizer),
+    Opus(Option<Bytes>),
+}
+
+/// A depacketized access unit before the timing is attached.
+struct Payload {
+    keyframe: bool,
+    config: Option<CodecConfig>,
+    data: Bytes,
+}
+
+/// Assembles access units from the RTP packets of a single stream.
+#[derive(Debug)]
+pub struct Depacketizer {
+    codec: Codec,
+    state: State,
+    /// The timestamp of the access unit being assembled.
+    timestamp: Option<u32>,
+    /// A packet of the current access unit was lost or malformed.
+    corrupted: bool,
+    /// Access units are dropped until the next key frame.
+    waiting_for_keyframe: bool,
+    /// Access units were dropped since the last returned one.
+    discontinuity: bool,
+    ready: VecDeque<AccessUnit>,
+}
+
+impl Depacketizer {
+    /// Creates a new depacketizer.
+    ///
+    /// Video access units are dropped until the first key frame.
+    pub fn new(codec: Codec) -> Self {
+        let state = match codec {
+            Codec::H264 => State::H264(Default::default()),
+            Codec::Vp8 => State::Vp8(Default::default()),
+            Codec::Vp9 => State::Vp9(Default::default()),
+            Codec::Av1 => State::Av1(Default::default()),
+            Codec::Opus => State::Opus(None),
+        };
+
+        Self {
+            codec,
+            state,
+            timestamp: None,
+            corrupted: false,
+            waiting_for_keyframe: codec.is_video(),
+            discontinuity: false,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Returns the codec of the depacketizer.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Returns true if video access units are dropped until the next key
+    /// frame.
+    ///
+    /// The receiver should request a key frame from the sender while this is
+    /// the case.
+    pub fn is_waiting_for_keyframe(&self) -> bool {
+        self.waiting_for_keyframe
+    }
+
+    /// Pushes the next packet of the stream.
+    pub fn push(&mut self, packet: &RtpPacket) {
+        // The last packet of the previous access unit was lost, or the sender
+        // does not set the marker bit.
+        if self.timestamp.is_some_and(|timestamp| timestamp != packet.timestamp) {
+            self.finish();
+        }
+
+        if packet.payload.is_empty() {
+            return;
+        }
+
+        self.timestamp = Some(packet.timestamp);
+
+        let result = match &mut self.state {
+            State::H264(state) => state.push(&packet.payload),
+            State::Vp8(state) => state.push(&packet.payload),
+            State::Vp9(state) => state.push(&packet.payload),
+            State::Av1(state) => state.push(&packet.payload),
+            State::Opus(state) => {
+                *state = Some(packet.payload.clone());
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            tracing::debug!(codec = ?self.codec, err, "dropping malformed packet");
+            self.corrupted = true;
+        }
+
+        if packet.marker || !self.codec.is_video() {
+            self.finish();
+        }
+    }
+
+    /// Drops the access unit being assembled because a packet was lost.
+    pub fn discard(&mut self) {
+        // A lost packet in between access units is detected by the codec
+        // specific state, or skipped by waiting for a key frame.
+        self.corrupted |= self.timestamp.is_some();
+        self.discontinuity = true;
+        self.waiting_for_keyframe |= self.codec.is_video();
+    }
+
+    /// Returns the next complete access unit.
+    pub fn pop(&mut self) -> Option<AccessUnit> {
+        self.ready.pop_front()
+    }
+
+    fn finish(&mut self) {
+        let Some(timestamp) = self.timestamp.take() else {
+            return;
+        };
+
+        let payload = match &mut self.state {
+            State::H264(state) => state.finish(),
+            State::Vp8(state) => state.finish(),
+            State::Vp9(state) => state.finish(),
+            State::Av1(state) => state.finish(),
+            State::Opus(state) => state.take().map(|data| Payload {
+                keyframe: true,
+                config: None,
+                data,
+            }),
+        };
+
+        let corrupted = std::mem::take(&mut self.corrupted);
+        let Some(payload) = payload.filter(|_| !corrupted) else {
+            self.discontinuity = true;
+            self.waiting_for_keyframe |= self.codec.is_video();
+            return;
+        };
+
+        if self.waiting_for_keyframe && !payload.keyframe {
+            self.discontinuity = true;
+            return;
+        }
+
+        self.waiting_for_keyframe = false;
+        self.ready.push_back(AccessUnit {
+            timestamp,
+            keyframe: payload.keyframe,
+            discontinuity: std::mem::take(&mut self.discontinuity),
+            config: payload.config,
+            data: payload.data,
+        });
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    pub(super) fn packet(sequence_number: u16, timestamp: u32, marker: bool, payload: &[u8]) -> RtpPacket {
+        RtpPacket {
+            marker,
+            payload_type: 96,
+            sequence_number,
+            timestamp,
+            ssrc: 1,
+            payload: Bytes::copy_from_slice(payload),
+        }
+    }
+
+    #[test]
+    fn opus() {
+        let mut depacketizer = Depacketizer::new(Codec::Opus);
+        depacketizer.push(&packet(0, 960, false, &[0xFC, 1, 2]));
+        depacketizer.push(&packet(1, 1920, false, &[]));
+        depacketizer.discard();
+        depacketizer.push(&packet(3, 2880, false, &[0xFC, 3]));
+
+        let first = depacketizer.pop().unwrap();
+        assert_eq!(first.timestamp, 960);
+        assert!(first.keyframe);
+        assert!(!first.discontinuity);
+        assert_eq!(first.data.as_ref(), &[0xFC, 1, 2]);
+
+        let second = depacketizer.pop().unwrap();
+        assert_eq!(second.timestamp, 2880);
+        assert!(second.discontinuity);
+        assert!(depacketizer.pop().is_none());
+    }
+
+    #[test]
+    fn waits_for_keyframe() {
+        // VP8 with a minimal payload descriptor, S bit and partition 0.
+        let mut depacketizer = Depacketizer::new(Codec::Vp8);
+        assert!(depacketizer.is_waiting_for_keyframe());
+
+        // Inter frame
+        depacketizer.push(&packet(0, 0, true, &[0x10, 0x01, 0xAA]));
+        assert!(depacketizer.pop().is_none());
+
+        // Key frame over two packets
+        depacketizer.push(&packet(1, 3000, false, &[0x10, 0x00, 0xBB]));
+        depacketizer.push(&packet(2, 3000, true, &[0x00, 0xCC]));
+        let frame = depacketizer.pop().unwrap();
+        assert!(frame.keyframe);
+        assert!(frame.discontinuity);
+        assert_eq!(frame.data.as_ref(), &[0x00, 0xBB, 0xCC]);
+        assert!(!depacketizer.is_waiting_for_keyframe());
+
+        // The second packet of this frame is lost.
+        depacketizer.push(&packet(3, 6000, false, &[0x10, 0x01, 0xDD]));
+        depacketizer.discard();
+        depacketizer.push(&packet(5, 9000, true, &[0x10, 0x01, 0xEE]));
+        assert!(depacketizer.pop().is_none());
+        assert!(depacketizer.is_waiting_for_keyframe());
+    }
+}
@@ -0,0 +1,238 @@
+//! AV1 depacketization.
+//!
+//! RTP Payload Format For AV1 - 4.4 and 5
+
+use bytes::{Buf, Bytes};
+
+use super::Payload;
+use crate::codec::{CodecConfig, av1_config};
+
+/// `Z`: the first OBU element continues an OBU of the previous packet
+const FLAG_CONTINUATION: u8 = 0x80;
+/// `Y`: the last OBU element continues in the next packet
+const FLAG_CONTINUES: u8 = 0x40;
+/// `N`: the packet is the first packet of a coded video sequence
+const FLAG_NEW_SEQUENCE: u8 = 0x08;
+
+/// `OBU_SEQUENCE_HEADER`
+const OBU_SEQUENCE_HEADER: u8 = 1;
+/// `OBU_TEMPORAL_DELIMITER`
+const OBU_TEMPORAL_DELIMITER: u8 = 2;
+/// `OBU_TILE_LIST`
+const OBU_TILE_LIST: u8 = 8;
+
+/// `obu_has_size_field`
+const OBU_HAS_SIZE_FIELD: u8 = 0x02;
+/// `obu_extension_flag`
+const OBU_EXTENSION_FLAG: u8 = 0x04;
+
+/// Reads a `leb128()` value.
+fn read_leb128(data: &mut Bytes) -> Result<usize, &'static str> {
+    let mut value = 0;
+    for i in 0..8 {
+        if !data.has_remaining() {
+            return Err("truncated leb128");
+        }
+
+        let byte = data.get_u8();
+        value |= ((byte & 0x7F) as usize) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err("invalid leb128")
+}
+
+/// Writes a `leb128()` value.
+fn write_leb128(buf: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+
+        buf.push(byte | 0x80);
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Av1Depacketizer {
+    /// The temporal unit in the low overhead bitstream format.
+    data: Vec<u8>,
+    /// The OBU being reassembled from fragments.
+    fragment: Option<Vec<u8>>,
+    /// The temporal unit starts a new coded video sequence.
+    new_sequence: bool,
+    /// The sequence header of the temporal unit.
+    sequence_header: Option<Bytes>,
+    /// The first packet of the temporal unit was received.
+    started: bool,
+}
+
+impl Av1Depacketizer {
+    pub(super) fn push(&mut self, payload: &Bytes) -> Result<(), &'static str> {
+        let mut data = payload.clone();
+        let header = data.get_u8();
+        let element_count = (header >> 4) & 0x03;
+
+        if !self.started {
+            self.started = true;
+            self.new_sequence = header & FLAG_NEW_SEQUENCE != 0;
+        }
+
+        let mut index = 0;
+        while data.has_remaining() {
+            index += 1;
+
+            // The last element has no length field if the count is given.
+            let size = if element_count != 0 && index == element_count {
+                data.remaining()
+            } else {
+                read_leb128(&mut data)?
+            };
+
+            if size > data.remaining() {
+                return Err("invalid obu element size");
+            }
+
+            let element = data.split_to(size);
+
+            if index == 1 && header & FLAG_CONTINUATION != 0 {
+                self.fragment
+                    .as_mut()
+                    .ok_or("obu fragment without start")?
+                    .extend_from_slice(&element);
+            } else if self.fragment.replace(element.to_vec()).is_some() {
+                return Err("incomplete obu fragment");
+            }
+
+            let is_last = !data.has_remaining();
+            if !is_last || header & FLAG_CONTINUES == 0 {
+                let obu = self.fragment.take().unwrap_or_default();
+                self.push_obu(Bytes::from(obu))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends an OBU to the temporal unit with a size field.
+    fn push_obu(&mut self, mut obu: Bytes) -> Result<(), &'static str> {
+        if obu.is_empty() {
+            return Ok(());
+        }
+
+        let header = obu.get_u8();
+        let obu_type = (header >> 3) & 0x0F;
+        let extension = if header & OBU_EXTENSION_FLAG != 0 {
+            if !obu.has_remaining() {
+                return Err("truncated obu header");
+            }
+            Some(obu.get_u8())
+        } else {
+            None
+        };
+
+        let payload = if header & OBU_HAS_SIZE_FIELD != 0 {
+            let size = read_leb128(&mut obu)?;
+            if size > obu.remaining() {
+                return Err("invalid obu size");
+            }
+            obu.split_to(size)
+        } else {
+            obu
+        };
+
+        // Temporal delimiters are implied and tile lists must be dropped.
+        if matches!(obu_type, OBU_TEMPORAL_DELIMITER | OBU_TILE_LIST) {
+            return Ok(());
+        }
+
+        let start = self.data.len();
+        self.data.push(header | OBU_HAS_SIZE_FIELD);
+        self.data.extend(extension);
+        write_leb128(&mut self.data, payload.len());
+        self.data.extend_from_slice(&payload);
+
+        if obu_type == OBU_SEQUENCE_HEADER {
+            self.sequence_header = Some(Bytes::copy_from_slice(&self.data[start..]));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn finish(&mut self) -> Option<Payload> {
+        let data = std::mem::take(&mut self.data);
+        let sequence_header = self.sequence_header.take();
+        let new_sequence = std::mem::take(&mut self.new_sequence);
+        self.started = false;
+
+        if self.fragment.take().is_some() || data.is_empty() {
+            return None;
+        }
+
+        let config = sequence_header.and_then(|obu| match av1_config(&obu) {
+            Ok(config) => Some(CodecConfig::Av1(config)),
+            Err(err) => {
+                tracing::debug!(err = %err, "invalid sequence header");
+                None
+            }
+        });
+
+        Some(Payload {
+            keyframe: new_sequence,
+            config,
+            data: Bytes::from(data),
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::super::tests::packet;
+    use super::*;
+    use crate::codec::Codec;
+    use crate::depacketize::Depacketizer;
+
+    #[test]
+    fn leb128() {
+        for value in [0, 1, 127, 128, 300, 1 << 20] {
+            let mut buf = Vec::new();
+            write_leb128(&mut buf, value);
+            assert_eq!(read_leb128(&mut Bytes::from(buf)).unwrap(), value);
+        }
+
+        assert!(read_leb128(&mut Bytes::from_static(&[0x80])).is_err());
+    }
+
+    #[test]
+    fn fragmented_obus() {
+        let mut depacketizer = Depacketizer::new(Codec::Av1);
+
+        // N, W=2: a temporal delimiter followed by the start of a frame OBU.
+        depacketizer.push(&packet(0, 0, false, &[0x68, 0x01, 0x10, 0x30, 1, 2]));
+        // Z, W=1: the end of the frame OBU.
+        depacketizer.push(&packet(1, 0, true, &[0x90, 3, 4]));
+
+        let temporal_unit = depacketizer.pop().unwrap();
+        assert!(temporal_unit.keyframe);
+        assert!(temporal_unit.config.is_none());
+        // OBU_FRAME with a size field
+        assert_eq!(temporal_unit.data.as_ref(), &[0x32, 4, 1, 2, 3, 4]);
+
+        // W=0: length prefixed elements, an OBU with an extension and size
+        // field.
+        depacketizer.push(&packet(2, 3000, true, &[0x00, 0x05, 0x36, 0x08, 0x02, 5, 6]));
+        let temporal_unit = depacketizer.pop().unwrap();
+        assert!(!temporal_unit.keyframe);
+        assert_eq!(temporal_unit.data.as_ref(), &[0x36, 0x08, 0x02, 5, 6]);
+
+        // A continuation without its start is dropped.
+        depacketizer.push(&packet(4, 6000, true, &[0x90, 3, 4]));
+        assert!(depacketizer.pop().is_none());
+    }
+}
@@ -0,0 +1,157 @@
+//! H.264 depacketization.
+//!
+//! RFC 6184 - 5.6, 5.7.1 and 5.8
+
+use bytes::{Buf, Bytes};
+
+use super::Payload;
+use crate::codec::{CodecConfig, avc_config};
+
+/// `STAP-A`
+const NAL_TYPE_STAP_A: u8 = 24;
+/// `FU-A`
+const NAL_TYPE_FU_A: u8 = 28;
+
+/// The Annex B start code.
+const START_CODE: &[u8] = &[0, 0, 0, 1];
+
+/// Supports the single NAL unit and non-interleaved packetization modes.
+#[derive(Debug, Default)]
+pub(super) struct H264Depacketizer {
+    nal_units: Vec<Bytes>,
+    /// The NAL unit being reassembled from fragmentation units.
+    fragment: Option<Vec<u8>>,
+}
+
+impl H264Depacketizer {
+    pub(super) fn push(&mut self, payload: &Bytes) -> Result<(), &'static str> {
+        match payload[0] & 0x1F {
+            1..=23 => self.nal_units.push(payload.clone()),
+            NAL_TYPE_STAP_A => {
+                let mut data = payload.slice(1..);
+                while data.has_remaining() {
+                    if data.remaining() < 2 {
+                        return Err("truncated aggregation packet");
+                    }
+
+                    let size = data.get_u16() as usize;
+                    if size == 0 || size > data.remaining() {
+                        return Err("invalid aggregation unit size");
+                    }
+
+                    self.nal_units.push(data.split_to(size));
+                }
+            }
+            NAL_TYPE_FU_A => {
+                let [indicator, header, data @ ..] = payload.as_ref() else {
+                    return Err("truncated fragmentation unit");
+                };
+
+                if header & 0x80 != 0 {
+                    // The NAL unit header is rebuilt from the indicator and
+                    // the fragmentation unit header.
+                    let mut nal_unit = vec![(indicator & 0xE0) | (header & 0x1F)];
+                    nal_unit.extend_from_slice(data);
+                    self.fragment = Some(nal_unit);
+                } else {
+                    self.fragment
+                        .as_mut()
+                        .ok_or("fragmentation unit without start")?
+                        .extend_from_slice(data);
+                }
+
+                if header & 0x40 != 0 {
+                    self.nal_units.extend(self.fragment.take().map(Bytes::from));
+                }
+            }
+            _ => return Err("unsupported packetization mode"),
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn finish(&mut self) -> Option<Payload> {
+        let nal_units = std::mem::take(&mut self.nal_units);
+        if self.fragment.take().is_some() || nal_units.is_empty() {
+            return None;
+        }
+
+        let keyframe = nal_units.iter().any(|nal_unit| nal_unit[0] & 0x1F == 5);
+        let config = match avc_config(&nal_units) {
+            Ok(config) => config.map(CodecConfig::Avc),
+            Err(err) => {
+                tracing::debug!(err = %err, "invalid sequence parameter set");
+                None
+            }
+        };
+
+        let mut data = Vec::with_capacity(nal_units.iter().map(|nal_unit| START_CODE.len() + nal_unit.len()).sum());
+        for nal_unit in &nal_units {
+            data.extend_from_slice(START_CODE);
+            data.extend_from_slice(nal_unit);
+        }
+
+        Some(Payload {
+            keyframe,
+            config,
+            data: Bytes::from(data),
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::super::tests::packet;
+    use crate::codec::Codec;
+    use crate::depacketize::Depacketizer;
+
+    /// A 1280x720 baseline profile SPS.
+    const SPS: &[u8] = &[0x67, 0x42, 0xC0, 0x1F, 0xDA, 0x01, 0x40, 0x16, 0xE4];
+    const PPS: &[u8] = &[0x68, 0xCE, 0x3C, 0x80];
+
+    #[test]
+    fn stap_a_and_fu_a() {
+        let mut depacketizer = Depacketizer::new(Codec::H264);
+
+        let mut stap_a = vec![24, 0, SPS.len() as u8];
+        stap_a.extend_from_slice(SPS);
+        stap_a.extend_from_slice(&[0, PPS.len() as u8]);
+        stap_a.extend_from_slice(PPS);
+        depacketizer.push(&packet(0, 0, false, &stap_a));
+
+        // IDR slice 0x65 split into three fragmentation units.
+        depacketizer.push(&packet(1, 0, false, &[0x7C, 0x85, 1, 2]));
+        depacketizer.push(&packet(2, 0, false, &[0x7C, 0x05, 3, 4]));
+        depacketizer.push(&packet(3, 0, true, &[0x7C, 0x45, 5]));
+
+        let access_unit = depacketizer.pop().unwrap();
+        assert!(access_unit.keyframe);
+
+        let mut expected = vec![0, 0, 0, 1];
+        expected.extend_from_slice(SPS);
+        expected.extend_from_slice(&[0, 0, 0, 1]);
+        expected.extend_from_slice(PPS);
+        expected.extend_from_slice(&[0, 0, 0, 1, 0x65, 1, 2, 3, 4, 5]);
+        assert_eq!(access_unit.data.as_ref(), expected);
+
+        let Some(crate::codec::CodecConfig::Avc(config)) = access_unit.config else {
+            panic!("missing config");
+        };
+        assert_eq!(config.profile_indication, 0x42);
+        assert_eq!(config.level_indication, 0x1F);
+        assert_eq!(config.sps[0].as_ref(), SPS);
+
+        // Single NAL unit packet of a non-IDR slice.
+        depacketizer.push(&packet(4, 3000, true, &[0x41, 9]));
+        let access_unit = depacketizer.pop().unwrap();
+        assert!(!access_unit.keyframe);
+        assert!(access_unit.config.is_none());
+        assert_eq!(access_unit.data.as_ref(), &[0, 0, 0, 1, 0x41, 9]);
+
+        // A fragmentation unit without its start is dropped.
+        depacketizer.push(&packet(6, 6000, true, &[0x7C, 0x45, 5]));
+        assert!(depacketizer.pop().is_none());
+        assert!(depacketizer.is_waiting_for_keyframe());
+    }
+}
@@ -0,0 +1,99 @@
+//! VP8 depacketization.
+//!
+//! RFC 7741 - 4.2
+
+use bytes::Bytes;
+
+use super::Payload;
+
+/// `X`: extended control bits present
+const FLAG_EXTENDED: u8 = 0x80;
+/// `S`: start of VP8 partition
+const FLAG_START: u8 = 0x10;
+/// `I`: picture id present
+const FLAG_PICTURE_ID: u8 = 0x80;
+/// `L`: `TL0PICIDX` present
+const FLAG_TL0_PIC_IDX: u8 = 0x40;
+/// `T` and `K`: `TID` and `KEYIDX` present
+const FLAG_TID_KEY_IDX: u8 = 0x30;
+/// `M`: the picture id is 15 bits long
+const FLAG_LONG_PICTURE_ID: u8 = 0x80;
+
+#[derive(Debug, Default)]
+pub(super) struct Vp8Depacketizer {
+    /// The frame being assembled, `None` until the start of the first
+    /// partition was received.
+    frame: Option<Vec<u8>>,
+}
+
+impl Vp8Depacketizer {
+    pub(super) fn push(&mut self, payload: &Bytes) -> Result<(), &'static str> {
+        let descriptor = payload[0];
+        let mut offset = 1;
+
+        if descriptor & FLAG_EXTENDED != 0 {
+            let extension = *payload.get(offset).ok_or("truncated payload descriptor")?;
+            offset += 1;
+
+            if extension & FLAG_PICTURE_ID != 0 {
+                let picture_id = *payload.get(offset).ok_or("truncated payload descriptor")?;
+                offset += if picture_id & FLAG_LONG_PICTURE_ID != 0 { 2 } else { 1 };
+            }
+
+            if extension & FLAG_TL0_PIC_IDX != 0 {
+                offset += 1;
+            }
+
+            if extension & FLAG_TID_KEY_IDX != 0 {
+                offset += 1;
+            }
+        }
+
+        let data = payload.get(offset..).ok_or("truncated payload descriptor")?;
+
+        // The start of partition 0 is the start of the frame.
+        if descriptor & FLAG_START != 0 && descriptor & 0x07 == 0 {
+            self.frame = Some(Vec::new());
+        }
+
+        self.frame.as_mut().ok_or("missing start of frame")?.extend_from_slice(data);
+
+        Ok(())
+    }
+
+    pub(super) fn finish(&mut self) -> Option<Payload> {
+        let frame = self.frame.take().filter(|frame| !frame.is_empty())?;
+
+        Some(Payload {
+            // The inverse key frame flag of the frame tag, RFC 6386 - 9.1
+            keyframe: frame[0] & 0x01 == 0,
+            config: None,
+            data: Bytes::from(frame),
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::super::tests::packet;
+    use crate::codec::Codec;
+    use crate::depacketize::Depacketizer;
+
+    #[test]
+    fn extended_descriptor() {
+        let mut depacketizer = Depacketizer::new(Codec::Vp8);
+
+        // X, S with I (long picture id), L and T
+        depacketizer.push(&packet(0, 0, false, &[0x90, 0xE0, 0x81, 0x23, 0x05, 0x40, 0x10, 0x02]));
+        // Second partition
+        depacketizer.push(&packet(1, 0, true, &[0x81, 0x80, 0x01, 0x03]));
+
+        let frame = depacketizer.pop().unwrap();
+        assert!(frame.keyframe);
+        assert_eq!(frame.data.as_ref(), &[0x10, 0x02, 0x03]);
+
+        depacketizer.push(&packet(2, 3000, true, &[0x90, 0xE0]));
+        assert!(depacketizer.pop().is_none());
+    }
+}
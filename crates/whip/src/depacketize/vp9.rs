@@ -0,0 +1,211 @@
+//! VP9 depacketization.
+//!
+//! RFC 9628 - 4.2
+
+use bytes::Bytes;
+
+use super::Payload;
+
+/// `I`: picture id present
+const FLAG_PICTURE_ID: u8 = 0x80;
+/// `P`: inter-picture predicted frame
+const FLAG_INTER_PICTURE: u8 = 0x40;
+/// `L`: layer indices present
+const FLAG_LAYER_INDICES: u8 = 0x20;
+/// `F`: flexible mode
+const FLAG_FLEXIBLE: u8 = 0x10;
+/// `B`: start of a frame
+const FLAG_BEGIN: u8 = 0x08;
+/// `E`: end of a frame
+const FLAG_END: u8 = 0x04;
+/// `V`: scalability structure present
+const FLAG_SCALABILITY_STRUCTURE: u8 = 0x02;
+/// `M`: the picture id is 15 bits long
+const FLAG_LONG_PICTURE_ID: u8 = 0x80;
+/// `N`: another reference index follows
+const FLAG_MORE_REFERENCES: u8 = 0x01;
+
+/// The maximum number of frames in a superframe.
+const MAX_SUPERFRAME_FRAMES: usize = 8;
+
+/// Reads a byte at `offset` and advances it.
+fn read(payload: &[u8], offset: &mut usize) -> Result<u8, &'static str> {
+    let byte = *payload.get(*offset).ok_or("truncated payload descriptor")?;
+    *offset += 1;
+    Ok(byte)
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Vp9Depacketizer {
+    /// The complete frames of the picture, one per spatial layer.
+    frames: Vec<Vec<u8>>,
+    /// The frame being assembled.
+    frame: Option<Vec<u8>>,
+}
+
+impl Vp9Depacketizer {
+    pub(super) fn push(&mut self, payload: &Bytes) -> Result<(), &'static str> {
+        let mut offset = 0;
+        let descriptor = read(payload, &mut offset)?;
+
+        if descriptor & FLAG_PICTURE_ID != 0 && read(payload, &mut offset)? & FLAG_LONG_PICTURE_ID != 0 {
+            read(payload, &mut offset)?;
+        }
+
+        if descriptor & FLAG_LAYER_INDICES != 0 {
+            read(payload, &mut offset)?;
+            if descriptor & FLAG_FLEXIBLE == 0 {
+                read(payload, &mut offset)?; // TL0PICIDX
+            }
+        }
+
+        if descriptor & FLAG_FLEXIBLE != 0 && descriptor & FLAG_INTER_PICTURE != 0 {
+            for _ in 0..3 {
+                if read(payload, &mut offset)? & FLAG_MORE_REFERENCES == 0 {
+                    break;
+                }
+            }
+        }
+
+        if descriptor & FLAG_SCALABILITY_STRUCTURE != 0 {
+            let header = read(payload, &mut offset)?;
+            let spatial_layers = (header >> 5) as usize + 1;
+
+            // Y: resolutions present
+            if header & 0x10 != 0 {
+                offset += 4 * spatial_layers;
+            }
+
+            // G: picture group description present
+            if header & 0x08 != 0 {
+                for _ in 0..read(payload, &mut offset)? {
+                    let references = (read(payload, &mut offset)? >> 2) & 0x03;
+                    offset += references as usize;
+                }
+            }
+        }
+
+        let data = payload.get(offset..).ok_or("truncated payload descriptor")?;
+
+        if descriptor & FLAG_BEGIN != 0 {
+            self.frame = Some(Vec::new());
+        }
+
+        self.frame.as_mut().ok_or("missing start of frame")?.extend_from_slice(data);
+
+        if descriptor & FLAG_END != 0 {
+            let frame = self.frame.take().unwrap_or_default();
+            if self.frames.len() == MAX_SUPERFRAME_FRAMES {
+                return Err("too many frames in picture");
+            }
+
+            self.frames.push(frame);
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn finish(&mut self) -> Option<Payload> {
+        let mut frames = std::mem::take(&mut self.frames);
+        if self.frame.take().is_some() || frames.iter().any(Vec::is_empty) {
+            return None;
+        }
+
+        let keyframe = is_keyframe(frames.first()?);
+
+        let data = if frames.len() == 1 {
+            frames.remove(0)
+        } else {
+            superframe(&frames)
+        };
+
+        Some(Payload {
+            keyframe,
+            config: None,
+            data: Bytes::from(data),
+        })
+    }
+}
+
+/// Reads the frame type from the start of the uncompressed header.
+///
+/// VP9 Bitstream Specification - 6.2
+fn is_keyframe(frame: &[u8]) -> bool {
+    let byte = frame[0];
+    if byte >> 6 != 0b10 {
+        return false;
+    }
+
+    let profile = ((byte >> 4) & 0x02) | ((byte >> 5) & 0x01);
+    // Profile 3 has a reserved zero bit.
+    let shift = if profile == 3 { 2 } else { 3 };
+
+    let show_existing_frame = (byte >> shift) & 0x01 == 1;
+    let frame_type = (byte >> (shift - 1)) & 0x01;
+
+    !show_existing_frame && frame_type == 0
+}
+
+/// Combines the frames of all spatial layers into a superframe.
+///
+/// VP9 Bitstream Specification - Annex B
+fn superframe(frames: &[Vec<u8>]) -> Vec<u8> {
+    // Four bytes per frame size.
+    let marker = 0b1100_0000 | (3 << 3) | (frames.len() as u8 - 1);
+
+    let mut data: Vec<u8> = frames.concat();
+    data.push(marker);
+    for frame in frames {
+        data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    }
+    data.push(marker);
+
+    data
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::super::tests::packet;
+    use super::*;
+    use crate::codec::Codec;
+    use crate::depacketize::Depacketizer;
+
+    #[test]
+    fn frame_type() {
+        // frame_marker, profile 0, show_existing_frame 0, frame_type 0
+        assert!(is_keyframe(&[0b1000_0000]));
+        // frame_type 1
+        assert!(!is_keyframe(&[0b1000_0100]));
+        // show_existing_frame
+        assert!(!is_keyframe(&[0b1000_1000]));
+        // profile 3, reserved bit, frame_type 0
+        assert!(is_keyframe(&[0b1011_0000]));
+        assert!(!is_keyframe(&[0b1011_0010]));
+    }
+
+    #[test]
+    fn spatial_layers() {
+        let mut depacketizer = Depacketizer::new(Codec::Vp9);
+
+        // I with a short picture id, L, B, V with one spatial layer and its
+        // resolution.
+        depacketizer.push(&packet(
+            0,
+            0,
+            false,
+            &[0xAA, 0x01, 0x00, 0x00, 0x10, 0x05, 0x00, 0x02, 0xD0, 0x80, 0x01],
+        ));
+        // End of the first spatial layer frame.
+        depacketizer.push(&packet(1, 0, false, &[0x84, 0x02, 0x02]));
+        // Second spatial layer in a single packet.
+        depacketizer.push(&packet(2, 0, true, &[0x8C, 0x03, 0x86, 0x07]));
+
+        let frame = depacketizer.pop().unwrap();
+        assert!(frame.keyframe);
+        assert_eq!(
+            frame.data.as_ref(),
+            &[0x80, 0x01, 0x02, 0x86, 0x07, 0xD9, 3, 0, 0, 0, 2, 0, 0, 0, 0xD9]
+        );
+    }
+}
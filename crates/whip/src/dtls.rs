@@ -0,0 +1,502 @@
+//! DTLS 1.2 for the DTLS-SRTP key exchange.
+//!
+//! The handshake is done by OpenSSL. Both peers authenticate with self-signed
+//! certificates whose fingerprints are exchanged in the SDP, and the
+//! `use_srtp` extension negotiates `SRTP_AES128_CM_HMAC_SHA1_80`. The server
+//! side answers the first `ClientHello` with a `HelloVerifyRequest`. No
+//! application data is carried over the connection, it is only used to export
+//! the SRTP keys.
+//!
+//! The connection is sans-IO, datagrams are passed in with
+//! [`DtlsConnection::handle_datagram`] and taken out with
+//! [`DtlsConnection::poll_transmit`].
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::srtp::SrtpProfileId;
+use openssl::ssl::{ErrorCode, Ssl, SslStream, SslVerifyMode};
+use openssl::x509::X509VerifyResult;
+
+use crate::srtp::{MASTER_KEY_LEN, MASTER_SALT_LEN};
+
+mod certificate;
+
+pub use certificate::{DtlsCertificate, Fingerprint};
+
+/// The maximum size of a datagram sent by the connection.
+const MTU: u32 = 1200;
+
+/// How often the connection is driven during the handshake. OpenSSL
+/// retransmits the last flight once its own timer expired.
+const HANDSHAKE_TICK: Duration = Duration::from_millis(100);
+
+/// DTLS error.
+#[derive(Debug, thiserror::Error)]
+pub enum DtlsError {
+    /// OpenSSL error.
+    #[error("openssl: {0}")]
+    Ssl(#[from] ErrorStack),
+    /// The handshake failed or the peer sent a fatal alert.
+    #[error("handshake failure: {0}")]
+    HandshakeFailure(openssl::ssl::Error),
+    /// The peer did not negotiate `SRTP_AES128_CM_HMAC_SHA1_80`.
+    #[error("no supported srtp profile")]
+    UnsupportedSrtpProfile,
+    /// The fingerprint of the peer certificate does not match the SDP.
+    #[error("certificate fingerprint mismatch")]
+    FingerprintMismatch,
+    /// A cryptographic operation failed.
+    #[error("cryptographic failure")]
+    Crypto,
+}
+
+/// The SRTP master keys exported from a DTLS connection.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SrtpKeys {
+    /// The master key protecting packets sent by this peer.
+    pub local_key: [u8; MASTER_KEY_LEN],
+    /// The master salt protecting packets sent by this peer.
+    pub local_salt: [u8; MASTER_SALT_LEN],
+    /// The master key protecting packets sent by the remote peer.
+    pub remote_key: [u8; MASTER_KEY_LEN],
+    /// The master salt protecting packets sent by the remote peer.
+    pub remote_salt: [u8; MASTER_SALT_LEN],
+}
+
+impl std::fmt::Debug for SrtpKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SrtpKeys").finish_non_exhaustive()
+    }
+}
+
+/// The transport of the OpenSSL stream.
+///
+/// Every read returns one received datagram and every write, which OpenSSL
+/// does once per flushed flight or record, is one datagram to send.
+#[derive(Debug, Default)]
+struct Datagrams {
+    incoming: Option<Bytes>,
+    outgoing: VecDeque<Bytes>,
+}
+
+impl io::Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let datagram = self.incoming.take().ok_or(io::ErrorKind::WouldBlock)?;
+        let len = datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+        Ok(len)
+    }
+}
+
+impl io::Write for Datagrams {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.outgoing.push_back(Bytes::copy_from_slice(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A DTLS 1.2 connection.
+pub struct DtlsConnection {
+    is_client: bool,
+    remote_fingerprint: Fingerprint,
+    stream: SslStream<Datagrams>,
+    timeout: Option<Instant>,
+    closed: bool,
+    srtp_keys: Option<SrtpKeys>,
+}
+
+impl std::fmt::Debug for DtlsConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DtlsConnection")
+            .field("is_client", &self.is_client)
+            .field("remote_fingerprint", &self.remote_fingerprint)
+            .field("state", &self.stream.ssl().state_string_long())
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DtlsConnection {
+    fn new(is_client: bool, certificate: &DtlsCertificate, remote_fingerprint: Fingerprint) -> Result<Self, DtlsError> {
+        let mut ssl = Ssl::new(certificate.context())?;
+        ssl.set_mtu(MTU)?;
+
+        // The certificates are self-signed, only the fingerprint of the leaf
+        // certificate is checked.
+        ssl.set_verify_callback(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT, move |_, store| {
+            if store.error_depth() != 0 {
+                return true;
+            }
+
+            let matches = store
+                .current_cert()
+                .and_then(|certificate| certificate.digest(MessageDigest::sha256()).ok())
+                .is_some_and(|digest| digest[..] == remote_fingerprint.0);
+            if !matches {
+                store.set_error(X509VerifyResult::APPLICATION_VERIFICATION);
+            }
+
+            matches
+        });
+
+        if is_client {
+            ssl.set_connect_state();
+        } else {
+            ssl.set_accept_state();
+        }
+
+        Ok(Self {
+            is_client,
+            remote_fingerprint,
+            stream: SslStream::new(ssl, Datagrams::default())?,
+            timeout: None,
+            closed: false,
+            srtp_keys: None,
+        })
+    }
+
+    /// Creates the server side of a connection, waiting for the `ClientHello`.
+    ///
+    /// The handshake fails if the certificate of the client does not match
+    /// `remote_fingerprint`.
+    pub fn server(certificate: Arc<DtlsCertificate>, remote_fingerprint: Fingerprint) -> Result<Self, DtlsError> {
+        Self::new(false, &certificate, remote_fingerprint)
+    }
+
+    /// Creates the client side of a connection and starts the handshake.
+    ///
+    /// The handshake fails if the certificate of the server does not match
+    /// `remote_fingerprint`.
+    pub fn client(
+        now: Instant,
+        certificate: Arc<DtlsCertificate>,
+        remote_fingerprint: Fingerprint,
+    ) -> Result<Self, DtlsError> {
+        let mut connection = Self::new(true, &certificate, remote_fingerprint)?;
+        connection.drive(now)?;
+        Ok(connection)
+    }
+
+    /// Returns true if the datagram looks like a DTLS record.
+    pub fn is_dtls(data: &[u8]) -> bool {
+        data.first().is_some_and(|byte| (20..=63).contains(byte))
+    }
+
+    /// Returns true once the handshake is complete.
+    pub fn is_connected(&self) -> bool {
+        self.srtp_keys.is_some()
+    }
+
+    /// Returns true if the connection was closed by either peer.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the SRTP keys once the handshake is complete.
+    pub fn srtp_keys(&self) -> Option<&SrtpKeys> {
+        self.srtp_keys.as_ref()
+    }
+
+    /// Returns the time at which [`DtlsConnection::handle_timeout`] should be
+    /// called.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.timeout
+    }
+
+    /// Returns the next datagram to send.
+    pub fn poll_transmit(&mut self) -> Option<Bytes> {
+        self.stream.get_mut().outgoing.pop_front()
+    }
+
+    /// Retransmits the last flight if the peer did not respond in time.
+    pub fn handle_timeout(&mut self, now: Instant) -> Result<(), DtlsError> {
+        if self.timeout.is_some_and(|at| at <= now) {
+            self.drive(now)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a `close_notify` alert and closes the connection.
+    pub fn close(&mut self) -> Result<(), DtlsError> {
+        if !self.closed {
+            self.closed = true;
+            self.timeout = None;
+
+            // There is nothing to notify before the handshake is complete.
+            if self.is_connected() {
+                match self.stream.shutdown() {
+                    Ok(_) => {}
+                    Err(err) if err.code() == ErrorCode::WANT_READ => {}
+                    Err(err) => return Err(DtlsError::HandshakeFailure(err)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a datagram received from the peer.
+    ///
+    /// Datagrams are not authenticated, anyone who knows the address of the
+    /// session can send them. OpenSSL drops records that are malformed or
+    /// fail to decrypt, only a failed handshake or an authenticated fatal
+    /// alert are returned as errors.
+    pub fn handle_datagram(&mut self, now: Instant, data: Bytes) -> Result<(), DtlsError> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.stream.get_mut().incoming = Some(data);
+        let result = self.drive(now);
+        self.stream.get_mut().incoming = None;
+        result
+    }
+
+    /// Advances the handshake, or reads the records received after it.
+    fn drive(&mut self, now: Instant) -> Result<(), DtlsError> {
+        if !self.is_connected() {
+            match self.stream.do_handshake() {
+                Ok(()) => {
+                    self.timeout = None;
+                    self.export_srtp_keys()?;
+                }
+                Err(err) if err.code() == ErrorCode::WANT_READ => {
+                    self.timeout = Some(now + HANDSHAKE_TICK);
+                    return Ok(());
+                }
+                Err(err) => {
+                    self.closed = true;
+                    self.timeout = None;
+                    return Err(
+                        if self.stream.ssl().verify_result() == X509VerifyResult::APPLICATION_VERIFICATION {
+                            DtlsError::FingerprintMismatch
+                        } else {
+                            DtlsError::HandshakeFailure(err)
+                        },
+                    );
+                }
+            }
+        }
+
+        // There is no application data, reading handles alerts and the
+        // retransmissions of the last flight of the peer.
+        let mut buf = [0; MTU as usize];
+        loop {
+            match self.stream.ssl_read(&mut buf) {
+                Ok(_) => {}
+                Err(err) if err.code() == ErrorCode::WANT_READ => return Ok(()),
+                Err(err) if err.code() == ErrorCode::ZERO_RETURN => {
+                    self.closed = true;
+                    return Ok(());
+                }
+                Err(err) => {
+                    self.closed = true;
+                    return Err(DtlsError::HandshakeFailure(err));
+                }
+            }
+        }
+    }
+
+    fn export_srtp_keys(&mut self) -> Result<(), DtlsError> {
+        let ssl = self.stream.ssl();
+        if ssl.selected_srtp_profile().map(|profile| profile.id()) != Some(SrtpProfileId::SRTP_AES128_CM_SHA1_80) {
+            return Err(DtlsError::UnsupportedSrtpProfile);
+        }
+
+        let mut material = [0; 2 * (MASTER_KEY_LEN + MASTER_SALT_LEN)];
+        ssl.export_keying_material(&mut material, "EXTRACTOR-dtls_srtp", None)?;
+
+        let (keys, salts) = material.split_at(2 * MASTER_KEY_LEN);
+        let (client_key, server_key) = keys.split_at(MASTER_KEY_LEN);
+        let (client_salt, server_salt) = salts.split_at(MASTER_SALT_LEN);
+
+        let (local_key, local_salt, remote_key, remote_salt) = if self.is_client {
+            (client_key, client_salt, server_key, server_salt)
+        } else {
+            (server_key, server_salt, client_key, client_salt)
+        };
+
+        self.srtp_keys = Some(SrtpKeys {
+            local_key: local_key.try_into().map_err(|_| DtlsError::Crypto)?,
+            local_salt: local_salt.try_into().map_err(|_| DtlsError::Crypto)?,
+            remote_key: remote_key.try_into().map_err(|_| DtlsError::Crypto)?,
+            remote_salt: remote_salt.try_into().map_err(|_| DtlsError::Crypto)?,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    fn connect(now: Instant) -> (DtlsConnection, DtlsConnection) {
+        let client_certificate = Arc::new(DtlsCertificate::generate().unwrap());
+        let server_certificate = Arc::new(DtlsCertificate::generate().unwrap());
+
+        let server = DtlsConnection::server(server_certificate.clone(), client_certificate.fingerprint()).unwrap();
+        let client = DtlsConnection::client(now, client_certificate, server_certificate.fingerprint()).unwrap();
+        (client, server)
+    }
+
+    /// Delivers all pending datagrams of `from` to `to`.
+    fn deliver(now: Instant, from: &mut DtlsConnection, to: &mut DtlsConnection) -> Result<usize, DtlsError> {
+        let mut count = 0;
+        while let Some(datagram) = from.poll_transmit() {
+            to.handle_datagram(now, datagram)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Drops all pending datagrams of `from`.
+    fn discard(from: &mut DtlsConnection) {
+        while from.poll_transmit().is_some() {}
+    }
+
+    /// Drives the timer of `connection` until it retransmits.
+    fn retransmit(connection: &mut DtlsConnection) {
+        while connection.stream.get_ref().outgoing.is_empty() {
+            let at = connection.poll_timeout().unwrap();
+            std::thread::sleep(at.saturating_duration_since(Instant::now()));
+            connection.handle_timeout(Instant::now()).unwrap();
+        }
+    }
+
+    #[test]
+    fn handshake() {
+        let now = Instant::now();
+        let (mut client, mut server) = connect(now);
+
+        // The first ClientHello is answered with a HelloVerifyRequest.
+        deliver(now, &mut client, &mut server).unwrap();
+        assert!(!server.is_connected());
+        assert!(deliver(now, &mut server, &mut client).unwrap() > 0);
+
+        while deliver(now, &mut client, &mut server).unwrap() + deliver(now, &mut server, &mut client).unwrap() > 0 {}
+
+        assert!(client.is_connected());
+        assert!(server.is_connected());
+        assert!(client.poll_timeout().is_none());
+        assert!(server.poll_timeout().is_none());
+
+        let client_keys = client.srtp_keys().unwrap();
+        let server_keys = server.srtp_keys().unwrap();
+        assert_eq!(client_keys.local_key, server_keys.remote_key);
+        assert_eq!(client_keys.local_salt, server_keys.remote_salt);
+        assert_eq!(client_keys.remote_key, server_keys.local_key);
+        assert_eq!(client_keys.remote_salt, server_keys.local_salt);
+        assert_ne!(client_keys.local_key, client_keys.remote_key);
+
+        client.close().unwrap();
+        deliver(now, &mut client, &mut server).unwrap();
+        assert!(client.is_closed());
+        assert!(server.is_closed());
+    }
+
+    #[test]
+    fn lost_flights() {
+        let now = Instant::now();
+        let (mut client, mut server) = connect(now);
+
+        // The client hello is lost.
+        discard(&mut client);
+        retransmit(&mut client);
+        deliver(now, &mut client, &mut server).unwrap();
+
+        // The hello verify request is lost.
+        discard(&mut server);
+        retransmit(&mut client);
+        deliver(now, &mut client, &mut server).unwrap();
+        deliver(now, &mut server, &mut client).unwrap();
+
+        // The server flight is lost.
+        deliver(now, &mut client, &mut server).unwrap();
+        discard(&mut server);
+        retransmit(&mut server);
+
+        while deliver(now, &mut server, &mut client).unwrap() + deliver(now, &mut client, &mut server).unwrap() > 0 {}
+
+        assert!(client.is_connected());
+        assert!(server.is_connected());
+        assert_eq!(client.srtp_keys().unwrap().local_key, server.srtp_keys().unwrap().remote_key);
+    }
+
+    #[test]
+    fn spoofed_datagrams() {
+        let now = Instant::now();
+        let (mut client, mut server) = connect(now);
+
+        let malformed = [
+            // Truncated record header.
+            &[22, 0xFE, 0xFD, 0, 0][..],
+            // Unsupported version.
+            &[22, 0x03, 0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // Record longer than the datagram.
+            &[22, 0xFE, 0xFD, 0, 0, 0, 0, 0, 0, 0, 1, 0, 40, 1, 0, 0, 9],
+        ];
+
+        for datagram in malformed {
+            server.handle_datagram(now, Bytes::from_static(datagram)).unwrap();
+            client.handle_datagram(now, Bytes::from_static(datagram)).unwrap();
+        }
+
+        while deliver(now, &mut client, &mut server).unwrap() + deliver(now, &mut server, &mut client).unwrap() > 0 {}
+        assert!(client.is_connected());
+        assert!(server.is_connected());
+
+        let spoofed = [
+            // Plaintext fatal handshake_failure alert.
+            &[21, 0xFE, 0xFD, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 2, 40][..],
+            // Plaintext close_notify alert.
+            &[21, 0xFE, 0xFD, 0, 0, 0, 0, 0, 0, 0, 3, 0, 2, 1, 0],
+            // Encrypted alert with a bad tag.
+            &[
+                21, 0xFE, 0xFD, 0, 1, 0, 0, 0, 0, 0, 9, 0, 26, 0, 0, 0, 0, 0, 0, 0, 9, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11,
+                12, 13, 14, 15, 16, 17, 18,
+            ],
+        ];
+
+        for datagram in malformed.into_iter().chain(spoofed) {
+            server.handle_datagram(now, Bytes::from_static(datagram)).unwrap();
+            client.handle_datagram(now, Bytes::from_static(datagram)).unwrap();
+        }
+
+        assert!(!client.is_closed());
+        assert!(!server.is_closed());
+    }
+
+    #[test]
+    fn fingerprint_mismatch() {
+        let now = Instant::now();
+        let client_certificate = Arc::new(DtlsCertificate::generate().unwrap());
+        let server_certificate = Arc::new(DtlsCertificate::generate().unwrap());
+
+        let mut server = DtlsConnection::server(server_certificate.clone(), Fingerprint([0; 32])).unwrap();
+        let mut client = DtlsConnection::client(now, client_certificate, server_certificate.fingerprint()).unwrap();
+
+        let err = loop {
+            if let Err(err) = deliver(now, &mut client, &mut server) {
+                break err;
+            }
+            deliver(now, &mut server, &mut client).unwrap();
+        };
+
+        assert!(matches!(err, DtlsError::FingerprintMismatch));
+        assert!(server.is_closed());
+    }
+}
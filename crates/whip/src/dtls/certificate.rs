@@ -0,0 +1,244 @@
+//! Self-signed certificates and their fingerprints.
+//!
+//! WebRTC peers do not validate certificate chains, instead the SHA-256
+//! fingerprint of the certificate is exchanged in the SDP and compared during
+//! the DTLS handshake.
+
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::ex_data::Index;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{Ssl, SslContext, SslMethod, SslOptions, SslVersion};
+use openssl::x509::{X509Builder, X509NameBuilder};
+
+use super::DtlsError;
+
+/// The common name of generated certificates.
+const COMMON_NAME: &str = "scuffle-whip";
+
+/// The time a generated certificate is valid for.
+const VALIDITY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The cipher suites offered and accepted, in order of preference.
+///
+/// The RSA suites are only used when connecting to a peer with a RSA
+/// certificate.
+const CIPHER_LIST: &str = "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-ECDSA-CHACHA20-POLY1305:\
+                           ECDHE-RSA-AES128-GCM-SHA256:ECDHE-RSA-AES256-GCM-SHA384:ECDHE-RSA-CHACHA20-POLY1305";
+
+/// The SRTP protection profile negotiated with the `use_srtp` extension.
+const SRTP_PROFILE: &str = "SRTP_AES128_CM_SHA1_80";
+
+/// The length of the cookie of a HelloVerifyRequest.
+const COOKIE_LEN: usize = 32;
+
+/// The slot of a connection holding the cookie of its HelloVerifyRequest.
+fn cookie_index() -> Result<Index<Ssl, [u8; COOKIE_LEN]>, ErrorStack> {
+    static INDEX: OnceLock<Index<Ssl, [u8; COOKIE_LEN]>> = OnceLock::new();
+
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+
+    let index = Ssl::new_ex_index()?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+/// The SHA-256 fingerprint of a certificate.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl Fingerprint {
+    /// Computes the fingerprint of a DER encoded certificate.
+    pub fn of(der: &[u8]) -> Self {
+        Self(openssl::sha::sha256(der))
+    }
+
+    /// Parses the value of a SDP `fingerprint` attribute.
+    ///
+    /// Returns `None` if the hash function is not SHA-256 or the value is
+    /// malformed.
+    pub fn parse_sdp(value: &str) -> Option<Self> {
+        let (hash, fingerprint) = value.split_once(' ')?;
+        if !hash.eq_ignore_ascii_case("sha-256") {
+            return None;
+        }
+
+        let mut bytes = [0; 32];
+        let mut parts = fingerprint.trim().split(':');
+        for byte in &mut bytes {
+            *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+        }
+
+        parts.next().is_none().then_some(Self(bytes))
+    }
+}
+
+/// Formats the fingerprint as the value of a SDP `fingerprint` attribute.
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sha-256 ")?;
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fingerprint({self})")
+    }
+}
+
+/// A self-signed ECDSA P-256 certificate used for the DTLS handshake.
+///
+/// It also holds the OpenSSL context all connections using the certificate
+/// are created from.
+pub struct DtlsCertificate {
+    der: Bytes,
+    fingerprint: Fingerprint,
+    context: SslContext,
+}
+
+impl fmt::Debug for DtlsCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DtlsCertificate")
+            .field("fingerprint", &self.fingerprint)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DtlsCertificate {
+    /// Generates a new key pair and a self-signed certificate, valid from one
+    /// day ago for 30 days.
+    pub fn generate() -> Result<Self, DtlsError> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_nid(Nid::COMMONNAME, COMMON_NAME)?;
+        let name = name.build();
+
+        let mut serial = BigNum::new()?;
+        serial.rand(63, MsbOption::MAYBE_ZERO, false)?;
+        let serial = serial.to_asn1_integer()?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let not_before = now.saturating_sub(Duration::from_secs(24 * 60 * 60));
+        let not_after = now + VALIDITY;
+        let not_before = Asn1Time::from_unix(not_before.as_secs() as _)?;
+        let not_after = Asn1Time::from_unix(not_after.as_secs() as _)?;
+
+        let mut builder = X509Builder::new()?;
+        builder.set_version(2)?;
+        builder.set_serial_number(&serial)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(&key)?;
+        builder.set_not_before(&not_before)?;
+        builder.set_not_after(&not_after)?;
+        builder.sign(&key, MessageDigest::sha256())?;
+        let certificate = builder.build();
+
+        let mut context = SslContext::builder(SslMethod::dtls())?;
+        context.set_min_proto_version(Some(SslVersion::DTLS1_2))?;
+        context.set_certificate(&certificate)?;
+        context.set_private_key(&key)?;
+        context.check_private_key()?;
+        context.set_cipher_list(CIPHER_LIST)?;
+        context.set_tlsext_use_srtp(SRTP_PROFILE)?;
+        // The MTU is set on every connection, the transport cannot be queried.
+        context.set_options(SslOptions::NO_QUERY_MTU | SslOptions::COOKIE_EXCHANGE);
+
+        // Every connection has its own transport, so the cookie only has to be
+        // repeated by the second ClientHello of the same connection. The client
+        // random cannot be used, it is not known yet when the cookie is verified.
+        let cookie_index = cookie_index()?;
+        context.set_cookie_generate_cb(move |ssl, cookie| {
+            let mut generated = [0; COOKIE_LEN];
+            openssl::rand::rand_bytes(&mut generated)?;
+            ssl.set_ex_data(cookie_index, generated);
+
+            let len = COOKIE_LEN.min(cookie.len());
+            cookie[..len].copy_from_slice(&generated[..len]);
+            Ok(len)
+        });
+        context.set_cookie_verify_cb(move |ssl, cookie| {
+            ssl.ex_data(cookie_index)
+                .is_some_and(|generated| cookie.len() == COOKIE_LEN && openssl::memcmp::eq(generated, cookie))
+        });
+
+        let der = certificate.to_der()?;
+
+        Ok(Self {
+            fingerprint: Fingerprint::of(&der),
+            der: Bytes::from(der),
+            context: context.build(),
+        })
+    }
+
+    /// Returns the DER encoded certificate.
+    pub fn der(&self) -> &Bytes {
+        &self.der
+    }
+
+    /// Returns the fingerprint of the certificate.
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    pub(super) fn context(&self) -> &SslContext {
+        &self.context
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use openssl::x509::X509;
+
+    use super::*;
+
+    #[test]
+    fn generate() {
+        let certificate = DtlsCertificate::generate().unwrap();
+        assert_eq!(certificate.fingerprint(), Fingerprint::of(certificate.der()));
+
+        let x509 = X509::from_der(certificate.der()).unwrap();
+        let common_name = x509.subject_name().entries_by_nid(Nid::COMMONNAME).next().unwrap();
+        assert_eq!(common_name.data().as_slice(), COMMON_NAME.as_bytes());
+        assert_eq!(
+            x509.public_key().unwrap().ec_key().unwrap().group().curve_name(),
+            Some(Nid::X9_62_PRIME256V1)
+        );
+        assert_eq!(
+            &x509.digest(MessageDigest::sha256()).unwrap()[..],
+            &certificate.fingerprint().0[..]
+        );
+    }
+
+    #[test]
+    fn fingerprint_sdp() {
+        let fingerprint = Fingerprint([0xAB; 32]);
+        let value = fingerprint.to_string();
+        assert!(value.starts_with("sha-256 AB:AB:"));
+        assert_eq!(Fingerprint::parse_sdp(&value), Some(fingerprint));
+        assert_eq!(Fingerprint::parse_sdp(&value.to_lowercase()), Some(fingerprint));
+        assert_eq!(Fingerprint::parse_sdp("sha-1 AB:CD"), None);
+        assert_eq!(Fingerprint::parse_sdp(&format!("{value}:AB")), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! Error types.
+
+use crate::dtls::DtlsError;
+use crate::session::server::ServerSessionError;
+
+/// WHIP error.
+#[derive(Debug, thiserror::Error)]
+pub enum WhipError {
+    /// IO error.
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    /// The SDP is malformed.
+    #[error("invalid sdp: {0}")]
+    InvalidSdp(String),
+    /// The offer does not contain anything we can receive.
+    #[error("unsupported offer: {0}")]
+    UnsupportedOffer(&'static str),
+    /// A STUN message is malformed.
+    #[error("invalid stun message: {0}")]
+    InvalidStun(&'static str),
+    /// A RTP or RTCP packet is malformed.
+    #[error("invalid rtp packet: {0}")]
+    InvalidRtp(&'static str),
+    /// A SRTP or SRTCP packet could not be unprotected.
+    #[error("srtp: {0}")]
+    Srtp(&'static str),
+    /// DTLS error.
+    #[error("dtls: {0}")]
+    Dtls(#[from] DtlsError),
+    /// The server has no host candidates to offer.
+    #[error("no candidates configured")]
+    NoCandidates,
+    /// The ICE and DTLS handshake did not complete in time.
+    #[error("handshake timeout")]
+    HandshakeTimeout,
+    /// Nothing was received from the peer for too long.
+    #[error("peer idle timeout")]
+    PeerIdle,
+    /// Session error.
+    #[error("session error: {0}")]
+    Session(#[from] ServerSessionError),
+    /// The session or server is closed.
+    #[error("closed")]
+    Closed,
+}
@@ -0,0 +1,497 @@
+//! A WHIP (WebRTC-HTTP ingestion protocol) endpoint, for receiving
+//! WebRTC publishers like OBS and browsers next to RTMP.
+//!
+//! This crate implements the receiving side of a WHIP session:
+//!
+//! - SDP offer/answer negotiation over HTTP with [`WhipService`], which can
+//!   be served by [`scuffle-http`][scuffle_http],
+//! - ICE-lite with host candidates on a single UDP socket shared by all
+//!   publishers,
+//! - DTLS 1.2 through OpenSSL with a self-signed ECDSA certificate and SRTP
+//!   key export,
+//! - SRTP/SRTCP with `AES_CM_128_HMAC_SHA1_80`,
+//! - reordering of RTP packets, key frame requests (PLI) and bitrate limits
+//!   (REMB),
+//! - depacketizing H.264, VP8, VP9, AV1 and Opus into access units.
+//!
+//! The [`ServerSession`] passes the access units to a
+//! [`SessionHandler`](session::server::SessionHandler) shaped like the one of
+//! the RTMP server session, with millisecond timestamps, so WHIP and RTMP
+//! publishers can be handled the same way.
+//!
+//! Trickle ICE, ICE restarts, simulcast and data channels are not supported.
+#![cfg_attr(feature = "docs", doc = "\n\nSee the [changelog][changelog] for a full release history.")]
+#![cfg_attr(feature = "docs", doc = "## Feature flags")]
+#![cfg_attr(feature = "docs", doc = document_features::document_features!())]
+//! ## Example
+//!
+//! ```no_run
+//! # use scuffle_whip::{ServerSession, WhipConfig, WhipServer};
+//! # use scuffle_whip::session::server::{ServerSessionError, SessionData, SessionHandler};
+//! #
+//! struct Handler;
+//!
+//! impl SessionHandler for Handler {
+//!     async fn on_data(&mut self, stream_id: u32, data: SessionData) -> Result<(), ServerSessionError> {
+//!         // Handle incoming video/audio data
+//!         Ok(())
+//!     }
+//!
+//!     async fn on_publish(&mut self, stream_id: u32, app_name: &str, stream_name: &str) -> Result<(), ServerSessionError> {
+//!         // Handle the publish event
+//!         Ok(())
+//!     }
+//!
+//!     async fn on_unpublish(&mut self, stream_id: u32) -> Result<(), ServerSessionError> {
+//!         // Handle the unpublish event
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let config = WhipConfig {
+//!         // The public address of this host.
+//!         candidates: vec!["203.0.113.1".parse().unwrap()],
+//!         ..Default::default()
+//!     };
+//!
+//!     let mut server = WhipServer::bind("[::]:8189", config).await.unwrap();
+//!     // receiving media on [::]:8189
+//!
+//!     // Publishers post their offers to http://<host>:8080/<app>/<stream>
+//!     let http = scuffle_http::HttpServer::builder()
+//!         .service_factory(scuffle_http::service::service_clone_factory(server.service()))
+//!         .bind("[::]:8080".parse().unwrap())
+//!         .build();
+//!     tokio::spawn(http.run());
+//!
+//!     while let Ok(publisher) = server.accept().await {
+//!         let session = ServerSession::new(publisher, Handler);
+//!
+//!         tokio::spawn(async move {
+//!             if let Err(err) = session.run().await {
+//!                 // Handle the session error
+//!             }
+//!         });
+//!     }
+//! }
+//! ```
+//!
+//! ## Specifications
+//!
+//! | Name | Version | Link | Comments |
+//! | --- | --- | --- | --- |
+//! | WebRTC-HTTP Ingestion Protocol (WHIP) | `RFC 9725` | <https://datatracker.ietf.org/doc/html/rfc9725> | Trickle ICE and ICE restarts are not supported |
+//! | SDP: Session Description Protocol | `RFC 8866` | <https://datatracker.ietf.org/doc/html/rfc8866> | |
+//! | Interactive Connectivity Establishment (ICE) | `RFC 8445` | <https://datatracker.ietf.org/doc/html/rfc8445> | ICE-lite |
+//! | Session Traversal Utilities for NAT (STUN) | `RFC 8489` | <https://datatracker.ietf.org/doc/html/rfc8489> | Short-term credentials only |
+//! | Datagram Transport Layer Security Version 1.2 | `RFC 6347` | <https://datatracker.ietf.org/doc/html/rfc6347> | `TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256` only |
+//! | DTLS Extension to Establish Keys for SRTP | `RFC 5764` | <https://datatracker.ietf.org/doc/html/rfc5764> | |
+//! | The Secure Real-time Transport Protocol (SRTP) | `RFC 3711` | <https://datatracker.ietf.org/doc/html/rfc3711> | `AES_CM_128_HMAC_SHA1_80` only |
+//! | RTP Payload Format for H.264 Video | `RFC 6184` | <https://datatracker.ietf.org/doc/html/rfc6184> | Interleaved mode is not supported |
+//! | RTP Payload Format for VP8 Video | `RFC 7741` | <https://datatracker.ietf.org/doc/html/rfc7741> | |
+//! | RTP Payload Format for VP9 Video | `RFC 9628` | <https://datatracker.ietf.org/doc/html/rfc9628> | |
+//! | RTP Payload Format For AV1 | `v1.0.0` | <https://aomediacodec.github.io/av1-rtp-spec/> | |
+//! | RTP Payload Format for the Opus Speech and Audio Codec | `RFC 7587` | <https://datatracker.ietf.org/doc/html/rfc7587> | |
+//!
+//! ## License
+//!
+//! This project is licensed under the MIT or Apache-2.0 license.
+//! You can choose between one of them if you use this work.
+//!
+//! `SPDX-License-Identifier: MIT OR Apache-2.0`
+#![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
+#![deny(unreachable_pub)]
+#![deny(clippy::mod_module_files)]
+
+mod codec;
+mod config;
+mod publisher;
+mod reorder;
+mod server;
+
+pub mod depacketize;
+pub mod dtls;
+pub mod error;
+pub mod rtp;
+pub mod sdp;
+pub mod session;
+pub mod srtp;
+pub mod stun;
+
+pub use codec::{Codec, CodecConfig};
+pub use config::WhipConfig;
+pub use publisher::{Frame, Publisher, Track};
+pub use server::{PublishFilter, WhipServer, WhipService};
+pub use session::server::ServerSession;
+
+/// Changelogs generated by [scuffle_changelog]
+#[cfg(feature = "docs")]
+#[scuffle_changelog::changelog]
+pub mod changelog {}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use http::{StatusCode, header};
+    use http_body_util::{BodyExt, Full};
+    use tokio::net::UdpSocket;
+    use tokio::sync::mpsc;
+
+    use crate::dtls::{DtlsCertificate, DtlsConnection, Fingerprint};
+    use crate::rtp::{RtcpPacket, RtpPacket};
+    use crate::sdp::SessionDescription;
+    use crate::session::server::{ServerSessionError, SessionData, SessionHandler};
+    use crate::srtp::SrtpContext;
+    use crate::stun::{Attribute, MessageType, StunMessage};
+    use crate::{Codec, CodecConfig, ServerSession, WhipConfig, WhipServer};
+
+    const SPS: &[u8] = &[0x67, 0x42, 0xC0, 0x1F, 0xDA, 0x01, 0x40, 0x16, 0xE4];
+    const PPS: &[u8] = &[0x68, 0xCE, 0x3C, 0x80];
+
+    fn request(method: http::Method, uri: &str, body: impl Into<Bytes>) -> http::Request<Full<Bytes>> {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/sdp")
+            .body(Full::new(body.into()))
+            .unwrap()
+    }
+
+    async fn recv(socket: &UdpSocket) -> Bytes {
+        let mut buf = vec![0; 1500];
+        let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .expect("timed out")
+            .unwrap();
+        buf.truncate(len);
+        Bytes::from(buf)
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Publish(String, String),
+        Track(u32, Codec),
+        Video(u32, bool, Bytes),
+        Audio(u32, Bytes),
+        Unpublish,
+    }
+
+    struct Handler(mpsc::UnboundedSender<Event>);
+
+    impl SessionHandler for Handler {
+        async fn on_publish(&mut self, _: u32, app_name: &str, stream_name: &str) -> Result<(), ServerSessionError> {
+            self.0.send(Event::Publish(app_name.into(), stream_name.into())).unwrap();
+            Ok(())
+        }
+
+        async fn on_unpublish(&mut self, _: u32) -> Result<(), ServerSessionError> {
+            self.0.send(Event::Unpublish).unwrap();
+            Ok(())
+        }
+
+        async fn on_data(&mut self, _: u32, data: SessionData) -> Result<(), ServerSessionError> {
+            let event = match data {
+                SessionData::Track(track) => {
+                    if track.codec == Codec::H264 {
+                        assert!(matches!(track.config, Some(CodecConfig::Avc(_))));
+                    }
+                    Event::Track(track.id, track.codec)
+                }
+                SessionData::Video { timestamp, frame } => Event::Video(timestamp, frame.keyframe, frame.data),
+                SessionData::Audio { timestamp, frame } => Event::Audio(timestamp, frame.data),
+            };
+            self.0.send(event).unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn publish() {
+        let mut server = WhipServer::bind("127.0.0.1:0", WhipConfig::default()).await.unwrap();
+        let service = server.service();
+        let certificate = Arc::new(DtlsCertificate::generate().unwrap());
+
+        // Offer/answer
+        let offer = crate::sdp::tests::offer(certificate.fingerprint());
+        let response = service.handle(request(http::Method::POST, "/live/key", offer)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_owned();
+        assert!(location.starts_with("/live/key/"));
+
+        let answer = response.into_body().collect().await.unwrap().to_bytes();
+        let answer = SessionDescription::parse(std::str::from_utf8(&answer).unwrap()).unwrap();
+        let ufrag = answer.attribute("ice-ufrag").unwrap().to_owned();
+        let pwd = answer.attribute("ice-pwd").unwrap().to_owned();
+        let fingerprint = Fingerprint::parse_sdp(answer.attribute("fingerprint").unwrap()).unwrap();
+        let candidate: Vec<_> = answer.media[0].attribute("candidate").unwrap().split(' ').collect();
+        let server_addr = SocketAddr::new(candidate[4].parse().unwrap(), candidate[5].parse().unwrap());
+        assert_eq!(server_addr, server.local_addr());
+
+        let publisher = server.accept().await.unwrap();
+        assert_eq!(publisher.tracks().len(), 2);
+        let (tx, mut events) = mpsc::unbounded_channel();
+        let session = tokio::spawn(ServerSession::new(publisher, Handler(tx)).run());
+        assert_eq!(events.recv().await, Some(Event::Publish("live".into(), "key".into())));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server_addr).await.unwrap();
+
+        // ICE connectivity check
+        let mut binding = StunMessage::new(MessageType::BindingRequest, [7; 12]);
+        binding.attributes.push(Attribute::Username(format!("{ufrag}:Ag5q")));
+        binding.attributes.push(Attribute::IceControlling(1));
+        binding.attributes.push(Attribute::UseCandidate);
+        socket.send(&binding.write(Some(pwd.as_bytes()))).await.unwrap();
+
+        let data = recv(&socket).await;
+        let response = StunMessage::parse(data.clone()).unwrap();
+        assert_eq!(response.message_type, MessageType::BindingSuccess);
+        assert_eq!(response.transaction_id, [7; 12]);
+        assert!(response.verify_integrity(&data, pwd.as_bytes()));
+        assert_eq!(
+            response.attributes,
+            vec![Attribute::XorMappedAddress(socket.local_addr().unwrap())]
+        );
+
+        // DTLS handshake
+        let mut dtls = DtlsConnection::client(Instant::now(), certificate, fingerprint).unwrap();
+        while !dtls.is_connected() {
+            while let Some(datagram) = dtls.poll_transmit() {
+                socket.send(&datagram).await.unwrap();
+            }
+
+            let data = recv(&socket).await;
+            if DtlsConnection::is_dtls(&data) {
+                dtls.handle_datagram(Instant::now(), data).unwrap();
+            }
+        }
+
+        let keys = dtls.srtp_keys().unwrap();
+        let mut srtp = SrtpContext::new(&keys.local_key, &keys.local_salt).unwrap();
+        let mut srtcp = SrtpContext::new(&keys.remote_key, &keys.remote_salt).unwrap();
+
+        let mut send = async |payload_type, ssrc, sequence_number, timestamp, marker, payload: Vec<u8>| {
+            let mut packet = Vec::new();
+            RtpPacket {
+                marker,
+                payload_type,
+                sequence_number,
+                timestamp,
+                ssrc,
+                payload: Bytes::from(payload),
+            }
+            .write(&mut packet)
+            .unwrap();
+            socket.send(&srtp.protect_rtp(&packet).unwrap()).await.unwrap();
+        };
+
+        // H.264: a STAP-A with the parameter sets and a fragmented IDR slice,
+        // then a single NAL unit.
+        let mut stap_a = vec![0x78];
+        for nal_unit in [SPS, PPS] {
+            stap_a.extend_from_slice(&(nal_unit.len() as u16).to_be_bytes());
+            stap_a.extend_from_slice(nal_unit);
+        }
+        send(97, 2222, 100, 9000, false, stap_a).await;
+        send(97, 2222, 101, 9000, false, vec![0x7C, 0x85, 1, 2, 3]).await;
+        send(97, 2222, 102, 9000, true, vec![0x7C, 0x45, 4, 5, 6]).await;
+        send(97, 2222, 103, 12000, true, vec![0x41, 7, 8, 9]).await;
+
+        // Opus
+        send(111, 1111, 5, 48000, true, vec![0xFC, 0]).await;
+        send(111, 1111, 6, 48960, true, vec![0xFC, 1]).await;
+
+        let mut keyframe = Vec::new();
+        for nal_unit in [SPS, PPS, &[0x65, 1, 2, 3, 4, 5, 6]] {
+            keyframe.extend_from_slice(&[0, 0, 0, 1]);
+            keyframe.extend_from_slice(nal_unit);
+        }
+
+        assert_eq!(events.recv().await, Some(Event::Track(2, Codec::H264)));
+        assert_eq!(events.recv().await, Some(Event::Video(0, true, Bytes::from(keyframe))));
+        assert_eq!(
+            events.recv().await,
+            Some(Event::Video(33, false, Bytes::from_static(&[0, 0, 0, 1, 0x41, 7, 8, 9])))
+        );
+        assert_eq!(events.recv().await, Some(Event::Track(1, Codec::Opus)));
+        let Some(Event::Audio(offset, data)) = events.recv().await else {
+            panic!("expected audio");
+        };
+        assert_eq!(data, Bytes::from_static(&[0xFC, 0]));
+        assert_eq!(
+            events.recv().await,
+            Some(Event::Audio(offset + 20, Bytes::from_static(&[0xFC, 1])))
+        );
+
+        // The server requested a key frame while waiting for the parameter
+        // sets and sends bitrate estimates.
+        let mut pli = None;
+        while pli.is_none() {
+            let data = recv(&socket).await;
+            for packet in RtcpPacket::parse_compound(srtcp.unprotect_rtcp(&data).unwrap()).unwrap() {
+                match packet {
+                    RtcpPacket::PictureLossIndication { media_ssrc, .. } => pli = Some(media_ssrc),
+                    RtcpPacket::ReceiverEstimatedMaximumBitrate { bitrate, .. } => assert_eq!(bitrate, 10_000_000),
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(pli, Some(2222));
+
+        // Ending the session
+        let response = service.handle(request(http::Method::DELETE, &location, "")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(session.await.unwrap().unwrap());
+        assert_eq!(events.recv().await, Some(Event::Unpublish));
+
+        // The publisher was removed.
+        let response = service.handle(request(http::Method::DELETE, &location, "")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Publishes to the server with str0m, an independent WebRTC
+    /// implementation, to check interoperability of ICE, DTLS and SRTP.
+    #[tokio::test]
+    async fn publish_str0m() {
+        use str0m::change::SdpAnswer;
+        use str0m::media::{Direction, Frequency, MediaKind, MediaTime};
+        use str0m::net::{Protocol, Receive};
+        use str0m::{Candidate, Input, Output, Rtc};
+
+        let mut server = WhipServer::bind("127.0.0.1:0", WhipConfig::default()).await.unwrap();
+        let service = server.service();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = socket.local_addr().unwrap();
+
+        let mut rtc = Rtc::new();
+        rtc.add_local_candidate(Candidate::host(local_addr, "udp").unwrap());
+        let mut change = rtc.sdp_api();
+        let mid = change.add_media(MediaKind::Video, Direction::SendOnly, None, None, None);
+        let (offer, pending) = change.apply().unwrap();
+
+        let response = service
+            .handle(request(http::Method::POST, "/live/str0m", offer.to_sdp_string()))
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_owned();
+        let answer = response.into_body().collect().await.unwrap().to_bytes();
+        let answer = SdpAnswer::from_sdp_string(std::str::from_utf8(&answer).unwrap()).unwrap();
+        rtc.sdp_api().accept_answer(pending, answer).unwrap();
+
+        let publisher = server.accept().await.unwrap();
+        let (tx, mut events) = mpsc::unbounded_channel();
+        let session = tokio::spawn(ServerSession::new(publisher, Handler(tx)).run());
+        assert_eq!(events.recv().await, Some(Event::Publish("live".into(), "str0m".into())));
+
+        let mut keyframe = Vec::new();
+        for nal_unit in [SPS, PPS, &[0x65, 1, 2, 3, 4, 5, 6]] {
+            keyframe.extend_from_slice(&[0, 0, 0, 1]);
+            keyframe.extend_from_slice(nal_unit);
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut buf = vec![0; 2000];
+        loop {
+            assert!(Instant::now() < deadline, "timed out");
+
+            let timeout = match rtc.poll_output().unwrap() {
+                Output::Timeout(timeout) => timeout,
+                Output::Transmit(transmit) => {
+                    socket.send_to(&transmit.contents, transmit.destination).await.unwrap();
+                    continue;
+                }
+                // ICE and DTLS are done, send a key frame.
+                Output::Event(str0m::Event::Connected) => {
+                    let writer = rtc.writer(mid).unwrap();
+                    let pt = writer
+                        .payload_params()
+                        .find(|params| params.spec().codec == str0m::format::Codec::H264)
+                        .unwrap()
+                        .pt();
+                    writer
+                        .write(pt, Instant::now(), MediaTime::new(0, Frequency::NINETY_KHZ), keyframe.clone())
+                        .unwrap();
+                    continue;
+                }
+                Output::Event(_) => continue,
+            };
+
+            match events.try_recv() {
+                Ok(Event::Track(_, codec)) => assert_eq!(codec, Codec::H264),
+                Ok(Event::Video(_, is_keyframe, data)) => {
+                    assert!(is_keyframe);
+                    assert_eq!(data, keyframe);
+                    break;
+                }
+                Ok(event) => panic!("unexpected event: {event:?}"),
+                Err(_) => {}
+            }
+
+            let wait = timeout
+                .saturating_duration_since(Instant::now())
+                .min(Duration::from_millis(50));
+            match tokio::time::timeout(wait, socket.recv_from(&mut buf)).await {
+                Ok(Ok((len, source))) => {
+                    let receive = Receive::new(Protocol::Udp, source, local_addr, &buf[..len]).unwrap();
+                    rtc.handle_input(Input::Receive(Instant::now(), receive)).unwrap();
+                }
+                _ => rtc.handle_input(Input::Timeout(Instant::now())).unwrap(),
+            }
+        }
+
+        let response = service.handle(request(http::Method::DELETE, &location, "")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(session.await.unwrap().unwrap());
+        assert_eq!(events.recv().await, Some(Event::Unpublish));
+    }
+
+    #[tokio::test]
+    async fn requests() {
+        let filter = |request: &http::request::Parts| match request.headers.get(header::AUTHORIZATION) {
+            Some(value) if value == "Bearer secret" => Ok(()),
+            _ => Err(StatusCode::UNAUTHORIZED),
+        };
+
+        let server = WhipServer::bind_with_filter("127.0.0.1:0", WhipConfig::default(), filter)
+            .await
+            .unwrap();
+        let service = server.service();
+        let offer = crate::sdp::tests::offer(Fingerprint([0xAB; 32]));
+
+        let response = service.handle(request(http::Method::POST, "/live/key", offer.clone())).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+        let mut authorized = request(http::Method::POST, "/live/key", "v=0\r\n");
+        authorized
+            .headers_mut()
+            .insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert_eq!(service.handle(authorized).await.status(), StatusCode::BAD_REQUEST);
+
+        let mut json = request(http::Method::POST, "/live/key", offer);
+        json.headers_mut()
+            .insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        assert_eq!(service.handle(json).await.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = service.handle(request(http::Method::OPTIONS, "/live/key", "")).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()["Accept-Post"], "application/sdp");
+
+        let response = service.handle(request(http::Method::GET, "/live/key", "")).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = service.handle(request(http::Method::DELETE, "/live/key/unknown", "")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
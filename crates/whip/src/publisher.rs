@@ -0,0 +1,479 @@
+//! A publisher of a WHIP server.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use tokio::sync::mpsc;
+
+use crate::codec::{Codec, CodecConfig};
+use crate::depacketize::Depacketizer;
+use crate::dtls::DtlsConnection;
+use crate::error::WhipError;
+use crate::reorder::ReorderBuffer;
+use crate::rtp::{self, RtcpPacket, RtcpType, RtpPacket};
+use crate::sdp::Negotiated;
+use crate::server::Shared;
+use crate::srtp::SrtpContext;
+use crate::stun::{Attribute, AttributeType, MessageType, StunMessage};
+
+/// The minimum interval between two key frame requests of a stream.
+const PLI_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The interval of the receiver estimated maximum bitrate.
+const REMB_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A track of a publisher, one for every accepted media description of the
+/// offer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    /// The id of the track, unique within the publisher.
+    pub id: u32,
+    /// The media id of the media description.
+    pub mid: String,
+    /// The negotiated codec.
+    pub codec: Codec,
+    /// The decoder configuration last found in the stream.
+    ///
+    /// This is `None` for codecs that carry no out of band configuration and
+    /// until the first configuration was received.
+    pub config: Option<CodecConfig>,
+}
+
+/// A frame received from a publisher.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The id of the track the frame belongs to.
+    pub track_id: u32,
+    /// The codec of the frame.
+    pub codec: Codec,
+    /// The RTP timestamp of the frame, in the clock rate of the codec.
+    pub timestamp: u32,
+    /// When the last packet of the frame was received.
+    pub received: Instant,
+    /// If the frame can be decoded without any previous frames.
+    pub keyframe: bool,
+    /// If frames were dropped before this one because of packet loss.
+    pub discontinuity: bool,
+    /// The decoder configuration found in the frame, if any.
+    pub config: Option<CodecConfig>,
+    /// The frame data, see [`Codec`] for the format.
+    pub data: Bytes,
+}
+
+/// The identity of a publisher, assigned by the server.
+pub(crate) struct PublisherInfo {
+    pub session_id: u32,
+    pub resource_id: String,
+    pub app_name: String,
+    pub stream_name: String,
+    /// The local ICE username fragment.
+    pub ufrag: String,
+    /// The local ICE password.
+    pub pwd: String,
+}
+
+/// The receive state of a RTP stream.
+struct Stream {
+    /// The index of the track.
+    track: usize,
+    reorder: ReorderBuffer,
+    depacketizer: Depacketizer,
+    last_pli: Option<Instant>,
+}
+
+/// A publisher whose offer was answered by a [`WhipServer`](crate::WhipServer).
+///
+/// The ICE and DTLS handshake happens while [`Publisher::recv`] is called, so
+/// it should be called right after the publisher was accepted.
+pub struct Publisher {
+    shared: Arc<Shared>,
+    info: PublisherInfo,
+    ctx: scuffle_context::Context,
+    packets: mpsc::Receiver<(SocketAddr, Bytes)>,
+    tracks: Vec<Track>,
+    /// The negotiated payload type and SSRC of every track, the SSRC is
+    /// learned from the first packet if the offer did not announce it.
+    track_sources: Vec<(u8, Option<u32>)>,
+    /// The address selected by the ICE agent of the publisher.
+    remote_addr: Option<SocketAddr>,
+    dtls: DtlsConnection,
+    srtp_in: Option<SrtpContext>,
+    srtp_out: Option<SrtpContext>,
+    streams: HashMap<u32, Stream>,
+    local_ssrc: u32,
+    created: Instant,
+    last_received: Instant,
+    next_remb: Instant,
+    ready: VecDeque<Frame>,
+    closed: bool,
+}
+
+impl std::fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Publisher")
+            .field("session_id", &self.info.session_id)
+            .field("app_name", &self.info.app_name)
+            .field("stream_name", &self.info.stream_name)
+            .field("tracks", &self.tracks)
+            .field("remote_addr", &self.remote_addr)
+            .field("dtls", &self.dtls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Publisher {
+    pub(crate) fn new(
+        shared: Arc<Shared>,
+        info: PublisherInfo,
+        negotiated: Negotiated,
+        dtls: DtlsConnection,
+        packets: mpsc::Receiver<(SocketAddr, Bytes)>,
+        ctx: scuffle_context::Context,
+    ) -> Self {
+        let now = Instant::now();
+
+        let tracks = negotiated
+            .tracks
+            .iter()
+            .zip(1..)
+            .map(|(track, id)| Track {
+                id,
+                mid: track.mid.clone(),
+                codec: track.codec,
+                config: None,
+            })
+            .collect();
+        let track_sources = negotiated
+            .tracks
+            .iter()
+            .map(|track| (track.payload_type, track.ssrc))
+            .collect();
+
+        Self {
+            dtls,
+            shared,
+            info,
+            ctx,
+            packets,
+            tracks,
+            track_sources,
+            remote_addr: None,
+            srtp_in: None,
+            srtp_out: None,
+            streams: HashMap::new(),
+            local_ssrc: rand::random(),
+            created: now,
+            last_received: now,
+            next_remb: now,
+            ready: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    /// Returns the id of the session, unique within the server.
+    pub fn session_id(&self) -> u32 {
+        self.info.session_id
+    }
+
+    /// Returns the app name, the first segment of the request path.
+    pub fn app_name(&self) -> &str {
+        &self.info.app_name
+    }
+
+    /// Returns the stream name, the rest of the request path.
+    pub fn stream_name(&self) -> &str {
+        &self.info.stream_name
+    }
+
+    /// Returns the tracks of the publisher.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Returns the address media is received from, once the ICE agent of the
+    /// publisher selected it.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Receives the next frame.
+    ///
+    /// Returns `None` if the publisher closed the DTLS connection or the
+    /// session was deleted with a `DELETE` request.
+    pub async fn recv(&mut self) -> Result<Option<Frame>, WhipError> {
+        loop {
+            if let Some(frame) = self.ready.pop_front() {
+                return Ok(Some(frame));
+            }
+
+            if self.closed {
+                return Ok(None);
+            }
+
+            let deadline = self.deadline();
+
+            tokio::select! {
+                _ = self.ctx.done() => {
+                    self.close().await;
+                    return Ok(None);
+                }
+                packet = self.packets.recv() => {
+                    let Some((addr, data)) = packet else {
+                        return Err(WhipError::Closed);
+                    };
+
+                    self.handle_datagram(addr, data).await?;
+                }
+                _ = tokio::time::sleep_until(deadline.into()) => {
+                    self.handle_timeout(Instant::now()).await?;
+                }
+            }
+        }
+    }
+
+    /// Closes the DTLS connection.
+    pub async fn close(&mut self) {
+        if let Err(err) = self.dtls.close() {
+            tracing::debug!(err = %err, "failed to close dtls connection");
+        }
+
+        self.flush_dtls().await;
+        self.closed = true;
+    }
+
+    fn deadline(&self) -> Instant {
+        let mut deadline = self.last_received + self.shared.config.peer_idle_timeout;
+
+        if !self.dtls.is_connected() {
+            deadline = deadline.min(self.created + self.shared.config.handshake_timeout);
+        }
+
+        if self.srtp_out.is_some() {
+            deadline = deadline.min(self.next_remb);
+        }
+
+        self.dtls
+            .poll_timeout()
+            .into_iter()
+            .chain(self.streams.values().filter_map(|stream| stream.reorder.deadline()))
+            .fold(deadline, Instant::min)
+    }
+
+    async fn handle_timeout(&mut self, now: Instant) -> Result<(), WhipError> {
+        if !self.dtls.is_connected() && now >= self.created + self.shared.config.handshake_timeout {
+            return Err(WhipError::HandshakeTimeout);
+        }
+
+        if now >= self.last_received + self.shared.config.peer_idle_timeout {
+            return Err(WhipError::PeerIdle);
+        }
+
+        self.dtls.handle_timeout(now)?;
+        self.flush_dtls().await;
+
+        let ssrcs: Vec<_> = self.streams.keys().copied().collect();
+        for ssrc in ssrcs {
+            self.drain(now, ssrc).await;
+        }
+
+        if self.srtp_out.is_some() && now >= self.next_remb {
+            self.next_remb = now + REMB_INTERVAL;
+            let remb = RtcpPacket::ReceiverEstimatedMaximumBitrate {
+                sender_ssrc: self.local_ssrc,
+                bitrate: self.shared.config.max_bitrate,
+                ssrcs: self.streams.keys().copied().collect(),
+            };
+            self.send_rtcp(remb).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_datagram(&mut self, addr: SocketAddr, data: Bytes) -> Result<(), WhipError> {
+        let now = Instant::now();
+        self.last_received = now;
+
+        if StunMessage::is_stun(&data) {
+            // The driver only routes authenticated requests.
+            let request = StunMessage::parse(data)?;
+            if request.message_type == MessageType::BindingRequest {
+                self.handle_binding_request(addr, request).await;
+            }
+        } else if DtlsConnection::is_dtls(&data) {
+            self.dtls.handle_datagram(now, data)?;
+            self.flush_dtls().await;
+
+            if let Some(keys) = self.dtls.srtp_keys().filter(|_| self.srtp_in.is_none()) {
+                self.srtp_in = Some(SrtpContext::new(&keys.remote_key, &keys.remote_salt)?);
+                self.srtp_out = Some(SrtpContext::new(&keys.local_key, &keys.local_salt)?);
+                tracing::debug!(session_id = self.info.session_id, "dtls handshake complete");
+            }
+
+            self.closed |= self.dtls.is_closed();
+        } else if let Some(srtp) = &mut self.srtp_in {
+            if rtp::is_rtcp(&data) {
+                // Sender reports are not needed, the timing is derived from
+                // the arrival time of the first frame.
+                if let Err(err) = srtp.unprotect_rtcp(&data) {
+                    tracing::debug!(err = %err, "dropping rtcp packet");
+                }
+                return Ok(());
+            }
+
+            match srtp.unprotect_rtp(&data).and_then(RtpPacket::parse) {
+                Ok(packet) => self.handle_rtp(now, packet).await,
+                Err(err) => tracing::debug!(err = %err, "dropping rtp packet"),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_binding_request(&mut self, addr: SocketAddr, request: StunMessage) {
+        // We are the controlled agent, the publisher nominates the pair.
+        if self.remote_addr.is_none() || request.has_attribute(AttributeType::UseCandidate) {
+            self.remote_addr = Some(addr);
+        }
+
+        let mut response = StunMessage::new(MessageType::BindingSuccess, request.transaction_id);
+        response.attributes.push(Attribute::XorMappedAddress(addr));
+        let response = response.write(Some(self.info.pwd.as_bytes()));
+
+        self.send(addr, &response).await;
+    }
+
+    async fn handle_rtp(&mut self, now: Instant, packet: RtpPacket) {
+        let ssrc = packet.ssrc;
+
+        if !self.streams.contains_key(&ssrc) {
+            // Retransmissions and simulcast layers use their own SSRC and
+            // payload type, they are not matched here.
+            let Some(track) = self
+                .track_sources
+                .iter()
+                .position(|&(_, source)| source == Some(ssrc))
+                .or_else(|| {
+                    self.track_sources
+                        .iter()
+                        .position(|&(payload_type, source)| source.is_none() && payload_type == packet.payload_type)
+                })
+            else {
+                return;
+            };
+
+            self.track_sources[track].1 = Some(ssrc);
+            self.streams.insert(
+                ssrc,
+                Stream {
+                    track,
+                    reorder: ReorderBuffer::new(self.shared.config.reorder_delay),
+                    depacketizer: Depacketizer::new(self.tracks[track].codec),
+                    last_pli: None,
+                },
+            );
+        }
+
+        if let Some(stream) = self.streams.get_mut(&ssrc) {
+            stream.reorder.push(now, packet);
+        }
+
+        self.drain(now, ssrc).await;
+    }
+
+    /// Depacketizes the packets of a stream released by its reorder buffer.
+    async fn drain(&mut self, now: Instant, ssrc: u32) {
+        let Some(stream) = self.streams.get_mut(&ssrc) else {
+            return;
+        };
+
+        while let Some((packet, lost)) = stream.reorder.pop(now) {
+            if lost {
+                stream.depacketizer.discard();
+            }
+
+            stream.depacketizer.push(&packet);
+        }
+
+        let track = &mut self.tracks[stream.track];
+        while let Some(unit) = stream.depacketizer.pop() {
+            if unit.config.is_some() {
+                track.config.clone_from(&unit.config);
+            }
+
+            self.ready.push_back(Frame {
+                track_id: track.id,
+                codec: track.codec,
+                timestamp: unit.timestamp,
+                received: now,
+                keyframe: unit.keyframe,
+                discontinuity: unit.discontinuity,
+                config: unit.config,
+                data: unit.data,
+            });
+        }
+
+        let request_keyframe = stream.depacketizer.is_waiting_for_keyframe()
+            && stream.last_pli.is_none_or(|last_pli| now >= last_pli + PLI_INTERVAL);
+
+        if request_keyframe {
+            stream.last_pli = Some(now);
+            let pli = RtcpPacket::PictureLossIndication {
+                sender_ssrc: self.local_ssrc,
+                media_ssrc: ssrc,
+            };
+            self.send_rtcp(pli).await;
+        }
+    }
+
+    /// Sends a feedback packet in a compound packet after an empty receiver
+    /// report.
+    async fn send_rtcp(&mut self, packet: RtcpPacket) {
+        let (Some(srtp), Some(addr)) = (&mut self.srtp_out, self.remote_addr) else {
+            return;
+        };
+
+        let receiver_report = RtcpPacket::Other {
+            packet_type: RtcpType::ReceiverReport,
+            count: 0,
+            body: Bytes::copy_from_slice(&self.local_ssrc.to_be_bytes()),
+        };
+
+        let mut compound = Vec::new();
+        let protected = receiver_report
+            .write(&mut compound)
+            .and_then(|()| packet.write(&mut compound))
+            .map_err(WhipError::from)
+            .and_then(|()| srtp.protect_rtcp(&compound));
+
+        match protected {
+            Ok(protected) => self.send(addr, &protected).await,
+            Err(err) => tracing::debug!(err = %err, "failed to protect rtcp packet"),
+        }
+    }
+
+    async fn flush_dtls(&mut self) {
+        while let Some(datagram) = self.dtls.poll_transmit() {
+            // Handshake messages are only sent in response to the publisher,
+            // which is always a validated address.
+            if let Some(addr) = self.remote_addr {
+                self.send(addr, &datagram).await;
+            }
+        }
+    }
+
+    async fn send(&self, addr: SocketAddr, data: &[u8]) {
+        if let Err(err) = self.shared.socket.send_to(data, addr).await {
+            tracing::debug!(err = %err, %addr, "failed to send datagram");
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        self.shared.routes.lock().unwrap().remove(&self.info.ufrag);
+        self.shared.resources.lock().unwrap().remove(&self.info.resource_id);
+    }
+}
@@ -0,0 +1,148 @@
+//! Reordering of received RTP packets.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::rtp::RtpPacket;
+
+/// The maximum number of packets held back while waiting for a missing one.
+const MAX_BUFFERED_PACKETS: usize = 512;
+
+/// Puts the RTP packets of a stream back into sequence number order.
+///
+/// A missing packet is waited for at most `delay` after a later packet
+/// arrived, then it is reported as lost and skipped.
+#[derive(Debug)]
+pub(crate) struct ReorderBuffer {
+    delay: Duration,
+    /// The extended sequence number of the next packet to return.
+    next: Option<u64>,
+    /// The highest extended sequence number received.
+    highest: u64,
+    packets: BTreeMap<u64, (Instant, RtpPacket)>,
+}
+
+impl ReorderBuffer {
+    pub(crate) fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            next: None,
+            highest: 0,
+            packets: BTreeMap::new(),
+        }
+    }
+
+    /// Extends a 16 bit sequence number relative to the highest received one.
+    fn extend(&self, sequence_number: u16) -> Option<u64> {
+        let delta = sequence_number.wrapping_sub(self.highest as u16) as i16;
+        self.highest.checked_add_signed(delta as i64)
+    }
+
+    /// Adds a received packet. Duplicates and packets that arrive after they
+    /// were reported as lost are dropped.
+    pub(crate) fn push(&mut self, now: Instant, packet: RtpPacket) {
+        let index = match self.next {
+            // Start far enough from zero so earlier packets can be extended.
+            None => 1 << 16 | packet.sequence_number as u64,
+            Some(_) => match self.extend(packet.sequence_number) {
+                Some(index) => index,
+                None => return,
+            },
+        };
+
+        let next = *self.next.get_or_insert(index);
+        if index < next {
+            return;
+        }
+
+        self.highest = self.highest.max(index);
+        self.packets.entry(index).or_insert((now, packet));
+    }
+
+    /// Returns the next packet in order and whether packets before it were
+    /// lost.
+    pub(crate) fn pop(&mut self, now: Instant) -> Option<(RtpPacket, bool)> {
+        let next = self.next?;
+        let full = self.packets.len() >= MAX_BUFFERED_PACKETS;
+        let entry = self.packets.first_entry()?;
+        let index = *entry.key();
+
+        let lost = index != next;
+        if lost && entry.get().0 + self.delay > now && !full {
+            return None;
+        }
+
+        let (_, packet) = entry.remove();
+        self.next = Some(index + 1);
+        Some((packet, lost))
+    }
+
+    /// Returns the time at which the first packet after a gap is released.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        let (&index, (received, _)) = self.packets.first_key_value()?;
+        (Some(index) != self.next).then_some(*received + self.delay)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    fn packet(sequence_number: u16) -> RtpPacket {
+        RtpPacket {
+            marker: false,
+            payload_type: 96,
+            sequence_number,
+            timestamp: 0,
+            ssrc: 1,
+            payload: Bytes::new(),
+        }
+    }
+
+    fn drain(buffer: &mut ReorderBuffer, now: Instant) -> Vec<(u16, bool)> {
+        std::iter::from_fn(|| buffer.pop(now))
+            .map(|(packet, lost)| (packet.sequence_number, lost))
+            .collect()
+    }
+
+    #[test]
+    fn reorder_across_wrap() {
+        let now = Instant::now();
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(50));
+
+        buffer.push(now, packet(65534));
+        buffer.push(now, packet(0));
+        assert_eq!(drain(&mut buffer, now), vec![(65534, false)]);
+        assert_eq!(buffer.deadline(), Some(now + Duration::from_millis(50)));
+
+        buffer.push(now, packet(65535));
+        buffer.push(now, packet(65535));
+        assert_eq!(drain(&mut buffer, now), vec![(65535, false), (0, false)]);
+        assert_eq!(buffer.deadline(), None);
+
+        // Late packet
+        buffer.push(now, packet(65533));
+        assert!(buffer.pop(now).is_none());
+    }
+
+    #[test]
+    fn loss() {
+        let now = Instant::now();
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(50));
+
+        buffer.push(now, packet(10));
+        buffer.push(now, packet(12));
+        assert_eq!(drain(&mut buffer, now), vec![(10, false)]);
+
+        let later = now + Duration::from_millis(50);
+        assert_eq!(drain(&mut buffer, later), vec![(12, true)]);
+
+        // The lost packet arrives too late.
+        buffer.push(later, packet(11));
+        buffer.push(later, packet(13));
+        assert_eq!(drain(&mut buffer, later), vec![(13, false)]);
+    }
+}
@@ -0,0 +1,346 @@
+//! RTP and RTCP packets.
+
+use std::io::{self, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, Bytes};
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::error::WhipError;
+
+/// The size of the fixed RTP header.
+pub const HEADER_SIZE: usize = 12;
+
+nutype_enum::nutype_enum! {
+    /// The packet type of a RTCP packet.
+    pub enum RtcpType(u8) {
+        /// Sender report.
+        SenderReport = 200,
+        /// Receiver report.
+        ReceiverReport = 201,
+        /// Source description.
+        SourceDescription = 202,
+        /// Goodbye.
+        Goodbye = 203,
+        /// Application defined.
+        App = 204,
+        /// Transport layer feedback.
+        TransportFeedback = 205,
+        /// Payload specific feedback.
+        PayloadFeedback = 206,
+    }
+}
+
+/// Returns true if the datagram is a RTCP packet multiplexed on the RTP port.
+///
+/// RFC 5761 reserves the payload types 64-95 for this, which are the RTCP
+/// packet types 192-223 with the marker bit set.
+pub fn is_rtcp(data: &[u8]) -> bool {
+    data.len() >= 8 && (192..=223).contains(&data[1])
+}
+
+/// A RTP packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpPacket {
+    /// The marker bit, set on the last packet of a video frame.
+    pub marker: bool,
+    /// The payload type.
+    pub payload_type: u8,
+    /// The sequence number.
+    pub sequence_number: u16,
+    /// The media timestamp.
+    pub timestamp: u32,
+    /// The synchronization source.
+    pub ssrc: u32,
+    /// The payload, without padding.
+    pub payload: Bytes,
+}
+
+impl RtpPacket {
+    /// Parses a RTP packet.
+    ///
+    /// Contributing sources and header extensions are skipped.
+    pub fn parse(data: Bytes) -> Result<Self, WhipError> {
+        if data.len() < HEADER_SIZE {
+            return Err(WhipError::InvalidRtp("packet too short"));
+        }
+
+        let mut reader = io::Cursor::new(data);
+        let byte0 = reader.read_u8()?;
+        if byte0 >> 6 != 2 {
+            return Err(WhipError::InvalidRtp("unsupported version"));
+        }
+
+        let padding = byte0 & 0x20 != 0;
+        let extension = byte0 & 0x10 != 0;
+        let csrc_count = (byte0 & 0x0F) as usize;
+
+        let byte1 = reader.read_u8()?;
+        let sequence_number = reader.read_u16::<BigEndian>()?;
+        let timestamp = reader.read_u32::<BigEndian>()?;
+        let ssrc = reader.read_u32::<BigEndian>()?;
+
+        reader.extract_bytes(csrc_count * 4)?;
+        if extension {
+            reader.read_u16::<BigEndian>()?; // profile
+            let length = reader.read_u16::<BigEndian>()? as usize;
+            reader.extract_bytes(length * 4)?;
+        }
+
+        let mut payload = reader.extract_remaining();
+        if padding {
+            let padding = payload.last().copied().unwrap_or_default() as usize;
+            if padding == 0 || padding > payload.len() {
+                return Err(WhipError::InvalidRtp("invalid padding"));
+            }
+
+            payload.truncate(payload.len() - padding);
+        }
+
+        Ok(Self {
+            marker: byte1 & 0x80 != 0,
+            payload_type: byte1 & 0x7F,
+            sequence_number,
+            timestamp,
+            ssrc,
+            payload,
+        })
+    }
+
+    /// Writes the packet without header extensions.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_u8(0x80)?;
+        writer.write_u8((self.marker as u8) << 7 | self.payload_type)?;
+        writer.write_u16::<BigEndian>(self.sequence_number)?;
+        writer.write_u32::<BigEndian>(self.timestamp)?;
+        writer.write_u32::<BigEndian>(self.ssrc)?;
+        writer.write_all(&self.payload)
+    }
+}
+
+/// A RTCP packet.
+///
+/// Only the packets sent by a receiver are fully represented.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpPacket {
+    /// Picture loss indication, requesting a key frame.
+    PictureLossIndication {
+        /// The source of the feedback.
+        sender_ssrc: u32,
+        /// The source of the media stream.
+        media_ssrc: u32,
+    },
+    /// Receiver estimated maximum bitrate.
+    ReceiverEstimatedMaximumBitrate {
+        /// The source of the feedback.
+        sender_ssrc: u32,
+        /// The estimated bitrate in bits per second.
+        bitrate: u64,
+        /// The media streams the estimate applies to.
+        ssrcs: Vec<u32>,
+    },
+    /// Any other packet.
+    Other {
+        /// The packet type.
+        packet_type: RtcpType,
+        /// The count or format field of the header.
+        count: u8,
+        /// The packet after the 4 byte header.
+        body: Bytes,
+    },
+}
+
+/// The unique identifier of a REMB packet.
+const REMB_IDENTIFIER: &[u8; 4] = b"REMB";
+
+impl RtcpPacket {
+    /// Parses a compound RTCP packet.
+    pub fn parse_compound(data: Bytes) -> Result<Vec<Self>, WhipError> {
+        let mut reader = io::Cursor::new(data);
+        let mut packets = Vec::new();
+
+        while reader.has_remaining() {
+            let byte0 = reader.read_u8()?;
+            if byte0 >> 6 != 2 {
+                return Err(WhipError::InvalidRtp("unsupported rtcp version"));
+            }
+
+            let count = byte0 & 0x1F;
+            let packet_type = RtcpType::from(reader.read_u8()?);
+            let length = reader.read_u16::<BigEndian>()? as usize * 4;
+            let mut body = reader.extract_bytes(length)?;
+            if byte0 & 0x20 != 0 {
+                let padding = body.last().copied().unwrap_or_default() as usize;
+                body.truncate(body.len().saturating_sub(padding));
+            }
+
+            packets.push(Self::parse(packet_type, count, body)?);
+        }
+
+        Ok(packets)
+    }
+
+    fn parse(packet_type: RtcpType, count: u8, body: Bytes) -> Result<Self, WhipError> {
+        let mut reader = io::Cursor::new(body.clone());
+
+        match (packet_type, count) {
+            (RtcpType::PayloadFeedback, 1) => Ok(Self::PictureLossIndication {
+                sender_ssrc: reader.read_u32::<BigEndian>()?,
+                media_ssrc: reader.read_u32::<BigEndian>()?,
+            }),
+            (RtcpType::PayloadFeedback, 15) if body.get(8..12) == Some(REMB_IDENTIFIER) => {
+                let sender_ssrc = reader.read_u32::<BigEndian>()?;
+                reader.read_u32::<BigEndian>()?; // media ssrc, always 0
+                reader.read_u32::<BigEndian>()?; // identifier
+                let num_ssrc = reader.read_u8()?;
+                let exponent = reader.read_u8()?;
+                let mantissa = reader.read_u16::<BigEndian>()? as u64 | ((exponent as u64 & 0x03) << 16);
+                let ssrcs = (0..num_ssrc)
+                    .map(|_| reader.read_u32::<BigEndian>())
+                    .collect::<io::Result<_>>()?;
+
+                Ok(Self::ReceiverEstimatedMaximumBitrate {
+                    sender_ssrc,
+                    bitrate: mantissa << (exponent >> 2),
+                    ssrcs,
+                })
+            }
+            _ => Ok(Self::Other {
+                packet_type,
+                count,
+                body,
+            }),
+        }
+    }
+
+    /// Writes the packet.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::PictureLossIndication { sender_ssrc, media_ssrc } => {
+                write_header(writer, 1, RtcpType::PayloadFeedback, 8)?;
+                writer.write_u32::<BigEndian>(*sender_ssrc)?;
+                writer.write_u32::<BigEndian>(*media_ssrc)
+            }
+            Self::ReceiverEstimatedMaximumBitrate {
+                sender_ssrc,
+                bitrate,
+                ssrcs,
+            } => {
+                // The bitrate is sent as an 18 bit mantissa with a 6 bit exponent.
+                let exponent = (64 - bitrate.leading_zeros()).saturating_sub(18);
+                let mantissa = bitrate >> exponent;
+
+                write_header(writer, 15, RtcpType::PayloadFeedback, 16 + ssrcs.len() * 4)?;
+                writer.write_u32::<BigEndian>(*sender_ssrc)?;
+                writer.write_u32::<BigEndian>(0)?;
+                writer.write_all(REMB_IDENTIFIER)?;
+                writer.write_u8(ssrcs.len() as u8)?;
+                writer.write_u8((exponent as u8) << 2 | (mantissa >> 16) as u8)?;
+                writer.write_u16::<BigEndian>(mantissa as u16)?;
+                for ssrc in ssrcs {
+                    writer.write_u32::<BigEndian>(*ssrc)?;
+                }
+
+                Ok(())
+            }
+            Self::Other {
+                packet_type,
+                count,
+                body,
+            } => {
+                write_header(writer, *count, *packet_type, body.len())?;
+                writer.write_all(body)
+            }
+        }
+    }
+}
+
+fn write_header(writer: &mut impl Write, count: u8, packet_type: RtcpType, body_len: usize) -> io::Result<()> {
+    writer.write_u8(0x80 | count)?;
+    writer.write_u8(packet_type.0)?;
+    writer.write_u16::<BigEndian>((body_len / 4) as u16)
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtp_round_trip() {
+        let packet = RtpPacket {
+            marker: true,
+            payload_type: 96,
+            sequence_number: 65535,
+            timestamp: 0xDEAD_BEEF,
+            ssrc: 0x1234_5678,
+            payload: Bytes::from_static(&[1, 2, 3]),
+        };
+
+        let mut buf = Vec::new();
+        packet.write(&mut buf).unwrap();
+        assert!(!is_rtcp(&buf));
+        assert_eq!(RtpPacket::parse(Bytes::from(buf)).unwrap(), packet);
+    }
+
+    #[test]
+    fn rtp_extension_and_padding() {
+        let data = Bytes::from_static(&[
+            0xB1, 0x60, 0x00, 0x01, // V=2, P, X, CC=1, PT=96
+            0x00, 0x00, 0x00, 0x02, // timestamp
+            0x00, 0x00, 0x00, 0x03, // ssrc
+            0x00, 0x00, 0x00, 0x04, // csrc
+            0xBE, 0xDE, 0x00, 0x01, // one-byte header extension
+            0x10, 0xFF, 0x00, 0x00, // extension element
+            0xAA, 0xBB, 0x00, 0x02, // payload and padding
+        ]);
+
+        let packet = RtpPacket::parse(data).unwrap();
+        assert!(!packet.marker);
+        assert_eq!(packet.payload_type, 96);
+        assert_eq!(packet.sequence_number, 1);
+        assert_eq!(packet.timestamp, 2);
+        assert_eq!(packet.ssrc, 3);
+        assert_eq!(packet.payload.as_ref(), &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn rtcp_round_trip() {
+        let packets = vec![
+            RtcpPacket::PictureLossIndication {
+                sender_ssrc: 1,
+                media_ssrc: 2,
+            },
+            RtcpPacket::ReceiverEstimatedMaximumBitrate {
+                sender_ssrc: 1,
+                bitrate: 10_000_000,
+                ssrcs: vec![2, 3],
+            },
+            RtcpPacket::Other {
+                packet_type: RtcpType::Goodbye,
+                count: 1,
+                body: Bytes::from_static(&[0, 0, 0, 2]),
+            },
+        ];
+
+        let mut buf = Vec::new();
+        for packet in &packets {
+            packet.write(&mut buf).unwrap();
+        }
+
+        assert!(is_rtcp(&buf));
+
+        let parsed = RtcpPacket::parse_compound(Bytes::from(buf)).unwrap();
+        // 10 Mbit/s fits into the 18 bit mantissa with an exponent of 6.
+        assert_eq!(
+            parsed[1],
+            RtcpPacket::ReceiverEstimatedMaximumBitrate {
+                sender_ssrc: 1,
+                bitrate: 10_000_000 >> 6 << 6,
+                ssrcs: vec![2, 3],
+            }
+        );
+        assert_eq!(parsed[0], packets[0]);
+        assert_eq!(parsed[2], packets[2]);
+    }
+}
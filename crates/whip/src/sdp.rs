@@ -0,0 +1,604 @@
+//! SDP (Session Description Protocol) offers and answers.
+//!
+//! Only the parts of SDP used by WebRTC are represented: the origin, session
+//! and media level attributes and the media descriptions. Bandwidth, timing
+//! and other lines are ignored when parsing.
+
+use std::fmt;
+use std::net::SocketAddr;
+
+use crate::codec::Codec;
+use crate::dtls::Fingerprint;
+use crate::error::WhipError;
+
+/// The priority of host candidates, type preference 126, local preference
+/// 65535 and component 1.
+const HOST_CANDIDATE_PRIORITY: u32 = (126 << 24) | (65535 << 8) | 255;
+
+/// A SDP attribute (`a=` line).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    /// The name of the attribute.
+    pub name: String,
+    /// The value of the attribute, `None` for property attributes.
+    pub value: Option<String>,
+}
+
+impl Attribute {
+    /// Creates a new attribute.
+    pub fn new(name: impl Into<String>, value: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+
+    fn parse(line: &str) -> Self {
+        match line.split_once(':') {
+            Some((name, value)) => Self::new(name, Some(value.to_owned())),
+            None => Self::new(line, None),
+        }
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "a={}:{value}\r\n", self.name),
+            None => write!(f, "a={}\r\n", self.name),
+        }
+    }
+}
+
+/// Finds the value of the first attribute named `name`.
+fn find<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.value.as_deref().unwrap_or_default())
+}
+
+/// A media description (`m=` section).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaDescription {
+    /// The media type, `audio` or `video`.
+    pub media: String,
+    /// The port, `0` if the media is rejected.
+    pub port: u16,
+    /// The transport protocol, `UDP/TLS/RTP/SAVPF` for WebRTC.
+    pub protocol: String,
+    /// The payload types.
+    pub formats: Vec<String>,
+    /// The connection data (`c=` line).
+    pub connection: Option<String>,
+    /// The media level attributes.
+    pub attributes: Vec<Attribute>,
+}
+
+impl MediaDescription {
+    /// Returns the value of the first attribute named `name`.
+    ///
+    /// Property attributes have an empty value.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        find(&self.attributes, name)
+    }
+
+    /// Returns the values of all attributes named `name`.
+    pub fn attributes<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.attributes
+            .iter()
+            .filter(move |attribute| attribute.name == name)
+            .filter_map(|attribute| attribute.value.as_deref())
+    }
+
+    /// Returns the format parameters (`fmtp`) of a payload type.
+    pub fn fmtp(&self, payload_type: u8) -> Option<&str> {
+        self.attributes("fmtp").find_map(|value| {
+            let (pt, params) = value.split_once(' ')?;
+            (pt.parse() == Ok(payload_type)).then_some(params)
+        })
+    }
+}
+
+impl fmt::Display for MediaDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m={} {} {}", self.media, self.port, self.protocol)?;
+        for format in &self.formats {
+            write!(f, " {format}")?;
+        }
+        f.write_str("\r\n")?;
+
+        if let Some(connection) = &self.connection {
+            write!(f, "c={connection}\r\n")?;
+        }
+
+        for attribute in &self.attributes {
+            attribute.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A session description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionDescription {
+    /// The origin (`o=` line).
+    pub origin: String,
+    /// The session name (`s=` line).
+    pub session_name: String,
+    /// The session level connection data (`c=` line).
+    pub connection: Option<String>,
+    /// The session level attributes.
+    pub attributes: Vec<Attribute>,
+    /// The media descriptions.
+    pub media: Vec<MediaDescription>,
+}
+
+impl SessionDescription {
+    /// Parses a session description.
+    pub fn parse(sdp: &str) -> Result<Self, WhipError> {
+        let mut lines = sdp.lines().map(str::trim_end).filter(|line| !line.is_empty());
+
+        if lines.next() != Some("v=0") {
+            return Err(WhipError::InvalidSdp("missing version".into()));
+        }
+
+        let mut description = Self {
+            origin: String::new(),
+            session_name: String::new(),
+            connection: None,
+            attributes: Vec::new(),
+            media: Vec::new(),
+        };
+
+        for line in lines {
+            let Some((kind, value)) = line.split_once('=') else {
+                return Err(WhipError::InvalidSdp(format!("invalid line: {line}")));
+            };
+
+            match (kind, description.media.last_mut()) {
+                ("m", _) => {
+                    let mut parts = value.split(' ');
+                    let (Some(media), Some(port), Some(protocol)) = (parts.next(), parts.next(), parts.next()) else {
+                        return Err(WhipError::InvalidSdp(format!("invalid media: {value}")));
+                    };
+
+                    // The port may be followed by the number of ports.
+                    let port = port
+                        .split('/')
+                        .next()
+                        .and_then(|port| port.parse().ok())
+                        .ok_or_else(|| WhipError::InvalidSdp(format!("invalid port: {port}")))?;
+
+                    description.media.push(MediaDescription {
+                        media: media.to_owned(),
+                        port,
+                        protocol: protocol.to_owned(),
+                        formats: parts.map(ToOwned::to_owned).collect(),
+                        connection: None,
+                        attributes: Vec::new(),
+                    });
+                }
+                ("a", Some(media)) => media.attributes.push(Attribute::parse(value)),
+                ("a", None) => description.attributes.push(Attribute::parse(value)),
+                ("c", Some(media)) => media.connection = Some(value.to_owned()),
+                ("c", None) => description.connection = Some(value.to_owned()),
+                ("o", None) => description.origin = value.to_owned(),
+                ("s", None) => description.session_name = value.to_owned(),
+                _ => {}
+            }
+        }
+
+        Ok(description)
+    }
+
+    /// Returns the value of the first session level attribute named `name`.
+    ///
+    /// Property attributes have an empty value.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        find(&self.attributes, name)
+    }
+
+    /// Returns the value of an attribute of a media description, falling back
+    /// to the session level attribute.
+    pub fn media_attribute<'a>(&'a self, media: &'a MediaDescription, name: &str) -> Option<&'a str> {
+        media.attribute(name).or_else(|| self.attribute(name))
+    }
+}
+
+impl fmt::Display for SessionDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v=0\r\no={}\r\ns={}\r\n", self.origin, self.session_name)?;
+        if let Some(connection) = &self.connection {
+            write!(f, "c={connection}\r\n")?;
+        }
+        f.write_str("t=0 0\r\n")?;
+
+        for attribute in &self.attributes {
+            attribute.fmt(f)?;
+        }
+
+        for media in &self.media {
+            media.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A track accepted from an offer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NegotiatedTrack {
+    /// The media id of the track.
+    pub mid: String,
+    /// The codec chosen for the track.
+    pub codec: Codec,
+    /// The payload type of the codec.
+    pub payload_type: u8,
+    /// The SSRC announced in the offer, if any.
+    pub ssrc: Option<u32>,
+}
+
+/// The result of negotiating an offer.
+#[derive(Debug, Clone)]
+pub(crate) struct Negotiated {
+    pub remote_ufrag: String,
+    pub remote_fingerprint: Fingerprint,
+    pub tracks: Vec<NegotiatedTrack>,
+}
+
+/// The local ICE and DTLS parameters put into the answer.
+#[derive(Debug, Clone)]
+pub(crate) struct LocalParameters<'a> {
+    pub ufrag: &'a str,
+    pub pwd: &'a str,
+    pub fingerprint: Fingerprint,
+    pub candidates: &'a [SocketAddr],
+}
+
+/// Chooses the codec of a media description, in the order of `codecs`.
+///
+/// For H.264 non-interleaved mode is preferred over single NAL unit mode,
+/// interleaved mode is not supported.
+fn choose_codec(media: &MediaDescription, codecs: &[Codec]) -> Option<(Codec, u8)> {
+    let offered: Vec<(Codec, u8, u8)> = media
+        .attributes("rtpmap")
+        .filter_map(|value| {
+            let (payload_type, encoding) = value.split_once(' ')?;
+            let payload_type = payload_type.parse().ok()?;
+            let mut encoding = encoding.split('/');
+            let name = encoding.next()?;
+            let clock_rate = encoding.next()?.parse().ok()?;
+            let codec = Codec::from_rtpmap(name, clock_rate)?;
+
+            let packetization_mode = media
+                .fmtp(payload_type)
+                .into_iter()
+                .flat_map(|params| params.split(';'))
+                .find_map(|param| param.trim().strip_prefix("packetization-mode="))
+                .unwrap_or("0");
+
+            let rank = match (codec, packetization_mode) {
+                (Codec::H264, "1") => 0,
+                (Codec::H264, "0") => 1,
+                (Codec::H264, _) => return None,
+                _ => 0,
+            };
+
+            // Only offered payload types can be used.
+            media
+                .formats
+                .iter()
+                .any(|format| format.parse() == Ok(payload_type))
+                .then_some((codec, payload_type, rank))
+        })
+        .collect();
+
+    codecs.iter().find_map(|&codec| {
+        offered
+            .iter()
+            .filter(|(offered, _, _)| *offered == codec)
+            .min_by_key(|(_, _, rank)| *rank)
+            .map(|&(codec, payload_type, _)| (codec, payload_type))
+    })
+}
+
+/// Returns the primary SSRC of a media description.
+///
+/// Retransmission and FEC streams of an `ssrc-group` are skipped.
+fn primary_ssrc(media: &MediaDescription) -> Option<u32> {
+    if let Some(group) = media.attributes("ssrc-group").next() {
+        return group.split(' ').nth(1).and_then(|ssrc| ssrc.parse().ok());
+    }
+
+    media
+        .attributes("ssrc")
+        .next()
+        .and_then(|value| value.split(' ').next())
+        .and_then(|ssrc| ssrc.parse().ok())
+}
+
+/// Negotiates an offer, choosing one codec for every media description that
+/// sends a supported codec.
+pub(crate) fn negotiate(offer: &SessionDescription, codecs: &[Codec]) -> Result<Negotiated, WhipError> {
+    let first_media = offer.media.first().ok_or(WhipError::UnsupportedOffer("offer has no media"))?;
+
+    let remote_ufrag = offer
+        .media_attribute(first_media, "ice-ufrag")
+        .ok_or(WhipError::UnsupportedOffer("missing ice-ufrag"))?;
+    // An ICE-lite agent never sends connectivity checks, so the password of
+    // the publisher is not needed.
+    if offer.media_attribute(first_media, "ice-pwd").is_none() {
+        return Err(WhipError::UnsupportedOffer("missing ice-pwd"));
+    }
+    let remote_fingerprint = offer
+        .media_attribute(first_media, "fingerprint")
+        .and_then(Fingerprint::parse_sdp)
+        .ok_or(WhipError::UnsupportedOffer("missing sha-256 fingerprint"))?;
+
+    // We are always the DTLS server.
+    if !matches!(offer.media_attribute(first_media, "setup"), Some("actpass" | "active")) {
+        return Err(WhipError::UnsupportedOffer("setup must be actpass or active"));
+    }
+
+    let mut tracks = Vec::new();
+    for media in &offer.media {
+        let sends = media.attribute("sendonly").is_some() || media.attribute("sendrecv").is_some();
+        if media.port == 0 || !sends || !media.protocol.ends_with("SAVPF") {
+            continue;
+        }
+
+        let Some(mid) = media.attribute("mid") else {
+            continue;
+        };
+
+        let Some((codec, payload_type)) = choose_codec(media, codecs) else {
+            continue;
+        };
+
+        if codec.is_video() != (media.media == "video") {
+            continue;
+        }
+
+        tracks.push(NegotiatedTrack {
+            mid: mid.to_owned(),
+            codec,
+            payload_type,
+            ssrc: primary_ssrc(media),
+        });
+    }
+
+    if tracks.is_empty() {
+        return Err(WhipError::UnsupportedOffer("no supported media"));
+    }
+
+    Ok(Negotiated {
+        remote_ufrag: remote_ufrag.to_owned(),
+        remote_fingerprint,
+        tracks,
+    })
+}
+
+/// Builds the answer to an offer.
+///
+/// Every media description of the offer is answered, the ones without a
+/// negotiated track are rejected with port `0`.
+pub(crate) fn answer(offer: &SessionDescription, negotiated: &Negotiated, local: LocalParameters) -> SessionDescription {
+    let bundle: Vec<&str> = negotiated.tracks.iter().map(|track| track.mid.as_str()).collect();
+
+    let attributes = vec![
+        Attribute::new("group", Some(format!("BUNDLE {}", bundle.join(" ")))),
+        Attribute::new("ice-lite", None),
+        Attribute::new("ice-ufrag", Some(local.ufrag.to_owned())),
+        Attribute::new("ice-pwd", Some(local.pwd.to_owned())),
+        Attribute::new("fingerprint", Some(local.fingerprint.to_string())),
+        Attribute::new("setup", Some("passive".into())),
+    ];
+
+    let media = offer
+        .media
+        .iter()
+        .map(|media| {
+            let mid = media.attribute("mid");
+            let Some(track) = negotiated.tracks.iter().find(|track| Some(track.mid.as_str()) == mid) else {
+                return MediaDescription {
+                    media: media.media.clone(),
+                    port: 0,
+                    protocol: media.protocol.clone(),
+                    formats: media.formats.clone(),
+                    connection: Some("IN IP4 0.0.0.0".into()),
+                    attributes: mid
+                        .map(|mid| vec![Attribute::new("mid", Some(mid.to_owned()))])
+                        .unwrap_or_default(),
+                };
+            };
+
+            let payload_type = track.payload_type;
+            let mut attributes = vec![
+                Attribute::new("mid", Some(track.mid.clone())),
+                Attribute::new("recvonly", None),
+                Attribute::new("rtcp-mux", None),
+                Attribute::new(
+                    "rtpmap",
+                    Some(match track.codec {
+                        Codec::Opus => format!("{payload_type} opus/48000/2"),
+                        codec => format!("{payload_type} {}/{}", codec.encoding_name(), codec.clock_rate()),
+                    }),
+                ),
+            ];
+
+            if let Some(fmtp) = media.fmtp(payload_type) {
+                attributes.push(Attribute::new("fmtp", Some(format!("{payload_type} {fmtp}"))));
+            }
+
+            if track.codec.is_video() {
+                attributes.push(Attribute::new("rtcp-fb", Some(format!("{payload_type} nack pli"))));
+                attributes.push(Attribute::new("rtcp-fb", Some(format!("{payload_type} goog-remb"))));
+            }
+
+            for (i, candidate) in local.candidates.iter().enumerate() {
+                attributes.push(Attribute::new(
+                    "candidate",
+                    Some(format!(
+                        "{} 1 udp {} {} {} typ host",
+                        i + 1,
+                        HOST_CANDIDATE_PRIORITY - i as u32,
+                        candidate.ip(),
+                        candidate.port()
+                    )),
+                ));
+            }
+            attributes.push(Attribute::new("end-of-candidates", None));
+
+            MediaDescription {
+                media: media.media.clone(),
+                port: 9,
+                protocol: media.protocol.clone(),
+                formats: vec![payload_type.to_string()],
+                connection: Some("IN IP4 0.0.0.0".into()),
+                attributes,
+            }
+        })
+        .collect();
+
+    SessionDescription {
+        origin: format!("- {} 2 IN IP4 127.0.0.1", rand::random::<u32>()),
+        session_name: "-".into(),
+        connection: None,
+        attributes,
+        media,
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An offer shaped like the one sent by OBS.
+    pub(crate) fn offer(fingerprint: Fingerprint) -> String {
+        format!(
+            "v=0\r\n\
+             o=rtc 3108012154 0 IN IP4 127.0.0.1\r\n\
+             s=-\r\n\
+             t=0 0\r\n\
+             a=group:BUNDLE 0 1\r\n\
+             a=msid-semantic:WMS *\r\n\
+             a=setup:actpass\r\n\
+             a=ice-ufrag:Ag5q\r\n\
+             a=ice-pwd:3zJ7eA6mWgjH9oPFLS2ckq\r\n\
+             a=ice-options:ice2,trickle\r\n\
+             a=fingerprint:{fingerprint}\r\n\
+             m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+             c=IN IP4 0.0.0.0\r\n\
+             a=mid:0\r\n\
+             a=sendonly\r\n\
+             a=ssrc:1111 cname:obs\r\n\
+             a=rtcp-mux\r\n\
+             a=rtpmap:111 opus/48000/2\r\n\
+             a=fmtp:111 minptime=10;maxaveragebitrate=96000;stereo=1;sprop-stereo=1;useinbandfec=1\r\n\
+             m=video 9 UDP/TLS/RTP/SAVPF 96 97 98\r\n\
+             c=IN IP4 0.0.0.0\r\n\
+             a=mid:1\r\n\
+             a=sendonly\r\n\
+             a=ssrc-group:FID 2222 3333\r\n\
+             a=ssrc:2222 cname:obs\r\n\
+             a=ssrc:3333 cname:obs\r\n\
+             a=rtcp-mux\r\n\
+             a=rtpmap:96 H264/90000\r\n\
+             a=fmtp:96 profile-level-id=42e01f;packetization-mode=0\r\n\
+             a=rtpmap:97 H264/90000\r\n\
+             a=fmtp:97 profile-level-id=42e01f;packetization-mode=1;level-asymmetry-allowed=1\r\n\
+             a=rtpmap:98 rtx/90000\r\n\
+             a=fmtp:98 apt=97\r\n\
+             m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+             a=mid:2\r\n"
+        )
+    }
+
+    #[test]
+    fn parse_and_write() {
+        let fingerprint = Fingerprint([0xAB; 32]);
+        let sdp = SessionDescription::parse(&offer(fingerprint)).unwrap();
+
+        assert_eq!(sdp.origin, "rtc 3108012154 0 IN IP4 127.0.0.1");
+        assert_eq!(sdp.attribute("ice-ufrag"), Some("Ag5q"));
+        assert_eq!(sdp.attribute("setup"), Some("actpass"));
+        assert_eq!(sdp.media.len(), 3);
+        assert_eq!(sdp.media[1].formats, vec!["96", "97", "98"]);
+        assert_eq!(sdp.media[1].fmtp(98), Some("apt=97"));
+        assert_eq!(sdp.media[0].attribute("sendonly"), Some(""));
+        assert_eq!(sdp.media_attribute(&sdp.media[0], "ice-pwd"), Some("3zJ7eA6mWgjH9oPFLS2ckq"));
+
+        let written = sdp.to_string();
+        assert_eq!(SessionDescription::parse(&written).unwrap(), sdp);
+        assert!(written.ends_with("a=mid:2\r\n"));
+
+        assert!(SessionDescription::parse("o=missing version").is_err());
+        assert!(SessionDescription::parse("v=0\r\nm=video x RTP 96").is_err());
+    }
+
+    #[test]
+    fn negotiate_offer() {
+        let fingerprint = Fingerprint([0xAB; 32]);
+        let sdp = SessionDescription::parse(&offer(fingerprint)).unwrap();
+
+        let negotiated = negotiate(&sdp, &Codec::ALL).unwrap();
+        assert_eq!(negotiated.remote_ufrag, "Ag5q");
+        assert_eq!(negotiated.remote_fingerprint, fingerprint);
+        assert_eq!(
+            negotiated.tracks,
+            vec![
+                NegotiatedTrack {
+                    mid: "0".into(),
+                    codec: Codec::Opus,
+                    payload_type: 111,
+                    ssrc: Some(1111),
+                },
+                NegotiatedTrack {
+                    mid: "1".into(),
+                    codec: Codec::H264,
+                    payload_type: 97,
+                    ssrc: Some(2222),
+                },
+            ]
+        );
+
+        // Only audio is accepted.
+        let audio_only = negotiate(&sdp, &[Codec::Opus]).unwrap();
+        assert_eq!(audio_only.tracks.len(), 1);
+
+        let candidates = ["192.0.2.1:5000".parse().unwrap()];
+        let answer = answer(
+            &sdp,
+            &audio_only,
+            LocalParameters {
+                ufrag: "local",
+                pwd: "localpassword",
+                fingerprint: Fingerprint([0xCD; 32]),
+                candidates: &candidates,
+            },
+        );
+
+        let answer = SessionDescription::parse(&answer.to_string()).unwrap();
+        assert_eq!(answer.attribute("group"), Some("BUNDLE 0"));
+        assert_eq!(answer.attribute("ice-lite"), Some(""));
+        assert_eq!(answer.attribute("setup"), Some("passive"));
+        assert_eq!(answer.media.len(), 3);
+        assert_eq!(answer.media[0].formats, vec!["111"]);
+        assert_eq!(answer.media[0].attribute("recvonly"), Some(""));
+        assert_eq!(
+            answer.media[0].attribute("candidate"),
+            Some("1 1 udp 2130706431 192.0.2.1 5000 typ host")
+        );
+        assert_eq!(answer.media[1].port, 0);
+        assert_eq!(answer.media[2].port, 0);
+        assert_eq!(answer.media[2].attribute("mid"), Some("2"));
+
+        assert!(matches!(
+            negotiate(&sdp, &[Codec::Vp8]),
+            Err(WhipError::UnsupportedOffer("no supported media"))
+        ));
+    }
+}
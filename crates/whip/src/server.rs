@@ -0,0 +1,445 @@
+//! The WHIP server.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use bytes::Bytes;
+use http::{Method, StatusCode, header};
+use http_body_util::{BodyExt, Full, Limited};
+use scuffle_context::ContextFutExt;
+use tokio::net::{ToSocketAddrs, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::config::WhipConfig;
+use crate::dtls::{DtlsCertificate, DtlsConnection};
+use crate::error::WhipError;
+use crate::publisher::{Publisher, PublisherInfo};
+use crate::sdp::{self, LocalParameters, SessionDescription};
+use crate::stun::StunMessage;
+
+/// The number of publishers waiting for [`WhipServer::accept`].
+const ACCEPT_BACKLOG: usize = 64;
+
+/// The number of datagrams queued for a publisher.
+const PACKET_QUEUE_SIZE: usize = 1024;
+
+/// The maximum size of a received datagram.
+const MAX_DATAGRAM_SIZE: usize = 1500;
+
+/// The SDP content type.
+const APPLICATION_SDP: &str = "application/sdp";
+
+/// Decides whether a publish request is accepted.
+///
+/// This is called before the offer is answered, with the request head, so
+/// the `Authorization` header and the path can be checked.
+pub trait PublishFilter: Send + Sync + 'static {
+    /// Returns the status code the request is rejected with.
+    fn filter(&self, request: &http::request::Parts) -> Result<(), StatusCode>;
+}
+
+impl<F> PublishFilter for F
+where
+    F: Fn(&http::request::Parts) -> Result<(), StatusCode> + Send + Sync + 'static,
+{
+    fn filter(&self, request: &http::request::Parts) -> Result<(), StatusCode> {
+        self(request)
+    }
+}
+
+/// Where the datagrams of a publisher are routed to.
+struct Route {
+    /// The ICE username fragment of the publisher.
+    remote_ufrag: String,
+    /// The local ICE password, the key of the STUN message integrity.
+    pwd: String,
+    packets: mpsc::Sender<(SocketAddr, Bytes)>,
+}
+
+/// Routes datagrams on the shared socket to publishers.
+#[derive(Default)]
+pub(crate) struct Routes {
+    /// The routes by local ICE username fragment.
+    routes: HashMap<String, Route>,
+    /// The local ICE username fragment by remote address, learned from
+    /// authenticated STUN requests.
+    addrs: HashMap<SocketAddr, String>,
+}
+
+impl Routes {
+    pub(crate) fn remove(&mut self, ufrag: &str) {
+        self.routes.remove(ufrag);
+        self.addrs.retain(|_, route| route != ufrag);
+    }
+}
+
+/// The state shared by the server, its services, the socket driver and the
+/// publishers.
+pub(crate) struct Shared {
+    pub(crate) config: WhipConfig,
+    pub(crate) certificate: Arc<DtlsCertificate>,
+    pub(crate) socket: Arc<UdpSocket>,
+    candidates: Vec<SocketAddr>,
+    filter: Box<dyn PublishFilter>,
+    accepted: mpsc::Sender<Publisher>,
+    pub(crate) routes: Mutex<Routes>,
+    /// The cancellation handlers of the publishers by resource id.
+    pub(crate) resources: Mutex<HashMap<String, scuffle_context::Handler>>,
+    next_session_id: AtomicU32,
+    /// Stops the socket driver once everything else is dropped.
+    _driver: scuffle_context::Handler,
+}
+
+/// A WHIP server.
+///
+/// The server answers publish requests through its [`WhipService`], which
+/// has to be served by an HTTP server, for example
+/// [`scuffle_http::HttpServer`]. The media of all publishers is received on a
+/// single UDP socket.
+///
+/// The socket keeps routing packets to accepted publishers after the server
+/// is dropped, until all of them are closed.
+pub struct WhipServer {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<Publisher>,
+}
+
+impl std::fmt::Debug for WhipServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WhipServer")
+            .field("local_addr", &self.local_addr)
+            .field("fingerprint", &self.shared.certificate.fingerprint())
+            .finish_non_exhaustive()
+    }
+}
+
+impl WhipServer {
+    /// Binds the UDP socket of a server accepting all publishers.
+    pub async fn bind(addr: impl ToSocketAddrs, config: WhipConfig) -> Result<Self, WhipError> {
+        Self::bind_with_filter(addr, config, |_: &http::request::Parts| Ok(())).await
+    }
+
+    /// Binds the UDP socket of a server that uses `filter` to decide which
+    /// publishers are accepted, for example based on a bearer token.
+    pub async fn bind_with_filter(
+        addr: impl ToSocketAddrs,
+        config: WhipConfig,
+        filter: impl PublishFilter,
+    ) -> Result<Self, WhipError> {
+        let socket = Arc::new(UdpSocket::bind(addr).await?);
+        let local_addr = socket.local_addr()?;
+
+        let candidates: Vec<SocketAddr> = if config.candidates.is_empty() {
+            if local_addr.ip().is_unspecified() {
+                return Err(WhipError::NoCandidates);
+            }
+            vec![local_addr]
+        } else {
+            config
+                .candidates
+                .iter()
+                .map(|ip| SocketAddr::new(*ip, local_addr.port()))
+                .collect()
+        };
+
+        let (accepted_tx, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+        let (ctx, handler) = scuffle_context::Context::new();
+
+        let shared = Arc::new(Shared {
+            config,
+            certificate: Arc::new(DtlsCertificate::generate()?),
+            socket: socket.clone(),
+            candidates,
+            filter: Box::new(filter),
+            accepted: accepted_tx,
+            routes: Mutex::new(Routes::default()),
+            resources: Mutex::new(HashMap::new()),
+            next_session_id: AtomicU32::new(1),
+            _driver: handler,
+        });
+
+        tokio::spawn(drive(socket, Arc::downgrade(&shared), ctx));
+
+        Ok(Self {
+            shared,
+            local_addr,
+            accepted,
+        })
+    }
+
+    /// Returns the local address of the UDP socket.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the HTTP service answering publish requests.
+    pub fn service(&self) -> WhipService {
+        WhipService {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Waits for the next publisher.
+    ///
+    /// The publisher is returned as soon as its offer was answered, the ICE
+    /// and DTLS handshake happens while it is received from.
+    pub async fn accept(&mut self) -> Result<Publisher, WhipError> {
+        self.accepted.recv().await.ok_or(WhipError::Closed)
+    }
+}
+
+/// Receives the datagrams of the shared socket and routes them to the
+/// publishers.
+async fn drive(socket: Arc<UdpSocket>, shared: Weak<Shared>, ctx: scuffle_context::Context) {
+    let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+
+    while let Some(result) = socket.recv_from(&mut buf).with_context(&ctx).await {
+        let (len, addr) = match result {
+            Ok(received) => received,
+            Err(err) => {
+                // ICMP errors of previous sends are reported here on some
+                // platforms.
+                tracing::debug!(err = %err, "failed to receive datagram");
+                continue;
+            }
+        };
+
+        let Some(shared) = shared.upgrade() else {
+            break;
+        };
+
+        let data = Bytes::copy_from_slice(&buf[..len]);
+        let mut routes = shared.routes.lock().unwrap();
+
+        let ufrag = if StunMessage::is_stun(&data) {
+            let Ok(message) = StunMessage::parse(data.clone()) else {
+                continue;
+            };
+
+            // The username of a request is `<local ufrag>:<remote ufrag>`.
+            let Some((ufrag, remote_ufrag)) = message.username().and_then(|username| username.split_once(':')) else {
+                continue;
+            };
+
+            let Some(route) = routes.routes.get(ufrag).filter(|route| route.remote_ufrag == remote_ufrag) else {
+                continue;
+            };
+
+            if !message.verify_integrity(&data, route.pwd.as_bytes()) {
+                tracing::debug!(%addr, "stun message integrity check failed");
+                continue;
+            }
+
+            let ufrag = ufrag.to_owned();
+            routes.addrs.insert(addr, ufrag.clone());
+            ufrag
+        } else {
+            match routes.addrs.get(&addr) {
+                Some(ufrag) => ufrag.clone(),
+                None => continue,
+            }
+        };
+
+        let Some(route) = routes.routes.get(&ufrag) else {
+            continue;
+        };
+
+        match route.packets.try_send((addr, data)) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => tracing::debug!(%addr, "publisher queue full"),
+            Err(mpsc::error::TrySendError::Closed(_)) => routes.remove(&ufrag),
+        }
+    }
+}
+
+/// Generates a random string of `len` alphanumeric characters.
+fn random_string(len: usize) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    (0..len)
+        .map(|_| ALPHABET[rand::random_range(0..ALPHABET.len())] as char)
+        .collect()
+}
+
+/// The HTTP service of a [`WhipServer`].
+///
+/// - `POST` with a SDP offer creates a publisher and answers with the SDP
+///   answer and the URL of the session in the `Location` header. The app and
+///   stream name are taken from the path, `/live/key` publishes the stream
+///   `key` to the app `live`.
+/// - `DELETE` on the session URL ends the session.
+/// - `OPTIONS` answers CORS preflight requests.
+///
+/// Trickle ICE and ICE restarts (`PATCH`) are not supported, the answer
+/// contains all candidates.
+#[derive(Clone)]
+pub struct WhipService {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for WhipService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WhipService").finish_non_exhaustive()
+    }
+}
+
+impl WhipService {
+    /// Handles a WHIP request.
+    pub async fn handle<B>(&self, request: http::Request<B>) -> http::Response<Full<Bytes>>
+    where
+        B: http_body::Body,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let (parts, body) = request.into_parts();
+
+        let response = match parts.method {
+            Method::POST => self.publish(parts, body).await,
+            Method::DELETE => self.delete(&parts),
+            Method::OPTIONS => response(StatusCode::NO_CONTENT)
+                .header(header::ACCESS_CONTROL_ALLOW_METHODS, "OPTIONS, POST, DELETE")
+                .header(header::ACCESS_CONTROL_ALLOW_HEADERS, "Authorization, Content-Type")
+                .header("Accept-Post", APPLICATION_SDP)
+                .body(Full::default()),
+            _ => response(StatusCode::METHOD_NOT_ALLOWED)
+                .header(header::ALLOW, "OPTIONS, POST, DELETE")
+                .body(Full::default()),
+        };
+
+        response.unwrap_or_else(|err| {
+            tracing::error!(err = %err, "failed to build response");
+            let mut response = http::Response::new(Full::default());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        })
+    }
+
+    async fn publish<B>(&self, parts: http::request::Parts, body: B) -> http::Result<http::Response<Full<Bytes>>>
+    where
+        B: http_body::Body,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let is_sdp = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with(APPLICATION_SDP));
+        if !is_sdp {
+            return error(StatusCode::UNSUPPORTED_MEDIA_TYPE, "expected application/sdp");
+        }
+
+        if let Err(status) = self.shared.filter.filter(&parts) {
+            return error(status, "rejected");
+        }
+
+        let offer = match Limited::new(body, self.shared.config.max_offer_size).collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(_) => return error(StatusCode::PAYLOAD_TOO_LARGE, "invalid offer body"),
+        };
+
+        let Ok(offer) = std::str::from_utf8(&offer) else {
+            return error(StatusCode::BAD_REQUEST, "offer is not utf-8");
+        };
+
+        let (offer, negotiated) = match SessionDescription::parse(offer)
+            .and_then(|offer| sdp::negotiate(&offer, &self.shared.config.codecs).map(|negotiated| (offer, negotiated)))
+        {
+            Ok(result) => result,
+            Err(err) => return error(StatusCode::BAD_REQUEST, &err.to_string()),
+        };
+
+        let path = parts.uri.path().trim_end_matches('/');
+        let resource = path.trim_start_matches('/');
+        let (app_name, stream_name) = resource.split_once('/').unwrap_or((resource, ""));
+
+        let ufrag = random_string(8);
+        let pwd = random_string(24);
+        let resource_id = random_string(32);
+
+        let answer = sdp::answer(
+            &offer,
+            &negotiated,
+            LocalParameters {
+                ufrag: &ufrag,
+                pwd: &pwd,
+                fingerprint: self.shared.certificate.fingerprint(),
+                candidates: &self.shared.candidates,
+            },
+        );
+
+        let dtls = match DtlsConnection::server(self.shared.certificate.clone(), negotiated.remote_fingerprint) {
+            Ok(dtls) => dtls,
+            Err(err) => return error(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+        };
+
+        let (packets_tx, packets) = mpsc::channel(PACKET_QUEUE_SIZE);
+        let (ctx, handler) = scuffle_context::Context::new();
+
+        self.shared.routes.lock().unwrap().routes.insert(
+            ufrag.clone(),
+            Route {
+                remote_ufrag: negotiated.remote_ufrag.clone(),
+                pwd: pwd.clone(),
+                packets: packets_tx,
+            },
+        );
+        self.shared.resources.lock().unwrap().insert(resource_id.clone(), handler);
+
+        let info = PublisherInfo {
+            session_id: self.shared.next_session_id.fetch_add(1, Ordering::Relaxed),
+            resource_id: resource_id.clone(),
+            app_name: app_name.to_owned(),
+            stream_name: stream_name.to_owned(),
+            ufrag,
+            pwd,
+        };
+        let publisher = Publisher::new(self.shared.clone(), info, negotiated, dtls, packets, ctx);
+
+        // Dropping the publisher removes its route and resource again.
+        if self.shared.accepted.try_send(publisher).is_err() {
+            return error(StatusCode::SERVICE_UNAVAILABLE, "server is busy");
+        }
+
+        response(StatusCode::CREATED)
+            .header(header::CONTENT_TYPE, APPLICATION_SDP)
+            .header(header::LOCATION, format!("{path}/{resource_id}"))
+            .body(Full::new(Bytes::from(answer.to_string())))
+    }
+
+    fn delete(&self, parts: &http::request::Parts) -> http::Result<http::Response<Full<Bytes>>> {
+        let resource_id = parts.uri.path().rsplit('/').next().unwrap_or_default();
+
+        match self.shared.resources.lock().unwrap().remove(resource_id) {
+            Some(handler) => {
+                handler.cancel();
+                response(StatusCode::OK).body(Full::default())
+            }
+            None => error(StatusCode::NOT_FOUND, "unknown session"),
+        }
+    }
+}
+
+impl scuffle_http::service::HttpService for WhipService {
+    type Error = Infallible;
+    type ResBody = Full<Bytes>;
+
+    async fn call(&mut self, req: scuffle_http::IncomingRequest) -> Result<http::Response<Self::ResBody>, Self::Error> {
+        Ok(self.handle(req).await)
+    }
+}
+
+/// Starts a response with the CORS headers.
+fn response(status: StatusCode) -> http::response::Builder {
+    http::Response::builder()
+        .status(status)
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::ACCESS_CONTROL_EXPOSE_HEADERS, "Location")
+}
+
+fn error(status: StatusCode, message: &str) -> http::Result<http::Response<Full<Bytes>>> {
+    response(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Full::new(Bytes::copy_from_slice(message.as_bytes())))
+}
@@ -0,0 +1,3 @@
+//! WHIP sessions.
+
+pub mod server;
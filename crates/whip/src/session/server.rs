@@ -0,0 +1,167 @@
+//! WHIP server session.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use scuffle_context::ContextFutExt;
+
+use crate::error::WhipError;
+use crate::publisher::{Frame, Publisher, Track};
+
+mod error;
+mod handler;
+
+pub use error::ServerSessionError;
+pub use handler::{SessionData, SessionHandler};
+
+/// The timing of a track.
+struct TrackClock {
+    /// The milliseconds between the first frame of the session and the first
+    /// frame of the track.
+    offset: u64,
+    /// The extended RTP timestamp of the first frame.
+    first: i64,
+    /// The extended RTP timestamp of the last frame.
+    last: i64,
+}
+
+impl TrackClock {
+    fn new(offset: u64, timestamp: u32) -> Self {
+        Self {
+            offset,
+            first: timestamp as i64,
+            last: timestamp as i64,
+        }
+    }
+
+    /// Returns the timestamp of a frame in milliseconds.
+    fn timestamp(&mut self, timestamp: u32, clock_rate: u32) -> u32 {
+        let delta = timestamp.wrapping_sub(self.last as u32) as i32;
+        self.last += delta as i64;
+
+        let elapsed = (self.last - self.first) * 1000 / clock_rate as i64;
+        // Wraps like the RTMP timestamp.
+        self.offset.wrapping_add_signed(elapsed) as u32
+    }
+}
+
+/// A WHIP server session that receives the media of a [`Publisher`].
+///
+/// This provides the same high-level API as the RTMP server session: the
+/// access units are passed to a [`SessionHandler`] with millisecond
+/// timestamps.
+///
+/// RTP timestamps of different tracks are unrelated, the tracks are aligned
+/// by the arrival time of their first frame.
+pub struct ServerSession<H> {
+    /// The context of the session.
+    /// The session is closed if this context gets cancelled.
+    ctx: Option<scuffle_context::Context>,
+    publisher: Publisher,
+    handler: H,
+    /// The arrival time of the first frame.
+    start: Option<Instant>,
+    clocks: HashMap<u32, TrackClock>,
+    /// The tracks last announced to the handler.
+    tracks: HashMap<u32, Track>,
+}
+
+impl<H> ServerSession<H> {
+    /// Create a new session from an accepted publisher.
+    pub fn new(publisher: Publisher, handler: H) -> Self {
+        Self {
+            ctx: None,
+            publisher,
+            handler,
+            start: None,
+            clocks: HashMap::new(),
+            tracks: HashMap::new(),
+        }
+    }
+
+    /// Set the context of the session.
+    pub fn with_context(mut self, ctx: scuffle_context::Context) -> Self {
+        self.ctx = Some(ctx);
+        self
+    }
+}
+
+impl<H: SessionHandler> ServerSession<H> {
+    /// Run the session to completion.
+    ///
+    /// The result of the return value will be true if the publisher closed
+    /// the connection or deleted the session and false if the connection
+    /// timed out or the context was cancelled.
+    pub async fn run(mut self) -> Result<bool, WhipError> {
+        let ctx = self.ctx.clone().unwrap_or_else(scuffle_context::Context::global);
+
+        let session_id = self.publisher.session_id();
+        self.handler
+            .on_publish(session_id, self.publisher.app_name(), self.publisher.stream_name())
+            .await?;
+
+        let result = loop {
+            match self.publisher.recv().with_context(&ctx).await {
+                Some(Ok(Some(frame))) => self.handle_frame(session_id, frame).await?,
+                Some(Ok(None)) => break Ok(true),
+                Some(Err(err @ (WhipError::PeerIdle | WhipError::HandshakeTimeout))) => {
+                    tracing::debug!(err = %err, "publisher timed out");
+                    break Ok(false);
+                }
+                Some(Err(err)) => break Err(err),
+                None => break Ok(false), // Context was cancelled
+            }
+        };
+
+        self.handler.on_unpublish(session_id).await?;
+        self.publisher.close().await;
+
+        result
+    }
+
+    async fn handle_frame(&mut self, session_id: u32, frame: Frame) -> Result<(), WhipError> {
+        let track = self.publisher.tracks().iter().find(|track| track.id == frame.track_id);
+        if let Some(track) = track.filter(|track| self.tracks.get(&track.id) != Some(track)) {
+            self.tracks.insert(track.id, track.clone());
+            self.handler.on_data(session_id, SessionData::Track(track.clone())).await?;
+        }
+
+        let start = *self.start.get_or_insert(frame.received);
+        let timestamp = self
+            .clocks
+            .entry(frame.track_id)
+            .or_insert_with(|| {
+                let offset = frame.received.saturating_duration_since(start).as_millis() as u64;
+                TrackClock::new(offset, frame.timestamp)
+            })
+            .timestamp(frame.timestamp, frame.codec.clock_rate());
+
+        let data = if frame.codec.is_video() {
+            SessionData::Video { timestamp, frame }
+        } else {
+            SessionData::Audio { timestamp, frame }
+        };
+
+        self.handler.on_data(session_id, data).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::TrackClock;
+
+    #[test]
+    fn track_clock() {
+        let start = 0u32.wrapping_sub(45_000);
+        let mut clock = TrackClock::new(20, start);
+        assert_eq!(clock.timestamp(start, 90000), 20);
+        // Across the RTP timestamp wrap.
+        assert_eq!(clock.timestamp(0, 90000), 520);
+        assert_eq!(clock.timestamp(45_000, 90000), 1020);
+        // Timestamps can go back with reordered frames.
+        assert_eq!(clock.timestamp(22_500, 90000), 770);
+    }
+}
@@ -0,0 +1,9 @@
+//! Error type for server sessions.
+
+/// Errors that can occur during a server session.
+#[derive(Debug, thiserror::Error)]
+pub enum ServerSessionError {
+    /// The handler rejected the published stream.
+    #[error("publish rejected")]
+    PublishRejected,
+}
@@ -0,0 +1,52 @@
+//! Defines types for handling session events.
+
+use super::error::ServerSessionError;
+use crate::publisher::{Frame, Track};
+
+/// Data received from a session.
+#[derive(Debug, Clone)]
+pub enum SessionData {
+    /// A track was announced, or its decoder configuration changed.
+    ///
+    /// This is always sent before the first frame of the track and again
+    /// whenever the decoder configuration found in the track changes.
+    Track(Track),
+    /// Video data.
+    Video {
+        /// Timestamp of the data in milliseconds.
+        timestamp: u32,
+        /// Data.
+        frame: Frame,
+    },
+    /// Audio data.
+    Audio {
+        /// Timestamp of the data in milliseconds.
+        timestamp: u32,
+        /// Data.
+        frame: Frame,
+    },
+}
+
+/// Handler for session events.
+pub trait SessionHandler {
+    /// Called when a stream is published.
+    ///
+    /// The app name and stream name are taken from the request path, see
+    /// [`Publisher::app_name`](crate::Publisher::app_name).
+    fn on_publish(
+        &mut self,
+        stream_id: u32,
+        app_name: &str,
+        stream_name: &str,
+    ) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send;
+
+    /// Called when a stream is unpublished.
+    fn on_unpublish(&mut self, stream_id: u32) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send;
+
+    /// Called when data is received.
+    fn on_data(
+        &mut self,
+        stream_id: u32,
+        data: SessionData,
+    ) -> impl std::future::Future<Output = Result<(), ServerSessionError>> + Send;
+}
@@ -0,0 +1,436 @@
+//! SRTP and SRTCP with the `AES_CM_128_HMAC_SHA1_80` protection profile.
+//!
+//! The master keys are exported from the DTLS handshake, see
+//! [`DtlsConnection::srtp_keys`](crate::dtls::DtlsConnection::srtp_keys).
+
+use std::collections::HashMap;
+
+use aws_lc_rs::cipher::{AES_128, EncryptingKey, EncryptionContext, UnboundCipherKey};
+use aws_lc_rs::iv::FixedLength;
+use aws_lc_rs::{constant_time, hmac};
+use bytes::Bytes;
+
+use crate::error::WhipError;
+
+/// The DTLS-SRTP protection profile identifier of `SRTP_AES128_CM_HMAC_SHA1_80`.
+pub const SRTP_AES128_CM_HMAC_SHA1_80: u16 = 0x0001;
+
+/// The size of the master key.
+pub const MASTER_KEY_LEN: usize = 16;
+
+/// The size of the master salt.
+pub const MASTER_SALT_LEN: usize = 14;
+
+/// The size of the authentication key.
+const AUTH_KEY_LEN: usize = 20;
+
+/// The size of the truncated HMAC-SHA1 authentication tag.
+const AUTH_TAG_LEN: usize = 10;
+
+/// The size of the SRTCP index with the encryption flag.
+const SRTCP_INDEX_LEN: usize = 4;
+
+/// The number of packets the replay window covers.
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+/// Key derivation labels, RFC 3711 - 4.3.1
+mod label {
+    pub(super) const RTP_ENCRYPTION: u8 = 0x00;
+    pub(super) const RTP_AUTHENTICATION: u8 = 0x01;
+    pub(super) const RTP_SALT: u8 = 0x02;
+    pub(super) const RTCP_ENCRYPTION: u8 = 0x03;
+    pub(super) const RTCP_AUTHENTICATION: u8 = 0x04;
+    pub(super) const RTCP_SALT: u8 = 0x05;
+}
+
+/// The session keys derived from a master key.
+struct SessionKeys {
+    cipher: EncryptingKey,
+    salt: [u8; MASTER_SALT_LEN],
+    auth: hmac::Key,
+}
+
+impl SessionKeys {
+    fn derive(master_key: &[u8], master_salt: &[u8], labels: [u8; 3]) -> Result<Self, WhipError> {
+        let [encryption, authentication, salt_label] = labels;
+
+        let key = derive_key(master_key, master_salt, encryption, MASTER_KEY_LEN)?;
+        let auth = derive_key(master_key, master_salt, authentication, AUTH_KEY_LEN)?;
+        let mut salt = [0; MASTER_SALT_LEN];
+        salt.copy_from_slice(&derive_key(master_key, master_salt, salt_label, MASTER_SALT_LEN)?);
+
+        Ok(Self {
+            cipher: ctr_key(&key)?,
+            salt,
+            auth: hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &auth),
+        })
+    }
+
+    /// XORs `data` with the AES-CM keystream of the packet.
+    fn apply_keystream(&self, ssrc: u32, index: u64, data: &mut [u8]) -> Result<(), WhipError> {
+        let mut iv = [0; 16];
+        iv[..MASTER_SALT_LEN].copy_from_slice(&self.salt);
+        for (i, byte) in ssrc.to_be_bytes().into_iter().enumerate() {
+            iv[4 + i] ^= byte;
+        }
+        for (i, byte) in index.to_be_bytes()[2..].iter().enumerate() {
+            iv[8 + i] ^= byte;
+        }
+
+        self.cipher
+            .less_safe_encrypt(data, EncryptionContext::Iv128(FixedLength::from(iv)))
+            .map_err(|_| WhipError::Srtp("encryption failed"))?;
+
+        Ok(())
+    }
+
+    fn tag(&self, data: &[u8], roc: Option<u32>) -> [u8; AUTH_TAG_LEN] {
+        let mut context = hmac::Context::with_key(&self.auth);
+        context.update(data);
+        if let Some(roc) = roc {
+            context.update(&roc.to_be_bytes());
+        }
+
+        let mut tag = [0; AUTH_TAG_LEN];
+        tag.copy_from_slice(&context.sign().as_ref()[..AUTH_TAG_LEN]);
+        tag
+    }
+}
+
+fn ctr_key(key: &[u8]) -> Result<EncryptingKey, WhipError> {
+    UnboundCipherKey::new(&AES_128, key)
+        .and_then(EncryptingKey::ctr)
+        .map_err(|_| WhipError::Srtp("invalid key"))
+}
+
+/// The AES-CM key derivation function with a key derivation rate of 0.
+fn derive_key(master_key: &[u8], master_salt: &[u8], label: u8, len: usize) -> Result<Vec<u8>, WhipError> {
+    if master_key.len() != MASTER_KEY_LEN || master_salt.len() != MASTER_SALT_LEN {
+        return Err(WhipError::Srtp("invalid master key length"));
+    }
+
+    let mut iv = [0; 16];
+    iv[..MASTER_SALT_LEN].copy_from_slice(master_salt);
+    iv[7] ^= label;
+
+    let mut key = vec![0; len];
+    ctr_key(master_key)?
+        .less_safe_encrypt(&mut key, EncryptionContext::Iv128(FixedLength::from(iv)))
+        .map_err(|_| WhipError::Srtp("key derivation failed"))?;
+
+    Ok(key)
+}
+
+/// The rollover counter and replay window of a RTP stream.
+#[derive(Default)]
+struct StreamState {
+    roc: u32,
+    highest_seq: u16,
+    highest_index: u64,
+    replay_window: u64,
+}
+
+impl StreamState {
+    fn new(seq: u16) -> Self {
+        Self {
+            highest_seq: seq,
+            highest_index: seq as u64,
+            ..Default::default()
+        }
+    }
+
+    /// Estimates the rollover counter of a packet, RFC 3711 - 3.3.1
+    fn estimate_roc(&self, seq: u16) -> u32 {
+        if self.highest_seq < 0x8000 {
+            if seq > self.highest_seq && seq - self.highest_seq > 0x8000 {
+                return self.roc.wrapping_sub(1);
+            }
+        } else if seq < self.highest_seq - 0x8000 {
+            return self.roc.wrapping_add(1);
+        }
+
+        self.roc
+    }
+
+    fn check_replay(&self, index: u64) -> Result<(), WhipError> {
+        if index > self.highest_index {
+            return Ok(());
+        }
+
+        let age = self.highest_index - index;
+        if age >= REPLAY_WINDOW_SIZE || self.replay_window & (1 << age) != 0 {
+            return Err(WhipError::Srtp("replayed packet"));
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, roc: u32, seq: u16) {
+        let index = (roc as u64) << 16 | seq as u64;
+
+        if index > self.highest_index {
+            let shift = index - self.highest_index;
+            self.replay_window = if shift >= REPLAY_WINDOW_SIZE {
+                0
+            } else {
+                self.replay_window << shift
+            };
+            self.replay_window |= 1;
+            self.highest_index = index;
+            self.roc = roc;
+            self.highest_seq = seq;
+        } else {
+            self.replay_window |= 1 << (self.highest_index - index);
+        }
+    }
+}
+
+/// A SRTP context for one direction of a session.
+pub struct SrtpContext {
+    rtp: SessionKeys,
+    rtcp: SessionKeys,
+    streams: HashMap<u32, StreamState>,
+    srtcp_index: u32,
+}
+
+impl std::fmt::Debug for SrtpContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SrtpContext")
+            .field("streams", &self.streams.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SrtpContext {
+    /// Creates a context from the master key and salt.
+    pub fn new(master_key: &[u8], master_salt: &[u8]) -> Result<Self, WhipError> {
+        Ok(Self {
+            rtp: SessionKeys::derive(
+                master_key,
+                master_salt,
+                [label::RTP_ENCRYPTION, label::RTP_AUTHENTICATION, label::RTP_SALT],
+            )?,
+            rtcp: SessionKeys::derive(
+                master_key,
+                master_salt,
+                [label::RTCP_ENCRYPTION, label::RTCP_AUTHENTICATION, label::RTCP_SALT],
+            )?,
+            streams: HashMap::new(),
+            srtcp_index: 0,
+        })
+    }
+
+    /// Encrypts and authenticates a RTP packet.
+    pub fn protect_rtp(&mut self, packet: &[u8]) -> Result<Vec<u8>, WhipError> {
+        let (ssrc, seq, header_len) = rtp_header(packet)?;
+
+        let stream = self.streams.entry(ssrc).or_insert_with(|| StreamState::new(seq));
+        let roc = stream.estimate_roc(seq);
+        stream.update(roc, seq);
+
+        let mut buf = Vec::with_capacity(packet.len() + AUTH_TAG_LEN);
+        buf.extend_from_slice(packet);
+        self.rtp
+            .apply_keystream(ssrc, (roc as u64) << 16 | seq as u64, &mut buf[header_len..])?;
+
+        let tag = self.rtp.tag(&buf, Some(roc));
+        buf.extend_from_slice(&tag);
+
+        Ok(buf)
+    }
+
+    /// Authenticates and decrypts a SRTP packet.
+    pub fn unprotect_rtp(&mut self, packet: &[u8]) -> Result<Bytes, WhipError> {
+        let (ssrc, seq, header_len) = rtp_header(packet)?;
+        if packet.len() < header_len + AUTH_TAG_LEN {
+            return Err(WhipError::Srtp("packet too short"));
+        }
+
+        let stream = self.streams.entry(ssrc).or_insert_with(|| StreamState::new(seq));
+        let roc = stream.estimate_roc(seq);
+        let index = (roc as u64) << 16 | seq as u64;
+        stream.check_replay(index)?;
+
+        let (data, tag) = packet.split_at(packet.len() - AUTH_TAG_LEN);
+        constant_time::verify_slices_are_equal(&self.rtp.tag(data, Some(roc)), tag)
+            .map_err(|_| WhipError::Srtp("authentication failed"))?;
+
+        stream.update(roc, seq);
+
+        let mut buf = data.to_vec();
+        self.rtp.apply_keystream(ssrc, index, &mut buf[header_len..])?;
+
+        Ok(Bytes::from(buf))
+    }
+
+    /// Encrypts and authenticates a compound RTCP packet.
+    pub fn protect_rtcp(&mut self, packet: &[u8]) -> Result<Vec<u8>, WhipError> {
+        let ssrc = rtcp_ssrc(packet)?;
+        let index = self.srtcp_index;
+        self.srtcp_index = (self.srtcp_index + 1) & 0x7FFF_FFFF;
+
+        let mut buf = Vec::with_capacity(packet.len() + SRTCP_INDEX_LEN + AUTH_TAG_LEN);
+        buf.extend_from_slice(packet);
+        self.rtcp.apply_keystream(ssrc, index as u64, &mut buf[8..])?;
+        buf.extend_from_slice(&(0x8000_0000 | index).to_be_bytes());
+
+        let tag = self.rtcp.tag(&buf, None);
+        buf.extend_from_slice(&tag);
+
+        Ok(buf)
+    }
+
+    /// Authenticates and decrypts a SRTCP packet.
+    pub fn unprotect_rtcp(&mut self, packet: &[u8]) -> Result<Bytes, WhipError> {
+        if packet.len() < 8 + SRTCP_INDEX_LEN + AUTH_TAG_LEN {
+            return Err(WhipError::Srtp("packet too short"));
+        }
+
+        let ssrc = rtcp_ssrc(packet)?;
+        let (data, tag) = packet.split_at(packet.len() - AUTH_TAG_LEN);
+        constant_time::verify_slices_are_equal(&self.rtcp.tag(data, None), tag)
+            .map_err(|_| WhipError::Srtp("authentication failed"))?;
+
+        let (data, index) = data.split_at(data.len() - SRTCP_INDEX_LEN);
+        let index = u32::from_be_bytes([index[0], index[1], index[2], index[3]]);
+
+        let mut buf = data.to_vec();
+        if index & 0x8000_0000 != 0 {
+            self.rtcp.apply_keystream(ssrc, (index & 0x7FFF_FFFF) as u64, &mut buf[8..])?;
+        }
+
+        Ok(Bytes::from(buf))
+    }
+}
+
+/// Returns the SSRC, sequence number and header length of a RTP packet.
+fn rtp_header(packet: &[u8]) -> Result<(u32, u16, usize), WhipError> {
+    if packet.len() < crate::rtp::HEADER_SIZE {
+        return Err(WhipError::Srtp("packet too short"));
+    }
+
+    let seq = u16::from_be_bytes([packet[2], packet[3]]);
+    let ssrc = u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]);
+
+    let mut header_len = crate::rtp::HEADER_SIZE + (packet[0] & 0x0F) as usize * 4;
+    if packet[0] & 0x10 != 0 {
+        let Some(&[_, _, high, low]) = packet.get(header_len..header_len + 4) else {
+            return Err(WhipError::Srtp("packet too short"));
+        };
+        header_len += 4 + u16::from_be_bytes([high, low]) as usize * 4;
+    }
+
+    if header_len > packet.len() {
+        return Err(WhipError::Srtp("packet too short"));
+    }
+
+    Ok((ssrc, seq, header_len))
+}
+
+fn rtcp_ssrc(packet: &[u8]) -> Result<u32, WhipError> {
+    match packet.get(4..8) {
+        Some(&[a, b, c, d]) => Ok(u32::from_be_bytes([a, b, c, d])),
+        _ => Err(WhipError::Srtp("packet too short")),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn key_derivation_rfc3711() {
+        // RFC 3711 - B.3
+        let master_key = hex("E1F97A0D3E018BE0D64FA32C06DE4139");
+        let master_salt = hex("0EC675AD498AFEEBB6960B3AABE6");
+
+        assert_eq!(
+            derive_key(&master_key, &master_salt, label::RTP_ENCRYPTION, 16).unwrap(),
+            hex("C61E7A93744F39EE10734AFE3FF7A087")
+        );
+        assert_eq!(
+            derive_key(&master_key, &master_salt, label::RTP_SALT, 14).unwrap(),
+            hex("30CBBC08863D8C85D49DB34A9AE1")
+        );
+        assert_eq!(
+            derive_key(&master_key, &master_salt, label::RTP_AUTHENTICATION, 20).unwrap(),
+            hex("CEBE321F6FF7716B6FD4AB49AF256A156D38BAA4")
+        );
+    }
+
+    fn rtp_packet(seq: u16) -> Vec<u8> {
+        let mut packet = vec![0x80, 96];
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&1234u32.to_be_bytes());
+        packet.extend_from_slice(&0xCAFEu32.to_be_bytes());
+        packet.extend_from_slice(b"hello world");
+        packet
+    }
+
+    #[test]
+    fn rtp_round_trip() {
+        let key = [1; MASTER_KEY_LEN];
+        let salt = [2; MASTER_SALT_LEN];
+        let mut sender = SrtpContext::new(&key, &salt).unwrap();
+        let mut receiver = SrtpContext::new(&key, &salt).unwrap();
+
+        // Crosses the rollover of the sequence number.
+        for seq in (65530..=65535).chain(0..5) {
+            let packet = rtp_packet(seq);
+            let protected = sender.protect_rtp(&packet).unwrap();
+            assert_ne!(&protected[12..packet.len()], &packet[12..]);
+
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap().as_ref(), packet.as_slice());
+            assert!(matches!(
+                receiver.unprotect_rtp(&protected),
+                Err(WhipError::Srtp("replayed packet"))
+            ));
+        }
+
+        assert_eq!(receiver.streams[&0xCAFE].roc, 1);
+
+        let mut protected = sender.protect_rtp(&rtp_packet(5)).unwrap();
+        protected[14] ^= 1;
+        assert!(matches!(
+            receiver.unprotect_rtp(&protected),
+            Err(WhipError::Srtp("authentication failed"))
+        ));
+    }
+
+    #[test]
+    fn rtp_reordered() {
+        let key = [3; MASTER_KEY_LEN];
+        let salt = [4; MASTER_SALT_LEN];
+        let mut sender = SrtpContext::new(&key, &salt).unwrap();
+        let mut receiver = SrtpContext::new(&key, &salt).unwrap();
+
+        let packets: Vec<_> = (0..4).map(|seq| sender.protect_rtp(&rtp_packet(seq)).unwrap()).collect();
+        for i in [0, 2, 1, 3] {
+            assert_eq!(receiver.unprotect_rtp(&packets[i]).unwrap().as_ref(), rtp_packet(i as u16));
+        }
+    }
+
+    #[test]
+    fn rtcp_round_trip() {
+        let key = [5; MASTER_KEY_LEN];
+        let salt = [6; MASTER_SALT_LEN];
+        let mut sender = SrtpContext::new(&key, &salt).unwrap();
+        let mut receiver = SrtpContext::new(&key, &salt).unwrap();
+
+        let packet = [0x81, 206, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2];
+        for _ in 0..2 {
+            let protected = sender.protect_rtcp(&packet).unwrap();
+            assert_eq!(receiver.unprotect_rtcp(&protected).unwrap().as_ref(), packet);
+        }
+
+        assert_eq!(sender.srtcp_index, 2);
+    }
+}
@@ -0,0 +1,433 @@
+//! STUN messages as used by ICE connectivity checks.
+//!
+//! Only the subset of STUN needed by an ICE-lite agent is implemented: binding
+//! requests and responses with short-term credentials.
+
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use aws_lc_rs::{constant_time, hmac};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, Bytes};
+use scuffle_bytes_util::BytesCursorExt;
+
+use crate::error::WhipError;
+
+/// The magic cookie every STUN message carries after the length field.
+pub const MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// The size of the STUN message header.
+pub const HEADER_SIZE: usize = 20;
+
+/// The value the CRC-32 of the message is XORed with in the `FINGERPRINT`
+/// attribute.
+const FINGERPRINT_XOR: u32 = 0x5354_554E;
+
+/// The size of the HMAC-SHA1 in the `MESSAGE-INTEGRITY` attribute.
+const MESSAGE_INTEGRITY_SIZE: usize = 20;
+
+nutype_enum::nutype_enum! {
+    /// The type (class and method) of a STUN message.
+    pub enum MessageType(u16) {
+        /// Binding request.
+        BindingRequest = 0x0001,
+        /// Binding indication.
+        BindingIndication = 0x0011,
+        /// Binding success response.
+        BindingSuccess = 0x0101,
+        /// Binding error response.
+        BindingError = 0x0111,
+    }
+}
+
+nutype_enum::nutype_enum! {
+    /// The type of a STUN attribute.
+    pub enum AttributeType(u16) {
+        /// `MAPPED-ADDRESS`
+        MappedAddress = 0x0001,
+        /// `USERNAME`
+        Username = 0x0006,
+        /// `MESSAGE-INTEGRITY`
+        MessageIntegrity = 0x0008,
+        /// `ERROR-CODE`
+        ErrorCode = 0x0009,
+        /// `XOR-MAPPED-ADDRESS`
+        XorMappedAddress = 0x0020,
+        /// `PRIORITY`
+        Priority = 0x0024,
+        /// `USE-CANDIDATE`
+        UseCandidate = 0x0025,
+        /// `FINGERPRINT`
+        Fingerprint = 0x8028,
+        /// `ICE-CONTROLLED`
+        IceControlled = 0x8029,
+        /// `ICE-CONTROLLING`
+        IceControlling = 0x802A,
+    }
+}
+
+/// A STUN attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attribute {
+    /// The username of the short-term credentials, `<remote ufrag>:<local ufrag>`.
+    Username(String),
+    /// The reflexive transport address of the client.
+    XorMappedAddress(SocketAddr),
+    /// The priority of the peer reflexive candidate.
+    Priority(u32),
+    /// The controlling agent nominates the candidate pair.
+    UseCandidate,
+    /// The sender is the controlled agent, with its tie breaker.
+    IceControlled(u64),
+    /// The sender is the controlling agent, with its tie breaker.
+    IceControlling(u64),
+    /// The error of an error response.
+    ErrorCode {
+        /// The error code, between 300 and 699.
+        code: u16,
+        /// The reason phrase.
+        reason: String,
+    },
+    /// Any other attribute.
+    Unknown {
+        /// The attribute type.
+        attribute_type: AttributeType,
+        /// The attribute value.
+        value: Bytes,
+    },
+}
+
+/// A STUN message.
+///
+/// `MESSAGE-INTEGRITY` and `FINGERPRINT` are not part of the attributes, they
+/// are checked with [`StunMessage::verify_integrity`] and added by
+/// [`StunMessage::write`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StunMessage {
+    /// The type of the message.
+    pub message_type: MessageType,
+    /// The transaction id.
+    pub transaction_id: [u8; 12],
+    /// The attributes of the message.
+    pub attributes: Vec<Attribute>,
+    /// The offset of the `MESSAGE-INTEGRITY` attribute in the parsed message.
+    integrity_offset: Option<usize>,
+}
+
+impl StunMessage {
+    /// Creates a new message without attributes.
+    pub fn new(message_type: MessageType, transaction_id: [u8; 12]) -> Self {
+        Self {
+            message_type,
+            transaction_id,
+            attributes: Vec::new(),
+            integrity_offset: None,
+        }
+    }
+
+    /// Returns true if the datagram looks like a STUN message.
+    pub fn is_stun(data: &[u8]) -> bool {
+        data.len() >= HEADER_SIZE && data[0] < 4 && data[4..8] == MAGIC_COOKIE.to_be_bytes()
+    }
+
+    /// Parses a STUN message.
+    ///
+    /// Attributes following `MESSAGE-INTEGRITY` are ignored, as required by
+    /// RFC 8489, except for the fingerprint which is verified.
+    pub fn parse(data: Bytes) -> Result<Self, WhipError> {
+        if !Self::is_stun(&data) {
+            return Err(WhipError::InvalidStun("not a stun message"));
+        }
+
+        let mut reader = io::Cursor::new(data.clone());
+        let message_type = MessageType::from(reader.read_u16::<BigEndian>()?);
+        let length = reader.read_u16::<BigEndian>()? as usize;
+        reader.read_u32::<BigEndian>()?; // magic cookie
+
+        let mut transaction_id = [0; 12];
+        io::Read::read_exact(&mut reader, &mut transaction_id)?;
+
+        if !length.is_multiple_of(4) || HEADER_SIZE + length != data.len() {
+            return Err(WhipError::InvalidStun("invalid length"));
+        }
+
+        let mut message = Self::new(message_type, transaction_id);
+
+        while reader.has_remaining() {
+            let offset = reader.position() as usize;
+            let attribute_type = AttributeType::from(reader.read_u16::<BigEndian>()?);
+            let length = reader.read_u16::<BigEndian>()? as usize;
+            let value = reader.extract_bytes(length)?;
+            reader.advance((4 - length % 4) % 4);
+
+            match attribute_type {
+                AttributeType::MessageIntegrity => {
+                    if length != MESSAGE_INTEGRITY_SIZE {
+                        return Err(WhipError::InvalidStun("invalid message integrity"));
+                    }
+
+                    message.integrity_offset = Some(offset);
+                }
+                AttributeType::Fingerprint => {
+                    let expected = crc32fast::hash(&data[..offset]) ^ FINGERPRINT_XOR;
+                    if value.as_ref() != expected.to_be_bytes() {
+                        return Err(WhipError::InvalidStun("fingerprint mismatch"));
+                    }
+                }
+                _ if message.integrity_offset.is_some() => {}
+                attribute_type => message
+                    .attributes
+                    .push(Attribute::parse(attribute_type, value, &transaction_id)?),
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// Returns the username of the message.
+    pub fn username(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            Attribute::Username(username) => Some(username.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns true if the message has the given attribute.
+    pub fn has_attribute(&self, attribute_type: AttributeType) -> bool {
+        self.attributes
+            .iter()
+            .any(|attribute| attribute.attribute_type() == attribute_type)
+    }
+
+    /// Verifies the `MESSAGE-INTEGRITY` of the parsed message `data` with the
+    /// short-term credential `key`.
+    ///
+    /// Returns false if the message has no `MESSAGE-INTEGRITY` attribute.
+    pub fn verify_integrity(&self, data: &[u8], key: &[u8]) -> bool {
+        let Some(offset) = self.integrity_offset else {
+            return false;
+        };
+
+        let expected = message_integrity(&data[..offset], key);
+        constant_time::verify_slices_are_equal(&expected, &data[offset + 4..offset + 4 + MESSAGE_INTEGRITY_SIZE]).is_ok()
+    }
+
+    /// Writes the message, adding `MESSAGE-INTEGRITY` if a key is given and
+    /// `FINGERPRINT`.
+    pub fn write(&self, key: Option<&[u8]>) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(128);
+        buf.write_u16::<BigEndian>(self.message_type.0).unwrap();
+        buf.write_u16::<BigEndian>(0).unwrap();
+        buf.write_u32::<BigEndian>(MAGIC_COOKIE).unwrap();
+        buf.write_all(&self.transaction_id).unwrap();
+
+        for attribute in &self.attributes {
+            attribute.write(&self.transaction_id, &mut buf);
+        }
+
+        if let Some(key) = key {
+            let offset = buf.len();
+            set_length(&mut buf, offset + 4 + MESSAGE_INTEGRITY_SIZE);
+            let integrity = message_integrity(&buf, key);
+            write_attribute(&mut buf, AttributeType::MessageIntegrity, &integrity);
+        }
+
+        let offset = buf.len();
+        set_length(&mut buf, offset + 8);
+        let fingerprint = crc32fast::hash(&buf) ^ FINGERPRINT_XOR;
+        write_attribute(&mut buf, AttributeType::Fingerprint, &fingerprint.to_be_bytes());
+
+        buf
+    }
+}
+
+impl Attribute {
+    fn parse(attribute_type: AttributeType, value: Bytes, transaction_id: &[u8; 12]) -> Result<Self, WhipError> {
+        let mut reader = io::Cursor::new(value.clone());
+
+        Ok(match attribute_type {
+            AttributeType::Username => Self::Username(
+                String::from_utf8(value.to_vec()).map_err(|_| WhipError::InvalidStun("username is not utf-8"))?,
+            ),
+            AttributeType::XorMappedAddress => {
+                reader.read_u8()?;
+                let family = reader.read_u8()?;
+                let port = reader.read_u16::<BigEndian>()? ^ (MAGIC_COOKIE >> 16) as u16;
+                let ip = match family {
+                    0x01 => IpAddr::V4(Ipv4Addr::from(reader.read_u32::<BigEndian>()? ^ MAGIC_COOKIE)),
+                    0x02 => IpAddr::V6(Ipv6Addr::from(reader.read_u128::<BigEndian>()? ^ xor_mask_v6(transaction_id))),
+                    _ => return Err(WhipError::InvalidStun("invalid address family")),
+                };
+
+                Self::XorMappedAddress(SocketAddr::new(ip, port))
+            }
+            AttributeType::Priority => Self::Priority(reader.read_u32::<BigEndian>()?),
+            AttributeType::UseCandidate => Self::UseCandidate,
+            AttributeType::IceControlled => Self::IceControlled(reader.read_u64::<BigEndian>()?),
+            AttributeType::IceControlling => Self::IceControlling(reader.read_u64::<BigEndian>()?),
+            AttributeType::ErrorCode => {
+                reader.read_u16::<BigEndian>()?;
+                let class = reader.read_u8()? & 0x07;
+                let number = reader.read_u8()?;
+                let reason = String::from_utf8_lossy(&reader.extract_remaining()).into_owned();
+
+                Self::ErrorCode {
+                    code: class as u16 * 100 + number as u16,
+                    reason,
+                }
+            }
+            attribute_type => Self::Unknown { attribute_type, value },
+        })
+    }
+
+    /// Returns the type of the attribute.
+    pub fn attribute_type(&self) -> AttributeType {
+        match self {
+            Self::Username(_) => AttributeType::Username,
+            Self::XorMappedAddress(_) => AttributeType::XorMappedAddress,
+            Self::Priority(_) => AttributeType::Priority,
+            Self::UseCandidate => AttributeType::UseCandidate,
+            Self::IceControlled(_) => AttributeType::IceControlled,
+            Self::IceControlling(_) => AttributeType::IceControlling,
+            Self::ErrorCode { .. } => AttributeType::ErrorCode,
+            Self::Unknown { attribute_type, .. } => *attribute_type,
+        }
+    }
+
+    fn write(&self, transaction_id: &[u8; 12], buf: &mut Vec<u8>) {
+        let mut value = Vec::new();
+
+        match self {
+            Self::Username(username) => value.extend_from_slice(username.as_bytes()),
+            Self::XorMappedAddress(addr) => {
+                value.push(0);
+                let port = addr.port() ^ (MAGIC_COOKIE >> 16) as u16;
+                match addr.ip() {
+                    IpAddr::V4(ip) => {
+                        value.push(0x01);
+                        value.extend_from_slice(&port.to_be_bytes());
+                        value.extend_from_slice(&(u32::from(ip) ^ MAGIC_COOKIE).to_be_bytes());
+                    }
+                    IpAddr::V6(ip) => {
+                        value.push(0x02);
+                        value.extend_from_slice(&port.to_be_bytes());
+                        value.extend_from_slice(&(u128::from(ip) ^ xor_mask_v6(transaction_id)).to_be_bytes());
+                    }
+                }
+            }
+            Self::Priority(priority) => value.extend_from_slice(&priority.to_be_bytes()),
+            Self::UseCandidate => {}
+            Self::IceControlled(tie_breaker) | Self::IceControlling(tie_breaker) => {
+                value.extend_from_slice(&tie_breaker.to_be_bytes())
+            }
+            Self::ErrorCode { code, reason } => {
+                value.extend_from_slice(&[0, 0, (code / 100) as u8, (code % 100) as u8]);
+                value.extend_from_slice(reason.as_bytes());
+            }
+            Self::Unknown { value: data, .. } => value.extend_from_slice(data),
+        }
+
+        write_attribute(buf, self.attribute_type(), &value);
+    }
+}
+
+/// IPv6 addresses are XORed with the magic cookie and the transaction id.
+fn xor_mask_v6(transaction_id: &[u8; 12]) -> u128 {
+    let mut mask = [0; 16];
+    mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    mask[4..].copy_from_slice(transaction_id);
+    u128::from_be_bytes(mask)
+}
+
+fn write_attribute(buf: &mut Vec<u8>, attribute_type: AttributeType, value: &[u8]) {
+    buf.write_u16::<BigEndian>(attribute_type.0).unwrap();
+    buf.write_u16::<BigEndian>(value.len() as u16).unwrap();
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + (4 - value.len() % 4) % 4, 0);
+
+    let len = buf.len();
+    set_length(buf, len);
+}
+
+/// Sets the length field of the message to cover `len` bytes of message.
+fn set_length(buf: &mut [u8], len: usize) {
+    buf[2..4].copy_from_slice(&((len - HEADER_SIZE) as u16).to_be_bytes());
+}
+
+/// Computes the HMAC-SHA1 over `data` with the length field adjusted to end
+/// after the `MESSAGE-INTEGRITY` attribute.
+fn message_integrity(data: &[u8], key: &[u8]) -> [u8; MESSAGE_INTEGRITY_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header.copy_from_slice(&data[..HEADER_SIZE]);
+    set_length(&mut header, data.len() + 4 + MESSAGE_INTEGRITY_SIZE);
+
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+    let mut context = hmac::Context::with_key(&key);
+    context.update(&header);
+    context.update(&data[HEADER_SIZE..]);
+
+    let mut integrity = [0; MESSAGE_INTEGRITY_SIZE];
+    integrity.copy_from_slice(context.sign().as_ref());
+    integrity
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_round_trip() {
+        let mut request = StunMessage::new(MessageType::BindingRequest, [7; 12]);
+        request.attributes.push(Attribute::Username("local:remote".into()));
+        request.attributes.push(Attribute::Priority(0x6E7F_1EFF));
+        request.attributes.push(Attribute::IceControlling(0x0123_4567_89AB_CDEF));
+        request.attributes.push(Attribute::UseCandidate);
+
+        let data = Bytes::from(request.write(Some(b"password")));
+        assert!(StunMessage::is_stun(&data));
+
+        let parsed = StunMessage::parse(data.clone()).unwrap();
+        assert_eq!(parsed.attributes, request.attributes);
+        assert_eq!(parsed.username(), Some("local:remote"));
+        assert!(parsed.has_attribute(AttributeType::UseCandidate));
+        assert!(parsed.verify_integrity(&data, b"password"));
+        assert!(!parsed.verify_integrity(&data, b"wrong"));
+
+        let mut response = StunMessage::new(MessageType::BindingSuccess, [7; 12]);
+        response
+            .attributes
+            .push(Attribute::XorMappedAddress("192.0.2.1:32853".parse().unwrap()));
+        response
+            .attributes
+            .push(Attribute::XorMappedAddress("[2001:db8::1]:32853".parse().unwrap()));
+        let data = Bytes::from(response.write(None));
+        let parsed = StunMessage::parse(data.clone()).unwrap();
+        assert_eq!(parsed.attributes, response.attributes);
+        assert!(!parsed.verify_integrity(&data, b"password"));
+    }
+
+    #[test]
+    fn corrupted_fingerprint() {
+        let request = StunMessage::new(MessageType::BindingRequest, [1; 12]);
+        let mut data = request.write(Some(b"password"));
+        data[8] ^= 1;
+
+        assert!(matches!(
+            StunMessage::parse(Bytes::from(data)),
+            Err(WhipError::InvalidStun("fingerprint mismatch"))
+        ));
+    }
+
+    #[test]
+    fn error_code() {
+        let mut response = StunMessage::new(MessageType::BindingError, [2; 12]);
+        response.attributes.push(Attribute::ErrorCode {
+            code: 401,
+            reason: "Unauthorized".into(),
+        });
+
+        let parsed = StunMessage::parse(Bytes::from(response.write(None))).unwrap();
+        assert_eq!(parsed.attributes, response.attributes);
+    }
+}